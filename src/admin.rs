@@ -0,0 +1,350 @@
+//! Status of the server for its administrator
+//!
+//! The server keeps an `AdminState` up to date during the game. With the `admin_http` feature, it
+//! can be read as JSON over HTTP, which is handy when the server runs on a remote machine:
+//!
+//! * `GET /health`: whether the server is up, and for how long
+//! * `GET /games`: each game hosted by the server, with its round, turn count, and players
+//! * `GET /players`: the players of all the games
+//! * `GET /games/<id>/snapshot`: the table and the players of a game (see the `snapshot` module),
+//!   with the hands if the request sent the token
+//! * `POST /shutdown`: stop once the current rounds are over, instead of offering to play again
+//! * `POST /reload`: read the settings which can change during a game again (see the `reload`
+//!   module)
+//!
+//! If the `MACHIAVELLI_ADMIN_TOKEN` environment variable is set, requests must send it in an
+//! `Authorization: Bearer <token>` header. Without it, anyone who can reach the endpoint could
+//! stop the server, so it only listens on the loopback interface (for instance `127.0.0.1:8080`).
+
+use std::sync::{ Arc, Mutex };
+use std::time::Instant;
+use crate::snapshot::GameSnapshot;
+
+/// flag giving the address the admin endpoint listens on (for instance `127.0.0.1:8080`)
+pub const ADMIN_FLAG: &str = "--admin";
+
+/// environment variable with the token needed to use the endpoint
+pub const ADMIN_TOKEN_VAR: &str = "MACHIAVELLI_ADMIN_TOKEN";
+
+/// Status of a player
+#[derive(Debug, PartialEq, Clone)]
+pub struct PlayerStatus {
+    pub name: String,
+    /// false if a bot holds the seat
+    pub connected: bool,
+    pub n_cards: usize
+}
+
+/// Status of a game
+#[derive(Debug, PartialEq, Clone)]
+pub struct GameStatus {
+    /// round number, starting from 1
+    pub round: usize,
+    /// number of turns played in the current round
+    pub turn: usize,
+    /// index of the player whose turn it is
+    pub current_player: usize,
+    pub players: Vec<PlayerStatus>,
+    /// state of the game, with all the hands
+    pub snapshot: Option<GameSnapshot>
+}
+
+/// Everything the administrator can see or ask
+#[derive(Debug, Clone)]
+pub struct AdminState {
+    started: Instant,
+    pub games: Vec<GameStatus>,
+    /// the administrator asked to stop once the current rounds are over
+    pub shutdown_requested: bool,
+    /// the administrator asked to read the settings again
+    pub reload_requested: bool
+}
+
+/// State shared between the game and the endpoint
+pub type SharedAdminState = Arc<Mutex<AdminState>>;
+
+impl AdminState {
+
+    /// Create the state of a server without any game yet
+    pub fn new() -> AdminState {
+        AdminState {
+            started: Instant::now(),
+            games: Vec::new(),
+            shutdown_requested: false,
+            reload_requested: false
+        }
+    }
+
+    /// Create a state which can be shared with the endpoint
+    pub fn shared() -> SharedAdminState {
+        Arc::new(Mutex::new(AdminState::new()))
+    }
+
+    /// JSON body of `GET /health`
+    pub fn health_json(&self) -> String {
+        format!("{{\"status\":\"ok\",\"uptime_seconds\":{},\"shutdown_requested\":{}}}",
+                self.started.elapsed().as_secs(), self.shutdown_requested)
+    }
+
+    /// JSON body of `GET /games`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::admin::*;
+    ///
+    /// let mut state = AdminState::new();
+    /// state.games.push(GameStatus {
+    ///     round: 1,
+    ///     turn: 3,
+    ///     current_player: 0,
+    ///     players: vec![PlayerStatus { name: "Alice".to_string(), connected: true, n_cards: 12 }],
+    ///     snapshot: None
+    /// });
+    ///
+    /// assert_eq!("[{\"id\":0,\"round\":1,\"turn\":3,\"current_player\":\"Alice\",\"players\":\
+    ///             [{\"name\":\"Alice\",\"connected\":true,\"n_cards\":12}]}]",
+    ///            state.games_json());
+    /// ```
+    pub fn games_json(&self) -> String {
+        let games: Vec<String> = self.games.iter().enumerate().map(|(id, game)| {
+            let current = game.players.get(game.current_player).map_or("", |p| p.name.as_str());
+            format!("{{\"id\":{},\"round\":{},\"turn\":{},\"current_player\":{},\"players\":{}}}",
+                    id, game.round, game.turn, json_string(current), players_json(&game.players))
+        }).collect();
+        format!("[{}]", games.join(","))
+    }
+
+    /// JSON body of `GET /players`
+    pub fn players_json(&self) -> String {
+        let players: Vec<PlayerStatus> = self.games.iter().flat_map(|g| g.players.clone()).collect();
+        players_json(&players)
+    }
+
+    /// JSON body of `GET /games/<id>/snapshot`, with the hands if `show_hands` is true
+    pub fn snapshot_json(&self, id: usize, show_hands: bool) -> Option<String> {
+        let snapshot = self.games.get(id)?.snapshot.as_ref()?;
+        Some(if show_hands { snapshot.to_json() } else { snapshot.public().to_json() })
+    }
+
+    /// Status code and JSON body of the answer to a request
+    ///
+    /// `token` is the token needed to use the endpoint, if any, and `authorization` the value of
+    /// the `Authorization` header of the request.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::admin::*;
+    ///
+    /// let mut state = AdminState::new();
+    ///
+    /// assert_eq!(401, state.answer("POST", "/shutdown", Some("secret"), None).0);
+    /// assert_eq!(200, state.answer("POST", "/shutdown", Some("secret"), Some("Bearer secret")).0);
+    /// assert!(state.shutdown_requested);
+    /// assert_eq!(404, state.answer("GET", "/lobbies", None, None).0);
+    /// assert_eq!(200, state.answer("POST", "/reload", None, None).0);
+    /// assert!(state.reload_requested);
+    /// ```
+    pub fn answer(&mut self, method: &str, path: &str, token: Option<&str>, authorization: Option<&str>)
+        -> (u16, String)
+    {
+        if let Some(token) = token {
+            if authorization != Some(&format!("Bearer {}", token)) {
+                return (401, "{\"error\":\"unauthorized\"}".to_string());
+            }
+        }
+        match (method, path) {
+            ("GET", "/health") => (200, self.health_json()),
+            ("GET", "/games") => (200, self.games_json()),
+            ("GET", "/players") => (200, self.players_json()),
+            ("GET", path) if snapshot_id(path).is_some() => {
+                match snapshot_id(path).and_then(|id| self.snapshot_json(id, token.is_some())) {
+                    Some(json) => (200, json),
+                    None => (404, "{\"error\":\"not found\"}".to_string())
+                }
+            },
+            ("POST", "/shutdown") => {
+                self.shutdown_requested = true;
+                (200, "{\"shutdown_requested\":true}".to_string())
+            },
+            ("POST", "/reload") => {
+                self.reload_requested = true;
+                (200, "{\"reload_requested\":true}".to_string())
+            },
+            (_, "/health") | (_, "/games") | (_, "/players") | (_, "/shutdown") | (_, "/reload") =>
+                (405, "{\"error\":\"method not allowed\"}".to_string()),
+            _ => (404, "{\"error\":\"not found\"}".to_string())
+        }
+    }
+}
+
+impl Default for AdminState {
+    fn default() -> Self {
+        AdminState::new()
+    }
+}
+
+/// Address given with `ADMIN_FLAG`, and the arguments without the flag
+pub fn admin_address(args: Vec<String>) -> (Option<String>, Vec<String>) {
+    crate::take_flag_value(args, ADMIN_FLAG)
+}
+
+fn players_json(players: &[PlayerStatus]) -> String {
+    let players: Vec<String> = players.iter().map(|p|
+        format!("{{\"name\":{},\"connected\":{},\"n_cards\":{}}}", json_string(&p.name), p.connected, p.n_cards)
+    ).collect();
+    format!("[{}]", players.join(","))
+}
+
+/// id of the game in a path `/games/<id>/snapshot`
+fn snapshot_id(path: &str) -> Option<usize> {
+    path.strip_prefix("/games/")?.strip_suffix("/snapshot")?.parse().ok()
+}
+
+/// a string between double quotes, with the special characters escaped
+pub(crate) fn json_string(s: &str) -> String {
+    let mut res = "\"".to_string();
+    for c in s.chars() {
+        match c {
+            '"' => res += "\\\"",
+            '\\' => res += "\\\\",
+            '\n' => res += "\\n",
+            c if (c as u32) < 0x20 => res += &format!("\\u{:04x}", c as u32),
+            c => res.push(c)
+        }
+    }
+    res + "\""
+}
+
+/// Check if the endpoint can listen on `ip`: any address with a token, only a loopback one without
+///
+/// # Example
+///
+/// ```
+/// use std::net::{ IpAddr, Ipv4Addr };
+/// use machiavelli::admin::may_listen_on;
+///
+/// assert!(may_listen_on(IpAddr::V4(Ipv4Addr::LOCALHOST), None));
+/// assert!(!may_listen_on(IpAddr::V4(Ipv4Addr::UNSPECIFIED), None));
+/// assert!(may_listen_on(IpAddr::V4(Ipv4Addr::UNSPECIFIED), Some("secret")));
+/// ```
+pub fn may_listen_on(ip: std::net::IpAddr, token: Option<&str>) -> bool {
+    token.is_some() || ip.is_loopback()
+}
+
+/// Listen for HTTP requests on an address, in a new thread
+///
+/// Without a token in `ADMIN_TOKEN_VAR`, the address must be a loopback one.
+#[cfg(feature = "admin_http")]
+pub fn serve(address: &str, state: SharedAdminState) -> std::io::Result<()> {
+    let listener = std::net::TcpListener::bind(address)?;
+    let token = std::env::var(ADMIN_TOKEN_VAR).ok().filter(|t| !t.is_empty());
+    if !may_listen_on(listener.local_addr()?.ip(), token.as_deref()) {
+        return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied,
+                   format!("{} is not a loopback address, and {} is not set", address, ADMIN_TOKEN_VAR)));
+    }
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // a failed request should not stop the endpoint
+            handle_request(stream, &state, token.as_deref()).unwrap_or(());
+        }
+    });
+    Ok(())
+}
+
+#[cfg(feature = "admin_http")]
+fn handle_request(stream: std::net::TcpStream, state: &SharedAdminState, token: Option<&str>)
+    -> std::io::Result<()>
+{
+    use std::io::{ BufRead, BufReader, Write };
+
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut words = request_line.split_whitespace();
+    let method = words.next().unwrap_or("").to_string();
+    let path = words.next().unwrap_or("").to_string();
+    let mut authorization = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            }
+        }
+    }
+    let (status, body) = match state.lock() {
+        Ok(mut state) => state.answer(&method, &path, token, authorization.as_deref()),
+        Err(_) => (500, "{\"error\":\"internal error\"}".to_string())
+    };
+    let reason = match status {
+        200 => "OK",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error"
+    };
+    let mut stream = stream;
+    write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+           status, reason, body.len(), body)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn names_are_escaped() {
+        assert_eq!("\"a \\\"b\\\" \\\\ \\u0007\"", json_string("a \"b\" \\ \u{7}"));
+    }
+
+    #[test]
+    fn players_of_all_games() {
+        let mut state = AdminState::new();
+        let player = PlayerStatus { name: "Bob".to_string(), connected: false, n_cards: 0 };
+        for _ in 0..2 {
+            state.games.push(GameStatus { round: 2, turn: 0, current_player: 5, players: vec![player.clone()],
+                                          snapshot: None });
+        }
+        assert_eq!(2, state.players_json().matches("Bob").count());
+        assert!(state.games_json().contains("\"current_player\":\"\""));
+        assert_eq!(405, state.answer("GET", "/shutdown", None, None).0);
+        assert!(!state.shutdown_requested);
+    }
+
+    #[test]
+    fn only_loopback_addresses_without_a_token() {
+        use std::net::{ IpAddr, Ipv4Addr, Ipv6Addr };
+
+        assert!(may_listen_on(IpAddr::V6(Ipv6Addr::LOCALHOST), None));
+        assert!(!may_listen_on(IpAddr::V6(Ipv6Addr::UNSPECIFIED), None));
+        assert!(!may_listen_on(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2)), None));
+        assert!(may_listen_on(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2)), Some("secret")));
+    }
+
+    #[test]
+    fn hands_are_only_shown_with_the_token() {
+        use crate::deck::Deck;
+        use crate::hand::Hand;
+        use crate::sequence_cards::*;
+        use crate::snapshot::{ snapshot, Visibility };
+        use crate::table::Table;
+
+        let hands = vec![Hand::new("Alice", Sequence::from_cards(&[RegularCard(Spade, 13)]))];
+        let mut state = AdminState::new();
+        state.games.push(GameStatus { round: 1, turn: 0, current_player: 0, players: Vec::new(),
+                                      snapshot: Some(snapshot(&Table::new(), &hands, &Deck::new(), 0, Visibility::All)) });
+
+        let (status, body) = state.answer("GET", "/games/0/snapshot", None, None);
+        assert_eq!(200, status);
+        assert!(!body.contains("KS"));
+        assert!(state.answer("GET", "/games/0/snapshot", Some("secret"), Some("Bearer secret")).1.contains("\"hand\":[\"KS\"]"));
+        assert_eq!(404, state.answer("GET", "/games/1/snapshot", None, None).0);
+    }
+}
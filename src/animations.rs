@@ -0,0 +1,107 @@
+//! Lightweight terminal animations
+//!
+//! The animations only render events and hands; they never change the state of the game, so
+//! turning them off (with the `--no-animations` command-line flag) only changes what is printed.
+
+use std::io::{ stdout, Write };
+use std::thread::sleep;
+use std::time::Duration;
+use crate::sequence_cards::Sequence;
+use crate::events::Event;
+use super::reset_style_string;
+
+/// command-line flag disabling the animations
+pub const NO_ANIMATIONS_FLAG: &str = "--no-animations";
+
+/// time between two cards when dealing a hand
+const N_MILLISECONDS_DEAL: u64 = 60;
+
+/// time a flashing line stays on or off
+const N_MILLISECONDS_FLASH: u64 = 150;
+
+/// number of times a line flashes
+const N_FLASHES: usize = 3;
+
+/// time left to read the animated lines before the screen changes
+const N_MILLISECONDS_HOLD: u64 = 1000;
+
+/// Animation settings of a terminal frontend
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Animations {
+    pub enabled: bool
+}
+
+impl Animations {
+
+    /// Create the settings, with the animations enabled or not
+    pub fn new(enabled: bool) -> Animations {
+        Animations { enabled }
+    }
+
+    /// Enable the animations unless the `--no-animations` flag is among the arguments
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::animations::Animations;
+    ///
+    /// let args = vec!["client".to_string(), "--no-animations".to_string()];
+    ///
+    /// assert!(!Animations::from_args(&args).enabled);
+    /// assert!(Animations::from_args(&args[..1]).enabled);
+    /// ```
+    pub fn from_args(args: &[String]) -> Animations {
+        Animations::new(!args.iter().any(|a| a == NO_ANIMATIONS_FLAG))
+    }
+
+    /// Print a hand, one card at a time if the animations are enabled
+    pub fn deal(&self, hand: &Sequence) {
+        if !self.enabled {
+            println!("{}", hand);
+            return;
+        }
+        for card in hand.to_vec() {
+            print!("{} {}", card, reset_style_string());
+            stdout().flush().unwrap_or(());
+            pause(N_MILLISECONDS_DEAL);
+        }
+        println!();
+    }
+
+    /// Print a line, making it flash first if the animations are enabled
+    pub fn flash(&self, text: &str) {
+        if self.enabled {
+            for _ in 0..N_FLASHES {
+                print!("\r{}", text);
+                stdout().flush().unwrap_or(());
+                pause(N_MILLISECONDS_FLASH);
+                print!("\r\x1b[K");
+                stdout().flush().unwrap_or(());
+                pause(N_MILLISECONDS_FLASH);
+            }
+        }
+        println!("\r{}{}", text, reset_style_string());
+    }
+
+    /// Leave some time to read what was just animated
+    pub fn hold(&self) {
+        if self.enabled {
+            pause(N_MILLISECONDS_HOLD);
+        }
+    }
+
+    /// Print a list of events, flashing the ones which put cards on the table
+    pub fn replay(&self, events: &[&Event]) {
+        for event in events {
+            match event {
+                Event::PlayedSequence(..) | Event::AddedToSequence(..) =>
+                    self.flash(&format!("  {}", event)),
+                _ => println!("  {}", event)
+            }
+        }
+    }
+}
+
+fn pause(n_milliseconds: u64) {
+    sleep(Duration::from_millis(n_milliseconds));
+}
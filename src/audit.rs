@@ -0,0 +1,390 @@
+//! Tamper-evident record of the shuffles and draws of a game
+//!
+//! Each deck is shuffled from a seed, and every card drawn from it is recorded. Each line of the
+//! log ends with the SHA-256 hash of the previous hash and the entry, so that changing, removing,
+//! or inserting a line breaks the chain. Replaying the log against the seeds shows that the cards
+//! were drawn in the order given by the shuffle. When the cards set aside are shuffled back into
+//! an empty deck, the log records them with the seed of their shuffle, so that the draws which
+//! follow can be checked too.
+
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use crate::sequence_cards::*;
+use crate::deck::Deck;
+use crate::sha256::{ sha256, to_hex };
+
+/// extension of the audit files
+pub const AUDIT_EXTENSION: &str = ".audit";
+
+/// hash preceding the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// An entry of the audit log
+#[derive(Debug, PartialEq, Clone)]
+pub enum AuditEntry {
+    /// a new deck is shuffled from a seed
+    Shuffle { n_decks: u8, n_jokers: u8, seed: u64 },
+    /// a player draws a card from the deck
+    Draw { player: usize, card: Card },
+    /// the cards set aside are shuffled from a seed into the empty deck
+    Refill { seed: u64, cards: Vec<Card> }
+}
+
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditEntry::Shuffle { n_decks, n_jokers, seed } =>
+                write!(f, "shuffle {} {} {}", n_decks, n_jokers, seed),
+            AuditEntry::Draw { player, card } => write!(f, "draw {} {}", player, card.to_byte()),
+            AuditEntry::Refill { seed, cards } => {
+                write!(f, "refill {}", seed)?;
+                cards.iter().try_for_each(|card| write!(f, " {}", card.to_byte()))
+            }
+        }
+    }
+}
+
+impl AuditEntry {
+
+    /// Parse an entry written with `to_string`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::audit::AuditEntry;
+    ///
+    /// let entry = AuditEntry::Shuffle { n_decks: 2, n_jokers: 4, seed: 1234 };
+    ///
+    /// assert_eq!(Some(entry.clone()), AuditEntry::parse(&entry.to_string()));
+    /// assert_eq!(None, AuditEntry::parse("draw 1"));
+    /// ```
+    pub fn parse(s: &str) -> Option<AuditEntry> {
+        let words: Vec<&str> = s.split(' ').collect();
+        match words.as_slice() {
+            ["shuffle", n_decks, n_jokers, seed] => Some(AuditEntry::Shuffle {
+                n_decks: n_decks.parse().ok()?,
+                n_jokers: n_jokers.parse().ok()?,
+                seed: seed.parse().ok()?
+            }),
+            ["draw", player, card] => Some(AuditEntry::Draw {
+                player: player.parse().ok()?,
+                card: Card::from_byte(card.parse().ok()?)?
+            }),
+            ["refill", seed, cards @ ..] => Some(AuditEntry::Refill {
+                seed: seed.parse().ok()?,
+                cards: cards.iter().map(|card| Card::from_byte(card.parse().ok()?)).collect::<Option<_>>()?
+            }),
+            _ => None
+        }
+    }
+}
+
+/// Build the deck corresponding to a seed
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::audit::shuffled_deck;
+///
+/// assert_eq!(shuffled_deck(2, 4, 42), shuffled_deck(2, 4, 42));
+/// assert_eq!(108, shuffled_deck(2, 4, 42).remaining());
+/// ```
+pub fn shuffled_deck(n_decks: u8, n_jokers: u8, seed: u64) -> Deck {
+    Deck::shuffled(n_decks, n_jokers, &mut StdRng::seed_from_u64(seed))
+}
+
+/// Shuffle cards set aside into an empty deck from a seed, as `AuditLog::refill` does
+fn refilled_deck(cards: &[Card], seed: u64) -> Deck {
+    let mut deck = Deck::new();
+    deck.discard(cards);
+    deck.refill(&mut StdRng::seed_from_u64(seed));
+    deck
+}
+
+/// Commitment to a seed, which can be shown to the players before the seed is revealed
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::audit::commitment;
+///
+/// assert_eq!(64, commitment(42).len());
+/// assert_ne!(commitment(42), commitment(43));
+/// ```
+pub fn commitment(seed: u64) -> String {
+    to_hex(&sha256(&seed.to_le_bytes()))
+}
+
+/// hash of an entry, chained to the previous one
+fn chain_hash(previous_hash: &str, entry: &str) -> String {
+    to_hex(&sha256(format!("{} {}", previous_hash, entry).as_bytes()))
+}
+
+/// Hash-chained log of the shuffles and draws, optionally written to a file as it grows
+#[derive(Debug, PartialEq, Clone)]
+pub struct AuditLog {
+    last_hash: String,
+    seed: Option<u64>,
+    fname: Option<String>
+}
+
+impl AuditLog {
+
+    /// Create an empty log which is only kept in memory
+    pub fn new() -> AuditLog {
+        AuditLog {
+            last_hash: GENESIS_HASH.to_string(),
+            seed: None,
+            fname: None
+        }
+    }
+
+    /// Create an empty log written to a file, replacing the previous content of the file
+    pub fn create(fname: &str) -> Result<AuditLog, AuditError> {
+        std::fs::File::create(fname)?;
+        Ok(AuditLog {
+            fname: Some(fname.to_string()),
+            ..AuditLog::new()
+        })
+    }
+
+    /// Continue the log of a file after checking it
+    pub fn resume(fname: &str) -> Result<AuditLog, AuditError> {
+        let content = std::fs::read_to_string(fname)?;
+        let report = verify(&content)?;
+        Ok(AuditLog {
+            last_hash: report.last_hash,
+            seed: report.seeds.last().cloned(),
+            fname: Some(fname.to_string())
+        })
+    }
+
+    /// Seed of the current deck, if any
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Add an entry, and return the line recording it
+    pub fn push(&mut self, entry: AuditEntry) -> Result<String, AuditError> {
+        if let AuditEntry::Shuffle { seed, .. } | AuditEntry::Refill { seed, .. } = entry {
+            self.seed = Some(seed);
+        }
+        let entry = entry.to_string();
+        self.last_hash = chain_hash(&self.last_hash, &entry);
+        let line = format!("{} {}\n", entry, self.last_hash);
+        if let Some(fname) = &self.fname {
+            let mut file = OpenOptions::new().append(true).create(true).open(fname)?;
+            file.write_all(line.as_bytes())?;
+        }
+        Ok(line)
+    }
+
+    /// Shuffle a new deck from a seed and record it
+    pub fn shuffle(&mut self, n_decks: u8, n_jokers: u8, seed: u64) -> Result<Deck, AuditError> {
+        self.push(AuditEntry::Shuffle { n_decks, n_jokers, seed })?;
+        Ok(shuffled_deck(n_decks, n_jokers, seed))
+    }
+
+    /// Shuffle the cards set aside into an empty deck from a seed, record it, and return whether
+    /// there were any
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::audit::{ verify, AuditLog };
+    /// use machiavelli::deck::Deck;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let mut log = AuditLog::new();
+    /// let mut content = String::new();
+    /// let mut deck = Deck::new();
+    /// deck.discard(&[Joker, RegularCard(Spade, 9)]);
+    ///
+    /// content += &log.refill(&mut deck, 42).unwrap();
+    /// content += &log.record_draws(0, &deck.clone(), &Deck::new()).unwrap();
+    /// assert_eq!(2, verify(&content).unwrap().n_draws);
+    /// assert_eq!("", log.refill(&mut deck, 43).unwrap());
+    /// ```
+    pub fn refill(&mut self, deck: &mut Deck, seed: u64) -> Result<String, AuditError> {
+        if deck.discarded().is_empty() {
+            return Ok(String::new());
+        }
+        let line = self.push(AuditEntry::Refill { seed, cards: deck.discarded().to_vec() });
+        deck.refill(&mut StdRng::seed_from_u64(seed));
+        line
+    }
+
+    /// Record the cards drawn by a player, given the deck before and after
+    ///
+    /// Nothing is recorded until a deck has been shuffled, as the draws could not be checked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::audit::AuditLog;
+    ///
+    /// let mut log = AuditLog::new();
+    /// let mut deck = log.shuffle(1, 0, 42).unwrap();
+    /// let deck_before = deck.clone();
+    /// deck.draw_n(2);
+    ///
+    /// assert_eq!(2, log.record_draws(0, &deck_before, &deck).unwrap().lines().count());
+    /// ```
+    pub fn record_draws(&mut self, player: usize, deck_before: &Deck, deck_after: &Deck)
+        -> Result<String, AuditError>
+    {
+        let mut res = String::new();
+        if self.seed.is_none() {
+            return Ok(res);
+        }
+        let cards = deck_before.cards();
+        for card in cards[deck_after.remaining().min(cards.len())..].iter().rev() {
+            res += &self.push(AuditEntry::Draw { player, card: card.clone() })?;
+        }
+        Ok(res)
+    }
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        AuditLog::new()
+    }
+}
+
+/// Summary of a verified log
+#[derive(Debug, PartialEq, Clone)]
+pub struct AuditReport {
+    /// seeds of the successive decks
+    pub seeds: Vec<u64>,
+    pub n_draws: usize,
+    last_hash: String
+}
+
+/// Check the hash chain of a log and replay the draws against the seeds
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::audit::*;
+///
+/// let mut log = AuditLog::new();
+/// let mut content = String::new();
+/// let mut deck = log.shuffle(1, 2, 42).unwrap();
+/// content += &log.push(AuditEntry::Draw { player: 1, card: deck.draw().unwrap() }).unwrap();
+///
+/// // the shuffle line is missing
+/// assert!(verify(&content).is_err());
+/// ```
+pub fn verify(content: &str) -> Result<AuditReport, AuditError> {
+    let mut report = AuditReport {
+        seeds: Vec::<u64>::new(),
+        n_draws: 0,
+        last_hash: GENESIS_HASH.to_string()
+    };
+    let mut deck = Deck::new();
+    for (i, line) in content.lines().enumerate() {
+        let line_error = |message: &str| AuditError { message: format!("line {}: {}", i + 1, message) };
+        let (entry, hash) = match line.rfind(' ') {
+            Some(j) => (&line[..j], &line[j+1..]),
+            None => return Err(line_error("invalid line"))
+        };
+        report.last_hash = chain_hash(&report.last_hash, entry);
+        if hash != report.last_hash {
+            return Err(line_error("the hash does not match; the log has been tampered with"));
+        }
+        match AuditEntry::parse(entry) {
+            Some(AuditEntry::Shuffle { n_decks, n_jokers, seed }) => {
+                deck = shuffled_deck(n_decks, n_jokers, seed);
+                report.seeds.push(seed);
+            },
+            Some(AuditEntry::Refill { seed, cards }) => {
+                if deck.remaining() > 0 {
+                    return Err(line_error("cards set aside were shuffled into a deck which was not empty"));
+                }
+                deck = refilled_deck(&cards, seed);
+                report.seeds.push(seed);
+            },
+            Some(AuditEntry::Draw { card, .. }) => {
+                if report.seeds.is_empty() {
+                    return Err(line_error("card drawn before the deck was shuffled"));
+                }
+                if deck.draw() != Some(card) {
+                    return Err(line_error("the card drawn is not the next card of the deck"));
+                }
+                report.n_draws += 1;
+            },
+            None => return Err(line_error("invalid entry"))
+        }
+    }
+    Ok(report)
+}
+
+/// Error reading or verifying an audit log
+#[derive(Debug)]
+pub struct AuditError {
+    pub message: String
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AuditError: {}", self.message)
+    }
+}
+
+impl std::convert::From<std::io::Error> for AuditError {
+    fn from(error: std::io::Error) -> Self {
+        AuditError { message: format!("IO Error: {}", error) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn sample_log() -> String {
+        let mut log = AuditLog::new();
+        let mut content = log.push(AuditEntry::Shuffle { n_decks: 1, n_jokers: 2, seed: 7 }).unwrap();
+        let deck = shuffled_deck(1, 2, 7);
+        let mut deck_after = deck.clone();
+        for _ in 0..3 {
+            deck_after.draw();
+        }
+        content += &log.record_draws(2, &deck, &deck_after).unwrap();
+        content
+    }
+
+    #[test]
+    fn verify_valid_log() {
+        let report = verify(&sample_log()).unwrap();
+        assert_eq!(vec![7], report.seeds);
+        assert_eq!(3, report.n_draws);
+    }
+
+    #[test]
+    fn verify_detects_changed_entry() {
+        let content = sample_log().replacen("draw 2", "draw 1", 1);
+        assert!(verify(&content).is_err());
+    }
+
+    #[test]
+    fn verify_detects_stacked_deck() {
+        // a consistent chain whose draws do not follow the shuffle
+        let mut log = AuditLog::new();
+        let mut content = log.push(AuditEntry::Shuffle { n_decks: 1, n_jokers: 0, seed: 7 }).unwrap();
+        let next_card = shuffled_deck(1, 0, 7).draw().unwrap();
+        let other_card = if next_card == Joker { RegularCard(Heart, 1) } else { Joker };
+        content += &log.push(AuditEntry::Draw { player: 0, card: other_card }).unwrap();
+        assert!(verify(&content).is_err());
+    }
+
+    #[test]
+    fn no_draws_before_shuffle() {
+        let mut log = AuditLog::new();
+        let deck = shuffled_deck(1, 0, 7);
+        assert_eq!("", log.record_draws(0, &deck, &Deck::new()).unwrap());
+    }
+}
@@ -3,54 +3,83 @@
 use std::env;
 use std::process::exit;
 use machiavelli::lib_client::*;
+use machiavelli::terminal;
 
 fn main() {
 
-    ctrlc::set_handler(|| {
-        print!("\x1b[0m\x1b[?25h"); // reset the style and show the cursor
-        print!("\x1b[2J\x1b[1;1H"); // clear the screen
-        print!("\x1b[K"); // redraw the screen
-        exit(0);
-    }).expect("Could not set the Ctrl-C signal handler!");
+    // restore the terminal on Ctrl-C, and if the client panics
+    terminal::set_handler(true).expect("Could not set the Ctrl-C signal handler!");
+    terminal::install_panic_hook();
+    if !terminal::enable_virtual_terminal() {
+        println!("{}", terminal::NO_VIRTUAL_TERMINAL);
+    }
 
     // parse the command-line arguments
     let args: Vec<String> = env::args().collect();
+    let animations = Animations::from_args(&args);
+    let args: Vec<String> = args.into_iter().filter(|a| a != NO_ANIMATIONS_FLAG).collect();
+
+    let single_byte_buffer: &mut [u8; 1] = &mut [0];
 
-    let mut single_byte_buffer: &mut [u8; 1] = &mut [0];
+    // load the player preferences
+    let settings = Settings::load().map(|mut settings| {
+        // the notification settings of earlier versions are moved to the settings file
+        migrate_notifications(&mut settings);
+        settings
+    }).unwrap_or_else(|e| {
+        println!("Could not load the settings: {}", e);
+        Settings::new()
+    });
+    settings.apply();
 
-    // set-up the TCP stream to communicate with the server
-    let mut stream = if args.len() > 1 {
-        
-        // if one command-line argument is given, use it as player name
-        connect(&args[1])
+    // how the hand is shown
+    let mut layout = settings.layout;
 
+    // name of the player: the command-line argument if one is given, otherwise the name from
+    // the settings (it is asked if empty)
+    let name = if args.len() > 1 {
+        args[1].clone()
     } else {
-        
-        //otherwise, the name will be asked
-        connect("")
+        settings.player_name.clone().unwrap_or_default()
     };
 
+    // set-up the TCP stream to communicate with the server, encrypted if there is a passphrase
+    let stream = connect_to_server(settings.server.clone()).unwrap_or_else(|e| {
+        println!("Failed to connect: {}", e);
+        exit(1);
+    });
+    match settings.passphrase.as_deref() {
+        Some(passphrase) => {
+            let stream = EncryptedConnection::handshake(stream, passphrase, Side::Client).unwrap_or_else(|e| {
+                println!("Failed to set up the encryption: {}", e);
+                exit(1);
+            });
+            play(stream, name, single_byte_buffer, &animations, &mut layout, &settings)
+        },
+        None => play(stream, name, single_byte_buffer, &animations, &mut layout, &settings)
+    }
+}
+
+
+// function to send the name to the server, then handle its requests until the game ends
+fn play<C: Connection>(stream: C, name: String, single_byte_buffer: &mut [u8; 1],
+                       animations: &Animations, layout: &mut HandLayout, settings: &Settings) -> ! {
+
+    // what the client knows of the game, checked against the server, and whose turn it waits for
+    let mut client = ClientState::default();
+
+    let mut stream = say_hello(stream, name).unwrap_or_else(|e| {
+        println!("Failed to connect: {}", e);
+        exit(1);
+    });
+
     loop {
 
         // handle the server request and quit if the server can not be reached
-        handle_server_request(&mut single_byte_buffer, &mut stream).unwrap_or_else(|_| {
+        handle_server_request(single_byte_buffer, &mut stream, &settings.notifications, animations, layout, settings, &mut client).unwrap_or_else(|_| {
             println!("lost connection to the server");
-            print!("\x1b[0m\x1b[?25h"); // reset the style and show the cursor
-            print!("\x1b[K"); // redraw the screen
-            exit(1);
+            terminal::exit(1);
         });
 
     }
 }
-
-
-// function to try to connect to the server and exit if unsuccessful
-fn connect(name: &str) -> TcpStream {
-    match say_hello(name.to_string()) {
-        Ok(s) => s,
-        Err(e) => {
-            println!("Failed to connect: {}", e);
-            exit(1);
-        }
-    }
-}
@@ -1,14 +1,80 @@
 //! Server for the Machiavelli game
 
 use std::process;
-use std::fs::File;
 use std::thread;
 use std::env;
 use rand::{ thread_rng, Rng };
 use machiavelli::lib_server::*;
+use machiavelli::audit::*;
+use machiavelli::invariants::CardLedger;
 
 const SAVE_EXTENSION: &str = ".sav";
 
+/// flag giving the directory where games are saved and resumed from
+const RESUME_DIR_FLAG: &str = "--resume-dir";
+
+/// extension added to the save files of finished games in the resume directory
+const FINISHED_EXTENSION: &str = ".done";
+
+// copy the status of the game to the admin state
+fn update_admin_state(admin_state: &admin::SharedAdminState, round: usize, turn: usize, player: usize,
+                      table: &Table, players: &[Player<WorkerConnection>], deck: &Deck) {
+    let snapshot = snapshot::snapshot(table, players, deck, player, snapshot::Visibility::All);
+    let players = players.iter().map(|p| admin::PlayerStatus {
+        name: p.name().to_string(),
+        connected: p.connected,
+        n_cards: p.hand.number_cards()
+    }).collect();
+    if let Ok(mut state) = admin_state.lock() {
+        state.games = vec![admin::GameStatus { round, turn, current_player: player, players, snapshot: Some(snapshot) }];
+    }
+}
+
+// read the settings which can change during a game again, after SIGHUP or a request of the
+// administrator
+fn reload_server_config(server_config: &mut ServerConfig) {
+    match ServerConfig::from_file(SERVER_CONFIG_FILE) {
+        Ok(new) => {
+            let changes = server_config.reload(&new);
+            logging::set_level(server_config.log_level);
+            if changes.is_empty() {
+                logging::info("Settings reloaded; nothing changed");
+            } else {
+                logging::info(&format!("Settings reloaded: {}", changes.join(", ")));
+            }
+        },
+        Err(_) => logging::error("Could not read the server settings again; keeping the current ones")
+    }
+}
+
+// write the state of the game, the last messages, and the transcript to a debug bundle, so that
+// the error which stopped the game can be reported, then quit
+fn stop_on_protocol_error(error: &StreamError, table: &Table, players: &[Player<WorkerConnection>], deck: &Deck,
+                          player: usize, config: &Config, record: &replay::GameRecord, log: &EventLog,
+                          dir: Option<&std::path::Path>) -> ! {
+    println!("{}", error);
+    let mut record = record.clone();
+    record.sync(log);
+    let transcript = replay::to_transcript(&record, &config.point_rules(), i18n::DEFAULT_LOCALE);
+    let snapshot = snapshot::snapshot(table, players, deck, player, snapshot::Visibility::All);
+    match debug_bundle::write(dir, &error.to_string(), &format!("{:?}", config), &snapshot.to_json(), &transcript) {
+        Ok(path) => println!("The state of the game was written to {}; please attach it to your bug report", path),
+        Err(e) => println!("Could not write the debug bundle: {}", e)
+    };
+    process::exit(1);
+}
+
+// load a checkpoint of the game, encrypted with the name of the save file, with its metadata
+fn load_checkpoint(path: &str, save_name: &str)
+    -> Result<(SavedGame, Option<SavedTurn>, Option<saves::SaveMetadata>), LoadingError>
+{
+    let data = std::fs::read(path)?;
+    let bytes = encode::decrypt_save(&data, save_name).map_err(|_| LoadingError::Corrupted)?;
+    let (game, turn, _) = save_format::load(&bytes, |_| false)?;
+    let metadata = encode::save_metadata(&data).and_then(saves::SaveMetadata::from_bytes);
+    Ok((game, turn, metadata))
+}
+
 // ask the user for the port to use
 fn get_port() -> usize {
     println!("Which port should I use?");
@@ -23,16 +89,205 @@ fn get_port() -> usize {
     }
 }
 
+// shuffle a new deck from a random seed, recording it in the audit log
+fn new_deck<R: Rng>(config: &Config, audit: &mut AuditLog, rng: &mut R) -> Deck {
+    let seed: u64 = rng.gen();
+    match audit.shuffle(config.n_decks, config.n_jokers, seed) {
+        Ok(d) => d,
+        Err(_) => {
+            println!("Could not write to the audit log!");
+            shuffled_deck(config.n_decks, config.n_jokers, seed)
+        }
+    }
+}
+
+// shuffle the cards set aside into the empty deck from a random seed, recording it in the audit
+// log, and return whether there were any
+fn refill_deck<R: Rng>(deck: &mut Deck, audit: &mut AuditLog, rng: &mut R) -> bool {
+    let refilled = !deck.discarded().is_empty();
+    if audit.refill(deck, rng.gen()).is_err() {
+        logging::error("Could not write to the audit log!");
+    }
+    refilled
+}
+
+// shuffle a new deck and deal the hands, recording both in the audit log
+fn new_deal<R: Rng>(config: &Config, audit: &mut AuditLog, rng: &mut R) -> (Deck, Vec<Sequence>) {
+    let mut deck = new_deck(config, audit, rng);
+    let mut hands = vec![Sequence::new(); config.n_players as usize];
+    for i in 0..config.n_players {
+        let deck_before = deck.clone();
+        for card in deck.draw_n(config.n_cards_to_start as usize) {
+            hands[i as usize].add_card(card);
+        }
+        record_draws(audit, i as usize, &deck_before, &deck);
+    }
+    (deck, hands)
+}
+
+// let each connected player reject their hand once, recording the new hands in the audit log and
+// the mulligans in the event log
+fn offer_mulligans<C: Connection>(players: &mut [Player<C>], deck: &mut Deck, config: &Config,
+                                  audit: &mut AuditLog, log: &mut EventLog) {
+    if config.mulligan == MulliganRule::Off {
+        return;
+    }
+    for (i, player) in players.iter_mut().enumerate() {
+        if !player.connected {
+            continue;
+        }
+        let question = format!("Your hand:\n{}{}\n{} (‘y’ for yes)\n", player.hand.cards(), reset_style_string(),
+                               config.mulligan.question());
+        send_message_to_client(player, &question).unwrap_or(());
+        if !get_string_from_client(player).is_ok_and(|reply| is_yes(reply.trim())) {
+            continue;
+        }
+        let deck_before = deck.clone();
+        let message = match config.mulligan.redeal(&mut player.hand, deck, config.n_cards_to_start) {
+            Ok(_) => {
+                record_draws(audit, i, &deck_before, deck);
+                log.push(Event::Mulligan(player.name().to_string()));
+                format!("Your new hand:\n{}{}\n", player.hand.cards(), reset_style_string())
+            },
+            Err(m) => format!("{}\n", m)
+        };
+        send_message_to_client(player, &message).unwrap_or(());
+    }
+}
+
+// let the players draw a card each, show everyone the draw, and let the one with the highest card
+// play first
+fn draw_first_player<C: Connection, R: Rng>(players: &mut [Player<C>], order: &mut TurnOrder, rng: &mut R,
+                                            log: &mut EventLog) {
+    let draw = turn_order::draw_for_first(players.len(), rng);
+    let names = hand::owners(players);
+    send_message_all_players(players, &format!("{}\n", draw.render(&names)));
+    *order = TurnOrder::new(players.len(), draw.winner);
+    log.push(Event::PlaysFirst(names[draw.winner].clone()));
+}
+
+// record the cards drawn by a player in the audit log
+fn record_draws(audit: &mut AuditLog, player: usize, deck_before: &Deck, deck: &Deck) {
+    if audit.record_draws(player, deck_before, deck).is_err() {
+        println!("Could not write to the audit log!");
+    }
+}
+
+// check an audit log and print the seeds, so that players can compare them with the commitments
+fn verify_audit_file(fname: &str) {
+    let content = match std::fs::read_to_string(fname) {
+        Ok(c) => c,
+        Err(_) => {
+            println!("Could not read the audit log {}", fname);
+            process::exit(1);
+        }
+    };
+    match verify(&content) {
+        Ok(report) => {
+            for (i, seed) in report.seeds.iter().enumerate() {
+                println!("Deck {}: seed {} (commitment {})", i + 1, seed, &commitment(*seed)[..16]);
+            }
+            println!("{} draws checked: the log is consistent with the seeds", report.n_draws);
+        },
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    }
+}
+
 fn main() {
     
     // get the command-line arguments
-    let mut args = env::args();
+    let (transcript, args) = replay::transcript_file(env::args().collect());
+    let (admin_address, args) = admin::admin_address(args);
+    let (rpc_address, args) = rpc::rpc_address(args);
+    let (resume_dir, args) = take_flag_value(args, RESUME_DIR_FLAG);
+    let (data_dir, args) = take_flag_value(args, paths::DATA_DIR_FLAG);
+    let (rollback, args) = take_flag_value(args, checkpoints::ROLLBACK_FLAG);
+    let rollback = match rollback.as_deref().map(checkpoints::RollbackTarget::parse) {
+        Some(None) => {
+            println!("Usage: server {} <turn> or {} <round>:<turn>", checkpoints::ROLLBACK_FLAG,
+                     checkpoints::ROLLBACK_FLAG);
+            return;
+        },
+        Some(target) => target,
+        None => None
+    };
+    let mut args = args.into_iter().peekable();
     args.next(); // skip the first one (name of the executable)
+
+    // check an audit log instead of running a game
+    if args.peek().map(|s| s.as_str()) == Some("verify") {
+        args.next();
+        match args.next() {
+            Some(fname) => verify_audit_file(&fname),
+            None => println!("Usage: server verify <audit file>")
+        };
+        return;
+    }
     
+    // host games for JSON-RPC clients instead of running a game with the terminal clients
+    if let Some(address) = rpc_address {
+        #[cfg(feature = "json_rpc")]
+        {
+            let mut server_config = ServerConfig::from_file(SERVER_CONFIG_FILE).unwrap_or_default();
+            logging::set_level(server_config.log_level);
+            let lobby = rpc::Lobby::shared();
+            if let Ok(mut lobby) = lobby.lock() {
+                lobby.max_players = server_config.max_players;
+            }
+
+            // read the settings again on SIGHUP, for the games created from then on
+            if reload::set_handler() {
+                let lobby = lobby.clone();
+                thread::spawn(move || loop {
+                    thread::sleep(std::time::Duration::from_secs(1));
+                    if reload::take_request() {
+                        reload_server_config(&mut server_config);
+                        if let Ok(mut lobby) = lobby.lock() {
+                            lobby.max_players = server_config.max_players;
+                        }
+                    }
+                });
+            }
+            println!("JSON-RPC interface listening on {}", address);
+            if let Err(e) = rpc::serve(&address, lobby) {
+                println!("Could not start the JSON-RPC interface: {}", e);
+            }
+        }
+        #[cfg(not(feature = "json_rpc"))]
+        println!("The server was built without the json_rpc feature; {} {} can't be used", rpc::RPC_FLAG, address);
+        return;
+    }
+
     // clear the terminal
     print!("\x1b[2J\x1b[1;1H");
     println!("Machiavelli server\n");
 
+    // save the game and tell the players before quitting on SIGINT or SIGTERM
+    if shutdown::set_handler().is_err() {
+        println!("Could not set the signal handler; stopping the server will not save the game");
+    }
+
+    // read the settings again on SIGHUP, instead of stopping
+    if !reload::set_handler() && cfg!(unix) {
+        println!("Could not set the SIGHUP handler; the settings can only be reloaded from the admin endpoint");
+    }
+
+    // status of the server, which can be read over HTTP with the `admin_http` feature
+    let admin_state = admin::AdminState::shared();
+    if let Some(address) = &admin_address {
+        #[cfg(feature = "admin_http")]
+        match admin::serve(address, admin_state.clone()) {
+            Ok(()) => println!("Admin endpoint listening on {}", address),
+            Err(e) => println!("Could not start the admin endpoint: {}", e)
+        };
+        #[cfg(not(feature = "admin_http"))]
+        println!("The server was built without the admin_http feature; ignoring {} {}", 
+                 admin::ADMIN_FLAG, address);
+    }
+
     // port on which to listen
     let name_file_port_server = "Config/port_server.dat";
     let port = match std::fs::read_to_string(name_file_port_server) {
@@ -43,56 +298,102 @@ fn main() {
         Err(_) => get_port()
     };
 
+    // settings for idle or disconnected players
+    let mut server_config = match ServerConfig::from_file(SERVER_CONFIG_FILE) {
+        Ok(c) => c,
+        Err(_) => {
+            println!("Could not read the server settings from the file; using the default ones");
+            ServerConfig::new()
+        }
+    };
+    logging::set_level(server_config.log_level);
+
+    // with a resume directory, the most recent unfinished game saved there is resumed
+    let resume_save = resume_dir.as_ref().and_then(|dir| {
+        std::fs::create_dir_all(dir).unwrap_or(());
+        let saves = unfinished_saves(dir, SAVE_EXTENSION);
+        if saves.len() > 1 {
+            println!("{} unfinished games in {}; the others will be resumed when the server restarts",
+                     saves.len(), dir);
+        }
+        saves.into_iter().next()
+    });
+
     // ask if a previous game should be loaded if not provided as an argument
     let load: bool;
     let load_from_command_line: bool;
-    match args.next() {
-        // "1" or "y" for yes, anything else for no
-        Some(s) => {
-            load_from_command_line = true;
-            match s.trim().parse::<u8>() {
-                Ok(1) => {
-                    println!("Loading a previous game");
-                    load = true;
-                },
-                Ok(121) => {
-                    println!("Loading a previous game");
-                    load = true;
-                },
-                _ => load = false
-            };
-        }
-        None => {
-            load_from_command_line = false;
-            println!("Load a previous game? (y/n)");
-            load = match get_input().unwrap().trim() {
-                "y" => true,
-                _ => false
-            };
-        }
-    };
+    if let Some(path) = &resume_save {
+        println!("Resuming the game saved in {}", path);
+        load = true;
+        load_from_command_line = false;
+    } else if resume_dir.is_some() {
+        println!("No unfinished game to resume; starting a new one");
+        load = false;
+        load_from_command_line = false;
+    } else {
+        match args.next() {
+            // "1" or "y" for yes, anything else for no
+            Some(s) => {
+                load_from_command_line = true;
+                match s.trim().parse::<u8>() {
+                    Ok(1) => {
+                        println!("Loading a previous game");
+                        load = true;
+                    },
+                    Ok(121) => {
+                        println!("Loading a previous game");
+                        load = true;
+                    },
+                    _ => load = false
+                };
+            }
+            None => {
+                load_from_command_line = false;
+                println!("Load a previous game? (y/n)");
+                load = match get_input().unwrap().trim() {
+                    "y" => true,
+                    _ => false
+                };
+            }
+        };
+    }
         
     let mut config = Config {
             n_decks: 0,
             n_jokers: 0,
             n_cards_to_start: 0,
             custom_rule_jokers: false,
-            n_players: 0
+            n_players: 0,
+            empty_deck: EmptyDeckRule::Draw,
+            joker_penalty: DEFAULT_JOKER_PENALTY,
+            mulligan: MulliganRule::Off,
+            draw_for_first: false
     };
 
     // default save file without the sav extension
-    let mut savefile = "machiavelli_save".to_string();
+    let mut savefile = DEFAULT_SAVEFILE.to_string();
+
+    // directory where bare save names go, if the data directory can be used
+    let save_dir = paths::save_dir(data_dir.as_deref().map(std::path::Path::new));
+    if save_dir.is_none() {
+        println!("Could not use the data directory; the game is saved in the current directory");
+    }
 
     if !load {
 
-        // get the config
-        match get_config_from_file(&"Config/config.dat") {
+        // get the config, from the legacy file if there is no other
+        let config_file = if std::path::Path::new(CONFIG_FILE).exists() {
+            CONFIG_FILE
+        } else {
+            LEGACY_CONFIG_FILE
+        };
+        match get_config_from_file(config_file) {
             Ok(conf) => {
                 config = conf.0;
                 savefile = conf.1;
             },
-            Err(_) => {
-                println!("Could not read the config from the file!");
+            Err(e) => {
+                println!("Could not read the config from {}: {}", config_file, e);
                 match get_config_and_savefile() {
                     Ok(conf) => {
                         config = conf.0;
@@ -107,18 +408,21 @@ fn main() {
         };
     }
     
-    let mut starting_player: u8;
+    let mut order: TurnOrder;
     let mut table = Table::new();
-    let mut deck: Sequence;
-    let mut hands: Vec<Sequence>;
-    let mut player: usize;
+    let mut deck: Deck;
+    let mut hands = Vec::<Hand>::new();
     let mut player_names = Vec::<String>::new();
+    let mut dealt = Vec::<Sequence>::new(); // cards dealt to the players of a new game before they join
     let mut rng = thread_rng();
+    let mut audit: AuditLog;
+    let mut progress = (1, 0); // round, and turns played in it
+    let mut resumed_turn = None; // turn saved while in progress
+    let mut saved_clock = None; // time taken by the players before the game was saved
     
     if load {
         
-        let mut fname = String::new(); // filename
-        let mut bytes = Vec::<u8>::new();
+        let mut fname = resume_save.clone().unwrap_or_default(); // filename
         // if there is a next command-line argument, use it as name for the save file
         // if not, use the default name
         if load_from_command_line {
@@ -128,66 +432,100 @@ fn main() {
             };
         }
         
+        // saves found in the save directory, which can be picked by their number
+        let mut saves = Vec::new();
+
         loop {
 
-            // get the file name if not set
+            // get the file name if not set (the saves are listed again after one was deleted or
+            // renamed)
             if fname.len() == 0 {
-                println!("Name of the save file (nothing for the default file):");
-                match stdin().read_line(&mut fname) {
-                    Ok(_) => (),
+                saves = save_dir.as_deref().map(|d| saves::list_saves(d, SAVE_EXTENSION)).unwrap_or_default();
+                if !saves.is_empty() {
+                    println!("Saved games:");
+                    saves::print_saves(&saves);
+                    println!("Number or name of the save file (nothing for the default file; {}):",
+                             saves::MANAGE_SAVES_HELP);
+                } else {
+                    println!("Name of the save file (nothing for the default file):");
+                }
+                match get_input() {
+                    Ok(s) => fname += &s,
                     Err(_) => {
                         println!("Could not read the input");
                         continue;
                     }
                 };
+                if let Some(message) = saves::manage_saves(&fname, &saves, save_dir.as_deref(), SAVE_EXTENSION) {
+                    println!("{}", message);
+                    fname.clear();
+                    continue;
+                }
             }
 
-            fname = fname.trim().to_string();
+            fname = match saves::pick_save(&saves, &fname) {
+                Some(save) => save.path.clone(),
+                None => fname.trim().to_string()
+            };
 
             // if the length is equal to 0, use the default file name
             if fname.len() == 0 {
                 fname = savefile.clone() + SAVE_EXTENSION;
             }
 
-            // try to open the file
-            let mut file: File; 
-            match File::open(fname.clone()) {
-                Ok(f) => file = f,
-                Err(_) => {
-                    println!("Could not open the file!");
-                    fname.clear();
-                    continue;
-                }
-            };
+            // bare names are looked for in the save directory
+            fname = paths::find_save(&fname, save_dir.as_deref());
 
-            // load the data from the file
-            match file.read_to_end(&mut bytes) {
-                Ok(_) => (),
+            // try to read the file, or the checkpoint to roll back to
+            let path = match rollback {
+                Some(target) => {
+                    let checkpoints = checkpoints::list(fname.trim_end_matches(SAVE_EXTENSION));
+                    match checkpoints::find(&checkpoints, target) {
+                        Some(checkpoint) => {
+                            println!("Rolling back to the start of turn {} of round {}", checkpoint.turn + 1,
+                                     checkpoint.round);
+                            checkpoint.path.clone()
+                        },
+                        None => {
+                            println!("No checkpoint of {} at or before this turn", fname);
+                            fname.clear();
+                            continue;
+                        }
+                    }
+                },
+                None => fname.clone()
+            };
+            let data = match std::fs::read(path) {
+                Ok(data) => data,
                 Err(_) => {
-                    println!("Could not read from the file!");
-                    bytes.clear();
+                    println!("Could not open the file!");
                     fname.clear();
                     continue;
                 }
             };
-            
-            // decode the sequence of bytes
-            bytes = encode::xor(&bytes, &fname.as_bytes());
 
-            // load the game
-            match load_game(&bytes) {
-                Ok(lg) => {
+            // decrypt and load the game
+            match encode::decrypt_save_or_legacy(&data, &fname, ask_legacy).map_err(|_| LoadingError::Corrupted)
+                .and_then(|b| save_format::load(&b, ask_repair))
+            {
+                Ok((lg, turn, report)) => {
+                    for note in report.notes() {
+                        println!("{}", note);
+                    }
                     config = lg.0;
-                    starting_player = lg.1;
-                    player = lg.2 as usize; 
+                    order = TurnOrder::resume(config.n_players as usize, lg.1 as usize, lg.2 as usize);
                     table = lg.3;
                     hands = lg.4; 
                     deck = lg.5;
-                    player_names = lg.6;
+                    player_names = hand::owners(&hands);
+                    resumed_turn = turn;
+                    if let Some(metadata) = encode::save_metadata(&data).and_then(saves::SaveMetadata::from_bytes) {
+                        progress = (metadata.round, metadata.turn);
+                        saved_clock = Some(metadata.clock());
+                    }
                 },
-                Err(_) => {
-                    println!("Error loading the save file!");
-                    bytes.clear();
+                Err(e) => {
+                    println!("Error loading the save file: {}", e);
                     fname.clear();
                     continue;
                 }
@@ -196,22 +534,63 @@ fn main() {
             break;
         }
 
+        // keep saving to the same file when resuming a game
+        if resume_dir.is_some() {
+            savefile = fname.trim_end_matches(SAVE_EXTENSION).to_string();
+        } else {
+            savefile = paths::in_dir(&savefile, save_dir.as_deref());
+        }
+
+        // continue the audit log of the game
+        let audit_name = savefile.clone() + AUDIT_EXTENSION;
+        audit = match AuditLog::resume(&audit_name) {
+            Ok(a) => a,
+            Err(err) => {
+                println!("Could not resume the audit log ({}); draws will be audited from the next round", err);
+                AuditLog::create(&audit_name).unwrap_or_default()
+            }
+        };
+        if rollback.is_some() {
+            println!("The audit log goes on from the last save; the draws after the checkpoint may not verify");
+        }
+
     } else {
 
-        // build the deck
-        deck = Sequence::multi_deck(config.n_decks, config.n_jokers, &mut rng);
+        if rollback.is_some() {
+            println!("{} is only used when loading a game", checkpoints::ROLLBACK_FLAG);
+            return;
+        }
+
+        // new games are saved to the resume directory, or to the save directory
+        if let Some(dir) = &resume_dir {
+            savefile = std::path::Path::new(dir).join(&savefile).to_string_lossy().to_string();
+        } else {
+            savefile = paths::in_dir(&savefile, save_dir.as_deref());
+        }
+
+        // an existing save is kept, and the new game saved next to it
+        let free_name = saves::versioned_name(&(savefile.clone() + SAVE_EXTENSION), SAVE_EXTENSION);
+        if free_name != savefile.clone() + SAVE_EXTENSION {
+            println!("{}{} already exists; the game will be saved to {}", savefile, SAVE_EXTENSION, free_name);
+            savefile = free_name.trim_end_matches(SAVE_EXTENSION).to_string();
+        }
+
+        // start the audit log of the game
+        audit = match AuditLog::create(&(savefile.clone() + AUDIT_EXTENSION)) {
+            Ok(a) => a,
+            Err(_) => {
+                println!("Could not create the audit log file!");
+                AuditLog::new()
+            }
+        };
+
+        // build the deck and the hands
+        let deal = new_deal(&config, &mut audit, &mut rng);
+        deck = deal.0;
+        dealt = deal.1;
     
         // choose the starting player randomly
-        starting_player = rng.gen_range(0..config.n_players);
-        player = starting_player as usize;
-        
-        // build the hands
-        hands = vec![Sequence::new(); config.n_players as usize];
-        for i in 0..config.n_players {
-            for _ in 0..config.n_cards_to_start {
-                hands[i as usize].add_card(deck.draw_card().unwrap());
-            }
-        }
+        order = TurnOrder::new(config.n_players as usize, rng.gen_range(0..config.n_players as usize));
 
     }
 
@@ -219,16 +598,22 @@ fn main() {
     let mut n_clients: u8 = 0;
 
     // vector of client threads
-    let mut client_threads = Vec::<thread::JoinHandle<(TcpStream, String, usize)>>::new();
+    let mut client_threads = Vec::<thread::JoinHandle<(WorkerConnection, String, usize)>>::new();
     
     // vector of client streams
-    let mut client_streams = Vec::<TcpStream>::new();
+    let mut client_streams = Vec::<WorkerConnection>::new();
+
+    // passphrase to encrypt the connections with, if any
+    let passphrase = server_config.passphrase.clone();
+
+    // index of the first player to connect, who acts as host
+    let mut host: usize = 0;
+    
+    // set-up the tcp listener (kept open during the game so that players can reconnect)
+    let listener = TcpListener::bind(format!("0.0.0.0:{}", port)).unwrap();
     
     {
 
-        // set-up the tcp listener
-        let listener = TcpListener::bind(format!("0.0.0.0:{}", port)).unwrap();
-        
         // accept connections and process them, each in its own thread
         let names_taken = Arc::new(Mutex::new(Vec::<String>::new())); // vector of the names that are already taken
         println!("\nserver listening to port {}", port);
@@ -236,7 +621,21 @@ fn main() {
             match stream_res {
                 Ok(stream) => {
                     n_clients += 1;
-                    println!("New connection: {} (player {})", stream.peer_addr().unwrap(), n_clients);
+                    logging::info(&format!("New connection: {} (player {})", stream.peer_addr().unwrap(), n_clients));
+
+                    // each connection has its own threads to write and read
+                    let stream = match &passphrase {
+                        Some(passphrase) => WorkerConnection::from_tcp_encrypted(stream, passphrase),
+                        None => WorkerConnection::from_tcp(stream)
+                    };
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            logging::error(&format!("Could not set up the connection of player {}: {}", n_clients, e));
+                            n_clients -= 1;
+                            continue;
+                        }
+                    };
                     if load {
                         let player_names_ = player_names.clone();
                         let arc = names_taken.clone();
@@ -248,7 +647,7 @@ fn main() {
                     }
                 },
                 Err(e) => {
-                    println!("Error: {}", e);
+                    logging::error(&format!("Error: {}", e));
                 }
             }
 
@@ -261,13 +660,17 @@ fn main() {
         // wait for all threads to finish and collect the client streams 
         if load {
 
-            for _i in 0..config.n_players {
-                client_streams.push(TcpStream::connect(format!("0.0.0.0:{}", port)).unwrap());
-            }
-            for thread in client_threads {
+            // put each stream at the position of the player in the save
+            let mut outputs = Vec::new();
+            for (i, thread) in client_threads.into_iter().enumerate() {
                 let output = thread.join().unwrap();
-                client_streams[output.2] = output.0;
+                if i == 0 {
+                    host = output.2;
+                }
+                outputs.push((output.2, output.0));
             }
+            outputs.sort_by_key(|(position, _)| *position);
+            client_streams = outputs.into_iter().map(|(_, stream)| stream).collect();
 
         } else {
 
@@ -277,11 +680,22 @@ fn main() {
                 player_names.push(output.1);
             }
 
-            // check that no players have the same name; if yes, rename players
-            ensure_names_are_different(&mut player_names, &mut client_streams).unwrap();
+            // players with the same name are told apart by a number after it
+            hands = Hand::deal(&player_name::display_names(&player_names), dealt);
         }
 
     }
+    listener.set_nonblocking(true).unwrap();
+    let listener = WorkerListener(listener, passphrase);
+    let mut players = Player::seat(hands, client_streams);
+    colours::register(&hand::owners(&players));
+
+    // give each client the token to send back if they need to reconnect
+    for player in players.iter_mut() {
+        player.token = player::new_token(&mut rng);
+        let token = player.token.clone();
+        send_token_to_client(player, &token).unwrap_or(());
+    }
 
     // name of the save file
     let save_name = &(savefile.clone() + SAVE_EXTENSION);
@@ -289,157 +703,460 @@ fn main() {
     // name of the backup save file
     let backup_name = &(savefile.clone() + &"_bak" + SAVE_EXTENSION);
    
-    // sort modes for the cards (0: unsorted, 1: sort by rank, 2: sort by suit)
-    let mut sort_modes: Vec<u8> = vec![0; config.n_players as usize];
-
     let mut play_again = true;
-    let mut previous_messages: Vec<Option<String>> = vec![None; config.n_players as usize];
+    let mut log = EventLog::new();
+
+    // moderation (the first player to connect is the host)
+    let mut seats = Seats::new(config.n_players as usize, host);
+    let mut stalemate = stalemate::StalemateDetector::new(config.n_players as usize);
+    let mut record = replay::GameRecord::new(&hand::owners(&players));
+    let mut round = progress.0 as usize;
+    let mut n_turns = progress.1 as usize;
+    let mut clock = saved_clock.unwrap_or_else(|| clock::Clock::new(config.n_players as usize));
+    let mut tracker = StateTracker::new();
+    let mut resync = false; // whether the clients need the whole state, after the game was rewound
+    let no_cards_taken = Sequence::new();
+    let cards_taken = resumed_turn.as_ref().map(|(_, cards)| cards).unwrap_or(&no_cards_taken);
+    if !load {
+        if config.draw_for_first {
+            draw_first_player(&mut players, &mut order, &mut rng, &mut log);
+        }
+        offer_mulligans(&mut players, &mut deck, &config, &mut audit, &mut log);
+    }
+    let mut ledger = CardLedger::new(&table, &players, &deck, cards_taken);
+    let mut previous_turn = PreviousTurn::default();
+    shutdown::SIGNAL.start_game();
     while play_again {
         loop {
-            
-            // if all the cards have been drawn, stop the game
-            if deck.number_cards() == 0 {
-                send_message_all_players(&mut client_streams, 
-                                         &"\n\x1b[1mNo more cards in the deck—it's a draw!\x1b[0m\n");
-                break;
+            let player = order.current();
+
+            // give their seat back to players who reconnected
+            while let Ok(Some(i)) = try_reconnection(&listener, &mut players) {
+                logging::info(&format!("Player {} is back", i + 1));
+                let message = format!("{} is back!\n", players[i].name());
+                send_message_all_players(&mut players, &message);
+            }
+            shutdown::SIGNAL.watch(&players);
+
+            // read the settings again if asked to, before the time of the turn is counted
+            let reload_requested = admin_state.lock().map(|mut s| std::mem::take(&mut s.reload_requested))
+                .unwrap_or(false);
+            if reload::take_request() | reload_requested {
+                reload_server_config(&mut server_config);
             }
             
-            // save the game
-            let mut bytes = game_to_bytes(starting_player as u8, player as u8, &table, &hands, &deck, 
-                                          &config, &player_names);
-            bytes = encode::xor(&bytes, save_name.as_bytes());
-            match File::create(save_name) {
-                Ok(mut f) => match f.write_all(&bytes) {
-                    Ok(_) => (),
-                    Err(_) => {
-                        println!("Could not write to the save file!");
-                    }
-                },
-                Err(_) => {
-                    println!("Could not create the save file!");
+            // if all the cards have been drawn, apply the rule for an empty deck (with the
+            // scoring rule, the round goes on until there is a stalemate)
+            if deck.remaining() == 0 {
+                match config.empty_deck {
+                    // without cards set aside, the round goes on as with the scoring rule
+                    EmptyDeckRule::NewDeck => if refill_deck(&mut deck, &mut audit, &mut rng) {
+                        log.push(Event::NoMoreCards);
+                        log.push(Event::NewDeck);
+                        send_message_all_players(&mut players, 
+                            "\nNo more cards in the deck—the cards set aside are shuffled into a new deck.\n");
+                    },
+                    EmptyDeckRule::Draw => {
+                        log.push(Event::NoMoreCards);
+                        send_message_all_players(&mut players, 
+                                                 &"\n\x1b[1mNo more cards in the deck—it's a draw!\x1b[0m\n");
+                        break;
+                    },
+                    EmptyDeckRule::Score => ()
                 }
+            }
+            
+            // save the game, with the turn if it resumes in progress
+            let bytes = game_to_bytes_with_turn(order.starting_player() as u8, player as u8, &table, &players,
+                                                &deck, &config, resumed_turn.as_ref());
+            // the files are replaced only once fully written, so that a crash keeps the last save
+            let metadata = saves::SaveMetadata::new(round as u32, n_turns as u32, player as u8, &players, &config)
+                .with_clock(&clock);
+            let encrypted = encode::encrypt_save(&bytes, &metadata.to_bytes(), save_name, &mut rand::thread_rng());
+            match write_atomically(save_name, &encrypted) {
+                Ok(_) => (),
+                Err(_) => logging::error("Could not write to the save file!")
             };
             
-            // backup the save file
-            match std::fs::copy(&save_name, &backup_name) {
+            // backup the save file (encrypted with the name of the save file, as a copy)
+            match write_atomically(backup_name, &encrypted) {
                 Ok(_) => (),
-                Err(_) => println!("Could not create the backup file!")
+                Err(_) => logging::error("Could not create the backup file!")
             };
+
+            // keep the start of the turn as a checkpoint every few turns
+            if checkpoints::is_due(n_turns as u32, server_config.checkpoint_every) {
+                let written = checkpoints::write(&savefile, round as u32, n_turns as u32, &encrypted,
+                                                 server_config.n_checkpoints);
+                if written.is_err() {
+                    logging::error("Could not write the checkpoint!");
+                }
+            }
+            seats.rewind_points = checkpoints::list(&savefile).into_iter()
+                .filter(|c| c.round == round as u32 && (c.turn as usize) < n_turns)
+                .map(|c| c.turn)
+                .collect();
+
+            // the game was just saved at the start of the turn, which is where it will resume
+            if shutdown::requested() {
+                break;
+            }
  
             // print the name of the current player 
-            clear_and_send_message_all_players(&mut client_streams, 
-                                               &format!("\x1b[1m{}'s turn:{}", 
-                                                        &player_names[player], &reset_style_string()));
+            let message = format!("\x1b[1m{}'s turn:{}", colours::paint(players[player].name()), &reset_style_string());
+            clear_and_send_message_all_players(&mut players, &message);
         
-            // string with the number of cards each player has
-            let mut string_n_cards = format!("\nNumber of cards ({} remaining in the deck):", deck.number_cards());
-            for i in 0..(config.n_players as usize) {
-                string_n_cards += &format!("\n  {}: {}", &player_names[i], &hands[i].number_cards());
-            }
-            string_n_cards += "\n";
+            // commitment to the seed of the deck, revealed at the end of the round
+            let string_commitment = match audit.seed() {
+                Some(seed) => format!("\nDeck commitment: {}\n", &commitment(seed)[..16]),
+                None => String::new()
+            };
 
+            // recent events, so that players who stepped away can catch up
+            let string_events = if log.is_empty() {
+                String::new()
+            } else {
+                format!("\n{}", log.render(N_EVENTS_SHOWN))
+            };
            
-            // print the situation for each player
+            // print the situation for each player, whose client draws the table from the public
+            // state: the sequences are numbered as in the normalized table
+            table.normalize();
+            let mut update = tracker.diff(&PublicState::new(&table, &players, &deck, player));
+            if n_turns.is_multiple_of(FULL_STATE_PERIOD) || std::mem::take(&mut resync) {
+                update = tracker.snapshot();
+            }
             for i in 0..(config.n_players as usize) {
+                if !players[i].connected {
+                    continue;
+                }
                 loop {
-                    match send_message_to_client(&mut client_streams[i], 
-                            &format!("{}{}", &string_n_cards, 
-                                &situation_to_string(&table, &hands[i], &Sequence::new()))
-                    ) {
+                    let view = PlayerView::new(&players, &order, i).with_previous_turn(previous_turn.clone());
+                    let sent = sync_state_with_client(&mut players[i], &tracker, &update).and_then(|in_sync| {
+                        if in_sync {
+                            let turn_view = TurnView { hand: players[i].hand.cards().clone(),
+                                                       changed: view.previous_turn.changed.clone(),
+                                                       pages: players[i].pages };
+                            send_message_to_client(&mut players[i], &string_commitment)?;
+                            send_situation_to_client(&mut players[i], &view.header(deck.remaining()), &turn_view,
+                                                     &string_events)
+                        } else {
+                            // a client out of sync can not draw the table, which is sent drawn
+                            let situation = situation_to_string(&table, &players[i].hand, &Sequence::new(),
+                                                                &players[i].layout, &players[i].pages,
+                                                                deck.remaining(), &view);
+                            send_message_to_client(&mut players[i],
+                                &format!("{}{}{}", &string_commitment, &situation, &string_events))
+                        }
+                    }).and_then(|()| match players[i].last_message.clone() {
+                        Some(s) => send_message_to_client(&mut players[i], &format!("\n{}", s)),
+                        None => Ok(())
+                    });
+                    match sent {
                         Ok(_) => break,
                         Err(_) => {
+                            let name = players[i].name().to_string();
+                            let token = players[i].token.clone();
                             send_message_all_players(
-                                &mut client_streams,
-                                &format!("{} seems to have disconnected... Waiting for them to reconnect.\n", 
-                                         &player_names[i])
+                                &mut players,
+                                &format!("{} seems to have disconnected... Waiting for them to reconnect.\n", &name)
                             );
-                            println!("Lost connection with player {}", i + 1);
-                            wait_for_reconnection(&mut client_streams[i], &player_names[i], port).unwrap();
-                            println!("Player {} is back", i + 1);
+                            logging::info(&format!("Lost connection with player {}", i + 1));
+                            let reconnected = wait_for_reconnection(&mut players[i].connection, &name, &token,
+                                                                    &listener, server_config.reconnection_timeout())
+                                .unwrap_or_else(|err| stop_on_protocol_error(&err, &table, &players, &deck, player,
+                                                                             &config, &record, &log,
+                                                                             save_dir.as_deref()));
+                            if !reconnected {
+                                seats.replace_by_bot(&mut players, i);
+                                log.push(Event::BotTookSeat(name.clone()));
+                                logging::info(&format!("A bot takes the place of player {}", i + 1));
+                                send_message_all_players(
+                                    &mut players,
+                                    &format!("A bot plays for {} until they reconnect.\n", &name)
+                                );
+                                break;
+                            }
+                            logging::info(&format!("Player {} is back", i + 1));
                             send_message_all_players(
-                                &mut client_streams,
-                                &format!("{} is back!\n", &player_names[i])
+                                &mut players,
+                                &format!("{} is back!\n", &name)
                             );
                         }
                     };
                 }
-                if let Some(s) =  &previous_messages[i] {
-                    send_message_to_client(&mut client_streams[i], &format!("\n{}", s)).unwrap();
-                };
             }
 
+            record.sync(&log);
+            record.start_turn(player);
+            update_admin_state(&admin_state, round, n_turns, player, &table, &players, &deck);
+            n_turns += 1;
+            logging::debug(&format!("Round {}, turn {}: player {}", round, n_turns, player + 1));
+
             // player turn
-            previous_messages[player] = match start_player_turn(&mut table, &mut hands, &mut deck, 
-                              config.custom_rule_jokers, &player_names,
-                              player, config.n_players as usize, &mut client_streams,
-                              port, &mut sort_modes[player], &previous_messages)
-            {
-                Ok(o_m) => o_m,
-                Err(err) => {
-                    println!("{}", err);
-                    process::exit(1);
+            let deck_before = deck.clone();
+            let table_start = table.clone();
+            let n_events_start = log.total();
+            let n_cards_before = players[player].hand.number_cards();
+            players[player].hand.start_turn();
+
+            // a turn in progress is only resumed by its player: otherwise it starts again
+            if !players[player].connected {
+                if let Some((turn, _)) = resumed_turn.take() {
+                    turn.restore(&mut table, &mut players[player].hand);
+                }
+            }
+
+            // the turn is timed from where it was left, if it was saved in progress
+            clock.start_turn(player);
+
+            // the turn is saved each time something is played or taken, so that it resumes as it
+            // is if the server stops
+            let starting_player = order.starting_player() as u8;
+            let turn_in_round = n_turns as u32 - 1;
+            let mut save_turn = |table: &Table, players: &[Player<WorkerConnection>], deck: &Deck,
+                                 turn: &turn::Ongoing, cards_from_table: &Sequence| {
+                let saved_turn = (turn.clone(), cards_from_table.clone());
+                let bytes = game_to_bytes_with_turn(starting_player, player as u8, table, players, deck, &config,
+                                                    Some(&saved_turn));
+                let metadata = saves::SaveMetadata::new(round as u32, turn_in_round, player as u8, players, &config)
+                    .with_clock(&clock);
+                let encrypted = encode::encrypt_save(&bytes, &metadata.to_bytes(), save_name, &mut rand::thread_rng());
+                if write_atomically(save_name, &encrypted).is_err() {
+                    logging::error("Could not write to the save file!");
                 }
             };
+            if players[player].connected {
+                players[player].turn_deadline = clock.remaining(server_config.turn_timeout())
+                    .map(|left| std::time::Instant::now() + left);
+                players[player].turn_started = std::time::Instant::now().checked_sub(clock.elapsed());
+                players[player].last_message = match start_player_turn(&mut table, &mut players, &mut deck, 
+                                  config.custom_rule_jokers, player, &listener, &mut log,
+                                  &server_config, &mut seats, resumed_turn.take(), &previous_turn, &mut save_turn)
+                {
+                    Ok(o_m) => o_m,
+                    Err(_) if shutdown::requested() => None,
+                    Err(err) => stop_on_protocol_error(&err, &table, &players, &deck, player, &config, &record, &log,
+                                                       save_dir.as_deref())
+                };
+                players[player].turn_deadline = None;
+                players[player].turn_started = None;
+                players[player].connection.set_timeout(None).unwrap_or(());
+            }
+
+            // the turn is not recorded if the server is shutting down: the game resumes from the
+            // last save, made when it started or when something was last played or taken
+            if shutdown::requested() {
+                break;
+            }
+
+            // the player may have left during their turn
+            if !players[player].connected && seats.host_action.is_none() {
+                let name = players[player].name().to_string();
+                bot::play_turn(&mut table, &mut players[player].hand, &mut deck, &config,
+                               &name, &mut log);
+                players[player].last_message = None;
+            }
+            clock.end_turn();
+            record_draws(&mut audit, player, &deck_before, &deck);
+            players[player].hand.end_turn(deck_before.remaining().saturating_sub(deck.remaining()));
+            table.normalize();
+            ledger.assert_holds(&table, &players, &deck, &Sequence::new(), "a turn");
+            previous_turn = PreviousTurn::new(players[player].name(), &table_start, &table, n_cards_before,
+                                              players[player].hand.number_cards(), &log.since(n_events_start));
+
+            // the host may have rewound the game to a checkpoint of the round: the game goes on
+            // from there, and the turns after it are dropped from the record
+            if let Some(HostAction::Rewind(point)) = seats.host_action {
+                seats.host_action = None;
+                let path = checkpoints::checkpoint_name(&savefile, round as u32, point);
+                match load_checkpoint(&path, save_name) {
+                    Ok((game, turn, metadata)) => {
+                        order = TurnOrder::resume(config.n_players as usize, game.1 as usize, game.2 as usize);
+                        table = game.3;
+                        for (player, hand) in players.iter_mut().zip(game.4) {
+                            player.hand = hand;
+                        }
+                        deck = game.5;
+                        resumed_turn = turn;
+                        if let Some(clock_then) = metadata.map(|m| m.clock()) {
+                            clock = clock_then;
+                        }
+                        let cards_taken = resumed_turn.as_ref().map(|(_, cards)| cards).unwrap_or(&no_cards_taken);
+                        ledger = CardLedger::new(&table, &players, &deck, cards_taken);
+                        stalemate.reset();
+                        previous_turn = PreviousTurn::default();
+                        record.sync(&log);
+                        record.rewind(point as usize);
+                        n_turns = point as usize;
+                        log.push(Event::Rewound(players[seats.host].name().to_string(), point + 1));
+                        resync = true;
+                        logging::info(&format!("Rewound to the start of turn {} of round {}", point + 1, round));
+                        send_message_all_players(&mut players, 
+                            &format!("\n\x1b[1mThe game was rewound to the start of turn {}.\x1b[0m{}\n",
+                                     point + 1, &reset_style_string()));
+                    },
+                    Err(e) => {
+                        // the turn of the host starts again
+                        logging::error(&format!("Could not load the checkpoint {}: {}", path, e));
+                        send_message_all_players(&mut players, "The game could not be rewound; it goes on.\n");
+                        record.sync(&log);
+                        n_turns -= 1;
+                        record.rewind(n_turns);
+                    }
+                };
+                continue;
+            }
+
+            // the host may have restarted the round or ended the game
+            if seats.host_action.is_some() {
+                break;
+            }
+
+            // with the scoring rule, the round ends when nobody can play any more
+            if config.empty_deck == EmptyDeckRule::Score {
+                if deck_before.remaining() > 0 && deck.remaining() == 0 {
+                    log.push(Event::NoMoreCards);
+                    send_message_all_players(&mut players, 
+                        "\nNo more cards in the deck—play goes on until nobody can play.\n");
+                }
+                let played = players[player].hand.number_cards() < n_cards_before;
+                if stalemate.record_turn(deck.remaining() == 0, played) {
+                    log.push(Event::Stalemate);
+                    let result = match round_winner(&players, &config.point_rules()) {
+                        Some(i) => {
+                            log.push(Event::Won(players[i].name().to_string()));
+                            players[i].score += 1;
+                            format!("{} wins with the fewest cards left!", players[i].name())
+                        },
+                        None => "it's a draw!".to_string()
+                    };
+                    send_message_all_players(&mut players, 
+                        &format!("\n\x1b[1mNobody can play any more—{}\x1b[0m{}\n", 
+                                 result, &reset_style_string()));
+                    break;
+                }
+            }
             
  
             // if the player has no more cards, stop the game
-            if hands[player].number_cards() == 0 {
-                send_message_all_players(&mut client_streams, 
-                    &format!("\n\u{0007}\u{0007}\u{0007}\x1b[1m{} wins! Congratulations!\x1b[0m{}\n\n", 
-                             player_names[player], &reset_style_string())
-                );
+            if players[player].hand.number_cards() == 0 {
+                log.push(Event::Won(players[player].name().to_string()));
+                players[player].score += 1;
+                let message = format!("\n\u{0007}\u{0007}\u{0007}\x1b[1m{} wins! Congratulations!\x1b[0m{}\n\n", 
+                                      players[player].name(), &reset_style_string());
+                send_message_all_players(&mut players, &message);
                 break;
             }
             
             // next player
-            player += 1;
-            if player >= config.n_players as usize {
-                player = 0;
-            }
+            order.advance();
 
         }
 
-        // ask the players if they want to play again
-        send_message_all_players(&mut client_streams, &"Play again? (‘y’ for yes)\n".to_string());
-        for stream in &mut client_streams {
-            let reply = match get_string_from_client(stream) {
-                Ok(s) => s,
-                Err(_) => "y".to_string()
-            };
+        if shutdown::requested() {
+            println!("Game saved to {}", save_name);
+            let message = format!("\n\x1b[1mThe server is shutting down; the game will resume where this turn \
+                                   was left.\x1b[0m{}", &reset_style_string());
+            for stream in &mut players {
+                send_exit_message_to_client(stream, &message).unwrap_or(());
+            }
+            break;
+        }
+
+        record.sync(&log);
+        record.end_round(&players);
 
-            // if at least one of them does not say yes, quit
-            if !is_yes(reply.trim()) {
+        // cards and points left in each hand
+        let summary = format!("{}\n{}", round_summary(&players, &config.point_rules()),
+                              clock.summary(&hand::owners(&players)));
+        send_message_all_players(&mut players, &summary);
+
+        // reveal the seed of the deck
+        if let Some(seed) = audit.seed() {
+            send_message_all_players(&mut players, 
+                &format!("The deck was shuffled with seed {} (commitment {}).\n", 
+                         seed, &commitment(seed)[..16]));
+        }
+
+        // ask the players if they want to play again, unless the host or the administrator has
+        // already decided
+        let shutdown_requested = admin_state.lock().map(|s| s.shutdown_requested).unwrap_or(false);
+        match seats.host_action.take() {
+            _ if shutdown_requested => {
+                send_message_all_players(&mut players, "The server is shutting down. Thanks for playing!\n");
                 play_again = false;
-                match stream.write(&mut [5]) {
-                    Ok(_) => {},
-                    Err(_) => println!("Could not send the exit signal")
-                };
+            },
+            Some(HostAction::RestartRound) | Some(HostAction::Rewind(_)) => (),
+            Some(HostAction::EndGame) => play_again = false,
+            None => {
+                send_message_all_players(&mut players, &"Play again? (‘y’ for yes)\n".to_string());
+                for stream in &mut players {
+                    let reply = match get_string_from_client(stream) {
+                        Ok(s) => s,
+                        Err(_) => "y".to_string()
+                    };
+
+                    // if at least one of them does not say yes, quit
+                    if !is_yes(reply.trim()) {
+                        play_again = false;
+                        match stream.write_all(&[5]) {
+                            Ok(_) => {},
+                            Err(_) => println!("Could not send the exit signal")
+                        };
+                    }
+                }
             }
         }
 
+        // no need to go on if only bots are left
+        if players.iter().all(|p| !p.connected) {
+            play_again = false;
+        }
+
         // if all of them say yes, re-initialize the game
         if play_again {
-            deck = Sequence::multi_deck(config.n_decks, config.n_jokers, &mut rng);
-            hands = vec![Sequence::new(); config.n_players as usize];
-            table = Table::new();
-            for i in 0..config.n_players {
-                for _ in 0..config.n_cards_to_start {
-                    hands[i as usize].add_card(deck.draw_card().unwrap());
-                }
+            let deal = new_deal(&config, &mut audit, &mut rng);
+            deck = deal.0;
+            for (player, cards) in players.iter_mut().zip(deal.1) {
+                player.hand.new_round(cards);
             }
+            offer_mulligans(&mut players, &mut deck, &config, &mut audit, &mut log);
+            table = Table::new();
+            previous_turn = PreviousTurn::default();
+            ledger = CardLedger::new(&table, &players, &deck, &Sequence::new());
+            stalemate.reset();
+            record.start_round();
+            round += 1;
+            n_turns = 0;
 
-            // update the starting player
-            starting_player += 1;
-            if starting_player >= config.n_players {
-                starting_player = 0;
-            }
-            player = starting_player as usize;
+            // the next player around the table starts the new round
+            order.new_round();
         }
     }
 
+    if let Some(fname) = &transcript {
+        match replay::save_transcript(&record, &config.point_rules(), i18n::DEFAULT_LOCALE, fname) {
+            Ok(()) => println!("Transcript saved to {}", fname),
+            Err(_) => println!("Could not save the transcript!")
+        };
+    }
+
+    // a finished game is not resumed
+    if resume_dir.is_some() && !shutdown::requested() {
+        std::fs::rename(save_name, save_name.to_string() + FINISHED_EXTENSION).unwrap_or(());
+    }
+
+    // the clients were already told if the server is shutting down; give them time to read the
+    // message, as they could not send anything back
+    if shutdown::requested() {
+        long_wait();
+        return;
+    }
+
     // send the exit signal to all clients
     for i in 0..config.n_players as usize {
-        match client_streams[i].write(&mut [5]) {
+        match players[i].write_all(&[5]) {
             Ok(_) => {},
             Err(_) => println!("Could not send the exit signal to client {}", i)
         };
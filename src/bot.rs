@@ -0,0 +1,519 @@
+//! A simple computer player
+//!
+//! The bot lays down all the sequences it can form with the cards in its hand, without touching
+//! the table, and picks a card if it could not play anything. It keeps its jokers rather than
+//! laying them in a sequence which does not empty its hand, when the next card is likely enough to
+//! complete a sequence without them (see the `odds` module). It is used by the server to keep the
+//! seat of a player who left until they come back.
+//!
+//! `evaluate` scores a hand the way the bot sees it, from 0 (nothing can be laid down) to 1
+//! (everything can), with the points of the cards it could not get rid of counted against it. Its
+//! components are given by `evaluation`:
+//!
+//! * the cards forming sequences in the hand, found one sequence at a time as the bot plays them,
+//!   and those which can then join a sequence of the table, cost nothing
+//! * the cards of near-melds, pairs which one more card would make into a sequence (two cards of
+//!   the same value, or of the same suit and at most two values apart), cost half their points
+//! * the jokers left cost a quarter of their penalty, since they can complete any near-meld
+//! * the other cards, the deadwood, cost all their points
+//!
+//! Bots written in any language can also play, as separate programs managed by an `ExternalBot`.
+//! They talk with the game through their standard input and output, one command per line, in
+//! the manner of chess engines:
+//!
+//! * the game sends `mvi`; the bot may answer `id name <name>`, and then answers `mviok`
+//! * at each move of its turn, the game sends `state <json>`, with the snapshot of the game as
+//!   the bot's player sees it (see the `snapshot` module), `taken <cards>`, with the cards taken
+//!   from the table and not played yet, and `go`
+//! * the bot answers `bestmove <action>`, with an action written as in a script (`p 1 2 3`,
+//!   `t 2`, `a 1 4`, `e`, or `g`; see the `script` module); it may send `info <text>` lines
+//!   before, which are ignored
+//! * if the action is not valid, the game sends `error <reason>` and asks again; after
+//!   `MAX_INVALID_MOVES` invalid actions, the turn is given up, or a card is picked
+//! * the game sends `quit` when it is over
+
+use crate::sequence_cards::*;
+use crate::table::Table;
+use crate::deck::Deck;
+use crate::events::{ Event, EventLog };
+use crate::solver::{ arrange, find_sequence };
+use crate::turn::Ongoing;
+use crate::script::ScriptedGame;
+use crate::snapshot::{ card_code, snapshot, Visibility };
+use crate::odds::draw_odds;
+use super::{ pick_a_card, Config };
+use std::io::{ self, BufRead, BufReader, Write };
+use std::process::{ Child, ChildStdin, ChildStdout, Command, Stdio };
+
+/// number of invalid actions after which an external bot loses its turn
+pub const MAX_INVALID_MOVES: usize = 3;
+
+/// probability of drawing a useful card above which the bot keeps its jokers
+pub const KEEP_JOKERS_ODDS: f64 = 0.25;
+
+/// share of their points that the cards of near-melds cost in `evaluate`
+pub const NEAR_MELD_COST: f64 = 0.5;
+
+/// share of their penalty that the jokers left cost in `evaluate`
+pub const FREE_JOKER_COST: f64 = 0.25;
+
+/// number of characters of the hand quality meter
+const METER_WIDTH: usize = 10;
+
+/// Components of the evaluation of a hand
+#[derive(Debug, PartialEq, Clone)]
+pub struct Evaluation {
+    /// number of cards forming sequences in the hand
+    pub melded: usize,
+    /// number of cards which can join a sequence of the table
+    pub on_table: usize,
+    /// number of cards in near-melds
+    pub near_melds: usize,
+    /// number of jokers left once the sequences are formed
+    pub free_jokers: usize,
+    /// cards which are in none of the above
+    pub deadwood: Vec<Card>,
+    /// points of the deadwood
+    pub deadwood_points: u16,
+    /// score, from 0 to 1
+    pub score: f64
+}
+
+impl Evaluation {
+
+    /// Hand quality meter, as shown to the player
+    pub fn meter(&self) -> String {
+        let n_full = (self.score * METER_WIDTH as f64).round() as usize;
+        format!("Hand quality: [{}{}] {:.0}%", "#".repeat(n_full), "-".repeat(METER_WIDTH - n_full),
+                100. * self.score)
+    }
+}
+
+/// Score of a hand, from 0 to 1, with the rules of a config
+///
+/// See `evaluation` for its components.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::parse_config;
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::table::Table;
+/// use machiavelli::bot::evaluate;
+///
+/// let (config, _) = parse_config("").unwrap();
+/// let run = Sequence::from_cards(&[RegularCard(Heart, 4), RegularCard(Heart, 5), RegularCard(Heart, 6)]);
+/// let pair = Sequence::from_cards(&[RegularCard(Spade, 12), RegularCard(Club, 12)]);
+/// let scattered = Sequence::from_cards(&[RegularCard(Spade, 12), RegularCard(Club, 2)]);
+///
+/// assert_eq!(1., evaluate(&run, &Table::new(), &config));
+/// assert_eq!(0.5, evaluate(&pair, &Table::new(), &config));
+/// assert_eq!(0., evaluate(&scattered, &Table::new(), &config));
+/// ```
+pub fn evaluate(hand: &Sequence, table: &Table, rules: &Config) -> f64 {
+    evaluation(hand, table, rules).score
+}
+
+/// Components of the score of a hand
+///
+/// The score is one minus the share of the points of the hand which the cards cost (see the
+/// module documentation); an empty hand scores 1.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::parse_config;
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::table::Table;
+/// use machiavelli::bot::evaluation;
+///
+/// let (config, _) = parse_config("").unwrap();
+/// let mut table = Table::new();
+/// table.add(Sequence::from_cards(&[RegularCard(Club, 7), RegularCard(Club, 8), RegularCard(Club, 9)]));
+/// let hand = Sequence::from_cards(&[RegularCard(Club, 10), RegularCard(Heart, 2), RegularCard(Heart, 4),
+///                                   RegularCard(Diamond, 13)]);
+///
+/// // the 10♣ joins the run of the table, the 2♥ and the 4♥ only need the 3♥
+/// let eval = evaluation(&hand, &table, &config);
+/// assert_eq!((0, 1, 2, 0), (eval.melded, eval.on_table, eval.near_melds, eval.free_jokers));
+/// assert_eq!(vec![RegularCard(Diamond, 13)], eval.deadwood);
+/// assert_eq!(10, eval.deadwood_points);
+/// ```
+pub fn evaluation(hand: &Sequence, table: &Table, rules: &Config) -> Evaluation {
+    let point_rules = rules.point_rules();
+    let total: f64 = hand.to_vec().iter().map(|card| point_rules.card_points(card) as f64).sum();
+
+    // sequences of the hand
+    let mut left = hand.clone();
+    let mut melded = 0;
+    while let Some(seq) = find_sequence(&left) {
+        for card in seq.to_vec() {
+            left.remove_card(&card);
+        }
+        melded += seq.number_cards();
+    }
+    let (jokers, cards): (Vec<Card>, Vec<Card>) = left.to_vec().into_iter().partition(|card| *card == Joker);
+
+    // cards joining a sequence of the table
+    let sequences: Vec<&Sequence> = (1..=table.number_sequences()).filter_map(|n| table.get(n)).collect();
+    let (on_table, cards): (Vec<Card>, Vec<Card>) = cards.into_iter().partition(|card| sequences.iter().any(|seq| {
+        let mut cards = (*seq).clone();
+        cards.add_card(card.clone());
+        arrange(&cards).is_some()
+    }));
+
+    // near-melds, paired greedily
+    let mut paired = vec![false; cards.len()];
+    for i in 0..cards.len() {
+        if paired[i] {
+            continue;
+        }
+        if let Some(j) = (i+1..cards.len()).find(|&j| !paired[j] && is_near_meld(&cards[i], &cards[j])) {
+            paired[i] = true;
+            paired[j] = true;
+        }
+    }
+    let near_melds: Vec<&Card> = cards.iter().zip(&paired).filter(|(_, &p)| p).map(|(card, _)| card).collect();
+    let deadwood: Vec<Card> = cards.iter().zip(&paired).filter(|(_, &p)| !p).map(|(card, _)| card.clone()).collect();
+
+    let points = |cards: &[&Card]| cards.iter().map(|card| point_rules.card_points(card) as f64).sum::<f64>();
+    let deadwood_points = deadwood.iter().map(|card| point_rules.card_points(card)).sum();
+    let cost = deadwood_points as f64
+        + NEAR_MELD_COST * points(&near_melds)
+        + FREE_JOKER_COST * points(&jokers.iter().collect::<Vec<&Card>>());
+    Evaluation {
+        melded,
+        on_table: on_table.len(),
+        near_melds: near_melds.len(),
+        free_jokers: jokers.len(),
+        deadwood,
+        deadwood_points,
+        score: if total == 0. { 1. } else { 1. - cost / total }
+    }
+}
+
+/// whether one more card could make a sequence of two cards
+fn is_near_meld(a: &Card, b: &Card) -> bool {
+    match (a, b) {
+        (RegularCard(suit_a, val_a), RegularCard(suit_b, val_b)) if suit_a == suit_b => {
+            // the ace is also next to the king
+            let gap = (*val_a as i8 - *val_b as i8).abs();
+            (1..=2).contains(&gap) || (val_a.min(val_b) == &1 && (1..=2).contains(&(13 - gap)))
+        },
+        (RegularCard(_, val_a), RegularCard(_, val_b)) => val_a == val_b,
+        _ => false
+    }
+}
+
+/// Play a turn in place of a player
+///
+/// The events are added to the log under the player's name.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::parse_config;
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::table::Table;
+/// use machiavelli::events::EventLog;
+/// use machiavelli::deck::Deck;
+/// use machiavelli::bot::play_turn;
+///
+/// let mut table = Table::new();
+/// let mut hand = Sequence::from_cards(&[
+///     RegularCard(Heart, 5),
+///     RegularCard(Club, 7),
+///     RegularCard(Heart, 6),
+///     RegularCard(Heart, 4),
+/// ]);
+/// let mut deck = Deck::from_cards(&[RegularCard(Spade, 1)]);
+/// let mut log = EventLog::new();
+/// let (config, _) = parse_config("").unwrap();
+///
+/// play_turn(&mut table, &mut hand, &mut deck, &config, "Alice", &mut log);
+///
+/// assert_eq!(Sequence::from_cards(&[RegularCard(Club, 7)]), hand);
+/// assert_eq!(1, deck.remaining());
+/// assert_eq!(1, table.number_sequences());
+/// ```
+pub fn play_turn(table: &mut Table, hand: &mut Sequence, deck: &mut Deck,
+                 config: &Config, player_name: &str, log: &mut EventLog) {
+
+    let custom_rule_jokers = config.custom_rule_jokers;
+
+    // nothing to search for if the bot can only draw
+    if Ongoing::start(table, hand).is_forced(table, hand, custom_rule_jokers) {
+        if pick_a_card(hand, deck).is_ok() {
+            log.push(Event::DrewCard(player_name.to_string()));
+        }
+        return;
+    }
+
+    let mut has_played = false;
+
+    // lay down the sequences found in the hand, one at a time
+    while let Some(seq) = find_sequence(hand) {
+        if !custom_rule_jokers && seq.contains_joker() && seq.number_cards() < hand.number_cards()
+            && keeps_jokers(table, hand, config, deck.remaining()) {
+            break;
+        }
+        for card in seq.to_vec() {
+            hand.remove_card(&card);
+        }
+        table.add(seq.clone());
+        log.push(Event::PlayedSequence(player_name.to_string(), seq));
+        has_played = true;
+    }
+
+    // jokers can't be kept with the custom rule
+    if custom_rule_jokers && hand.contains_joker() {
+        let mut jokers = Vec::<Card>::new();
+        while hand.remove_card(&Joker) {
+            jokers.push(Joker);
+        }
+        let seq = Sequence::from_cards(&jokers);
+        table.add(seq.clone());
+        log.push(Event::PlayedSequence(player_name.to_string(), seq));
+        has_played = true;
+    }
+
+    if has_played {
+        log.push(Event::EndedTurn(player_name.to_string()));
+    } else if pick_a_card(hand, deck).is_ok() {
+        log.push(Event::DrewCard(player_name.to_string()));
+    }
+}
+
+/// whether the next card is likely enough to complete a sequence without the jokers of the hand
+fn keeps_jokers(table: &Table, hand: &Sequence, config: &Config, deck_remaining: usize) -> bool {
+    // the jokers are left out of the hand, but still known not to be in the deck
+    let (jokers, regular_cards): (Vec<Card>, Vec<Card>) = hand.to_vec().into_iter().partition(|c| *c == Joker);
+    let odds = draw_odds(&Sequence::from_cards(&regular_cards), table, &jokers, config.n_decks, config.n_jokers,
+                         deck_remaining);
+    odds.probability >= KEEP_JOKERS_ODDS
+}
+
+/// Bot running as a separate program, reading the game from `reader` and writing to `writer`
+#[derive(Debug)]
+pub struct ExternalBot<R, W: Write> {
+    /// name given by the bot, if any
+    pub name: Option<String>,
+    reader: R,
+    writer: W,
+    /// process of the bot, if it was started by the game
+    child: Option<Child>
+}
+
+impl ExternalBot<BufReader<ChildStdout>, ChildStdin> {
+
+    /// Start the program of a bot with a command line, and greet it
+    pub fn spawn(command: &str) -> io::Result<Self> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+        let mut child = Command::new(program).args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let reader = BufReader::new(child.stdout.take().expect("the output of the bot is piped"));
+        let writer = child.stdin.take().expect("the input of the bot is piped");
+        let mut bot = ExternalBot::new(reader, writer)?;
+        bot.child = Some(child);
+        Ok(bot)
+    }
+}
+
+impl<R: BufRead, W: Write> ExternalBot<R, W> {
+
+    /// Greet a bot reading and writing through the given streams, and wait until it is ready
+    pub fn new(reader: R, writer: W) -> io::Result<Self> {
+        let mut bot = ExternalBot { name: None, reader, writer, child: None };
+        bot.send("mvi")?;
+        loop {
+            let line = bot.receive()?;
+            if line == "mviok" {
+                return Ok(bot);
+            }
+            if let Some(name) = line.strip_prefix("id name ") {
+                bot.name = Some(name.trim().to_string());
+            }
+        }
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()
+    }
+
+    /// next line sent by the bot
+    fn receive(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the bot stopped"));
+        }
+        Ok(line.trim().to_string())
+    }
+
+    /// Send the game as the current player sees it, and return the action chosen by the bot
+    pub fn best_move(&mut self, game: &ScriptedGame) -> io::Result<String> {
+        let player = game.current_player();
+        let state = snapshot(&game.table, &game.hands, &game.deck, player, Visibility::Player(player));
+        let taken: Vec<String> = game.cards_from_table().to_vec().iter().map(card_code).collect();
+        self.send(&format!("state {}", state.to_json()))?;
+        self.send(format!("taken {}", taken.join(" ")).trim_end())?;
+        self.send("go")?;
+        loop {
+            let line = self.receive()?;
+            if let Some(action) = line.strip_prefix("bestmove") {
+                return Ok(action.trim().to_string());
+            }
+        }
+    }
+
+    /// Let the bot play the turn of the current player
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::bot::ExternalBot;
+    /// use machiavelli::script::run;
+    ///
+    /// let mut game = run("n_cards_to_start = 4\ndeck AH 2H 3H * 4H 5H 6H 7H 8H\n").unwrap();
+    ///
+    /// // the bot first plays an invalid sequence, then all its cards
+    /// let answers = "id name Example\nmviok\nbestmove p 1 4\nbestmove p 1 2 3 4\nbestmove e\n";
+    /// let mut sent = Vec::<u8>::new();
+    /// let mut bot = ExternalBot::new(answers.as_bytes(), &mut sent).unwrap();
+    /// bot.play_turn(&mut game).unwrap();
+    ///
+    /// assert_eq!(Some("Example".to_string()), bot.name);
+    /// assert_eq!(1, game.n_turns);
+    /// assert_eq!(1, game.table.number_sequences());
+    /// drop(bot);
+    /// assert!(String::from_utf8(sent).unwrap().contains("\nerror "));
+    /// ```
+    pub fn play_turn(&mut self, game: &mut ScriptedGame) -> io::Result<()> {
+        let n_turns = game.n_turns;
+        let mut n_invalid = 0;
+        while game.outcome.is_none() && game.n_turns == n_turns {
+            let action = self.best_move(game)?;
+            if let Err(message) = game.play(&action) {
+                self.send(&format!("error {}", message))?;
+                n_invalid += 1;
+                if n_invalid >= MAX_INVALID_MOVES {
+                    return game.play("g").or_else(|_| game.play("e"))
+                        .map_err(|m| io::Error::new(io::ErrorKind::InvalidData, m));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R, W: Write> Drop for ExternalBot<R, W> {
+    fn drop(&mut self) {
+        writeln!(self.writer, "quit").and_then(|_| self.writer.flush()).unwrap_or(());
+        // a bot which does not stop by itself is not waited for
+        if let Some(child) = &mut self.child {
+            child.kill().unwrap_or(());
+            child.wait().map(|_| ()).unwrap_or(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn config(custom_rule_jokers: bool) -> Config {
+        let (mut config, _) = crate::parse_config("").unwrap();
+        config.custom_rule_jokers = custom_rule_jokers;
+        config
+    }
+
+    #[test]
+    fn evaluation_of_aces_and_jokers() {
+        let table = Table::new();
+        let config = config(false);
+        assert!(is_near_meld(&RegularCard(Spade, 1), &RegularCard(Spade, 13)));
+        assert!(is_near_meld(&RegularCard(Spade, 12), &RegularCard(Spade, 1)));
+        assert!(!is_near_meld(&RegularCard(Spade, 2), &RegularCard(Spade, 13)));
+        assert!(!is_near_meld(&RegularCard(Spade, 2), &RegularCard(Heart, 3)));
+
+        let eval = evaluation(&Sequence::from_cards(&[Joker]), &table, &config);
+        assert_eq!((1, 0.75), (eval.free_jokers, eval.score));
+        assert_eq!(1., evaluate(&Sequence::new(), &table, &config));
+        assert_eq!("Hand quality: [########--] 75%", eval.meter());
+    }
+
+    #[test]
+    fn bot_draws_if_nothing_to_play() {
+        let mut table = Table::new();
+        let mut hand = Sequence::from_cards(&[RegularCard(Heart, 5), RegularCard(Club, 9)]);
+        let mut deck = Deck::from_cards(&[RegularCard(Spade, 1)]);
+        let mut log = EventLog::new();
+        play_turn(&mut table, &mut hand, &mut deck, &config(false), "Bob", &mut log);
+        assert_eq!(3, hand.number_cards());
+        assert_eq!(0, deck.remaining());
+        assert_eq!(vec![&Event::DrewCard("Bob".to_string())], log.last(5));
+    }
+
+    #[test]
+    fn bot_plays_jokers_with_custom_rule() {
+        let mut table = Table::new();
+        let mut hand = Sequence::from_cards(&[Joker, RegularCard(Club, 9)]);
+        let mut deck = Deck::from_cards(&[RegularCard(Spade, 1)]);
+        let mut log = EventLog::new();
+        play_turn(&mut table, &mut hand, &mut deck, &config(true), "Bob", &mut log);
+        assert_eq!(Sequence::from_cards(&[RegularCard(Club, 9)]), hand);
+        assert_eq!(1, deck.remaining());
+    }
+
+    #[test]
+    fn bot_keeps_its_joker_if_it_will_likely_draw_a_useful_card() {
+
+        // all the diamonds and spades are on the table
+        let mut table = Table::new();
+        for suit in [Diamond, Spade].iter() {
+            table.add(Sequence::from_cards(&(1..=13).map(|v| RegularCard(*suit, v)).collect::<Vec<Card>>()));
+        }
+        let mut hand = Sequence::from_cards(&[
+            Joker,
+            RegularCard(Heart, 2), RegularCard(Heart, 4), RegularCard(Heart, 6), RegularCard(Heart, 8),
+            RegularCard(Club, 2), RegularCard(Club, 4), RegularCard(Club, 6), RegularCard(Club, 8)
+        ]);
+        let mut config = config(false);
+        config.n_decks = 1;
+        config.n_jokers = 1;
+        let mut log = EventLog::new();
+
+        // 6 of the 18 cards left would complete a sequence
+        let mut deck = Deck::from_cards(&[RegularCard(Heart, 10)]);
+        play_turn(&mut table, &mut hand, &mut deck, &config, "Bob", &mut log);
+        assert_eq!(10, hand.number_cards());
+        assert_eq!(2, table.number_sequences());
+
+        // with nothing to draw, the joker is played
+        play_turn(&mut table, &mut hand, &mut deck, &config, "Bob", &mut log);
+        assert_eq!(7, hand.number_cards());
+        assert!(!hand.contains_joker());
+    }
+
+    #[test]
+    fn bot_can_empty_its_hand() {
+        let mut table = Table::new();
+        let mut hand = Sequence::from_cards(&[
+            RegularCard(Club, 9),
+            RegularCard(Heart, 9),
+            RegularCard(Spade, 9),
+            RegularCard(Diamond, 2),
+            Joker,
+            RegularCard(Diamond, 4),
+        ]);
+        let mut deck = Deck::new();
+        let mut log = EventLog::new();
+        play_turn(&mut table, &mut hand, &mut deck, &config(false), "Bob", &mut log);
+        assert_eq!(0, hand.number_cards());
+        assert_eq!(2, table.number_sequences());
+    }
+}
@@ -0,0 +1,259 @@
+//! Rules check of a single sequence, with the reasons
+//!
+//! `machiavelli check "2♣ 3♣ 4♣"` tells whether a sequence can be laid on the table, and why:
+//! which kind of sequence it is, or what keeps it from being a set (cards of the same rank and
+//! different suits) or a run (consecutive cards of the same suit, where jokers fill the gaps).
+//! With `--decks N` and `--jokers N`, it also checks that the cards can be found in a game
+//! played with `N` decks and `N` jokers.
+//!
+//! Cards are written as their rank (`A`, `2` to `10`, `J`, `Q`, `K`, or the letters of French or
+//! Italian cards, such as `V`, `D`, `R`) followed by their suit, as a symbol (`♥`, `♦`, `♣`, `♠`) or
+//! an initial (`H`, `D`, `C`, `S`); jokers are written `*`, `★`, or `joker`.
+
+use std::collections::HashMap;
+use crate::sequence_cards::{ Card, Sequence, Suit };
+use crate::snapshot::parse_card_code;
+use crate::i18n::{ parse_rank, rank_symbol };
+use crate::settings::rank_locale;
+
+/// flag giving the number of decks of the game
+pub const DECKS_FLAG: &str = "--decks";
+
+/// flag giving the number of jokers of the game
+pub const JOKERS_FLAG: &str = "--jokers";
+
+/// Whether a sequence is valid, with the reasons
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Verdict {
+    pub valid: bool,
+    pub reasons: Vec<String>
+}
+
+impl Verdict {
+
+    /// Verdict on one line, followed by the reasons, one per line
+    pub fn render(&self) -> String {
+        let mut res = if self.valid { "Valid" } else { "Invalid" }.to_string();
+        for reason in &self.reasons {
+            res += &format!("\n  {}", reason);
+        }
+        res + "\n"
+    }
+}
+
+/// Read a sequence written with the cards separated by spaces or commas
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::check::parse_sequence;
+/// use machiavelli::sequence_cards::*;
+///
+/// assert_eq!(Ok(Sequence::from_cards(&[RegularCard(Club, 2), Joker, RegularCard(Heart, 10)])),
+///            parse_sequence("2♣, joker 10h"));
+/// assert_eq!(parse_sequence("J♠ Q♠ K♠"), parse_sequence("V♠ D♠ R♠"));
+/// assert_eq!(Err("unknown card `1♣`".to_string()), parse_sequence("1♣ 2♣"));
+/// ```
+pub fn parse_sequence(s: &str) -> Result<Sequence, String> {
+    let cards = s.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|w| !w.is_empty())
+        .map(|w| parse_card(w).ok_or_else(|| format!("unknown card `{}`", w)))
+        .collect::<Result<Vec<Card>, String>>()?;
+    Ok(Sequence::from_cards(&cards))
+}
+
+pub(crate) fn parse_card(word: &str) -> Option<Card> {
+    if word == "*" || word == "★" || word.eq_ignore_ascii_case("joker") {
+        return Some(Card::Joker);
+    }
+    let code = word.replace('♥', "H").replace('♦', "D").replace('♣', "C").replace('♠', "S");
+    let suit = code.chars().last()?;
+    let rank = parse_rank(&code[..code.len() - suit.len_utf8()])?;
+    match parse_card_code(&format!("2{}", suit))? {
+        Card::RegularCard(suit, _) => Some(Card::RegularCard(suit, rank)),
+        Card::Joker => None
+    }
+}
+
+/// Name of a card with the symbol of its suit
+pub(crate) fn name(card: &Card) -> String {
+    match card {
+        Card::Joker => "★".to_string(),
+        Card::RegularCard(suit, rank) => format!("{}{}", rank_name(*rank), suit_symbol(*suit))
+    }
+}
+
+pub(crate) fn rank_name(rank: u8) -> String {
+    rank_symbol(rank, rank_locale())
+}
+
+pub(crate) fn suit_symbol(suit: Suit) -> &'static str {
+    match suit {
+        Suit::Heart => "♥",
+        Suit::Diamond => "♦",
+        Suit::Club => "♣",
+        Suit::Spade => "♠"
+    }
+}
+
+/// Check a sequence, and that its cards exist in a game with `n_decks` decks and `n_jokers`
+/// jokers if these are given
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::check::{ check, parse_sequence };
+///
+/// let verdict = check(&parse_sequence("Q♠ * A♠").unwrap(), None);
+/// assert!(verdict.valid);
+/// assert_eq!(vec!["a run of ♠ from Q to A, with 1 joker".to_string()], verdict.reasons);
+///
+/// let verdict = check(&parse_sequence("7♥ 7♣ 8♣").unwrap(), None);
+/// assert!(!verdict.valid);
+/// assert_eq!(vec!["not a set: the ranks differ (7, 8)".to_string(),
+///                 "not a run: the suits differ (♥, ♣)".to_string()], verdict.reasons);
+///
+/// let verdict = check(&parse_sequence("7♥ 7♥ 7♥ 7♣").unwrap(), Some((2, 4)));
+/// assert_eq!("there are only 2 copies of 7♥ with 2 decks", verdict.reasons[0]);
+/// ```
+pub fn check(sequence: &Sequence, limits: Option<(u8, u8)>) -> Verdict {
+    let cards = sequence.to_vec();
+    let mut reasons = Vec::new();
+
+    // cards which can't all be in the game
+    if let Some((n_decks, n_jokers)) = limits {
+        let mut counts = Vec::<(Card, usize)>::new();
+        for card in &cards {
+            match counts.iter_mut().find(|(c, _)| c == card) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((card.clone(), 1))
+            }
+        }
+        for (card, n) in counts {
+            match card {
+                Card::Joker if n > n_jokers as usize =>
+                    reasons.push(format!("there are only {} jokers in the game", n_jokers)),
+                Card::RegularCard(_, _) if n > n_decks as usize =>
+                    reasons.push(match n_decks {
+                        1 => format!("there is only 1 copy of {} with 1 deck", name(&card)),
+                        n => format!("there are only {} copies of {} with {} decks", n, name(&card), n)
+                    }),
+                _ => ()
+            }
+        }
+    }
+    let exists = reasons.is_empty();
+    let valid = exists && sequence.clone().is_valid();
+
+    let regular: Vec<(Suit, u8)> = cards.iter().filter_map(|c| match c {
+        Card::RegularCard(suit, rank) => Some((*suit, *rank)),
+        Card::Joker => None
+    }).collect();
+    let n_jokers = cards.len() - regular.len();
+    let with_jokers = match n_jokers {
+        0 => String::new(),
+        1 => ", with 1 joker".to_string(),
+        n => format!(", with {} jokers", n)
+    };
+
+    if cards.is_empty() {
+        reasons.push("there is no card".to_string());
+    } else if regular.is_empty() {
+        reasons.push("only jokers, which can always be laid together".to_string());
+    } else if cards.len() < 3 {
+        reasons.push("a sequence needs at least 3 cards".to_string());
+    } else {
+        reasons.extend(set_reason(&regular, &with_jokers));
+        if !valid || !reasons.iter().any(|r| r.starts_with("a set")) {
+            reasons.extend(run_reason(&regular, n_jokers, &with_jokers));
+        }
+    }
+
+    // keep only what explains the verdict
+    if valid {
+        reasons.retain(|r| r.starts_with("a ") || r.starts_with("only"));
+    } else {
+        reasons.retain(|r| !r.starts_with("a ") || r.starts_with("a sequence"));
+    }
+    Verdict { valid, reasons }
+}
+
+/// why the cards are a set or not
+fn set_reason(regular: &[(Suit, u8)], with_jokers: &str) -> Option<String> {
+    let rank = regular[0].1;
+    let mut ranks: Vec<u8> = regular.iter().map(|&(_, r)| r).collect();
+    ranks.sort_unstable();
+    ranks.dedup();
+    if ranks.len() > 1 {
+        let ranks: Vec<String> = ranks.iter().map(|&r| rank_name(r)).collect();
+        return Some(format!("not a set: the ranks differ ({})", ranks.join(", ")));
+    }
+    let mut suits = HashMap::<Suit, usize>::new();
+    for &(suit, _) in regular {
+        *suits.entry(suit).or_insert(0) += 1;
+    }
+    if let Some(&(suit, _)) = regular.iter().find(|(s, _)| suits[s] > 1) {
+        return Some(format!("not a set: {} is there more than once", name(&Card::RegularCard(suit, rank))));
+    }
+    Some(format!("a set of {}{}", rank_name(rank), with_jokers))
+}
+
+/// why the cards are a run or not
+fn run_reason(regular: &[(Suit, u8)], n_jokers: usize, with_jokers: &str) -> Option<String> {
+    let mut suits: Vec<Suit> = Vec::new();
+    for &(suit, _) in regular {
+        if !suits.contains(&suit) {
+            suits.push(suit);
+        }
+    }
+    if suits.len() > 1 {
+        let suits: Vec<&str> = suits.iter().map(|&s| suit_symbol(s)).collect();
+        return Some(format!("not a run: the suits differ ({})", suits.join(", ")));
+    }
+    let suit = suits[0];
+
+    // aces count as 1 or as 14, whichever needs fewer jokers
+    let mut best: Option<(usize, u8, u8)> = None;
+    for ace in [1, 14].iter() {
+        let mut ranks: Vec<u8> = regular.iter().map(|&(_, r)| if r == 1 { *ace } else { r }).collect();
+        ranks.sort_unstable();
+        if let Some(pair) = ranks.windows(2).find(|w| w[0] == w[1]) {
+            return Some(format!("not a run: {} is there more than once", name(&Card::RegularCard(suit, pair[0]))));
+        }
+        let gaps: usize = ranks.windows(2).map(|w| (w[1] - w[0] - 1) as usize).sum();
+        if best.is_none_or(|(g, _, _)| gaps < g) {
+            best = Some((gaps, ranks[0], ranks[ranks.len() - 1]));
+        }
+    }
+    let (gaps, low, high) = best?;
+    if gaps > n_jokers {
+        Some(format!("not a run: the gaps between {} and {} need {} joker{}, and there {}", rank_name(low),
+                     rank_name(high), gaps, if gaps > 1 { "s" } else { "" },
+                     match n_jokers { 0 => "is none".to_string(), 1 => "is 1".to_string(), n => format!("are {}", n) }))
+    } else {
+        Some(format!("a run of {} from {} to {}{}", suit_symbol(suit), rank_name(low), rank_name(high), with_jokers))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn verdicts_agree_with_the_rules() {
+        for (cards, expected) in [
+            ("7♥ 7♣ ★", "a set of 7, with 1 joker"),
+            ("K♦ A♦ 2♦", "not a run: the gaps between A and K need 10 jokers, and there is none"),
+            ("3♠ ★ ★ 6♠", "a run of ♠ from 3 to 6, with 2 jokers"),
+            ("5♥ 5♥ 6♥", "not a run: 5♥ is there more than once"),
+            ("★ ★", "only jokers, which can always be laid together"),
+            ("9♣ 9♦", "a sequence needs at least 3 cards")
+        ].iter() {
+            let sequence = parse_sequence(cards).unwrap();
+            let verdict = check(&sequence, None);
+            assert_eq!(sequence.clone().is_valid(), verdict.valid, "{}", cards);
+            assert!(verdict.reasons.contains(&expected.to_string()), "{}: {:?}", cards, verdict.reasons);
+        }
+    }
+}
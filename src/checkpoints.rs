@@ -0,0 +1,134 @@
+//! Checkpoints of long games
+//!
+//! Every `checkpoint_every` turns of a round (see `ServerConfig`), the server copies the save
+//! written at the start of the turn to a checkpoint next to it, named after the round and the
+//! turn (`machiavelli_save_r2_t30.ckpt` for the start of the 31st turn of the second round).
+//! Only the last `n_checkpoints` are kept: older ones are deleted when a new one is written.
+//!
+//! When a rules dispute or a bug spoils a game, `server --rollback <turn>` loads the game from
+//! the last checkpoint at or before that turn of the last round which has one, or `--rollback
+//! <round>:<turn>` from another round, instead of from the save itself. The game is then saved
+//! as usual, and the checkpoints after it are replaced as it goes on again. During a game, the host
+//! can also rewind it to a checkpoint of the current round (see `HostAction::Rewind`).
+
+use std::io;
+use std::path::Path;
+
+/// flag giving the turn to roll back to when loading a game
+pub const ROLLBACK_FLAG: &str = "--rollback";
+
+/// extension of the checkpoint files
+pub const CHECKPOINT_EXTENSION: &str = ".ckpt";
+
+/// Checkpoint written at the start of a turn
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Checkpoint {
+    /// number of the round, from 1
+    pub round: u32,
+    /// number of turns played in the round
+    pub turn: u32,
+    pub path: String
+}
+
+/// Turn to roll back to, as given with `ROLLBACK_FLAG`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RollbackTarget {
+    /// round, by default the last one with a checkpoint
+    pub round: Option<u32>,
+    pub turn: u32
+}
+
+impl RollbackTarget {
+
+    /// Read a target written as `<turn>` or `<round>:<turn>`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::checkpoints::RollbackTarget;
+    ///
+    /// assert_eq!(Some(RollbackTarget { round: None, turn: 12 }), RollbackTarget::parse("12"));
+    /// assert_eq!(Some(RollbackTarget { round: Some(2), turn: 0 }), RollbackTarget::parse("2:0"));
+    /// assert_eq!(None, RollbackTarget::parse("last"));
+    /// ```
+    pub fn parse(s: &str) -> Option<RollbackTarget> {
+        match s.trim().split_once(':') {
+            Some((round, turn)) => Some(RollbackTarget { round: Some(round.parse().ok()?), turn: turn.parse().ok()? }),
+            None => Some(RollbackTarget { round: None, turn: s.trim().parse().ok()? })
+        }
+    }
+}
+
+/// Path of the checkpoint of a turn, for a save file without its extension
+pub fn checkpoint_name(savefile: &str, round: u32, turn: u32) -> String {
+    format!("{}_r{}_t{}{}", savefile, round, turn, CHECKPOINT_EXTENSION)
+}
+
+/// Whether a checkpoint is due at the start of a turn, after `turn` turns of the round
+pub fn is_due(turn: u32, every: u32) -> bool {
+    every > 0 && turn.is_multiple_of(every)
+}
+
+/// Checkpoints of a save file (without its extension), from the oldest
+pub fn list(savefile: &str) -> Vec<Checkpoint> {
+    let path = Path::new(savefile);
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new(".")
+    };
+    let prefix = format!("{}_r", path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default());
+    let mut res: Vec<Checkpoint> = std::fs::read_dir(dir).into_iter().flatten().flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let (round, turn) = name.strip_prefix(&prefix)?.strip_suffix(CHECKPOINT_EXTENSION)?.split_once("_t")?;
+            Some(Checkpoint {
+                round: round.parse().ok()?,
+                turn: turn.parse().ok()?,
+                path: dir.join(&name).to_string_lossy().to_string()
+            })
+        })
+        .collect();
+    res.sort_by_key(|c| (c.round, c.turn));
+    res
+}
+
+/// Write the checkpoint of a turn, and delete the oldest ones so that `n_kept` are left
+pub fn write(savefile: &str, round: u32, turn: u32, bytes: &[u8], n_kept: usize) -> io::Result<()> {
+    crate::write_atomically(checkpoint_name(savefile, round, turn), bytes)?;
+    let checkpoints = list(savefile);
+    for old in &checkpoints[..checkpoints.len().saturating_sub(n_kept)] {
+        std::fs::remove_file(&old.path)?;
+    }
+    Ok(())
+}
+
+/// Last checkpoint at or before a turn
+pub fn find(checkpoints: &[Checkpoint], target: RollbackTarget) -> Option<&Checkpoint> {
+    let round = target.round.or_else(|| checkpoints.last().map(|c| c.round))?;
+    checkpoints.iter().rev().find(|c| c.round == round && c.turn <= target.turn)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn only_the_last_checkpoints_are_kept() {
+        let dir = std::env::temp_dir().join(format!("machiavelli_checkpoints_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let savefile = dir.join("game").to_string_lossy().to_string();
+        for (round, turn) in [(1, 0), (1, 10), (1, 20), (2, 0), (2, 10)] {
+            write(&savefile, round, turn, &[round as u8, turn as u8], 3).unwrap();
+        }
+        let checkpoints = list(&savefile);
+        std::fs::remove_dir_all(&dir).unwrap_or(());
+        assert_eq!(vec![(1, 20), (2, 0), (2, 10)], checkpoints.iter().map(|c| (c.round, c.turn)).collect::<Vec<_>>());
+        assert_eq!(checkpoint_name(&savefile, 2, 0), checkpoints[1].path);
+
+        let target = |s| RollbackTarget::parse(s).unwrap();
+        assert_eq!(Some(&checkpoints[2]), find(&checkpoints, target("15")));
+        assert_eq!(Some(&checkpoints[0]), find(&checkpoints, target("1:25")));
+        assert_eq!(None, find(&checkpoints, target("1:5")));
+    }
+}
@@ -0,0 +1,159 @@
+//! Time taken by the players to play their turns
+//!
+//! The clock measures each turn, and adds it to the thinking time of the player. The times are
+//! kept in the saves, with the time already spent on a turn saved while in progress, so that
+//! neither the totals nor the turn timer start again from zero when the game is loaded.
+
+use std::time::{ Duration, Instant };
+
+/// Time taken by a player over the game
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct PlayerTime {
+    /// time spent on all the turns
+    pub total: Duration,
+    pub n_turns: u32,
+    /// time spent on the longest turn
+    pub longest: Duration
+}
+
+/// Measures the turns of a game
+#[derive(Debug, Clone)]
+pub struct Clock {
+    times: Vec<PlayerTime>,
+    /// player whose turn is being measured, and when it started
+    current: Option<(usize, Instant)>,
+    /// time spent on the current turn before the game was saved
+    carried: Duration
+}
+
+impl Clock {
+
+    /// Clock for a new game with `n_players` players
+    pub fn new(n_players: usize) -> Clock {
+        Clock::resume(vec![PlayerTime::default(); n_players], Duration::from_secs(0))
+    }
+
+    /// Clock for a loaded game, with the times of the players and the time already spent on the
+    /// turn it resumes
+    pub fn resume(times: Vec<PlayerTime>, turn_elapsed: Duration) -> Clock {
+        Clock { times, current: None, carried: turn_elapsed }
+    }
+
+    /// Start measuring the turn of `player`
+    pub fn start_turn(&mut self, player: usize) {
+        self.current = Some((player, Instant::now()));
+    }
+
+    /// Time spent on the current turn, including that before the game was saved
+    pub fn elapsed(&self) -> Duration {
+        self.carried + self.current.map(|(_, start)| start.elapsed()).unwrap_or_default()
+    }
+
+    /// Time left on the current turn with the time limit `limit`, if there is one
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use machiavelli::clock::{ Clock, PlayerTime };
+    ///
+    /// let clock = Clock::resume(vec![PlayerTime::default(); 2], Duration::from_secs(50));
+    ///
+    /// assert_eq!(Some(Duration::from_secs(10)), clock.remaining(Some(Duration::from_secs(60))));
+    /// assert_eq!(Some(Duration::from_secs(0)), clock.remaining(Some(Duration::from_secs(30))));
+    /// assert_eq!(None, clock.remaining(None));
+    /// ```
+    pub fn remaining(&self, limit: Option<Duration>) -> Option<Duration> {
+        limit.map(|limit| limit.checked_sub(self.elapsed()).unwrap_or_default())
+    }
+
+    /// Stop measuring the current turn, add it to the time of its player, and return how long it
+    /// took
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use machiavelli::clock::{ Clock, PlayerTime };
+    ///
+    /// // a game saved after 20 seconds of the turn of the second player
+    /// let mut clock = Clock::resume(vec![PlayerTime::default(); 2], Duration::from_secs(20));
+    /// clock.start_turn(1);
+    /// let turn = clock.end_turn();
+    ///
+    /// assert!(turn >= Duration::from_secs(20));
+    /// assert_eq!(1, clock.times()[1].n_turns);
+    /// assert_eq!(turn, clock.times()[1].longest);
+    /// assert_eq!(PlayerTime::default(), clock.times()[0]);
+    /// ```
+    pub fn end_turn(&mut self) -> Duration {
+        let elapsed = self.elapsed();
+        if let Some(time) = self.current.take().and_then(|(player, _)| self.times.get_mut(player)) {
+            time.total += elapsed;
+            time.n_turns += 1;
+            time.longest = time.longest.max(elapsed);
+        }
+        self.carried = Duration::from_secs(0);
+        elapsed
+    }
+
+    /// Time taken by each player, in the order of their seats
+    pub fn times(&self) -> &[PlayerTime] {
+        &self.times
+    }
+
+    /// Thinking time of each player, one line per player
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use machiavelli::clock::{ Clock, PlayerTime };
+    ///
+    /// let times = vec![
+    ///     PlayerTime { total: Duration::from_secs(192), n_turns: 8, longest: Duration::from_secs(45) },
+    ///     PlayerTime::default()
+    /// ];
+    /// let clock = Clock::resume(times, Duration::from_secs(0));
+    ///
+    /// assert_eq!("Thinking time:\n  Alice: 3m 12s over 8 turns (24s per turn, longest 45s)\n  Bob: 0s\n",
+    ///            clock.summary(&["Alice".to_string(), "Bob".to_string()]));
+    /// ```
+    pub fn summary(&self, names: &[String]) -> String {
+        let mut summary = "Thinking time:\n".to_string();
+        for (name, time) in names.iter().zip(&self.times) {
+            if time.n_turns == 0 {
+                summary += &format!("  {}: {}\n", name, format_duration(time.total));
+            } else {
+                summary += &format!("  {}: {} over {} turn{} ({} per turn, longest {})\n", name,
+                                    format_duration(time.total), time.n_turns,
+                                    if time.n_turns > 1 { "s" } else { "" },
+                                    format_duration(time.total / time.n_turns), format_duration(time.longest));
+            }
+        }
+        summary
+    }
+}
+
+/// Duration in hours, minutes, and seconds, leaving out the units which are zero at the start
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use machiavelli::clock::format_duration;
+///
+/// assert_eq!("45s", format_duration(Duration::from_millis(45_900)));
+/// assert_eq!("3m 02s", format_duration(Duration::from_secs(182)));
+/// assert_eq!("1h 00m 05s", format_duration(Duration::from_secs(3605)));
+/// ```
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!("{}h {:02}m {:02}s", seconds / 3600, seconds % 3600 / 60, seconds % 60)
+    } else if seconds >= 60 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
@@ -0,0 +1,101 @@
+//! Colour of each player
+//!
+//! Each player gets a colour from their seat, used for their name wherever it is shown: the
+//! events, the private messages, the status bar, the turn order, the end of the round, and the
+//! lines at the top of the screen. The colours are mid-tones of the 256-colour palette, which can
+//! be read with the light and the dark themes. With more than `N_COLOURS` players, the colours
+//! are used again, underlined. Names are not coloured when the `NO_COLOR` environment variable is
+//! set.
+//!
+//! As for the settings, the names of the players are registered once in a process, so that the
+//! events can be rendered without knowing the seats.
+
+use std::sync::Mutex;
+use crate::settings::{ current_theme, Theme };
+
+/// codes of the colours of the players, from the first seat
+const COLOURS: [&str; 8] = ["38;5;33", "38;5;170", "38;5;34", "38;5;37", "38;5;208", "38;5;135", "38;5;160",
+                            "38;5;136"];
+
+/// number of players with a colour of their own
+pub const N_COLOURS: usize = COLOURS.len();
+
+/// names of the players of the game played in this process, in the order of the seats
+static NAMES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Give the players of the game played in this process their colours
+pub fn register(names: &[String]) {
+    if let Ok(mut registered) = NAMES.lock() {
+        *registered = names.to_vec();
+    }
+}
+
+/// Whether names are left uncoloured, as asked with the `NO_COLOR` environment variable
+pub fn no_colour() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+/// Escape sequence starting the colour of a seat
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::colours::*;
+///
+/// assert_eq!("\x1b[38;5;33m", seat_colour(0));
+/// assert_eq!("\x1b[4;38;5;33m", seat_colour(N_COLOURS));
+/// ```
+pub fn seat_colour(seat: usize) -> String {
+    let underline = if (seat / N_COLOURS) % 2 == 1 { "4;" } else { "" };
+    format!("\x1b[{}{}m", underline, COLOURS[seat % N_COLOURS])
+}
+
+/// Escape sequence ending a coloured name, back to the text colour of a theme
+pub fn name_end(theme: Theme) -> String {
+    format!("\x1b[{};24m", theme.black_cards())
+}
+
+/// Name of a player in their colour, or as it is if they are not registered or names are not
+/// coloured
+pub fn paint(name: &str) -> String {
+    let seat = NAMES.lock().ok().and_then(|names| names.iter().position(|n| n == name));
+    match seat {
+        Some(seat) if !no_colour() => format!("{}{}{}", seat_colour(seat), name, name_end(current_theme())),
+        _ => name.to_string()
+    }
+}
+
+/// Remove the colours of the names from a text rendered with the light theme
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::colours::*;
+/// use machiavelli::settings::Theme;
+///
+/// let text = format!("{}Alice{} drew a card", seat_colour(9), name_end(Theme::Light));
+/// assert_eq!("Alice drew a card", strip(&text));
+/// ```
+pub fn strip(s: &str) -> String {
+    let mut res = s.replace(&name_end(Theme::Light), "");
+    for seat in 0..(2 * N_COLOURS) {
+        res = res.replace(&seat_colour(seat), "");
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn names_in_their_colours() {
+        register(&["Colour test A".to_string(), "Colour test B".to_string()]);
+        if !no_colour() {
+            assert_eq!(format!("\x1b[38;5;170mColour test B{}", name_end(current_theme())), paint("Colour test B"));
+        }
+        assert_eq!("Colour test C", paint("Colour test C"));
+        assert_eq!("Colour test B drew a card", strip(&format!("{} drew a card", paint("Colour test B"))));
+    }
+}
@@ -0,0 +1,377 @@
+//! Whole turns typed on one line
+//!
+//! Instead of one action at a time, a player can type a list of actions separated by `;`, such as
+//! `take 3; play 1 2 5; extend 4 with 7♦; end`. Each action is played on the situation left by
+//! the previous ones, and the list is played as a whole: if one of the actions can not be played,
+//! none is, and the player is told which one failed and why. The same lists are read by the local
+//! game, by the server, and in the scripts (see the `script` module). The actions are:
+//!
+//! * `take x y ...` (or `t`) takes the sequences x, y, ... from the table
+//! * `play x y ...` (or `p`) lays down the cards x, y, ...
+//! * `extend x with y z ...` (or `a`, `x`) adds the cards y, z, ... after those of sequence x, and
+//!   `extend x before y z ...` before them
+//! * `split x y` (or `d`) splits sequence x after its first y cards
+//! * `sort rank` and `sort suit` (or `r` and `s`) sort the hand
+//! * `end` (or `e`), last, ends the turn, picking a card if nothing was played
+//!
+//! Cards are given by their numbers, those taken from the table being numbered after the hand, or
+//! by their names (`7♦`, `10h`, `joker`, as in the `check` subcommand), which unlike the numbers
+//! do not change as the cards are played.
+//!
+//! Lists used often can be named in the `[macros]` section of the settings (see `Settings`): the
+//! name of a macro then stands for its actions, alone or as one of the actions of a list.
+
+use crate::check::parse_card;
+use crate::deck::Deck;
+use crate::events::Event;
+use crate::selection::{ self, PlayPreview, SelectionError };
+use crate::sequence_cards::{ Card, Sequence };
+use crate::table::Table;
+use crate::turn::{ Ongoing, Position, TurnEnd };
+
+/// Card of an action, by its number or by its name
+#[derive(Debug, PartialEq, Clone)]
+pub enum CardRef {
+    Index(usize),
+    Card(Card)
+}
+
+/// Action of a list
+#[derive(Debug, PartialEq, Clone)]
+pub enum Command {
+    Take(Vec<usize>),
+    Play(Vec<CardRef>),
+    Extend(usize, Position, Vec<CardRef>),
+    Split(usize, usize),
+    SortByRank,
+    SortBySuit,
+    End
+}
+
+/// Action of a list, with the text it was read from
+#[derive(Debug, PartialEq, Clone)]
+pub struct Step {
+    pub text: String,
+    pub command: Command
+}
+
+/// Whether a line is a list of actions rather than a single command of the game: it has several
+/// actions, or starts with the full name of one
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::commands::is_list;
+///
+/// assert!(is_list("t 3; p 1 2 5"));
+/// assert!(is_list("play 1 2 5"));
+/// assert!(!is_list("p"));
+/// ```
+pub fn is_list(line: &str) -> bool {
+    line.contains(';') || matches!(line.split_whitespace().next(),
+                                   Some("take" | "play" | "extend" | "split" | "sort" | "end"))
+}
+
+/// Replace the actions of a line which are the names of macros by the actions of these macros,
+/// given as pairs `(name, actions)`
+///
+/// Macros are not expanded in the actions of other macros. A line without macros is returned as
+/// it was.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::commands::expand_macros;
+///
+/// let macros = vec![("tidy".to_string(), "sort suit".to_string())];
+/// assert_eq!("take 2; sort suit; end", expand_macros("take 2; tidy; end", &macros));
+/// assert_eq!("p 1 2 3", expand_macros("p 1 2 3", &macros));
+/// ```
+pub fn expand_macros(line: &str, macros: &[(String, String)]) -> String {
+    let parts: Vec<&str> = line.split(';').map(str::trim).collect();
+    if !parts.iter().any(|part| macros.iter().any(|(name, _)| name == part)) {
+        return line.to_string();
+    }
+    parts.iter()
+        .map(|&part| macros.iter().find(|(name, _)| name == part).map_or(part, |(_, actions)| actions.as_str()))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("; ")
+}
+
+/// Read a list of actions separated by `;`
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::commands::*;
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::turn::Position;
+///
+/// let steps = parse("take 3; play 1 2 5; extend 4 with 7♦; end").unwrap();
+/// assert_eq!(vec![Command::Take(vec![3]),
+///                 Command::Play(vec![CardRef::Index(1), CardRef::Index(2), CardRef::Index(5)]),
+///                 Command::Extend(4, Position::End, vec![CardRef::Card(RegularCard(Diamond, 7))]),
+///                 Command::End],
+///            steps.into_iter().map(|s| s.command).collect::<Vec<Command>>());
+/// assert_eq!(Err("`end` must be the last action".to_string()), parse("end; t 1"));
+/// ```
+pub fn parse(line: &str) -> Result<Vec<Step>, String> {
+    let texts: Vec<&str> = line.split(';').map(str::trim).filter(|t| !t.is_empty()).collect();
+    let mut steps = Vec::<Step>::new();
+    for text in &texts {
+        let command = parse_command(text).map_err(|m| format!("`{}`: {}", text, m))?;
+        if command == Command::End && steps.len() + 1 < texts.len() {
+            return Err("`end` must be the last action".to_string());
+        }
+        steps.push(Step { text: text.to_string(), command });
+    }
+    if steps.is_empty() {
+        return Err("no action given".to_string());
+    }
+    Ok(steps)
+}
+
+fn parse_command(text: &str) -> Result<Command, String> {
+    let mut words = text.split_whitespace();
+    let name = words.next().unwrap_or("");
+    let args: Vec<&str> = words.collect();
+    let number = |word: &str| word.parse::<usize>().map_err(|_| format!("`{}` is not a number", word));
+    match (name, &args[..]) {
+        ("take" | "t", []) => Err("no sequence given".to_string()),
+        ("take" | "t", args) => Ok(Command::Take(args.iter().map(|w| number(w)).collect::<Result<_, _>>()?)),
+        ("play" | "p", args) => Ok(Command::Play(parse_cards(args)?)),
+        ("extend" | "a" | "x", [n, "with" | ">", cards @ ..]) => Ok(Command::Extend(number(n)?, Position::End, parse_cards(cards)?)),
+        ("extend" | "a" | "x", [n, "before" | "<", cards @ ..]) => Ok(Command::Extend(number(n)?, Position::Start, parse_cards(cards)?)),
+        ("extend" | "a" | "x", [n, cards @ ..]) => Ok(Command::Extend(number(n)?, Position::End, parse_cards(cards)?)),
+        ("split" | "d", [n, index]) => Ok(Command::Split(number(n)?, number(index)?)),
+        ("sort", ["rank"]) | ("r", []) => Ok(Command::SortByRank),
+        ("sort", ["suit"]) | ("s", []) => Ok(Command::SortBySuit),
+        ("end" | "e", []) => Ok(Command::End),
+        ("extend" | "a" | "x" | "split" | "d" | "sort" | "r" | "s" | "end" | "e", _) =>
+            Err("wrong arguments".to_string()),
+        (name, _) => Err(format!("unknown action `{}`", name))
+    }
+}
+
+fn parse_cards(words: &[&str]) -> Result<Vec<CardRef>, String> {
+    if words.is_empty() {
+        return Err(SelectionError::Empty.to_string());
+    }
+    words.iter()
+        .map(|word| match word.parse::<usize>() {
+            Ok(i) => Ok(CardRef::Index(i)),
+            Err(_) => parse_card(word).map(CardRef::Card).ok_or_else(|| format!("unknown card `{}`", word))
+        })
+        .collect()
+}
+
+// numbers of the cards of an action among the cards of the hand and those taken from the table;
+// a card given by its name is the first one with that name which was not given before
+fn resolve(refs: &[CardRef], cards: &[Card]) -> Result<Vec<usize>, String> {
+    let mut res = Vec::<usize>::new();
+    for card_ref in refs {
+        let i = match card_ref {
+            CardRef::Index(i) if *i == 0 || *i > cards.len() => return Err(SelectionError::OutOfRange(*i).to_string()),
+            CardRef::Index(i) if res.contains(i) => return Err(SelectionError::Repeated(*i).to_string()),
+            CardRef::Index(i) => *i,
+            CardRef::Card(card) => (1..=cards.len())
+                .find(|i| cards[i - 1] == *card && !res.contains(i))
+                .ok_or_else(|| format!("no {}{} left", card, crate::reset_style_string()))?
+        };
+        res.push(i);
+    }
+    Ok(res)
+}
+
+/// What a player's turn holds, changed by the actions
+pub struct TurnCards<'a> {
+    pub table: &'a mut Table,
+    pub hand: &'a mut Sequence,
+    /// cards taken from the table and not played yet, kept apart from the hand
+    pub cards_from_table: &'a mut Sequence,
+    pub deck: &'a mut Deck
+}
+
+/// State of the turn after a list of actions
+#[derive(Debug, PartialEq, Clone)]
+pub enum Executed {
+    Ongoing(Ongoing),
+    Ended(TurnEnd)
+}
+
+/// Play a list of actions of the player `name`, and return the public events they made
+///
+/// If an action can not be played, the table, the hand, and the cards taken from the table are
+/// put back as they were before the list, and the turn is given back with the reason.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::commands::*;
+/// use machiavelli::deck::Deck;
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::table::Table;
+/// use machiavelli::turn::{ Ongoing, TurnEnd };
+///
+/// let mut table = Table::new();
+/// table.add(Sequence::from_cards(&[RegularCard(Spade, 3), RegularCard(Spade, 4), RegularCard(Spade, 5)]));
+/// let mut hand = Sequence::from_cards(&[RegularCard(Spade, 6), RegularCard(Heart, 9)]);
+/// let (mut cards_from_table, mut deck) = (Sequence::new(), Deck::new());
+/// let turn = Ongoing::start(&table, &hand);
+/// let cards = TurnCards { table: &mut table, hand: &mut hand, cards_from_table: &mut cards_from_table,
+///                         deck: &mut deck };
+///
+/// let (executed, events) = execute(&parse("extend 1 with 6♠; end").unwrap(), turn, "Alice", cards, false).unwrap();
+/// assert_eq!(Executed::Ended(TurnEnd::Passed), executed);
+/// assert_eq!(2, events.len());
+/// assert_eq!(vec![RegularCard(Heart, 9)], hand.to_vec());
+/// ```
+pub fn execute(steps: &[Step], turn: Ongoing, name: &str, mut cards: TurnCards, custom_rule_jokers: bool)
+    -> Result<(Executed, Vec<Event>), (Ongoing, String)>
+{
+    let original = turn.clone();
+    let before = (cards.table.clone(), cards.hand.clone(), cards.cards_from_table.clone());
+    let mut events = Vec::<Event>::new();
+    let mut turn = turn;
+    for (i, step) in steps.iter().enumerate() {
+        let played = match step.command {
+            Command::End => match end_turn(turn.clone(), &mut cards, custom_rule_jokers) {
+                Ok(end) => {
+                    events.push(match end {
+                        TurnEnd::Drew(_) => Event::DrewCard(name.to_string()),
+                        _ => Event::EndedTurn(name.to_string())
+                    });
+                    return Ok((Executed::Ended(end), events));
+                },
+                Err(message) => Err(message)
+            },
+            _ => play_command(&step.command, name, &mut cards, &mut events)
+        };
+        if let Err(message) = played {
+            *cards.table = before.0;
+            *cards.hand = before.1;
+            *cards.cards_from_table = before.2;
+            return Err((original, format!("Nothing was played: action {} (`{}`): {}", i + 1, step.text, message)));
+        }
+        if !matches!(step.command, Command::SortByRank | Command::SortBySuit) {
+            turn = turn.meld();
+        }
+    }
+    Ok((Executed::Ongoing(turn), events))
+}
+
+// pick a card if nothing was played, and pass otherwise
+fn end_turn(turn: Ongoing, cards: &mut TurnCards, custom_rule_jokers: bool) -> Result<TurnEnd, String> {
+    match turn {
+        Ongoing::Draw(draw) => draw.draw(cards.hand, cards.deck, custom_rule_jokers)
+            .map(|end| end.outcome().clone())
+            .map_err(|(_, m)| m),
+        Ongoing::Meld(meld) => meld.pass(cards.hand, cards.cards_from_table, custom_rule_jokers)
+            .map(|end| end.outcome().clone())
+            .map_err(|(_, m)| m)
+    }
+}
+
+// play an action other than the end of the turn
+fn play_command(command: &Command, name: &str, cards: &mut TurnCards, events: &mut Vec<Event>) -> Result<(), String> {
+    let mut all = cards.hand.to_vec();
+    all.extend(cards.cards_from_table.to_vec());
+    match command {
+        Command::Take(sequences) => {
+            let mut sorted = sequences.clone();
+            sorted.sort_unstable();
+            sorted.dedup();
+            if let Some(&n) = sorted.iter().find(|&&n| n == 0 || n > cards.table.number_sequences()) {
+                return Err(SelectionError::NoSequence(n).to_string());
+            }
+            let mut taken = Vec::<Event>::new();
+            for &n in sorted.iter().rev() {
+                if let Some(seq) = cards.table.take(n) {
+                    taken.push(Event::TookSequence(name.to_string(), n, seq.clone()));
+                    cards.cards_from_table.merge(seq.reverse());
+                }
+            }
+            events.extend(taken.into_iter().rev());
+        },
+        Command::Play(refs) => {
+            let indices = resolve(refs, &all)?;
+            let seq = selection::take_cards(&mut [&mut *cards.hand, &mut *cards.cards_from_table], &indices);
+            let preview = PlayPreview::new(seq, None);
+            preview.validity.clone()?;
+            cards.table.add(preview.sequence.clone());
+            events.push(Event::PlayedSequence(name.to_string(), preview.sequence));
+        },
+        Command::Extend(n, position, refs) => {
+            let target = cards.table.get(*n).cloned().ok_or_else(|| SelectionError::NoSequence(*n).to_string())?;
+            let indices = resolve(refs, &all)?;
+            let added = selection::take_cards(&mut [&mut *cards.hand, &mut *cards.cards_from_table], &indices);
+            let preview = PlayPreview::extension(&target, *n, &added, *position);
+            preview.validity.clone()?;
+            cards.table.take(*n);
+            cards.table.add(preview.sequence);
+            events.push(Event::AddedToSequence(name.to_string(), *n, added));
+        },
+        Command::Split(n, index) => {
+            let taken = cards.table.split(*n, *index)
+                .ok_or_else(|| format!("sequence {} can not be split after its first {} cards", n, index))?;
+            events.push(Event::SplitSequence(name.to_string(), *n, *index, taken.clone()));
+            cards.cards_from_table.merge(taken.reverse());
+        },
+        Command::SortByRank => {
+            cards.hand.sort_by_rank();
+            cards.cards_from_table.sort_by_rank();
+        },
+        Command::SortBySuit => {
+            cards.hand.sort_by_suit();
+            cards.cards_from_table.sort_by_suit();
+        },
+        Command::End => ()
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sequence_cards::Card::*;
+    use crate::sequence_cards::Suit::*;
+
+    #[test]
+    fn nothing_is_played_if_an_action_fails() {
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5), RegularCard(Club, 6)]));
+        let mut hand = Sequence::from_cards(&[RegularCard(Club, 3), RegularCard(Heart, 9), RegularCard(Club, 7)]);
+        let (mut cards_from_table, mut deck) = (Sequence::new(), Deck::new());
+        let (table_start, hand_start) = (table.clone(), hand.clone());
+        let turn = Ongoing::start(&table, &hand);
+        let cards = TurnCards { table: &mut table, hand: &mut hand, cards_from_table: &mut cards_from_table,
+                                deck: &mut deck };
+
+        // the 3♣ is played with the sequence taken, so that the 9♥ is then the first card
+        let steps = parse("take 1; play 3♣ 4 5 6; play 1 2").unwrap();
+        let (turn, message) = execute(&steps, turn, "Alice", cards, false).unwrap_err();
+        assert_eq!(Ongoing::start(&table_start, &hand_start), turn);
+        assert!(message.starts_with("Nothing was played: action 3 (`play 1 2`): "));
+        assert_eq!((table_start, hand_start), (table.clone(), hand.clone()));
+        assert_eq!(0, cards_from_table.number_cards());
+
+        let cards = TurnCards { table: &mut table, hand: &mut hand, cards_from_table: &mut cards_from_table,
+                                deck: &mut deck };
+        let (executed, events) = execute(&parse("take 1; play 3♣ 4 5 6 7♣").unwrap(), turn, "Alice", cards, false).unwrap();
+        assert!(matches!(executed, Executed::Ongoing(Ongoing::Meld(_))));
+        assert_eq!(2, events.len());
+        assert_eq!(vec![RegularCard(Heart, 9)], hand.to_vec());
+    }
+
+    #[test]
+    fn actions_are_read_with_their_arguments() {
+        assert_eq!(Ok(vec![Command::Split(2, 3), Command::SortBySuit, Command::Extend(1, Position::Start, vec![CardRef::Card(Joker)])]),
+                   parse("d 2 3;; sort suit; x 1 before joker").map(|steps| steps.into_iter().map(|s| s.command).collect()));
+        assert_eq!(Err("`split 2`: wrong arguments".to_string()), parse("split 2"));
+        assert_eq!(Err("`take 1 q`: `q` is not a number".to_string()), parse("take 1 q"));
+        assert_eq!(Err("`draw`: unknown action `draw`".to_string()), parse("draw"));
+        assert_eq!(Err("no action given".to_string()), parse(" ; "));
+    }
+}
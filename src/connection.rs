@@ -0,0 +1,933 @@
+//! Connections between the server and the clients
+//!
+//! Messages are sent with their length on four bytes (big-endian), followed by the bytes
+//! themselves, which are read in chunks of at most `BUFFER_SIZE` bytes, so that messages of
+//! any size can be sent. The receiver confirms that it got the message by sending back a single
+//! byte. Besides TCP streams, connections can go through memory channels, which lets a whole
+//! game be played in tests without opening any socket.
+//!
+//! The server wraps the TCP streams of the players in `WorkerConnection`s: a thread writes
+//! the messages and another one reads the replies, so that the game goes on while a slow client
+//! catches up, and confirmations are only read when the next reply is needed.
+//!
+//! If the server and the clients share a passphrase, connections can be wrapped in an
+//! `EncryptedConnection`, which encrypts each write with XChaCha20-Poly1305. The key of each
+//! connection is derived from the passphrase and a random salt, which the server sends in clear
+//! when the connection opens (see `handshake`).
+
+use std::convert::TryFrom;
+use std::io::{ Error, ErrorKind, Read, Write };
+use std::net::{ Shutdown, TcpListener, TcpStream };
+use std::sync::Mutex;
+use std::sync::mpsc::{ channel, sync_channel, Receiver, RecvTimeoutError, Sender, SyncSender, TryRecvError, TrySendError };
+use std::thread;
+use std::time::Duration;
+use rand::Rng;
+use crate::encode::{ encrypt, decrypt, key_from_password, KEY_SIZE, NONCE_SIZE, SALT_SIZE, TAG_SIZE };
+
+/// default size of the chunks in which messages are read
+pub const BUFFER_SIZE: usize = 1024;
+
+/// Connection able to send and receive messages prefixed by their length
+pub trait Connection: Read + Write {
+
+    /// Write a message with its length, without waiting for the receiver to confirm it got it
+    fn write_frames(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        let length = u32::try_from(bytes.len()).map_err(|_| Error::new(ErrorKind::InvalidInput, 
+            format!("Message too long: {} bytes", bytes.len())))?;
+        self.write_all(&length.to_be_bytes())?;
+        self.write_all(bytes)
+    }
+
+    /// Write a message which `write` appends to the frame, with its length, in a single write
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use machiavelli::connection::*;
+    ///
+    /// let (mut server, mut client) = MemoryConnection::pair();
+    /// server.write_frame_with(|frame| frame.write_all(b"Hello")).unwrap();
+    ///
+    /// assert_eq!(b"Hello".to_vec(), client.read_frames().unwrap());
+    /// ```
+    fn write_frame_with<F>(&mut self, write: F) -> std::io::Result<()>
+        where F: FnOnce(&mut Vec<u8>) -> std::io::Result<()>
+    {
+        // the length goes in the first four bytes once the message is written
+        let mut frame = vec![0u8; 4];
+        write(&mut frame)?;
+        let length = u32::try_from(frame.len() - 4).map_err(|_| Error::new(ErrorKind::InvalidInput,
+            format!("Message too long: {} bytes", frame.len() - 4)))?;
+        frame[..4].copy_from_slice(&length.to_be_bytes());
+        self.write_all(&frame)
+    }
+
+    /// Read a message, and confirm that it was received
+    fn read_frames(&mut self) -> std::io::Result<Vec<u8>> {
+        let mut res = Vec::<u8>::new();
+        self.copy_message(&mut res, BUFFER_SIZE)?;
+        Ok(res)
+    }
+
+    /// Read a message in chunks of `buffer_size` bytes, write each chunk to `out` as soon as it
+    /// is read, confirm that the message was received, and return its length
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::connection::*;
+    ///
+    /// let (mut server, mut client) = MemoryConnection::pair();
+    /// let message = vec![7u8; 100_000];
+    /// server.write_frames(&message).unwrap();
+    ///
+    /// let mut received = Vec::new();
+    /// assert_eq!(100_000, client.copy_message(&mut received, 16).unwrap());
+    /// assert_eq!(message, received);
+    /// ```
+    fn copy_message<W: Write>(&mut self, out: &mut W, buffer_size: usize) -> std::io::Result<usize> {
+        let mut length = [0u8; 4];
+        self.read_exact(&mut length)?;
+        let length = u32::from_be_bytes(length) as usize;
+        let mut buffer = vec![0u8; buffer_size.clamp(1, length.max(1))];
+        let mut n_left = length;
+        while n_left > 0 {
+            let n = n_left.min(buffer.len());
+            self.read_exact(&mut buffer[..n])?;
+            out.write_all(&buffer[..n])?;
+            n_left -= n;
+        }
+
+        // confirm that the message was received
+        self.write_all(&[0])?;
+        Ok(length)
+    }
+
+    /// Wait for the receiver to confirm that it got a message
+    ///
+    /// Connections reading in another thread may leave the confirmation to be read later.
+    fn wait_for_ack(&mut self) -> std::io::Result<()> {
+        self.read(&mut [0]).map(|_| ())
+    }
+
+    /// Close the connection in both directions
+    fn close(&mut self) {}
+
+    /// Set how long a read may wait before failing (`None`: no limit)
+    fn set_timeout(&mut self, _timeout: Option<Duration>) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Underlying TCP stream, if any
+    fn tcp_stream(&self) -> Option<&TcpStream> {
+        None
+    }
+}
+
+impl Connection for TcpStream {
+
+    fn close(&mut self) {
+        self.shutdown(Shutdown::Both).unwrap_or(());
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.set_read_timeout(timeout)
+    }
+
+    fn tcp_stream(&self) -> Option<&TcpStream> {
+        Some(self)
+    }
+}
+
+/// Source of new connections, used when players reconnect
+pub trait Listener {
+
+    type Stream: Connection;
+
+    /// Accept a pending connection, if any, without waiting
+    fn try_accept(&self) -> std::io::Result<Option<Self::Stream>>;
+}
+
+impl Listener for TcpListener {
+
+    type Stream = TcpStream;
+
+    /// The listener must be non-blocking
+    fn try_accept(&self) -> std::io::Result<Option<TcpStream>> {
+        match self.accept() {
+            Ok((stream, address)) => {
+                println!("New connection: {}", address);
+
+                // the stream may inherit the non-blocking mode of the listener
+                stream.set_nonblocking(false)?;
+                Ok(Some(stream))
+            },
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e)
+        }
+    }
+}
+
+/// largest number of writes a `WorkerConnection` queues for its writer thread
+pub const WRITE_QUEUE_SIZE: usize = 1024;
+
+/// longest time the writer thread of a `WorkerConnection` may wait for a TCP write
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connection whose writes and reads are done by two worker threads
+///
+/// Writes are queued for the writer thread and never wait for the other end. Everything the
+/// reader thread receives is kept until it is read; confirmations which `wait_for_ack` does not
+/// wait for are skipped by the next read.
+///
+/// At most `WRITE_QUEUE_SIZE` writes are queued. If the queue is full, or if the writer thread
+/// stopped because a write failed or took longer than `WRITE_TIMEOUT`, the connection is closed:
+/// the other end may have missed part of a frame, so nothing more can be sent to it.
+#[derive(Debug)]
+pub struct WorkerConnection {
+    writer: Option<SyncSender<Vec<u8>>>,
+    reader: Receiver<Vec<u8>>,
+    /// rest of the last bytes received
+    pending: Vec<u8>,
+    timeout: Option<Duration>,
+    /// number of confirmations to skip before the next read
+    acks_to_skip: usize,
+    /// stream to shut down when the connection is closed
+    stream: Option<TcpStream>
+}
+
+impl WorkerConnection {
+
+    /// Start the threads reading from `reader` and writing to `writer`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::{ Read, Write };
+    /// use machiavelli::connection::*;
+    ///
+    /// // the reads and the writes of the worker go through two memory connections
+    /// let (to_worker, mut client_writer) = MemoryConnection::pair();
+    /// let (from_worker, mut client_reader) = MemoryConnection::pair();
+    /// let mut worker = WorkerConnection::spawn(to_worker, from_worker);
+    ///
+    /// // the worker does not wait for the confirmation
+    /// worker.write_frames(b"Hello").unwrap();
+    /// worker.wait_for_ack().unwrap();
+    /// assert_eq!(b"Hello".to_vec(), client_reader.read_frames().unwrap());
+    ///
+    /// // which is skipped before the reply
+    /// client_writer.write_all(&[0, 42]).unwrap();
+    /// let mut reply = [0];
+    /// worker.read_exact(&mut reply).unwrap();
+    /// assert_eq!([42], reply);
+    /// ```
+    pub fn spawn<R, W>(mut reader: R, mut writer: W) -> WorkerConnection
+        where R: Read + Send + 'static, W: Write + Send + 'static
+    {
+        let (to_writer, writer_queue) = sync_channel::<Vec<u8>>(WRITE_QUEUE_SIZE);
+        thread::spawn(move || {
+            for bytes in writer_queue {
+                if writer.write_all(&bytes).and_then(|_| writer.flush()).is_err() {
+                    break;
+                }
+            }
+        });
+        let (reader_queue, from_reader) = channel();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 1024];
+            loop {
+                match reader.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(n) => if reader_queue.send(buffer[..n].to_vec()).is_err() {
+                        break;
+                    },
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(_) => break
+                }
+            }
+        });
+        WorkerConnection {
+            writer: Some(to_writer),
+            reader: from_reader,
+            pending: Vec::new(),
+            timeout: None,
+            acks_to_skip: 0,
+            stream: None
+        }
+    }
+
+    /// Start the threads of a TCP stream
+    pub fn from_tcp(stream: TcpStream) -> std::io::Result<WorkerConnection> {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let mut connection = WorkerConnection::spawn(stream.try_clone()?, stream.try_clone()?);
+        connection.stream = Some(stream);
+        Ok(connection)
+    }
+
+    /// Start the threads of a TCP stream, encrypting what goes through it with a key derived from
+    /// `passphrase` (see `handshake`)
+    pub fn from_tcp_encrypted(mut stream: TcpStream, passphrase: &str) -> std::io::Result<WorkerConnection> {
+        let key = handshake(&mut stream, passphrase, Side::Server)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let mut connection = WorkerConnection::spawn(EncryptedConnection::new(stream.try_clone()?, &key, Side::Server),
+                                                     EncryptedConnection::new(stream.try_clone()?, &key, Side::Server));
+        connection.stream = Some(stream);
+        Ok(connection)
+    }
+
+    /// wait for bytes if none are left, and return false if the connection is closed
+    fn fill(&mut self) -> std::io::Result<bool> {
+        if self.pending.is_empty() {
+            let received = match self.timeout {
+                Some(t) => self.reader.recv_timeout(t),
+                None => self.reader.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            };
+            match received {
+                Ok(bytes) => self.pending = bytes,
+                Err(RecvTimeoutError::Timeout) =>
+                    return Err(Error::new(ErrorKind::TimedOut, "no message before the timeout")),
+                Err(RecvTimeoutError::Disconnected) => return Ok(false)
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl Read for WorkerConnection {
+
+    /// Return 0 once the connection is closed
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.writer.is_none() {
+            return Ok(0);
+        }
+        while self.acks_to_skip > 0 {
+            if !self.fill()? {
+                return Ok(0);
+            }
+            self.pending.remove(0);
+            self.acks_to_skip -= 1;
+        }
+        if !self.fill()? {
+            return Ok(0);
+        }
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
+}
+
+impl Write for WorkerConnection {
+
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let error = match &self.writer {
+            Some(writer) => match writer.try_send(buf.to_vec()) {
+                Ok(()) => return Ok(buf.len()),
+                Err(TrySendError::Full(_)) => Error::new(ErrorKind::BrokenPipe, "the other end does not keep up"),
+                Err(TrySendError::Disconnected(_)) => Error::new(ErrorKind::BrokenPipe, "the other end is closed")
+            },
+            None => return Err(Error::new(ErrorKind::BrokenPipe, "the connection is closed"))
+        };
+
+        // the other end may have got part of a frame
+        self.close();
+        Err(error)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Connection for WorkerConnection {
+
+    fn wait_for_ack(&mut self) -> std::io::Result<()> {
+        self.acks_to_skip += 1;
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.writer = None;
+        self.pending.clear();
+        if let Some(stream) = &self.stream {
+            stream.shutdown(Shutdown::Both).unwrap_or(());
+        }
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn tcp_stream(&self) -> Option<&TcpStream> {
+        self.stream.as_ref()
+    }
+}
+
+impl Drop for WorkerConnection {
+
+    /// The threads hold copies of the stream, which must be shut down to stop them
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// TCP listener handing out worker connections, encrypted if a passphrase is given
+#[derive(Debug)]
+pub struct WorkerListener(pub TcpListener, pub Option<String>);
+
+impl Listener for WorkerListener {
+
+    type Stream = WorkerConnection;
+
+    fn try_accept(&self) -> std::io::Result<Option<WorkerConnection>> {
+        match (self.0.try_accept()?, &self.1) {
+            (Some(stream), Some(passphrase)) => Ok(Some(WorkerConnection::from_tcp_encrypted(stream, passphrase)?)),
+            (Some(stream), None) => Ok(Some(WorkerConnection::from_tcp(stream)?)),
+            (None, _) => Ok(None)
+        }
+    }
+}
+
+/// largest record sent by an `EncryptedConnection`, nonce and tag included
+pub const MAX_RECORD_SIZE: usize = 1 << 16;
+
+/// End of an encrypted connection
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Side {
+    Server,
+    Client
+}
+
+impl Side {
+
+    /// label authenticated with the records written by this end
+    fn label(self) -> u8 {
+        match self {
+            Side::Server => b'S',
+            Side::Client => b'C'
+        }
+    }
+
+    /// other end of the connection
+    fn other(self) -> Side {
+        match self {
+            Side::Server => Side::Client,
+            Side::Client => Side::Server
+        }
+    }
+}
+
+/// longest time a client waits for the salt of the server
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Agree on the key of a new encrypted connection
+///
+/// The server draws a random salt and sends it in clear when the connection opens; both ends
+/// derive the key from their passphrase and this salt, so that each connection has its own key.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::connection::*;
+///
+/// let (mut server, mut client) = MemoryConnection::pair();
+/// let thread = std::thread::spawn(move || handshake(&mut client, "secret", Side::Client).unwrap());
+///
+/// let key = handshake(&mut server, "secret", Side::Server).unwrap();
+///
+/// assert_eq!(key, thread.join().unwrap());
+/// ```
+pub fn handshake<C: Connection>(stream: &mut C, passphrase: &str, side: Side) -> std::io::Result<[u8; KEY_SIZE]> {
+    let mut salt = [0u8; SALT_SIZE];
+    match side {
+        Side::Server => {
+            rand::thread_rng().fill(&mut salt);
+            stream.write_all(&salt)?;
+        },
+        Side::Client => {
+            stream.set_timeout(Some(HANDSHAKE_TIMEOUT))?;
+            let read = stream.read_exact(&mut salt);
+            stream.set_timeout(None)?;
+            read?;
+        }
+    }
+    Ok(key_from_password(passphrase, &salt))
+}
+
+/// data authenticated with a record: the end which wrote it, and its number
+fn record_aad(writer: Side, number: u64) -> Vec<u8> {
+    let mut res = vec![writer.label()];
+    res.extend_from_slice(&number.to_be_bytes());
+    res
+}
+
+/// Connection encrypting everything written to it
+///
+/// Each write is sent as a record: its length on four bytes (big-endian), followed by the
+/// nonce, the encrypted bytes and the tag. The records are numbered in each direction, and
+/// authenticated with their number and the end which wrote them, so that a record which was
+/// changed, dropped, replayed, sent back to the end which wrote it, or encrypted with another key
+/// makes the read fail.
+#[derive(Debug)]
+pub struct EncryptedConnection<C> {
+    inner: C,
+    key: [u8; KEY_SIZE],
+    /// end of the connection writing through this one
+    side: Side,
+    /// number of records written so far
+    sent: u64,
+    /// number of records read so far
+    received: u64,
+    /// rest of the last record read
+    pending: Vec<u8>
+}
+
+impl<C> EncryptedConnection<C> {
+
+    /// Encrypt the bytes going through `inner` with `key`, at the `side` end of the connection
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::connection::*;
+    ///
+    /// let key = [7u8; 32];
+    /// let (server, client) = MemoryConnection::pair();
+    /// let (mut server, mut client) = (EncryptedConnection::new(server, &key, Side::Server),
+    ///                                 EncryptedConnection::new(client, &key, Side::Client));
+    /// let thread = std::thread::spawn(move || client.read_frames().unwrap());
+    ///
+    /// server.write_frames(b"Hello").unwrap();
+    ///
+    /// assert_eq!(b"Hello".to_vec(), thread.join().unwrap());
+    /// ```
+    pub fn new(inner: C, key: &[u8; KEY_SIZE], side: Side) -> EncryptedConnection<C> {
+        EncryptedConnection { inner, key: *key, side, sent: 0, received: 0, pending: Vec::new() }
+    }
+}
+
+impl<C: Connection> EncryptedConnection<C> {
+
+    /// Agree on a key with the other end (see `handshake`), and encrypt the bytes going through
+    /// `inner` with it
+    pub fn handshake(mut inner: C, passphrase: &str, side: Side) -> std::io::Result<EncryptedConnection<C>> {
+        let key = handshake(&mut inner, passphrase, side)?;
+        Ok(EncryptedConnection::new(inner, &key, side))
+    }
+}
+
+impl<C: Read> EncryptedConnection<C> {
+
+    /// read and decrypt the next record, and return false if the connection is closed
+    fn next_record(&mut self) -> std::io::Result<bool> {
+        let mut length = [0u8; 4];
+        if self.inner.read(&mut length[..1])? == 0 {
+            return Ok(false);
+        }
+        self.inner.read_exact(&mut length[1..])?;
+        let length = u32::from_be_bytes(length) as usize;
+        if !(NONCE_SIZE + TAG_SIZE..=MAX_RECORD_SIZE).contains(&length) {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Invalid record length: {}", length)));
+        }
+        let mut record = vec![0u8; length];
+        self.inner.read_exact(&mut record)?;
+        self.pending = decrypt(&self.key, &record, &record_aad(self.side.other(), self.received))
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+        self.received += 1;
+        Ok(true)
+    }
+}
+
+impl<C: Read> Read for EncryptedConnection<C> {
+
+    /// Return 0 once the connection is closed
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.pending.is_empty() {
+            if !self.next_record()? {
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
+}
+
+impl<C: Write> Write for EncryptedConnection<C> {
+
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let n = buf.len().min(MAX_RECORD_SIZE - NONCE_SIZE - TAG_SIZE);
+        let record = encrypt(&self.key, &buf[..n], &record_aad(self.side, self.sent), &mut rand::thread_rng());
+        let mut bytes = (record.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(record);
+        self.inner.write_all(&bytes)?;
+        self.sent += 1;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<C: Connection> Connection for EncryptedConnection<C> {
+
+    fn close(&mut self) {
+        self.pending.clear();
+        self.inner.close();
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+
+    fn tcp_stream(&self) -> Option<&TcpStream> {
+        self.inner.tcp_stream()
+    }
+}
+
+/// One end of a connection going through memory channels
+///
+/// As with TCP in practice, a read returns at most what was sent by one write of the other end.
+#[derive(Debug)]
+pub struct MemoryConnection {
+    sender: Option<Sender<Vec<u8>>>,
+    receiver: Receiver<Vec<u8>>,
+    /// rest of the last write of the other end
+    pending: Vec<u8>,
+    timeout: Option<Duration>
+}
+
+impl MemoryConnection {
+
+    /// Create the two ends of a connection
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::connection::*;
+    ///
+    /// let (mut server, mut client) = MemoryConnection::pair();
+    /// let thread = std::thread::spawn(move || client.read_frames().unwrap());
+    ///
+    /// server.write_frames(b"Hello").unwrap();
+    ///
+    /// assert_eq!(b"Hello".to_vec(), thread.join().unwrap());
+    /// ```
+    pub fn pair() -> (MemoryConnection, MemoryConnection) {
+        let (sender_a, receiver_b) = channel();
+        let (sender_b, receiver_a) = channel();
+        (MemoryConnection { sender: Some(sender_a), receiver: receiver_a, pending: Vec::new(), timeout: None },
+         MemoryConnection { sender: Some(sender_b), receiver: receiver_b, pending: Vec::new(), timeout: None })
+    }
+}
+
+impl Read for MemoryConnection {
+
+    /// Return 0 once the connection is closed
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.sender.is_none() {
+            return Ok(0);
+        }
+        if self.pending.is_empty() {
+            let received = match self.timeout {
+                Some(t) => self.receiver.recv_timeout(t),
+                None => self.receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            };
+            match received {
+                Ok(bytes) => self.pending = bytes,
+                Err(RecvTimeoutError::Timeout) =>
+                    return Err(Error::new(ErrorKind::TimedOut, "no message before the timeout")),
+                Err(RecvTimeoutError::Disconnected) => return Ok(0)
+            }
+        }
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
+}
+
+impl Write for MemoryConnection {
+
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match &self.sender {
+            Some(sender) => match sender.send(buf.to_vec()) {
+                Ok(()) => Ok(buf.len()),
+                Err(_) => Err(Error::new(ErrorKind::BrokenPipe, "the other end is closed"))
+            },
+            None => Err(Error::new(ErrorKind::BrokenPipe, "the connection is closed"))
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Connection for MemoryConnection {
+
+    fn close(&mut self) {
+        self.sender = None;
+        self.pending.clear();
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+}
+
+/// Listener handing out memory connections
+#[derive(Debug)]
+pub struct MemoryListener {
+    sender: Sender<MemoryConnection>,
+    receiver: Mutex<Receiver<MemoryConnection>>
+}
+
+impl MemoryListener {
+
+    /// Create a listener without any pending connection
+    pub fn new() -> MemoryListener {
+        let (sender, receiver) = channel();
+        MemoryListener { sender, receiver: Mutex::new(receiver) }
+    }
+
+    /// Open a connection to the listener, and return the end of the client
+    pub fn connect(&self) -> MemoryConnection {
+        let (server, client) = MemoryConnection::pair();
+        self.sender.send(server).unwrap_or(());
+        client
+    }
+}
+
+impl Default for MemoryListener {
+    fn default() -> Self {
+        MemoryListener::new()
+    }
+}
+
+impl Listener for MemoryListener {
+
+    type Stream = MemoryConnection;
+
+    fn try_accept(&self) -> std::io::Result<Option<MemoryConnection>> {
+        match self.receiver.lock() {
+            Ok(receiver) => match receiver.try_recv() {
+                Ok(connection) => Ok(Some(connection)),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => Ok(None)
+            },
+            Err(_) => Err(Error::other("the listener is poisoned"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn long_and_empty_messages() {
+        let (mut a, mut b) = MemoryConnection::pair();
+        let long: Vec<u8> = (0..120).collect();
+        a.write_frames(&long).unwrap();
+        a.write_frames(&[]).unwrap();
+        assert_eq!(long, b.read_frames().unwrap());
+        assert!(b.read_frames().unwrap().is_empty());
+
+        // each message is confirmed
+        let mut ack = [1u8; 2];
+        a.read_exact(&mut ack).unwrap();
+        assert_eq!([0, 0], ack);
+
+        // messages may be larger than the buffer
+        let huge = vec![3u8; 20 * BUFFER_SIZE + 1];
+        a.write_frames(&huge).unwrap();
+        assert_eq!(huge, b.read_frames().unwrap());
+    }
+
+    #[test]
+    fn closed_connection() {
+        let (mut a, mut b) = MemoryConnection::pair();
+        b.set_timeout(Some(Duration::from_millis(10))).unwrap();
+        assert_eq!(ErrorKind::TimedOut, b.read(&mut [0]).unwrap_err().kind());
+        a.close();
+        assert!(a.write_all(&[1]).is_err());
+        assert_eq!(0, b.read(&mut [0]).unwrap());
+        assert!(b.read_frames().is_err());
+    }
+
+    #[test]
+    fn listener() {
+        let listener = MemoryListener::new();
+        assert!(listener.try_accept().unwrap().is_none());
+        let mut client = listener.connect();
+        let mut server = listener.try_accept().unwrap().unwrap();
+        client.write_all(&[7]).unwrap();
+        let mut buffer = [0];
+        server.read_exact(&mut buffer).unwrap();
+        assert_eq!([7], buffer);
+    }
+
+    /// encrypted connection reading `bytes`, with the other end
+    fn encrypted_reader(bytes: &[u8], key: &[u8; KEY_SIZE]) -> (EncryptedConnection<MemoryConnection>, MemoryConnection) {
+        let (mut a, b) = MemoryConnection::pair();
+        a.write_all(bytes).unwrap();
+        (EncryptedConnection::new(b, key, Side::Client), a)
+    }
+
+    #[test]
+    fn encrypted_connection() {
+        let key = [7u8; KEY_SIZE];
+        let (a, mut b) = MemoryConnection::pair();
+        let mut a = EncryptedConnection::new(a, &key, Side::Server);
+        let huge = vec![3u8; 2 * MAX_RECORD_SIZE];
+        a.write_frames(&huge).unwrap();
+        a.write_frames(b"Hello").unwrap();
+
+        // nothing is sent in clear
+        let mut sent = Vec::new();
+        b.set_timeout(Some(Duration::from_millis(10))).unwrap();
+        let mut buffer = [0u8; 1024];
+        while let Ok(n) = b.read(&mut buffer) {
+            sent.extend_from_slice(&buffer[..n]);
+        }
+        assert!(!sent.windows(5).any(|w| w == b"Hello"));
+
+        // which can be read with the key
+        let (mut reader, other_end) = encrypted_reader(&sent, &key);
+        assert_eq!(huge, reader.read_frames().unwrap());
+        assert_eq!(b"Hello".to_vec(), reader.read_frames().unwrap());
+        drop(other_end);
+        assert_eq!(0, reader.read(&mut [0]).unwrap());
+
+        // but not with another key, or if a byte was changed
+        let mut reader = encrypted_reader(&sent, &[8u8; KEY_SIZE]).0;
+        assert_eq!(ErrorKind::InvalidData, reader.read(&mut [0]).unwrap_err().kind());
+        sent[40] ^= 1;
+        let mut reader = encrypted_reader(&sent, &key).0;
+        assert_eq!(ErrorKind::InvalidData, reader.read(&mut [0]).unwrap_err().kind());
+    }
+
+    #[test]
+    fn encrypted_records_can_not_be_replayed() {
+        let key = [7u8; KEY_SIZE];
+        let mut sent = Vec::new();
+        let mut writer = EncryptedConnection::new(&mut sent, &key, Side::Server);
+        writer.write_all(b"first").unwrap();
+        let mut reader = encrypted_reader(&sent.repeat(2), &key).0;
+        let mut buffer = [0u8; 5];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(b"first", &buffer);
+        assert_eq!(ErrorKind::InvalidData, reader.read(&mut buffer).unwrap_err().kind());
+    }
+
+    #[test]
+    fn encrypted_records_can_not_be_sent_back() {
+        let key = [7u8; KEY_SIZE];
+        let (a, b) = MemoryConnection::pair();
+        let mut server = EncryptedConnection::new(a, &key, Side::Server);
+        let mut attacker = b;
+        server.write_all(b"deal").unwrap();
+
+        // the record of the server is reflected to it, in place of the reply of the client
+        let mut record = vec![0u8; 4 + NONCE_SIZE + 4 + TAG_SIZE];
+        attacker.read_exact(&mut record).unwrap();
+        attacker.write_all(&record).unwrap();
+        assert_eq!(ErrorKind::InvalidData, server.read(&mut [0u8; 4]).unwrap_err().kind());
+    }
+
+    #[test]
+    fn worker_keeps_going_while_the_client_is_away() {
+        let (to_worker, client_writer) = MemoryConnection::pair();
+        let (from_worker, mut client_reader) = MemoryConnection::pair();
+        let mut worker = WorkerConnection::spawn(to_worker, from_worker);
+
+        // nobody reads the messages yet
+        for i in 0..3 {
+            worker.write_frames(&[i]).unwrap();
+            worker.wait_for_ack().unwrap();
+        }
+        for i in 0..3 {
+            assert_eq!(vec![i], client_reader.read_frames().unwrap());
+        }
+
+        // the confirmations were sent to the other memory connection, and never come
+        worker.set_timeout(Some(Duration::from_millis(10))).unwrap();
+        assert_eq!(ErrorKind::TimedOut, worker.read(&mut [0]).unwrap_err().kind());
+        drop(client_writer);
+        assert_eq!(0, worker.read(&mut [0]).unwrap());
+        worker.close();
+        assert!(worker.write_all(&[1]).is_err());
+    }
+
+    /// writer which never returns before the test ends
+    struct StuckWriter(Receiver<()>);
+
+    impl Write for StuckWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.recv().unwrap_or(());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn worker_closes_when_its_queue_is_full() {
+        let (_unblock, stuck) = channel();
+        let (to_worker, _client_writer) = MemoryConnection::pair();
+        let mut worker = WorkerConnection::spawn(to_worker, StuckWriter(stuck));
+
+        // the writer thread takes the first write, and the queue holds the next ones
+        let mut result = Ok(());
+        for _ in 0..WRITE_QUEUE_SIZE + 2 {
+            result = worker.write_all(&[3]);
+            if result.is_err() {
+                break;
+            }
+        }
+        assert_eq!(ErrorKind::BrokenPipe, result.unwrap_err().kind());
+
+        // nothing more goes through the connection
+        assert_eq!(ErrorKind::BrokenPipe, worker.write_all(&[1]).unwrap_err().kind());
+        assert_eq!(0, worker.read(&mut [0]).unwrap());
+    }
+
+    #[test]
+    fn worker_closes_when_a_write_fails() {
+        let (to_worker, _client_writer) = MemoryConnection::pair();
+        let (from_worker, client_reader) = MemoryConnection::pair();
+        let mut worker = WorkerConnection::spawn(to_worker, from_worker);
+        drop(client_reader);
+
+        // the writer thread stops after the first write, which the next ones find out
+        worker.write_all(&[1]).unwrap();
+        let failed = (0..100).any(|_| {
+            std::thread::sleep(Duration::from_millis(10));
+            worker.write_all(&[2]).is_err()
+        });
+        assert!(failed);
+        assert_eq!(0, worker.read(&mut [0]).unwrap());
+    }
+}
@@ -0,0 +1,109 @@
+//! Debug bundles written when the server stops on a protocol error
+//!
+//! The last `N_MESSAGES_KEPT` messages exchanged with the clients are kept in memory. When the
+//! game can not go on because a client sent something unexpected or could not be read, the
+//! server writes them to a text file with the error, the rules, the full state of the game (as
+//! in the snapshots, with all the hands), and the transcript of the game so far, so that players
+//! can attach it to their bug reports. The file is named after the time it was written, and
+//! holds the hands of all the players: it is meant for the developers, not to be shared with the
+//! other players during the game.
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{ SystemTime, UNIX_EPOCH };
+use crate::paths;
+
+/// number of messages kept
+pub const N_MESSAGES_KEPT: usize = 50;
+
+/// longest part of a message written to the bundle, in characters
+const MAX_MESSAGE_LENGTH: usize = 300;
+
+/// last messages sent to and received from the clients, the oldest first
+static MESSAGES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Way a message went
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Direction {
+    Sent,
+    Received
+}
+
+/// Keep a message exchanged with a client
+pub fn record(direction: Direction, bytes: &[u8]) {
+    let arrow = match direction {
+        Direction::Sent => "->",
+        Direction::Received => "<-"
+    };
+    let text = format!("{:?}", String::from_utf8_lossy(bytes));
+    let mut shown: String = text.chars().take(MAX_MESSAGE_LENGTH).collect();
+    if shown.len() < text.len() {
+        shown += "...";
+    }
+    if let Ok(mut messages) = MESSAGES.lock() {
+        messages.push_back(format!("{} {} bytes: {}", arrow, bytes.len(), shown));
+        while messages.len() > N_MESSAGES_KEPT {
+            messages.pop_front();
+        }
+    }
+}
+
+/// Last messages exchanged with the clients, the oldest first
+pub fn recent_messages() -> Vec<String> {
+    MESSAGES.lock().map(|messages| messages.iter().cloned().collect()).unwrap_or_default()
+}
+
+/// Text of a bundle
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::debug_bundle::render;
+///
+/// let bundle = render("StreamError: IO Error: broken pipe", "n_players: 2", "{}",
+///                     &["-> 5 bytes: \"hello\"".to_string()], "# Game");
+/// assert!(bundle.contains("Error: StreamError: IO Error: broken pipe\n"));
+/// assert!(bundle.contains("-> 5 bytes: \"hello\"\n"));
+/// ```
+pub fn render(error: &str, rules: &str, snapshot_json: &str, messages: &[String], transcript: &str) -> String {
+    let mut res = format!("Machiavelli {} debug bundle\n\nError: {}\n\n== Rules ==\n{}\n\n== Game ==\n{}\n\n\
+                           == Last messages (oldest first; -> to a client, <- from a client) ==\n",
+                          env!("CARGO_PKG_VERSION"), error, rules, snapshot_json);
+    for message in messages {
+        res += &format!("{}\n", message);
+    }
+    res += &format!("\n== Transcript ==\n{}\n", transcript);
+    res
+}
+
+/// Write a bundle with the recent messages to `dir` (by default, the current directory), and
+/// return the path of the file
+pub fn write(dir: Option<&Path>, error: &str, rules: &str, snapshot_json: &str, transcript: &str)
+    -> std::io::Result<String>
+{
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let path = paths::in_dir(&format!("machiavelli_debug_{}.txt", seconds), dir);
+    std::fs::write(&path, render(error, rules, snapshot_json, &recent_messages(), transcript))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn only_the_last_messages_are_kept() {
+        for i in 0..(N_MESSAGES_KEPT + 10) {
+            record(Direction::Sent, format!("bundle test {}", i).as_bytes());
+        }
+        record(Direction::Received, &[0xff; 1000]);
+        let messages = recent_messages();
+        assert_eq!(N_MESSAGES_KEPT, messages.len());
+        assert!(!messages.iter().any(|m| m.ends_with("\"bundle test 10\"")));
+        assert!(messages.iter().any(|m| m.ends_with("\"bundle test 59\"")));
+        let last = messages.last().unwrap();
+        assert!(last.starts_with("<- 1000 bytes: \"\u{fffd}") && last.ends_with("..."));
+    }
+}
@@ -0,0 +1,138 @@
+//! Step-through debugger for scripted games
+//!
+//! `machiavelli script <file> --step` plays a script (see the `script` module) one action at a
+//! time. After each action, including the turns of the external bots, it prints everything the
+//! game holds (see `ScriptedGame::debug_state`), with the cards counted against those dealt, and
+//! waits for a command:
+//!
+//! * an empty line plays the next action
+//! * `c` plays the rest of the script without stopping
+//! * `d [file]` writes the snapshot of the game, with all the hands, to a file (by default
+//!   `step_N.json` for the `N`-th action)
+//! * `q` stops the script there
+//!
+//! As the commands are read from the standard input, the script must come from a file.
+
+use std::io::{ BufRead, Write };
+use crate::script::ScriptedGame;
+
+/// flag playing a script step by step
+pub const STEP_FLAG: &str = "--step";
+
+/// Command of the debugger
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Command {
+    Step,
+    Continue,
+    Dump(Option<String>),
+    Quit
+}
+
+/// Read a command of the debugger
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::debugger::{ parse_command, Command };
+///
+/// assert_eq!(Some(Command::Step), parse_command(""));
+/// assert_eq!(Some(Command::Dump(Some("state.json".to_string()))), parse_command("d state.json"));
+/// assert_eq!(None, parse_command("x"));
+/// ```
+pub fn parse_command(line: &str) -> Option<Command> {
+    let mut words = line.split_whitespace();
+    let command = match words.next() {
+        None => Command::Step,
+        Some("c") => Command::Continue,
+        Some("d") => Command::Dump(words.next().map(str::to_string)),
+        Some("q") => Command::Quit,
+        Some(_) => return None
+    };
+    match words.next() {
+        Some(_) => None,
+        None => Some(command)
+    }
+}
+
+/// Debugger reading its commands from `input` and writing the state of the game to `output`
+pub struct Debugger<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    n_steps: usize,
+    running: bool
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+
+    pub fn new(input: R, output: W) -> Debugger<R, W> {
+        Debugger { input, output, n_steps: 0, running: false }
+    }
+
+    /// Show the game after an action played from a line of the script, and wait for a command;
+    /// return `false` if the script should stop
+    pub fn after_action(&mut self, game: &ScriptedGame, line: usize, action: &str) -> bool {
+        self.n_steps += 1;
+        if self.running {
+            return true;
+        }
+        let shown = writeln!(self.output, "\n=== Step {}, line {}: {}\n{}", self.n_steps, line, action,
+                             game.debug_state());
+        if shown.is_err() {
+            return false;
+        }
+        loop {
+            write!(self.output, "[Enter] step, [c]ontinue, [d]ump [file], [q]uit> ").unwrap_or(());
+            self.output.flush().unwrap_or(());
+            let mut command = String::new();
+            match self.input.read_line(&mut command) {
+                Ok(0) | Err(_) => return false,
+                Ok(_) => ()
+            }
+            match parse_command(&command) {
+                Some(Command::Step) => return true,
+                Some(Command::Continue) => {
+                    self.running = true;
+                    return true;
+                },
+                Some(Command::Dump(fname)) => {
+                    let fname = fname.unwrap_or_else(|| format!("step_{}.json", self.n_steps));
+                    let message = match std::fs::write(&fname, game.snapshot().to_json()) {
+                        Ok(()) => format!("Snapshot written to {}", fname),
+                        Err(e) => format!("Could not write {}: {}", fname, e)
+                    };
+                    writeln!(self.output, "{}", message).unwrap_or(());
+                },
+                Some(Command::Quit) => return false,
+                None => writeln!(self.output, "Unknown command `{}`", command.trim()).unwrap_or(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::script::run_with;
+
+    #[test]
+    fn steps_until_asked_to_continue() {
+        let script = "n_cards_to_start = 4\ncustom_rule_jokers = false\ndeck AH 2H 3H 9C  4H 5H 6H 9D  KS\n\
+                      players Alice Bob\nAlice: p 1 2 3\nAlice: e\nBob: e\n";
+        let mut output = Vec::new();
+        let mut debugger = Debugger::new("x\n\nc\n".as_bytes(), &mut output);
+        let game = run_with(script, |game, line, action| debugger.after_action(game, line, action)).unwrap();
+        assert_eq!(2, game.n_turns);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("=== Step 1, line 5: Alice: p 1 2 3\nTurn 1 of Alice (melding)\n"));
+        assert!(output.contains("> Alice (1 cards): 9C\n"));
+        assert!(output.contains("Deck (1 cards, next first): KS\nTable:\n1: AH 2H 3H\nCards: all accounted for\n"));
+        assert!(output.contains("Unknown command `x`"));
+        assert!(!output.contains("Step 3"));
+
+        // the end of the input stops the script
+        let mut debugger = Debugger::new("".as_bytes(), Vec::new());
+        let game = run_with(script, |game, line, action| debugger.after_action(game, line, action)).unwrap();
+        assert_eq!(0, game.n_turns);
+    }
+}
@@ -0,0 +1,213 @@
+//! The draw pile
+//!
+//! Unlike a sequence, a deck is never laid on the table, checked, or sorted: cards are only drawn
+//! from its top. Its cards are stored from the bottom to the top, and it is saved in the same way
+//! as a sequence, one byte per card.
+//!
+//! The cards set aside during a round, like the hands rejected with a mulligan, are kept with the
+//! deck until they are shuffled back into it, so that no card leaves the game.
+
+use std::io::{ Read, Write };
+use rand::Rng;
+use rand::seq::SliceRandom;
+use crate::sequence_cards::{ Card, Sequence };
+
+/// Cards left to draw
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Deck {
+    /// the last card is the top of the deck
+    cards: Vec<Card>,
+    /// cards set aside, which can be shuffled into the deck once it is empty
+    discarded: Vec<Card>
+}
+
+impl Deck {
+
+    /// Create an empty deck
+    pub fn new() -> Deck {
+        Deck { cards: Vec::new(), discarded: Vec::new() }
+    }
+
+    /// Create a deck from cards listed from the bottom to the top
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::deck::Deck;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let mut deck = Deck::from_cards(&[Joker, RegularCard(Heart, 3)]);
+    ///
+    /// assert_eq!(Some(RegularCard(Heart, 3)), deck.draw());
+    /// assert_eq!(Some(Joker), deck.draw());
+    /// assert_eq!(None, deck.draw());
+    /// ```
+    pub fn from_cards(cards: &[Card]) -> Deck {
+        Deck { cards: cards.to_vec(), discarded: Vec::new() }
+    }
+
+    /// Shuffle `n_decks` full decks and `n_jokers` jokers together
+    ///
+    /// The cards come in the same order as with `Sequence::multi_deck` for the same random number
+    /// generator, so that audit logs written with either can be checked.
+    pub fn shuffled<R: Rng + ?Sized>(n_decks: u8, n_jokers: u8, rng: &mut R) -> Deck {
+        Deck::from_cards(&Sequence::multi_deck(n_decks, n_jokers, rng).to_vec())
+    }
+
+    /// Draw the card at the top of the deck, if any
+    pub fn draw(&mut self) -> Option<Card> {
+        self.cards.pop()
+    }
+
+    /// Draw `n` cards, or all the cards left if there are fewer, in the order they were drawn
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::deck::Deck;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let mut deck = Deck::from_cards(&[Joker, RegularCard(Heart, 3), RegularCard(Club, 1)]);
+    ///
+    /// assert_eq!(vec![RegularCard(Club, 1), RegularCard(Heart, 3)], deck.draw_n(2));
+    /// assert_eq!(vec![Joker], deck.draw_n(2));
+    /// assert!(deck.is_empty());
+    /// ```
+    pub fn draw_n(&mut self, n: usize) -> Vec<Card> {
+        let start = self.cards.len().saturating_sub(n);
+        self.cards.drain(start..).rev().collect()
+    }
+
+    /// Number of cards left in the deck
+    pub fn remaining(&self) -> usize {
+        self.cards.len()
+    }
+
+    /// Whether all the cards have been drawn
+    pub fn is_empty(&self) -> bool {
+        self.cards.is_empty()
+    }
+
+    /// Cards left in the deck, from the bottom to the top
+    pub fn cards(&self) -> &[Card] {
+        &self.cards
+    }
+
+    /// Add cards to the deck and shuffle it
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::deck::Deck;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let mut deck = Deck::from_cards(&[Joker]);
+    /// deck.reshuffle(vec![RegularCard(Spade, 4), RegularCard(Spade, 5)], &mut rand::thread_rng());
+    ///
+    /// assert_eq!(3, deck.remaining());
+    /// assert!(deck.cards().contains(&RegularCard(Spade, 4)));
+    /// ```
+    pub fn reshuffle<R: Rng + ?Sized>(&mut self, extra_cards: Vec<Card>, rng: &mut R) {
+        self.cards.extend(extra_cards);
+        self.cards.shuffle(rng);
+    }
+
+    /// Set cards aside until the deck is refilled
+    pub fn discard(&mut self, cards: &[Card]) {
+        self.discarded.extend_from_slice(cards);
+    }
+
+    /// Cards set aside, in the order they were
+    pub fn discarded(&self) -> &[Card] {
+        &self.discarded
+    }
+
+    /// Shuffle the cards set aside into the deck, and return whether there were any
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::deck::Deck;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let mut deck = Deck::new();
+    /// assert!(!deck.refill(&mut rand::thread_rng()));
+    ///
+    /// deck.discard(&[Joker, RegularCard(Club, 8)]);
+    /// assert_eq!(0, deck.remaining());
+    /// assert!(deck.refill(&mut rand::thread_rng()));
+    /// assert_eq!(2, deck.remaining());
+    /// assert!(deck.discarded().is_empty());
+    /// ```
+    pub fn refill<R: Rng + ?Sized>(&mut self, rng: &mut R) -> bool {
+        if self.discarded.is_empty() {
+            return false;
+        }
+        let cards = std::mem::take(&mut self.discarded);
+        self.reshuffle(cards, rng);
+        true
+    }
+
+    /// Write the cards left to draw, one byte each as in a sequence, from the bottom to the top
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        for card in &self.cards {
+            writer.write_all(&[card.to_byte()])?;
+        }
+        Ok(())
+    }
+
+    /// Read a deck of `n_cards` cards written by `write_to`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::deck::Deck;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let deck = Deck::from_cards(&[Joker, RegularCard(Diamond, 13)]);
+    /// let bytes = deck.to_bytes();
+    ///
+    /// assert_eq!(deck, Deck::read_from(&mut &bytes[..], 2).unwrap());
+    /// assert!(Deck::read_from(&mut &bytes[..], 3).is_err());
+    /// ```
+    pub fn read_from<R: Read>(reader: &mut R, n_cards: usize) -> std::io::Result<Deck> {
+        Ok(Deck::from_cards(&Sequence::read_from(reader, n_cards)?.to_vec()))
+    }
+
+    /// Bytes written by `write_to`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.cards.iter().map(|card| card.to_byte()).collect()
+    }
+
+    /// Read a deck from bytes given by `to_bytes`, skipping those which are not cards
+    pub fn from_bytes(bytes: &[u8]) -> Deck {
+        Deck::from_cards(&Sequence::from_bytes(bytes).to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn same_order_as_a_sequence() {
+        let mut sequence = Sequence::multi_deck(2, 4, &mut StdRng::seed_from_u64(3));
+        let mut deck = Deck::shuffled(2, 4, &mut StdRng::seed_from_u64(3));
+        assert_eq!(108, deck.remaining());
+        while let Some(card) = deck.draw() {
+            assert_eq!(Some(card), sequence.draw_card());
+        }
+        assert_eq!(0, sequence.number_cards());
+    }
+
+    #[test]
+    fn draw_from_an_empty_deck() {
+        let mut deck = Deck::new();
+        assert_eq!(None, deck.draw());
+        assert!(deck.draw_n(3).is_empty());
+        assert_eq!(deck, Deck::from_bytes(&deck.to_bytes()));
+    }
+}
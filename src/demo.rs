@@ -0,0 +1,216 @@
+//! Demo mode: bots playing each other
+//!
+//! `machiavelli --demo` has bots play games against each other in the terminal, with all the
+//! hands shown, one turn every `DEFAULT_DELAY_MS` milliseconds (or the number given with
+//! `--demo-delay`), until Ctrl-C. A game ends when a bot has no card left, or when nobody can play
+//! with the deck empty, and a new one starts. The cards are counted after each turn (see the
+//! `invariants` module), so that watching the demo also tests the game and its screen on many
+//! random games.
+
+use std::fmt;
+use std::time::Duration;
+use rand::Rng;
+use crate::{ bot, clear_terminal, colours, parse_config, reset_style_string, Config };
+use crate::deck::Deck;
+use crate::events::{ Event, EventLog, PreviousTurn, N_EVENTS_SHOWN };
+use crate::hand::{ self, Hand };
+use crate::invariants::CardLedger;
+use crate::layout::{ HandLayout, LayoutSettings, render_table };
+use crate::sequence_cards::Sequence;
+use crate::stalemate::StalemateDetector;
+use crate::table::Table;
+use crate::turn_order::TurnOrder;
+
+/// flag starting the demo
+pub const DEMO_FLAG: &str = "--demo";
+
+/// flag giving the time between two turns, in milliseconds
+pub const DELAY_FLAG: &str = "--demo-delay";
+
+/// time between two turns, in milliseconds
+pub const DEFAULT_DELAY_MS: u64 = 1500;
+
+/// rules of the games of the demo
+pub const DEMO_CONFIG: &str = "n_players = 3\n";
+
+/// most turns in a game, in case the bots can neither win nor get stuck
+pub const MAX_TURNS: u32 = 2000;
+
+/// How a game of the demo ended
+#[derive(Debug, PartialEq, Clone)]
+pub enum Outcome {
+    Won(String),
+    Stalemate,
+    TooLong
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Won(name) => write!(f, "{} wins!", colours::paint(name)),
+            Outcome::Stalemate => write!(f, "Nobody can play any more—the game is over."),
+            Outcome::TooLong => write!(f, "The game was stopped after {} turns.", MAX_TURNS)
+        }
+    }
+}
+
+/// Game played by bots only
+pub struct DemoGame {
+    config: Config,
+    table: Table,
+    deck: Deck,
+    hands: Vec<Hand>,
+    order: TurnOrder,
+    log: EventLog,
+    stalemate: StalemateDetector,
+    ledger: CardLedger,
+    previous_turn: PreviousTurn,
+    n_turns: u32
+}
+
+impl DemoGame {
+
+    /// Deal a new game between `config.n_players` bots, named `Bot 1`, `Bot 2`, ...
+    pub fn new<R: Rng + ?Sized>(config: Config, rng: &mut R) -> DemoGame {
+        let n_players = config.n_players as usize;
+        let mut deck = Deck::shuffled(config.n_decks, config.n_jokers, rng);
+        let names: Vec<String> = (1..=n_players).map(|i| format!("Bot {}", i)).collect();
+        let dealt = (0..n_players)
+            .map(|_| Sequence::from_cards(&deck.draw_n(config.n_cards_to_start as usize)))
+            .collect();
+        let hands = Hand::deal(&names, dealt);
+        let table = Table::new();
+        let ledger = CardLedger::new(&table, &hands, &deck, &Sequence::new());
+        DemoGame {
+            config,
+            table,
+            deck,
+            hands,
+            order: TurnOrder::new(n_players, 0),
+            log: EventLog::new(),
+            stalemate: StalemateDetector::new(n_players),
+            ledger,
+            previous_turn: PreviousTurn::default(),
+            n_turns: 0
+        }
+    }
+
+    /// Names of the bots
+    pub fn names(&self) -> Vec<String> {
+        hand::owners(&self.hands)
+    }
+
+    /// Play the turn of the current bot, and return how the game ended if it did
+    ///
+    /// # Panics
+    ///
+    /// Panics if cards were created or lost during the turn.
+    pub fn play_turn(&mut self) -> Option<Outcome> {
+        let player = self.order.current();
+        let name = self.hands[player].owner().to_string();
+        let table_start = self.table.clone();
+        let n_events_start = self.log.total();
+        let n_cards_deck = self.deck.remaining();
+        let n_cards_before = self.hands[player].number_cards();
+
+        self.hands[player].start_turn();
+        bot::play_turn(&mut self.table, &mut self.hands[player], &mut self.deck, &self.config, &name,
+                       &mut self.log);
+        self.hands[player].end_turn(n_cards_deck.saturating_sub(self.deck.remaining()));
+        self.table.normalize();
+        self.ledger.assert_holds(&self.table, &self.hands, &self.deck, &Sequence::new(), "a turn of the demo");
+        let n_cards = self.hands[player].number_cards();
+        self.previous_turn = PreviousTurn::new(&name, &table_start, &self.table, n_cards_before, n_cards,
+                                               &self.log.since(n_events_start));
+        self.n_turns += 1;
+
+        if n_cards == 0 {
+            self.log.push(Event::Won(name.clone()));
+            return Some(Outcome::Won(name));
+        }
+        if n_cards_deck > 0 && self.deck.is_empty() {
+            self.log.push(Event::NoMoreCards);
+        }
+        if self.stalemate.record_turn(self.deck.is_empty(), n_cards < n_cards_before) {
+            self.log.push(Event::Stalemate);
+            return Some(Outcome::Stalemate);
+        }
+        if self.n_turns >= MAX_TURNS {
+            return Some(Outcome::TooLong);
+        }
+        self.order.advance();
+        None
+    }
+
+    /// Screen of the demo: the hands of all the bots, the deck, the table, and the last events
+    ///
+    /// The bot whose turn is next is marked with `>`. The sequences of the table are wrapped at the
+    /// width of the screen, and all shown, as there is nobody to turn the pages.
+    pub fn render(&self, screen: &LayoutSettings) -> String {
+        let mut res = format!("\x1b[1mDemo: bots playing each other (Ctrl-C to quit){}\n\n", reset_style_string());
+        if let Some(banner) = self.previous_turn.banner() {
+            res += &format!("{}\n\n", banner);
+        }
+        for (i, hand) in self.hands.iter().enumerate() {
+            let marker = if i == self.order.current() { ">" } else { " " };
+            res += &format!("{} {} ({} cards): {}{}\n", marker, colours::paint(hand.owner()), hand.number_cards(),
+                            hand.cards(), reset_style_string());
+        }
+        res += &format!("\nDeck: {} cards\n\nTable:\n", self.deck.remaining());
+        let whole_table = LayoutSettings { height: usize::MAX, ..*screen };
+        res += &render_table(&self.table, &self.previous_turn.changed, 0, &whole_table).0;
+        if !self.log.is_empty() {
+            res += &format!("\n{}\n", self.log.render(N_EVENTS_SHOWN));
+        }
+        res
+    }
+}
+
+/// Rules of the demo, with the number of players given in `DEMO_CONFIG`
+pub fn demo_config() -> Config {
+    parse_config(DEMO_CONFIG).map(|(config, _)| config).expect("the rules of the demo should be valid")
+}
+
+/// Play games between bots until the program is stopped, waiting `delay` between two turns
+pub fn run(config: Config, delay: Duration) -> ! {
+    let mut rng = rand::thread_rng();
+    loop {
+        let mut game = DemoGame::new(config.clone(), &mut rng);
+        colours::register(&game.names());
+        loop {
+            clear_terminal();
+            print!("{}", game.render(&LayoutSettings::of_terminal(HandLayout::Line)));
+            std::thread::sleep(delay);
+            if let Some(outcome) = game.play_turn() {
+                clear_terminal();
+                print!("{}", game.render(&LayoutSettings::of_terminal(HandLayout::Line)));
+                println!("\n\x1b[1m{}{}\n\nA new game starts soon.", outcome, reset_style_string());
+                std::thread::sleep(delay * 4);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn games_end_with_all_their_cards() {
+        for seed in 0..5 {
+            let mut game = DemoGame::new(demo_config(), &mut StdRng::seed_from_u64(seed));
+            let screen = LayoutSettings { layout: HandLayout::Line, width: 80, height: 40 };
+            let outcome = loop {
+                if let Some(outcome) = game.play_turn() {
+                    break outcome;
+                }
+                assert!(game.render(&screen).contains("Bot 3 ("));
+            };
+            assert_ne!(Outcome::TooLong, outcome);
+        }
+    }
+}
@@ -1,4 +1,342 @@
-//! A simple encoding/decoding library using XOR
+//! Encryption of the save files and of the connections
+//!
+//! `encrypt` and `decrypt` use XChaCha20-Poly1305 (from the `chacha20poly1305` crate): each
+//! message gets a random nonce, and a message which was changed, or decrypted with another key,
+//! is rejected. Keys are derived from passwords with scrypt (from the `scrypt` crate). Save files
+//! are written with `encrypt_save`, after a header with the parameters of scrypt, the salt, and a
+//! block of metadata which can be read without the password. The password of a save is the name
+//! of its file, without the directory, so that a save still loads once its directory is moved or
+//! written another way; the encryption catches saves which were damaged, but does not protect
+//! them from anyone who has the file. The header tells them apart from the older saves; these were
+//! only xored with their file name, and `decrypt_save_or_legacy` reads them if the user asks for
+//! it. The XOR functions are kept for them.
+//! A save is encrypted and authenticated as a whole, so it is held in memory while it is written
+//! or read; a game takes a few kilobytes.
+
+use std::convert::{ TryFrom, TryInto };
+use std::fmt;
+use chacha20poly1305::{ KeyInit, XChaCha20Poly1305, XNonce };
+use chacha20poly1305::aead::{ Aead, Payload };
+use rand::Rng;
+
+/// size of a key, in bytes
+pub const KEY_SIZE: usize = 32;
+
+/// size of a nonce, in bytes
+pub const NONCE_SIZE: usize = 24;
+
+/// size of the authentication tag added to each message, in bytes
+pub const TAG_SIZE: usize = 16;
+
+/// start of the save files written by `encrypt_save`, with the version of the format
+pub const SAVE_HEADER: &[u8; 8] = b"MACHSAV\x03";
+
+/// version of the format before the metadata block, which can still be read
+const VERSION_WITHOUT_METADATA: u8 = 2;
+
+/// size of the random salt of each save file and each encrypted connection, in bytes
+pub const SALT_SIZE: usize = 16;
+
+/// most memory a save file may ask for to derive its key, in bytes
+pub const MAX_SAVE_MEMORY: u64 = 1 << 30;
+
+/// Cost of deriving a key with scrypt
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Params {
+    /// base-2 logarithm of the number of blocks kept in memory (`N` in RFC 7914)
+    pub log_n: u8,
+    /// size of the blocks, in units of 128 bytes
+    pub r: u32,
+    /// number of independent computations
+    pub p: u32
+}
+
+impl Params {
+
+    /// Recommended parameters for interactive use: 16 MiB of memory
+    pub const RECOMMENDED: Params = Params { log_n: 14, r: 8, p: 1 };
+
+    /// Number of bytes of memory needed to derive a key, or `u64::MAX` if it does not fit
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::encode::Params;
+    ///
+    /// assert_eq!(16 << 20, Params::RECOMMENDED.memory());
+    /// assert_eq!(u64::MAX, Params { log_n: 60, r: 8, p: 1 }.memory());
+    /// ```
+    pub fn memory(&self) -> u64 {
+        if self.log_n >= 64 {
+            return u64::MAX;
+        }
+        u64::try_from((128 * self.r as u128) << self.log_n).unwrap_or(u64::MAX)
+    }
+
+    /// Whether scrypt can derive a key with these parameters: `r` and `p` positive, and `log_n`
+    /// small enough
+    pub fn is_valid(&self) -> bool {
+        scrypt::Params::new(self.log_n, self.r, self.p, KEY_SIZE).is_ok()
+    }
+
+    /// Write the parameters on 9 bytes: `log_n`, then `r` and `p` on 4 bytes each (big-endian)
+    pub fn to_bytes(&self) -> [u8; 9] {
+        let mut res = [0u8; 9];
+        res[0] = self.log_n;
+        res[1..5].copy_from_slice(&self.r.to_be_bytes());
+        res[5..].copy_from_slice(&self.p.to_be_bytes());
+        res
+    }
+
+    /// Read parameters written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8; 9]) -> Params {
+        let mut r = [0u8; 4];
+        let mut p = [0u8; 4];
+        r.copy_from_slice(&bytes[1..5]);
+        p.copy_from_slice(&bytes[5..]);
+        Params { log_n: bytes[0], r: u32::from_be_bytes(r), p: u32::from_be_bytes(p) }
+    }
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params::RECOMMENDED
+    }
+}
+
+/// Error returned when a message can not be decrypted
+#[derive(Debug, PartialEq)]
+pub struct DecryptionError {}
+
+impl fmt::Display for DecryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DecryptionError: wrong key, or the message was changed")
+    }
+}
+
+/// Key for a password and a salt, derived with scrypt and its recommended parameters
+///
+/// Each connection gets its own random salt (see `connection::handshake`), as does each save.
+pub fn key_from_password(password: &str, salt: &[u8]) -> [u8; KEY_SIZE] {
+    key_from_password_and_salt(password, salt, &Params::RECOMMENDED)
+}
+
+/// Key for a password, derived with scrypt (from the `scrypt` crate)
+///
+/// The parameters must be valid (see `Params::is_valid`).
+pub fn key_from_password_and_salt(password: &str, salt: &[u8], params: &Params) -> [u8; KEY_SIZE] {
+    let params = scrypt::Params::new(params.log_n, params.r, params.p, KEY_SIZE).expect("invalid parameters of scrypt");
+    let mut key = [0u8; KEY_SIZE];
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut key).expect("keys have a valid length");
+    key
+}
+
+/// Encrypt a message with a random nonce, and authenticate it with `aad`
+///
+/// The result starts with the nonce, followed by the ciphertext and the tag, so it is
+/// `NONCE_SIZE + TAG_SIZE` bytes longer than the plaintext.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::encode::{ encrypt, decrypt, key_from_password };
+///
+/// let key = key_from_password("passw0rd", b"salt");
+/// let cipher = encrypt(&key, b"I am a secret", b"", &mut rand::thread_rng());
+///
+/// assert_eq!(Ok(b"I am a secret".to_vec()), decrypt(&key, &cipher, b""));
+/// assert!(decrypt(&key_from_password("password", b"salt"), &cipher, b"").is_err());
+/// ```
+pub fn encrypt<R: Rng + ?Sized>(key: &[u8; KEY_SIZE], plaintext: &[u8], aad: &[u8], rng: &mut R) -> Vec<u8> {
+    let mut nonce = [0u8; NONCE_SIZE];
+    rng.fill(&mut nonce);
+    let mut res = nonce.to_vec();
+    // the encryption only fails for messages longer than 256 GiB
+    res.extend(XChaCha20Poly1305::new(key.into()).encrypt(XNonce::from_slice(&nonce), Payload { msg: plaintext, aad })
+        .expect("message too long to be encrypted"));
+    res
+}
+
+/// Decrypt a message written by `encrypt`
+pub fn decrypt(key: &[u8; KEY_SIZE], cipher: &[u8], aad: &[u8]) -> Result<Vec<u8>, DecryptionError> {
+    if cipher.len() < NONCE_SIZE + TAG_SIZE {
+        return Err(DecryptionError {});
+    }
+    let (nonce, sealed) = cipher.split_at(NONCE_SIZE);
+    XChaCha20Poly1305::new(key.into()).decrypt(XNonce::from_slice(nonce), Payload { msg: sealed, aad })
+        .map_err(|_| DecryptionError {})
+}
+
+/// Password of the save at `path`: the name of the file, without its directory
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::encode::save_password;
+///
+/// assert_eq!("game.sav", save_password("/home/alice/.local/share/machiavelli/game.sav"));
+/// assert_eq!("game.sav", save_password("./saves/../saves/game.sav"));
+/// assert_eq!("game.sav", save_password("game.sav"));
+/// ```
+pub fn save_password(path: &str) -> &str {
+    std::path::Path::new(path).file_name().and_then(|name| name.to_str()).unwrap_or(path)
+}
+
+/// Encrypt the bytes of the save at `path`, with the recommended parameters of scrypt
+pub fn encrypt_save<R: Rng + ?Sized>(bytes: &[u8], metadata: &[u8], path: &str, rng: &mut R) -> Vec<u8> {
+    encrypt_save_with(bytes, metadata, path, &Params::RECOMMENDED, rng)
+}
+
+/// Encrypt the bytes of the save at `path`, with the name of the file as password (see
+/// `save_password`)
+///
+/// The file starts with `SAVE_HEADER`, the parameters of scrypt (see `Params::to_bytes`), a
+/// random salt, and the metadata with its length on four bytes (big-endian), which is not
+/// encrypted. The whole header is authenticated with the encrypted bytes, so changing the
+/// parameters or the metadata makes the decryption fail.
+pub fn encrypt_save_with<R: Rng + ?Sized>(bytes: &[u8], metadata: &[u8], path: &str, params: &Params,
+                                          rng: &mut R) -> Vec<u8>
+{
+    let mut salt = [0u8; SALT_SIZE];
+    rng.fill(&mut salt);
+    let mut header = SAVE_HEADER.to_vec();
+    header.extend_from_slice(&params.to_bytes());
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&(metadata.len() as u32).to_be_bytes());
+    header.extend_from_slice(metadata);
+    let key = key_from_password_and_salt(save_password(path), &salt, params);
+    let cipher = encrypt(&key, bytes, &header, rng);
+    header.extend(cipher);
+    header
+}
+
+/// Whether a save file may use these parameters to derive its key
+///
+/// Parameters cheaper than the recommended ones are refused, so that a file can not make the
+/// password easier to guess, as are parameters needing more than `MAX_SAVE_MEMORY`.
+pub fn are_acceptable(params: &Params) -> bool {
+    let min = Params::RECOMMENDED;
+    params.log_n >= min.log_n && params.r >= min.r && params.p >= min.p 
+        && params.p <= 16 && params.memory() <= MAX_SAVE_MEMORY && params.is_valid()
+}
+
+/// Header of an encrypted save
+struct SaveHeader<'a> {
+    params: Params,
+    salt: &'a [u8],
+    /// `None` for the version without metadata
+    metadata: Option<&'a [u8]>,
+    /// number of bytes of the header
+    size: usize
+}
+
+/// read the header of an encrypted save, if it has the current version or the previous one
+fn read_header(data: &[u8]) -> Option<SaveHeader<'_>> {
+    let version = *data.get(SAVE_HEADER.len() - 1)?;
+    if !data.starts_with(&SAVE_HEADER[..7]) || (version != SAVE_HEADER[7] && version != VERSION_WITHOUT_METADATA) {
+        return None;
+    }
+    let mut position = SAVE_HEADER.len();
+    let params = Params::from_bytes(data.get(position..position + 9)?.try_into().ok()?);
+    position += 9;
+    let salt = data.get(position..position + SALT_SIZE)?;
+    position += SALT_SIZE;
+    let metadata = if version == VERSION_WITHOUT_METADATA {
+        None
+    } else {
+        let length = u32::from_be_bytes(data.get(position..position + 4)?.try_into().ok()?) as usize;
+        position += 4;
+        let metadata = data.get(position..position.checked_add(length)?)?;
+        position += length;
+        Some(metadata)
+    };
+    Some(SaveHeader { params, salt, metadata, size: position })
+}
+
+/// Metadata of an encrypted save, read without the password
+///
+/// The metadata is only authenticated when the whole save is decrypted. `None` is returned for
+/// files without metadata.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::encode::{ encrypt_save, save_metadata };
+///
+/// let save = encrypt_save(&[1, 2, 3], b"Alice and Bob", "game.sav", &mut rand::thread_rng());
+/// assert_eq!(Some(&b"Alice and Bob"[..]), save_metadata(&save));
+/// assert_eq!(None, save_metadata(&[1, 2, 3]));
+/// ```
+pub fn save_metadata(data: &[u8]) -> Option<&[u8]> {
+    read_header(data)?.metadata
+}
+
+/// Whether a file looks like a save of the versions which only xored them with their name
+///
+/// Anything which does not start with `MACHSAV` does, including a save whose header was removed,
+/// so such files must only be read if the user asks for it (see `decrypt_save_or_legacy`).
+pub fn is_legacy_save(data: &[u8]) -> bool {
+    !data.starts_with(&SAVE_HEADER[..7])
+}
+
+/// Decrypt the save at `path`, or a save of older versions if `allow_legacy` says so
+///
+/// Saves written before they were encrypted can not be authenticated: any file without the
+/// header of the encrypted saves would be xored with the name of the file and read as one. `allow_legacy`
+/// is only called for such files, and should ask the user whether to load it; it is an error
+/// otherwise.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::encode::{ encrypt_save, decrypt_save_or_legacy, xor };
+///
+/// let old_save = xor(&[1, 2, 3], b"game.sav");
+/// assert!(decrypt_save_or_legacy(&old_save, "game.sav", || false).is_err());
+/// assert_eq!(Ok(vec![1, 2, 3]), decrypt_save_or_legacy(&old_save, "saves/game.sav", || true));
+///
+/// // the question is not asked for the current format
+/// let save = encrypt_save(&[1, 2, 3], &[], "game.sav", &mut rand::thread_rng());
+/// assert_eq!(Ok(vec![1, 2, 3]), decrypt_save_or_legacy(&save, "game.sav", || unreachable!()));
+/// ```
+pub fn decrypt_save_or_legacy<F: FnOnce() -> bool>(data: &[u8], path: &str, allow_legacy: F)
+    -> Result<Vec<u8>, DecryptionError>
+{
+    if is_legacy_save(data) && allow_legacy() {
+        return Ok(xor(data, save_password(path).as_bytes()));
+    }
+    decrypt_save(data, path)
+}
+
+/// Decrypt the save at `path`
+///
+/// Only the name of the file is used as password (see `save_password`). Files must start with
+/// `MACHSAV`, and use the current format, or the previous one (without metadata), with
+/// acceptable parameters. Saves of older versions, which were only xored with the name of the
+/// file, are refused; `decrypt_save_or_legacy` can read them.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::encode::{ encrypt_save, decrypt_save, xor };
+///
+/// let save = encrypt_save(&[1, 2, 3], &[], "game.sav", &mut rand::thread_rng());
+/// assert_eq!(Ok(vec![1, 2, 3]), decrypt_save(&save, "game.sav"));
+/// assert_eq!(Ok(vec![1, 2, 3]), decrypt_save(&save, "/tmp/game.sav"));
+/// assert!(decrypt_save(&save, "other.sav").is_err());
+///
+/// let old_save = xor(&[1, 2, 3], b"game.sav");
+/// assert!(decrypt_save(&old_save, "game.sav").is_err());
+/// ```
+pub fn decrypt_save(data: &[u8], path: &str) -> Result<Vec<u8>, DecryptionError> {
+    let header = read_header(data).ok_or(DecryptionError {})?;
+    if !are_acceptable(&header.params) {
+        return Err(DecryptionError {});
+    }
+    let key = key_from_password_and_salt(save_password(path), header.salt, &header.params);
+    let (header_bytes, cipher) = data.split_at(header.size);
+    decrypt(&key, cipher, header_bytes)
+}
 
 /// Encrypt a plaintext by xoring it with a password
 ///
@@ -63,3 +401,92 @@ pub fn decrypt_str(cipher: &[u8], password: &str) -> Result<String, std::str::Ut
         Err(e) => return Err(e)
     };
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn wrong_password() {
+        let save = encrypt_save(b"game", b"", "game.sav", &mut rand::thread_rng());
+        assert_eq!(Err(DecryptionError {}), decrypt_save(&save, "game.sav "));
+        assert_eq!(Err(DecryptionError {}), decrypt_save(&save, ""));
+    }
+
+    #[test]
+    fn stronger_parameters_are_read() {
+        let params = Params { r: 9, ..Params::RECOMMENDED };
+        let save = encrypt_save_with(b"game", b"", "game.sav", &params, &mut rand::thread_rng());
+        assert_eq!(Ok(b"game".to_vec()), decrypt_save(&save, "game.sav"));
+    }
+
+    #[test]
+    fn downgrades_are_rejected() {
+        let mut rng = rand::thread_rng();
+
+        // a file asking for cheaper parameters, even with the right password
+        let weak = Params { log_n: 4, r: 1, p: 1 };
+        let save = encrypt_save_with(b"game", b"", "game.sav", &weak, &mut rng);
+        assert_eq!(Err(DecryptionError {}), decrypt_save(&save, "game.sav"));
+
+        // parameters changed in the header
+        let save = encrypt_save(b"game", b"", "game.sav", &mut rng);
+        let mut changed = save.clone();
+        changed[SAVE_HEADER.len()] += 1;
+        assert_eq!(Err(DecryptionError {}), decrypt_save(&changed, "game.sav"));
+        changed[SAVE_HEADER.len()] = 57;
+        assert_eq!(Err(DecryptionError {}), decrypt_save(&changed, "game.sav"));
+
+        // an older version of the format, or a truncated header
+        let mut changed = save.clone();
+        changed[SAVE_HEADER.len() - 1] = 1;
+        assert_eq!(Err(DecryptionError {}), decrypt_save(&changed, "game.sav"));
+        assert_eq!(Err(DecryptionError {}), decrypt_save(&save[..20], "game.sav"));
+
+        // changed metadata, or a file claiming to have none
+        let save = encrypt_save(b"game", b"Alice", "game.sav", &mut rng);
+        let mut changed = save.clone();
+        changed[SAVE_HEADER.len() + 9 + SALT_SIZE + 4] = b'B';
+        assert_eq!(Some(&b"Blice"[..]), save_metadata(&changed));
+        assert_eq!(Err(DecryptionError {}), decrypt_save(&changed, "game.sav"));
+        let mut changed = save.clone();
+        changed[SAVE_HEADER.len() - 1] = VERSION_WITHOUT_METADATA;
+        assert_eq!(Err(DecryptionError {}), decrypt_save(&changed, "game.sav"));
+    }
+
+    #[test]
+    fn params_to_bytes_and_back() {
+        let params = Params { log_n: 15, r: 8, p: 2 };
+        assert_eq!([15, 0, 0, 0, 8, 0, 0, 0, 2], params.to_bytes());
+        assert_eq!(params, Params::from_bytes(&params.to_bytes()));
+        assert!(params.is_valid());
+        assert!(!Params { log_n: 15, r: 0, p: 1 }.is_valid());
+    }
+
+    #[test]
+    fn saves_without_header_are_not_xored() {
+        let save = encrypt_save(b"game", b"Alice", "game.sav", &mut rand::thread_rng());
+
+        // without its header, a save would be read as one of an older version
+        let stripped = &save[SAVE_HEADER.len()..];
+        assert!(is_legacy_save(stripped));
+        assert_eq!(Err(DecryptionError {}), decrypt_save(stripped, "game.sav"));
+        assert_eq!(Err(DecryptionError {}), decrypt_save_or_legacy(stripped, "game.sav", || false));
+        assert_eq!(Ok(xor(stripped, b"game.sav")), decrypt_save_or_legacy(stripped, "game.sav", || true));
+    }
+
+    #[test]
+    fn saves_without_metadata() {
+        let params = Params::RECOMMENDED;
+        let mut header = SAVE_HEADER[..7].to_vec();
+        header.push(VERSION_WITHOUT_METADATA);
+        header.extend_from_slice(&params.to_bytes());
+        header.extend_from_slice(&[3; SALT_SIZE]);
+        let key = key_from_password_and_salt("game.sav", &[3; SALT_SIZE], &params);
+        let mut save = header.clone();
+        save.extend(encrypt(&key, b"game", &header, &mut rand::thread_rng()));
+        assert_eq!(None, save_metadata(&save));
+        assert_eq!(Ok(b"game".to_vec()), decrypt_save(&save, "game.sav"));
+    }
+}
@@ -0,0 +1,337 @@
+//! Log of the public events of a game
+//!
+//! Only the information visible to all players is recorded (for instance, the card drawn by a
+//! player is not), so that the log can be sent to every client.
+
+use std::fmt;
+use std::collections::VecDeque;
+use crate::sequence_cards::Sequence;
+use crate::table::Table;
+use crate::colours::paint;
+use super::reset_style_string;
+
+/// number of events shown to the players by default
+pub const N_EVENTS_SHOWN: usize = 5;
+
+/// maximum number of events kept in a log
+const MAX_EVENTS: usize = 200;
+
+/// A public event, with the name of the player involved
+#[derive(Debug, PartialEq, Clone)]
+pub enum Event {
+    DrewCard(String),
+    PlayedSequence(String, Sequence),
+    TookSequence(String, usize, Sequence),
+    AddedToSequence(String, usize, Sequence),
+    /// a player split a sequence of the table after its first cards, and took the cards of the
+    /// halves which were not valid
+    SplitSequence(String, usize, usize, Sequence),
+    GaveUp(String),
+    EndedTurn(String),
+    Won(String),
+    BotTookSeat(String),
+    Kicked(String),
+    NoMoreCards,
+    NewDeck,
+    Stalemate,
+    /// a player rejected the hand they were dealt
+    Mulligan(String),
+    /// a player drew the highest card and plays first
+    PlaysFirst(String),
+    /// the host rewound the game to the start of a turn of the round (from 1)
+    Rewound(String, u32)
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::DrewCard(name) => write!(f, "{} drew a card", paint(name)),
+            Event::PlayedSequence(name, seq) =>
+                write!(f, "{} played {}{}", paint(name), seq, reset_style_string()),
+            Event::TookSequence(name, i, seq) =>
+                write!(f, "{} took sequence {} ({}{})", paint(name), i, seq, reset_style_string()),
+            Event::AddedToSequence(name, i, seq) =>
+                write!(f, "{} added {}{} to sequence {}", paint(name), seq, reset_style_string(), i),
+            Event::SplitSequence(name, i, index, taken) if taken.number_cards() == 0 =>
+                write!(f, "{} split sequence {} after its first {} cards", paint(name), i, index),
+            Event::SplitSequence(name, i, index, taken) =>
+                write!(f, "{} split sequence {} after its first {} cards and took {}{}", paint(name), i, index,
+                       taken, reset_style_string()),
+            Event::GaveUp(name) => write!(f, "{} gave up and reset their turn", paint(name)),
+            Event::EndedTurn(name) => write!(f, "{} ended their turn", paint(name)),
+            Event::Won(name) => write!(f, "{} won the game", paint(name)),
+            Event::BotTookSeat(name) => write!(f, "A bot took the seat of {}", paint(name)),
+            Event::Kicked(name) => write!(f, "{} was kicked out", paint(name)),
+            Event::NoMoreCards => write!(f, "No more cards in the deck"),
+            Event::NewDeck => write!(f, "A new deck was shuffled"),
+            Event::Stalemate => write!(f, "Nobody could play any more"),
+            Event::Mulligan(name) => write!(f, "{} rejected their hand and was dealt a new one", paint(name)),
+            Event::PlaysFirst(name) => write!(f, "{} drew the highest card and plays first", paint(name)),
+            Event::Rewound(name, turn) => write!(f, "{} rewound the game to the start of turn {}", paint(name), turn)
+        }
+    }
+}
+
+/// Summary of a turn shown to the player before it ends: what they did, and the number of cards
+/// they are left with
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::events::*;
+/// use machiavelli::sequence_cards::*;
+///
+/// let played = Event::AddedToSequence("Alice".to_string(), 2, Sequence::from_cards(&[RegularCard(Heart, 9)]));
+/// let summary = turn_summary(&[&played], 1);
+/// assert!(summary.starts_with("This turn:\n  "));
+/// assert!(summary.ends_with("\nYou will have 1 card left"));
+/// ```
+pub fn turn_summary(events: &[&Event], n_cards: usize) -> String {
+    let mut res = "This turn:\n".to_string();
+    for event in events {
+        res += &format!("  {}\n", event);
+    }
+    res + &format!("You will have {} card{} left", n_cards, if n_cards == 1 { "" } else { "s" })
+}
+
+/// Rolling log of the most recent events
+#[derive(Debug, PartialEq, Clone)]
+pub struct EventLog {
+    events: VecDeque<Event>,
+    n_pushed: usize
+}
+
+impl EventLog {
+
+    /// Create an empty log
+    pub fn new() -> EventLog {
+        EventLog {
+            events: VecDeque::<Event>::new(),
+            n_pushed: 0
+        }
+    }
+
+    /// Add an event to the log, forgetting the oldest one if the log is full
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::events::*;
+    ///
+    /// let mut log = EventLog::new();
+    /// log.push(Event::DrewCard("Alice".to_string()));
+    ///
+    /// assert_eq!(1, log.len());
+    /// ```
+    pub fn push(&mut self, event: Event) {
+        if self.events.len() >= MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+        self.n_pushed += 1;
+    }
+
+    /// Total number of events pushed since the log was created, including the forgotten ones
+    pub fn total(&self) -> usize {
+        self.n_pushed
+    }
+
+    /// Return the events pushed after the log reached a given total, from the oldest
+    ///
+    /// Events which have been forgotten are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::events::*;
+    ///
+    /// let mut log = EventLog::new();
+    /// log.push(Event::DrewCard("Alice".to_string()));
+    /// let seen = log.total();
+    /// log.push(Event::EndedTurn("Bob".to_string()));
+    ///
+    /// assert_eq!(vec![&Event::EndedTurn("Bob".to_string())], log.since(seen));
+    /// ```
+    pub fn since(&self, total: usize) -> Vec<&Event> {
+        self.last(self.n_pushed.saturating_sub(total))
+    }
+
+    /// Number of events in the log
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Check if the log is empty
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Remove all the events
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Return the `n` most recent events, from the oldest to the most recent
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::events::*;
+    ///
+    /// let mut log = EventLog::new();
+    /// log.push(Event::DrewCard("Alice".to_string()));
+    /// log.push(Event::EndedTurn("Bob".to_string()));
+    /// log.push(Event::DrewCard("Carol".to_string()));
+    ///
+    /// assert_eq!(vec![&Event::EndedTurn("Bob".to_string()), &Event::DrewCard("Carol".to_string())],
+    ///            log.last(2));
+    /// ```
+    pub fn last(&self, n: usize) -> Vec<&Event> {
+        let n_skip = self.events.len().saturating_sub(n);
+        self.events.iter().skip(n_skip).collect()
+    }
+
+    /// Render the `n` most recent events, one per line
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::events::*;
+    ///
+    /// let mut log = EventLog::new();
+    /// log.push(Event::DrewCard("Alice".to_string()));
+    ///
+    /// assert_eq!("Recent events:\n  Alice drew a card\n".to_string(), log.render(5));
+    /// assert_eq!("".to_string(), EventLog::new().render(5));
+    /// ```
+    pub fn render(&self, n: usize) -> String {
+        if self.events.is_empty() {
+            return String::new();
+        }
+        let mut res = "Recent events:\n".to_string();
+        for event in self.last(n) {
+            res += &format!("  {}\n", event);
+        }
+        res
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        EventLog::new()
+    }
+}
+
+/// What the player of the previous turn did, shown to all the players at the start of the next
+/// one
+///
+/// The default value stands for no previous turn, as at the start of a round.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct PreviousTurn {
+    pub name: String,
+    /// number of cards laid from the hand
+    pub n_played: usize,
+    /// number of cards drawn, penalties included
+    pub n_drawn: usize,
+    pub gave_up: bool,
+    /// sequences of the table laid or changed
+    pub changed: Vec<Sequence>
+}
+
+fn cards_on(table: &Table) -> usize {
+    table.count_cards().values().map(|&n| n as usize).sum()
+}
+
+fn plural(n: usize, word: &str) -> String {
+    format!("{} {}{}", n, word, if n > 1 { "s" } else { "" })
+}
+
+impl PreviousTurn {
+
+    /// Summary of the turn of `name`, from the table and the number of cards in their hand at its
+    /// start and at its end, and from the events of the turn
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::events::*;
+    /// use machiavelli::sequence_cards::*;
+    /// use machiavelli::table::Table;
+    ///
+    /// let seq = Sequence::from_cards(&[RegularCard(Club, 2), RegularCard(Club, 3), RegularCard(Club, 4)]);
+    /// let mut table = Table::new();
+    /// table.add(seq.clone());
+    /// let events = [Event::PlayedSequence("Bob".to_string(), seq.clone())];
+    /// let turn = PreviousTurn::new("Bob", &Table::new(), &table, 10, 7, &events.iter().collect::<Vec<&Event>>());
+    ///
+    /// assert_eq!(Some("Last turn: Bob played 3 cards".to_string()), turn.banner());
+    /// assert_eq!(vec![seq], turn.changed);
+    /// ```
+    pub fn new(name: &str, table_start: &Table, table: &Table, n_cards_start: usize, n_cards: usize,
+               events: &[&Event]) -> PreviousTurn {
+        let n_played = cards_on(table).saturating_sub(cards_on(table_start));
+        PreviousTurn {
+            name: name.to_string(),
+            n_played,
+            n_drawn: (n_cards + n_played).saturating_sub(n_cards_start),
+            gave_up: events.iter().any(|e| **e == Event::GaveUp(name.to_string())),
+            changed: table.changed_since(table_start)
+        }
+    }
+
+    /// Line shown at the top of the screen, if there was a previous turn
+    pub fn banner(&self) -> Option<String> {
+        if self.name.is_empty() {
+            return None;
+        }
+        let mut res = if self.gave_up {
+            format!("{} gave up their turn", paint(&self.name))
+        } else if self.n_played > 0 {
+            format!("{} played {}", paint(&self.name), plural(self.n_played, "card"))
+        } else if !self.changed.is_empty() {
+            format!("{} rearranged the table", paint(&self.name))
+        } else if self.n_drawn > 0 {
+            return Some(format!("Last turn: {} drew {}", paint(&self.name), plural(self.n_drawn, "card")));
+        } else {
+            format!("{} passed", paint(&self.name))
+        };
+        if self.n_drawn > 0 {
+            res += &format!(" and drew {}", self.n_drawn);
+        }
+        Some(format!("Last turn: {}", res))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn log_is_bounded() {
+        let mut log = EventLog::new();
+        for _ in 0..(MAX_EVENTS + 10) {
+            log.push(Event::DrewCard("Alice".to_string()));
+        }
+        log.push(Event::Won("Bob".to_string()));
+        assert_eq!(MAX_EVENTS, log.len());
+        assert_eq!(vec![&Event::Won("Bob".to_string())], log.last(1));
+    }
+
+    #[test]
+    fn last_more_than_len() {
+        let mut log = EventLog::new();
+        log.push(Event::NoMoreCards);
+        assert_eq!(1, log.last(10).len());
+    }
+
+    #[test]
+    fn banners() {
+        let table = Table::new();
+        assert_eq!(None, PreviousTurn::default().banner());
+        let drew = PreviousTurn::new("Alice", &table, &table, 5, 6, &[&Event::DrewCard("Alice".to_string())]);
+        assert_eq!(Some("Last turn: Alice drew 1 card".to_string()), drew.banner());
+        let gave_up = PreviousTurn::new("Alice", &table, &table, 5, 8, &[&Event::GaveUp("Alice".to_string())]);
+        assert_eq!(Some("Last turn: Alice gave up their turn and drew 3".to_string()), gave_up.banner());
+    }
+}
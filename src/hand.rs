@@ -0,0 +1,161 @@
+//! Hand of a player, with what the game needs to know about it
+//!
+//! A hand is a sequence of cards which also knows its owner, whether they have laid cards on the
+//! table yet (they have "opened"), how many cards they drew since the start of the round, and
+//! the cards they held at the start of each of their turns. It can be used wherever a sequence
+//! is expected.
+
+use std::ops::{ Deref, DerefMut };
+use crate::sequence_cards::Sequence;
+
+/// Cards held by a player, and their history in the round
+#[derive(Debug, PartialEq, Clone)]
+pub struct Hand {
+    owner: String,
+    cards: Sequence,
+    has_opened: bool,
+    n_drawn: usize,
+    snapshots: Vec<Sequence>
+}
+
+impl Hand {
+
+    /// Hand dealt to a player at the start of a round
+    pub fn new(owner: &str, cards: Sequence) -> Hand {
+        Hand {
+            owner: owner.to_string(),
+            cards,
+            has_opened: false,
+            n_drawn: 0,
+            snapshots: Vec::new()
+        }
+    }
+
+    /// Hands dealt to players, in the order of their names
+    pub fn deal(owners: &[String], hands: Vec<Sequence>) -> Vec<Hand> {
+        owners.iter().zip(hands).map(|(owner, cards)| Hand::new(owner, cards)).collect()
+    }
+
+    /// Name of the player holding the hand
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// Cards in the hand
+    pub fn cards(&self) -> &Sequence {
+        &self.cards
+    }
+
+    /// Whether the player has laid cards from their hand on the table this round
+    pub fn has_opened(&self) -> bool {
+        self.has_opened
+    }
+
+    /// Number of cards drawn from the deck this round, penalties included
+    pub fn n_drawn(&self) -> usize {
+        self.n_drawn
+    }
+
+    /// Cards held at the start of each turn of the round, from the first turn
+    pub fn snapshots(&self) -> &[Sequence] {
+        &self.snapshots
+    }
+
+    /// Keep a copy of the cards at the start of a turn
+    pub fn start_turn(&mut self) {
+        self.snapshots.push(self.cards.clone());
+    }
+
+    /// Update the history at the end of a turn during which `n_drawn` cards were drawn
+    ///
+    /// The player has opened if their hand lost cards other than those drawn since the start of
+    /// the turn.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::hand::Hand;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let mut hand = Hand::new("Alice", Sequence::from_cards(&[RegularCard(Heart, 5), Joker]));
+    ///
+    /// // the player draws a card
+    /// hand.start_turn();
+    /// hand.add_card(RegularCard(Spade, 1));
+    /// hand.end_turn(1);
+    /// assert!(!hand.has_opened());
+    ///
+    /// // the player lays two cards on the table
+    /// hand.start_turn();
+    /// hand.take_card(1);
+    /// hand.take_card(1);
+    /// hand.end_turn(0);
+    /// assert!(hand.has_opened());
+    /// assert_eq!(1, hand.n_drawn());
+    /// assert_eq!(2, hand.snapshots().len());
+    /// ```
+    pub fn end_turn(&mut self, n_drawn: usize) {
+        self.n_drawn += n_drawn;
+        let n_cards_before = self.snapshots.last().map(|s| s.number_cards()).unwrap_or(0);
+        if self.cards.number_cards() < n_cards_before + n_drawn {
+            self.has_opened = true;
+        }
+    }
+
+    /// Replace the cards by a new hand for a new round, and forget the history
+    pub fn new_round(&mut self, cards: Sequence) {
+        *self = Hand::new(&self.owner, cards);
+    }
+}
+
+impl AsRef<Hand> for Hand {
+    fn as_ref(&self) -> &Hand {
+        self
+    }
+}
+
+impl Deref for Hand {
+    type Target = Sequence;
+
+    fn deref(&self) -> &Sequence {
+        &self.cards
+    }
+}
+
+impl DerefMut for Hand {
+    fn deref_mut(&mut self) -> &mut Sequence {
+        &mut self.cards
+    }
+}
+
+/// Names of the owners of the hands
+pub fn owners<H: AsRef<Hand>>(hands: &[H]) -> Vec<String> {
+    hands.iter().map(|h| h.as_ref().owner.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sequence_cards::Card::*;
+    use crate::sequence_cards::Suit::*;
+
+    #[test]
+    fn taking_from_the_table_is_not_opening() {
+        let mut hand = Hand::new("Bob", Sequence::from_cards(&[RegularCard(Club, 2)]));
+        hand.start_turn();
+        hand.add_card(RegularCard(Club, 3));
+        hand.end_turn(0);
+        assert!(!hand.has_opened());
+    }
+
+    #[test]
+    fn new_round_keeps_the_owner() {
+        let mut hands = Hand::deal(&["Alice".to_string(), "Bob".to_string()], vec![Sequence::new(); 2]);
+        hands[1].start_turn();
+        hands[1].end_turn(3);
+        hands[1].new_round(Sequence::from_cards(&[Joker]));
+        assert_eq!(Hand::new("Bob", Sequence::from_cards(&[Joker])), hands[1]);
+        assert_eq!(vec!["Alice".to_string(), "Bob".to_string()], owners(&hands));
+    }
+}
@@ -0,0 +1,182 @@
+//! Names of the cards in several languages
+//!
+//! Cards are usually drawn with a rank and a suit symbol; these tables give their full names
+//! ("Queen of Hearts", "Dame de cœur"), for the prompts and the transcripts, and the letters of
+//! the ace and of the court cards (`V`, `D`, `R` on French cards), which are used instead of the
+//! English ones with the `local_ranks` setting. Locales are given by their language code, as in
+//! the `locale` setting; unknown ones fall back to English.
+
+use crate::sequence_cards::Suit::{ self, * };
+
+/// locale used when none is given, or when the one given is not available
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// locales for which the names of the cards are available
+pub const LOCALES: [&str; 3] = ["en", "fr", "it"];
+
+/// names of the ranks, from the ace to the king
+const RANKS_EN: [&str; 13] = ["Ace", "Two", "Three", "Four", "Five", "Six", "Seven", "Eight", "Nine", "Ten",
+                              "Jack", "Queen", "King"];
+const RANKS_FR: [&str; 13] = ["As", "Deux", "Trois", "Quatre", "Cinq", "Six", "Sept", "Huit", "Neuf", "Dix",
+                              "Valet", "Dame", "Roi"];
+const RANKS_IT: [&str; 13] = ["Asso", "Due", "Tre", "Quattro", "Cinque", "Sei", "Sette", "Otto", "Nove", "Dieci",
+                              "Fante", "Donna", "Re"];
+
+/// letters of the ace, the jack, the queen, and the king
+const LETTERS_EN: [&str; 4] = ["A", "J", "Q", "K"];
+const LETTERS_FR: [&str; 4] = ["A", "V", "D", "R"];
+const LETTERS_IT: [&str; 4] = ["A", "F", "D", "R"];
+
+/// Language of the names, from a locale such as `fr` or `fr_FR.UTF-8`
+fn language(locale: &str) -> &'static str {
+    let code = locale.split(['_', '-', '.']).next().unwrap_or("").to_lowercase();
+    LOCALES.iter().find(|&&l| l == code).copied().unwrap_or(DEFAULT_LOCALE)
+}
+
+/// Name of a rank (1 for the ace to 13 for the king)
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::i18n::rank_name;
+///
+/// assert_eq!("Queen", rank_name(12, "en"));
+/// assert_eq!("Dame", rank_name(12, "fr_FR.UTF-8"));
+/// assert_eq!("Asso", rank_name(1, "it"));
+/// ```
+pub fn rank_name(rank: u8, locale: &str) -> &'static str {
+    let ranks = match language(locale) {
+        "fr" => &RANKS_FR,
+        "it" => &RANKS_IT,
+        _ => &RANKS_EN
+    };
+    ranks[(rank.clamp(1, 13) - 1) as usize]
+}
+
+// letters of the ace and of the court cards in the language of a locale
+fn letters(locale: &str) -> &'static [&'static str; 4] {
+    match language(locale) {
+        "fr" => &LETTERS_FR,
+        "it" => &LETTERS_IT,
+        _ => &LETTERS_EN
+    }
+}
+
+/// Rank as written on a card: a letter for the ace and the court cards, and a number otherwise
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::i18n::rank_symbol;
+///
+/// assert_eq!("Q", rank_symbol(12, "en"));
+/// assert_eq!("D", rank_symbol(12, "fr"));
+/// assert_eq!("F", rank_symbol(11, "it"));
+/// assert_eq!("10", rank_symbol(10, "fr"));
+/// ```
+pub fn rank_symbol(rank: u8, locale: &str) -> String {
+    match rank {
+        1 | 14 => letters(locale)[0].to_string(),
+        11..=13 => letters(locale)[(rank - 10) as usize].to_string(),
+        _ => rank.to_string()
+    }
+}
+
+/// Rank written by `rank_symbol` in any of the `LOCALES`, in upper or lower case
+///
+/// The letters of the different languages never stand for different ranks, so a card can be
+/// read whichever letters it was written with.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::i18n::parse_rank;
+///
+/// assert_eq!(Some(13), parse_rank("K"));
+/// assert_eq!(Some(13), parse_rank("r"));
+/// assert_eq!(Some(11), parse_rank("V"));
+/// assert_eq!(Some(7), parse_rank("7"));
+/// assert_eq!(None, parse_rank("1"));
+/// ```
+pub fn parse_rank(s: &str) -> Option<u8> {
+    let s = s.to_uppercase();
+    for letters in [&LETTERS_EN, &LETTERS_FR, &LETTERS_IT] {
+        if let Some(i) = letters.iter().position(|&l| l == s) {
+            return Some(if i == 0 { 1 } else { 10 + i as u8 });
+        }
+    }
+    s.parse::<u8>().ok().filter(|n| (2..=10).contains(n))
+}
+
+/// Index of the language of a locale in `LOCALES`
+pub(crate) fn locale_index(locale: &str) -> u8 {
+    let language = language(locale);
+    LOCALES.iter().position(|&l| l == language).unwrap_or(0) as u8
+}
+
+/// Name of a suit, as used after the rank
+pub fn suit_name(suit: Suit, locale: &str) -> &'static str {
+    match (language(locale), suit) {
+        ("fr", Heart) => "cœur",
+        ("fr", Diamond) => "carreau",
+        ("fr", Club) => "trèfle",
+        ("fr", Spade) => "pique",
+        ("it", Heart) => "cuori",
+        ("it", Diamond) => "quadri",
+        ("it", Club) => "fiori",
+        ("it", Spade) => "picche",
+        (_, Heart) => "Hearts",
+        (_, Diamond) => "Diamonds",
+        (_, Club) => "Clubs",
+        (_, Spade) => "Spades"
+    }
+}
+
+/// Name of the joker
+pub fn joker_name(locale: &str) -> &'static str {
+    match language(locale) {
+        "it" => "Jolly",
+        _ => "Joker"
+    }
+}
+
+/// Full name of a card with a rank and a suit
+pub fn card_name(rank: u8, suit: Suit, locale: &str) -> String {
+    let link = match language(locale) {
+        "fr" => "de",
+        "it" => "di",
+        _ => "of"
+    };
+    format!("{} {} {}", rank_name(rank, locale), link, suit_name(suit, locale))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn locales() {
+        assert_eq!("fr", language("FR"));
+        assert_eq!("it", language("it-CH"));
+        assert_eq!("en", language("de_DE"));
+        assert_eq!("en", language(""));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!("Ten of Spades", card_name(10, Spade, "en"));
+        assert_eq!("Roi de trèfle", card_name(13, Club, "fr"));
+        assert_eq!("Fante di quadri", card_name(11, Diamond, "it"));
+        assert_eq!("Jolly", joker_name("it"));
+    }
+
+    #[test]
+    fn letters_do_not_clash() {
+        for locale in LOCALES.iter() {
+            for rank in 1..=13 {
+                assert_eq!(Some(rank), parse_rank(&rank_symbol(rank, locale)));
+            }
+        }
+    }
+}
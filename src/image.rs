@@ -0,0 +1,153 @@
+//! Pictures of the table and a hand
+//!
+//! The table is drawn with one row per sequence, numbered as in the game, and the hand below it.
+//! The pictures are SVG files, which can be shared or embedded in a web page as they are. This
+//! module is only built with the `render_image` feature.
+
+use std::fs::File;
+use std::io::Write;
+use crate::sequence_cards::*;
+use crate::table::Table;
+
+/// name of the file the final position is saved to
+pub const FINAL_POSITION_FILE: &str = "final_position.svg";
+
+const CARD_WIDTH: usize = 40;
+const CARD_HEIGHT: usize = 56;
+const GAP: usize = 6;
+const MARGIN: usize = 20;
+const LABEL_WIDTH: usize = 30;
+const TITLE_HEIGHT: usize = 30;
+
+/// Draw the table and a hand as an SVG image
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::table::Table;
+/// use machiavelli::image::render_svg;
+///
+/// let mut table = Table::new();
+/// table.add(Sequence::from_cards(&[
+///     RegularCard(Heart, 10),
+///     RegularCard(Heart, 11),
+///     RegularCard(Heart, 12),
+/// ]));
+/// let hand = Sequence::from_cards(&[Joker, RegularCard(Spade, 1)]);
+///
+/// let svg = render_svg(&table, &hand);
+///
+/// assert!(svg.starts_with("<svg"));
+/// assert!(svg.contains(">10♥</text>"));
+/// assert!(svg.contains(">A♠</text>"));
+/// ```
+pub fn render_svg(table: &Table, hand: &Sequence) -> String {
+    let rows: Vec<Vec<Card>> = (1..=table.number_sequences())
+        .filter_map(|i| table.get(i))
+        .map(|seq| seq.to_vec())
+        .collect();
+    let hand = hand.to_vec();
+    let n_columns = rows.iter().map(|r| r.len()).chain(std::iter::once(hand.len())).max().unwrap_or(0);
+    let row_height = CARD_HEIGHT + GAP;
+    let width = 2 * MARGIN + LABEL_WIDTH + n_columns.max(1) * (CARD_WIDTH + GAP);
+    let height = 2 * MARGIN + 2 * TITLE_HEIGHT + (rows.len().max(1) + 1) * row_height;
+
+    let mut body = String::new();
+    let mut y = MARGIN;
+    body += &title("Table", y);
+    y += TITLE_HEIGHT;
+    if rows.is_empty() {
+        body += &format!("<text x=\"{}\" y=\"{}\" class=\"label\">(empty)</text>\n",
+                         MARGIN, y + CARD_HEIGHT / 2);
+        y += row_height;
+    }
+    for (i, row) in rows.iter().enumerate() {
+        body += &format!("<text x=\"{}\" y=\"{}\" class=\"label\">{}</text>\n",
+                         MARGIN, y + CARD_HEIGHT / 2 + 5, i + 1);
+        body += &cards(row, y);
+        y += row_height;
+    }
+    body += &title("Hand", y);
+    y += TITLE_HEIGHT;
+    body += &cards(&hand, y);
+
+    format!(concat!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+            "<style>\n",
+            ".card {{ fill: white; stroke: #444; stroke-width: 1 }}\n",
+            "text {{ font-family: sans-serif; font-weight: bold }}\n",
+            ".red {{ fill: #c00 }}\n",
+            ".black {{ fill: #000 }}\n",
+            ".joker {{ fill: #00c }}\n",
+            ".label {{ fill: #444; font-size: 14px }}\n",
+            "</style>\n",
+            "<rect width=\"100%\" height=\"100%\" fill=\"#2a6e3f\"/>\n",
+            "{body}",
+            "</svg>\n"),
+            w = width, h = height, body = body)
+}
+
+/// Save the table and a hand to an SVG file
+pub fn save_svg(table: &Table, hand: &Sequence, fname: &str) -> std::io::Result<()> {
+    File::create(fname)?.write_all(render_svg(table, hand).as_bytes())
+}
+
+fn title(text: &str, y: usize) -> String {
+    format!("<text x=\"{}\" y=\"{}\" fill=\"white\" font-size=\"18px\">{}</text>\n",
+            MARGIN, y + TITLE_HEIGHT - 10, text)
+}
+
+/// a row of cards with their top at `y`
+fn cards(cards: &[Card], y: usize) -> String {
+    let mut res = String::new();
+    for (i, card) in cards.iter().enumerate() {
+        let x = MARGIN + LABEL_WIDTH + i * (CARD_WIDTH + GAP);
+        let (text, class) = label(card);
+        res += &format!(concat!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"4\" class=\"card\"/>",
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-size=\"16px\" class=\"{}\">{}</text>\n"),
+                x, y, CARD_WIDTH, CARD_HEIGHT, x + CARD_WIDTH / 2, y + CARD_HEIGHT / 2 + 6, class, text);
+    }
+    res
+}
+
+/// text and style class of a card
+fn label(card: &Card) -> (String, &'static str) {
+    match card {
+        Joker => ("★".to_string(), "joker"),
+        RegularCard(suit, rank) => {
+            let rank = crate::i18n::rank_symbol(*rank, crate::settings::rank_locale());
+            match suit {
+                Heart => (format!("{}♥", rank), "red"),
+                Diamond => (format!("{}♦", rank), "red"),
+                Club => (format!("{}♣", rank), "black"),
+                Spade => (format!("{}♠", rank), "black")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn one_rectangle_per_card() {
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[Joker, Joker]));
+        table.add(Sequence::from_cards(&[RegularCard(Club, 5)]));
+        let hand = Sequence::from_cards(&[RegularCard(Diamond, 13)]);
+        let svg = render_svg(&table, &hand);
+        assert_eq!(4, svg.matches("class=\"card\"").count());
+        assert!(svg.contains("class=\"red\">K♦</text>"));
+    }
+
+    #[test]
+    fn empty_table() {
+        let svg = render_svg(&Table::new(), &Sequence::new());
+        assert!(svg.contains("(empty)"));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
@@ -0,0 +1,174 @@
+//! Checks that no card is created or lost
+//!
+//! The cards of a game, in the hands, in the deck or set aside with it, on the table, and taken
+//! from the table during a turn, always form the same multiset: actions only move them around. A `CardLedger` records
+//! this multiset (for instance right after the deal) and compares the game with it after each
+//! action, so that a card duplicated or lost by a rearrangement, a take, or a message from a
+//! client is caught where it happens.
+//!
+//! The checks are made in debug builds, or with the `invariants` feature; otherwise `assert_holds`
+//! does nothing.
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::deck::Deck;
+use crate::hand::Hand;
+use crate::sequence_cards::{ Card, Sequence };
+use crate::table::Table;
+
+/// whether the invariants are checked
+pub const ENABLED: bool = cfg!(any(debug_assertions, feature = "invariants"));
+
+/// Cards which differ from those recorded
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CardCountError {
+    /// recorded cards which are not in the game any more
+    pub missing: Vec<Card>,
+    /// cards of the game which were not recorded, or more copies of them
+    pub extra: Vec<Card>
+}
+
+impl fmt::Display for CardCountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cards lost: {:?}; cards created: {:?}", self.missing, self.extra)
+    }
+}
+
+/// Number of copies of each card of a game
+fn count_cards<H: AsRef<Hand>>(table: &Table, hands: &[H], deck: &Deck, cards_from_table: &Sequence)
+    -> HashMap<Card, usize>
+{
+    let mut counts = HashMap::<Card, usize>::new();
+    let table_cards = table.count_cards().into_iter().map(|(card, n)| (card, n as usize));
+    let other_cards = hands.iter()
+        .flat_map(|h| h.as_ref().to_vec())
+        .chain(deck.cards().iter().cloned())
+        .chain(deck.discarded().iter().cloned())
+        .chain(cards_from_table.to_vec())
+        .map(|card| (card, 1));
+    for (card, n) in table_cards.chain(other_cards) {
+        *counts.entry(card).or_insert(0) += n;
+    }
+    counts
+}
+
+/// Multiset of the cards of a game, to check that actions keep it
+#[derive(Debug, PartialEq, Clone)]
+pub struct CardLedger {
+    expected: HashMap<Card, usize>
+}
+
+impl CardLedger {
+
+    /// Record the cards of the game as they are
+    pub fn new<H: AsRef<Hand>>(table: &Table, hands: &[H], deck: &Deck, cards_from_table: &Sequence)
+        -> CardLedger
+    {
+        CardLedger { expected: count_cards(table, hands, deck, cards_from_table) }
+    }
+
+    /// Check that the game has the recorded cards, no more and no less
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::deck::Deck;
+    /// use machiavelli::hand::Hand;
+    /// use machiavelli::invariants::CardLedger;
+    /// use machiavelli::sequence_cards::*;
+    /// use machiavelli::table::Table;
+    ///
+    /// let mut table = Table::new();
+    /// let mut hands = vec![Hand::new("Alice", Sequence::from_cards(&[Joker, RegularCard(Heart, 5)]))];
+    /// let mut deck = Deck::from_cards(&[RegularCard(Spade, 1)]);
+    /// let ledger = CardLedger::new(&table, &hands, &deck, &Sequence::new());
+    ///
+    /// // drawing a card moves it
+    /// hands[0].add_card(deck.draw().unwrap());
+    /// assert!(ledger.check(&table, &hands, &deck, &Sequence::new()).is_ok());
+    ///
+    /// // a card laid on the table without leaving the hand is created
+    /// table.add(Sequence::from_cards(&[Joker]));
+    /// let error = ledger.check(&table, &hands, &deck, &Sequence::new()).unwrap_err();
+    /// assert_eq!((vec![], vec![Joker]), (error.missing, error.extra));
+    /// ```
+    pub fn check<H: AsRef<Hand>>(&self, table: &Table, hands: &[H], deck: &Deck, cards_from_table: &Sequence)
+        -> Result<(), CardCountError>
+    {
+        let counts = count_cards(table, hands, deck, cards_from_table);
+        let mut missing = Vec::new();
+        let mut extra = Vec::new();
+        for (card, &n) in &self.expected {
+            let found = counts.get(card).cloned().unwrap_or(0);
+            missing.extend(std::iter::repeat_n(card.clone(), n.saturating_sub(found)));
+        }
+        for (card, &n) in &counts {
+            let recorded = self.expected.get(card).cloned().unwrap_or(0);
+            extra.extend(std::iter::repeat_n(card.clone(), n.saturating_sub(recorded)));
+        }
+        if missing.is_empty() && extra.is_empty() {
+            Ok(())
+        } else {
+            Err(CardCountError { missing, extra })
+        }
+    }
+
+    /// Panic if the invariants are checked and the game does not have the recorded cards, with
+    /// `context` telling after which action
+    pub fn assert_holds<H: AsRef<Hand>>(&self, table: &Table, hands: &[H], deck: &Deck,
+                                         cards_from_table: &Sequence, context: &str) {
+        if ENABLED {
+            if let Err(error) = self.check(table, hands, deck, cards_from_table) {
+                panic!("the cards changed after {}: {}", context, error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sequence_cards::Card::*;
+    use crate::sequence_cards::Suit::*;
+
+    #[test]
+    #[should_panic(expected = "the cards changed after a take: cards lost: [RegularCard(Club, 4)]")]
+    fn lost_cards_are_caught() {
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5), RegularCard(Club, 6)]));
+        let hands = vec![Hand::new("Alice", Sequence::new())];
+        let ledger = CardLedger::new(&table, &hands, &Deck::new(), &Sequence::new());
+
+        // the sequence leaves the table, but only two of its cards are kept apart
+        let mut taken = table.take(1).unwrap();
+        ledger.assert_holds(&table, &hands, &Deck::new(), &taken, "a take");
+        taken.take_card(1);
+        ledger.assert_holds(&table, &hands, &Deck::new(), &taken, "a take");
+    }
+
+    #[test]
+    fn refilling_the_deck_keeps_the_cards() {
+        use rand::SeedableRng;
+        use rand::rngs::StdRng;
+        use crate::MulliganRule;
+
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut deck = Deck::shuffled(1, 2, &mut rng);
+        let full_deck = count_cards(&Table::new(), &Vec::<Hand>::new(), &deck, &Sequence::new());
+        let mut hands = vec![Hand::new("Alice", Sequence::from_cards(&deck.draw_n(13))),
+                             Hand::new("Bob", Sequence::from_cards(&deck.draw_n(13)))];
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&deck.draw_n(3)));
+        MulliganRule::Free.redeal(&mut hands[1], &mut deck, 13).unwrap();
+
+        // the players draw every card, then the hand set aside is shuffled into the deck
+        while let Some(card) = deck.draw() {
+            hands[0].add_card(card);
+        }
+        assert!(deck.refill(&mut rng));
+        assert_eq!(13, deck.remaining());
+        assert_eq!(full_deck, count_cards(&table, &hands, &deck, &Sequence::new()));
+        assert!(!deck.refill(&mut rng));
+    }
+}
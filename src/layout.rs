@@ -0,0 +1,657 @@
+//! Layouts of a hand on the screen
+//!
+//! Each card is shown with its index in the hand (the number used to play it), whatever the
+//! layout. Long hands can be wrapped at the terminal width, split into one labeled row per suit,
+//! or shown as a compact grid of `index:card` cells.
+//!
+//! When the table or the hand does not fit on the screen, as happens in games with many decks,
+//! they are shown one page at a time; `>` and `<` turn the pages of the table, and `> h` and `< h`
+//! those of the hand.
+//!
+//! The size of the terminal is read again before each redraw, so that the screen follows when the
+//! terminal is resized; sequences of the table too long for its width are wrapped.
+
+use crate::sequence_cards::*;
+use crate::table::Table;
+use crate::settings::ascii_mode;
+use super::reset_style_string;
+
+/// terminal width used when the actual one is unknown
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// number of cards above which a hand shown on a single line is shown with one card per line
+pub const MAX_CARDS_IN_LINE: usize = 60;
+
+/// terminal height used when the actual one is unknown
+pub const DEFAULT_HEIGHT: usize = 40;
+
+/// rows of the screen kept for the status bar, the events, and the instructions
+const RESERVED_ROWS: usize = 24;
+
+/// fewest rows of a page of the table or of the hand
+const MIN_PAGE_ROWS: usize = 4;
+
+/// style of the number of a sequence laid or changed during the previous turn
+pub const CHANGED_START: &str = "\x1b[4m";
+
+/// end of the style of the number of a sequence laid or changed during the previous turn
+pub const CHANGED_END: &str = "\x1b[24m";
+
+/// Width of the terminal, or `DEFAULT_WIDTH` if it can not be found
+///
+/// The `COLUMNS` environment variable is used if it is set; otherwise, the size is asked to
+/// `stty`.
+pub fn terminal_width() -> usize {
+    if let Some(w) = std::env::var("COLUMNS").ok().and_then(|s| s.trim().parse().ok()) {
+        return w;
+    }
+    stty_size(1).unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Height of the terminal, or `DEFAULT_HEIGHT` if it can not be found
+///
+/// As for the width, the `LINES` environment variable is used if it is set.
+pub fn terminal_height() -> usize {
+    if let Some(h) = std::env::var("LINES").ok().and_then(|s| s.trim().parse().ok()) {
+        return h;
+    }
+    stty_size(0).unwrap_or(DEFAULT_HEIGHT)
+}
+
+// one of the numbers of rows (0) and columns (1) of the terminal, as given by `stty`
+fn stty_size(i: usize) -> Option<usize> {
+    std::process::Command::new("stty")
+        .arg("size")
+        .stdin(std::process::Stdio::inherit())
+        .output()
+        .ok()
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .and_then(|s| s.split_whitespace().nth(i).and_then(|w| w.parse().ok()))
+        .filter(|&w| w > 0)
+}
+
+/// How to lay out a hand
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HandLayout {
+    /// all the cards on a single line
+    Line,
+    /// as many lines as needed to fit the terminal width
+    Wrap,
+    /// one row per suit, plus one for the jokers
+    BySuit,
+    /// `index:card` cells in aligned columns
+    Grid
+}
+
+impl HandLayout {
+
+    /// Parse the name of a layout (`line`, `wrap`, `suit`, or `grid`)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::layout::HandLayout;
+    ///
+    /// assert_eq!(Some(HandLayout::BySuit), HandLayout::parse("suit"));
+    /// assert_eq!(None, HandLayout::parse("spiral"));
+    /// ```
+    pub fn parse(s: &str) -> Option<HandLayout> {
+        match s {
+            "line" => Some(HandLayout::Line),
+            "wrap" => Some(HandLayout::Wrap),
+            "suit" => Some(HandLayout::BySuit),
+            "grid" => Some(HandLayout::Grid),
+            _ => None
+        }
+    }
+
+    /// Name of the layout, as accepted by `parse`
+    pub fn name(&self) -> &'static str {
+        match self {
+            HandLayout::Line => "line",
+            HandLayout::Wrap => "wrap",
+            HandLayout::BySuit => "suit",
+            HandLayout::Grid => "grid"
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            HandLayout::Line => 0,
+            HandLayout::Wrap => 1,
+            HandLayout::BySuit => 2,
+            HandLayout::Grid => 3
+        }
+    }
+
+    fn from_byte(x: u8) -> Option<HandLayout> {
+        match x {
+            0 => Some(HandLayout::Line),
+            1 => Some(HandLayout::Wrap),
+            2 => Some(HandLayout::BySuit),
+            3 => Some(HandLayout::Grid),
+            _ => None
+        }
+    }
+}
+
+/// Layout of a player's hand and size of their terminal
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LayoutSettings {
+    pub layout: HandLayout,
+    pub width: usize,
+    pub height: usize
+}
+
+impl LayoutSettings {
+
+    /// Default settings: a single line
+    pub fn new() -> LayoutSettings {
+        LayoutSettings {
+            layout: HandLayout::Line,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT
+        }
+    }
+
+    /// Settings with a layout and the current size of the terminal
+    pub fn of_terminal(layout: HandLayout) -> LayoutSettings {
+        LayoutSettings { layout, width: terminal_width(), height: terminal_height() }
+    }
+
+    /// Convert the settings to bytes, to be sent to the server
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::layout::*;
+    ///
+    /// let settings = LayoutSettings { layout: HandLayout::Grid, width: 300, height: 50 };
+    ///
+    /// assert_eq!(Some(settings), LayoutSettings::from_bytes(&settings.to_bytes()));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let width = self.width.min(u16::MAX as usize) as u16;
+        let height = self.height.min(u16::MAX as usize) as u16;
+        let mut res = vec![self.layout.to_byte()];
+        res.extend_from_slice(&width.to_be_bytes());
+        res.extend_from_slice(&height.to_be_bytes());
+        res
+    }
+
+    /// Read settings converted with `to_bytes`
+    ///
+    /// Older clients do not send the height, which is then `DEFAULT_HEIGHT`.
+    pub fn from_bytes(bytes: &[u8]) -> Option<LayoutSettings> {
+        if bytes.len() < 3 {
+            return None;
+        }
+        Some(LayoutSettings {
+            layout: HandLayout::from_byte(bytes[0])?,
+            width: u16::from_be_bytes([bytes[1], bytes[2]]) as usize,
+            height: match bytes.get(3..5) {
+                Some(h) => u16::from_be_bytes([h[0], h[1]]) as usize,
+                None => DEFAULT_HEIGHT
+            }
+        })
+    }
+
+    /// Number of rows of a page of the table or of the hand
+    pub fn page_rows(&self) -> usize {
+        (self.height.saturating_sub(RESERVED_ROWS) / 2).max(MIN_PAGE_ROWS)
+    }
+}
+
+impl Default for LayoutSettings {
+    fn default() -> Self {
+        LayoutSettings::new()
+    }
+}
+
+/// Render a hand with the indices of its cards, starting from `first_index`
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::layout::*;
+///
+/// let hand = Sequence::from_cards(&[
+///     RegularCard(Heart, 1),
+///     RegularCard(Club, 10),
+///     RegularCard(Heart, 3),
+/// ]);
+/// let settings = LayoutSettings { layout: HandLayout::BySuit, width: 80, height: DEFAULT_HEIGHT };
+/// let rendered = render_hand(&hand, 1, &settings);
+///
+/// // one row of cards and one row of indices per suit
+/// assert_eq!(4, rendered.lines().count());
+/// assert!(rendered.contains("1  3"));
+/// ```
+pub fn render_hand(hand: &Sequence, first_index: usize, settings: &LayoutSettings) -> String {
+    let cells: Vec<(Card, usize)> = hand.to_vec().into_iter()
+        .enumerate()
+        .map(|(i, card)| (card, first_index + i))
+        .collect();
+    match settings.layout {
+        HandLayout::Line if hand.number_cards() > MAX_CARDS_IN_LINE =>
+            hand.show_indices_vertical(first_index.saturating_sub(1)),
+        HandLayout::Line => {
+            let (cards, indices) = if first_index <= 1 {
+                hand.show_indices()
+            } else {
+                hand.show_indices_shifted(first_index - 1)
+            };
+            format!("{}\n{}{}", cards, reset_style_string(), indices)
+        },
+        HandLayout::Wrap => rows_with_indices(&cells, "", settings.width),
+        HandLayout::BySuit => {
+            let mut rows = Vec::<String>::new();
+            for &(suit, label) in &[(Heart, "♥"), (Diamond, "♦"), (Club, "♣"), (Spade, "♠")] {
+                let suit_cells: Vec<(Card, usize)> = cells.iter()
+                    .filter(|(card, _)| matches!(card, RegularCard(s, _) if *s == suit))
+                    .cloned()
+                    .collect();
+                if !suit_cells.is_empty() {
+                    rows.push(rows_with_indices(&suit_cells, label, settings.width));
+                }
+            }
+            let jokers: Vec<(Card, usize)> = cells.iter()
+                .filter(|(card, _)| *card == Joker)
+                .cloned()
+                .collect();
+            if !jokers.is_empty() {
+                rows.push(rows_with_indices(&jokers, "#", settings.width));
+            }
+            rows.join("\n")
+        },
+        HandLayout::Grid => grid(&cells, settings.width)
+    }
+}
+
+/// number of columns taken by a card on the screen
+fn card_width(card: &Card) -> usize {
+    card.width()
+}
+
+/// number of columns taken by an index on the screen
+fn index_width(i: usize) -> usize {
+    i.to_string().len()
+}
+
+/// cards over their indices, wrapped so that each line fits in the width
+///
+/// A label is printed in front of the first card line, with the other lines aligned after it.
+fn rows_with_indices(cells: &[(Card, usize)], label: &str, width: usize) -> String {
+    let label_width = label.chars().count();
+    let margin = if label_width > 0 { label_width + 1 } else { 0 };
+    let mut res = Vec::<String>::new();
+    let mut card_line = String::new();
+    let mut index_line = String::new();
+    let mut line_width = margin;
+    for (card, i) in cells {
+        let cell_width = card_width(card).max(index_width(*i)) + 1;
+        if line_width + cell_width > width.max(margin + cell_width) {
+            res.push(finish_rows(&card_line, &index_line, label, res.is_empty(), margin));
+            card_line.clear();
+            index_line.clear();
+            line_width = margin;
+        }
+        card_line += &format!("{}{}", card, " ".repeat(cell_width - card_width(card)));
+        index_line += &format!("{}{}", i, " ".repeat(cell_width - index_width(*i)));
+        line_width += cell_width;
+    }
+    if !card_line.is_empty() {
+        res.push(finish_rows(&card_line, &index_line, label, res.is_empty(), margin));
+    }
+    res.join("\n")
+}
+
+fn finish_rows(card_line: &str, index_line: &str, label: &str, first: bool, margin: usize)
+    -> String
+{
+    let prefix = if first && margin > 0 { format!("{} ", label) } else { " ".repeat(margin) };
+    format!("{}{}{}\n{}{}", prefix, card_line.trim_end(), reset_style_string(),
+            " ".repeat(margin), index_line.trim_end())
+}
+
+/// `index:card` cells of equal width, as many per line as fit in the width
+fn grid(cells: &[(Card, usize)], width: usize) -> String {
+    let cell_width = cells.iter()
+        .map(|(card, i)| index_width(*i) + 1 + card_width(card))
+        .max()
+        .unwrap_or(0) + 2;
+    let n_columns = (width / cell_width.max(1)).max(1);
+    cells.chunks(n_columns)
+        .map(|row| {
+            let line: String = row.iter()
+                .map(|(card, i)| {
+                    let w = index_width(*i) + 1 + card_width(card);
+                    format!("{}:{}{}", i, card, " ".repeat(cell_width - w))
+                })
+                .collect();
+            format!("{}{}", line.trim_end(), reset_style_string())
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Part of the screen whose pages can be turned
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PagedView {
+    Table,
+    Hand
+}
+
+/// Pages of the table and of the hand shown to a player, from 0
+///
+/// Pages past the last one show the last one, so that the pages need not be turned back when the
+/// table or the hand gets smaller.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Pages {
+    pub table: usize,
+    pub hand: usize
+}
+
+impl Pages {
+
+    /// First pages of the table and of the hand
+    pub fn new() -> Pages {
+        Pages { table: 0, hand: 0 }
+    }
+
+    /// Turn a page of the table or of the hand, forwards or backwards
+    ///
+    /// Going back from a page past the last of the `n_pages` pages shows the page before the last
+    /// one.
+    pub fn turn(&mut self, view: PagedView, forward: bool, n_pages: usize) {
+        let page = match view {
+            PagedView::Table => &mut self.table,
+            PagedView::Hand => &mut self.hand
+        };
+        let last = n_pages.max(1) - 1;
+        *page = if forward { (*page + 1).min(last) } else { (*page).min(last).saturating_sub(1) };
+    }
+}
+
+/// Read a command turning the pages: `>` (next page) or `<` (previous page), followed by `h` for
+/// the hand or by nothing (or `t`) for the table
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::layout::*;
+///
+/// assert_eq!(Some((PagedView::Table, true)), parse_page_command(">"));
+/// assert_eq!(Some((PagedView::Hand, false)), parse_page_command("< h"));
+/// assert_eq!(None, parse_page_command("> x"));
+/// ```
+pub fn parse_page_command(input: &str) -> Option<(PagedView, bool)> {
+    let mut chars = input.chars().filter(|c| !c.is_whitespace());
+    let forward = match chars.next()? {
+        '>' => true,
+        '<' => false,
+        _ => return None
+    };
+    let view = match chars.next() {
+        None | Some('t') => PagedView::Table,
+        Some('h') => PagedView::Hand,
+        _ => return None
+    };
+    match chars.next() {
+        None => Some((view, forward)),
+        Some(_) => None
+    }
+}
+
+// entries of a page, each of one or several lines kept together, with a last line telling which
+// page it is if there are several, and the number of pages
+fn page_of(entries: &[String], rows: usize, page: usize, view: PagedView) -> (String, usize) {
+    // first entry of each page, filling the pages in turn
+    let mut starts = vec![0];
+    let mut used = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        let n_rows = entry.lines().count().max(1);
+        if used > 0 && used + n_rows > rows {
+            starts.push(i);
+            used = 0;
+        }
+        used += n_rows;
+    }
+    let n_pages = starts.len();
+    if n_pages == 1 {
+        return (entries.join("\n"), 1);
+    }
+    let page = page.min(n_pages - 1);
+    let (name, command) = match view {
+        PagedView::Table => ("the table", "> and <"),
+        PagedView::Hand => ("your hand", "> h and < h")
+    };
+    let end = starts.get(page + 1).copied().unwrap_or(entries.len());
+    (format!("{}\n(page {}/{} of {}: {} to turn the pages)", entries[starts[page]..end].join("\n"),
+             page + 1, n_pages, name, command), n_pages)
+}
+
+/// Page of the table, with the number of pages
+///
+/// The numbers of the sequences in `changed`, laid or changed during the previous turn, are
+/// underlined, or marked with a `*` in ASCII mode. A sequence wider than the screen is wrapped,
+/// its next lines starting under its first card.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::layout::*;
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::table::Table;
+///
+/// let mut table = Table::new();
+/// for value in 1..=10 {
+///     table.add(Sequence::from_cards(&[RegularCard(Heart, value)]));
+/// }
+/// let screen = LayoutSettings { layout: HandLayout::Line, width: 80, height: 0 };
+///
+/// let (page, n_pages) = render_table(&table, &[], 2, &screen);
+/// assert_eq!(3, n_pages);
+/// assert!(page.starts_with("9: "));
+/// assert!(page.ends_with("(page 3/3 of the table: > and < to turn the pages)\n"));
+///
+/// let (page, _) = render_table(&table, &[Sequence::from_cards(&[RegularCard(Heart, 2)])], 2, &screen);
+/// assert!(page.starts_with("\x1b[4m9:\x1b[24m "));
+/// ```
+pub fn render_table(table: &Table, changed: &[Sequence], page: usize, screen: &LayoutSettings) -> (String, usize) {
+    let mut changed: Vec<&Sequence> = changed.iter().collect();
+    let lines: Vec<String> = (1..=table.number_sequences())
+        .filter_map(|i| table.get(i).map(|seq| (i, seq)))
+        .map(|(i, seq)| {
+            let (label, label_width) = match changed.iter().position(|s| *s == seq) {
+                Some(k) => {
+                    changed.remove(k);
+                    if ascii_mode() {
+                        (format!("*{}:", i), index_width(i) + 2)
+                    } else {
+                        (format!("{}{}:{}", CHANGED_START, i, CHANGED_END), index_width(i) + 1)
+                    }
+                },
+                None => (format!("{}:", i), index_width(i) + 1)
+            };
+            format!("{} {}", label, wrap_cards(seq, label_width + 1, screen.width))
+        })
+        .collect();
+    let (res, n_pages) = page_of(&lines, screen.page_rows(), page, PagedView::Table);
+    (if res.is_empty() { res } else { res + "\n" }, n_pages)
+}
+
+// cards of a sequence starting after a margin, on as many lines as needed to fit the width, the
+// next lines starting after the margin too
+fn wrap_cards(seq: &Sequence, margin: usize, width: usize) -> String {
+    let mut res = String::new();
+    let mut line_width = margin;
+    for card in seq.to_vec() {
+        let cell_width = card.width() + 1;
+        if line_width > margin && line_width + cell_width > width {
+            res += &format!("{}\n{}", reset_style_string(), " ".repeat(margin));
+            line_width = margin;
+        }
+        res += &format!("{} ", card);
+        line_width += cell_width;
+    }
+    res + &reset_style_string()
+}
+
+/// Page of a hand rendered with `render_hand`, with the number of pages
+///
+/// The rows of cards are kept with the rows of their indices.
+pub fn render_hand_page(hand: &Sequence, first_index: usize, settings: &LayoutSettings, page: usize)
+    -> (String, usize)
+{
+    let rendered = render_hand(hand, first_index, settings);
+    let lines: Vec<&str> = rendered.lines().collect();
+    let group = match settings.layout {
+        HandLayout::Grid => 1,
+        HandLayout::Line if hand.number_cards() > MAX_CARDS_IN_LINE => 1,
+        _ => 2
+    };
+    let entries: Vec<String> = lines.chunks(group).map(|rows| rows.join("\n")).collect();
+    page_of(&entries, settings.page_rows(), page, PagedView::Hand)
+}
+
+/// Page of a hand shown as a single line which may wrap, as in the single-terminal game, with
+/// the number of pages
+pub fn render_cards_page(hand: &Sequence, settings: &LayoutSettings, page: usize) -> (String, usize) {
+    let mut lines = Vec::<String>::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    for card in hand.to_vec() {
+        if line_width + card.width() + 1 > settings.width.max(card.width() + 1) {
+            lines.push(line.trim_end().to_string());
+            line.clear();
+            line_width = 0;
+        }
+        line += &format!("{} ", card);
+        line_width += card.width() + 1;
+    }
+    if !line.is_empty() {
+        lines.push(line.trim_end().to_string());
+    }
+    page_of(&lines, settings.page_rows(), page, PagedView::Hand)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn long_hand(n: usize) -> Sequence {
+        let cards: Vec<Card> = (0..n).map(|i| RegularCard(Spade, (i % 13) as u8 + 1)).collect();
+        Sequence::from_cards(&cards)
+    }
+
+    /// width of a line without the escape sequences
+    fn visible_width(line: &str) -> usize {
+        let mut width = 0;
+        let mut in_escape = false;
+        for c in line.chars() {
+            if c == '\x1b' {
+                in_escape = true;
+            } else if in_escape {
+                in_escape = !c.is_ascii_alphabetic();
+            } else {
+                width += 1;
+            }
+        }
+        width
+    }
+
+    #[test]
+    fn wrap_fits_width() {
+        let settings = LayoutSettings { layout: HandLayout::Wrap, width: 30, height: DEFAULT_HEIGHT };
+        let rendered = render_hand(&long_hand(40), 1, &settings);
+        assert!(rendered.lines().count() > 2);
+        assert!(rendered.lines().all(|l| visible_width(l) <= 30));
+    }
+
+    #[test]
+    fn grid_fits_width() {
+        let settings = LayoutSettings { layout: HandLayout::Grid, width: 40, height: DEFAULT_HEIGHT };
+        let rendered = render_hand(&long_hand(40), 1, &settings);
+        assert!(rendered.lines().all(|l| visible_width(l) <= 40));
+        assert!(rendered.contains("40:"));
+    }
+
+    #[test]
+    fn line_matches_show_indices() {
+        let hand = long_hand(5);
+        let (cards, indices) = hand.show_indices();
+        assert_eq!(format!("{}\n{}{}", cards, reset_style_string(), indices),
+                   render_hand(&hand, 1, &LayoutSettings::new()));
+    }
+
+    #[test]
+    fn very_large_hands_are_vertical() {
+        let hand = long_hand(MAX_CARDS_IN_LINE + 1);
+        let rendered = render_hand(&hand, 1, &LayoutSettings::new());
+        assert_eq!(MAX_CARDS_IN_LINE + 1, rendered.lines().count());
+        assert!(rendered.lines().last().unwrap().starts_with("61: "));
+    }
+
+    #[test]
+    fn columns_fit_cards_and_indices() {
+        let (cards, indices) = long_hand(12).show_indices();
+        let starts = |line: &str, word: &str| visible_width(&line[..line.find(word).unwrap()]);
+        assert_eq!(starts(&cards, "10♠"), starts(&indices, " 10 ") + 1);
+        assert_eq!(starts(&cards, "J♠"), starts(&indices, " 11 ") + 1);
+    }
+
+    #[test]
+    fn shifted_indices() {
+        let settings = LayoutSettings { layout: HandLayout::Wrap, width: 80, height: DEFAULT_HEIGHT };
+        let rendered = render_hand(&long_hand(2), 8, &settings);
+        assert!(rendered.ends_with("8  9"));
+    }
+
+    #[test]
+    fn pages_of_a_large_hand() {
+        let settings = LayoutSettings { layout: HandLayout::Wrap, width: 20, height: 0 };
+        let hand = long_hand(100);
+        let (first, n_pages) = render_hand_page(&hand, 1, &settings, 0);
+        assert!(n_pages > 1);
+        assert!(first.lines().count() <= settings.page_rows() + 1);
+        let (last, _) = render_hand_page(&hand, 1, &settings, 1000);
+        assert!(last.contains("100"));
+        assert!(last.contains(&format!("(page {}/{} of your hand", n_pages, n_pages)));
+
+        // card rows stay with their indices
+        let (second, _) = render_hand_page(&hand, 1, &settings, 1);
+        assert!(second.lines().next().unwrap().contains('♠'));
+    }
+
+    #[test]
+    fn long_sequences_wrap_under_their_first_card() {
+        let mut table = Table::new();
+        table.add(long_hand(3));
+        table.add(long_hand(13));
+        let screen = LayoutSettings { layout: HandLayout::Line, width: 14, height: 0 };
+        let (page, n_pages) = render_table(&table, &[], 0, &screen);
+        assert_eq!(2, n_pages);
+        let lines: Vec<&str> = page.lines().collect();
+        assert!(lines.len() > 2);
+        assert!(lines[0].starts_with("1: "));
+        assert!(lines[1].starts_with("   "));
+        assert!(lines[..lines.len() - 1].iter().all(|l| visible_width(l) <= 14));
+    }
+
+    #[test]
+    fn turning_pages() {
+        let mut pages = Pages::new();
+        pages.turn(PagedView::Hand, false, 3);
+        assert_eq!(0, pages.hand);
+        pages.turn(PagedView::Table, true, 3);
+        pages.turn(PagedView::Table, true, 3);
+        pages.turn(PagedView::Table, true, 3);
+        assert_eq!(2, pages.table);
+        pages.table = 10;
+        pages.turn(PagedView::Table, false, 3);
+        assert_eq!(1, pages.table);
+    }
+}
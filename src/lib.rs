@@ -10,16 +10,82 @@ pub mod sort;
 pub mod encode;
 pub mod lib_server;
 pub mod lib_client;
+pub mod solver;
+pub mod puzzle;
+pub mod events;
+pub mod bot;
+pub mod sha256;
+pub mod audit;
+pub mod animations;
+pub mod layout;
+pub mod settings;
+pub mod toml;
+pub mod prompt;
+pub mod stalemate;
+pub mod replay;
+pub mod admin;
+pub mod shutdown;
+pub mod reload;
+pub mod logging;
+pub mod connection;
+pub mod player_name;
+pub mod sanitize;
+pub mod turn_order;
+pub mod i18n;
+pub mod deck;
+pub mod hand;
+pub mod player;
+pub mod turn;
+pub mod state;
+pub mod paths;
+pub mod saves;
+pub mod clock;
+pub mod invariants;
+pub mod snapshot;
+pub mod script;
+pub mod debugger;
+pub mod debug_bundle;
+pub mod save_format;
+pub mod checkpoints;
+pub mod selection;
+pub mod commands;
+pub mod rpc;
+pub mod check;
+pub mod odds;
+pub mod tracking;
+pub mod plugins;
+pub mod search;
+pub mod colours;
+pub mod mouse;
+pub mod demo;
+pub mod waiting;
+pub mod terminal;
+#[cfg(feature = "line_editing")]
+pub mod line_editor;
+#[cfg(feature = "render_image")]
+pub mod image;
 pub use sequence_cards::*;
 pub use table::*;
+pub use deck::Deck;
+pub use hand::Hand;
+use events::{ Event, EventLog, PreviousTurn, N_EVENTS_SHOWN };
+use layout::{ LayoutSettings, Pages, PagedView, parse_page_command, render_cards_page, render_hand, render_hand_page,
+              render_table };
+use turn_order::TurnOrder;
+use turn::{ Action, Ongoing, TurnEnd };
+use invariants::CardLedger;
+use tracking::CardTracker;
 
 /// number of cards to take when resetting 
 pub const PENALTY_RESET: usize = 3;
 
+/// number of cards from which taking a sequence from the table must be confirmed
+pub const N_CARDS_CONFIRM_TAKE: usize = 5;
+
 pub fn reset_style_string() -> String {
     [
         "\x1b[0m", // reset attributes
-        "\x1b[30;47m", // set the foreground and background colours
+        settings::current_theme().colours(), // set the foreground and background colours
         "\x1b[?25l", // hide the cursor
         "\x1b[K" // redraw the prompt
     ].join("")
@@ -32,36 +98,469 @@ pub fn reset_style() {
 
 /// clear the terminal
 pub fn clear_terminal() {
-    print!("\x1b[2J\x1b[1;1H");
+    print!("{}", terminal::CLEAR);
+    mouse::cleared();
+}
+
+
+/// maximum number of players in a game
+pub const MAX_N_PLAYERS: u8 = 16;
+
+/// number of cards in a deck, without the jokers
+const N_CARDS_PER_DECK: usize = 52;
+
+/// Named sets of rules: their key in the config file, and their name
+pub const PRESETS: [(&str, &str); 3] = [
+    ("classic", "Classic Machiavelli"),
+    ("rummikub", "Rummikub-style"),
+    ("strict", "Strict")
+];
+
+/// number of cards dealt to each player with the classic and strict presets, from a number of
+/// players on: 13 cards for up to three players, and 11 from four players
+const CLASSIC_HAND_SIZES: [(u8, u16); 2] = [(1, 13), (4, 11)];
+
+/// number of cards dealt to each player with the Rummikub-style preset, from a number of players on
+const RUMMIKUB_HAND_SIZES: [(u8, u16); 1] = [(1, 14)];
+
+// number of cards dealt to each of `n_players` players from a table of hand sizes, leaving at least
+// one card in a deck of `n_cards` cards
+fn hand_size(sizes: &[(u8, u16)], n_players: u8, n_cards: usize) -> u16 {
+    let size = sizes.iter().rev()
+        .find(|(from, _)| n_players >= *from)
+        .or_else(|| sizes.first())
+        .map_or(13, |(_, size)| *size);
+    let max = n_cards.saturating_sub(1) / (n_players.max(1) as usize);
+    size.min(max.min(u16::MAX as usize) as u16).max(1)
+}
+
+/// What happens when the deck is empty
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EmptyDeckRule {
+    /// the round ends in a draw
+    Draw,
+    /// the round goes on without drawing until nobody can play, and the player with the fewest
+    /// cards wins
+    Score,
+    /// the cards set aside during the round (the hands rejected with a mulligan) are shuffled
+    /// into a new deck and the round goes on; if there are none, the round goes on as with
+    /// `Score`
+    NewDeck
+}
+
+impl EmptyDeckRule {
+
+    /// Parse the name of a rule (`draw`, `score`, or `new_deck`)
+    pub fn parse(s: &str) -> Option<EmptyDeckRule> {
+        match s {
+            "draw" => Some(EmptyDeckRule::Draw),
+            "score" => Some(EmptyDeckRule::Score),
+            "new_deck" => Some(EmptyDeckRule::NewDeck),
+            _ => None
+        }
+    }
+
+    /// Name of the rule, as accepted by `parse`
+    pub fn name(&self) -> &'static str {
+        match self {
+            EmptyDeckRule::Draw => "draw",
+            EmptyDeckRule::Score => "score",
+            EmptyDeckRule::NewDeck => "new_deck"
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            EmptyDeckRule::Draw => 0,
+            EmptyDeckRule::Score => 1,
+            EmptyDeckRule::NewDeck => 2
+        }
+    }
+
+    fn from_byte(x: u8) -> EmptyDeckRule {
+        match x {
+            1 => EmptyDeckRule::Score,
+            2 => EmptyDeckRule::NewDeck,
+            _ => EmptyDeckRule::Draw
+        }
+    }
+}
+
+/// Whether players can reject the hand they are dealt
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MulliganRule {
+    /// the hands dealt are kept
+    Off,
+    /// each player can reject their hand once, and get a new one of the same size
+    Free,
+    /// each player can reject their hand once, and get a new one with one more card
+    Penalty
+}
+
+impl MulliganRule {
+
+    /// Parse the name of a rule (`off`, `free`, or `penalty`)
+    pub fn parse(s: &str) -> Option<MulliganRule> {
+        match s {
+            "off" => Some(MulliganRule::Off),
+            "free" => Some(MulliganRule::Free),
+            "penalty" => Some(MulliganRule::Penalty),
+            _ => None
+        }
+    }
+
+    /// Name of the rule, as accepted by `parse`
+    pub fn name(&self) -> &'static str {
+        match self {
+            MulliganRule::Off => "off",
+            MulliganRule::Free => "free",
+            MulliganRule::Penalty => "penalty"
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            MulliganRule::Off => 0,
+            MulliganRule::Free => 1,
+            MulliganRule::Penalty => 2
+        }
+    }
+
+    fn from_byte(x: u8) -> MulliganRule {
+        match x {
+            1 => MulliganRule::Free,
+            2 => MulliganRule::Penalty,
+            _ => MulliganRule::Off
+        }
+    }
+
+    /// Question asked to a player holding their first hand
+    pub fn question(&self) -> &'static str {
+        match self {
+            MulliganRule::Penalty => "Reject this hand and get a new one, with one more card?",
+            _ => "Reject this hand and get a new one?"
+        }
+    }
+
+    /// Reject a hand of `n_cards_to_start` cards and deal a new one from the deck
+    ///
+    /// The rejected cards are set aside with the deck (see `Deck::discard`) rather than put back
+    /// into it, so that the deck keeps the order of its shuffle and the audit log can still be
+    /// checked. They are returned. The hand is kept if the deck does not have enough cards left.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::MulliganRule;
+    /// use machiavelli::deck::Deck;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let mut deck = Deck::from_cards(&[RegularCard(Spade, 1), RegularCard(Spade, 2), RegularCard(Spade, 3)]);
+    /// let mut hand = Sequence::from_cards(&[Joker]);
+    ///
+    /// assert_eq!(Ok(Sequence::from_cards(&[Joker])), MulliganRule::Penalty.redeal(&mut hand, &mut deck, 1));
+    /// assert_eq!(Sequence::from_cards(&[RegularCard(Spade, 3), RegularCard(Spade, 2)]), hand);
+    /// assert_eq!(&[Joker], deck.discarded());
+    ///
+    /// assert!(MulliganRule::Penalty.redeal(&mut hand, &mut deck, 1).is_err());
+    /// assert!(MulliganRule::Off.redeal(&mut hand, &mut deck, 1).is_err());
+    /// ```
+    pub fn redeal(&self, hand: &mut Sequence, deck: &mut Deck, n_cards_to_start: u16) -> Result<Sequence, String> {
+        let n_cards = match self {
+            MulliganRule::Off => return Err("Hands can't be rejected in this game".to_string()),
+            MulliganRule::Free => n_cards_to_start as usize,
+            MulliganRule::Penalty => n_cards_to_start as usize + 1
+        };
+        if deck.remaining() < n_cards + 1 {
+            return Err("There are not enough cards left in the deck for a new hand".to_string());
+        }
+        let rejected = std::mem::replace(hand, Sequence::from_cards(&deck.draw_n(n_cards)));
+        deck.discard(&rejected.to_vec());
+        Ok(rejected)
+    }
+}
+
+/// Index of the player with the fewest cards in hand, the fewest points breaking ties, if only
+/// one player is ahead
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::*;
+///
+/// let hands = vec![
+///     Hand::new("Alice", Sequence::from_cards(&[Joker, Joker])),
+///     Hand::new("Bob", Sequence::from_cards(&[RegularCard(Heart, 4)])),
+///     Hand::new("Carol", Sequence::from_cards(&[RegularCard(Club, 2)])),
+/// ];
+///
+/// assert_eq!(Some(2), round_winner(&hands, &PointRules::new()));
+/// assert_eq!(None, round_winner(&[hands[0].clone(), hands[0].clone()], &PointRules::new()));
+/// ```
+pub fn round_winner<H: AsRef<Hand>>(hands: &[H], rules: &PointRules) -> Option<usize> {
+    let score = |i: usize| (hands[i].as_ref().number_cards(), hands[i].as_ref().points(rules));
+    let best = (0..hands.len()).map(score).min()?;
+    let mut players = (0..hands.len()).filter(|&i| score(i) == best);
+    match (players.next(), players.next()) {
+        (Some(i), None) => Some(i),
+        _ => None
+    }
 }
 
+/// Number of cards and points left in each player's hand, to be shown at the end of a round
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::*;
+///
+/// let hands = vec![
+///     Hand::new("Alice", Sequence::new()),
+///     Hand::new("Bob", Sequence::from_cards(&[Joker, RegularCard(Spade, 13)])),
+/// ];
+///
+/// assert_eq!("\nEnd of the round:\n  Alice: 0 cards, 0 points\n  Bob: 2 cards, 35 points\n",
+///            round_summary(&hands, &PointRules::new()));
+/// ```
+pub fn round_summary<H: AsRef<Hand>>(hands: &[H], rules: &PointRules) -> String {
+    let mut res = "\nEnd of the round:".to_string();
+    for hand in hands.iter().map(AsRef::as_ref) {
+        res += &format!("\n  {}: {} cards, {} points", colours::paint(hand.owner()), hand.number_cards(), hand.points(rules));
+    }
+    res + "\n"
+}
+
+/// What a player sees of the others: the number of cards they hold, and the order of the turns
+#[derive(Debug, PartialEq, Clone)]
+pub struct PlayerView {
+    pub name: String,
+    /// names of the other players and sizes of their hands, in the order in which they play
+    pub opponents: Vec<(String, usize)>,
+    /// seating of the players, with whose turn it is and who plays next
+    pub turn_order: String,
+    /// what the player of the previous turn did
+    pub previous_turn: PreviousTurn
+}
+
+impl PlayerView {
+
+    /// View of the game for `player`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::*;
+    /// use machiavelli::turn_order::TurnOrder;
+    ///
+    /// let hands = vec![
+    ///     Hand::new("Alice", Sequence::new()),
+    ///     Hand::new("Bob", Sequence::from_cards(&[Joker])),
+    ///     Hand::new("Carol", Sequence::from_cards(&[Joker, Joker])),
+    /// ];
+    /// let view = PlayerView::new(&hands, &TurnOrder::new(3, 0), 1);
+    ///
+    /// assert_eq!("Bob", view.name);
+    /// assert_eq!(vec![("Carol".to_string(), 2), ("Alice".to_string(), 0)], view.opponents);
+    /// assert_eq!("Turn order: [Alice] → Bob → Carol (next: Bob)", view.turn_order);
+    /// ```
+    pub fn new<H: AsRef<Hand>>(hands: &[H], order: &TurnOrder, player: usize) -> PlayerView {
+        let n_players = hands.len();
+        let opponents = (1..n_players)
+            .map(|i| (player + i) % n_players)
+            .map(|i| (hands[i].as_ref().owner().to_string(), hands[i].as_ref().number_cards()))
+            .collect();
+        PlayerView { 
+            name: hands[player].as_ref().owner().to_string(), 
+            opponents, 
+            turn_order: order.render(&hand::owners(hands)),
+            previous_turn: PreviousTurn::default()
+        }
+    }
+
+    /// Same view, with what the player of the previous turn did
+    pub fn with_previous_turn(self, previous_turn: PreviousTurn) -> PlayerView {
+        PlayerView { previous_turn, ..self }
+    }
+
+    /// Status bar and turn order, below the banner of the previous turn
+    pub fn header(&self, n_cards_deck: usize) -> String {
+        let bar = format!("{}\n{}", status_bar(n_cards_deck, &self.opponents), self.turn_order);
+        match self.previous_turn.banner() {
+            Some(banner) => format!("{}\n{}", banner, bar),
+            None => bar
+        }
+    }
+}
+
+/// One-line bar with the number of cards in the deck and in the hands of the opponents
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::status_bar;
+///
+/// let opponents = vec![("Bob".to_string(), 12), ("Carol".to_string(), 1)];
+///
+/// assert_eq!("\x1b[7m Deck: 40 │ Bob: 12 cards │ Carol: 1 card \x1b[27m", status_bar(40, &opponents));
+/// ```
+pub fn status_bar(n_cards_deck: usize, opponents: &[(String, usize)]) -> String {
+    let mut res = format!("\x1b[7m Deck: {}", n_cards_deck);
+    for (name, n_cards) in opponents {
+        res += &format!(" │ {}: {} card{}", colours::paint(name), n_cards, if *n_cards == 1 { "" } else { "s" });
+    }
+    res + " \x1b[27m"
+}
 
 /// Structure to store the game configuration
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Config {
     pub n_decks: u8,
     pub n_jokers: u8,
     pub n_cards_to_start: u16,
     pub custom_rule_jokers: bool,
-    pub n_players: u8
+    pub n_players: u8,
+    /// what happens when the deck is empty
+    pub empty_deck: EmptyDeckRule,
+    /// points of a joker left in a hand
+    pub joker_penalty: u16,
+    /// whether players can reject the hand they are dealt
+    pub mulligan: MulliganRule,
+    /// whether the players draw a card to decide who plays first
+    pub draw_for_first: bool
 }
 
 
+impl Default for Config {
+
+    /// Settings of a game not otherwise configured, in the config file as in the questions: two
+    /// decks, four jokers, 13 cards, the custom rule, two players, a draw when the deck is empty,
+    /// `DEFAULT_JOKER_PENALTY`, no mulligan, and no draw for the first turn
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::*;
+    ///
+    /// assert_eq!(Config::default(), parse_config("").unwrap().0);
+    /// ```
+    fn default() -> Config {
+        Config {
+            n_decks: 2,
+            n_jokers: 4,
+            n_cards_to_start: 13,
+            custom_rule_jokers: true,
+            n_players: 2,
+            empty_deck: EmptyDeckRule::Draw,
+            joker_penalty: DEFAULT_JOKER_PENALTY,
+            mulligan: MulliganRule::Off,
+            draw_for_first: false
+        }
+    }
+}
+
 impl Config {
 
-    /// Convert the config structure to a sequence of bytes
+    /// Classic Machiavelli: two decks and four jokers, 13 cards each (11 from four players on),
+    /// jokers kept as long as one likes, and a draw when the deck is empty
     ///
     /// # Example
     ///
     /// ```
     /// use machiavelli::Config;
     ///
+    /// assert_eq!(13, Config::classic(3).n_cards_to_start);
+    /// assert_eq!(11, Config::classic(4).n_cards_to_start);
+    ///
+    /// // with more players, the hands get smaller so that they fit in the deck
+    /// assert_eq!(6, Config::classic(16).n_cards_to_start);
+    /// ```
+    pub fn classic(n_players: u8) -> Config {
+        Config {
+            n_decks: 2,
+            n_jokers: 4,
+            n_cards_to_start: hand_size(&CLASSIC_HAND_SIZES, n_players, 2 * N_CARDS_PER_DECK + 4),
+            custom_rule_jokers: false,
+            n_players,
+            empty_deck: EmptyDeckRule::Draw,
+            joker_penalty: DEFAULT_JOKER_PENALTY,
+            mulligan: MulliganRule::Off,
+            draw_for_first: false
+        }
+    }
+
+    /// Rules close to those of Rummikub: two decks and two jokers, 14 cards each whatever the
+    /// number of players, and play goes on without drawing once the deck is empty, a joker left in
+    /// a hand costing 30 points
+    pub fn rummikub(n_players: u8) -> Config {
+        Config {
+            n_decks: 2,
+            n_jokers: 2,
+            n_cards_to_start: hand_size(&RUMMIKUB_HAND_SIZES, n_players, 2 * N_CARDS_PER_DECK + 2),
+            custom_rule_jokers: false,
+            n_players,
+            empty_deck: EmptyDeckRule::Score,
+            joker_penalty: 30,
+            mulligan: MulliganRule::Off,
+            draw_for_first: false
+        }
+    }
+
+    /// Strict rules: jokers must be played at once, play goes on without drawing once the deck
+    /// is empty, and a joker left in a hand costs 50 points; the hands are as large as with the
+    /// classic rules
+    pub fn strict(n_players: u8) -> Config {
+        Config {
+            n_decks: 2,
+            n_jokers: 4,
+            n_cards_to_start: hand_size(&CLASSIC_HAND_SIZES, n_players, 2 * N_CARDS_PER_DECK + 4),
+            custom_rule_jokers: true,
+            n_players,
+            empty_deck: EmptyDeckRule::Score,
+            joker_penalty: 50,
+            mulligan: MulliganRule::Off,
+            draw_for_first: false
+        }
+    }
+
+    /// Config of a preset, given by its key or its name, in upper or lower case
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::{ Config, EmptyDeckRule };
+    ///
+    /// let config = Config::preset("Rummikub-style", 3).unwrap();
+    /// assert_eq!((2, 14, 3), (config.n_jokers, config.n_cards_to_start, config.n_players));
+    /// assert_eq!(Some(config), Config::preset("rummikub", 3));
+    /// assert_eq!(None, Config::preset("chess", 2));
+    /// ```
+    pub fn preset(name: &str, n_players: u8) -> Option<Config> {
+        let (key, _) = PRESETS.iter()
+            .find(|(key, full_name)| key.eq_ignore_ascii_case(name) || full_name.eq_ignore_ascii_case(name))?;
+        match *key {
+            "classic" => Some(Config::classic(n_players)),
+            "rummikub" => Some(Config::rummikub(n_players)),
+            _ => Some(Config::strict(n_players))
+        }
+    }
+
+    /// Convert the config structure to a sequence of bytes
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::{ Config, EmptyDeckRule, MulliganRule, DEFAULT_JOKER_PENALTY };
+    ///
     /// let config = Config {
     ///     n_decks: 2,
     ///     n_jokers: 4,
     ///     n_cards_to_start: 13,
     ///     custom_rule_jokers: false,
-    ///     n_players: 2
+    ///     n_players: 2,
+    ///     empty_deck: EmptyDeckRule::Draw,
+    ///     joker_penalty: DEFAULT_JOKER_PENALTY,
+    ///     mulligan: MulliganRule::Off,
+    ///     draw_for_first: false
     /// };
     ///
     /// let config_bytes = config.to_bytes();
@@ -70,15 +569,34 @@ impl Config {
     ///     vec![2,4,0,13,0,2], 
     ///     config_bytes);
     /// ```
+    ///
+    /// A joker penalty other than the default one is written in two more bytes, flagged in the
+    /// fifth one, which also holds the mulligan rule and whether the players draw for the first
+    /// turn.
     pub fn to_bytes(&self) -> Vec<u8> {
-        vec![
+        let custom_penalty = self.joker_penalty != DEFAULT_JOKER_PENALTY;
+        let mut bytes = vec![
             self.n_decks,
             self.n_jokers,
             (self.n_cards_to_start >> 8) as u8,
             (self.n_cards_to_start & 255) as u8,
-            self.custom_rule_jokers as u8,
+            (self.custom_rule_jokers as u8) | (self.empty_deck.to_byte() << 1) 
+                | ((custom_penalty as u8) << 3) | (self.mulligan.to_byte() << 5)
+                | ((self.draw_for_first as u8) << 7),
             self.n_players
-        ]
+        ];
+        if custom_penalty {
+            bytes.extend_from_slice(&self.joker_penalty.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// number of bytes taken by a config converted with `to_bytes`, from its first bytes
+    fn n_bytes(bytes: &[u8]) -> usize {
+        match bytes.get(4) {
+            Some(b) if b & 8 != 0 => 8,
+            _ => 6
+        }
     }
 
     /// Get a config from a vector of bytes
@@ -86,31 +604,208 @@ impl Config {
     /// # Example
     ///
     /// ```
-    /// use machiavelli::Config;
+    /// use machiavelli::{ Config, EmptyDeckRule, MulliganRule, DEFAULT_JOKER_PENALTY };
     ///
     /// let bytes: Vec<u8> = vec![2,4,0,13,0,2];
     ///
-    /// let config = Config::from_bytes(&bytes);
+    /// let config = Config::from_bytes(&bytes).unwrap();
     ///
     /// let expected_config = Config {
     ///     n_decks: 2,
     ///     n_jokers: 4,
     ///     n_cards_to_start: 13,
     ///     custom_rule_jokers: false,
-    ///     n_players: 2
+    ///     n_players: 2,
+    ///     empty_deck: EmptyDeckRule::Draw,
+    ///     joker_penalty: DEFAULT_JOKER_PENALTY,
+    ///     mulligan: MulliganRule::Off,
+    ///     draw_for_first: false
     /// };
     ///
     /// assert_eq!(expected_config, config);
+    ///
+    /// // 60 cards for each of the two players is more than two decks
+    /// assert!(Config::from_bytes(&[2,4,0,60,0,2]).is_err());
     /// ```
-    pub fn from_bytes(bytes: &[u8]) -> Config {
-        Config {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Config, ConfigError> {
+        if bytes.len() < Config::n_bytes(bytes) {
+            return Err(ConfigError { message: "not enough bytes".to_string() });
+        }
+        let config = Config {
             n_decks: bytes[0],
             n_jokers: bytes[1],
             n_cards_to_start: (bytes[2] as u16)*256 + (bytes[3] as u16),
-            custom_rule_jokers: bytes[4] != 0,
-            n_players: bytes[5]
+            custom_rule_jokers: bytes[4] & 1 != 0,
+            n_players: bytes[5],
+            empty_deck: EmptyDeckRule::from_byte((bytes[4] >> 1) & 3),
+            joker_penalty: if Config::n_bytes(bytes) > 6 {
+                u16::from_be_bytes([bytes[6], bytes[7]])
+            } else {
+                DEFAULT_JOKER_PENALTY
+            },
+            mulligan: MulliganRule::from_byte((bytes[4] >> 5) & 3),
+            draw_for_first: bytes[4] & 128 != 0
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Points of the cards left in a hand with this config
+    pub fn point_rules(&self) -> PointRules {
+        PointRules::with_joker_penalty(self.joker_penalty)
+    }
+
+    /// Check that the config describes a game which can be played
+    ///
+    /// The constraints are:
+    ///
+    /// * there is at least one deck
+    /// * there are between 1 and `MAX_N_PLAYERS` players
+    /// * each player starts with at least one card
+    /// * at least one card is left in the deck once the hands are dealt
+    ///
+    /// All the constraints which are not met are listed in the error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::{ Config, EmptyDeckRule, MulliganRule, DEFAULT_JOKER_PENALTY };
+    ///
+    /// let config = Config {
+    ///     n_decks: 0,
+    ///     n_jokers: 4,
+    ///     n_cards_to_start: 13,
+    ///     custom_rule_jokers: false,
+    ///     n_players: 255,
+    ///     empty_deck: EmptyDeckRule::Draw,
+    ///     joker_penalty: DEFAULT_JOKER_PENALTY,
+    ///     mulligan: MulliganRule::Off,
+    ///     draw_for_first: false
+    /// };
+    ///
+    /// assert_eq!("ConfigError: there should be at least one deck; \
+    ///             the number of players should be between 1 and 16; \
+    ///             255 hands of 13 cards need more than the 4 cards of the deck",
+    ///            config.validate().unwrap_err().to_string());
+    /// ```
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::<String>::new();
+        if self.n_decks == 0 {
+            problems.push("there should be at least one deck".to_string());
+        }
+        if self.n_players == 0 || self.n_players > MAX_N_PLAYERS {
+            problems.push(format!("the number of players should be between 1 and {}", MAX_N_PLAYERS));
+        }
+        if self.n_cards_to_start == 0 {
+            problems.push("each player should start with at least one card".to_string());
+        }
+        let n_cards = N_CARDS_PER_DECK * (self.n_decks as usize) + (self.n_jokers as usize);
+        if (self.n_players as usize) * (self.n_cards_to_start as usize) >= n_cards {
+            problems.push(format!("{} hands of {} cards need more than the {} cards of the deck",
+                                  self.n_players, self.n_cards_to_start, n_cards));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError { message: problems.join("; ") })
         }
     }
+
+    /// Parameters of a valid config which make for odd games, and should be confirmed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::parse_config;
+    ///
+    /// let (config, _) = parse_config("n_decks = 1\nn_jokers = 200\n").unwrap();
+    /// assert_eq!(vec!["200 of the 252 cards are jokers, so most sequences will be made of jokers".to_string()],
+    ///            config.warnings());
+    ///
+    /// let (config, _) = parse_config("").unwrap();
+    /// assert!(config.warnings().is_empty());
+    /// ```
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::<String>::new();
+        let n_cards = N_CARDS_PER_DECK * (self.n_decks as usize) + (self.n_jokers as usize);
+        let n_left = n_cards.saturating_sub((self.n_players as usize) * (self.n_cards_to_start as usize));
+        if 4 * (self.n_jokers as usize) > n_cards {
+            warnings.push(format!("{} of the {} cards are jokers, so most sequences will be made of jokers",
+                                  self.n_jokers, n_cards));
+        }
+        if 10 * n_left < n_cards {
+            warnings.push(format!("only {} cards are left in the deck once the hands are dealt", n_left));
+        }
+        if self.n_cards_to_start < 3 {
+            warnings.push(format!("hands of {} cards are too small to hold a sequence", self.n_cards_to_start));
+        }
+        warnings
+    }
+
+    /// Summary of the cards of the game and of what a starting hand holds
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::parse_config;
+    ///
+    /// let (config, _) = parse_config("").unwrap();
+    /// assert_eq!("2 decks and 4 jokers: 108 cards, 4% of which are jokers. 2 hands of 13 cards leave 82 \
+    ///             cards in the deck. A starting hand holds 0.5 jokers on average, and at least one 41% of the time.",
+    ///            config.describe());
+    /// ```
+    pub fn describe(&self) -> String {
+        let n_cards = N_CARDS_PER_DECK * (self.n_decks as usize) + (self.n_jokers as usize);
+        let n_dealt = (self.n_players as usize) * (self.n_cards_to_start as usize);
+        let jokers_share = if n_cards == 0 { 0. } else { self.n_jokers as f64 / n_cards as f64 };
+
+        // probability that a hand has no joker, drawing its cards one at a time
+        let p_no_joker: f64 = (0..self.n_cards_to_start as usize)
+            .map(|i| (n_cards.saturating_sub(self.n_jokers as usize + i)) as f64 / n_cards.saturating_sub(i).max(1) as f64)
+            .product();
+
+        format!("{} deck{} and {} joker{}: {} cards, {:.0}% of which are jokers. {} hand{} of {} cards leave{} {} \
+                 cards in the deck. A starting hand holds {:.1} jokers on average, and at least one {:.0}% of the time.",
+                self.n_decks, if self.n_decks == 1 { "" } else { "s" },
+                self.n_jokers, if self.n_jokers == 1 { "" } else { "s" },
+                n_cards, 100. * jokers_share,
+                self.n_players, if self.n_players == 1 { "" } else { "s" }, self.n_cards_to_start,
+                if self.n_players == 1 { "s" } else { "" }, n_cards.saturating_sub(n_dealt),
+                jokers_share * self.n_cards_to_start as f64, 100. * (1. - p_no_joker))
+            + match self.mulligan {
+                MulliganRule::Off => "",
+                MulliganRule::Free => " Each player can reject their hand once.",
+                MulliganRule::Penalty => " Each player can reject their hand once, and then gets one more card."
+            }
+            + if self.draw_for_first { " The player drawing the highest card plays first." } else { "" }
+    }
+}
+
+/// Value given after a command-line flag, and the arguments without the flag and its value
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::take_flag_value;
+///
+/// let args: Vec<String> = vec!["server", "--resume-dir", "saves", "1"]
+///     .into_iter().map(String::from).collect();
+///
+/// assert_eq!((Some("saves".to_string()), vec!["server".to_string(), "1".to_string()]),
+///            take_flag_value(args, "--resume-dir"));
+/// ```
+pub fn take_flag_value(args: Vec<String>, flag: &str) -> (Option<String>, Vec<String>) {
+    let mut value = None;
+    let mut rest = Vec::<String>::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            value = args.next();
+        } else {
+            rest.push(arg);
+        }
+    }
+    (value, rest)
 }
 
 /// get the vector of player names from a file
@@ -122,176 +817,369 @@ pub fn load_names(fname: &str) -> Result<Vec<String>, InvalidInputError> {
 /// save the vector of player names to a file
 pub fn save_names(names: &Vec<String>, fname: &str) -> Result<(), InvalidInputError> {
     let names_single_string = names.join("\n");
-    let mut file = std::fs::File::create(fname)?;
-    file.write_all(names_single_string.as_bytes())?;
+    write_atomically(fname, names_single_string.as_bytes())?;
     Ok(())
 }
 
-fn first_word(s: &str) -> Result<String,InvalidInputError> {
-    match s.split(' ').next() {
-        Some(res) => Ok(res.to_string()),
-        None => Err(InvalidInputError {})
+/// Replace the content of a file, without leaving it half-written if the process stops
+///
+/// The bytes are written to a temporary file in the same directory, which is flushed to the
+/// disk and then renamed over `path`. If anything fails, the file keeps its previous content.
+///
+/// # Example
+///
+/// ```
+/// let path = std::env::temp_dir().join(format!("machiavelli_atomic_doc_{}", std::process::id()));
+/// machiavelli::write_atomically(&path, b"first").unwrap();
+/// machiavelli::write_atomically(&path, b"second").unwrap();
+/// assert_eq!(b"second".to_vec(), std::fs::read(&path).unwrap());
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn write_atomically<P: AsRef<std::path::Path>>(path: P, bytes: &[u8]) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let name = path.file_name().ok_or_else(|| 
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "the path has no file name"))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+    let written = std::fs::File::create(&temp_path)
+        .and_then(|mut file| file.write_all(bytes).and_then(|_| file.sync_all()))
+        .and_then(|_| std::fs::rename(&temp_path, path));
+    if written.is_err() {
+        std::fs::remove_file(&temp_path).unwrap_or(());
+        return written;
+    }
+
+    // make the rename itself durable
+    #[cfg(unix)]
+    {
+        let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or_else(|| std::path::Path::new("."));
+        std::fs::File::open(dir).and_then(|d| d.sync_all()).unwrap_or(());
     }
+    Ok(())
+}
+
+/// file with the game config
+pub const CONFIG_FILE: &str = "Config/config.toml";
+
+/// file with the game config in the legacy format, used if `CONFIG_FILE` is missing
+pub const LEGACY_CONFIG_FILE: &str = "Config/config.dat";
+
+/// name of the save file (without extension) used when none is given
+pub const DEFAULT_SAVEFILE: &str = "machiavelli_save";
+
+/// Parse a game config written as `key = value` pairs, and return it with the name of the save
+/// file
+///
+/// The keys are `n_decks`, `n_jokers`, `n_cards_to_start`, `custom_rule_jokers`, `n_players`,
+/// `empty_deck`, `joker_penalty`, `mulligan`, `draw_for_first`, and `savefile`. Missing keys take
+/// their value in `Config::default()`, and the save file is `DEFAULT_SAVEFILE`. With the `preset`
+/// key,
+/// they take the values of one of the `PRESETS` instead, wherever the key is in the file; the
+/// size of the hands then depends on the number of players, unless `n_cards_to_start` is given.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::*;
+///
+/// let (config, savefile) = parse_config("n_players = 3\nsavefile = \"game\"\n").unwrap();
+///
+/// assert_eq!(3, config.n_players);
+/// assert_eq!(2, config.n_decks);
+/// assert_eq!("game", savefile);
+///
+/// let (config, _) = parse_config("n_players = 3\njoker_penalty = 40\npreset = \"strict\"\n").unwrap();
+/// assert_eq!(Config { n_players: 3, joker_penalty: 40, ..Config::strict(3) }, config);
+///
+/// let (config, _) = parse_config("preset = \"classic\"\nn_players = 5\n").unwrap();
+/// assert_eq!(11, config.n_cards_to_start);
+/// let (config, _) = parse_config("preset = \"classic\"\nn_players = 5\nn_cards_to_start = 13\n").unwrap();
+/// assert_eq!(13, config.n_cards_to_start);
+///
+/// let error = parse_config("n_decks = 2\nn_jokers = -1\n").unwrap_err();
+/// assert_eq!("ConfigError: line 2: `n_jokers` should be an integer between 0 and 255",
+///            error.to_string());
+/// ```
+pub fn parse_config(content: &str) -> Result<(Config, String), ConfigError> {
+    let mut config = Config::default();
+    let mut savefile = DEFAULT_SAVEFILE.to_string();
+    let entries = toml::parse(content)?;
+
+    // the other keys change the preset, whose hands depend on the number of players
+    if let Some(entry) = entries.iter().find(|e| e.section.is_none() && e.key == "preset") {
+        let n_players = match entries.iter().find(|e| e.section.is_none() && e.key == "n_players") {
+            Some(e) => e.integer(1, 255)? as u8,
+            None => config.n_players
+        };
+        let names: Vec<String> = PRESETS.iter().map(|(key, _)| format!("\"{}\"", key)).collect();
+        config = Config::preset(&entry.string()?, n_players).ok_or_else(||
+            entry.error(&format!("`preset` should be one of {}", names.join(", "))))?;
+    }
+
+    for entry in entries {
+        if let Some(section) = &entry.section {
+            return Err(ConfigError::from(entry.error(&format!("unknown section `{}`", section))));
+        }
+        match entry.key.as_str() {
+            "preset" => (),
+            "n_decks" => config.n_decks = entry.integer(1, 255)? as u8,
+            "n_jokers" => config.n_jokers = entry.integer(0, 255)? as u8,
+            "n_cards_to_start" => config.n_cards_to_start = entry.integer(1, 65535)? as u16,
+            "custom_rule_jokers" => config.custom_rule_jokers = entry.boolean()?,
+            "n_players" => config.n_players = entry.integer(1, 255)? as u8,
+            "empty_deck" => config.empty_deck = EmptyDeckRule::parse(&entry.string()?).ok_or_else(||
+                entry.error("`empty_deck` should be \"draw\", \"score\", or \"new_deck\""))?,
+            "joker_penalty" => config.joker_penalty = entry.integer(0, 65535)? as u16,
+            "mulligan" => config.mulligan = MulliganRule::parse(&entry.string()?).ok_or_else(||
+                entry.error("`mulligan` should be \"off\", \"free\", or \"penalty\""))?,
+            "draw_for_first" => config.draw_for_first = entry.boolean()?,
+            "savefile" => savefile = entry.string()?,
+            key => return Err(ConfigError::from(entry.error(&format!("unknown key `{}`", key))))
+        }
+    }
+    config.validate()?;
+    Ok((config, savefile))
+}
+
+/// Parse a game config in the legacy format, where each line starts with a value:
+/// number of decks, number of jokers, number of cards to start with, custom rule for the jokers
+/// (`1` for yes), number of players, and name of the save file
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::*;
+///
+/// let (config, savefile) = parse_legacy_config("1 deck\n0 jokers\n10 cards\n0\n3\ngame\n").unwrap();
+///
+/// assert_eq!(3, config.n_players);
+/// assert!(!config.custom_rule_jokers);
+/// assert_eq!("game", savefile);
+///
+/// let error = parse_legacy_config("2 decks\n4 jokers\nthirteen cards\n").unwrap_err();
+///
+/// assert_eq!("ConfigError: line 3: the number of cards to start with should be an integer",
+///            error.to_string());
+/// ```
+pub fn parse_legacy_config(content: &str) -> Result<(Config, String), ConfigError> {
+    let words: Vec<&str> = content.lines().map(|l| l.split(' ').next().unwrap_or("")).collect();
+    let word = |i: usize, name: &str| -> Result<&str, ConfigError> {
+        match words.get(i) {
+            Some(w) if !w.is_empty() => Ok(w),
+            _ => Err(ConfigError { message: format!("line {}: missing {}", i + 1, name) })
+        }
+    };
+    let number = |i: usize, name: &str| -> Result<u16, ConfigError> {
+        word(i, name)?.parse::<u16>().map_err(|_| 
+            ConfigError { message: format!("line {}: {} should be an integer", i + 1, name) })
+    };
+    let small_number = |i: usize, name: &str| -> Result<u8, ConfigError> {
+        match number(i, name)? {
+            n if n <= 255 => Ok(n as u8),
+            _ => Err(ConfigError { message: format!("line {}: {} should be at most 255", i + 1, name) })
+        }
+    };
+    let config = Config {
+        n_decks: small_number(0, "the number of decks")?,
+        n_jokers: small_number(1, "the number of jokers")?,
+        n_cards_to_start: number(2, "the number of cards to start with")?,
+        custom_rule_jokers: word(3, "the custom rule for the jokers")? == "1",
+        n_players: small_number(4, "the number of players")?,
+        empty_deck: EmptyDeckRule::Draw,
+        joker_penalty: DEFAULT_JOKER_PENALTY,
+        mulligan: MulliganRule::Off,
+        draw_for_first: false
+    };
+    config.validate()?;
+    Ok((config, word(5, "the name of the save file")?.to_string()))
 }
 
-/// load the config from a file
-pub fn get_config_from_file(fname: &str) -> Result<(Config,String),InvalidInputError> {
+/// load the config from a file, in either format
+///
+/// The legacy format is assumed if no line contains `=`.
+pub fn get_config_from_file(fname: &str) -> Result<(Config,String),ConfigError> {
     
     // open the file
     let content = std::fs::read_to_string(fname)?;
-    let content: Vec<&str> = content.split("\n").collect();
-
-    // check that the file has at least the right number of lines
-    if content.len() < 6 {
-        return Err(InvalidInputError {});
-    }
 
     // get the config
-    let n_decks = first_word(&content[0])?.parse::<u8>()?;
-    let n_jokers = first_word(&content[1])?.parse::<u8>()?;
-    let n_cards_to_start = first_word(&content[2])?.parse::<u16>()?;
-    let custom_rule_jokers = first_word(&content[3])? == "1";
-    let n_players = first_word(&content[4])?.parse::<u8>()?;
-    let savefile = first_word(&content[5])?;
+    let (config, savefile) = if content.lines().any(|l| l.contains('=')) {
+        parse_config(&content)?
+    } else {
+        parse_legacy_config(&content)?
+    };
    
     // print the parameters
-    println!("{}: {}\n{}: {}\n{}: {}\n{}: {}\n{}: {}\n{}: {}",
+    println!("{}: {}\n{}: {}\n{}: {}\n{}: {}\n{}: {}\n{}: {}\n{}: {}\n{}: {}",
              "Number of decks",
-             n_decks,
+             config.n_decks,
              "Number of jokers",
-             n_jokers,
+             config.n_jokers,
              "Number of starting cards",
-             n_cards_to_start,
+             config.n_cards_to_start,
              "Jokers can't be kept",
-             custom_rule_jokers,
+             config.custom_rule_jokers,
              "Number of players",
-             n_players,
+             config.n_players,
+             "When the deck is empty",
+             config.empty_deck.name(),
+             "Points of a joker left in a hand",
+             config.joker_penalty,
              "Savefile", 
              savefile);
 
-    Ok((Config {
-        n_decks,
-        n_jokers,
-        n_cards_to_start,
-        custom_rule_jokers,
-        n_players
-    }, savefile.to_string()))
+    Ok((config, savefile))
 }
 
 /// ask the user for the game information and savefile name
 pub fn get_config_and_savefile() -> Result<(Config, String),InvalidInputError> {
-    let conf = get_config()?;
-    println!("Name of the save file: ");
-    let savefile = get_input()?.trim().to_string();
-    Ok((conf, savefile))
+    get_config_from(&mut prompt::Prompter::new(prompt::terminal_input()), true)
 }
 
 /// ask the user for the game information and return a Config
 pub fn get_config() -> Result<Config,InvalidInputError> {
-    
-    println!("Number of decks (integer between 1 and 255) (enter 0 to load a previously saved game): ");
-    let mut n_decks: u8 = 0;
-    let mut load = false;
-    while n_decks == 0 {
-        n_decks = match get_input()?.trim().parse::<u8>() {
-            Ok(0) => {
-                load = true;
-                1
-            },
-            Ok(n) => n,
-            Err(_) => {
-                println!("Invalid input");
-                0
-            }
-        };
-    }
+    Ok(get_config_from(&mut prompt::Prompter::new(prompt::terminal_input()), false)?.0)
+}
 
-    if load {
-        return Ok(Config {
-            n_decks: 0,
-            n_jokers: 0,
-            n_cards_to_start: 0,
-            custom_rule_jokers: false,
-            n_players: 0
-        });
-    }
-    
-    println!("Number of jokers (integer between 0 and 255): ");
-    let mut n_jokers: u8 = 0; 
-    let mut set = false;
-    while !set {
-        n_jokers = match get_input()?.trim().parse::<u8>() {
-            Ok(n) => {
-                set = true;
-                n
+/// Ask the game information, and optionally the name of the save file, with a prompter
+///
+/// A config with 0 decks is returned if the user wants to load a saved game. The name of the
+/// save file is `DEFAULT_SAVEFILE` if it is not asked. The values offered first are those of
+/// `Config::default()`, as in a config file.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::*;
+/// use machiavelli::prompt::Prompter;
+///
+/// // defaults for the decks and jokers, 20 cards, then back to change the jokers
+/// let answers = "\n\n20\n<\n<\n0\n10\nn\n3\nscore\ngame\n";
+/// let (config, savefile) = get_config_from(&mut Prompter::new(answers.as_bytes()), true).unwrap();
+///
+/// assert_eq!(0, config.n_jokers);
+/// assert_eq!(10, config.n_cards_to_start);
+/// assert_eq!(3, config.n_players);
+/// assert_eq!(EmptyDeckRule::Score, config.empty_deck);
+/// assert_eq!("game", savefile);
+///
+/// // keeping every value gives the same game as an empty config file
+/// let (config, _) = get_config_from(&mut Prompter::new("\n\n\n\n\n\n".as_bytes()), false).unwrap();
+/// assert_eq!(parse_config("").unwrap().0, config);
+/// ```
+pub fn get_config_from<R: std::io::BufRead>(prompter: &mut prompt::Prompter<R>, ask_savefile: bool)
+    -> Result<(Config, String), InvalidInputError>
+{
+    use prompt::Answer::{ Value, Back };
+
+    let mut config = Config::default();
+    let mut savefile = DEFAULT_SAVEFILE.to_string();
+    let n_steps = if ask_savefile { 7 } else { 6 };
+
+    println!("(Press Enter to keep the value in brackets, or type {} to go back to the previous question)",
+             prompt::BACK);
+    let mut step = 0;
+    while step < n_steps {
+        let answer = match step {
+            0 => prompter.u8("Number of decks (enter 0 to load a previously saved game)", 0..=255,
+                             Some(config.n_decks))?
+                .map(|n| config.n_decks = n),
+            1 => prompter.u8("Number of jokers", 0..=255, Some(config.n_jokers))?
+                .map(|n| config.n_jokers = n),
+            2 => {
+                let max = 52 * (config.n_decks as u16) + (config.n_jokers as u16);
+                prompter.u16("Number of cards to start with", 1..=max, Some(config.n_cards_to_start.min(max)))?
+                    .map(|n| config.n_cards_to_start = n)
             },
-            Err(_) => {
-                println!("Invalid input");
-                0
-            }
+            3 => prompter.yes_no("Custom rule—jokers must be played immediately", config.custom_rule_jokers)?
+                .map(|b| config.custom_rule_jokers = b),
+            4 => prompter.u8("Number of players", 1..=MAX_N_PLAYERS, Some(config.n_players))?
+                .map(|n| config.n_players = n),
+            5 => prompter.choice("When the deck is empty", &["draw", "score", "new_deck"], 
+                                 config.empty_deck.name())?
+                .map(|s| config.empty_deck = EmptyDeckRule::parse(&s).unwrap_or(EmptyDeckRule::Draw)),
+            _ => prompter.text("Name of the save file", Some(&savefile))?
+                .map(|s| savefile = s)
         };
-    }
-    
-    println!("Number of cards to start with (integer): ");
-    let mut n_cards_to_start: u16 = 0;
-    while n_cards_to_start == 0 {
-        n_cards_to_start = match get_input()?.trim().parse::<u16>() {
-            Ok(n) => {
-                let mut res = 0;
-                if n==0 {
-                    println!("You need to start with at least one card");
-                } else if n > ((52 * (n_decks as u16)) + (n_jokers as u16)) {
-                    println!("You can't draw more cards than there are in the deck");
-                } else {
-                    res = n;
+        match answer {
+            Value(()) if step == 0 && config.n_decks == 0 => {
+                return Ok((Config {
+                    n_decks: 0,
+                    n_jokers: 0,
+                    n_cards_to_start: 0,
+                    custom_rule_jokers: false,
+                    n_players: 0,
+                    empty_deck: EmptyDeckRule::Draw,
+                    joker_penalty: DEFAULT_JOKER_PENALTY,
+                    mulligan: MulliganRule::Off,
+                    draw_for_first: false
+                }, savefile));
+            },
+            Value(()) if step == 4 => match config.validate() {
+                Ok(()) => {
+                    println!("{}", config.describe());
+                    let warnings = config.warnings();
+                    for warning in &warnings {
+                        println!("Warning: {}", warning);
+                    }
+                    if warnings.is_empty() || prompter.confirm("Keep these settings anyway?") {
+                        step += 1;
+                    } else {
+                        step = 0;
+                    }
+                },
+                Err(e) => {
+                    // the other constraints are enforced by the questions
+                    println!("{}; please choose fewer cards", e.message);
+                    step = 2;
                 }
-                res
             },
-            Err(_) => return Err(InvalidInputError {})
-        };
-    }
-    
-    println!("Custom rule—jokers must be played immediately (y/n): ");
-    let custom_rule_jokers = match get_input()?.trim() {
-        "y" => true,
-        _ => false
-    };
-    
-    println!("Number of players: ");
-    let mut n_players = 0;
-    while n_players == 0 {
-        n_players = match get_input()?.trim().parse::<u8>() {
-            Ok(0) => {
-                println!("I need at least one player!");
-                0
-            }
-            Ok(n) => n,
-            Err(_) => {
-                println!("Could not parse the input");
-                0
-            }
-        };
+            Value(()) => step += 1,
+            Back if step == 0 => println!("This is the first question"),
+            Back => step -= 1
+        }
     }
 
-    Ok(Config {
-        n_decks, 
-        n_jokers,
-        n_cards_to_start,
-        custom_rule_jokers,
-        n_players
-    })
+    Ok((config, savefile))
 }
 
-fn instructions() -> String {
-    format!("{}\n{}\n{}\n{}\n{}\n{}\n{}\n",
-        "q: Save and quit",
-        "c: Pick a card",
-        "p: Play a sequence",
-        "t: Take from the table",
-        "a: Pass",
-        "r, s: Sort cards by rank or suit",
-        "g: Give up and reset"
-        )
+/// instruction to turn the pages of the table and of the hand
+const PAGES_INSTRUCTION: &str = ">, <: Next or previous page of the table (> h, < h: of your hand)";
+
+/// instruction to play several actions at once (see the `commands` module)
+const LIST_INSTRUCTION: &str = "take 3; play 1 2 5; end: Play several actions at once, all of them or none";
+
+/// instructions for the local game, with the options the player can't use now greyed out
+fn instructions(actions: &[Action]) -> String {
+    let can_play = actions.iter().any(|a| matches!(a, Action::Play(_)));
+    let can_take = actions.iter().any(|a| matches!(a, Action::Take(_)));
+    let can_extend = actions.iter().any(|a| matches!(a, Action::Extend(..)));
+    let can_split = actions.iter().any(|a| matches!(a, Action::Split(..)));
+    [
+        ("q: Save and quit", actions.contains(&Action::Save)),
+        ("c: Pick a card", actions.contains(&Action::Draw)),
+        ("p: Play a sequence", can_play),
+        ("t: Take from the table", can_take),
+        ("x: Add cards to a sequence of the table", can_extend),
+        ("d: Split a sequence of the table in two", can_split),
+        (LIST_INSTRUCTION, true),
+        ("a: Pass", actions.contains(&Action::Pass)),
+        ("r, s: Sort cards by rank or suit", true),
+        ("o: Odds of drawing a useful card", true),
+        ("u: Cards you have not seen", true),
+        ("h: Hint: the most cards you could lay down this turn", true),
+        ("/find 7♦: Find a card (or a rank or a suit) in your hand and on the table", true),
+        (PAGES_INSTRUCTION, true),
+        ("g: Give up and reset", actions.contains(&Action::GiveUp))
+    ].iter()
+        .map(|(option, available)| if *available {
+            format!("{}\n", option)
+        } else {
+            format!("\x1b[2m{}{}\n", option, reset_style_string())
+        })
+        .collect()
 }
 
 pub fn instructions_no_save(must_pick_a_card: bool, print_reset_option: bool) 
@@ -305,141 +1193,326 @@ pub fn instructions_no_save(must_pick_a_card: bool, print_reset_option: bool)
     if print_reset_option {
         reset_option = &"g: Give up and reset\n";
     }
-    format!("{}{}\n{}\n{}\n{}\n{}\n{}\n",
+    format!("{}{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n",
         "e: End your turn",
         will_pick_a_card,
         "p x y ...: Play the sequence x y ...",
         "t x y ...: Take the sequences x, y, ... from the table",
-        "a x y z ...: Add the sequence y z ... to sequence x on the table",
+        "a x y z ...: Add the cards y z ... to sequence x on the table (a x < y z ...: before its cards)",
+        "d x y: Split sequence x on the table after its first y cards",
+        LIST_INSTRUCTION,
         "r, s: Sort cards by rank or suit",
+        "l line/wrap/suit/grid: Change the layout of your hand",
+        PAGES_INSTRUCTION,
         reset_option
         )
 }
 
-pub fn player_turn(table: &mut Table, hand: &mut Sequence, deck: &mut Sequence, 
-                   custom_rule_jokers: bool, player_name: &String) -> bool {
+/// Turn of a player of the local game, from its start or resumed from a save
+///
+/// If the player saves and quits, the turn is returned as it is, so that it can be saved too.
+pub fn player_turn(table: &mut Table, hand: &mut Sequence, deck: &mut Deck, 
+                   config: &Config, player: &PlayerView, log: &mut EventLog, tracker: &mut CardTracker,
+                   settings: &settings::Settings, resumed: Option<Ongoing>) -> Option<Ongoing> {
 
-    // copy the initial hand
-    let hand_start_round = hand.clone();
-    
-    // copy the initial table
-    let table_start_round = table.clone();
+    let player_name = &player.name;
+    let custom_rule_jokers = config.custom_rule_jokers;
+
+    // the hand and the table at the start of the turn are kept to give up
+    let mut turn = resumed.unwrap_or_else(|| Ongoing::start(table, hand));
+
+    // only the hand of the player changes during the turn, so it is counted with the table and the deck
+    let ledger = CardLedger::new(table, &[] as &[Hand], deck, hand);
+
+    // pages of the table and of the hand, if they do not fit on the screen
+    let mut pages = Pages::new();
 
     // get the player choice
     let mut message = String::new();
     loop {
+        ledger.assert_holds(table, &[] as &[Hand], deck, hand, "an action");
         
+        // size of the screen, read again at each redraw in case the terminal was resized
+        let screen = LayoutSettings::of_terminal(layout::HandLayout::Line);
+
         // clear the terminal
         clear_terminal();
         
-        println!("\x1b[1m{}'s turn", player_name);
+        // the lines are kept to find what is clicked (see the `mouse` module)
+        mouse::print_line(&format!("\x1b[1m{}'s turn", colours::paint(player_name)));
         reset_style();
         
-        print_situation(table, hand, deck);
+        print_situation(table, hand, deck, player, &screen, &pages);
+
+        // print the recent events
+        mouse::print_line(&log.render(N_EVENTS_SHOWN));
+        reset_style();
 
         // print the options
-        println!("{}", &instructions());
+        let actions = turn.legal_actions(table, hand, &Sequence::new(), custom_rule_jokers);
+        mouse::print_line(&instructions(&actions));
         
         if message.len() > 0 {
-            println!("\n{}", message);
+            mouse::print_line(&format!("\n{}", message));
             message.clear()
         }
+
+        // offer to finish the turn when there is nothing else to do
+        let forced = turn.is_forced(table, hand, custom_rule_jokers);
+        if forced {
+            mouse::print_line("\nNothing can be played: press Enter to pick a card and end your turn");
+        }
         
-        match get_input().unwrap_or_else(|_| {"".to_string()}).trim() {
-            "q" => {
-                if !hand_start_round.contains(hand) {
-                    message = "You can't save until you've played all the cards you've taken from the table!".to_string();
-                } else if !hand.contains(&hand_start_round) {
-                    message = "You need to pass before saving".to_string();
-                } else {
-                    return true;
+        let mut input = settings.translate_input(get_input().unwrap_or_else(|_| {"".to_string()}).trim());
+        if forced && input.is_empty() {
+            input = "c".to_string();
+        }
+
+        // a list of actions is played as a whole, with the cards taken from the table kept apart
+        // until it is over
+        if commands::is_list(&input) {
+            let steps = match commands::parse(&input) {
+                Ok(steps) => steps,
+                Err(m) => {
+                    message = format!("Nothing was played: {}", m);
+                    continue;
                 }
+            };
+            let mut cards_from_table = Sequence::new();
+            let cards = commands::TurnCards { table, hand, cards_from_table: &mut cards_from_table, deck };
+            match commands::execute(&steps, turn, player_name, cards, custom_rule_jokers) {
+                Ok((executed, events)) => {
+                    hand.merge(cards_from_table.reverse());
+                    for event in events {
+                        log.push(event);
+                    }
+                    match executed {
+                        commands::Executed::Ongoing(ongoing) => turn = ongoing,
+                        commands::Executed::Ended(TurnEnd::Drew(Some(card))) => {
+                            println!("You have picked a {}\x1b[38;2;0;0;0;1m ({})", card, card.long_name(&settings.locale));
+                            break
+                        },
+                        commands::Executed::Ended(_) => break
+                    }
+                },
+                Err((ongoing, m)) => {
+                    turn = ongoing;
+                    message = m;
+                }
+            }
+            continue;
+        }
+
+        turn = match (input.as_str(), turn) {
+            ("q", turn) => {
+                if !settings.confirm || prompt::Prompter::new(prompt::terminal_input()).confirm("Save and quit the game?") {
+                    return Some(turn);
+                }
+                turn
             },
-            "c" => {
-                if !hand_start_round.contains(hand) {
-                    message = "You can't pick a card until you've played all the cards you've taken from the table!".to_string();
-                } else if !hand.contains(&hand_start_round) {
-                    message = "You can't pick a card after having played something".to_string();
-                } else if custom_rule_jokers && hand.contains_joker() {
-                    message = "Jokers must be played!".to_string();
-                } else {
-                    match pick_a_card(hand, deck) {
-                        Ok(card) => println!("You have picked a {}\x1b[38;2;0;0;0;1m", &card),
-                        Err(_) => println!("No more card to draw!")
-                    };
-                    break
+            ("c", Ongoing::Draw(turn)) => {
+                match turn.draw(hand, deck, custom_rule_jokers) {
+                    Ok(end) => {
+                        match end.outcome() {
+                            TurnEnd::Drew(Some(card)) => println!("You have picked a {}\x1b[38;2;0;0;0;1m ({})", card, 
+                                                                  card.long_name(&settings.locale)),
+                            _ => println!("No more card to draw!")
+                        };
+                        log.push(Event::DrewCard(player_name.clone()));
+                        break
+                    },
+                    Err((turn, m)) => {
+                        message = m;
+                        Ongoing::Draw(turn)
+                    }
                 }
             },
-            "p" => {
-                message = play_sequence(hand, table);
-                print_situation(table, hand, deck);
+            ("c", turn) => {
+                message = "You can't pick a card after having played something".to_string();
+                turn
+            },
+            ("p", turn) => {
+                let turn = match play_sequence(hand, table, settings.confirm) {
+                    Ok(seq) => {
+                        log.push(Event::PlayedSequence(player_name.clone(), seq));
+                        turn.meld()
+                    },
+                    Err(m) => {
+                        message = m;
+                        turn
+                    }
+                };
+                print_situation(table, hand, deck, player, &screen, &pages);
+                turn
+            },
+            ("x", turn) => {
+                let turn = match extend_sequence(table, hand, settings.confirm) {
+                    Ok((n, seq)) => {
+                        log.push(Event::AddedToSequence(player_name.clone(), n, seq));
+                        turn.meld()
+                    },
+                    Err(m) => {
+                        message = m;
+                        turn
+                    }
+                };
+                print_situation(table, hand, deck, player, &screen, &pages);
+                turn
+            },
+            ("d", turn) => {
+                let turn = match split_sequence(table, hand) {
+                    Ok((n, index, taken)) => {
+                        log.push(Event::SplitSequence(player_name.clone(), n, index, taken));
+                        turn.meld()
+                    },
+                    Err(m) => {
+                        message = m;
+                        turn
+                    }
+                };
+                print_situation(table, hand, deck, player, &screen, &pages);
+                turn
             },
-            "t" => {
-                message = take_sequence(table, hand);
-                print_situation(table, hand, deck);
+            ("t", turn) => {
+                let turn = match take_sequence(table, hand, settings.confirm) {
+                    Ok((n, seq)) => {
+                        log.push(Event::TookSequence(player_name.clone(), n, seq));
+                        turn.meld()
+                    },
+                    Err(m) => {
+                        message = m;
+                        turn
+                    }
+                };
+                print_situation(table, hand, deck, player, &screen, &pages);
+                turn
             },
-            "a" => {
-                if !hand_start_round.contains(hand) {
-                    message = "You can't pass until you've played all the cards you've taken from the table!".to_string();
-                } else if hand.contains(&hand_start_round) {
-                    message = "You need to play something to pass".to_string();
-                } else if custom_rule_jokers && hand.contains_joker() {
-                    message = "Jokers need to be played!".to_string();
-                } else {
-                    break
+            ("a", Ongoing::Meld(turn)) => {
+                match turn.pass(hand, &Sequence::new(), custom_rule_jokers) {
+                    Ok(_) => {
+                        log.push(Event::EndedTurn(player_name.clone()));
+                        break
+                    },
+                    Err((turn, m)) => {
+                        message = m;
+                        Ongoing::Meld(turn)
+                    }
                 }
-            }
-            "r" => {
+            },
+            ("a", turn) => {
+                message = "You need to play something to pass".to_string();
+                turn
+            },
+            ("r", turn) => {
                 hand.sort_by_rank();
-                print_situation(table, hand, deck);
+                print_situation(table, hand, deck, player, &screen, &pages);
+                turn
             },
-            "s" => {
+            ("s", turn) => {
                 hand.sort_by_suit();
-                print_situation(table, hand, deck);
+                print_situation(table, hand, deck, player, &screen, &pages);
+                turn
+            },
+            ("o", turn) => {
+                tracker.sync(log);
+                message = odds::draw_odds(hand, table, &tracker.held_by_others(player_name), config.n_decks,
+                                          config.n_jokers, deck.remaining()).render();
+                turn
             },
-            "g" => {
-                give_up(table, hand, deck, &hand_start_round, &table_start_round, &mut Sequence::new());
-                print_situation(table, hand, deck);
+            ("u", turn) => {
+                tracker.sync(log);
+                message = tracker.render(player_name, hand);
+                turn
             },
-            _ => ()
+            ("h", turn) => {
+                println!("Looking for the best play...");
+                message = format!("{}\n{}", bot::evaluation(hand, table, config).meter(),
+                                  solver::best_play(turn.hand_start(), turn.table_start(), &settings.hint_budget()).render());
+                turn
+            },
+            ("g", Ongoing::Meld(turn)) => {
+                turn.give_up(table, hand, deck, &mut Sequence::new());
+                log.push(Event::GaveUp(player_name.clone()));
+                break
+            },
+            ("g", turn) => {
+                message = "There is nothing to give up".to_string();
+                turn
+            },
+            (command, turn) => {
+                if let Some(arg) = search::parse_find_command(command) {
+                    message = search::answer(arg, hand, table);
+                } else if let Some((view, forward)) = parse_page_command(command) {
+                    let n_pages = match view {
+                        PagedView::Table => render_table(table, &[], 0, &screen).1,
+                        PagedView::Hand => render_cards_page(hand, &screen, 0).1
+                    };
+                    pages.turn(view, forward, n_pages);
+                }
+                turn
+            }
         };
     }
+    ledger.assert_holds(table, &[] as &[Hand], deck, hand, "the end of the turn");
 
-    false
+    None
 }
 
 
-fn print_situation(table: &Table, hand: &Sequence, deck: &Sequence) {
+fn print_situation(table: &Table, hand: &Sequence, deck: &Deck, view: &PlayerView, screen: &LayoutSettings,
+                   pages: &Pages) {
     
-    println!("\n{} cards remaining in the deck", deck.number_cards());
+    mouse::print_line(&format!("\n{}", view.header(deck.remaining())));
     
     // print the table
-    println!("Table: \n{}", table);
+    mouse::print_line(&format!("Table: \n{}", render_table(table, &view.previous_turn.changed, pages.table, screen).0));
 
     // print the player hand
-    println!("Your hand:\n{}", hand);
+    mouse::print_line(&format!("Your hand:\n{}", render_cards_page(hand, screen, pages.hand).0));
     reset_style();
 
 }
 
 
-pub fn situation_to_string(table: &Table, hand: &Sequence, 
-                           cards_from_table: &Sequence) -> String {
-  
-    let hi = hand.show_indices();
-    let ht = cards_from_table.show_indices_shifted(hand.number_cards());
+/// Situation of the game as seen by a player: what the previous player did, the status bar, the
+/// turn order, the table, and their hand, showing the pages they turned to if the table or the
+/// hand does not fit on their screen
+pub fn situation_to_string(table: &Table, hand: &Sequence, cards_from_table: &Sequence, 
+                           layout: &LayoutSettings, pages: &Pages, n_cards_deck: usize, view: &PlayerView)
+    -> String
+{
+    render_situation(&view.header(n_cards_deck), table, &view.previous_turn.changed, hand, cards_from_table,
+                     layout, pages)
+}
+
+/// Situation of the game below a header, with the sequences of the table in `changed` marked
+///
+/// A network client draws the situation from its copy of the public state with this, as the
+/// server only sends it the header, its hand, and the sequences changed by the previous turn.
+pub fn render_situation(bar: &str, table: &Table, changed: &[Sequence], hand: &Sequence,
+                        cards_from_table: &Sequence, layout: &LayoutSettings, pages: &Pages) -> String
+{
+    let table = render_table(table, changed, pages.table, layout).0;
+    let hi = render_hand_page(hand, 1, layout, pages.hand).0;
     if cards_from_table.number_cards() == 0 {
-        format!("\n{}\n{}\n{}\n{}\n{}{}\n",
-                "Table:", table, "Your hand:", hi.0, reset_style_string(), hi.1)
+        format!("\n{}\n\n{}\n{}\n{}\n{}\n",
+                bar, "Table:", table, "Your hand:", hi)
     } else {
-        format!("\n{}\n{}\n{}\n{}{}\n{}\n\n{}\n{}\n{}{}\n", 
-                "Table:", table, "Your hand:", hi.0, reset_style_string(), hi.1,
-                "Cards from the table:", ht.0, reset_style_string(), ht.1)
+        let ht = render_hand(cards_from_table, hand.number_cards() + 1, layout);
+        format!("\n{}\n\n{}\n{}\n{}\n{}\n\n{}\n{}\n", 
+                bar, "Table:", table, "Your hand:", hi,
+                "Cards from the table:", ht)
     }
 }
 
 
+/// Read a line from the terminal, with line editing and history if the `line_editing` feature is
+/// enabled
 pub fn get_input() -> Result<String, InvalidInputError> {
+    #[cfg(feature = "line_editing")]
+    if let Some(line) = line_editor::read_line() {
+        return line.map_err(|_| InvalidInputError {});
+    }
     let mut buffer = String::new();
     match stdin().read_line(&mut buffer) {
         Ok(_) => (),
@@ -449,8 +1522,8 @@ pub fn get_input() -> Result<String, InvalidInputError> {
 }
 
 
-fn pick_a_card(hand: &mut Sequence, deck: &mut Sequence) -> Result<Card, NoMoreCards> {
-    let card = match deck.draw_card() {
+fn pick_a_card(hand: &mut Sequence, deck: &mut Deck) -> Result<Card, NoMoreCards> {
+    let card = match deck.draw() {
         Some(c) => c,
         None => return Err(NoMoreCards {})
     };
@@ -459,65 +1532,101 @@ fn pick_a_card(hand: &mut Sequence, deck: &mut Sequence) -> Result<Card, NoMoreC
 }
 
 
-fn play_sequence(hand: &mut Sequence, table: &mut Table) -> String {
-    println!("Please enter the sequence, separated by spaces");
-    let hand_and_indices = hand.show_indices();
-    println!("{}", hand_and_indices.0);
+/// lay down a sequence from the hand, showing it and asking for confirmation if `confirm` is set
+fn play_sequence(hand: &mut Sequence, table: &mut Table, confirm: bool) -> Result<Sequence, String> {
+    mouse::print_line("Please enter the sequence, separated by spaces");
+    mouse::print_line(&format!("Your hand:\n{}", render_hand(hand, 1, &LayoutSettings::of_terminal(layout::HandLayout::Wrap))));
     reset_style();
-    println!("{}", hand_and_indices.1);
-    let mut seq = Sequence::new();
     
-    let mut s = get_input().unwrap_or_else(|_| {"".to_string()});
-    s.pop();
-    let mut seq_i = Vec::<usize>::new();
-    for item in s.split(' ') {
-        match item.parse::<usize>() {
-            Ok(n) => {
-                let mut n_i = 0;
-                for &i in &seq_i {
-                    if i < n {
-                        n_i += 1;
-                    }
-                }
-                let card = match hand.take_card(n-n_i) {
-                    Some(c) => c,
-                    None => continue
-                };
-                seq.add_card(card);
-                seq_i.push(n);
-            },
-            Err(_) => ()
+    // the whole input is rejected if a card can not be found
+    let s = get_input().unwrap_or_else(|_| {"".to_string()});
+    let indices = selection::parse_indices(&s, hand.number_cards())
+        .map_err(|e| format!("Nothing was played: {}", e))?;
+    let preview = selection::PlayPreview::new(selection::take_cards(&mut [hand], &indices), None);
+    if let Err(message) = preview.validity {
+        hand.merge(preview.sequence);
+        return Err(message);
+    }
+    if confirm {
+        mouse::print_line(&preview.render());
+        if !prompt::Prompter::new(prompt::terminal_input()).confirm("Play it?") {
+            hand.merge(preview.sequence);
+            return Err("Nothing was played".to_string());
         }
     }
+    table.add(preview.sequence.clone());
+    Ok(preview.sequence)
+}
 
-    if seq.is_valid() {
-        table.add(seq);
-        return String::new();
-    } else {
-        let message = format!("{} is not a valid sequence!", &seq);
-        hand.merge(seq);
-        return message;
+
+/// add cards from the hand to a sequence of the table, showing it and asking for confirmation if
+/// `confirm` is set
+fn extend_sequence(table: &mut Table, hand: &mut Sequence, confirm: bool) -> Result<(usize, Sequence), String> {
+    mouse::print_line("Please enter the sequence of the table, then the cards to add to it (< before the cards \
+                       puts them first)");
+    mouse::print_line(&format!("Your hand:\n{}", render_hand(hand, 1, &LayoutSettings::of_terminal(layout::HandLayout::Wrap))));
+    reset_style();
+
+    let s = get_input().unwrap_or_else(|_| {"".to_string()});
+    let (n, position, indices) = selection::parse_extension(&s, table.number_sequences(), hand.number_cards())
+        .map_err(|e| format!("Nothing was added: {}", e))?;
+    let added = selection::take_cards(&mut [hand], &indices);
+    let target = table.get(n).cloned().unwrap_or_else(Sequence::new);
+    let preview = selection::PlayPreview::extension(&target, n, &added, position);
+    if let Err(message) = preview.validity {
+        hand.merge(added.reverse());
+        return Err(message);
+    }
+    if confirm {
+        mouse::print_line(&preview.render());
+        if !prompt::Prompter::new(prompt::terminal_input()).confirm("Add the cards?") {
+            hand.merge(added.reverse());
+            return Err("Nothing was added".to_string());
+        }
     }
+    table.take(n);
+    table.add(preview.sequence);
+    Ok((n, added))
+}
+
+
+/// split a sequence of the table in two, taking into the hand the halves which are not valid
+fn split_sequence(table: &mut Table, hand: &mut Sequence) -> Result<(usize, usize, Sequence), String> {
+    mouse::print_line("Please enter the sequence of the table, then the number of its cards to keep in the first half");
+    let s = get_input().unwrap_or_else(|_| {"".to_string()});
+    let (n, index) = selection::parse_split(&s, table).map_err(|e| format!("Nothing was split: {}", e))?;
+    let taken = table.split(n, index).unwrap_or_else(Sequence::new);
+    hand.merge(taken.clone());
+    Ok((n, index, taken))
 }
 
 
-fn take_sequence(table: &mut Table, hand: &mut Sequence) -> String {
-    println!("Which sequence would you like to take?");
-    match get_input().unwrap_or_else(|_| {"".to_string()})
+/// take a sequence from the table, asking for confirmation if it is long and `confirm` is set
+fn take_sequence(table: &mut Table, hand: &mut Sequence, confirm: bool) -> Result<(usize, Sequence), String> {
+    mouse::print_line("Which sequence would you like to take?");
+    let n = match get_input().unwrap_or_else(|_| {"".to_string()})
           .trim().parse::<usize>() {
-        Ok(n) => match table.take(n) {
-            Some(seq) => {
-                hand.merge(seq);
-                return String::new();
-            },
-            None => return "This sequence is not on the table".to_string()
-        },
-        Err(_) => return "Error parsing the input!".to_string()
+        Ok(n) => n,
+        Err(_) => return Err("Error parsing the input!".to_string())
     };
+    let n_cards = table.get(n).map_or(0, |seq| seq.number_cards());
+    if confirm && n_cards >= N_CARDS_CONFIRM_TAKE {
+        let question = format!("Take this sequence of {} cards into your hand?", n_cards);
+        if !prompt::Prompter::new(prompt::terminal_input()).confirm(&question) {
+            return Err("Nothing was taken".to_string());
+        }
+    }
+    match table.take(n) {
+        Some(seq) => {
+            hand.merge(seq.clone());
+            Ok((n, seq))
+        },
+        None => Err("This sequence is not on the table".to_string())
+    }
 }
 
 
-pub fn give_up(table: &mut Table, hand: &mut Sequence, deck: &mut Sequence, 
+pub fn give_up(table: &mut Table, hand: &mut Sequence, deck: &mut Deck, 
                hand_start_round: &Sequence, table_start_round: &Table,
                cards_from_table: &mut Sequence) {
     
@@ -539,15 +1648,69 @@ pub fn give_up(table: &mut Table, hand: &mut Sequence, deck: &mut Sequence,
 }
 
 
+/// flag, in the fifth byte of a saved game (that of the config with the rules), of a turn in
+/// progress written before the table
+const TURN_IN_PROGRESS_FLAG: u8 = 16;
+
 /// convert the game info to a sequence of bytes
-pub fn game_to_bytes (starting_player: u8, player: u8, table: &Table, hands: &Vec<Sequence>, 
-                      deck: &Sequence, config: &Config, player_names: &Vec<String>) -> Vec<u8> {
+pub fn game_to_bytes<H: AsRef<Hand>>(starting_player: u8, player: u8, table: &Table, hands: &[H],
+                                     deck: &Deck, config: &Config) -> Vec<u8> {
+    game_to_bytes_with_turn(starting_player, player, table, hands, deck, config, None)
+}
+
+/// convert the game info to a sequence of bytes, with the turn of the current player if it is in
+/// progress
+///
+/// The turn is written as by `Ongoing::write_to`, followed by the cards taken from the table and
+/// not played yet (their number on two bytes, then the cards).
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::*;
+/// use machiavelli::deck::Deck;
+/// use machiavelli::hand::Hand;
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::table::Table;
+/// use machiavelli::turn::Ongoing;
+///
+/// let config = Config { n_decks: 1, n_jokers: 2, n_cards_to_start: 2, custom_rule_jokers: false, n_players: 2,
+///                       empty_deck: EmptyDeckRule::Draw, joker_penalty: DEFAULT_JOKER_PENALTY,
+///                       mulligan: MulliganRule::Off, draw_for_first: false };
+/// let mut table = Table::new();
+/// table.add(Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5), RegularCard(Club, 6)]));
+/// let mut hands = Hand::deal(&["Alice".to_string(), "Bob".to_string()],
+///                            vec![Sequence::from_cards(&[Joker, RegularCard(Club, 7)]),
+///                                 Sequence::from_cards(&[Joker, RegularCard(Heart, 1)])]);
+///
+/// // Alice takes the sequence from the table, keeping it apart
+/// let turn = Ongoing::start(&table, &hands[0]).meld();
+/// let taken = table.take(1).unwrap();
+/// let bytes = game_to_bytes_with_turn(0, 0, &table, &hands, &Deck::new(), &config, Some(&(turn.clone(), taken.clone())));
+///
+/// // the turn is resumed as it was
+/// let (game, saved_turn) = load_game_with_turn(&bytes).ok().unwrap();
+/// assert_eq!(Some((turn, taken)), saved_turn);
+/// assert_eq!(0, game.3.number_sequences());
+///
+/// // or put back as it was when it started
+/// let (_, _, _, table, hands, _) = load_game(&bytes).ok().unwrap();
+/// assert_eq!(1, table.number_sequences());
+/// assert_eq!(2, hands[0].number_cards());
+/// ```
+pub fn game_to_bytes_with_turn<H: AsRef<Hand>>(starting_player: u8, player: u8, table: &Table, hands: &[H],
+                                               deck: &Deck, config: &Config, turn: Option<&SavedTurn>) -> Vec<u8> {
     
-    // construct the sequence of bytes to be saved
-    let mut bytes = Vec::<u8>::new();
+    // construct the sequence of bytes to be saved, starting with the format
+    let mut bytes = save_format::SAVE_MAGIC.to_vec();
+    bytes.push(save_format::CURRENT_VERSION);
     
-    // config
-    bytes.append(&mut config.to_bytes());
+    // config, with the flag of the turn in progress
+    let mut config_bytes = config.to_bytes();
+    if turn.is_some() {
+        config_bytes[4] |= TURN_IN_PROGRESS_FLAG;
+    }
+    bytes.extend_from_slice(&config_bytes);
 
     // starting player
     bytes.push(starting_player);
@@ -559,98 +1722,301 @@ pub fn game_to_bytes (starting_player: u8, player: u8, table: &Table, hands: &Ve
     for i_player in 0..config.n_players {
         
         // number of cards in the hand as 2 u8
-        let n_cards_in_hand = hands[i_player as usize].number_cards() as u16;
+        let hand = hands[i_player as usize].as_ref();
+        let n_cards_in_hand = hand.number_cards() as u16;
         bytes.push((n_cards_in_hand >> 8) as u8);
         bytes.push((n_cards_in_hand & 255) as u8);
         
-        // append the hand
-        bytes.append(&mut hands[i_player as usize].to_bytes());
+        // append the hand (writing to a vector can not fail)
+        hand.write_to(&mut bytes).unwrap_or(());
     }
 
-    // player names
+    // player names, cleaned so that their length fits in a byte
     for i_player in 0..config.n_players {
-        let name_b = player_names[i_player as usize].as_bytes();
-        bytes.push(name_b.len() as u8);
-        bytes.append(&mut name_b.to_vec());
+        let name = player_name::PlayerName::new(hands[i_player as usize].as_ref().owner())
+            .map(String::from)
+            .unwrap_or_else(|_| format!("Player {}", i_player + 1));
+        bytes.push(name.len() as u8);
+        bytes.extend_from_slice(name.as_bytes());
     }
     
     // deck 
-    let n_cards_in_deck = deck.number_cards();
+    let n_cards_in_deck = deck.remaining();
     bytes.push((n_cards_in_deck >> 8) as u8);
     bytes.push((n_cards_in_deck & 255) as u8);
-    bytes.append(&mut deck.to_bytes());
+    deck.write_to(&mut bytes).unwrap_or(());
+
+    // turn in progress
+    if let Some((turn, cards_from_table)) = turn {
+        turn.write_to(&mut bytes).unwrap_or(());
+        bytes.extend_from_slice(&(cards_from_table.number_cards() as u16).to_be_bytes());
+        cards_from_table.write_to(&mut bytes).unwrap_or(());
+    }
     
     // table 
-    bytes.append(&mut table.to_bytes());
+    table.write_to(&mut bytes).unwrap_or(());
+
+    // cards set aside with the deck
+    bytes.extend_from_slice(&(deck.discarded().len() as u16).to_be_bytes());
+    Sequence::from_cards(deck.discarded()).write_to(&mut bytes).unwrap_or(());
 
     bytes
 }
 
 
+/// game info loaded from a save: config, starting player, current player, table, hands (with the
+/// names of the players), and deck
+///
+/// Only the cards and the owners of the hands are saved: loaded hands start a new history.
+pub type SavedGame = (Config, u8, u8, Table, Vec<Hand>, Deck);
+
+/// turn of the current player saved while in progress, with the cards taken from the table and
+/// kept apart
+pub type SavedTurn = (Ongoing, Sequence);
+
 /// load the game info from a sequence of bytes
-pub fn load_game(bytes: &[u8]) -> Result<(Config, u8, u8, Table, Vec<Sequence>, Sequence, Vec<String>), LoadingError> {
-    let mut i_byte: usize = 0; // index of the current element in bytes
+///
+/// A turn saved while in progress is put back as it was when it started.
+/// An error is returned if the bytes end before the game does.
+pub fn load_game(bytes: &[u8]) -> Result<SavedGame, LoadingError> {
+    let mut reader = bytes;
+    read_game(&mut reader)
+}
+
+/// load the game info from a sequence of bytes, with the turn in progress if there is one
+///
+/// An error is returned if the bytes end before the game does.
+pub fn load_game_with_turn(bytes: &[u8]) -> Result<(SavedGame, Option<SavedTurn>), LoadingError> {
+    let mut reader = bytes;
+    read_game_with_turn(&mut reader)
+}
+
+/// load the game info from a reader, as written by `game_to_bytes`
+///
+/// Save files have to be decrypted with `encode::decrypt_save` first. A turn saved while in
+/// progress is put back as it was when it started.
+/// An error is returned if the reader ends before the game does.
+pub fn read_game<R: std::io::Read>(reader: &mut R) -> Result<SavedGame, LoadingError> {
+    let (mut game, turn) = read_game_with_turn(reader)?;
+    if let Some((turn, _)) = turn {
+        let hand = game.4.get_mut(game.2 as usize).ok_or(LoadingError::Corrupted)?;
+        turn.restore(&mut game.3, hand);
+    }
+    Ok(game)
+}
+
+/// load the game info from a reader, as written by `game_to_bytes_with_turn`, with the turn in
+/// progress if there is one
+///
+/// An error is returned if the reader ends before the game does.
+pub fn read_game_with_turn<R: std::io::Read>(reader: &mut R) -> Result<(SavedGame, Option<SavedTurn>), LoadingError> {
+    read_save(reader, false).map(|(game, turn, _)| (game, turn))
+}
+
+/// load the game info from a sequence of bytes as `load_game_with_turn`, replacing what can not
+/// be read in the names of the players instead of failing, and return what was changed (see
+/// `save_format::LoadReport`)
+///
+/// The characters which are not valid UTF-8 are replaced by `�`, and names left empty by
+/// `Player 1`, `Player 2`, ... as in a new game.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::*;
+/// use machiavelli::deck::Deck;
+/// use machiavelli::hand::Hand;
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::table::Table;
+///
+/// let config = Config { n_decks: 1, n_jokers: 0, n_cards_to_start: 1, custom_rule_jokers: false, n_players: 2,
+///                       empty_deck: EmptyDeckRule::Draw, joker_penalty: DEFAULT_JOKER_PENALTY,
+///                       mulligan: MulliganRule::Off, draw_for_first: false };
+/// let hands = Hand::deal(&["Ann".to_string(), "Bob".to_string()], vec![Sequence::from_cards(&[Joker]); 2]);
+/// let mut bytes = game_to_bytes(0, 0, &Table::new(), &hands, &Deck::new(), &config);
+///
+/// // the last letter of the name of Bob is damaged
+/// let i = bytes.windows(3).position(|w| w == b"Bob").unwrap();
+/// bytes[i + 2] = 0xff;
+/// assert_eq!(Some(LoadingError::InvalidName(1)), load_game_with_turn(&bytes).err());
+///
+/// let (game, _, report) = load_game_lossy(&bytes).ok().unwrap();
+/// assert_eq!(vec![1], report.repaired_names);
+/// assert_eq!("Bo\u{fffd}", game.4[1].owner());
+/// ```
+pub fn load_game_lossy(bytes: &[u8])
+    -> Result<(SavedGame, Option<SavedTurn>, save_format::LoadReport), LoadingError>
+{
+    load_save(bytes, true)
+}
+
+/// load the game info from a sequence of bytes in any format, with the names which can not be
+/// read replaced if `lossy` is `true`, and report the format and what was changed
+pub(crate) fn load_save(bytes: &[u8], lossy: bool)
+    -> Result<(SavedGame, Option<SavedTurn>, save_format::LoadReport), LoadingError>
+{
+    let mut reader = bytes;
+    read_save(&mut reader, lossy)
+}
+
+/// ask in the terminal whether to load a save although the name of a player can not be read,
+/// replacing what can not be (for `save_format::load`)
+pub fn ask_repair(player: usize) -> bool {
+    let question = format!("The name of player {} in the save can not be read. Load the game anyway, replacing \
+                            the characters which can not be read?", player + 1);
+    prompt::Prompter::new(prompt::terminal_input()).confirm(&question)
+}
+
+/// ask in the terminal whether to load a file without the header of the encrypted saves, as a
+/// save of the older versions which could be changed without it showing (for
+/// `encode::decrypt_save_or_legacy`)
+pub fn ask_legacy() -> bool {
+    let question = "This file is not an encrypted save: it may be a save of an older version, or a save which \
+                    was damaged. Load it as a save of an older version? It will be encrypted when the game is saved.";
+    prompt::Prompter::new(prompt::terminal_input()).confirm(question)
+}
+
+// load the game info in any format, with the names which can not be read replaced if `lossy`
+// is `true`, and report the format and what was changed
+fn read_save<R: std::io::Read>(reader: &mut R, lossy: bool)
+    -> Result<(SavedGame, Option<SavedTurn>, save_format::LoadReport), LoadingError>
+{
+    let mut report = save_format::LoadReport::new();
+
+    // the format, unless the save starts with the config as in older versions
+    let mut config_bytes: [u8; 8] = [0; 8];
+    reader.read_exact(&mut config_bytes[..1])?;
+    if config_bytes[0] == save_format::SAVE_MAGIC[0] {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic[1..])?;
+        let mut version = [0u8];
+        reader.read_exact(&mut version)?;
+        if magic[1..] != save_format::SAVE_MAGIC[1..] || version[0] < 2 || version[0] > save_format::CURRENT_VERSION {
+            return Err(LoadingError::Corrupted);
+        }
+        report.format = save_format::SaveFormat::Versioned(version[0]);
+        reader.read_exact(&mut config_bytes[..1])?;
+    } else {
+        report.format = save_format::SaveFormat::Legacy;
+    }
+    let legacy = report.format == save_format::SaveFormat::Legacy;
 
     // load the config
-    let n_bytes_config: usize = 6;
-    let config = Config::from_bytes(&bytes[i_byte..n_bytes_config]);
-    i_byte += n_bytes_config;
+    reader.read_exact(&mut config_bytes[1..6])?;
+    let n_bytes_config = Config::n_bytes(&config_bytes);
+    reader.read_exact(&mut config_bytes[6..n_bytes_config])?;
+    let config = Config::from_bytes(&config_bytes[..n_bytes_config]).map_err(|_| LoadingError::Corrupted)?;
+    let has_turn = config_bytes[4] & TURN_IN_PROGRESS_FLAG != 0;
     
-    // load the starting player
-    let starting_player = bytes[i_byte];
-    i_byte += 1;
-    
-    // load the current player
-    let player = bytes[i_byte];
-    i_byte += 1;
+    // load the starting player and the current player
+    let mut players: [u8; 2] = [0; 2];
+    reader.read_exact(&mut players)?;
+    let [starting_player, player] = players;
     
     // hand of each player
-    let mut hands = Vec::<Sequence>::new();
+    let mut cards = Vec::<Sequence>::new();
     for _i_player in 0..config.n_players {
         
         // number of cards in the hand as 2 u8
-        let n_cards_in_hand = ((bytes[i_byte] as usize) << 8) + (bytes[i_byte+1] as usize);
-        i_byte += 2;
+        let n_cards_in_hand = read_u16(reader)? as usize;
  
         // append the hand
-        hands.push(Sequence::from_bytes(&bytes[i_byte..i_byte+n_cards_in_hand]));
-        i_byte += n_cards_in_hand;
+        cards.push(Sequence::read_from(reader, n_cards_in_hand)?);
     }
     
     // player names
     let mut player_names = Vec::<String>::new();
-    for i_player in 0..config.n_players {
+    for i_player in 0..(config.n_players as usize) {
         
         // number of characters in the name
-        let n_chars = bytes[i_byte] as usize;
-        i_byte += 1;
+        let mut n_chars: [u8; 1] = [0];
+        reader.read_exact(&mut n_chars)?;
         
         // append the name
-        player_names.push(String::from_utf8(bytes[i_byte..i_byte+n_chars].to_vec())
-                          .unwrap_or_else(|_| {format!("Player {}", i_player+1)}));
-        i_byte += n_chars;
+        let mut name = vec![0; n_chars[0] as usize];
+        reader.read_exact(&mut name)?;
+        let name = match player_name::PlayerName::from_bytes(&name) {
+            Ok(name) => name.into(),
+            Err(_) if lossy => {
+                report.repaired_names.push(i_player);
+                player_name::PlayerName::new(&String::from_utf8_lossy(&name))
+                    .map(String::from)
+                    .unwrap_or_else(|_| format!("Player {}", i_player + 1))
+            },
+            Err(_) => return Err(LoadingError::InvalidName(i_player))
+        };
+        player_names.push(name);
     }
 
     // deck
-    let n_cards_in_deck = ((bytes[i_byte] as usize) << 8) + (bytes[i_byte+1] as usize);
-    i_byte += 2;
-    let deck = Sequence::from_bytes(&bytes[i_byte..i_byte+n_cards_in_deck]);
-    i_byte += n_cards_in_deck;
+    let n_cards_in_deck = read_u16(reader)? as usize;
+    let mut deck = Deck::read_from(reader, n_cards_in_deck)?;
+
+    // turn in progress
+    let turn = if has_turn {
+        let turn = if legacy {
+            let (turn, n_dropped) = Ongoing::read_legacy(reader)?;
+            report.dropped_table_bytes += n_dropped;
+            turn
+        } else {
+            Ongoing::read_from(reader)?
+        };
+        let n_cards_from_table = read_u16(reader)? as usize;
+        Some((turn, Sequence::read_from(reader, n_cards_from_table)?))
+    } else {
+        None
+    };
 
-    // table
-    let table = Table::from_bytes(&bytes[i_byte..]);
+    // table, up to the end of the save in older versions
+    let table = if legacy {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let (table, n_dropped) = Table::from_legacy_bytes(&bytes);
+        report.dropped_table_bytes += n_dropped;
+        table
+    } else {
+        Table::read_from(reader)?
+    };
+
+    // cards set aside with the deck, which older formats did not have
+    if report.format == save_format::SaveFormat::Versioned(save_format::CURRENT_VERSION) {
+        let n_discarded = read_u16(reader)? as usize;
+        deck.discard(&Sequence::read_from(reader, n_discarded)?.to_vec());
+    }
 
     Ok((
-        config,
-        starting_player,
-        player,
-        table,
-        hands,
-        deck,
-        player_names
+        (
+            config,
+            starting_player,
+            player,
+            table,
+            Hand::deal(&player_names, cards),
+            deck
+        ),
+        turn,
+        report
     ))
 }
 
+/// Paths of the save files of unfinished games in a directory, from the most recently saved
+///
+/// A file is a save if its name ends with `extension` (but not with `_bak` and the extension, as
+/// for backups), and it is unfinished if it can be loaded and no player has emptied their hand.
+/// Each file is decoded with its name, as it was when the game was saved.
+pub fn unfinished_saves(dir: &str, extension: &str) -> Vec<String> {
+    saves::list_saves(std::path::Path::new(dir), extension).into_iter()
+        .filter(|save| !save.is_finished())
+        .map(|save| save.path)
+        .collect()
+}
+
+/// read a number written as two bytes, the most significant first
+fn read_u16<R: std::io::Read>(reader: &mut R) -> std::io::Result<u16> {
+    let mut bytes: [u8; 2] = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
 
 #[derive(Debug)]
 pub struct InvalidInputError {}
@@ -662,6 +2028,258 @@ impl<T: std::error::Error> From<T> for InvalidInputError {
 }
 
 pub struct NoMoreCards {}
-pub struct LoadingError {}
 
+/// error loading a saved game
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LoadingError {
+    /// the bytes end before the game does, or do not describe a game
+    Corrupted,
+    /// the name of the player with this index is not valid UTF-8, or empty (see `load_game_lossy`)
+    InvalidName(usize)
+}
+
+impl std::fmt::Display for LoadingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoadingError::Corrupted => write!(f, "the save is corrupted"),
+            LoadingError::InvalidName(i) => write!(f, "the name of player {} can not be read", i + 1)
+        }
+    }
+}
+
+impl From<std::io::Error> for LoadingError {
+    fn from(_error: std::io::Error) -> Self {
+        LoadingError::Corrupted
+    }
+}
+
+/// error in a config file
+#[derive(Debug)]
+pub struct ConfigError {
+    pub message: String
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ConfigError: {}", self.message)
+    }
+}
+
+impl From<toml::TomlError> for ConfigError {
+    fn from(error: toml::TomlError) -> Self {
+        ConfigError { message: error.to_string() }
+    }
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(error: std::io::Error) -> Self {
+        ConfigError { message: format!("IO Error: {}", error) }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn config(n_decks: u8, n_jokers: u8, n_cards_to_start: u16, n_players: u8) -> Config {
+        Config { n_decks, n_jokers, n_cards_to_start, custom_rule_jokers: false, n_players,
+                 empty_deck: EmptyDeckRule::Draw, joker_penalty: DEFAULT_JOKER_PENALTY,
+                 mulligan: MulliganRule::Off, draw_for_first: false }
+    }
+
+    #[test]
+    fn deck_size_boundary() {
+        // one deck with two jokers has 54 cards
+        assert!(config(1, 2, 53, 1).validate().is_ok());
+        assert!(config(1, 2, 54, 1).validate().is_err());
+        assert!(config(1, 2, 26, 2).validate().is_ok());
+        assert!(config(1, 2, 27, 2).validate().is_err());
+    }
+
+    #[test]
+    fn number_of_players_boundary() {
+        assert!(config(2, 0, 1, 0).validate().is_err());
+        assert!(config(2, 0, 1, 1).validate().is_ok());
+        assert!(config(2, 0, 1, MAX_N_PLAYERS).validate().is_ok());
+        assert!(config(2, 0, 1, MAX_N_PLAYERS + 1).validate().is_err());
+    }
+
+    #[test]
+    fn no_decks_or_cards() {
+        assert!(config(0, 255, 1, 2).validate().is_err());
+        assert!(config(1, 0, 0, 2).validate().is_err());
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert!(parse_config("n_players = 16\nn_cards_to_start = 13\n").is_err());
+        assert!(parse_legacy_config("1\n0\n13\n0\n4\nsave\n").is_err());
+        assert!(parse_legacy_config("1\n0\n12\n0\n4\nsave\n").is_ok());
+    }
+
+    #[test]
+    fn presets_are_valid() {
+        for (key, name) in PRESETS.iter() {
+            assert_eq!(Config::preset(key, 4), Config::preset(name, 4));
+            for n_players in 1..=MAX_N_PLAYERS {
+                assert!(Config::preset(key, n_players).unwrap().validate().is_ok());
+            }
+        }
+        assert_eq!("ConfigError: line 2: `preset` should be one of \"classic\", \"rummikub\", \"strict\"",
+                   parse_config("n_players = 3\npreset = \"house\"\n").unwrap_err().to_string());
+    }
 
+    #[test]
+    fn empty_deck_rule_bytes() {
+        // the rule shares a byte with the custom rule, so that older saves still load
+        for &rule in &[EmptyDeckRule::Draw, EmptyDeckRule::Score, EmptyDeckRule::NewDeck] {
+            for &custom_rule_jokers in &[false, true] {
+                let config = Config { custom_rule_jokers, empty_deck: rule, ..config(2, 4, 13, 2) };
+                assert_eq!(config, Config::from_bytes(&config.to_bytes()).unwrap());
+            }
+        }
+        assert_eq!(EmptyDeckRule::Draw, Config::from_bytes(&[2, 4, 0, 13, 1, 2]).unwrap().empty_deck);
+    }
+
+    #[test]
+    fn mulligan_rule_bytes() {
+        for &mulligan in &[MulliganRule::Off, MulliganRule::Free, MulliganRule::Penalty] {
+            let config = Config { mulligan, empty_deck: EmptyDeckRule::NewDeck, joker_penalty: 40, ..config(2, 4, 13, 2) };
+            assert_eq!(config, Config::from_bytes(&config.to_bytes()).unwrap());
+            let (parsed, _) = parse_config(&format!("mulligan = \"{}\"\n", mulligan.name())).unwrap();
+            assert_eq!(mulligan, parsed.mulligan);
+        }
+        assert_eq!(MulliganRule::Off, Config::from_bytes(&[2, 4, 0, 13, 1, 2]).unwrap().mulligan);
+        assert!(parse_config("mulligan = \"twice\"\n").is_err());
+    }
+
+    #[test]
+    fn draw_for_first_bytes() {
+        let config = Config { draw_for_first: true, mulligan: MulliganRule::Penalty, ..config(2, 4, 13, 2) };
+        assert_eq!(config, Config::from_bytes(&config.to_bytes()).unwrap());
+        assert!(parse_config("draw_for_first = true\n").unwrap().0.draw_for_first);
+        assert!(!Config::from_bytes(&[2, 4, 0, 13, 1, 2]).unwrap().draw_for_first);
+    }
+
+    #[test]
+    fn joker_penalty_bytes() {
+        let custom = Config { joker_penalty: 300, ..config(2, 4, 13, 2) };
+        assert_eq!(8, custom.to_bytes().len());
+        assert_eq!(custom, Config::from_bytes(&custom.to_bytes()).unwrap());
+        assert!(Config::from_bytes(&custom.to_bytes()[..6]).is_err());
+        assert_eq!(6, config(2, 4, 13, 2).to_bytes().len());
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        assert!(Config::from_bytes(&[1, 0, 0, 13]).is_err());
+        assert!(Config::from_bytes(&[1, 0, 0, 13, 0, 0]).is_err());
+        assert!(load_game(&[1, 0, 0, 13, 0, 0]).is_err());
+
+        // a save cut in the middle of the hands
+        let bytes = game_to_bytes(0, 0, &Table::new(), &vec![Hand::new("a", Sequence::from_cards(&[Joker])); 2],
+                                  &Deck::new(), &config(1, 0, 1, 2));
+        assert!(load_game(&bytes).is_ok());
+        assert!(load_game(&bytes[..10]).is_err());
+
+        // a name which is not valid UTF-8
+        let mut bytes = bytes;
+        assert_eq!([1, b'a'], bytes[19..21]);
+        bytes[20] = 0xff;
+        assert_eq!(Some(LoadingError::InvalidName(0)), load_game(&bytes).err());
+    }
+
+    #[test]
+    fn turns_in_progress_in_saves() {
+        let hands = vec![Hand::new("a", Sequence::from_cards(&[Joker])); 2];
+        let bytes = game_to_bytes(0, 0, &Table::new(), &hands, &Deck::new(), &config(1, 0, 1, 2));
+        assert!(matches!(load_game_with_turn(&bytes), Ok((_, None))));
+
+        // a turn in the meld phase, with a card taken from the table
+        let turn = Ongoing::start(&Table::new(), &hands[1]).meld();
+        let saved_turn = (turn, Sequence::from_cards(&[Joker]));
+        let bytes = game_to_bytes_with_turn(0, 1, &Table::new(), &hands, &Deck::new(), &config(1, 0, 1, 2),
+                                            Some(&saved_turn));
+        match load_game_with_turn(&bytes) {
+            Ok((game, turn)) => {
+                assert_eq!(Some(saved_turn), turn);
+                assert_eq!(config(1, 0, 1, 2), game.0);
+            },
+            Err(_) => panic!("the save could not be loaded")
+        };
+
+        // the turn is cut, or in an unknown phase
+        assert!(load_game_with_turn(&bytes[..27]).is_err());
+        let mut bytes = bytes;
+        assert_eq!(1, bytes[25]);
+        bytes[25] = 2;
+        assert!(load_game_with_turn(&bytes).is_err());
+    }
+
+    #[test]
+    fn long_names_are_cut_in_saves() {
+        let names = vec!["x".repeat(300), "\x1b[31mBob".to_string()];
+        let hands = Hand::deal(&names, vec![Sequence::from_cards(&[Joker]); 2]);
+        let bytes = game_to_bytes(0, 0, &Table::new(), &hands, &Deck::new(), &config(1, 0, 1, 2));
+        let loaded_names = match load_game(&bytes) {
+            Ok(game) => hand::owners(&game.4),
+            Err(_) => panic!("the save could not be loaded")
+        };
+        assert_eq!(vec!["x".repeat(player_name::MAX_NAME_LENGTH), "Bob".to_string()], loaded_names);
+    }
+
+    #[test]
+    fn atomic_writes_keep_the_old_content_on_failure() {
+        let dir = std::env::temp_dir().join(format!("machiavelli_atomic_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("game.sav");
+        write_atomically(&path, b"turn 1").unwrap();
+        write_atomically(&path, b"turn 2").unwrap();
+        assert_eq!(b"turn 2".to_vec(), std::fs::read(&path).unwrap());
+
+        // the rename fails as a directory is in the way, and nothing is left behind
+        let blocked = dir.join("blocked");
+        std::fs::create_dir_all(blocked.join("inside")).unwrap();
+        assert!(write_atomically(&blocked, b"turn 3").is_err());
+        assert!(blocked.is_dir());
+        let mut names: Vec<String> = std::fs::read_dir(&dir).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect();
+        names.sort();
+        assert_eq!(vec!["blocked", "game.sav"], names);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unfinished_saves_in_a_directory() {
+        let dir = std::env::temp_dir().join(format!("machiavelli_saves_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = dir.to_string_lossy().to_string();
+        let names = vec!["a".to_string(); 2];
+        let save = |name: &str, hands: &Vec<Sequence>| {
+            let path = std::path::Path::new(&dir).join(name).to_string_lossy().to_string();
+            let hands = Hand::deal(&names, hands.clone());
+            let bytes = game_to_bytes(0, 0, &Table::new(), &hands, &Deck::new(), &config(1, 0, 1, 2));
+            let metadata = saves::SaveMetadata::new(1, 0, 0, &hands, &config(1, 0, 1, 2)).to_bytes();
+            std::fs::write(&path, encode::encrypt_save(&bytes, &metadata, &path, &mut rand::thread_rng())).unwrap();
+            path
+        };
+        let ongoing = save("ongoing.sav", &vec![Sequence::from_cards(&[Joker]); 2]);
+        save("ongoing_bak.sav", &vec![Sequence::from_cards(&[Joker]); 2]);
+        save("won.sav", &vec![Sequence::new(), Sequence::from_cards(&[Joker])]);
+        save("ongoing.sav.done", &vec![Sequence::from_cards(&[Joker]); 2]);
+
+        // older saves are xored with their name
+        let old = std::path::Path::new(&dir).join("old.sav").to_string_lossy().to_string();
+        let bytes = game_to_bytes(0, 0, &Table::new(), &Hand::deal(&names, vec![Sequence::from_cards(&[Joker]); 2]),
+                                  &Deck::new(), &config(1, 0, 1, 2));
+        std::fs::write(&old, encode::xor(&bytes, b"old.sav")).unwrap();
+        std::fs::write(std::path::Path::new(&dir).join("garbage.sav"), [1, 2, 3]).unwrap();
+        let mut saves = unfinished_saves(&dir, ".sav");
+        saves.sort();
+        assert_eq!(vec![old, ongoing], saves);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
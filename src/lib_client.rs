@@ -4,11 +4,37 @@ use super::*;
 pub use std::net::TcpStream;
 pub use std::io::{ Read, Write };
 pub use std::str::from_utf8;
+pub use super::animations::{ Animations, NO_ANIMATIONS_FLAG };
+pub use super::layout::{ HandLayout, LayoutSettings, terminal_height, terminal_width };
+pub use super::settings::{ NotificationSettings, Settings, restyle };
+pub use super::connection::{ Connection, EncryptedConnection, Side };
+pub use super::state::{ StateTracker, StateUpdate, TurnView };
+pub use super::waiting::WaitingScreen;
+use super::prompt::{ Prompter, terminal_input };
 
-const BUFFER_SIZE: usize = 50;
-const MAX_N_BUFFERS: usize = 255;
 const N_MILLISECONDS_WAIT: u64 = 10;
 
+/// file where earlier versions kept the notification settings, which are now in the settings file
+pub const CLIENT_CONFIG_FILE: &str = "Config/client.dat";
+
+/// file with the token sent by the server, to reconnect to the current game
+pub const RECONNECTION_TOKEN_FILE: &str = "Config/token_client.dat";
+
+/// Move the notification settings of `CLIENT_CONFIG_FILE`, if it exists, to the settings file
+///
+/// The old file is renamed once the settings are saved, so that it is only read once; if they can
+/// not be saved, it is kept and its settings are used all the same.
+pub fn migrate_notifications(settings: &mut Settings) {
+    let content = match std::fs::read_to_string(CLIENT_CONFIG_FILE) {
+        Ok(content) => content,
+        Err(_) => return
+    };
+    settings.notifications = NotificationSettings::parse(&content);
+    if settings.save().is_ok() {
+        std::fs::rename(CLIENT_CONFIG_FILE, format!("{}.old", CLIENT_CONFIG_FILE)).unwrap_or(());
+    }
+}
+
 // ask for the port
 fn get_address() -> String {
     println!("Address and port of the server?");
@@ -20,102 +46,146 @@ fn get_address() -> String {
     }
 }
 
-/// try to connect to the server and send the player name
+/// try to connect to the server
 ///
-/// If the connection is successful, clear the terminal, print the reply from the server, and
-/// return a `TcpStream`. 
-/// If not, return a `StreamError`.
-pub fn say_hello(mut name: String) -> Result<TcpStream, StreamError> {
+/// The address of the server is read from `Config/port_client.dat`; if this file is missing,
+/// `default_server` is used, and if it is `None` the address is asked.
+pub fn connect_to_server(default_server: Option<String>) -> Result<TcpStream, StreamError> {
 
     // host address
     let name_file_port_server = "Config/port_client.dat";
     let host = match std::fs::read_to_string(name_file_port_server) {
         Ok(s) => s.trim().to_string(),
-        Err(_) => get_address()
+        Err(_) => default_server.unwrap_or_else(get_address)
     };
 
-    match TcpStream::connect(&host) {
-        Ok(mut stream) => {
-            println!("Successfully connected to {}", &host);
-            
-            loop {
-                
-                if name.len() == 0 {
-                    // get the player name
-                    let mut cont = true;
-                    println!("Player name:");
-                    while cont {
-                        match get_input() {
-                            Ok(s) => {
-                                name = s.trim().to_string();
-                                cont = false
-                            },
-                            Err(_) => println!("Could not parse the input")
-                        };
-                    }
-                }
+    let stream = TcpStream::connect(&host)?;
+    println!("Successfully connected to {}", &host);
+    Ok(stream)
+}
 
-                send_str_to_server(&mut stream, &name)?;
-                println!("Sent the name to server; awaiting reply...");
-    
-                let mut buffer: [u8; 1] = [0];
-                stream.read_exact(&mut buffer)?;
-                match buffer[0] {
-                    1 => {
-                        match get_str_from_server(&mut stream) {
-                            Ok(s) => {
-                                
-                                // set the terminal appearance
-                                reset_style();
-
-                                // clear the terminal
-                                clear_terminal();
-
-                                // print the message sent by the server
-                                println!("{}", s);
-                            }
-                            Err(e) => {
-                                println!("Failed to receive data: {}", e);
-                            }
-                        }
-                        break;
-                    },
-                    2 => {
-                        match get_str_from_server(&mut stream) {
-                            Ok(s) => { 
-                                // print the message sent by the server
-                                println!("{}", s);
-                            }
-                            Err(e) => {
-                                println!("Failed to receive data: {}", e);
-                            }
-                        }
-                        break;
+/// send the player name to the server, asking for another one until the server accepts it
+///
+/// If the server accepts the name, clear the terminal, print the reply from the server, and
+/// return the connection. 
+/// If not, return a `StreamError`.
+pub fn say_hello<C: Connection>(mut stream: C, mut name: String) -> Result<C, StreamError> {
+    loop {
+        
+        if name.is_empty() {
+            // get the player name
+            let mut cont = true;
+            println!("Player name:");
+            while cont {
+                match get_input() {
+                    Ok(s) => {
+                        name = s.trim().to_string();
+                        cont = false
                     },
-                    _ => {
-                        name.clear();
-                        println!("{}", get_str_from_server(&mut stream)?)
-                    }
+                    Err(_) => println!("Could not parse the input")
                 };
             }
-            Ok(stream)
         }
-        Err(e) => { Err(StreamError::from(e)) }
+
+        let token = std::fs::read_to_string(RECONNECTION_TOKEN_FILE).unwrap_or_default();
+        send_hello(&mut stream, &name, token.trim())?;
+        println!("Sent the name to server; awaiting reply...");
+    
+        let mut buffer: [u8; 1] = [0];
+        stream.read_exact(&mut buffer)?;
+        match buffer[0] {
+            1 => {
+                match get_str_from_server(&mut stream) {
+                    Ok(s) => {
+                        
+                        // set the terminal appearance
+                        reset_style();
+
+                        // clear the terminal
+                        clear_terminal();
+
+                        // print the message sent by the server
+                        println!("{}", restyle(&s));
+                    }
+                    Err(e) => {
+                        println!("Failed to receive data: {}", e);
+                    }
+                }
+                break;
+            },
+            2 => {
+                match get_str_from_server(&mut stream) {
+                    Ok(s) => { 
+                        // print the message sent by the server
+                        println!("{}", s);
+                    }
+                    Err(e) => {
+                        println!("Failed to receive data: {}", e);
+                    }
+                }
+                break;
+            },
+            _ => {
+                name.clear();
+                println!("{}", get_str_from_server(&mut stream)?)
+            }
+        };
     }
+    Ok(stream)
+}
+
+/// What the client keeps between two requests of the server
+#[derive(Debug, Default)]
+pub struct ClientState {
+    /// public state of the game, while it is in sync with the server
+    pub public: Option<StateTracker>,
+    /// line showing whose turn the player waits for
+    pub waiting: WaitingScreen
 }
 
 /// get a request from te server and act accordingly
 ///
 /// The request is initially encoded in a single byte sent by the server to `stream`. 
-/// Five values are currently supported: 
+/// The following values are currently supported: 
 ///
 /// * 1: print the next message sent by the server
 /// * 2: clear the terminal and print the next message sent by the server
 /// * 3: print the next message sent by the server and send back a message from stdin
 /// * 4: send a message from stdin
 /// * 5: close the client
-pub fn handle_server_request(single_byte_buffer: &mut [u8; 1], stream: &mut TcpStream) -> Result<(), StreamError> {
-    stream.read(single_byte_buffer)?;
+/// * 6: notify the player that their turn starts
+/// * 7: print the next message sent by the server, with an animation if they are enabled
+/// * 8: send the layout of the hand and the width of the terminal
+/// * 9: print the next message sent by the server and close the client
+/// * 10: print a private message: the name of its sender (empty for the server), then the message
+/// * 11: ask the player to confirm an action, and send back `y` or `n`
+/// * 12: save the token to send to reconnect to `RECONNECTION_TOKEN_FILE`
+/// * 13: apply an update of the public state of the game, then check the checksum of the new
+///   state sent by the server, and reply whether it matches (0) or the whole state should be sent
+///   (1)
+/// * 14: show whose turn the player is waiting for, from the next two messages: the name of the
+///   active player, and the number of seconds their turn has lasted
+/// * 15: show what the active player is doing, described in the next message, while waiting for
+///   their turn
+///
+/// The public state, as long as it is in sync with the server, is kept in `client`.
+/// The layout is changed when the player sends a `l` command, and the new one is saved to the
+/// settings file.
+/// The messages are printed with the theme of `settings`, and the key bindings are applied to
+/// the messages sent.
+/// While the player waits for their turn, the waiting screen of `client` shows the time going by
+/// under the last message; the screen stops waiting when it is cleared, and when the player is asked something.
+pub fn handle_server_request<C: Connection>(single_byte_buffer: &mut [u8; 1], stream: &mut C,
+                             notifications: &NotificationSettings, animations: &Animations,
+                             layout: &mut HandLayout, settings: &Settings,
+                             client: &mut ClientState) 
+    -> Result<(), StreamError> {
+    let waiting = &mut client.waiting;
+    stream.read_exact(single_byte_buffer)?;
+    waiting.pause();
+    if let 2 | 3 | 4 | 5 | 6 | 9 | 11 = single_byte_buffer[0] {
+        waiting.stop();
+    }
     match single_byte_buffer[0] {
         
         // value 1: print the message from the server
@@ -125,87 +195,236 @@ pub fn handle_server_request(single_byte_buffer: &mut [u8; 1], stream: &mut TcpS
         2 => clear_and_print_str_from_server(stream)?,
         
         // value 3: print the message and return a reply in bytes
-        3 => print_and_reply(stream)?,
+        3 => print_and_reply(stream, layout, settings)?,
         
         // value 4: send a message
-        4 => send_message(stream)?,
+        4 => send_message(stream, layout, settings)?,
         
         // value 5: exit
         5 => {
-            print!("\x1b[0m\x1b[?25h"); // reset the style and show the cursor
-            print!("\x1b[2J\x1b[1;1H"); // clear the screen
-            print!("\x1b[K"); // redraw the screen
-            std::process::exit(0)
+            print!("{}{}\x1b[K", terminal::RESTORE, terminal::CLEAR);
+            terminal::exit(0)
+        },
+
+        // value 6: the turn starts
+        6 => notify_turn(notifications),
+
+        // value 7: print a line, possibly animated
+        7 => {
+            let text = restyle(&get_str_from_server(stream)?);
+            animations.flash(&text);
+            mouse::shown(&format!("{}\n", text));
+        },
+
+        // value 8: send the layout settings
+        8 => {
+            let settings = LayoutSettings::of_terminal(*layout);
+            send_bytes_to_server(stream, &settings.to_bytes())?
+        },
+
+        // value 9: print the message from the server and exit, keeping it on the screen
+        9 => {
+            println!("{}", restyle(&get_str_from_server(stream)?));
+            terminal::exit(0)
+        },
+
+        // value 10: print a private message
+        10 => {
+            let sender = restyle(&get_str_from_server(stream)?);
+            let message = restyle(&get_str_from_server(stream)?);
+            mouse::print_line(&private_message_to_string(&sender, message.trim_end()));
+        },
+
+        // value 11: confirm an action
+        11 => {
+            let question = restyle(&get_str_from_server(stream)?);
+            let confirmed = !settings.confirm || Prompter::new(terminal_input()).confirm(question.trim_end());
+            send_str_to_server(stream, if confirmed { "y" } else { "n" })?;
+        },
+
+        // value 12: keep the token to reconnect
+        12 => {
+            let token = get_str_from_server(stream)?;
+            std::fs::write(RECONNECTION_TOKEN_FILE, token).unwrap_or(());
+        },
+
+        // value 13: check and keep the public state
+        13 => {
+            let in_sync = receive_state(stream, &mut client.public)?;
+            send_bytes_to_server(stream, &[if in_sync { 0 } else { 1 }])?;
+        },
+
+        // value 14: wait for the turn of another player
+        14 => {
+            let name = restyle(&get_str_from_server(stream)?);
+            let n_seconds = get_str_from_server(stream)?.parse().unwrap_or(0);
+            waiting.wait_for(&name, std::time::Duration::from_secs(n_seconds));
+        },
+
+        // value 15: what the active player is doing
+        15 => waiting.show_activity(&restyle(&get_str_from_server(stream)?)),
+
+        // value 16: show the situation, drawn from the public state
+        16 => {
+            let text = restyle(&receive_situation(stream, &client.public, &LayoutSettings::of_terminal(*layout))?);
+            print!("{}", text);
+            mouse::shown(&text);
         },
 
         _ => ()
     };
+    waiting.resume();
     Ok(())
 }
 
-fn clear_and_print_str_from_server(stream:  &mut TcpStream) -> Result<(), StreamError> {
+/// get an update of the public state and the checksum of the new state from the server, apply
+/// the update, and return whether the checksum matches
+///
+/// The state is forgotten if it is out of sync, so that only a full update can restore it.
+pub fn receive_state<C: Connection>(stream: &mut C, state: &mut Option<StateTracker>) 
+    -> Result<bool, StreamError>
+{
+    let update = StateUpdate::from_bytes(&get_bytes_from_server(stream)?);
+    let checksum = get_str_from_server(stream)?;
+    let (update, mut tracker) = match (update, state.take()) {
+        (Some(update), _) if update.full => (update, StateTracker::new()),
+        (Some(update), Some(tracker)) => (update, tracker),
+        _ => return Ok(false)
+    };
+    tracker.apply(&update);
+    if tracker.public_state().checksum() != checksum {
+        return Ok(false);
+    }
+    *state = Some(tracker);
+    Ok(true)
+}
+
+/// get the situation at the start of a turn from the server, and draw it with the table of the
+/// public state
+///
+/// The server only sends it once the client is in sync, so the table is only empty if the game
+/// has not started.
+pub fn receive_situation<C: Connection>(stream: &mut C, state: &Option<StateTracker>, layout: &LayoutSettings)
+    -> Result<String, StreamError>
+{
+    let header = get_str_from_server(stream)?;
+    let view = TurnView::from_bytes(&get_bytes_from_server(stream)?)
+        .ok_or_else(|| StreamError { message: "Could not read the situation".to_string() })?;
+    let footer = get_str_from_server(stream)?;
+    let table = state.as_ref().map(|tracker| tracker.public_state().table).unwrap_or_else(Table::new);
+    let situation = render_situation(&header, &table, &view.changed, &view.hand, &Sequence::new(), layout,
+                                     &view.pages);
+    Ok(format!("{}{}", situation, footer))
+}
+
+/// format a private message, in italics to set it apart from the rest of the game
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::lib_client::private_message_to_string;
+///
+/// assert_eq!("\x1b[3m(private) Alice: hi\x1b[23m", private_message_to_string("Alice", "hi"));
+/// assert_eq!("\x1b[3m(private) Invalid input\x1b[23m", private_message_to_string("", "Invalid input"));
+/// ```
+pub fn private_message_to_string(sender: &str, message: &str) -> String {
+    if sender.is_empty() {
+        format!("\x1b[3m(private) {}\x1b[23m", message)
+    } else {
+        format!("\x1b[3m(private) {}: {}\x1b[23m", sender, message)
+    }
+}
+
+/// ring the bell and/or send a desktop notification, depending on the settings
+fn notify_turn(notifications: &NotificationSettings) {
+    if notifications.bell {
+        print!("\u{0007}");
+        std::io::stdout().flush().unwrap_or(());
+    }
+    if notifications.desktop {
+        send_desktop_notification("Machiavelli", "It's your turn!");
+    }
+}
+
+/// send a desktop notification using the tools provided by the OS, ignoring any failure
+fn send_desktop_notification(title: &str, body: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut c = std::process::Command::new("osascript");
+        c.arg("-e").arg(format!("display notification \"{}\" with title \"{}\"", body, title));
+        c
+    } else if cfg!(target_os = "windows") {
+        let mut c = std::process::Command::new("msg");
+        c.arg("*").arg(format!("{}: {}", title, body));
+        c
+    } else {
+        let mut c = std::process::Command::new("notify-send");
+        c.arg(title).arg(body);
+        c
+    };
+    command.spawn().map(|_| ()).unwrap_or(());
+}
+
+fn clear_and_print_str_from_server<C: Connection>(stream: &mut C) -> Result<(), StreamError> {
     clear_terminal();
-    println!("{}", get_str_from_server(stream)?);
+    mouse::print_line(&restyle(&get_str_from_server(stream)?));
     Ok(())
 }
 
-fn print_str_from_server(stream:  &mut TcpStream) -> Result<(), StreamError> {
-    print!("{}", get_str_from_server(stream)?);
+fn print_str_from_server<C: Connection>(stream: &mut C) -> Result<(), StreamError> {
+    let text = restyle(&get_str_from_server(stream)?);
+    print!("{}", text);
+    mouse::shown(&text);
     Ok(())
 }
 
-fn print_and_reply(stream:  &mut TcpStream) -> Result<(), StreamError> {
-    println!("{}", get_str_from_server(stream)?);
-    send_message(stream)
+fn print_and_reply<C: Connection>(stream: &mut C, layout: &mut HandLayout, settings: &Settings) 
+    -> Result<(), StreamError> 
+{
+    mouse::print_line(&restyle(&get_str_from_server(stream)?));
+    send_message(stream, layout, settings)
 }
 
-fn send_message(stream:  &mut TcpStream) -> Result<(), StreamError> {
+fn send_message<C: Connection>(stream: &mut C, layout: &mut HandLayout, settings: &Settings) 
+    -> Result<(), StreamError> 
+{
     let mut reply = String::new();
     let mut cont = true;
     while cont {
         match get_input() {
             Ok(s) => {
-                reply = s.trim().to_string();
+                reply = settings.translate_input(s.trim());
                 cont = false
             },
             Err(_) => println!("Could not parse the input")
         };
     }
+
+    // remember a new layout before the server asks for it
+    if let Some(new_layout) = reply.strip_prefix('l').and_then(|r| HandLayout::parse(r.trim())) {
+        *layout = new_layout;
+        Settings::save_layout(new_layout).unwrap_or(());
+    }
+
     send_str_to_server(stream, &reply)?;
     Ok(())
 }
 
+/// send the name of the player and the token to reconnect, which is empty if there is none
+pub fn send_hello<C: Connection>(stream: &mut C, name: &str, token: &str) -> Result<(), StreamError> {
+    send_str_to_server(stream, name)?;
+    send_str_to_server(stream, token)
+}
+
 /// convert a string to a sequence of bytes and sent it to the server
-pub fn send_str_to_server(stream: &mut TcpStream, s: &str) -> Result<(), StreamError> {
+pub fn send_str_to_server<C: Connection>(stream: &mut C, s: &str) -> Result<(), StreamError> {
     send_bytes_to_server(stream, &s.as_bytes())?;
     Ok(())
 }
 
 /// send a sequence of bytes to the server and wait for confirmation that it has been received
-pub fn send_bytes_to_server(stream: &mut TcpStream, bytes: &[u8]) -> Result<(), StreamError> {
+pub fn send_bytes_to_server<C: Connection>(stream: &mut C, bytes: &[u8]) -> Result<(), StreamError> {
     
-    // ensure that the number of bytes is small enough
-    if bytes.len() > MAX_N_BUFFERS * BUFFER_SIZE {
-        return Err(StreamError { message: format!(
-                    "Stream too long: size: {}, maximum size: {}",
-                    bytes.len(), MAX_N_BUFFERS*BUFFER_SIZE
-                   ) })
-    }
-
-    // the first bytes will determine the number of times the buffer should be read
-    let mut n_buffers: u8 = (bytes.len() / BUFFER_SIZE) as u8;
-    if bytes.len() % BUFFER_SIZE != 0 {
-        n_buffers += 1;
-    }
-    stream.write(&[n_buffers])?;
-
-    // write the data stream
-    for i in 1..(n_buffers as usize) {
-        stream.write(&bytes[(i-1)*BUFFER_SIZE..i*BUFFER_SIZE])?;
-    }
-    if n_buffers > 0 {
-        stream.write(&bytes[((n_buffers-1) as usize)*BUFFER_SIZE..])?;
-    }
+    stream.write_frames(bytes)?;
 
     // wait for a reply to be sent from the receiver
     while let Err(_) = stream.read_exact(&mut [0]) {}
@@ -214,7 +433,7 @@ pub fn send_bytes_to_server(stream: &mut TcpStream, bytes: &[u8]) -> Result<(),
 }
 
 /// get a sequence of bytes from the server and convert it to a string
-pub fn get_str_from_server(stream: &mut TcpStream) -> Result<String, StreamError> {
+pub fn get_str_from_server<C: Connection>(stream: &mut C) -> Result<String, StreamError> {
     let bytes = get_bytes_from_server(stream)?;
     match String::from_utf8(bytes) {
         Ok(s) => Ok(s),
@@ -223,30 +442,8 @@ pub fn get_str_from_server(stream: &mut TcpStream) -> Result<String, StreamError
 }
 
 /// get a sequence of bytes from the server
-pub fn get_bytes_from_server(stream: &mut TcpStream) -> Result<Vec<u8>, StreamError> {
-    
-    // buffer
-    let mut buffer: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
-
-    // the first bytes will determine the number of times the buffer should be read
-    let mut n_buffers: [u8; 1] = [0];
-    stream.read_exact(&mut n_buffers)?;
-
-    // vector containing the result
-    let mut res = Vec::<u8>::new();
-
-    // read the data stream
-    let mut size;
-    for _i in 0..n_buffers[0] {
-        size = stream.read(&mut buffer)?;
-        res.extend_from_slice(&buffer[..size]);
-    }
-   
-    // send something to confirm I have received the data
-    stream.write(&[0])?;
-
-    // return the result
-    Ok(res)
+pub fn get_bytes_from_server<C: Connection>(stream: &mut C) -> Result<Vec<u8>, StreamError> {
+    Ok(stream.read_frames()?)
 }
 
 /// wait a moment (`N_MILLISECONDS_WAIT` in milliseconds)
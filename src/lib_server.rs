@@ -5,14 +5,295 @@ pub use std::io::{ stdin, Read, Write };
 pub use std::net::{ TcpListener, TcpStream, Shutdown };
 pub use std::str::from_utf8;
 pub use std::sync::{ Arc, Mutex };
+pub use events::{ Event, EventLog, PreviousTurn, N_EVENTS_SHOWN };
+pub use layout::LayoutSettings;
+pub use connection::{ Connection, EncryptedConnection, Listener, Side, WorkerConnection, WorkerListener };
+pub use encode::KEY_SIZE;
+pub use player_name::PlayerName;
+pub use turn_order::TurnOrder;
+pub use player::Player;
+pub use state::{ PublicState, StateTracker, StateUpdate, TurnView, FULL_STATE_PERIOD };
+pub use logging::LogLevel;
+use sanitize::sanitize_bytes;
 use std::string::FromUtf8Error;
+use std::time::{ Duration, Instant };
 
-const BUFFER_SIZE: usize = 50;
-const MAX_N_BUFFERS: usize = 255;
 const N_MILLISECONDS_WAIT: u64 = 10;
 const N_MILLISECONDS_LONG_WAIT: u64 = 1000;
+const N_SECONDS_NAME_TIMEOUT: u64 = 5;
+
+/// a player whose time is up gets this fraction of the turn timeout more before they are
+/// considered gone
+const TURN_GRACE_DIVISOR: u32 = 4;
 const YES_VALUES: [&str;10] = ["y", "yes", "yeah", "aye", "oui", "ja", "da", "ok", "si", "sim"];
 
+/// file with the server settings
+pub const SERVER_CONFIG_FILE: &str = "Config/server.dat";
+
+/// number of checkpoints kept by default
+pub const DEFAULT_N_CHECKPOINTS: usize = 10;
+
+/// Server settings which do not affect the game rules
+#[derive(Debug, PartialEq, Clone)]
+pub struct ServerConfig {
+    /// let a bot play in place of a player who left, until they reconnect
+    pub bot_takeover: bool,
+    /// number of seconds a player has to act before being considered gone (0: no limit), after
+    /// which they get a quarter of it more
+    pub turn_timeout: u64,
+    /// number of seconds to wait for a player to reconnect before a bot takes over (0: no limit)
+    pub reconnection_timeout: u64,
+    /// passphrase shared with the clients to encrypt the connections, if any
+    pub passphrase: Option<String>,
+    /// number of turns of a round between two checkpoints of the game (0: no checkpoint)
+    pub checkpoint_every: u32,
+    /// number of checkpoints kept
+    pub n_checkpoints: usize,
+    /// most players a new game of the JSON-RPC lobby can have
+    pub max_players: u8,
+    /// how much the server prints
+    pub log_level: LogLevel
+}
+
+impl ServerConfig {
+
+    /// Default settings: no bot, no timeout, and no checkpoint
+    pub fn new() -> ServerConfig {
+        ServerConfig {
+            bot_takeover: false,
+            turn_timeout: 0,
+            reconnection_timeout: 0,
+            passphrase: None,
+            checkpoint_every: 0,
+            n_checkpoints: DEFAULT_N_CHECKPOINTS,
+            max_players: MAX_N_PLAYERS,
+            log_level: LogLevel::Info
+        }
+    }
+
+    /// Load the settings from a file
+    ///
+    /// As for the game config, each setting is given by the first word of a line:
+    ///
+    /// * whether a bot should take the seat of a player who left (`1` for yes and `0` for no)
+    /// * number of seconds a player has to act (`0` for no limit)
+    /// * number of seconds to wait for a player to reconnect (`0` for no limit)
+    /// * passphrase to encrypt the connections with, which the clients must also use (`-` for
+    ///   none)
+    /// * number of turns of a round between two checkpoints (`0` for none; see the `checkpoints`
+    ///   module)
+    /// * number of checkpoints kept
+    /// * most players a new game of the JSON-RPC lobby can have (16 at most)
+    /// * log level: `error`, `info`, or `debug` (see the `logging` module)
+    ///
+    /// Missing lines keep their default value.
+    pub fn from_file(fname: &str) -> Result<ServerConfig, InvalidInputError> {
+        let content = std::fs::read_to_string(fname)?;
+        ServerConfig::parse(&content)
+    }
+
+    /// Parse the settings from the content of a settings file
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::lib_server::{ LogLevel, ServerConfig };
+    ///
+    /// let settings = ServerConfig::parse("1 bot takeover\n60 turn timeout\n").unwrap();
+    ///
+    /// assert_eq!(ServerConfig { bot_takeover: true, turn_timeout: 60, reconnection_timeout: 0, passphrase: None,
+    ///                          checkpoint_every: 0, n_checkpoints: 10, max_players: 16, log_level: LogLevel::Info },
+    ///            settings);
+    /// assert_eq!(20, ServerConfig::parse("0\n0\n0\n-\n20\n").unwrap().checkpoint_every);
+    /// assert_eq!(Some("s3cret".to_string()), ServerConfig::parse("0\n0\n0\ns3cret\n").unwrap().passphrase);
+    /// assert!(ServerConfig::parse("1\nsixty\n").is_err());
+    /// assert_eq!(LogLevel::Debug, ServerConfig::parse("0\n0\n0\n-\n0\n10\n4\ndebug\n").unwrap().log_level);
+    /// assert!(ServerConfig::parse("0\n0\n0\n-\n0\n10\n17\n").is_err());
+    /// ```
+    pub fn parse(content: &str) -> Result<ServerConfig, InvalidInputError> {
+        let mut settings = ServerConfig::new();
+        let mut lines = content.lines().map(|l| l.split(' ').next().unwrap_or(""));
+        if let Some(w) = lines.next() {
+            settings.bot_takeover = w == "1";
+        }
+        if let Some(w) = lines.next() {
+            settings.turn_timeout = w.parse::<u64>()?;
+        }
+        if let Some(w) = lines.next() {
+            settings.reconnection_timeout = w.parse::<u64>()?;
+        }
+        if let Some(w) = lines.next() {
+            settings.passphrase = Some(w.to_string()).filter(|w| !w.is_empty() && w != "-");
+        }
+        if let Some(w) = lines.next() {
+            settings.checkpoint_every = w.parse::<u32>()?;
+        }
+        if let Some(w) = lines.next() {
+            settings.n_checkpoints = w.parse::<usize>()?;
+        }
+        if let Some(w) = lines.next() {
+            settings.max_players = w.parse::<u8>()?;
+            if settings.max_players == 0 || settings.max_players > MAX_N_PLAYERS {
+                return Err(InvalidInputError {});
+            }
+        }
+        if let Some(w) = lines.next() {
+            settings.log_level = LogLevel::parse(w).ok_or(InvalidInputError {})?;
+        }
+        Ok(settings)
+    }
+
+    /// Take the settings which can change while games are running from `new`, and describe the
+    /// changes
+    ///
+    /// Only the turn timeout, the most players of a new game, and the log level are taken; the
+    /// other settings keep the values they had when the server started.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::lib_server::*;
+    ///
+    /// let mut settings = ServerConfig::new();
+    /// let new = ServerConfig::parse("1\n90\n0\n-\n0\n10\n4\n").unwrap();
+    ///
+    /// assert_eq!(vec!["turn timeout: no limit → 90 s", "most players in a new game: 16 → 4"], settings.reload(&new));
+    /// assert_eq!((90, 4, false), (settings.turn_timeout, settings.max_players, settings.bot_takeover));
+    /// assert!(settings.reload(&new).is_empty());
+    /// ```
+    pub fn reload(&mut self, new: &ServerConfig) -> Vec<String> {
+        let timeout = |t: u64| if t == 0 { "no limit".to_string() } else { format!("{} s", t) };
+        let mut changes = Vec::new();
+        if new.turn_timeout != self.turn_timeout {
+            changes.push(format!("turn timeout: {} → {}", timeout(self.turn_timeout), timeout(new.turn_timeout)));
+        }
+        if new.max_players != self.max_players {
+            changes.push(format!("most players in a new game: {} → {}", self.max_players, new.max_players));
+        }
+        if new.log_level != self.log_level {
+            changes.push(format!("log level: {} → {}", self.log_level.name(), new.log_level.name()));
+        }
+        self.turn_timeout = new.turn_timeout;
+        self.max_players = new.max_players;
+        self.log_level = new.log_level;
+        changes
+    }
+
+    /// Time a player has to act, if limited
+    pub fn turn_timeout(&self) -> Option<Duration> {
+        to_duration(self.turn_timeout)
+    }
+
+    /// Time to wait for a player to reconnect before a bot takes over
+    ///
+    /// Without bot takeover, the server waits for as long as it takes.
+    pub fn reconnection_timeout(&self) -> Option<Duration> {
+        if self.bot_takeover {
+            to_duration(self.reconnection_timeout)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig::new()
+    }
+}
+
+/// Request from the host which ends the current turn
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HostAction {
+    RestartRound,
+    EndGame,
+    /// go back to the checkpoint written after this number of turns of the round
+    Rewind(u32)
+}
+
+/// Moderation state: who the host is, the votes to kick players out, and what the host asked for
+#[derive(Debug, PartialEq, Clone)]
+pub struct Seats {
+    /// index of the player who can use the moderation commands
+    pub host: usize,
+    /// `votes[i][j]` is true if player `j` voted to kick player `i`
+    votes: Vec<Vec<bool>>,
+    /// action requested by the host, to be applied at the end of the turn
+    pub host_action: Option<HostAction>,
+    /// numbers of turns played in the round before each of its checkpoints (see the
+    /// `checkpoints` module), which the host can rewind the game to
+    pub rewind_points: Vec<u32>
+}
+
+impl Seats {
+
+    /// Create the seats for a game
+    pub fn new(n_players: usize, host: usize) -> Seats {
+        Seats {
+            host,
+            votes: vec![vec![false; n_players]; n_players],
+            host_action: None,
+            rewind_points: Vec::new()
+        }
+    }
+
+    /// Let a bot play at a seat; the host role goes to another player if needed
+    pub fn replace_by_bot<S>(&mut self, players: &mut [Player<S>], player: usize) {
+        players[player].connected = false;
+        if self.host == player {
+            if let Some(i) = players.iter().position(|p| p.connected) {
+                self.host = i;
+            }
+        }
+    }
+
+    /// Kick a player out: a bot takes their seat for the rest of the game
+    pub fn kick<S>(&mut self, players: &mut [Player<S>], player: usize) {
+        players[player].kicked = true;
+        self.votes[player] = vec![false; self.votes.len()];
+        self.replace_by_bot(players, player);
+    }
+
+    /// Record a vote to kick a player; return the number of votes and the number needed
+    ///
+    /// A player is kicked out when a majority of the other players (not counting bots), and at least
+    /// two of them, agree.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::lib_server::*;
+    ///
+    /// let names: Vec<String> = ["Alice", "Bob", "Carol", "Dave"].iter().map(|s| s.to_string()).collect();
+    /// let players = Player::seat(Hand::deal(&names, vec![Sequence::new(); 4]), vec![(); 4]);
+    ///
+    /// let mut seats = Seats::new(4, 0);
+    /// assert_eq!((1, 2), seats.vote_kick(&players, 1, 3));
+    /// assert_eq!((1, 2), seats.vote_kick(&players, 1, 3));
+    /// assert_eq!((2, 2), seats.vote_kick(&players, 2, 3));
+    ///
+    /// // a single player can't kick another one out
+    /// let mut seats = Seats::new(2, 0);
+    /// assert_eq!((1, 2), seats.vote_kick(&players[..2], 1, 0));
+    /// ```
+    pub fn vote_kick<S>(&mut self, players: &[Player<S>], voter: usize, target: usize) -> (usize, usize) {
+        self.votes[target][voter] = true;
+        let n_voters = (0..players.len()).filter(|&i| i != target && players[i].connected).count();
+        let n_votes = (0..players.len())
+            .filter(|&i| i != target && players[i].connected && self.votes[target][i])
+            .count();
+        (n_votes, (n_voters / 2 + 1).max(2))
+    }
+}
+
+fn to_duration(n_seconds: u64) -> Option<Duration> {
+    if n_seconds == 0 {
+        None
+    } else {
+        Some(Duration::from_secs(n_seconds))
+    }
+}
+
 /// check if a string is a synonym of ‘yes’
 ///
 /// # Example
@@ -36,36 +317,52 @@ pub fn is_yes(s: &str) -> bool {
     false
 }
 
-/// get the player name
-pub fn handle_client(mut stream: TcpStream) -> Result<(TcpStream, String, usize), StreamError> {
+/// address of the other end of a connection, for the logs
+fn peer_address<C: Connection>(stream: &C) -> String {
+    stream.tcp_stream().and_then(|s| s.peer_addr().ok())
+        .map(|a| a.to_string()).unwrap_or_else(|| "an unknown address".to_string())
+}
+
+/// get the player name, asking again until it is valid
+pub fn handle_client<C: Connection>(mut stream: C) -> Result<(C, String, usize), StreamError> {
     let mut player_name: String = "".to_string();
-    match get_str_from_client(&mut stream) {
-        Ok(s) => {
-            // great the player
-            player_name = s.clone();
-            let msg = format!("Hello {}!\nWaiting for other players to join...", &s);
-            stream.write(&[1])?;
-            send_str_to_client(&mut stream, &msg)?;
-        },
-        Err(_)=> {
-            println!("An error occured while reading the stream; terminating connection with {}", 
-                     stream.peer_addr()?);
-            stream.shutdown(Shutdown::Both)?;
-        }
-    };
+    loop {
+        match get_hello_from_client(&mut stream) {
+            Ok((s, _)) => match PlayerName::new(&s) {
+                Ok(name) => {
+                    // great the player
+                    player_name = name.into();
+                    let msg = format!("Hello {}!\nWaiting for other players to join...", &player_name);
+                    stream.write_all(&[1])?;
+                    send_str_to_client(&mut stream, &msg)?;
+                    break;
+                },
+                Err(e) => {
+                    stream.write_all(&[0])?;
+                    send_str_to_client(&mut stream, &format!("{}\n", e))?;
+                }
+            },
+            Err(_)=> {
+                logging::error(&format!("An error occured while reading the stream; terminating connection with {}", 
+                                        peer_address(&stream)));
+                stream.close();
+                break;
+            }
+        };
+    }
     Ok((stream, player_name, 0))
 }
 
 /// get the player name and check that it is in the list of players and not already taken
-pub fn handle_client_load(mut stream: TcpStream, names: &Vec<String>, names_taken: Arc<Mutex<Vec<String>>>) 
-    -> Result<(TcpStream, String, usize), StreamError> 
+pub fn handle_client_load<C: Connection>(mut stream: C, names: &Vec<String>, names_taken: Arc<Mutex<Vec<String>>>) 
+    -> Result<(C, String, usize), StreamError> 
 {
     let mut player_name: String;
     let position: usize;
     loop {
-        match get_str_from_client(&mut stream) {
-            Ok(s) => {
-                player_name = s.clone();
+        match get_hello_from_client(&mut stream) {
+            Ok((s, _)) => {
+                player_name = PlayerName::new(&s).map(String::from).unwrap_or_default();
                 
                 // check if the name is in the list
                 match names.iter().position(|x| x == &player_name) {
@@ -74,14 +371,14 @@ pub fn handle_client_load(mut stream: TcpStream, names: &Vec<String>, names_take
                         let mut lock = names_taken.lock().unwrap();
                         match lock.iter().position(|x| x == &player_name) {
                             Some(_) => {
-                                stream.write(&[0])?;
+                                stream.write_all(&[0])?;
                                 let msg = format!("Sorry, this name is already taken!\n");
                                 send_str_to_client(&mut stream, &msg)?;
                             },
                             None => {
                                 position = i;
-                                stream.write(&[1])?;
-                                let msg = format!("Hello {}!\nWaiting for other players to join...", &s);
+                                stream.write_all(&[1])?;
+                                let msg = format!("Hello {}!\nWaiting for other players to join...", &player_name);
                                 send_str_to_client(&mut stream, &msg)?;
                                 lock.push(player_name.clone());
                                 break;
@@ -89,17 +386,17 @@ pub fn handle_client_load(mut stream: TcpStream, names: &Vec<String>, names_take
                         }
                     },
                     None => {
-                        stream.write(&[0])?;
-                        let msg = format!("Sorry, {} is not in the list of players!\n", &s);
+                        stream.write_all(&[0])?;
+                        let msg = format!("Sorry, {} is not in the list of players!\n", &player_name);
                         send_str_to_client(&mut stream, &msg)?;
                     }
                 }
 
             },
             Err(_)=> {
-                println!("An error occured while reading the stream; terminating connection with {}", 
-                         stream.peer_addr()?);
-                stream.shutdown(Shutdown::Both)?;
+                logging::error(&format!("An error occured while reading the stream; terminating connection with {}", 
+                                        peer_address(&stream)));
+                stream.close();
             }
         };
     }
@@ -107,359 +404,872 @@ pub fn handle_client_load(mut stream: TcpStream, names: &Vec<String>, names_take
 }
 
 /// wait for a player to reconnect
-pub fn wait_for_reconnection(stream: &mut TcpStream, name: &str, port: usize) 
-    -> Result<(), StreamError>
+///
+/// A TCP listener must be non-blocking. Connections from other players are turned down. Return
+/// `false` if the player did not come back before the timeout.
+pub fn wait_for_reconnection<L: Listener>(stream: &mut L::Stream, name: &str, token: &str, listener: &L, 
+                                          timeout: Option<Duration>) -> Result<bool, StreamError>
 {
-
-    // wait for a connection
-
-    // set-up the tcp listener
-    let listener = TcpListener::bind(format!("0.0.0.0:{}", port))?;
-
-    // get connections and check the player is the right one
-    for stream_res in listener.incoming() {
-        match stream_res {
-            Ok(mut new_stream) => {
-                println!("New connection: {}", new_stream.peer_addr()?);
-
-                // get the name 
-                match get_str_from_client(&mut new_stream) {
-                    Ok(s) => {
-                        if s == name {
-                            new_stream.write(&[1]).unwrap_or(1);
-                            send_str_to_client(&mut new_stream, 
-                                    &reset_style_string()).unwrap_or(());
-                            *stream = new_stream;
-                            break;
-                        } else {
-                            new_stream.write(&[2]).unwrap_or(1);
-                            send_str_to_client(&mut new_stream, 
-                                    &"Sorry; you're not the player we're expecting\n").unwrap_or(());
-                            new_stream.write(&[5]).unwrap_or(1);
-                        }
-                    },
-                    _ => ()
-                }
-            },
-            _ => ()
-        };
+    let start = Instant::now();
+    let expected = [(name.to_string(), token.to_string())];
+    loop {
+        if let Some(new_stream) = accept_player(listener, &expected)?.map(|(s, _)| s) {
+            *stream = new_stream;
+            return Ok(true);
+        }
+        if let Some(t) = timeout {
+            if start.elapsed() >= t {
+                return Ok(false);
+            }
+        }
+        if shutdown::requested() {
+            return Ok(false);
+        }
+        long_wait();
     }
-    Ok(())
 } 
 
+/// check if a player whose seat is held by a bot is trying to reconnect
+///
+/// A TCP listener must be non-blocking. If the player is back, their stream is replaced, the bot
+/// is removed from their seat, and their index is returned. Players who were kicked out are
+/// turned down.
+pub fn try_reconnection<L: Listener>(listener: &L, players: &mut [Player<L::Stream>])
+    -> Result<Option<usize>, StreamError>
+{
+    let seats: Vec<usize> = (0..players.len()).filter(|&i| players[i].can_reconnect()).collect();
+    if seats.is_empty() {
+        return Ok(None);
+    }
+    let expected: Vec<(String, String)> = seats.iter()
+        .map(|&i| (players[i].name().to_string(), players[i].token.clone()))
+        .collect();
+    match accept_player(listener, &expected)? {
+        Some((new_stream, k)) => {
+            let i = seats[k];
+            players[i].connection = new_stream;
+            players[i].connected = true;
+            Ok(Some(i))
+        },
+        None => Ok(None)
+    }
+}
+
+/// accept a pending connection from one of the expected players, given by their names and tokens,
+/// and return its position among them
+///
+/// A player is recognised by their token, or by their name if the client did not send a known
+/// token.
+fn accept_player<L: Listener>(listener: &L, expected: &[(String, String)]) 
+    -> Result<Option<(L::Stream, usize)>, StreamError>
+{
+    let mut new_stream = match listener.try_accept()? {
+        Some(s) => s,
+        None => return Ok(None)
+    };
+
+    // get the name and the token, without waiting forever for a client which does not send them
+    new_stream.set_timeout(Some(Duration::from_secs(N_SECONDS_NAME_TIMEOUT)))?;
+    let hello = get_hello_from_client(&mut new_stream);
+    new_stream.set_timeout(None)?;
+    let position = hello.map(|(name, token)| {
+        let name = PlayerName::new(&name).map(String::from).unwrap_or_default();
+        expected.iter().position(|(_, t)| !token.is_empty() && *t == token)
+            .or_else(|| expected.iter().position(|(n, _)| *n == name))
+    });
+    match position {
+        Ok(Some(k)) => {
+            new_stream.write_all(&[1]).unwrap_or(());
+            send_str_to_client(&mut new_stream, &reset_style_string()).unwrap_or(());
+            Ok(Some((new_stream, k)))
+        },
+        Ok(None) => {
+            new_stream.write_all(&[2]).unwrap_or(());
+            send_str_to_client(&mut new_stream, 
+                    "Sorry; you're not the player we're expecting\n").unwrap_or(());
+            new_stream.write_all(&[5]).unwrap_or(());
+            Ok(None)
+        },
+        Err(_) => Ok(None)
+    }
+}
+
+/// function called after each play of a turn with the table, the players, the deck, the turn,
+/// and the cards taken from the table, to save the turn in progress
+pub type SaveTurn<'a, S> = dyn FnMut(&Table, &[Player<S>], &Deck, &Ongoing, &Sequence) + 'a;
+
 /// player turn
-pub fn start_player_turn(table: &mut Table, hands: &mut Vec<Sequence>, deck: &mut Sequence, 
-                         custom_rule_jokers: bool, player_names: &Vec<String>, current_player: usize, 
-                         n_players: usize, streams: &mut Vec<TcpStream>, port: usize, 
-                         sort_mode: &mut u8, previous_messages: &Vec<Option<String>>)
+///
+/// The turn starts afresh, or is resumed from a save made while it was in progress. It is handed
+/// to `save_turn` each time something is played or taken.
+pub fn start_player_turn<L: Listener>(table: &mut Table, players: &mut [Player<L::Stream>], deck: &mut Deck, 
+                         custom_rule_jokers: bool, current_player: usize, listener: &L, 
+                         log: &mut EventLog, server_config: &ServerConfig, seats: &mut Seats,
+                         resumed: Option<SavedTurn>, previous_turn: &PreviousTurn, save_turn: &mut SaveTurn<L::Stream>)
     -> Result<Option<String>,StreamError> {
 
-    // copy the initial hand
-    let hand_start_round = hands[current_player].clone();
+    // names of the players
+    let player_names = &hand::owners(players);
 
-    // copy the initial table
-    let table_start_round = table.clone();
-    
-    // cards taken from the table
-    let mut cards_from_table = Sequence::new();
+    // the hand and the table at the start of the turn are kept to give up, with the cards taken
+    // from the table
+    let is_resumed = resumed.is_some();
+    let (mut turn, mut cards_from_table) = resumed.unwrap_or_else(|| {
+        (Ongoing::start(table, &players[current_player].hand), Sequence::new())
+    });
+    let ledger = CardLedger::new(table, players, deck, &cards_from_table);
+
+    // the events of the turn are summed up for the player before it ends
+    let n_events_start = log.total();
     
-    // send the instructions
-    send_message_to_client(&mut streams[current_player], 
-                           &format!("\u{0007}\n{}", instructions_no_save(true,false)))?;
+    // tell the client that the turn starts and send the instructions
+    notify_turn_start(&mut players[current_player])?;
+    send_message_to_client(&mut players[current_player], 
+                           &format!("\n{}{}{}", instructions_no_save(true,false), CHAT_INSTRUCTIONS,
+                                    moderation_instructions(seats.host == current_player)))?;
+
+    // the other players see how long the turn lasts while they wait
+    let elapsed = players[current_player].turn_started.map(|t| t.elapsed()).unwrap_or_default();
+    for i in 0..players.len() {
+        if i != current_player && players[i].connected
+            && send_waiting_to_client(&mut players[i], &player_names[current_player], elapsed).is_err() {
+            lose_waiting_player(players, seats, log, i);
+        }
+    }
+
+    // number of events in the log when the other players were last told that the player is busy
+    let mut indicated_at = None;
+
+    // whether the player was asked for a message which has not come yet, and whether they already
+    // got more time once theirs was up
+    let mut asked = false;
+    let mut grace_given = false;
+
+    // show a resumed turn as it was left
+    if is_resumed {
+        print_situation_remote(table, players, deck, current_player, current_player, true, &cards_from_table,
+                               turn.has_played(&players[current_player].hand),
+                               cards_from_table.number_cards() > 0, previous_turn, log)?;
+    }
+
+    // tell the player when all they can do is pick a card
+    if turn.is_forced(table, &players[current_player].hand, custom_rule_jokers) {
+        send_private_message_to_client(&mut players[current_player], "", 
+                                       "Nothing can be played: ‘e’ picks a card and ends your turn\n")?;
+    }
 
     // get and process the player choice
-    let mut message: String;
     loop {
-        match get_message_from_client(&mut streams[current_player]) {
+        ledger.assert_holds(table, players, deck, &cards_from_table, "a message from the client");
+
+        // the time left on the turn is counted over all the messages
+        if let Some(deadline) = players[current_player].turn_deadline {
+            let left = deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1));
+            players[current_player].set_timeout(Some(left)).unwrap_or(());
+        }
+
+        let received = if asked {
+            get_bytes_from_client(&mut players[current_player])
+        } else {
+            get_message_from_client(&mut players[current_player])
+        };
+        asked = false;
+        match received {
             Ok(mes) => {
+
+                // the other players see that the player is busy when it does not show on the table,
+                // once until something new happens
+                if is_arranging(&mes) && indicated_at != Some(log.total()) {
+                    indicated_at = Some(log.total());
+                    let activity = format!("{} is arranging cards...", player_names[current_player]);
+                    for i in 0..players.len() {
+                        if i != current_player && players[i].connected
+                            && send_activity_to_client(&mut players[i], &activity).is_err() {
+                            lose_waiting_player(players, seats, log, i);
+                        }
+                    }
+                }
+
                 if mes.len() == 0 {
-                    ()
+                    // nothing more can be read once the server is shutting down
+                    if shutdown::requested() {
+                        turn.restore(table, &mut players[current_player].hand);
+                        return Ok(None);
+                    }
+
+                    // an empty line shows the situation again, at the size of the terminal
+                    print_situation_remote(table, players, deck, current_player,
+                                           current_player, true, &cards_from_table,
+                                           turn.has_played(&players[current_player].hand),
+                                           cards_from_table.number_cards() > 0, previous_turn, log)?;
+                } else if commands::is_list(&String::from_utf8_lossy(&mes)) {
+
+                    // a list of actions, played as a whole
+                    let steps = match commands::parse(&sanitize_bytes(mes.to_vec())?) {
+                        Ok(steps) => steps,
+                        Err(m) => {
+                            send_private_message_to_client(&mut players[current_player], "",
+                                                           &format!("Nothing was played: {}\n", m))?;
+                            continue;
+                        }
+                    };
+                    let cards = commands::TurnCards { table, hand: &mut players[current_player].hand,
+                                                      cards_from_table: &mut cards_from_table, deck };
+                    let events = match commands::execute(&steps, turn, &player_names[current_player], cards,
+                                                         custom_rule_jokers) {
+                        Ok((executed, events)) => {
+                            let n_events = events.len();
+                            for event in events {
+                                log.push(event);
+                            }
+                            match executed {
+                                commands::Executed::Ongoing(ongoing) => turn = ongoing,
+                                commands::Executed::Ended(TurnEnd::Drew(card)) => {
+                                    match players[current_player].sort_mode {
+                                        1 => players[current_player].hand.sort_by_rank(),
+                                        2 => players[current_player].hand.sort_by_suit(),
+                                        _ => ()
+                                    }
+                                    ledger.assert_holds(table, players, deck, &cards_from_table, "a draw");
+                                    return Ok(Some(match card {
+                                        Some(card) => format!("You picked a {}{}\n", card, &reset_style_string()),
+                                        None => "No more card to draw!\n".to_string()
+                                    }));
+                                },
+                                commands::Executed::Ended(_) => {
+                                    let summary = events::turn_summary(&log.since(n_events_start),
+                                                                       players[current_player].hand.number_cards());
+                                    send_private_message_to_client(&mut players[current_player], "", &format!("{}\n", summary))?;
+                                    break
+                                }
+                            }
+                            log.last(n_events).iter().map(|e| format!("  {}", e)).collect::<Vec<String>>()
+                        },
+                        Err((ongoing, m)) => {
+                            turn = ongoing;
+                            send_private_message_to_client(&mut players[current_player], "", &format!("{}\n", m))?;
+                            continue;
+                        }
+                    };
+                    ledger.assert_holds(table, players, deck, &cards_from_table, "a list of actions");
+                    save_turn(table, players, deck, &turn, &cards_from_table);
+                    let has_played = turn.has_played(&players[current_player].hand);
+                    show_new_situation(table, players, deck, current_player, &cards_from_table, has_played,
+                                       &events, previous_turn, log, seats)?;
+
+                    // if the player has no more card, end the turn 
+                    if players[current_player].hand.number_cards() == 0 && cards_from_table.number_cards() == 0 {
+                        break;
+                    }
                 } else {
                     match mes[0] {
                     
-                        // value 'e': end the turn
+                        // value 'e': end the turn, picking a card if nothing was played
                         101 => {
-                            if cards_from_table.number_cards() != 0 {
-                                message = "You can't end your turn until you've played all the cards you've taken from the table!\n"
-                                          .to_string();
-                                send_message_to_client(&mut streams[current_player], &message)?;
-                            } else if custom_rule_jokers && hands[current_player].contains_joker() {
-                                message = "Jokers must be played!\n".to_string();
-                                send_message_to_client(&mut streams[current_player], &message)?;
-                            } else if hands[current_player].contains(&hand_start_round) {
-                                match pick_a_card(&mut hands[current_player], deck) {
-                                    Ok(card) => message = format!("You picked a {}{}\n", &card, &reset_style_string()),
-                                    Err(_) => message = "No more card to draw!\n".to_string()
-                                };
-                                match *sort_mode {
-                                    1 => hands[current_player].sort_by_rank(),
-                                    2 => hands[current_player].sort_by_suit(),
-                                    _ => ()
+                            turn = match turn {
+                                Ongoing::Draw(draw) => match draw.draw(&mut players[current_player].hand, deck,
+                                                                       custom_rule_jokers) {
+                                    Ok(end) => {
+                                        let message = match end.outcome() {
+                                            TurnEnd::Drew(Some(card)) => format!("You picked a {}{}\n", card, &reset_style_string()),
+                                            _ => "No more card to draw!\n".to_string()
+                                        };
+                                        match players[current_player].sort_mode {
+                                            1 => players[current_player].hand.sort_by_rank(),
+                                            2 => players[current_player].hand.sort_by_suit(),
+                                            _ => ()
+                                        }
+                                        log.push(Event::DrewCard(player_names[current_player].clone()));
+                                        ledger.assert_holds(table, players, deck, &cards_from_table, "a draw");
+                                        return Ok(Some(message));
+                                    },
+                                    Err((draw, m)) => {
+                                        send_private_message_to_client(&mut players[current_player], "", &format!("{}\n", m))?;
+                                        Ongoing::Draw(draw)
+                                    }
+                                },
+                                Ongoing::Meld(meld) => {
+
+                                    // show the player what they did before the others see the end of the turn
+                                    let hand = &players[current_player].hand;
+                                    let summary = match meld.can_pass(hand, &cards_from_table, custom_rule_jokers) {
+                                        Ok(()) => Some(events::turn_summary(&log.since(n_events_start), hand.number_cards())),
+                                        Err(_) => None
+                                    };
+                                    let confirmed = match summary {
+                                        Some(summary) => ask_confirmation(&mut players[current_player].connection,
+                                                                          &format!("{}\nEnd your turn?", summary))?,
+                                        None => true
+                                    };
+                                    if !confirmed {
+                                        Ongoing::Meld(meld)
+                                    } else {
+                                        match meld.pass(&players[current_player].hand, &cards_from_table, custom_rule_jokers) {
+                                            Ok(_) => {
+                                                log.push(Event::EndedTurn(player_names[current_player].clone()));
+                                                break
+                                            },
+                                            Err((meld, m)) => {
+                                                send_private_message_to_client(&mut players[current_player], "", &format!("{}\n", m))?;
+                                                Ongoing::Meld(meld)
+                                            }
+                                        }
+                                    }
                                 }
-                                return Ok(Some(message));
-                            } else {
-                                break
-                            }
+                            };
                         },
                     
                         // value 'p': play a sequence
                         112 => {
-                            match play_sequence_remote(&mut hands[current_player], &mut cards_from_table,
-                                                       table, &mes[1..]) {
-                                Ok(None) => {
-                                    
-                                    // print the situation for the current player
-                                    print_situation_remote(&table, &hands, deck, player_names, current_player,
-                                                           current_player, &mut streams[current_player],
-                                                           true, &cards_from_table, 
-                                                           !hands[current_player].contains(&hand_start_round),
-                                                           cards_from_table.number_cards() > 0)?;
-
-                                    // print the new situation for the other players
-                                    for i in 0..n_players {
-                                        if i != current_player {
-                                            print_situation_remote(&table, &hands, deck, player_names, 
-                                                                   i, current_player, &mut streams[i],
-                                                                   false, &cards_from_table, false, false)?;
-                                            if let Some(s) = &previous_messages[i] {
-                                                send_message_to_client(&mut streams[i], &s).unwrap();
-                                            };
-                                        }
-                                    }
+                            let player = &mut players[current_player];
+                            match play_sequence_remote(&mut player.hand, &mut cards_from_table, table, &mes[1..],
+                                                       &mut player.connection) {
+                                Ok(Ok(seq)) => {
+                                    turn = turn.meld();
+                                    save_turn(table, players, deck, &turn, &cards_from_table);
+                                    log.push(Event::PlayedSequence(player_names[current_player].clone(), seq));
+                                    let event_string = format!("  {}", log.last(1)[0]);
+                                    let has_played = turn.has_played(&players[current_player].hand);
+                                    show_new_situation(table, players, deck, current_player, &cards_from_table,
+                                                       has_played, &[event_string], previous_turn, log, seats)?;
 
                                     // if the player has no more card, end the turn 
-                                    if hands[current_player].number_cards() == 0 {
+                                    if players[current_player].hand.number_cards() == 0 {
                                         break;
                                     }
                                 },
 
-                                Ok(Some(s)) => {
-                                    print_situation_remote(&table, &hands, deck, player_names, current_player,
-                                                           current_player, &mut streams[current_player],
-                                                           true, &cards_from_table, 
-                                                           !hands[current_player].contains(&hand_start_round),
-                                                           cards_from_table.number_cards() > 0)?;
-                                    send_message_to_client(&mut streams[current_player], &s)?;
+                                Ok(Err(s)) => {
+                                    print_situation_remote(table, players, deck, current_player,
+                                                           current_player, true, &cards_from_table, 
+                                                           turn.has_played(&players[current_player].hand),
+                                                           cards_from_table.number_cards() > 0, previous_turn, log)?;
+                                    send_private_message_to_client(&mut players[current_player], "", &s)?;
                                 },
 
-                                Err(_) => send_message_to_client(&mut streams[current_player], &"Communication error\n")?
+                                Err(_) => send_message_to_client(&mut players[current_player], &"Communication error\n")?
                             };
                         },
                         
                         // value 't': take a sequence from the table
                         116 => {
                             match take_sequence_remote(table, &mut cards_from_table, &mes[1..], 
-                                                       &mut streams[current_player]) {
-                                Ok(()) => {
-
-                                    // print the new situation for the current player
-                                    print_situation_remote(&table, &hands, deck, player_names, 
-                                                           current_player, current_player, 
-                                                           &mut streams[current_player], true, &cards_from_table,
-                                                           false, cards_from_table.number_cards() > 0)?;
-
-                                    // print the new situation for the other players
-                                    for i in 0..n_players {
-                                        if i != current_player {
-                                            print_situation_remote(&table, &hands, deck, player_names, 
-                                                                   i, current_player, &mut streams[i],
-                                                                   false, &cards_from_table, false, false)?;
-                                            match &previous_messages[i] {
-                                                Some(s) => send_message_to_client(&mut streams[i], &s).unwrap(),
-                                                None => ()
-                                            };
-                                        }
+                                                       &mut players[current_player]) {
+                                Ok(taken) => {
+                                    if !taken.is_empty() {
+                                        turn = turn.meld();
+                                        save_turn(table, players, deck, &turn, &cards_from_table);
+                                    }
+                                    for (n, seq) in taken {
+                                        log.push(Event::TookSequence(player_names[current_player].clone(), n, seq));
                                     }
+
+                                    show_new_situation(table, players, deck, current_player, &cards_from_table,
+                                                       false, &[], previous_turn, log, seats)?;
                                 },
 
-                                Err(_) => send_message_to_client(&mut streams[current_player], &"Communication error\n")?
+                                Err(_) => send_message_to_client(&mut players[current_player], &"Communication error\n")?
+                            };
+                        },
+                        
+                        // value 'd': split a sequence of the table in two
+                        100 => {
+                            let content = sanitize_bytes(mes[1..].to_vec())?;
+                            match selection::parse_split(&content, table) {
+                                Ok((n, index)) => {
+                                    let taken = table.split(n, index).unwrap_or_else(Sequence::new);
+                                    cards_from_table.merge(taken.clone());
+                                    turn = turn.meld();
+                                    save_turn(table, players, deck, &turn, &cards_from_table);
+                                    log.push(Event::SplitSequence(player_names[current_player].clone(), n, index, taken));
+
+                                    let has_played = turn.has_played(&players[current_player].hand);
+                                    show_new_situation(table, players, deck, current_player, &cards_from_table,
+                                                       has_played, &[], previous_turn, log, seats)?;
+                                },
+                                Err(e) => send_private_message_to_client(&mut players[current_player], "",
+                                                                         &format!("Nothing was split: {}\n", e))?
                             };
                         },
                         
                         // value 'a': add cards to a sequence already on the table
                         97 => {
-                            match add_to_table_sequence_remote(table, &mut hands[current_player], 
-                                                               &mut cards_from_table, &mes[1..]) {
-                                Ok(None) => {
+                            let player = &mut players[current_player];
+                            match add_to_table_sequence_remote(table, &mut player.hand, &mut cards_from_table,
+                                                               &mes[1..], &mut player.connection) {
+                                Ok(Ok((n, seq))) => {
+                                    turn = turn.meld();
+                                    save_turn(table, players, deck, &turn, &cards_from_table);
+                                    log.push(Event::AddedToSequence(player_names[current_player].clone(), n, seq));
+                                    let event_string = format!("  {}", log.last(1)[0]);
+                                    let has_played = turn.has_played(&players[current_player].hand);
+                                    show_new_situation(table, players, deck, current_player, &cards_from_table,
+                                                       has_played, &[event_string], previous_turn, log, seats)?;
 
-                                    // print the new situation for the current player
-                                    print_situation_remote(&table, &hands, deck, player_names, 
-                                                           current_player, current_player, 
-                                                           &mut streams[current_player], true, &cards_from_table,
-                                                           !hands[current_player].contains(&hand_start_round),
-                                                           cards_from_table.number_cards() > 0)?;
-
-                                    // print the new situation for the other players
-                                    for i in 0..n_players {
-                                        if i != current_player {
-                                            print_situation_remote(&table, &hands, deck, player_names, 
-                                                                   i, current_player, &mut streams[i],
-                                                                   false, &cards_from_table, false, false)?;
-                                            match &previous_messages[i] {
-                                                Some(s) => send_message_to_client(&mut streams[i], &s).unwrap(),
-                                                None => ()
-                                            };
-                                        }
-                                    }
-                                    
                                     // if the player has no more card, end the turn 
-                                    if hands[current_player].number_cards() == 0 {
+                                    if players[current_player].hand.number_cards() == 0 {
                                         break;
                                     }
                                 },
-                                Ok(Some(s)) => {
-                                    print_situation_remote(&table, &hands, deck, player_names, 
+                                Ok(Err(s)) => {
+                                    print_situation_remote(table, players, deck, 
                                                            current_player, current_player, 
-                                                           &mut streams[current_player], true, &cards_from_table,
-                                                           !hands[current_player].contains(&hand_start_round),
-                                                           cards_from_table.number_cards() > 0)?;
-                                    send_message_to_client(&mut streams[current_player], &s)?;
+                                                           true, &cards_from_table,
+                                                           turn.has_played(&players[current_player].hand),
+                                                           cards_from_table.number_cards() > 0, previous_turn, log)?;
+                                    send_private_message_to_client(&mut players[current_player], "", &s)?;
                                 },
-                                Err(_) => send_message_to_client(&mut streams[current_player], &"Communication error\n")?
+                                Err(_) => send_message_to_client(&mut players[current_player], &"Communication error\n")?
                             };
                         },
  
                         // value 'r': sort cards by rank
                         114 => {
-                            hands[current_player].sort_by_rank();
+                            players[current_player].hand.sort_by_rank();
                             cards_from_table.sort_by_rank();
-                            *sort_mode = 1;
-                            print_situation_remote(&table, &hands, deck, player_names, current_player,
-                                                   current_player, &mut streams[current_player],
-                                                   true, &cards_from_table,
-                                                   !hands[current_player].contains(&hand_start_round),
-                                                   cards_from_table.number_cards() > 0)?;
+                            players[current_player].sort_mode = 1;
+                            print_situation_remote(table, players, deck, current_player,
+                                                   current_player, true, &cards_from_table,
+                                                   turn.has_played(&players[current_player].hand),
+                                                   cards_from_table.number_cards() > 0, previous_turn, log)?;
                         },
                         
                         // value 's': sort cards by suit
                         115 => {
-                            hands[current_player].sort_by_suit();
+                            players[current_player].hand.sort_by_suit();
                             cards_from_table.sort_by_suit();
-                            *sort_mode = 2;
-                            print_situation_remote(&table, &hands, deck, player_names, current_player,
-                                                   current_player, &mut streams[current_player],
-                                                   true, &cards_from_table, 
-                                                   !hands[current_player].contains(&hand_start_round),
-                                                   cards_from_table.number_cards() > 0)?;
+                            players[current_player].sort_mode = 2;
+                            print_situation_remote(table, players, deck, current_player,
+                                                   current_player, true, &cards_from_table, 
+                                                   turn.has_played(&players[current_player].hand),
+                                                   cards_from_table.number_cards() > 0, previous_turn, log)?;
                         },
             
-                        // value 'g': give up on that round and take the penalty
+                        // value 'g': give up on that round, take the penalty, and end the turn
                         103 => {
-                            match cards_from_table.number_cards() {
-                                0 => (),
-                                _ => {
-                                    give_up(table, &mut hands[current_player], deck, &hand_start_round, 
-                                            &table_start_round, &mut cards_from_table);
-                                    print_situation_remote(&table, &hands, deck, player_names, current_player,
-                                                           current_player, &mut streams[current_player],
-                                                           true, &cards_from_table, false, false)?;
+                            turn = match turn {
+                                Ongoing::Meld(meld) => {
+                                    meld.give_up(table, &mut players[current_player].hand, deck, &mut cards_from_table);
+                                    log.push(Event::GaveUp(player_names[current_player].clone()));
+                                    break
+                                },
+                                draw => {
+                                    send_private_message_to_client(&mut players[current_player], "", 
+                                                                   "There is nothing to give up\n")?;
+                                    draw
                                 }
+                            };
+                        },
+
+                        // value 'l': change the layout of the hand
+                        108 => {
+                            players[current_player].layout = get_layout_from_client(&mut players[current_player])?;
+                            print_situation_remote(table, players, deck, current_player,
+                                                   current_player, true, &cards_from_table, 
+                                                   turn.has_played(&players[current_player].hand),
+                                                   cards_from_table.number_cards() > 0, previous_turn, log)?;
+                        },
+
+                        // values '<' and '>': turn the pages of the table or of the hand
+                        60 | 62 => {
+                            let command = sanitize_bytes(mes.to_vec())?;
+                            if let Some((view, forward)) = layout::parse_page_command(command.trim()) {
+                                let player = &mut players[current_player];
+                                let n_pages = match view {
+                                    layout::PagedView::Table =>
+                                        layout::render_table(table, &[], 0, &player.layout).1,
+                                    layout::PagedView::Hand =>
+                                        layout::render_hand_page(&player.hand, 1, &player.layout, 0).1
+                                };
+                                player.pages.turn(view, forward, n_pages);
+                            }
+                            print_situation_remote(table, players, deck, current_player,
+                                                   current_player, true, &cards_from_table, 
+                                                   turn.has_played(&players[current_player].hand),
+                                                   cards_from_table.number_cards() > 0, previous_turn, log)?;
+                        },
+
+                        // values 'k', 'b', 'u', 'n', and 'q': moderation commands
+                        107 | 98 | 117 | 110 | 113 => {
+                            let arg = sanitize_bytes(mes[1..].to_vec())?;
+                            if moderation_command(mes[0], arg.trim(), player_names, current_player, 
+                                                  players, seats, log)? {
+                                turn.restore(table, &mut players[current_player].hand);
+                                return Ok(None);
                             }
                         },
 
-                        _ => send_message_to_client(&mut streams[current_player], &"Invalid input; please try again.")?,
+                        // value '/': chat commands
+                        47 => {
+                            let command = sanitize_bytes(mes[1..].to_vec())?;
+                            chat_command(command.trim(), table, player_names, current_player, players)?;
+                        },
+
+                        _ => send_private_message_to_client(&mut players[current_player], "", 
+                                                            "Invalid input; please try again.")?,
                     }
                 }
             },
-            Err(_) => {
+            Err(e) => {
+                // the server is shutting down: put the turn back as it was when it started
+                if shutdown::requested() {
+                    turn.restore(table, &mut players[current_player].hand);
+                    return Ok(None);
+                }
+
+                // the first time the player runs out of time, wait a little more for the message
+                // they were asked for
+                let grace = server_config.turn_timeout().map(|t| t / TURN_GRACE_DIVISOR);
+                if let Some(grace) = grace.filter(|_| e.timed_out() && !grace_given) {
+                    grace_given = true;
+                    asked = true;
+                    players[current_player].turn_deadline = Some(Instant::now() + grace);
+                    logging::info(&format!("Player {} is out of time; waiting {} s more", current_player + 1,
+                                           grace.as_secs()));
+                    let message = format!("{} is out of time; waiting a little more for them...\n",
+                                          &player_names[current_player]);
+                    for i in 0..players.len() {
+                        if i != current_player && players[i].connected
+                            && send_message_to_client(&mut players[i], &message).is_err() {
+                            lose_waiting_player(players, seats, log, i);
+                        }
+                    }
+                    continue;
+                }
+
+                // the connection is closed even if the player was only idle, as the client
+                // would be out of sync when they come back
+                players[current_player].close();
                 send_message_all_players(
-                    streams,
+                    players,
                     &format!("{} seems to have disconnected... Waiting for them to reconnect.\n", 
                              &player_names[current_player])
                 );
-                println!("Lost connection with player {}", current_player + 1);
-                wait_for_reconnection(&mut streams[current_player], &player_names[current_player], port)?;
-                println!("Player {} is back", current_player + 1);
-                print_situation_remote(&table, &hands, deck, player_names, current_player,
-                                       current_player, &mut streams[current_player],
-                                       true, &cards_from_table, 
-                                       !hands[current_player].contains(&hand_start_round),
-                                       cards_from_table.number_cards() > 0)?;
+                logging::info(&format!("Lost connection with player {}", current_player + 1));
+                let token = players[current_player].token.clone();
+                if !wait_for_reconnection(&mut players[current_player].connection, &player_names[current_player], 
+                                          &token, listener, server_config.reconnection_timeout())? {
+                    
+                    // let a bot play from the start of the turn
+                    turn.restore(table, &mut players[current_player].hand);
+                    if shutdown::requested() {
+                        return Ok(None);
+                    }
+                    seats.replace_by_bot(players, current_player);
+                    log.push(Event::BotTookSeat(player_names[current_player].clone()));
+                    logging::info(&format!("A bot takes the place of player {}", current_player + 1));
+                    send_message_all_players(
+                        players,
+                        &format!("A bot plays for {} until they reconnect.\n", 
+                                 &player_names[current_player])
+                    );
+                    return Ok(None);
+                }
+                logging::info(&format!("Player {} is back", current_player + 1));
+                shutdown::SIGNAL.watch(players);
+                players[current_player].turn_deadline = server_config.turn_timeout().map(|t| Instant::now() + t);
+                players[current_player].set_timeout(server_config.turn_timeout())?;
+                print_situation_remote(table, players, deck, current_player,
+                                       current_player, true, &cards_from_table, 
+                                       turn.has_played(&players[current_player].hand),
+                                       cards_from_table.number_cards() > 0, previous_turn, log)?;
                 send_message_all_players(
-                    streams,
+                    players,
                     &format!("{} is back!\n", 
                              &player_names[current_player])
                 );
             }
         };
     }
+    ledger.assert_holds(table, players, deck, &cards_from_table, "the end of the turn");
     Ok(None)
 }
 
-fn play_sequence_remote(hand: &mut Sequence, cards_from_table: &mut Sequence,
-                        table: &mut Table, mes: &[u8]) 
-    -> Result<Option<String>, StreamError>
+/// instructions for the chat commands
+const CHAT_INSTRUCTIONS: &str = "/whisper name message: Send a private message to a player\n\
+                                 /find 7♦: Find a card (or a rank or a suit) in your hand and on the table\n";
+
+/// show the situation after something was played: the whole screen for the current player, and
+/// for the others the new situation, the events in `flashes`, and the last message they got
+///
+/// A waiting player whose connection fails is replaced by a bot, so that the turn goes on.
+fn show_new_situation<C: Connection>(table: &Table, players: &mut [Player<C>], deck: &Deck, current_player: usize,
+                                     cards_from_table: &Sequence, has_played: bool, flashes: &[String],
+                                     previous_turn: &PreviousTurn, log: &mut EventLog, seats: &mut Seats)
+    -> Result<(), StreamError>
 {
-    // copy the initial hand and cards from tables
-    let hand_copy = hand.clone();
-    let cards_from_table_copy = cards_from_table.clone();
+    print_situation_remote(table, players, deck, current_player, current_player, true, cards_from_table,
+                           has_played, cards_from_table.number_cards() > 0, previous_turn, log)?;
+    for i in 0..players.len() {
+        if i != current_player && players[i].connected
+            && show_new_situation_to_waiting_player(table, players, deck, i, current_player, cards_from_table,
+                                                    flashes, previous_turn, log).is_err() {
+            lose_waiting_player(players, seats, log, i);
+        }
+    }
+    Ok(())
+}
 
-    // combine the hand and cards from the table
-    let mut full_hand = hand.clone();
-    let buffer = cards_from_table.clone();
-    full_hand.merge(buffer.reverse());
-  
-    let mut seq = Sequence::new();
-    
-    let s = String::from_utf8(mes.to_vec())?;
-    
-    let mut seq_i_hand = Vec::<usize>::new();
-    let mut seq_i_cft = Vec::<usize>::new();
-    let n_hand = hand.number_cards();
-    for item in s.trim().split(' ') {
-        match item.parse::<usize>() {
-            Ok(n) => {
-                if n <= n_hand {
-                    let mut n_i = 0;
-                    for &i in &seq_i_hand {
-                        if i < n {
-                            n_i += 1;
-                        }
-                    }
-                    let card = match hand.take_card(n-n_i) {
-                        Some(c) => c,
-                        None => continue
-                    };
-                    seq.add_card(card);
-                    seq_i_hand.push(n);
-                } else {
-                    let m = n - n_hand;
-                    let mut n_i = 0;
-                    for &i in &seq_i_cft {
-                        if i < m {
-                            n_i += 1;
-                        }
-                    }
-                    let card = match cards_from_table.take_card(m-n_i) {
-                        Some(c) => c,
-                        None => continue
+/// show a waiting player the situation after something was played, the events in `flashes`, and
+/// the last message they got
+fn show_new_situation_to_waiting_player<C: Connection>(table: &Table, players: &mut [Player<C>], deck: &Deck,
+                                                       player: usize, current_player: usize,
+                                                       cards_from_table: &Sequence, flashes: &[String],
+                                                       previous_turn: &PreviousTurn, log: &EventLog)
+    -> Result<(), StreamError>
+{
+    print_situation_remote(table, players, deck, player, current_player, false, cards_from_table, false, false,
+                           previous_turn, log)?;
+    for flash in flashes {
+        send_flash_to_client(&mut players[player], flash)?;
+    }
+    if let Some(s) = players[player].last_message.clone() {
+        send_message_to_client(&mut players[player], &s)?;
+    }
+    Ok(())
+}
+
+/// let a bot play for a waiting player whose connection failed, so that the turn goes on
+///
+/// This is used for the messages telling the waiting players about the turn, which they do not
+/// answer; a failure shows that the connection is lost.
+///
+/// The player can reconnect to take their seat back, as after any lost connection.
+fn lose_waiting_player<C: Connection>(players: &mut [Player<C>], seats: &mut Seats, log: &mut EventLog,
+                                      player: usize) {
+    let name = players[player].name().to_string();
+    logging::info(&format!("Lost connection with player {}; a bot takes their place", player + 1));
+    seats.replace_by_bot(players, player);
+    log.push(Event::BotTookSeat(name.clone()));
+    send_message_all_players(players, &format!("A bot plays for {} until they reconnect.\n", &name));
+}
+
+/// whether a message of the player whose turn it is shows them working on their cards, rather than
+/// chatting, moderating the game, or playing or ending their turn at once
+fn is_arranging(mes: &[u8]) -> bool {
+    !commands::is_list(&String::from_utf8_lossy(mes))
+        && !matches!(mes.first(), None | Some(b'e') | Some(b'g') | Some(b'/') | Some(b'k') | Some(b'b') | Some(b'u')
+                                  | Some(b'n') | Some(b'q'))
+}
+
+/// apply a chat command from the current player (without the leading '/')
+fn chat_command<C: Connection>(command: &str, table: &Table, player_names: &[String], current_player: usize,
+                               players: &mut [Player<C>]) -> Result<(), StreamError>
+{
+    let (name, arg) = command.split_once(' ').unwrap_or((command, ""));
+    if name == "find" || name == "f" {
+        let answer = search::answer(arg.trim(), &players[current_player].hand, table);
+        return send_private_message_to_client(&mut players[current_player], "", &answer);
+    }
+    if name != "whisper" && name != "w" {
+        return send_private_message_to_client(&mut players[current_player], "", 
+                                              &format!("Unknown command ‘/{}’!\n", name));
+    }
+    let (target, message) = match parse_whisper(arg, player_names) {
+        Some((target, _)) if target == current_player => {
+            return send_private_message_to_client(&mut players[current_player], "", 
+                                                  "You can't whisper to yourself!\n");
+        },
+        Some(whisper) => whisper,
+        None => {
+            return send_private_message_to_client(&mut players[current_player], "", 
+                                                  "Usage: /whisper name message\n");
+        }
+    };
+
+    // bots do not read their messages, and the player may have left
+    let sent = players[target].connected 
+        && send_private_message_to_client(&mut players[target], &colours::paint(&player_names[current_player]), message)
+            .is_ok();
+    let reply = if sent {
+        format!("To {}: {}\n", colours::paint(&player_names[target]), message)
+    } else {
+        format!("{} is not connected and could not get your message.\n", player_names[target])
+    };
+    send_private_message_to_client(&mut players[current_player], "", &reply)
+}
+
+/// split the argument of a whisper into the recipient and the message
+///
+/// As names may contain spaces, the longest name followed by a non-empty message is used.
+fn parse_whisper<'a>(arg: &'a str, player_names: &[String]) -> Option<(usize, &'a str)> {
+    player_names.iter().enumerate()
+        .filter_map(|(i, name)| {
+            let message = arg.strip_prefix(name.as_str())?.strip_prefix(' ')?.trim();
+            if message.is_empty() { None } else { Some((i, name.len(), message)) }
+        })
+        .max_by_key(|&(_, len, _)| len)
+        .map(|(i, _, message)| (i, message))
+}
+
+/// instructions for the moderation commands
+fn moderation_instructions(is_host: bool) -> String {
+    if is_host {
+        format!("{}\n{}\n{}\n{}\n{}\n",
+            "k name: Kick a player out",
+            "b name: Let a bot play for a player until they reconnect",
+            "u 12: Rewind the game to the start of a turn of this round",
+            "n: Restart the round",
+            "q: End the game")
+    } else {
+        "k name: Vote to kick a player out\n".to_string()
+    }
+}
+
+/// apply a moderation command from the current player
+///
+/// Return `true` if the turn should end.
+fn moderation_command<C: Connection>(command: u8, arg: &str, player_names: &[String], current_player: usize,
+                      players: &mut [Player<C>], seats: &mut Seats, log: &mut EventLog)
+    -> Result<bool, StreamError>
+{
+    let is_host = seats.host == current_player;
+    let name = &player_names[current_player];
+
+    // commands other than voting are reserved to the host
+    if command != 107 && !is_host {
+        send_private_message_to_client(&mut players[current_player], "", "Only the host can do that!\n")?;
+        return Ok(false);
+    }
+
+    match command {
+
+        // restart the round or end the game
+        110 | 113 => {
+            let (action, description) = if command == 110 {
+                (HostAction::RestartRound, "restart the round")
+            } else {
+                (HostAction::EndGame, "end the game")
+            };
+            if ask_confirmation(&mut players[current_player], &format!("Really {}?", description))? {
+                send_message_all_players(players, &format!("{} (host) decided to {}.\n", name, description));
+                seats.host_action = Some(action);
+                return Ok(true);
+            }
+        },
+
+        // rewind the game to the last checkpoint at or before a turn
+        117 => {
+            let point = arg.parse::<u32>().ok()
+                .and_then(|turn| seats.rewind_points.iter().rev().find(|&&n| n < turn).copied());
+            let point = match point {
+                Some(point) => point,
+                None => {
+                    let message = if seats.rewind_points.is_empty() {
+                        "There is no checkpoint of this round to rewind to.\n".to_string()
+                    } else {
+                        format!("Usage: u turn (checkpoints at the start of turns {})\n",
+                                seats.rewind_points.iter().map(|n| (n + 1).to_string())
+                                    .collect::<Vec<String>>().join(", "))
                     };
-                    seq.add_card(card);
-                    seq_i_cft.push(m);
+                    send_private_message_to_client(&mut players[current_player], "", &message)?;
+                    return Ok(false);
                 }
-            },
-            Err(_) => ()
+            };
+            let description = format!("rewind the game to the start of turn {}", point + 1);
+            if ask_confirmation(&mut players[current_player], &format!("Really {}?", description))? {
+                send_message_all_players(players, &format!("{} (host) decided to {}.\n", name, description));
+                seats.host_action = Some(HostAction::Rewind(point));
+                return Ok(true);
+            }
+        },
+
+        // kick a player out or let a bot play for them
+        _ => {
+            let target = match player_names.iter().position(|n| n == arg) {
+                Some(i) if i != current_player => i,
+                Some(_) => {
+                    send_private_message_to_client(&mut players[current_player], "", "You can't do that to yourself!\n")?;
+                    return Ok(false);
+                },
+                None => {
+                    send_private_message_to_client(&mut players[current_player], "",
+                                                   &format!("There is no player called ‘{}’!\n", arg))?;
+                    return Ok(false);
+                }
+            };
+            if players[target].kicked {
+                send_private_message_to_client(&mut players[current_player], "",
+                                               &format!("{} has already been kicked out!\n", arg))?;
+                return Ok(false);
+            }
+
+            if command == 107 && !is_host {
+                let (n_votes, n_needed) = seats.vote_kick(players, current_player, target);
+                send_message_all_players(players, &format!("{} votes to kick {} out ({}/{}).\n", 
+                                                           name, arg, n_votes, n_needed));
+                if n_votes < n_needed {
+                    return Ok(false);
+                }
+            } else {
+                let description = if command == 107 { "kick out" } else { "let a bot play for" };
+                if !ask_confirmation(&mut players[current_player], &format!("Really {} {}?", description, arg))? {
+                    return Ok(false);
+                }
+            }
+
+            // tell the player, then close the connection
+            let message = if command == 107 {
+                "You have been kicked out of the game.\n"
+            } else {
+                "The host let a bot take your seat; reconnect to take it back.\n"
+            };
+            if players[target].connected {
+                send_private_message_to_client(&mut players[target], "", message).unwrap_or(());
+                players[target].write_all(&[5]).unwrap_or(());
+                players[target].close();
+            }
+            if command == 107 {
+                seats.kick(players, target);
+                log.push(Event::Kicked(arg.to_string()));
+                send_message_all_players(players, &format!("{} has been kicked out; a bot takes their seat.\n", arg));
+            } else {
+                seats.replace_by_bot(players, target);
+                log.push(Event::BotTookSeat(arg.to_string()));
+                send_message_all_players(players, &format!("A bot plays for {} until they reconnect.\n", arg));
+            }
         }
     }
+    Ok(false)
+}
 
-    if seq.is_valid() {
-        table.add(seq);
-        return Ok(None);
-    } else {
-        *hand = hand_copy;
-        *cards_from_table = cards_from_table_copy;
-        let message = format!("{}{} is not a valid sequence!\n", 
-                              &seq, &reset_style_string());
-        return Ok(Some(message));
+fn play_sequence_remote<C: Connection>(hand: &mut Sequence, cards_from_table: &mut Sequence,
+                                       table: &mut Table, mes: &[u8], stream: &mut C)
+    -> Result<Result<Sequence, String>, StreamError>
+{
+    // copy the initial hand and cards from tables
+    let hand_copy = hand.clone();
+    let cards_from_table_copy = cards_from_table.clone();
+
+    // the cards from the table are numbered after the hand; the whole input is rejected if a card
+    // can not be found
+    let s = sanitize_bytes(mes.to_vec())?;
+    let indices = match selection::parse_indices(&s, hand.number_cards() + cards_from_table.number_cards()) {
+        Ok(indices) => indices,
+        Err(e) => return Ok(Err(format!("Nothing was played: {}\n", e)))
+    };
+    let preview = selection::PlayPreview::new(selection::take_cards(&mut [hand, cards_from_table], &indices), None);
+    let confirmed = preview.is_valid() && ask_confirmation(stream, &format!("{}\nPlay it?", preview.render()))?;
+    match preview.validity {
+        Ok(()) if confirmed => {
+            table.add(preview.sequence.clone());
+            Ok(Ok(preview.sequence))
+        },
+        validity => {
+            *hand = hand_copy;
+            *cards_from_table = cards_from_table_copy;
+            Ok(Err(validity.err().map(|m| format!("{}\n", m)).unwrap_or_else(|| "Nothing was played\n".to_string())))
+        }
     }
 }
 
-fn take_sequence_remote(table: &mut Table, hand: &mut Sequence, mes: &[u8], stream: &mut TcpStream) 
-    -> Result<(), StreamError> 
+fn take_sequence_remote<C: Connection>(table: &mut Table, hand: &mut Sequence, mes: &[u8], stream: &mut C) 
+    -> Result<Vec<(usize, Sequence)>, StreamError> 
 {
-    let content = String::from_utf8(mes.to_vec())?;
+    let content = sanitize_bytes(mes.to_vec())?;
     let content = content.trim().split(" ");
     let mut seq_i = Vec::<usize>::new();
+    let mut taken = Vec::<(usize, Sequence)>::new();
     for s in content {
         match s.parse::<usize>() {
             Ok(n) => {
@@ -469,219 +1279,163 @@ fn take_sequence_remote(table: &mut Table, hand: &mut Sequence, mes: &[u8], stre
                         n_i += 1;
                     }
                 }
+                let n_cards = table.get(n - n_i).map_or(0, |seq| seq.number_cards());
+                if n_cards >= N_CARDS_CONFIRM_TAKE 
+                    && !ask_confirmation(stream, &format!("Take sequence {} ({} cards) into your hand?", n, n_cards))?
+                {
+                    continue;
+                }
                 seq_i.push(n);
                 match table.take(n-n_i) {
                     Some(seq) => {
+                        taken.push((n, seq.clone()));
                         hand.merge(seq.reverse());
                     },
-                    None => send_message_to_client(stream, &"This sequence is not on the table\n")?
+                    None => send_private_message_to_client(stream, "", "This sequence is not on the table\n")?
                 }
             },
-            Err(_) => send_message_to_client(stream, &"Error parsing the input!\n")?
+            Err(_) => send_private_message_to_client(stream, "", "Error parsing the input!\n")?
         };
     }
-    Ok(())
+    Ok(taken)
 }
 
-fn add_to_table_sequence_remote(table: &mut Table, hand: &mut Sequence, 
-                                cards_from_table: &mut Sequence, mes: &[u8]) 
-    -> Result<Option<String>, StreamError> 
+fn add_to_table_sequence_remote<C: Connection>(table: &mut Table, hand: &mut Sequence,
+                                                cards_from_table: &mut Sequence, mes: &[u8], stream: &mut C)
+    -> Result<Result<(usize, Sequence), String>, StreamError> 
 {
     
     // copy the initial hand and cards from tables
     let hand_copy = hand.clone();
     let cards_from_table_copy = cards_from_table.clone();
 
-    let mut seq_from_table: Sequence;
-    let mut seq_from_hand = Sequence::new();
-    let mut seq_from_hand_from_table = Sequence::new();
-
-    // parse the request
-    let content = String::from_utf8(mes.to_vec())?;
-    let mut content = content.trim().split(" ");
-
-    // parse the index of the sequence to which to add cards
-    match content.next() {
-        Some(x) => match x.parse::<usize>() {
-            Ok(n) => match table.take(n) {
-                Some(seq) => {
-                    seq_from_table = seq;
-                },
-                None => {
-                    let message = format!("Sequence {} is not on the table\n", n);
-                    return Ok(Some(message))
-                }
-            },
-            Err(_) => {
-                let message = "Error parsing the input!\n".to_string();
-                return Ok(Some(message))
-            }
-        },
-        None => return Ok(None)
+    // parse the sequence to which to add cards, where they go, and the cards, numbered as to play
+    // a sequence
+    let content = sanitize_bytes(mes.to_vec())?;
+    if content.trim().is_empty() {
+        return Ok(Err(String::new()));
     }
-
-    // parse the sequence to play
-    let mut seq_i_hand = Vec::<usize>::new();
-    let mut seq_i_cft = Vec::<usize>::new();
-    let n_hand = hand.number_cards();
-    while let Some(s) = content.next() {
-        match s.parse::<usize>() {
-            Ok(n) => {
-                if n <= n_hand {
-                    let mut n_i = 0;
-                    for &i in &seq_i_hand {
-                        if i < n {
-                            n_i += 1;
-                        }
-                    }
-                    let card = match hand.take_card(n-n_i) {
-                        Some(c) => c,
-                        None => continue
-                    };
-                    seq_from_hand.add_card(card);
-                    seq_i_hand.push(n);
-                } else {
-                    let m = n - n_hand;
-                    let mut n_i = 0;
-                    for &i in &seq_i_cft {
-                        if i < m {
-                            n_i += 1;
-                        }
-                    }
-                    let card = match cards_from_table.take_card(m-n_i) {
-                        Some(c) => c,
-                        None => continue
-                    };
-                    seq_from_hand_from_table.add_card(card);
-                    seq_i_cft.push(m);
-                }
-            },
-            Err(_) => ()
-        }
-    }
-
-    // clone the sequence from the table 
-    let seq_from_table_org = seq_from_table.clone();
-
-    // merge the sequences
-    seq_from_hand.merge(seq_from_hand_from_table);
-    seq_from_table.merge(seq_from_hand);
-
-    // if it is valid, add it to the table; if not, restore the original situation
-    if seq_from_table.is_valid() {
-            table.add(seq_from_table);
-            return Ok(None);
-    } else {
+    let n_cards = hand.number_cards() + cards_from_table.number_cards();
+    let (i_seq_from_table, position, indices) =
+        match selection::parse_extension(&content, table.number_sequences(), n_cards) {
+            Ok(extension) => extension,
+            Err(e) => return Ok(Err(format!("Nothing was added: {}\n", e)))
+        };
+    let seq_added = selection::take_cards(&mut [hand, cards_from_table], &indices);
+
+    // show the sequence with the cards added, and lay it down if it is valid and confirmed; if
+    // not, restore the original situation
+    let target = table.get(i_seq_from_table).cloned().unwrap_or_else(Sequence::new);
+    let preview = selection::PlayPreview::extension(&target, i_seq_from_table, &seq_added, position);
+    let confirmed = preview.is_valid() && ask_confirmation(stream, &format!("{}\nAdd the cards?", preview.render()))?;
+    match preview.validity {
+        Ok(()) if confirmed => {
+            table.take(i_seq_from_table);
+            table.add(preview.sequence);
+            Ok(Ok((i_seq_from_table, seq_added)))
+        },
+        validity => {
             *hand = hand_copy;
             *cards_from_table = cards_from_table_copy;
-            table.add(seq_from_table_org);
-            let message = format!("{}{} is not a valid sequence!\n", 
-                                  &seq_from_table, &reset_style_string());
-            return Ok(Some(message));
+            Ok(Err(validity.err().map(|m| format!("{}\n", m)).unwrap_or_else(|| "Nothing was added\n".to_string())))
+        }
     }
 }
 
-fn print_situation_remote(table: &Table, hands: &Vec<Sequence>, deck: &Sequence, 
-                          player_names: &Vec<String>, player: usize, current_player: usize, 
-                          stream: &mut TcpStream, print_instructions: bool, cards_from_table: &Sequence, 
-                          has_played_something: bool, print_reset_option: bool) 
+fn print_situation_remote<C: Connection>(table: &Table, players: &mut [Player<C>], deck: &Deck, 
+                          player: usize, current_player: usize, print_instructions: bool,
+                          cards_from_table: &Sequence, has_played_something: bool, print_reset_option: bool,
+                          previous_turn: &PreviousTurn, log: &EventLog) 
     -> Result<(), StreamError>
 {
-    // string with the number of cards each player has
-    let mut string_n_cards = format!("\nNumber of cards ({} remaining in the deck):", deck.number_cards());
-    for i in 0..(hands.len()) {
-        string_n_cards += &format!("\n  {}: {}", &player_names[i], &hands[i].number_cards());
-    }
-    string_n_cards += "\n";
+    let current_name = players[current_player].name().to_string();
 
+    // the terminal of the active player may have been resized since the situation was last shown;
+    // the others keep the last layout they sent, so that a slow client does not hold up the turn
+    if player == current_player {
+        players[player].layout = get_layout_from_client(&mut players[player])?;
+    }
+    let view = PlayerView::new(players, &TurnOrder::new(players.len(), current_player), player)
+        .with_previous_turn(previous_turn.clone());
+    let situation = situation_to_string(table, &players[player].hand, cards_from_table, &players[player].layout,
+                                        &players[player].pages, deck.remaining(), &view);
+    let stream = &mut players[player];
     clear_and_send_message_to_client(stream, 
-        &format!("\x1b[1m{}'s turn:{}", player_names[current_player], &reset_style_string()))?;
-    send_message_to_client(stream, &string_n_cards)?;
-    send_message_to_client(stream, &situation_to_string(table, &hands[player], cards_from_table))?;
+        &format!("\x1b[1m{}'s turn:{}", colours::paint(&current_name), &reset_style_string()))?;
+    send_message_to_client(stream, &situation)?;
+    if !log.is_empty() {
+        send_message_to_client(stream, &format!("\n{}", log.render(N_EVENTS_SHOWN)))?;
+    }
     if print_instructions {
         send_message_to_client(stream, &"\n")?;
         send_message_to_client(stream, &instructions_no_save(!has_played_something, print_reset_option))?;
     }
+    if player != current_player {
+        let elapsed = players[current_player].turn_started.map(|t| t.elapsed()).unwrap_or_default();
+        send_waiting_to_client(&mut players[player], &current_name, elapsed)?;
+    }
     Ok(())
 }
 
 /// send a message as a string to a client
-pub fn send_str_to_client(stream: &mut TcpStream, s: &str) -> Result<(), StreamError> {
+pub fn send_str_to_client<C: Connection>(stream: &mut C, s: &str) -> Result<(), StreamError> {
     send_bytes_to_client(stream, &s.as_bytes())?;
     Ok(())
 }
 
-fn send_bytes_to_client_no_wait(stream: &mut TcpStream, bytes: &[u8]) -> Result<(), StreamError> {
-    
-    // ensure that the number of bytes is small enough
-    if bytes.len() > MAX_N_BUFFERS * BUFFER_SIZE {
-        return Err(StreamError { message: format!(
-                    "Stream too long: size: {}, maximum size: {}",
-                    bytes.len(), MAX_N_BUFFERS*BUFFER_SIZE
-                   ) })
-    }
-
-    // the first bytes will determine the number of times the buffer should be read
-    let mut n_buffers: u8 = (bytes.len() / BUFFER_SIZE) as u8;
-    if bytes.len() % BUFFER_SIZE != 0 {
-        n_buffers += 1;
-    }
-    stream.write(&[n_buffers])?;
+fn send_bytes_to_client_no_wait<C: Connection>(stream: &mut C, bytes: &[u8]) -> Result<(), StreamError> {
+    debug_bundle::record(debug_bundle::Direction::Sent, bytes);
+    stream.write_frames(bytes)?;
+    Ok(())
+}
 
-    // write the data stream
-    for i in 0..((n_buffers-1) as usize) {
-        stream.write(&bytes[i*BUFFER_SIZE..(i+1)*BUFFER_SIZE])?;
-    }
-    stream.write(&bytes[((n_buffers-1) as usize)*BUFFER_SIZE..])?;
-    
+/// send a message written by `write` to a client, without building it apart from its frame
+fn send_written_to_client<C, F>(stream: &mut C, write: F) -> Result<(), StreamError>
+    where C: Connection, F: FnOnce(&mut Vec<u8>) -> std::io::Result<()>
+{
+    stream.write_frame_with(|frame| {
+        let start = frame.len();
+        write(frame)?;
+        debug_bundle::record(debug_bundle::Direction::Sent, &frame[start..]);
+        Ok(())
+    })?;
+    stream.wait_for_ack()?;
     Ok(())
 }
 
 /// send a message as bytes to a client
-pub fn send_bytes_to_client(stream: &mut TcpStream, bytes: &[u8]) -> Result<(), StreamError> {
+pub fn send_bytes_to_client<C: Connection>(stream: &mut C, bytes: &[u8]) -> Result<(), StreamError> {
     
     send_bytes_to_client_no_wait(stream, bytes)?;
     
     // wait for a reply to be sent from the receiver
-    stream.read(&mut [0])?;
+    stream.wait_for_ack()?;
     
     Ok(())
 }
 
-/// get a message (string) from a client
-pub fn get_str_from_client(stream: &mut TcpStream) -> Result<String, StreamError> {
+/// get a message (string) from a client, without escape sequences or non-printable characters
+pub fn get_str_from_client<C: Connection>(stream: &mut C) -> Result<String, StreamError> {
     let bytes = get_bytes_from_client(stream)?;
-    match String::from_utf8(bytes) {
+    match sanitize_bytes(bytes) {
         Ok(s) => Ok(s),
         Err(_) => Err(StreamError::from(BytesToStringError {}))
     }
 }
 
-/// get a message (bytes) from a client
-pub fn get_bytes_from_client(stream: &mut TcpStream) -> Result<Vec<u8>, StreamError> {
-    
-    // buffer
-    let mut buffer: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
-
-    // the first bytes will determine the number of times the buffer should be read
-    let mut n_buffers: [u8; 1] = [0];
-    stream.read(&mut n_buffers)?;
-
-    // vector containing the result
-    let mut res = Vec::<u8>::new();
+/// get the name and the reconnection token (empty for a new player) sent by a client when it
+/// connects
+pub fn get_hello_from_client<C: Connection>(stream: &mut C) -> Result<(String, String), StreamError> {
+    let name = get_str_from_client(stream)?;
+    let token = get_str_from_client(stream)?;
+    Ok((name, token))
+}
 
-    // read the data stream
-    let mut size;
-    for _i in 0..n_buffers[0] {
-        size = stream.read(&mut buffer)?;
-        res.extend_from_slice(&buffer[..size]);
-    }
-    
-    // send something to confirm I have received the data
-    stream.write(&[0])?;
-    
-    // return the result
-    Ok(res)
+/// get a message (bytes) from a client
+pub fn get_bytes_from_client<C: Connection>(stream: &mut C) -> Result<Vec<u8>, StreamError> {
+    let bytes = stream.read_frames()?;
+    debug_bundle::record(debug_bundle::Direction::Received, &bytes);
+    Ok(bytes)
 }
 
 /// wait a moment
@@ -694,98 +1448,190 @@ pub fn long_wait() {
     std::thread::sleep(std::time::Duration::from_millis(N_MILLISECONDS_LONG_WAIT));
 }
 
-/// check that no players have the same name; if yes, rename players
-pub fn ensure_names_are_different(player_names: &mut Vec<String>, client_streams: &mut Vec<TcpStream>) 
-    -> Result<(), StreamError>
-{
-    let mut cont = true;
-    while cont {
-        cont = false;
-        for i in 0..player_names.len() {
-            for j in (i+1)..player_names.len() {
-                if player_names[j] == player_names[i] {
-                    cont = true;
-                    match String::from_utf8(send_message_get_reply(&mut client_streams[j], 
-                                       &format!("The name {} is already taken! Please choose a different one.\n",
-                                                &player_names[j]))?) {
-                        Ok(n) => player_names[j] = n,
-                        Err(_) => send_message_to_client(&mut client_streams[j], &"Could not read the input!")?
-                    }
-                }
-            }
-        }
-    }
-    Ok(())
-}
-
-/// send the instruction to send a message to the client, and read the response as a string
-pub fn get_string_from_client(stream: &mut TcpStream) -> Result<String, StreamError> {
+/// send the instruction to send a message to the client, and read the response as a cleaned string
+pub fn get_string_from_client<C: Connection>(stream: &mut C) -> Result<String, StreamError> {
     let msg = get_message_from_client(stream)?;
-    match String::from_utf8(msg) {
+    match sanitize_bytes(msg) {
         Ok(s) => Ok(s),
-        Err(_) => Err(StreamError { message: "Could not convert the input to a string".to_string() })
+        Err(_) => Err(StreamError { message: "Could not convert the input to a string".to_string(), timed_out: false })
     }
 }
 
-fn get_message_from_client(stream: &mut TcpStream) -> Result<Vec<u8>, StreamError>{
-    stream.write(&mut [4])?;
+fn get_message_from_client<C: Connection>(stream: &mut C) -> Result<Vec<u8>, StreamError>{
+    stream.write_all(&[4])?;
     get_bytes_from_client(stream)
 }
 
 /// send the instruction to clear the screen and send back a message to the client, and read the 
 /// response as a string
-pub fn clear_and_send_message_to_client(stream: &mut TcpStream, msg: &str) -> Result<(), StreamError>{
-    stream.write(&mut [2])?;
+pub fn clear_and_send_message_to_client<C: Connection>(stream: &mut C, msg: &str) -> Result<(), StreamError>{
+    stream.write_all(&[2])?;
     send_str_to_client(stream, msg)
 }
 
 /// send the instruction to print a message to the client, then send a message to the same client
-pub fn send_message_to_client(stream: &mut TcpStream, msg: &str) -> Result<(), StreamError>{
-    stream.write(&mut [1])?;
+pub fn send_message_to_client<C: Connection>(stream: &mut C, msg: &str) -> Result<(), StreamError>{
+    stream.write_all(&[1])?;
     send_str_to_client(stream, msg)
 }
 
+/// send the instruction to print a last message and quit
+pub fn send_exit_message_to_client<C: Connection>(stream: &mut C, msg: &str) -> Result<(), StreamError>{
+    stream.write_all(&[9])?;
+    send_str_to_client(stream, msg)
+}
+
+/// send the instruction to print a line which the client may animate
+pub fn send_flash_to_client<C: Connection>(stream: &mut C, msg: &str) -> Result<(), StreamError>{
+    stream.write_all(&[7])?;
+    send_str_to_client(stream, msg)
+}
+
+/// send the instruction to print a message meant for this player only
+///
+/// `sender` is the name of the player who wrote the message, or is empty if it comes from the
+/// server.
+pub fn send_private_message_to_client<C: Connection>(stream: &mut C, sender: &str, msg: &str) 
+    -> Result<(), StreamError>
+{
+    stream.write_all(&[10])?;
+    send_str_to_client(stream, sender)?;
+    send_str_to_client(stream, msg)
+}
+
+/// send the token the client must send back to reconnect
+pub fn send_token_to_client<C: Connection>(stream: &mut C, token: &str) -> Result<(), StreamError> {
+    stream.write_all(&[12])?;
+    send_str_to_client(stream, token)
+}
+
+/// tell a player waiting for their turn whose turn it is, and how long it has lasted, so that
+/// their client shows the time going by until the next message
+pub fn send_waiting_to_client<C: Connection>(stream: &mut C, name: &str, elapsed: Duration) 
+    -> Result<(), StreamError>
+{
+    stream.write_all(&[14])?;
+    send_str_to_client(stream, name)?;
+    send_str_to_client(stream, &elapsed.as_secs().to_string())
+}
+
+/// tell a player waiting for their turn what the active player is doing, when it does not show on
+/// the table
+pub fn send_activity_to_client<C: Connection>(stream: &mut C, activity: &str) -> Result<(), StreamError> {
+    stream.write_all(&[15])?;
+    send_str_to_client(stream, activity)
+}
+
+/// send an update of the public state with the checksum of the new state, and return whether
+/// the client found the same checksum
+pub fn send_state_to_client<C: Connection>(stream: &mut C, update: &StateUpdate, checksum: &str) 
+    -> Result<bool, StreamError>
+{
+    stream.write_all(&[13])?;
+    send_written_to_client(stream, |frame| update.write_to(frame))?;
+    send_str_to_client(stream, checksum)?;
+    Ok(get_bytes_from_client(stream)? == [0])
+}
+
+/// send an update of the public state to a player, and the whole state if their client is out
+/// of sync, and return whether the client ends up in sync
+///
+/// `tracker` holds the state after the update. Desyncs are logged; the whole state is sent
+/// once, and a client still out of sync gets it again at the next update.
+pub fn sync_state_with_client<C: Connection>(player: &mut Player<C>, tracker: &StateTracker, update: &StateUpdate) 
+    -> Result<bool, StreamError>
+{
+    let checksum = tracker.public_state().checksum();
+    if !send_state_to_client(player, update, &checksum)? {
+        logging::info(&format!("Desync with player {}; sending the whole state again", player.id + 1));
+        if !send_state_to_client(player, &tracker.snapshot(), &checksum)? {
+            logging::error(&format!("Player {} is still out of sync", player.id + 1));
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// show the situation at the start of a turn to a client in sync with the public state
+///
+/// Only the header, what the player sees besides the public state, and the text after the
+/// situation are sent; the client draws the table from its copy of the public state.
+pub fn send_situation_to_client<C: Connection>(stream: &mut C, header: &str, view: &TurnView, footer: &str)
+    -> Result<(), StreamError>
+{
+    stream.write_all(&[16])?;
+    send_str_to_client(stream, header)?;
+    send_written_to_client(stream, |frame| view.write_to(frame))?;
+    send_str_to_client(stream, footer)
+}
+
+/// send the instruction to notify the player that their turn starts
+pub fn notify_turn_start<C: Connection>(stream: &mut C) -> Result<(), StreamError>{
+    stream.write_all(&[6])?;
+    Ok(())
+}
+
+/// ask the client how the hand should be shown
+///
+/// The default layout is used if the reply can not be read as a layout.
+pub fn get_layout_from_client<C: Connection>(stream: &mut C) -> Result<LayoutSettings, StreamError> {
+    stream.write_all(&[8])?;
+    let bytes = get_bytes_from_client(stream)?;
+    Ok(LayoutSettings::from_bytes(&bytes).unwrap_or_default())
+}
+
+/// ask the player to confirm an action which can not be undone, and return their answer
+///
+/// The client shows a yes/no question, or answers yes by itself if the player turned the
+/// confirmations off.
+pub fn ask_confirmation<C: Connection>(stream: &mut C, question: &str) -> Result<bool, StreamError> {
+    stream.write_all(&[11])?;
+    send_str_to_client(stream, question)?;
+    let reply = get_bytes_from_client(stream)?;
+    Ok(is_yes(sanitize_bytes(reply)?.trim()))
+}
+
 /// send a message and get the response
-pub fn send_message_get_reply(stream: &mut TcpStream, message: &str) 
+pub fn send_message_get_reply<C: Connection>(stream: &mut C, message: &str) 
     -> Result<Vec<u8>, StreamError>
 {
-    stream.write(&mut [3])?;
+    stream.write_all(&[3])?;
     send_str_to_client(stream, message)?;
     get_bytes_from_client(stream)
 }
 
 /// send the same message to all players
-pub fn send_message_all_players(client_streams: &mut [TcpStream], message: &str) {
+pub fn send_message_all_players<C: Connection>(client_streams: &mut [C], message: &str) {
 
     let n_players: usize = client_streams.len();
 
     // send the messages
     for i in 0..n_players {
-        client_streams[i].write(&mut [1]).unwrap_or(1);
+        client_streams[i].write_all(&[1]).unwrap_or(());
         send_bytes_to_client_no_wait(&mut client_streams[i], &message.as_bytes()).unwrap_or(());
     }
 
     // wait until all clients have confirmed reception
     for i in 0..n_players {
-        client_streams[i].read(&mut [0]).unwrap_or(0);
+        client_streams[i].wait_for_ack().unwrap_or(());
     }
     
 }
 
 /// clear the screens and send the same message to all players
-pub fn clear_and_send_message_all_players(client_streams: &mut [TcpStream], message: &str) {
+pub fn clear_and_send_message_all_players<C: Connection>(client_streams: &mut [C], message: &str) {
 
     let n_players: usize = client_streams.len();
 
     // send the messages
     for i in 0..n_players {
-        client_streams[i].write(&mut [2]).unwrap_or(1);
+        client_streams[i].write_all(&[2]).unwrap_or(());
         send_bytes_to_client_no_wait(&mut client_streams[i], &message.as_bytes()).unwrap_or(());
     }
 
     // wait until all clients have confirmed reception
     for i in 0..n_players {
-        client_streams[i].read(&mut [0]).unwrap_or(1);
+        client_streams[i].wait_for_ack().unwrap_or(());
     }
     
 }
@@ -794,7 +1640,17 @@ pub fn clear_and_send_message_all_players(client_streams: &mut [TcpStream], mess
 
 #[derive(Debug)]
 pub struct StreamError {
-    message: String
+    message: String,
+    /// whether nothing came before the timeout of the connection
+    timed_out: bool
+}
+
+impl StreamError {
+
+    /// Whether the error is a read which timed out, rather than a lost connection
+    pub fn timed_out(&self) -> bool {
+        self.timed_out
+    }
 }
 
 #[derive(Debug)]
@@ -808,18 +1664,19 @@ impl std::fmt::Display for StreamError {
 
 impl std::convert::From<std::io::Error> for StreamError {
     fn from(error: std::io::Error) -> Self {
-        StreamError { message: format!("IO Error: {}", error) }
+        let timed_out = matches!(error.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock);
+        StreamError { message: format!("IO Error: {}", error), timed_out }
     }
 }
 
 impl std::convert::From<BytesToStringError> for StreamError {
     fn from(_error: BytesToStringError) -> Self {
-        StreamError { message: "Could not convert the byte sequence to a string".to_string() }
+        StreamError { message: "Could not convert the byte sequence to a string".to_string(), timed_out: false }
     }
 }
 
 impl std::convert::From<FromUtf8Error> for StreamError {
     fn from(error: FromUtf8Error) -> Self {
-        StreamError { message: format!("UTF-8 error: {}", &error) }
+        StreamError { message: format!("UTF-8 error: {}", &error), timed_out: false }
     }
 }
@@ -0,0 +1,430 @@
+//! Line editing and history for the prompts (`line_editing` feature)
+//!
+//! When the standard input is a terminal, lines are read with the terminal in non-canonical mode
+//! (set with `stty`), so that they can be edited: the left and right arrows, Home and End (or
+//! Ctrl-A and Ctrl-E) move the cursor, Backspace and Delete remove a character, Ctrl-U clears the
+//! line, and the up and down arrows go through the lines entered before. Ctrl-C restores the
+//! terminal, resets the style, shows the cursor, and quits. Clicks on the cards and on the table
+//! write their numbers in the line (see the `mouse` module).
+//!
+//! If the terminal can not be set up, lines are read as usual.
+
+use std::io::{ self, stdin, stdout, BufRead, IsTerminal, Read, Write };
+use std::process::{ Command, Stdio };
+use std::sync::Mutex;
+use crate::layout::terminal_height;
+use crate::mouse;
+
+/// number of lines kept in the history
+pub const HISTORY_SIZE: usize = 100;
+
+/// lines entered in this process, from the oldest
+static HISTORY: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Keep a line in the history, unless it is empty or the same as the last one
+pub fn remember(line: &str) {
+    if let Ok(mut history) = HISTORY.lock() {
+        if line.trim().is_empty() || history.last().map(|l| l.as_str()) == Some(line) {
+            return;
+        }
+        if history.len() >= HISTORY_SIZE {
+            history.remove(0);
+        }
+        history.push(line.to_string());
+    }
+}
+
+fn history() -> Vec<String> {
+    HISTORY.lock().map(|h| h.clone()).unwrap_or_default()
+}
+
+// run `stty` on the terminal, and return what it prints
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).stderr(Stdio::null()).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+/// escape sequence asking the terminal to report the clicks
+const MOUSE_ON: &str = "\x1b[?1000h\x1b[?1006h";
+
+/// escape sequence asking the terminal to stop reporting the clicks
+const MOUSE_OFF: &str = "\x1b[?1006l\x1b[?1000l";
+
+/// Terminal in non-canonical mode and reporting the clicks, restored when dropped
+struct RawMode {
+    saved: String
+}
+
+impl RawMode {
+    fn enable() -> Option<RawMode> {
+        let saved = stty(&["-g"])?.trim().to_string();
+        stty(&["-icanon", "-echo", "-isig", "min", "1", "time", "0"])?;
+        print!("{}", MOUSE_ON);
+        stdout().flush().unwrap_or(());
+        Some(RawMode { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("{}", MOUSE_OFF);
+        stdout().flush().unwrap_or(());
+        stty(&[&self.saved]);
+    }
+}
+
+/// A key of the terminal
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    ClearLine,
+    /// click of the left button, at a column and a row from 1
+    Click(usize, usize),
+    Interrupt,
+    /// end of the input
+    Eof,
+    /// a key which does nothing
+    Other
+}
+
+/// Read a key from bytes of the terminal
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::line_editor::*;
+///
+/// let mut input = "é\x1b[D\x1b[3~".as_bytes();
+///
+/// assert_eq!(Key::Char('é'), read_key(&mut input));
+/// assert_eq!(Key::Left, read_key(&mut input));
+/// assert_eq!(Key::Delete, read_key(&mut input));
+/// assert_eq!(Key::Eof, read_key(&mut input));
+///
+/// // a click at the 12th column of the 3rd row, and the release of the button
+/// let mut input = "\x1b[<0;12;3M\x1b[<0;12;3m".as_bytes();
+/// assert_eq!(Key::Click(12, 3), read_key(&mut input));
+/// assert_eq!(Key::Other, read_key(&mut input));
+/// ```
+pub fn read_key<R: Read>(input: &mut R) -> Key {
+    let mut byte = [0u8];
+    let mut next = |input: &mut R| input.read_exact(&mut byte).ok().map(|_| byte[0]);
+    let first = match next(input) {
+        Some(b) => b,
+        None => return Key::Eof
+    };
+    match first {
+        b'\n' | b'\r' => Key::Enter,
+        3 => Key::Interrupt,
+        4 => Key::Eof,
+        1 => Key::Home,
+        5 => Key::End,
+        21 => Key::ClearLine,
+        8 | 127 => Key::Backspace,
+        27 => match (next(input), next(input)) {
+            (Some(b'['), Some(b'A')) => Key::Up,
+            (Some(b'['), Some(b'B')) => Key::Down,
+            (Some(b'['), Some(b'C')) => Key::Right,
+            (Some(b'['), Some(b'D')) => Key::Left,
+            (Some(b'['), Some(b'H')) | (Some(b'O'), Some(b'H')) => Key::Home,
+            (Some(b'['), Some(b'F')) | (Some(b'O'), Some(b'F')) => Key::End,
+            (Some(b'['), Some(b'3')) => {
+                next(input);
+                Key::Delete
+            },
+            (Some(b'['), Some(b'<')) => {
+                // button, column, and row, ending with `M` when pressed and `m` when released
+                let mut report = String::new();
+                loop {
+                    match next(input) {
+                        Some(b) if b.is_ascii_digit() || b == b';' => report.push(b as char),
+                        Some(b'M') => break,
+                        _ => return Key::Other
+                    }
+                }
+                let numbers: Vec<usize> = report.split(';').filter_map(|n| n.parse().ok()).collect();
+                match numbers[..] {
+                    [0, column, row] => Key::Click(column, row),
+                    _ => Key::Other
+                }
+            },
+            _ => Key::Other
+        },
+        b if b < 32 => Key::Other,
+        b => {
+            // the other bytes of a character written with several bytes
+            let len = match b {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1
+            };
+            let mut bytes = vec![b];
+            for _ in 1..len {
+                match next(input) {
+                    Some(b) => bytes.push(b),
+                    None => return Key::Eof
+                }
+            }
+            match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+                Some(c) => Key::Char(c),
+                None => Key::Other
+            }
+        }
+    }
+}
+
+/// Line being edited, with the position of the cursor and of the line shown from the history
+#[derive(Debug, PartialEq, Clone)]
+pub struct LineEditor {
+    chars: Vec<char>,
+    cursor: usize,
+    history: Vec<String>,
+    /// line of the history shown, or `history.len()` for the new line
+    i_history: usize,
+    /// new line, kept while going through the history
+    draft: Vec<char>
+}
+
+impl LineEditor {
+
+    /// Empty line, with the lines entered before
+    pub fn new(history: Vec<String>) -> LineEditor {
+        let i_history = history.len();
+        LineEditor { chars: Vec::new(), cursor: 0, history, i_history, draft: Vec::new() }
+    }
+
+    /// Text of the line
+    pub fn line(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    /// Position of the cursor, in characters
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Write a word at the cursor, with a space before it if needed, and one after it
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::line_editor::LineEditor;
+    ///
+    /// let mut editor = LineEditor::new(Vec::new());
+    /// editor.insert_word("p");
+    /// editor.insert_word("12");
+    /// assert_eq!("p 12 ", editor.line());
+    /// ```
+    pub fn insert_word(&mut self, word: &str) {
+        if self.cursor > 0 && !self.chars[self.cursor - 1].is_whitespace() {
+            self.apply(Key::Char(' '));
+        }
+        for c in word.chars() {
+            self.apply(Key::Char(c));
+        }
+        self.apply(Key::Char(' '));
+    }
+
+    fn show_history(&mut self, i: usize) {
+        if self.i_history == self.history.len() {
+            self.draft = self.chars.clone();
+        }
+        self.i_history = i;
+        self.chars = match self.history.get(i) {
+            Some(line) => line.chars().collect(),
+            None => self.draft.clone()
+        };
+        self.cursor = self.chars.len();
+    }
+
+    /// Apply a key other than Enter, Ctrl-C, or the end of the input
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::line_editor::*;
+    ///
+    /// let mut editor = LineEditor::new(vec!["p 1 2 3".to_string()]);
+    /// for key in [Key::Char('4'), Key::Home, Key::Char('p'), Key::Char(' '), Key::Up] {
+    ///     editor.apply(key);
+    /// }
+    /// assert_eq!("p 1 2 3", editor.line());
+    ///
+    /// // the line being written comes back after the history
+    /// editor.apply(Key::Down);
+    /// assert_eq!("p 4", editor.line());
+    /// ```
+    pub fn apply(&mut self, key: Key) {
+        match key {
+            Key::Char(c) => {
+                self.chars.insert(self.cursor, c);
+                self.cursor += 1;
+            },
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.chars.remove(self.cursor);
+            },
+            Key::Delete if self.cursor < self.chars.len() => {
+                self.chars.remove(self.cursor);
+            },
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(self.chars.len()),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = self.chars.len(),
+            Key::ClearLine => {
+                self.chars.clear();
+                self.cursor = 0;
+            },
+            Key::Up if self.i_history > 0 => self.show_history(self.i_history - 1),
+            Key::Down if self.i_history < self.history.len() => self.show_history(self.i_history + 1),
+            _ => ()
+        }
+    }
+}
+
+/// reset the style and show the cursor, after the terminal mode is restored
+fn interrupt(raw: RawMode) -> ! {
+    drop(raw);
+    println!();
+    crate::terminal::exit(130)
+}
+
+/// Read a line from the terminal with editing and history, ending with a newline as with
+/// `BufRead::read_line`
+///
+/// Returns `None` if the standard input is not a terminal or can not be set up, in which case
+/// nothing is read.
+pub fn read_line() -> Option<io::Result<String>> {
+    if !stdin().is_terminal() {
+        return None;
+    }
+    let raw = RawMode::enable()?;
+    let mut input = stdin().lock();
+    let mut out = stdout();
+    let mut editor = LineEditor::new(history());
+    loop {
+        let (line_before, cursor_before) = (editor.line(), editor.cursor());
+        match read_key(&mut input) {
+            Key::Enter => break,
+            Key::Interrupt => interrupt(raw),
+            Key::Eof if editor.line().is_empty() => {
+                drop(raw);
+                return Some(Ok(String::new()));
+            },
+            Key::Eof => break,
+            Key::Click(column, row) => {
+                if let Some(n) = mouse::clicked(row, column, terminal_height()) {
+                    editor.insert_word(&n.to_string());
+                }
+            },
+            key => editor.apply(key)
+        }
+
+        // redraw the line from its start, and put the cursor back
+        let line = editor.line();
+        if line == line_before && editor.cursor() == cursor_before {
+            continue;
+        }
+        let mut redraw = String::new();
+        if cursor_before > 0 {
+            redraw += &format!("\x1b[{}D", cursor_before);
+        }
+        redraw += &format!("{}\x1b[K", line);
+        let back = line.chars().count() - editor.cursor();
+        if back > 0 {
+            redraw += &format!("\x1b[{}D", back);
+        }
+        if out.write_all(redraw.as_bytes()).and_then(|_| out.flush()).is_err() {
+            break;
+        }
+    }
+    drop(raw);
+    println!();
+    let line = editor.line();
+    mouse::shown(&format!("{}\n", line));
+    remember(&line);
+    Some(Ok(line + "\n"))
+}
+
+/// Standard input, read through the line editor when it is a terminal
+pub struct EditedStdin {
+    buffer: Vec<u8>,
+    pos: usize
+}
+
+impl EditedStdin {
+    pub fn new() -> EditedStdin {
+        EditedStdin { buffer: Vec::new(), pos: 0 }
+    }
+}
+
+impl Default for EditedStdin {
+    fn default() -> Self {
+        EditedStdin::new()
+    }
+}
+
+impl Read for EditedStdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for EditedStdin {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos >= self.buffer.len() {
+            let line = match read_line() {
+                Some(line) => line?,
+                None => {
+                    let mut line = String::new();
+                    stdin().read_line(&mut line)?;
+                    line
+                }
+            };
+            self.buffer = line.into_bytes();
+            self.pos = 0;
+        }
+        Ok(&self.buffer[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buffer.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn editing_in_the_middle() {
+        let mut editor = LineEditor::new(Vec::new());
+        let mut input = "p 13\x1b[D\x1b[D2 \x1b[F\x7f\x1b[H\x1b[3~t".as_bytes();
+        loop {
+            match read_key(&mut input) {
+                Key::Eof => break,
+                key => editor.apply(key)
+            }
+        }
+        assert_eq!("t 2 1", editor.line());
+        assert_eq!(1, editor.cursor());
+    }
+}
@@ -0,0 +1,107 @@
+//! Messages the server prints for its administrator
+//!
+//! While a game is running, the server prints what happens to the connections and to the saves.
+//! How much it prints depends on the log level of its settings: `error` only shows what went
+//! wrong, `info` (the default) also shows the players connecting, leaving, and coming back, and
+//! `debug` also shows the start of each turn. The level is shared by the whole server, so that
+//! reloading the settings changes it for the games already running.
+
+use std::sync::atomic::{ AtomicU8, Ordering };
+
+/// How much the server prints
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+pub enum LogLevel {
+    Error,
+    #[default]
+    Info,
+    Debug
+}
+
+impl LogLevel {
+
+    /// Read a level from its name
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::logging::LogLevel;
+    ///
+    /// assert_eq!(Some(LogLevel::Debug), LogLevel::parse("debug"));
+    /// assert_eq!(None, LogLevel::parse("verbose"));
+    /// ```
+    pub fn parse(name: &str) -> Option<LogLevel> {
+        match name {
+            "error" => Some(LogLevel::Error),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            _ => None
+        }
+    }
+
+    /// Name of the level, as written in the settings
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug"
+        }
+    }
+
+    fn from_u8(n: u8) -> LogLevel {
+        match n {
+            0 => LogLevel::Error,
+            1 => LogLevel::Info,
+            _ => LogLevel::Debug
+        }
+    }
+}
+
+/// level of the messages printed
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+/// Print the messages up to `level` from now on
+pub fn set_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::SeqCst);
+}
+
+/// Level of the messages printed
+pub fn level() -> LogLevel {
+    LogLevel::from_u8(LEVEL.load(Ordering::SeqCst))
+}
+
+/// Print a message if the log level shows messages of `level`
+pub fn log(level: LogLevel, message: &str) {
+    if level <= self::level() {
+        println!("{}", message);
+    }
+}
+
+/// Print something which went wrong
+pub fn error(message: &str) {
+    log(LogLevel::Error, message);
+}
+
+/// Print what happens to the players and the game
+pub fn info(message: &str) {
+    log(LogLevel::Info, message);
+}
+
+/// Print the details of the game
+pub fn debug(message: &str) {
+    log(LogLevel::Debug, message);
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn levels_are_ordered_and_named() {
+        assert!(LogLevel::Error < LogLevel::Info && LogLevel::Info < LogLevel::Debug);
+        for level in [LogLevel::Error, LogLevel::Info, LogLevel::Debug].iter() {
+            assert_eq!(Some(*level), LogLevel::parse(level.name()));
+            assert_eq!(*level, LogLevel::from_u8(*level as u8));
+        }
+    }
+}
@@ -3,62 +3,217 @@
 //! A simple machiavelli card game *(work in progress)*
 
 use std::process;
-use std::io::{ stdin, Read, Write };
-use std::fs::File;
+use std::env;
+use std::io::stdin;
 use rand::thread_rng;
 use machiavelli::*;
+use machiavelli::turn_order::{ self, TurnOrder };
+use machiavelli::player::Player;
+use machiavelli::turn::Ongoing;
+use machiavelli::invariants::CardLedger;
 
 fn main() {
 
+    // script mode: `machiavelli script <file>`, or `-` to read the script from the standard input;
+    // the game is played without the terminal, and only its outcome is printed; with `--step`, the
+    // game stops after each action to show its state (see the `debugger` module)
+    let args: Vec<String> = env::args().collect();
+    if args.len() > 1 && args[1] == "script" {
+        let step = args.iter().any(|a| a == debugger::STEP_FLAG);
+        let args: Vec<&String> = args.iter().filter(|a| *a != debugger::STEP_FLAG).collect();
+        let content = match args.get(2).map(|a| a.as_str()) {
+            Some("-") | None if step => {
+                println!("The script must come from a file to be played step by step");
+                process::exit(1);
+            },
+            Some("-") | None => std::io::read_to_string(stdin()),
+            Some(fname) => std::fs::read_to_string(fname)
+        };
+        let content = content.unwrap_or_else(|e| {
+            println!("Could not read the script: {}", e);
+            process::exit(1);
+        });
+        let result = if step {
+            let mut debugger = debugger::Debugger::new(stdin().lock(), std::io::stdout());
+            script::run_with(&content, |game, line, action| debugger.after_action(game, line, action))
+        } else {
+            script::run(&content)
+        };
+        match result {
+            Ok(game) => print!("{}", game.report()),
+            Err(e) => {
+                println!("Error in the script: {}", e);
+                process::exit(1);
+            }
+        };
+        return;
+    }
+
+    // rules check: `machiavelli check "2♣ 3♣ 4♣" [--decks N] [--jokers N]`; tells whether the
+    // sequence is valid and why
+    if args.len() > 1 && args[1] == "check" {
+        let (n_decks, args) = take_flag_value(args, check::DECKS_FLAG);
+        let (n_jokers, args) = take_flag_value(args, check::JOKERS_FLAG);
+        let limits = if n_decks.is_some() || n_jokers.is_some() {
+            let n_decks = n_decks.map_or(Ok(2), |n| n.parse::<u8>());
+            let n_jokers = n_jokers.map_or(Ok(4), |n| n.parse::<u8>());
+            match (n_decks, n_jokers) {
+                (Ok(n_decks), Ok(n_jokers)) => Some((n_decks, n_jokers)),
+                _ => {
+                    println!("The numbers of decks and jokers must be integers between 0 and 255");
+                    process::exit(2);
+                }
+            }
+        } else {
+            None
+        };
+        match check::parse_sequence(&args[2..].join(" ")) {
+            Ok(sequence) => {
+                let verdict = check::check(&sequence, limits);
+                print!("{}", verdict.render());
+                process::exit(if verdict.valid { 0 } else { 1 });
+            },
+            Err(e) => {
+                println!("Could not read the sequence: {}", e);
+                process::exit(2);
+            }
+        }
+    }
+
+    // load the player preferences
+    let settings = settings::Settings::load().unwrap_or_else(|e| {
+        println!("Could not load the settings: {}", e);
+        settings::Settings::new()
+    });
+    settings.apply();
+
+    // put the terminal back as it was when the game ends, panics, or is interrupted
+    if !terminal::enable_virtual_terminal() {
+        println!("{}", terminal::NO_VIRTUAL_TERMINAL);
+    }
+    let _terminal = terminal::TerminalGuard::new();
+    if terminal::set_handler(false).is_err() {
+        println!("Could not set the Ctrl-C signal handler!");
+    }
+
     // set the style
     reset_style();
 
     // clear the terminal
     print!("\x1b[2J\x1b[1;1H");
 
+    // animations, unless disabled with `--no-animations`
+    let animations = animations::Animations::from_args(&args);
+    let args: Vec<String> = args.into_iter().filter(|a| a != animations::NO_ANIMATIONS_FLAG).collect();
+
+    // file to write the transcript to at the end of the game, if any
+    let (transcript, args) = replay::transcript_file(args);
+
+    // demo mode: `machiavelli --demo [--demo-delay <milliseconds>]`; bots play each other until
+    // Ctrl-C
+    let (delay, args) = take_flag_value(args, demo::DELAY_FLAG);
+    if args.iter().any(|a| a == demo::DEMO_FLAG) {
+        let delay = delay.and_then(|d| d.parse().ok()).unwrap_or(demo::DEFAULT_DELAY_MS);
+        demo::run(demo::demo_config(), std::time::Duration::from_millis(delay));
+    }
+
+    // puzzle mode: `machiavelli puzzle [YYYY-MM-DD]`
+    if args.len() > 1 && args[1] == "puzzle" {
+        let date = match args.get(2) {
+            Some(s) => match puzzle::parse_date(s) {
+                Some(d) => d,
+                None => {
+                    println!("Invalid date (expected YYYY-MM-DD)");
+                    terminal::exit(1);
+                }
+            },
+            None => puzzle::today()
+        };
+        let daily_puzzle = puzzle::Puzzle::daily(date.0, date.1, date.2);
+        match puzzle::play_puzzle(&daily_puzzle) {
+            Some(n_moves) => println!("\x1b[1mSolved in {} moves! Congratulations!\x1b[0m\n", n_moves),
+            None => println!("\x1b[0mSee you tomorrow!\n")
+        };
+        return;
+    }
+
     // get the config
     println!("Hi there! Up for a game of Machiavelli?\n");
     let mut config = match get_config() {
         Ok(conf) => conf, 
         Err(_) => {
             println!("Invalid input!");
-            process::exit(1);
+            terminal::exit(1);
         },
     };
     
+    // directory where bare save names go, if the data directory can be used
+    let save_dir = paths::save_dir(None);
+
     // create the table
     let mut table = Table::new();
-    let mut deck = Sequence::new();
-    let mut hands = Vec::<Sequence>::new();
-    let mut starting_player: u8 = 0;
-    let mut player: u8 = 0;
-    let mut player_names = Vec::<String>::new();
+    let mut deck = Deck::new();
+    let mut hands = Vec::<Hand>::new();
+    let mut mulligans = Vec::<String>::new();
+    let mut first_player = None; // player who drew the highest card, with the draw for the first turn
+    let mut order = TurnOrder::new(config.n_players as usize, 0);
+    let mut player: u8;
+    let mut n_turns: u32 = 0;
+    let mut resumed_turn = None; // turn saved while in progress
+    let mut saved_clock = None; // time taken by the players before the game was saved
 
     if config.n_decks == 0 {
         
-        // load the previous game
-        println!("Name of the save file:");
+        // load the previous game, which can be picked by its number among the saves found in the
+        // save directory
+        let mut saves = Vec::new();
+        let mut list_saves = true;
         let mut fname = String::new();
-        let mut bytes = Vec::<u8>::new();
         let mut retry = true;
         while retry {
 
             retry = false;
+
+            // list the saves again after one was deleted or renamed
+            if list_saves {
+                saves = save_dir.as_deref().map(|d| saves::list_saves(d, "")).unwrap_or_default();
+                if saves.is_empty() {
+                    println!("Name of the save file:");
+                } else {
+                    println!("Saved games:");
+                    saves::print_saves(&saves);
+                    println!("Number or name of the save file ({}):", saves::MANAGE_SAVES_HELP);
+                }
+                list_saves = false;
+            }
             
             // get the file name
-            match stdin().read_line(&mut fname) {
-                Ok(_) => (),
+            match get_input() {
+                Ok(s) => fname += &s,
                 Err(_) => retry = true
             };
 
-            fname = fname.trim().to_string();
+            if let Some(message) = saves::manage_saves(&fname, &saves, save_dir.as_deref(), "") {
+                println!("{}", message);
+                fname.clear();
+                retry = true;
+                list_saves = true;
+                continue;
+            }
+
+            fname = match saves::pick_save(&saves, &fname) {
+                Some(save) => save.path.clone(),
+                None => fname.trim().to_string()
+            };
+
+            // bare names are looked for in the save directory
+            fname = paths::find_save(&fname, save_dir.as_deref());
 
             if !retry {
 
-                // load the data from the file
-                let mut file: File; 
-                match File::open(fname.clone()) {
-                    Ok(f) => file = f,
+                // read the file
+                let data = match std::fs::read(fname.clone()) {
+                    Ok(data) => data,
                     Err(_) => {
                         println!("Could not open the file!");
                         retry = true;
@@ -66,32 +221,32 @@ fn main() {
                         continue;
                     }
                 };
-                match file.read_to_end(&mut bytes) {
-                    Ok(_) => (),
-                    Err(_) => {
-                        println!("Could not read from the file!");
-                        retry = true;
-                        bytes.clear();
-                        fname.clear();
-                    }
-                };
                 
-                // decode the sequence of bytes
-                bytes = encode::xor(&bytes, &fname.as_bytes());
-
-                match load_game(&bytes) {
-                    Ok(lg) => {
+                // decrypt and load the game
+                match encode::decrypt_save_or_legacy(&data, &fname, ask_legacy).map_err(|_| LoadingError::Corrupted)
+                    .and_then(|b| save_format::load(&b, ask_repair))
+                {
+                    Ok((lg, turn, report)) => {
+                        for note in report.notes() {
+                            println!("{}", note);
+                        }
                         config = lg.0;
-                        starting_player = lg.1; 
-                        player = lg.2; 
+                        order = TurnOrder::resume(config.n_players as usize, lg.1 as usize, lg.2 as usize);
                         table = lg.3;
                         hands = lg.4; 
                         deck = lg.5;
-                        player_names = lg.6;
-                        bytes = Vec::<u8>::new();
+                        // cards taken from the table and kept apart go to the hand, as in this game
+                        resumed_turn = turn.map(|(turn, cards_from_table)| {
+                            hands[order.current()].merge(cards_from_table);
+                            turn
+                        });
+                        if let Some(metadata) = encode::save_metadata(&data).and_then(saves::SaveMetadata::from_bytes) {
+                            n_turns = metadata.turn;
+                            saved_clock = Some(metadata.clock());
+                        }
                     },
-                    Err(_) => {
-                        println!("Error loading the save file!");
+                    Err(e) => {
+                        println!("Error loading the save file: {}", e);
                     }
                 };
             }
@@ -101,48 +256,171 @@ fn main() {
 
         // build the deck
         let mut rng = thread_rng();
-        deck = Sequence::multi_deck(config.n_decks, config.n_jokers, &mut rng);
+        deck = Deck::shuffled(config.n_decks, config.n_jokers, &mut rng);
         
         // build the hands
-        hands = vec![Sequence::new(); config.n_players as usize];
-        for i in 0..config.n_players {
-            for _ in 0..config.n_cards_to_start {
-                hands[i as usize].add_card(deck.draw_card().unwrap());
+        let mut dealt = vec![Sequence::new(); config.n_players as usize];
+        for hand in dealt.iter_mut() {
+            for card in deck.draw_n(config.n_cards_to_start as usize) {
+                hand.add_card(card);
             }
         }
 
         // get the players name
+        let mut player_names = Vec::<String>::new();
         for i in 0..config.n_players {
-            println!("Player {}'s name: ", i+1);
+
+            // the first player can use the name from the settings
+            let default_name = match &settings.player_name {
+                Some(name) if i == 0 => Some(name.clone()),
+                _ => None
+            };
+            match &default_name {
+                Some(name) => println!("Player {}'s name (leave empty for {}): ", i+1, name),
+                None => println!("Player {}'s name: ", i+1)
+            };
             let mut cont = true;
             while cont {
                 match get_input() {
                     Ok(s) => {
-                        player_names.push(s.trim().to_string());
-                        cont = false
+                        let name = match &default_name {
+                            Some(name) if s.trim().is_empty() => name.clone(),
+                            _ => s.trim().to_string()
+                        };
+                        match player_name::PlayerName::new(&name) {
+                            Ok(name) => {
+                                player_names.push(name.into());
+                                cont = false
+                            },
+                            Err(e) => println!("{}", e)
+                        }
                     },
                     Err(_) => println!("Could not parse the input")
                 };
             }
         }
+        hands = Hand::deal(&player_names, dealt);
 
+        // each player may reject their hand once
+        if config.mulligan != MulliganRule::Off {
+            for hand in hands.iter_mut() {
+                clear_terminal();
+                println!("\x1b[1m{}'s hand:", hand.owner());
+                reset_style();
+                println!("{}", hand.cards());
+                reset_style();
+                if prompt::Prompter::new(prompt::terminal_input()).confirm(config.mulligan.question()) {
+                    match config.mulligan.redeal(hand, &mut deck, config.n_cards_to_start) {
+                        Ok(_) => mulligans.push(hand.owner().to_string()),
+                        Err(m) => println!("{}", m)
+                    }
+                }
+            }
+        }
 
+        // the player drawing the highest card plays first
+        if config.draw_for_first {
+            clear_terminal();
+            let draw = turn_order::draw_for_first(config.n_players as usize, &mut rng);
+            println!("{}\n\nPress Enter to start", draw.render(&player_names));
+            get_input().unwrap_or_default();
+            order = TurnOrder::new(config.n_players as usize, draw.winner);
+            first_player = player_names.get(draw.winner).cloned();
+        }
     }
+
+    // seat the players; they all play on this terminal
+    let mut players = Player::seat(hands, vec![(); config.n_players as usize]);
+    colours::register(&hand::owners(&players));
     
     // play until a player wins, there is no card left in the deck, or the player decides to save
     // and quit
-    let mut save_and_quit: bool;
+    let mut save_and_quit = false;
+    let mut log = events::EventLog::new();
+    let mut tracker = tracking::CardTracker::new(config.n_decks, config.n_jokers, &table);
+    let mut n_events_seen: usize = 0;
+    let mut has_seen_hand = vec![false; config.n_players as usize];
+    let mut stalemate = stalemate::StalemateDetector::new(config.n_players as usize);
+    let mut record = replay::GameRecord::new(&hand::owners(&players));
+    let mut clock = saved_clock.unwrap_or_else(|| clock::Clock::new(config.n_players as usize));
+    let ledger = CardLedger::new(&table, &players, &deck, &Sequence::new());
+    let mut previous_turn = events::PreviousTurn::default();
+    for name in mulligans {
+        log.push(events::Event::Mulligan(name));
+    }
+    if let Some(name) = first_player {
+        log.push(events::Event::PlaysFirst(name));
+    }
     loop {
-        if deck.number_cards() == 0 {
-            println!("\x1b[1mNo more cards in the deck—It's a draw!\x1b[0m\n");
-            break;
+        player = order.current() as u8;
+        if deck.remaining() == 0 {
+            match config.empty_deck {
+                // without cards set aside, the round goes on as with the scoring rule
+                EmptyDeckRule::NewDeck => if deck.refill(&mut thread_rng()) {
+                    log.push(events::Event::NoMoreCards);
+                    log.push(events::Event::NewDeck);
+                },
+                EmptyDeckRule::Draw => {
+                    println!("\x1b[1mNo more cards in the deck—It's a draw!\x1b[0m\n");
+                    break;
+                },
+                // the round goes on until there is a stalemate
+                EmptyDeckRule::Score => ()
+            }
+        }
+
+        // replay the last events and deal the hand of players who have not played yet
+        if animations.enabled {
+            clear_terminal();
+            println!("\x1b[1m{}'s turn", colours::paint(players[player as usize].name()));
+            reset_style();
+            if !has_seen_hand[player as usize] {
+                println!("\nYour hand:");
+                animations.deal(&players[player as usize].hand);
+                has_seen_hand[player as usize] = true;
+            }
+            let new_events = log.since(n_events_seen);
+            if !new_events.is_empty() {
+                println!("\nRecent events:");
+                animations.replay(&new_events);
+            }
+            n_events_seen = log.total();
+            animations.hold();
         }
-        save_and_quit = player_turn(&mut table, &mut hands[player as usize], 
-                                    &mut deck, config.custom_rule_jokers, &player_names[player as usize]);
+
+        record.sync(&log);
+        record.start_turn(player as usize);
+
+        let n_cards_deck_before = deck.remaining();
+        let n_cards_hand_before = players[player as usize].hand.number_cards();
+        let view = PlayerView::new(&players, &order, player as usize).with_previous_turn(previous_turn.clone());
+        let table_start = table.clone();
+        let n_events_start = log.total();
+        players[player as usize].hand.start_turn();
+        clock.start_turn(player as usize);
+        let saved_turn = player_turn(&mut table, &mut players[player as usize].hand, 
+                                     &mut deck, &config, &view,
+                                     &mut log, &mut tracker, &settings, resumed_turn.take());
+        save_and_quit = saved_turn.is_some();
+        players[player as usize].hand.end_turn(n_cards_deck_before.saturating_sub(deck.remaining()));
+        table.normalize();
+        ledger.assert_holds(&table, &players, &deck, &Sequence::new(), "a turn");
+        previous_turn = events::PreviousTurn::new(players[player as usize].name(), &table_start, &table,
+                                                  n_cards_hand_before, players[player as usize].hand.number_cards(),
+                                                  &log.since(n_events_start));
         if save_and_quit {
             
-            // convert the game data to a sequence of bytes
-            let mut bytes = game_to_bytes(starting_player, player, &table, &hands, &deck, &config, &player_names);
+            // convert the game data to a sequence of bytes, with the turn if something was
+            // played or taken in it, so that it resumes as it is
+            let saved_turn = match saved_turn {
+                Some(turn @ Ongoing::Meld(_)) => Some((turn, Sequence::new())),
+                _ => None
+            };
+            let bytes = game_to_bytes_with_turn(order.starting_player() as u8, player, &table, &players, &deck,
+                                                &config, saved_turn.as_ref());
+            let metadata = saves::SaveMetadata::new(1, n_turns, player, &players, &config)
+                .with_clock(&clock)
+                .to_bytes();
 
             println!("Name of the save file:");
             let mut fname = String::new();
@@ -152,29 +430,37 @@ fn main() {
                 retry = false;
                 
                 // get the file name
-                match stdin().read_line(&mut fname) {
-                    Ok(_) => (),
+                match get_input() {
+                    Ok(s) => fname += &s,
                     Err(_) => retry = true
                 };
-                fname = fname.trim().to_string();
+                fname = paths::in_dir(fname.trim(), save_dir.as_deref());
 
-                // obfuscate the save file (not very secure!)
-                bytes = encode::xor(&bytes, &fname.as_bytes());
-                
-                if !retry {
-
-                    // save the data to the file
-                    let mut file: File; 
-                    match File::create(fname.clone()) {
-                        Ok(f) => file = f,
-                        Err(_) => {
-                            println!("Could not create the file!");
+                // an existing save is only replaced if the player says so
+                if !retry && std::path::Path::new(&fname).exists() {
+                    let extension = std::path::Path::new(&fname).extension()
+                        .map(|e| format!(".{}", e.to_string_lossy()))
+                        .unwrap_or_default();
+                    let versioned = saves::versioned_name(&fname, &extension);
+                    println!("{} already exists: overwrite it (‘o’), save as {} (‘v’), or choose another name \
+                              (anything else)?", fname, versioned);
+                    match get_input().as_ref().map(|s| s.trim()) {
+                        Ok("o") => (),
+                        Ok("v") => fname = versioned,
+                        _ => {
+                            println!("Name of the save file:");
+                            fname.clear();
                             retry = true;
-                            continue;
                         }
                     };
-                    match file.write_all(&bytes) {
-                        Ok(_) => (),
+                }
+                
+                if !retry {
+
+                    // encrypt the save file with its name, and replace the file only once it
+                    // is fully written
+                    match write_atomically(&fname, &encode::encrypt_save(&bytes, &metadata, &fname, &mut rand::thread_rng())) {
+                        Ok(_) => println!("Game saved to {}", fname),
                         Err(_) => {
                             println!("Could not write to the file!");
                             retry = true;
@@ -185,14 +471,57 @@ fn main() {
 
             break;
         }
-        if hands[player as usize].number_cards() == 0 {
+        clock.end_turn();
+        if players[player as usize].hand.number_cards() == 0 {
             println!("\x1b[1mPlayer {} wins! Congratulations!\x1b[0m\n", player+1);
+            players[player as usize].score += 1;
             break;
         }
-        player = (player + 1) % config.n_players;
+
+        // with the scoring rule, the round ends when nobody can play any more
+        if config.empty_deck == EmptyDeckRule::Score {
+            if n_cards_deck_before > 0 && deck.remaining() == 0 {
+                log.push(events::Event::NoMoreCards);
+            }
+            let played = players[player as usize].hand.number_cards() < n_cards_hand_before;
+            if stalemate.record_turn(deck.remaining() == 0, played) {
+                match round_winner(&players, &config.point_rules()) {
+                    Some(i) => {
+                        println!("\x1b[1mNobody can play any more—{} wins with the fewest cards left!\x1b[0m\n",
+                                 players[i].name());
+                        players[i].score += 1;
+                    },
+                    None => println!("\x1b[1mNobody can play any more—It's a draw!\x1b[0m\n")
+                };
+                break;
+            }
+        }
+        order.advance();
+        n_turns += 1;
+    }
+
+    // cards and points left in each hand
+    if !save_and_quit {
+        println!("{}", round_summary(&players, &config.point_rules()));
+        println!("{}", clock.summary(&hand::owners(&players)));
+
+        // picture of the final position, with the hand of the last player
+        #[cfg(feature = "render_image")]
+        match image::save_svg(&table, &players[player as usize].hand, image::FINAL_POSITION_FILE) {
+            Ok(()) => println!("The final position was saved to {}", image::FINAL_POSITION_FILE),
+            Err(_) => println!("Could not save the final position!")
+        };
+
+        if let Some(fname) = &transcript {
+            record.sync(&log);
+            record.end_round(&players);
+            match replay::save_transcript(&record, &config.point_rules(), &settings.locale, fname) {
+                Ok(()) => println!("Transcript saved to {}", fname),
+                Err(_) => println!("Could not save the transcript!")
+            };
+        }
     }
     
-    // reset the style
-    println!("\x1b[0m");
-    print!("\x1b[?25h");
+    // the style is reset when the terminal guard is dropped
+    println!();
 }
@@ -0,0 +1,200 @@
+//! Clicks on the cards and on the table
+//!
+//! With the `line_editing` feature, while a line is edited, the terminal reports the clicks of the mouse. A click on a card of the
+//! hand writes its index in the line, and a click on a sequence of the table writes its number,
+//! so that `p` followed by clicks on three cards plays them, and `a`, a click on a sequence, and
+//! clicks on cards adds them to it. The keyboard works as before.
+//!
+//! The terminal does not tell what is under the mouse, so the lines printed since the screen was
+//! last cleared are kept here, and a click is matched with them counting from the line being
+//! edited. Text printed without going through `shown` shifts the lines, and clicks then miss.
+
+use std::sync::Mutex;
+use crate::sanitize::sanitize;
+
+/// number of lines kept, the last ones being shown at the bottom of the terminal
+const MAX_LINES: usize = 1000;
+
+/// lines printed since the screen was last cleared, without their escape sequences; the last one
+/// is the line being written
+static SHOWN: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Forget the lines printed, after the screen was cleared
+pub fn cleared() {
+    if let Ok(mut shown) = SHOWN.lock() {
+        shown.clear();
+    }
+}
+
+/// Keep the text just printed
+pub fn shown(text: &str) {
+    if let Ok(mut shown) = SHOWN.lock() {
+        if shown.is_empty() {
+            shown.push(String::new());
+        }
+        let mut parts = text.split('\n');
+        if let (Some(first), Some(last)) = (parts.next(), shown.last_mut()) {
+            *last += &sanitize(first);
+        }
+        shown.extend(parts.map(sanitize));
+        let n_lines = shown.len();
+        if n_lines > MAX_LINES {
+            shown.drain(..n_lines - MAX_LINES);
+        }
+    }
+}
+
+/// Print a line and keep it
+pub fn print_line(text: &str) {
+    println!("{}", text);
+    shown(&format!("{}\n", text));
+}
+
+/// Number written by a click at a row and a column of the terminal, from 1, where the line being
+/// edited is at the bottom of the text or of the terminal if it has scrolled
+pub fn clicked(row: usize, column: usize, terminal_height: usize) -> Option<usize> {
+    let shown = SHOWN.lock().ok()?;
+    let edited_row = shown.len().min(terminal_height);
+    if row > edited_row {
+        return None;
+    }
+    let i = (shown.len() + row).checked_sub(edited_row + 1)?;
+    target(&shown, i, column.checked_sub(1)?)
+}
+
+// the line matches `n: ...`, possibly with spaces or the `*` of a changed sequence in front
+fn numbered(line: &str) -> Option<usize> {
+    let (number, _) = line.trim_start().trim_start_matches('*').split_once(": ")?;
+    number.parse().ok()
+}
+
+// words of a line, with the columns where they start
+fn words(line: &str) -> Vec<(usize, &str)> {
+    let mut res = Vec::new();
+    let mut start = None;
+    for (column, (i, c)) in line.char_indices().enumerate() {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some((column, i)),
+            (Some((column_start, i_start)), true) => {
+                res.push((column_start, &line[i_start..i]));
+                start = None;
+            },
+            _ => ()
+        }
+    }
+    if let Some((column_start, i_start)) = start {
+        res.push((column_start, &line[i_start..]));
+    }
+    res
+}
+
+// index of the card at a column of a row of indices, the one starting last before the column
+fn index_at(line: &str, column: usize) -> Option<usize> {
+    let words = words(line);
+    if words.is_empty() || words.iter().any(|(_, w)| w.parse::<usize>().is_err()) {
+        return None;
+    }
+    words.iter().rev().find(|(start, _)| *start <= column).and_then(|(_, w)| w.parse().ok())
+}
+
+/// Number written by a click on the `column`-th character (from 0) of the `i`-th line of a text
+///
+/// A sequence of the table gives its number, and a card of the hand its index, whether the hand
+/// is shown with its indices (in any layout) or as a line of cards as in the single-terminal
+/// game.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::mouse::target;
+///
+/// let screen: Vec<String> = ["Table:", "1: A♠ 2♠ 3♠", "2: 7♥ 7♦ 7♣", "Your hand:", "K♥ 10♣ #", "1  2   3"]
+///     .iter().map(|s| s.to_string()).collect();
+///
+/// assert_eq!(Some(2), target(&screen, 2, 5));
+/// assert_eq!(Some(2), target(&screen, 4, 5));
+/// assert_eq!(Some(3), target(&screen, 5, 7));
+/// assert_eq!(None, target(&screen, 0, 0));
+/// ```
+pub fn target(lines: &[String], i: usize, column: usize) -> Option<usize> {
+    // the part of the screen the line belongs to
+    let header = (0..=i).rev().find(|&k| {
+        let line = lines[k].trim();
+        line == "Table:" || line == "Your hand:" || line == "Cards from the table:"
+    })?;
+    let line = lines.get(i)?;
+    if lines[header + 1..=i].iter().any(|l| l.trim().is_empty()) {
+        return None;
+    }
+    if lines[header].trim() == "Table:" {
+        // the first line of the sequence, which may be wrapped
+        return (header + 1..=i).rev().find_map(|k| numbered(&lines[k]));
+    }
+    if i == header {
+        return None;
+    }
+
+    // a card with its index written in front of it
+    if let Some(n) = numbered(line) {
+        return Some(n);
+    }
+    if let Some((_, word)) = words(line).into_iter().rev().find(|(start, _)| *start <= column) {
+        if let Some((n, _)) = word.split_once(':') {
+            return n.parse().ok();
+        }
+    }
+
+    // a row of indices, or a row of cards above it
+    if let Some(n) = index_at(line, column) {
+        return Some(n);
+    }
+    if let Some(n) = lines.get(i + 1).and_then(|next| index_at(next, column)) {
+        return Some(n);
+    }
+
+    // a line of cards without their indices, counting the cards of the lines before
+    let before: usize = (header + 1..i).map(|k| words(&lines[k]).len()).sum();
+    let on_line = words(line).iter().filter(|(start, _)| *start <= column).count();
+    if lines[header + 1..].iter().any(|l| l.starts_with("(page ") && !l.starts_with("(page 1/")) || on_line == 0 {
+        return None;
+    }
+    Some(before + on_line)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn wrapped_sequences_and_layouts_of_the_hand() {
+        let screen = lines("Table:\n1: A♠ 2♠ 3♠ 4♠\n   5♠ 6♠\nYour hand:\n♥ A♥  3♥\n  1   12\n\
+                            Cards from the table:\n13:7♦  14:#");
+        assert_eq!(Some(1), target(&screen, 2, 4));
+        assert_eq!(Some(12), target(&screen, 4, 6));
+        assert_eq!(Some(1), target(&screen, 5, 2));
+        assert_eq!(Some(14), target(&screen, 7, 9));
+
+        // cards without indices, as in the single-terminal game
+        let screen = lines("Table:\nYour hand:\nA♠ 2♠ 3♠\n4♠ 5♠");
+        assert_eq!(Some(5), target(&screen, 3, 4));
+        assert_eq!(None, target(&screen, 1, 0));
+    }
+
+    #[test]
+    fn clicks_count_from_the_edited_line() {
+        cleared();
+        shown("\x1b[1mTable:\n1: A♠ 2♠ 3♠\x1b[0m\nYour hand:\nK♥ 10♣\n1  2\n\nEvents\n> p ");
+        assert_eq!(Some(1), clicked(2, 5, 40));
+        assert_eq!(Some(2), clicked(4, 5, 40));
+        assert_eq!(None, clicked(7, 1, 40));
+
+        // the terminal has scrolled, with the edited line at its bottom
+        assert_eq!(Some(2), clicked(2, 5, 6));
+        assert_eq!(None, clicked(9, 1, 40));
+    }
+}
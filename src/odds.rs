@@ -0,0 +1,127 @@
+//! Odds of drawing a useful card
+//!
+//! A player does not know the order of the deck, but knows which cards they have not seen: the
+//! cards of the game which are neither in their hand, nor on the table, nor among the cards they
+//! saw go by. The next card drawn is any of them with the same probability. `draw_odds` counts
+//! the unseen cards which would complete a sequence, either with cards of the hand or by joining
+//! a sequence of the table, and gives the probability that the next card is one of them.
+
+use std::collections::HashMap;
+use crate::check::name;
+use crate::sequence_cards::{ Card, Sequence };
+use crate::solver::{ arrange, completes_sequence };
+use crate::table::Table;
+
+/// number of card types: the joker and the 52 regular cards
+const N_CARD_TYPES: u8 = 53;
+
+/// Cards a player could draw which would complete a sequence
+#[derive(Debug, PartialEq, Clone)]
+pub struct DrawOdds {
+    /// cards completing a sequence, with the number of their copies not seen
+    pub useful: Vec<(Card, usize)>,
+    /// number of cards not seen, in the deck or in the other hands
+    pub n_unseen: usize,
+    /// probability that the next card drawn completes a sequence
+    pub probability: f64
+}
+
+impl DrawOdds {
+
+    /// Number of unseen cards completing a sequence
+    pub fn n_useful(&self) -> usize {
+        self.useful.iter().map(|(_, n)| n).sum()
+    }
+
+    /// Odds as shown to the player
+    pub fn render(&self) -> String {
+        if self.useful.is_empty() {
+            return "No card you could draw would complete a sequence".to_string();
+        }
+        let cards: Vec<String> = self.useful.iter().map(|(card, _)| name(card)).collect();
+        format!("Chance that the next card completes a sequence: {:.0}% ({} of {} unseen cards: {})",
+                100. * self.probability, self.n_useful(), self.n_unseen, cards.join(" "))
+    }
+}
+
+/// Odds that the next card drawn completes a sequence, for a player holding `hand` in a game with
+/// `n_decks` decks and `n_jokers` jokers, who has also seen the cards `seen` apart from the table
+///
+/// The probability is zero when no card is left in the deck.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::odds::draw_odds;
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::table::Table;
+///
+/// let hand = Sequence::from_cards(&[RegularCard(Heart, 5), RegularCard(Heart, 6)]);
+/// let mut table = Table::new();
+/// table.add(Sequence::from_cards(&[RegularCard(Heart, 2), RegularCard(Heart, 3), RegularCard(Heart, 4)]));
+///
+/// // a 7♥ or a joker completes the hand (the 4♥ and 5♥ left can also go in the run of the table)
+/// let odds = draw_odds(&hand, &table, &[], 1, 2, 10);
+/// assert_eq!(vec![(Joker, 2), (RegularCard(Heart, 1), 1), (RegularCard(Heart, 7), 1)], odds.useful);
+/// assert_eq!(49, odds.n_unseen);
+/// assert_eq!(4. / 49., odds.probability);
+///
+/// assert_eq!(0., draw_odds(&hand, &table, &[], 1, 2, 0).probability);
+/// ```
+pub fn draw_odds(hand: &Sequence, table: &Table, seen: &[Card], n_decks: u8, n_jokers: u8,
+                 deck_remaining: usize) -> DrawOdds {
+
+    // copies of each card the player has not seen
+    let mut known = HashMap::<Card, usize>::new();
+    let table_cards = table.count_cards().into_iter().map(|(card, n)| (card, n as usize));
+    let other_cards = hand.to_vec().into_iter().chain(seen.iter().cloned()).map(|card| (card, 1));
+    for (card, n) in table_cards.chain(other_cards) {
+        *known.entry(card).or_insert(0) += n;
+    }
+    let unseen: Vec<(Card, usize)> = (0..N_CARD_TYPES)
+        .filter_map(Card::from_byte)
+        .map(|card| {
+            let n_copies = if card == Card::Joker { n_jokers } else { n_decks } as usize;
+            let n = n_copies.saturating_sub(known.get(&card).cloned().unwrap_or(0));
+            (card, n)
+        })
+        .filter(|&(_, n)| n > 0)
+        .collect();
+    let n_unseen = unseen.iter().map(|(_, n)| n).sum();
+
+    let sequences: Vec<&Sequence> = (1..=table.number_sequences()).filter_map(|n| table.get(n)).collect();
+    let useful: Vec<(Card, usize)> = unseen.into_iter()
+        .filter(|(card, _)| completes_sequence(hand, card) || sequences.iter().any(|seq| {
+            let mut cards = (*seq).clone();
+            cards.add_card(card.clone());
+            arrange(&cards).is_some()
+        }))
+        .collect();
+
+    let n_useful: usize = useful.iter().map(|(_, n)| n).sum();
+    let probability = if deck_remaining == 0 || n_unseen == 0 {
+        0.
+    } else {
+        n_useful as f64 / n_unseen as f64
+    };
+    DrawOdds { useful, n_unseen, probability }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sequence_cards::Card::*;
+    use crate::sequence_cards::Suit::*;
+
+    #[test]
+    fn seen_cards_are_not_counted() {
+        let hand = Sequence::from_cards(&[RegularCard(Club, 9), RegularCard(Spade, 9)]);
+        let seen = [RegularCard(Heart, 9), RegularCard(Heart, 9), Joker];
+        let odds = draw_odds(&hand, &Table::new(), &seen, 2, 2, 40);
+        assert_eq!(vec![(Joker, 1), (RegularCard(Diamond, 9), 2)], odds.useful);
+        assert_eq!(104 + 2 - 5, odds.n_unseen);
+        assert_eq!("Chance that the next card completes a sequence: 3% (3 of 101 unseen cards: ★ 9♦)",
+                   odds.render());
+    }
+}
@@ -0,0 +1,141 @@
+//! Directories where the game keeps its files
+//!
+//! Settings go to the configuration directory of the platform, and saves (with their audit
+//! logs) to the `saves` directory in its data directory. The server can use another data
+//! directory with `--data-dir <directory>`. Names of save files with a directory, or absolute
+//! paths, are used as given.
+
+use std::path::{ Path, PathBuf };
+
+/// flag giving the data directory of the server
+pub const DATA_DIR_FLAG: &str = "--data-dir";
+
+/// name of the directory of the saves, in the data directory
+pub const SAVES_DIR_NAME: &str = "saves";
+
+/// value of an environment variable, if it is set and not empty
+fn env_path(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var).filter(|v| !v.is_empty()).map(PathBuf::from)
+}
+
+/// Configuration directory of the game, if one can be found for the platform
+///
+/// * Linux and others: `$XDG_CONFIG_HOME/machiavelli`, or `$HOME/.config/machiavelli`
+/// * macOS: `$HOME/Library/Application Support/machiavelli`
+/// * Windows: `%APPDATA%\machiavelli`
+pub fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        env_path("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_path("HOME").map(|h| h.join("Library").join("Application Support"))
+    } else {
+        env_path("XDG_CONFIG_HOME").or_else(|| env_path("HOME").map(|h| h.join(".config")))
+    };
+    base.map(|b| b.join("machiavelli"))
+}
+
+/// Data directory of the game, if one can be found for the platform
+///
+/// * Linux and others: `$XDG_DATA_HOME/machiavelli`, or `$HOME/.local/share/machiavelli`
+/// * macOS: `$HOME/Library/Application Support/machiavelli`
+/// * Windows: `%LOCALAPPDATA%\machiavelli`, or `%APPDATA%\machiavelli`
+pub fn data_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        env_path("LOCALAPPDATA").or_else(|| env_path("APPDATA"))
+    } else if cfg!(target_os = "macos") {
+        env_path("HOME").map(|h| h.join("Library").join("Application Support"))
+    } else {
+        env_path("XDG_DATA_HOME").or_else(|| env_path("HOME").map(|h| h.join(".local").join("share")))
+    };
+    base.map(|b| b.join("machiavelli"))
+}
+
+/// Directory of the saves in the default data directory, if one can be found for the platform
+pub fn default_save_dir() -> Option<PathBuf> {
+    data_dir().map(|d| d.join(SAVES_DIR_NAME))
+}
+
+/// Directory of the saves in `data_dir`, or in the default data directory if it is `None`
+///
+/// The directory is created if needed. `None` is returned if it can not be found or created,
+/// in which case the saves stay in the current directory.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::paths::save_dir;
+///
+/// let data_dir = std::env::temp_dir().join(format!("machiavelli_data_doc_{}", std::process::id()));
+/// let saves = save_dir(Some(&data_dir)).unwrap();
+///
+/// assert_eq!(data_dir.join("saves"), saves);
+/// assert!(saves.is_dir());
+/// # std::fs::remove_dir_all(&data_dir).unwrap();
+/// ```
+pub fn save_dir(data_dir: Option<&Path>) -> Option<PathBuf> {
+    let dir = match data_dir {
+        Some(d) => d.join(SAVES_DIR_NAME),
+        None => default_save_dir()?
+    };
+    std::fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+/// Path of the file `name` in `dir`, unless `name` already has a directory or there is no `dir`
+///
+/// # Example
+///
+/// ```
+/// use std::path::Path;
+/// use machiavelli::paths::in_dir;
+///
+/// let dir = Path::new("saves");
+///
+/// assert_eq!(Path::new("saves").join("game.sav").to_string_lossy(), in_dir("game.sav", Some(dir)));
+/// assert_eq!("./game.sav", in_dir("./game.sav", Some(dir)));
+/// assert_eq!("game.sav", in_dir("game.sav", None));
+/// ```
+pub fn in_dir(name: &str, dir: Option<&Path>) -> String {
+    let has_dir = Path::new(name).parent().is_some_and(|p| !p.as_os_str().is_empty());
+    match dir {
+        Some(dir) if !has_dir && !name.is_empty() => dir.join(name).to_string_lossy().to_string(),
+        _ => name.to_string()
+    }
+}
+
+/// Path of the save `name` to load: in `dir` if it is there, otherwise in the current directory,
+/// where older versions wrote the saves
+pub fn find_save(name: &str, dir: Option<&Path>) -> String {
+    let path = in_dir(name, dir);
+    if !Path::new(&path).exists() && Path::new(name).exists() {
+        return name.to_string();
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn saves_are_looked_for_in_the_directory_first() {
+        let root = std::env::temp_dir().join(format!("machiavelli_paths_{}", std::process::id()));
+        let dir = save_dir(Some(&root)).unwrap();
+        let old_save = root.join("old.sav").to_string_lossy().to_string();
+        std::fs::write(&old_save, [1]).unwrap();
+
+        // a save with a directory is used as given, even if missing
+        assert_eq!(old_save, find_save(&old_save, Some(&dir)));
+        let missing = root.join("missing.sav").to_string_lossy().to_string();
+        assert_eq!(missing, find_save(&missing, Some(&dir)));
+
+        // a bare name is in the directory, unless only the current directory has it
+        let in_saves = dir.join("new.sav").to_string_lossy().to_string();
+        assert_eq!(in_saves, find_save("new.sav", Some(&dir)));
+        std::fs::write(&in_saves, [1]).unwrap();
+        assert_eq!(in_saves, find_save("new.sav", Some(&dir)));
+        assert_eq!("Cargo.toml", find_save("Cargo.toml", Some(&dir)));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
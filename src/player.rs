@@ -0,0 +1,167 @@
+//! Players sitting at the table
+//!
+//! Everything the game knows about a player is kept together: their hand, the connection to
+//! their client in a network game, whether they play themselves or a bot holds their seat, and
+//! their score. A player can be used as the connection to their client, so that messages can be
+//! sent to all the players at once.
+//!
+//! In a network game, each player gets a token when the game starts, which their client sends
+//! back to take their seat again after losing the connection. Players are told apart by their
+//! seat and their token rather than by their names, which need not be different.
+
+use std::io::{ Read, Write };
+use std::net::TcpStream;
+use std::time::{ Duration, Instant };
+use rand::Rng;
+use crate::hand::Hand;
+use crate::layout::{ LayoutSettings, Pages };
+use crate::connection::Connection;
+
+/// A player, with the connection `S` to their client (`()` in a local game)
+#[derive(Debug, Clone)]
+pub struct Player<S> {
+    /// seat at the table, from 0
+    pub id: usize,
+    pub hand: Hand,
+    pub connection: S,
+    /// false while a bot holds the seat
+    pub connected: bool,
+    /// kicked-out players can't come back
+    pub kicked: bool,
+    /// secret sent by the client to reconnect (empty in a local game)
+    pub token: String,
+    /// number of rounds won
+    pub score: u32,
+    /// how the player wants their hand to be shown, as last sent by their client
+    pub layout: LayoutSettings,
+    /// pages of the table and of the hand the player turned to
+    pub pages: Pages,
+    /// how the hand is sorted after drawing a card (0: unsorted, 1: by rank, 2: by suit)
+    pub sort_mode: u8,
+    /// message shown to the player after the situation at the start of the next turn
+    pub last_message: Option<String>,
+    /// when the time for the turn of the player runs out, if it is limited
+    pub turn_deadline: Option<Instant>,
+    /// when the turn of the player started, counting the time spent on it before the game was
+    /// saved, while it is their turn
+    pub turn_started: Option<Instant>
+}
+
+impl<S> Player<S> {
+
+    /// Player sitting at seat `id`
+    pub fn new(id: usize, hand: Hand, connection: S) -> Player<S> {
+        Player {
+            id,
+            hand,
+            connection,
+            connected: true,
+            kicked: false,
+            token: String::new(),
+            score: 0,
+            layout: LayoutSettings::new(),
+            pages: Pages::new(),
+            sort_mode: 0,
+            last_message: None,
+            turn_deadline: None,
+            turn_started: None
+        }
+    }
+
+    /// Players for the hands, in the order of the seats
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::hand::Hand;
+    /// use machiavelli::player::Player;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let hands = Hand::deal(&["Alice".to_string(), "Bob".to_string()], vec![Sequence::new(); 2]);
+    /// let players = Player::seat(hands, vec![(), ()]);
+    ///
+    /// assert_eq!(1, players[1].id);
+    /// assert_eq!("Bob", players[1].name());
+    /// ```
+    pub fn seat(hands: Vec<Hand>, connections: Vec<S>) -> Vec<Player<S>> {
+        hands.into_iter().zip(connections).enumerate()
+            .map(|(id, (hand, connection))| Player::new(id, hand, connection))
+            .collect()
+    }
+
+    /// Name of the player
+    pub fn name(&self) -> &str {
+        self.hand.owner()
+    }
+
+    /// Whether the player left and may take their seat back from the bot
+    pub fn can_reconnect(&self) -> bool {
+        !self.connected && !self.kicked
+    }
+}
+
+/// Random token for a player to reconnect, as 16 hexadecimal digits
+pub fn new_token<R: Rng + ?Sized>(rng: &mut R) -> String {
+    format!("{:016x}", rng.gen::<u64>())
+}
+
+impl<S> AsRef<Hand> for Player<S> {
+    fn as_ref(&self) -> &Hand {
+        &self.hand
+    }
+}
+
+impl<S: Read> Read for Player<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.connection.read(buf)
+    }
+}
+
+impl<S: Write> Write for Player<S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.connection.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.connection.flush()
+    }
+}
+
+impl<S: Connection> Connection for Player<S> {
+
+    fn wait_for_ack(&mut self) -> std::io::Result<()> {
+        self.connection.wait_for_ack()
+    }
+
+    fn close(&mut self) {
+        self.connection.close();
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.connection.set_timeout(timeout)
+    }
+
+    fn tcp_stream(&self) -> Option<&TcpStream> {
+        self.connection.tcp_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sequence_cards::Sequence;
+    use crate::connection::MemoryConnection;
+
+    #[test]
+    fn players_relay_their_connection() {
+        let (server, mut client) = MemoryConnection::pair();
+        let mut player = Player::new(0, Hand::new("Alice", Sequence::new()), server);
+        player.write_frames(b"hi").unwrap();
+        assert_eq!(b"hi".to_vec(), client.read_frames().unwrap());
+        player.connected = false;
+        assert!(player.can_reconnect());
+        player.kicked = true;
+        assert!(!player.can_reconnect());
+    }
+}
@@ -0,0 +1,197 @@
+//! Names of the players
+//!
+//! A name comes from a player, and ends up on the screens of all the others and in the save
+//! files. It is cleaned before being used: terminal escape sequences and control characters are
+//! removed, so that a name can not change how the screens look, and long names are cut without
+//! splitting a character from its accents.
+
+use std::fmt;
+use crate::sanitize::sanitize;
+
+/// maximum number of characters in a name, counting accents with the character they modify
+pub const MAX_NAME_LENGTH: usize = 24;
+
+/// maximum number of bytes in a name, so that its length fits in one byte of the save files
+pub const MAX_NAME_BYTES: usize = 255;
+
+/// A cleaned player name, which is never empty
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct PlayerName(String);
+
+impl PlayerName {
+
+    /// Clean a name given by a player
+    ///
+    /// Escape sequences, control characters, and the characters changing the direction of the
+    /// text are removed, the spaces around the name are trimmed, and the name is cut after
+    /// `MAX_NAME_LENGTH` characters or `MAX_NAME_BYTES` bytes. An error is returned if nothing is
+    /// left.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::player_name::PlayerName;
+    ///
+    /// assert_eq!("Lorenzo", PlayerName::new("  \x1b[2J\x1b[31mLorenzo\x1b[0m\n").unwrap().as_str());
+    ///
+    /// // accents stay with their letter
+    /// let long = "e\u{301}".repeat(30);
+    /// assert_eq!("e\u{301}".repeat(24), PlayerName::new(&long).unwrap().as_str());
+    ///
+    /// assert!(PlayerName::new(" \x1b[1m\t").is_err());
+    /// ```
+    pub fn new(name: &str) -> Result<PlayerName, NameError> {
+        let cleaned = sanitize(name);
+        let cleaned = truncate(cleaned.trim());
+        if cleaned.is_empty() {
+            return Err(NameError { message: "the name is empty".to_string() });
+        }
+        Ok(PlayerName(cleaned.to_string()))
+    }
+
+    /// Read a name from bytes, returning an error if they are not valid UTF-8
+    pub fn from_bytes(bytes: &[u8]) -> Result<PlayerName, NameError> {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => PlayerName::new(s),
+            Err(_) => Err(NameError { message: "the name is not valid UTF-8".to_string() })
+        }
+    }
+
+    /// The name as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for PlayerName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl AsRef<str> for PlayerName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<PlayerName> for String {
+    fn from(name: PlayerName) -> String {
+        name.0
+    }
+}
+
+/// Names shown for players who may have chosen the same name
+///
+/// The second player with a name gets ` (2)` after it, the third ` (3)`, and so on, skipping the
+/// names which are already taken. The names chosen by the players are not changed otherwise.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::player_name::display_names;
+///
+/// let names = ["Bob", "Alice", "Bob", "Bob (2)"].iter().map(|s| s.to_string()).collect::<Vec<_>>();
+///
+/// assert_eq!(vec!["Bob", "Alice", "Bob (3)", "Bob (2)"], display_names(&names));
+/// ```
+pub fn display_names(names: &[String]) -> Vec<String> {
+    let mut shown = Vec::<String>::new();
+    for (i, name) in names.iter().enumerate() {
+        let mut display = name.clone();
+        let mut n = 1;
+        while shown.contains(&display) || (display != *name && names[i+1..].contains(&display)) {
+            n += 1;
+            display = format!("{} ({})", name, n);
+        }
+        shown.push(display);
+    }
+    shown
+}
+
+/// error raised when a name can not be used
+#[derive(Debug, PartialEq)]
+pub struct NameError {
+    pub message: String
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid name: {}", self.message)
+    }
+}
+
+/// characters which are part of the previous one: combining accents, joiners, variation
+/// selectors, and skin tone modifiers
+fn extends_previous(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036f}' | '\u{1ab0}'..='\u{1aff}' | '\u{1dc0}'..='\u{1dff}'
+        | '\u{20d0}'..='\u{20ff}' | '\u{fe20}'..='\u{fe2f}' | '\u{fe00}'..='\u{fe0f}'
+        | '\u{200c}' | '\u{200d}' | '\u{1f3fb}'..='\u{1f3ff}')
+}
+
+/// cut a string after `MAX_NAME_LENGTH` characters or `MAX_NAME_BYTES` bytes, keeping the
+/// characters which extend the last one
+fn truncate(s: &str) -> &str {
+    let mut n_chars = 0;
+    let mut after_joiner = false;
+    for (i, c) in s.char_indices() {
+        let starts_new = !extends_previous(c) && !after_joiner;
+        if starts_new {
+            n_chars += 1;
+        }
+        if (starts_new && n_chars > MAX_NAME_LENGTH) || i + c.len_utf8() > MAX_NAME_BYTES {
+            return cut_before_char(s, i, starts_new);
+        }
+        after_joiner = c == '\u{200d}';
+    }
+    s
+}
+
+/// cut a string at byte `i`, or before the character containing it if `i` is inside one
+fn cut_before_char(s: &str, i: usize, at_boundary: bool) -> &str {
+    if at_boundary {
+        return &s[..i];
+    }
+
+    // go back to the start of the character being cut
+    let start = s[..i].char_indices().rev()
+        .find(|&(j, c)| !extends_previous(c) && !s[..j].ends_with('\u{200d}'))
+        .map_or(0, |(j, _)| j);
+    &s[..start]
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn forbidden_characters() {
+        assert_eq!("Bob", PlayerName::new("B\u{202e}o\u{7}b\u{9b}").unwrap().as_str());
+    }
+
+    #[test]
+    fn long_names() {
+        let name = "a".repeat(30);
+        assert_eq!(MAX_NAME_LENGTH, PlayerName::new(&name).unwrap().as_str().len());
+
+        // a family emoji is a single character
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        let name = family.repeat(MAX_NAME_LENGTH + 1);
+        let cut = PlayerName::new(&name).unwrap();
+        assert!(cut.as_str().len() <= MAX_NAME_BYTES);
+        assert!(cut.as_str().ends_with(family));
+
+        // many accents on a single letter
+        let name = format!("ab{}", "\u{301}".repeat(200));
+        let cut = PlayerName::new(&name).unwrap();
+        assert_eq!("a", cut.as_str());
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        assert!(PlayerName::from_bytes(&[0x66, 0xff]).is_err());
+        assert_eq!("Niccolò", PlayerName::from_bytes("Niccolò".as_bytes()).unwrap().to_string());
+    }
+}
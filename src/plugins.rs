@@ -0,0 +1,170 @@
+//! House rules added by other crates
+//!
+//! A `RulePlugin` adds its own checks to those of the game: it can refuse sequences laid on the
+//! table (`validate_meld`), forbid actions (`allowed_actions`), and follow the end of each turn
+//! (`on_turn_end`). Plugins are registered once, when the program starts, with `register`; the
+//! game then runs all of them, in the order they were registered, wherever it checks a sequence
+//! or lists, passes, or draws.
+//!
+//! ```
+//! use machiavelli::plugins::{ self, RulePlugin };
+//! use machiavelli::sequence_cards::Sequence;
+//!
+//! /// sequences of at most five cards
+//! struct ShortSequences;
+//!
+//! impl RulePlugin for ShortSequences {
+//!     fn name(&self) -> &str {
+//!         "short sequences"
+//!     }
+//!
+//!     fn validate_meld(&self, sequence: &Sequence) -> Result<(), String> {
+//!         if sequence.number_cards() > 5 {
+//!             Err("sequences can't have more than five cards".to_string())
+//!         } else {
+//!             Ok(())
+//!         }
+//!     }
+//! }
+//!
+//! plugins::register(ShortSequences);
+//! ```
+
+use std::sync::{ Arc, RwLock };
+use crate::sequence_cards::Sequence;
+use crate::turn::{ Action, TurnEnd };
+
+/// A house rule
+///
+/// All the hooks have a default which changes nothing, so that a plugin only implements those it
+/// needs.
+pub trait RulePlugin: Send + Sync {
+
+    /// Name of the rule, shown when it forbids an action
+    fn name(&self) -> &str;
+
+    /// Check a sequence laid on the table, once the rules of the game have found it valid, and
+    /// give the reason if it is refused
+    fn validate_meld(&self, _sequence: &Sequence) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Actions left to a player, among those the rules of the game allow, given their hand at the
+    /// start of the turn and now
+    fn allowed_actions(&self, _hand_start: &Sequence, _hand: &Sequence, actions: Vec<Action>) -> Vec<Action> {
+        actions
+    }
+
+    /// Called when a turn ends, with the hand of the player
+    fn on_turn_end(&self, _hand: &Sequence, _outcome: &TurnEnd) {}
+}
+
+/// Plugins run one after the other
+#[derive(Clone, Default)]
+pub struct Plugins {
+    plugins: Vec<Arc<dyn RulePlugin>>
+}
+
+impl Plugins {
+
+    /// No plugin
+    pub const fn new() -> Plugins {
+        Plugins { plugins: Vec::new() }
+    }
+
+    /// Add a plugin, run after the others
+    pub fn register<P: RulePlugin + 'static>(&mut self, plugin: P) {
+        self.plugins.push(Arc::new(plugin));
+    }
+
+    /// Names of the plugins, in the order they run
+    pub fn names(&self) -> Vec<String> {
+        self.plugins.iter().map(|p| p.name().to_string()).collect()
+    }
+
+    /// Check a sequence with all the plugins, and give the reason of the first one which refuses it
+    pub fn validate_meld(&self, sequence: &Sequence) -> Result<(), String> {
+        self.plugins.iter().try_for_each(|p| p.validate_meld(sequence))
+    }
+
+    /// Actions left by all the plugins
+    pub fn allowed_actions(&self, hand_start: &Sequence, hand: &Sequence, actions: Vec<Action>) -> Vec<Action> {
+        self.plugins.iter().fold(actions, |actions, p| p.allowed_actions(hand_start, hand, actions))
+    }
+
+    /// Why an action is forbidden, if a plugin forbids it
+    pub fn refusal(&self, hand_start: &Sequence, hand: &Sequence, action: &Action) -> Option<String> {
+        self.plugins.iter()
+            .find(|p| !p.allowed_actions(hand_start, hand, vec![action.clone()]).contains(action))
+            .map(|p| format!("This is not allowed by the {} rule", p.name()))
+    }
+
+    /// Tell all the plugins that a turn ended
+    pub fn on_turn_end(&self, hand: &Sequence, outcome: &TurnEnd) {
+        for plugin in &self.plugins {
+            plugin.on_turn_end(hand, outcome);
+        }
+    }
+}
+
+impl std::fmt::Debug for Plugins {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+/// plugins of the program
+static REGISTERED: RwLock<Plugins> = RwLock::new(Plugins::new());
+
+/// Add a plugin to those the game runs
+pub fn register<P: RulePlugin + 'static>(plugin: P) {
+    REGISTERED.write().unwrap_or_else(|e| e.into_inner()).register(plugin);
+}
+
+/// Plugins registered so far
+pub fn registered() -> Plugins {
+    REGISTERED.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sequence_cards::Card::*;
+    use crate::sequence_cards::Suit::*;
+
+    /// no joker on the table, and no passing before three cards are played
+    struct HouseRules;
+
+    impl RulePlugin for HouseRules {
+        fn name(&self) -> &str {
+            "house"
+        }
+
+        fn validate_meld(&self, sequence: &Sequence) -> Result<(), String> {
+            if sequence.contains_joker() { Err("no jokers".to_string()) } else { Ok(()) }
+        }
+
+        fn allowed_actions(&self, hand_start: &Sequence, hand: &Sequence, actions: Vec<Action>) -> Vec<Action> {
+            let n_played = hand_start.number_cards().saturating_sub(hand.number_cards());
+            actions.into_iter().filter(|a| *a != Action::Pass || n_played >= 3).collect()
+        }
+    }
+
+    #[test]
+    fn all_the_plugins_run() {
+        let mut plugins = Plugins::new();
+        plugins.register(HouseRules);
+        assert_eq!(vec!["house".to_string()], plugins.names());
+
+        let run = Sequence::from_cards(&[RegularCard(Heart, 2), Joker, RegularCard(Heart, 4)]);
+        assert_eq!(Err("no jokers".to_string()), plugins.validate_meld(&run));
+
+        let hand_start = Sequence::from_cards(&[RegularCard(Heart, 2), RegularCard(Heart, 3), RegularCard(Heart, 4)]);
+        let hand = Sequence::from_cards(&[RegularCard(Heart, 2)]);
+        assert_eq!(vec![Action::GiveUp], plugins.allowed_actions(&hand_start, &hand, vec![Action::Pass, Action::GiveUp]));
+        assert_eq!(Some("This is not allowed by the house rule".to_string()),
+                   plugins.refusal(&hand_start, &hand, &Action::Pass));
+        assert_eq!(None, plugins.refusal(&hand_start, &Sequence::new(), &Action::Pass));
+    }
+}
@@ -0,0 +1,199 @@
+//! Interactive prompts
+//!
+//! Each question shows its default value, which is used if the answer is empty, and is asked
+//! again until the answer is valid. Answering `<` goes back to the previous question.
+
+use std::fmt::Display;
+use std::io::BufRead;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use crate::InvalidInputError;
+
+/// answer going back to the previous question
+pub const BACK: &str = "<";
+
+/// Answer to a question
+#[derive(Debug, PartialEq, Clone)]
+pub enum Answer<T> {
+    Value(T),
+    /// the user wants to change their answer to the previous question
+    Back
+}
+
+impl<T> Answer<T> {
+
+    /// Apply a function to the value, if any
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Answer<U> {
+        match self {
+            Answer::Value(x) => Answer::Value(f(x)),
+            Answer::Back => Answer::Back
+        }
+    }
+}
+
+/// Input of the prompts: the terminal, with line editing and history if the `line_editing`
+/// feature is enabled
+pub fn terminal_input() -> Box<dyn BufRead> {
+    #[cfg(feature = "line_editing")]
+    return Box::new(crate::line_editor::EditedStdin::new());
+    #[cfg(not(feature = "line_editing"))]
+    return Box::new(std::io::stdin().lock());
+}
+
+/// Asks questions and reads the answers from an input
+pub struct Prompter<R: BufRead> {
+    input: R
+}
+
+impl<R: BufRead> Prompter<R> {
+
+    /// Create a prompter reading from `input` (for instance `stdin().lock()`)
+    pub fn new(input: R) -> Prompter<R> {
+        Prompter { input }
+    }
+
+    /// Ask for a number in a range
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::prompt::*;
+    ///
+    /// // the first two answers are invalid, and the last one is empty
+    /// let mut prompter = Prompter::new("300\nfour\n\n".as_bytes());
+    ///
+    /// assert_eq!(Answer::Value(2), prompter.number("Number of decks", 1..=255, Some(2)).unwrap());
+    /// ```
+    pub fn number<T>(&mut self, label: &str, range: RangeInclusive<T>, default: Option<T>)
+        -> Result<Answer<T>, InvalidInputError>
+        where T: FromStr + PartialOrd + Display + Copy
+    {
+        let question = format!("{} ({} to {})", label, range.start(), range.end());
+        self.ask(&question, default, |s| match s.parse::<T>() {
+            Ok(n) if range.contains(&n) => Ok(n),
+            _ => Err(format!("Please enter an integer between {} and {}", range.start(), range.end()))
+        })
+    }
+
+    /// Ask for a number between 0 and 255
+    pub fn u8(&mut self, label: &str, range: RangeInclusive<u8>, default: Option<u8>)
+        -> Result<Answer<u8>, InvalidInputError>
+    {
+        self.number(label, range, default)
+    }
+
+    /// Ask for a number between 0 and 65535
+    pub fn u16(&mut self, label: &str, range: RangeInclusive<u16>, default: Option<u16>)
+        -> Result<Answer<u16>, InvalidInputError>
+    {
+        self.number(label, range, default)
+    }
+
+    /// Ask a yes/no question
+    pub fn yes_no(&mut self, label: &str, default: bool) -> Result<Answer<bool>, InvalidInputError> {
+        let question = format!("{} (y/n)", label);
+        let default = if default { "y" } else { "n" };
+        self.ask(&question, Some(default), |s| match s {
+            "y" | "yes" => Ok("y"),
+            "n" | "no" => Ok("n"),
+            _ => Err("Please answer y or n".to_string())
+        }).map(|answer| answer.map(|s| s == "y"))
+    }
+
+    /// Ask to confirm an action which can not be undone
+    ///
+    /// The action is not confirmed unless the answer is yes; going back or a closed input also
+    /// count as no.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::prompt::Prompter;
+    ///
+    /// let mut prompter = Prompter::new("maybe\ny\n\n".as_bytes());
+    ///
+    /// assert!(prompter.confirm("Save and quit?"));
+    /// assert!(!prompter.confirm("Save and quit?"));
+    /// assert!(!prompter.confirm("Save and quit?"));
+    /// ```
+    pub fn confirm(&mut self, question: &str) -> bool {
+        matches!(self.yes_no(question, false), Ok(Answer::Value(true)))
+    }
+
+    /// Ask to choose one of several options
+    pub fn choice(&mut self, label: &str, options: &[&str], default: &str)
+        -> Result<Answer<String>, InvalidInputError>
+    {
+        let question = format!("{} ({})", label, options.join(", "));
+        self.ask(&question, Some(default.to_string()), |s| if options.contains(&s) {
+            Ok(s.to_string())
+        } else {
+            Err(format!("Please answer one of: {}", options.join(", ")))
+        })
+    }
+
+    /// Ask for a non-empty text
+    pub fn text(&mut self, label: &str, default: Option<&str>) -> Result<Answer<String>, InvalidInputError> {
+        self.ask(label, default.map(String::from), |s| Ok(s.to_string()))
+    }
+
+    /// ask a question until the answer can be parsed
+    fn ask<T, F>(&mut self, question: &str, default: Option<T>, parse: F)
+        -> Result<Answer<T>, InvalidInputError>
+        where T: Display + Clone, F: Fn(&str) -> Result<T, String>
+    {
+        loop {
+            match &default {
+                Some(d) => println!("{} [{}]:", question, d),
+                None => println!("{}:", question)
+            };
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                // the input is closed, so asking again would loop forever
+                return Err(InvalidInputError {});
+            }
+            let answer = line.trim();
+            if answer == BACK {
+                return Ok(Answer::Back);
+            }
+            if answer.is_empty() {
+                match &default {
+                    Some(d) => return Ok(Answer::Value(d.clone())),
+                    None => {
+                        println!("Please enter a value");
+                        continue;
+                    }
+                }
+            }
+            match parse(answer) {
+                Ok(value) => return Ok(Answer::Value(value)),
+                Err(message) => println!("{}", message)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn back_and_reprompt() {
+        let mut prompter = Prompter::new("<\nmaybe\nyes\n".as_bytes());
+        assert_eq!(Answer::Back, prompter.u8("Number of players", 1..=255, None).unwrap());
+        assert_eq!(Answer::Value(true), prompter.yes_no("Custom rule", false).unwrap());
+    }
+
+    #[test]
+    fn text_needs_a_value() {
+        let mut prompter = Prompter::new("\n  game  \n".as_bytes());
+        assert_eq!(Answer::Value("game".to_string()), prompter.text("Save file", None).unwrap());
+    }
+
+    #[test]
+    fn closed_input() {
+        let mut prompter = Prompter::new("".as_bytes());
+        assert!(prompter.u16("Number of cards", 1..=100, Some(13)).is_err());
+    }
+}
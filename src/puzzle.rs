@@ -0,0 +1,340 @@
+//! Daily puzzles: lay down all your cards in a single turn
+//!
+//! A puzzle is a table and a hand built from a seeded deck, such that all the cards of the hand
+//! can be played in one turn, but only by rearranging the table. The seed of the daily puzzle is
+//! derived from the date, so that everyone gets the same puzzle on the same day.
+
+use std::time::{ SystemTime, UNIX_EPOCH };
+use rand::{ Rng, SeedableRng };
+use rand::rngs::StdRng;
+use crate::sequence_cards::*;
+use crate::table::Table;
+use crate::solver::solve;
+use super::{ clear_terminal, reset_style, get_input, play_sequence, take_sequence };
+
+/// number of sequences initially on the table
+const N_SEQUENCES_TABLE: usize = 4;
+
+/// number of sequences from which the hand is built
+const N_SEQUENCES_HAND: usize = 2;
+
+/// maximum number of attempts to build a sequence from a random card
+const MAX_ATTEMPTS: usize = 1000;
+
+/// A puzzle: a table and a hand to lay down in a single turn
+#[derive(Debug, PartialEq, Clone)]
+pub struct Puzzle {
+    pub seed: u64,
+    pub table: Table,
+    pub hand: Sequence
+}
+
+impl Puzzle {
+
+    /// Generate the puzzle corresponding to a seed
+    ///
+    /// The same seed always gives the same puzzle.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::puzzle::Puzzle;
+    ///
+    /// let puzzle = Puzzle::from_seed(42);
+    ///
+    /// assert_eq!(puzzle, Puzzle::from_seed(42));
+    /// assert!(puzzle.is_solvable());
+    /// ```
+    pub fn from_seed(seed: u64) -> Puzzle {
+        let mut rng = StdRng::seed_from_u64(seed);
+        loop {
+            if let Some(puzzle) = try_build(seed, &mut rng) {
+                return puzzle;
+            }
+        }
+    }
+
+    /// Generate the puzzle of the day for a given date
+    ///
+    /// The seed is the date read as a number, `YYYYMMDD`; for years with more than four digits,
+    /// it wraps around instead of overflowing.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::puzzle::Puzzle;
+    ///
+    /// let puzzle = Puzzle::daily(2021, 8, 14);
+    ///
+    /// assert_eq!(20210814, puzzle.seed);
+    /// ```
+    pub fn daily(year: i64, month: u32, day: u32) -> Puzzle {
+        let seed = (year as u64).wrapping_mul(10000).wrapping_add((month as u64) * 100 + day as u64);
+        Puzzle::from_seed(seed)
+    }
+
+    /// Check with the solver that all the cards of the hand can be laid down
+    pub fn is_solvable(&self) -> bool {
+        solve(&self.hand, &self.table).is_some()
+    }
+}
+
+/// try to build a puzzle; return `None` if the cards drawn did not lead to an interesting one
+fn try_build<R: Rng + ?Sized>(seed: u64, rng: &mut R) -> Option<Puzzle> {
+
+    let mut deck = Sequence::multi_deck(1, 0, rng).to_vec();
+    let mut sequences = Vec::<Vec<Card>>::new();
+    let mut n_attempts = 0;
+    while sequences.len() < N_SEQUENCES_TABLE + N_SEQUENCES_HAND {
+        n_attempts += 1;
+        if n_attempts > MAX_ATTEMPTS {
+            return None;
+        }
+        if let Some(seq) = random_sequence(&mut deck, rng) {
+            sequences.push(seq);
+        }
+    }
+
+    // the hand starts with the last sequences
+    let mut hand = Vec::<Card>::new();
+    for seq in sequences.drain(N_SEQUENCES_TABLE..) {
+        hand.extend(seq);
+    }
+
+    // move one card from each long enough sequence of the table to the hand
+    for seq in sequences.iter_mut() {
+        if seq.len() > 3 {
+            let i = if is_set(seq) { rng.gen_range(0..seq.len()) } else { seq.len() - 1 };
+            hand.push(seq.remove(i));
+        }
+    }
+
+    let mut table = Table::new();
+    for seq in sequences {
+        table.add(Sequence::from_cards(&seq));
+    }
+    let mut hand = Sequence::from_cards(&hand);
+    hand.shuffle(rng);
+
+    // reject puzzles which can be solved without touching the table, or not at all
+    let puzzle = Puzzle { seed, table, hand };
+    if crate::solver::arrange(&puzzle.hand).is_some() || !puzzle.is_solvable() {
+        return None;
+    }
+    Some(puzzle)
+}
+
+fn is_set(seq: &[Card]) -> bool {
+    match (&seq[0], &seq[1]) {
+        (RegularCard(_, v1), RegularCard(_, v2)) => v1 == v2,
+        _ => false
+    }
+}
+
+/// take a random run or set from the deck, if one can be built from a random card
+fn random_sequence<R: Rng + ?Sized>(deck: &mut Vec<Card>, rng: &mut R) -> Option<Vec<Card>> {
+
+    let (suit, value) = match deck[rng.gen_range(0..deck.len())] {
+        RegularCard(suit, value) => (suit, value),
+        Joker => return None
+    };
+    let length = rng.gen_range(3..=5);
+
+    let cards: Vec<Card> = if rng.gen_bool(0.5) {
+        (0..length).map(|i| RegularCard(suit, value + i as u8)).collect()
+    } else {
+        [Heart, Diamond, Club, Spade].iter()
+            .filter(|&&s| s != suit)
+            .take(length - 1)
+            .map(|&s| RegularCard(s, value))
+            .chain(std::iter::once(RegularCard(suit, value)))
+            .collect()
+    };
+
+    if cards.iter().any(|card| !deck.contains(card)) {
+        return None;
+    }
+    for card in &cards {
+        let i = deck.iter().position(|c| c == card).unwrap();
+        deck.remove(i);
+    }
+    Some(cards)
+}
+
+/// Today's date as (year, month, day), in UTC
+pub fn today() -> (i64, u32, u32) {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    civil_from_days((seconds / 86400) as i64)
+}
+
+/// convert a number of days since 1970-01-01 to a date
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// number of days in a month of the Gregorian calendar
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    }
+}
+
+/// Parse a date written as `YYYY-MM-DD`, if the year is between 1 and 9999 and the day is in
+/// the month
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::puzzle::parse_date;
+///
+/// assert_eq!(Some((2021, 8, 14)), parse_date("2021-08-14"));
+/// assert_eq!(None, parse_date("14/08/2021"));
+/// assert_eq!(None, parse_date("2021-02-31"));
+/// assert_eq!(None, parse_date("99999999999999-01-01"));
+/// ```
+pub fn parse_date(s: &str) -> Option<(i64, u32, u32)> {
+    let parts: Vec<&str> = s.trim().split('-').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let year = parts[0].parse::<i64>().ok()?;
+    let month = parts[1].parse::<u32>().ok()?;
+    let day = parts[2].parse::<u32>().ok()?;
+    if !(1..=9999).contains(&year) || month == 0 || month > 12 || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    Some((year, month, day))
+}
+
+fn puzzle_instructions() -> String {
+    format!("{}\n{}\n{}\n{}\n{}\n",
+        "p: Play a sequence",
+        "t: Take from the table",
+        "r, s: Sort cards by rank or suit",
+        "g: Start again",
+        "q: Quit"
+        )
+}
+
+/// Play a puzzle in the terminal
+///
+/// Return the number of moves (sequences played or taken from the table) used to solve it, or
+/// `None` if the player gave up.
+pub fn play_puzzle(puzzle: &Puzzle) -> Option<usize> {
+
+    let mut table = puzzle.table.clone();
+    let mut hand = puzzle.hand.clone();
+    let mut n_moves: usize = 0;
+    let mut message = String::new();
+
+    loop {
+
+        clear_terminal();
+        println!("\x1b[1mPuzzle {}: lay down all your cards!", puzzle.seed);
+        reset_style();
+        println!("\n{} moves so far", n_moves);
+        println!("Table: \n{}", table);
+        println!("Your hand:\n{}", hand);
+        reset_style();
+        println!("{}", &puzzle_instructions());
+
+        if !message.is_empty() {
+            println!("\n{}", message);
+            message.clear()
+        }
+
+        match get_input().unwrap_or_else(|_| {"".to_string()}).trim() {
+            "p" => match play_sequence(&mut hand, &mut table, false) {
+                Ok(_) => {
+                    n_moves += 1;
+                    if hand.number_cards() == 0 {
+                        return Some(n_moves);
+                    }
+                },
+                Err(m) => message = m
+            },
+            "t" => match take_sequence(&mut table, &mut hand, false) {
+                Ok(_) => n_moves += 1,
+                Err(m) => message = m
+            },
+            "r" => hand.sort_by_rank(),
+            "s" => hand.sort_by_suit(),
+            "g" => {
+                table = puzzle.table.clone();
+                hand = puzzle.hand.clone();
+                n_moves = 0;
+            },
+            "q" => return None,
+            _ => ()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn civil_from_days_1() {
+        assert_eq!((1970, 1, 1), civil_from_days(0));
+        assert_eq!((2000, 2, 29), civil_from_days(11016));
+        assert_eq!((2021, 8, 14), civil_from_days(18853));
+    }
+
+    #[test]
+    fn days_past_the_end_of_the_month_are_rejected() {
+        assert_eq!(Some((2021, 1, 31)), parse_date("2021-01-31"));
+        assert_eq!(None, parse_date("2021-04-31"));
+        assert_eq!(None, parse_date("2021-02-29"));
+        assert_eq!(Some((2024, 2, 29)), parse_date("2024-02-29"));
+        assert_eq!(Some((2000, 2, 29)), parse_date("2000-02-29"));
+        assert_eq!(None, parse_date("1900-02-29"));
+        assert_eq!(None, parse_date("2021-13-01"));
+        assert_eq!(None, parse_date("2021-12-00"));
+    }
+
+    #[test]
+    fn years_have_four_digits_at_most() {
+        assert_eq!(Some((1, 1, 1)), parse_date("0001-01-01"));
+        assert_eq!(Some((9999, 12, 31)), parse_date("9999-12-31"));
+        assert_eq!(None, parse_date("0000-01-01"));
+        assert_eq!(None, parse_date("10000-01-01"));
+        assert_eq!(None, parse_date("-2021-01-01"));
+        assert_eq!(None, parse_date("9223372036854775807-01-01"));
+    }
+
+    #[test]
+    fn daily_seeds_of_large_years_do_not_overflow() {
+        assert_eq!(99991231, Puzzle::daily(9999, 12, 31).seed);
+        let far = Puzzle::daily(i64::MAX, 12, 31);
+        assert_eq!(far.seed, (i64::MAX as u64).wrapping_mul(10000).wrapping_add(1231));
+    }
+
+    #[test]
+    fn puzzles_are_solvable() {
+        for seed in 0..20 {
+            let puzzle = Puzzle::from_seed(seed);
+            assert!(puzzle.is_solvable());
+            assert!(crate::solver::arrange(&puzzle.hand).is_none());
+        }
+    }
+
+    #[test]
+    fn daily_puzzle_is_deterministic() {
+        assert_eq!(Puzzle::daily(2021, 8, 14), Puzzle::daily(2021, 8, 14));
+        assert_ne!(Puzzle::daily(2021, 8, 14), Puzzle::daily(2021, 8, 15));
+    }
+}
@@ -0,0 +1,77 @@
+//! Reloading the settings of a running server
+//!
+//! Some of the settings of `Config/server.dat` can change without stopping the games: the time a
+//! player has for their turn, the most players a new game of the JSON-RPC lobby can have, and the
+//! log level (see `lib_server::ServerConfig::reload`). The server reads them again when it gets
+//! SIGHUP (on Unix), or when the administrator sends `POST /reload` to the admin endpoint. A game
+//! takes the new settings at the start of its next turn; the other settings are only read when
+//! the server starts.
+
+use std::sync::atomic::{ AtomicBool, Ordering };
+
+/// raised by the handler of SIGHUP
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Ask for the settings to be read again
+pub fn request() {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Check if the settings should be read again, and forget the request
+pub fn take_request() -> bool {
+    REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Handle SIGHUP by asking for the settings to be read again, instead of stopping the server
+///
+/// This must be called after `shutdown::set_handler`, which would otherwise handle SIGHUP as
+/// SIGTERM. Return whether the handler could be set; it never is outside Unix.
+pub fn set_handler() -> bool {
+    hangup::set_handler()
+}
+
+#[cfg(unix)]
+mod hangup {
+
+    use std::os::raw::c_int;
+    use std::sync::atomic::Ordering;
+
+    const SIGHUP: c_int = 1;
+
+    /// value returned by `signal` if the handler could not be set
+    const SIG_ERR: usize = usize::MAX;
+
+    extern "C" {
+        fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    }
+
+    extern "C" fn on_hangup(_signum: c_int) {
+        super::REQUESTED.store(true, Ordering::SeqCst);
+    }
+
+    pub fn set_handler() -> bool {
+        // SAFETY: the handler only stores to an atomic, which can be done in a signal handler
+        unsafe { signal(SIGHUP, on_hangup) != SIG_ERR }
+    }
+}
+
+#[cfg(not(unix))]
+mod hangup {
+
+    pub fn set_handler() -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn requests_are_taken_once() {
+        request();
+        assert!(take_request());
+        assert!(!take_request());
+    }
+}
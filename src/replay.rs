@@ -0,0 +1,252 @@
+//! Record of a whole game, and its transcript
+//!
+//! The events of each turn are copied from the event log after the turn, so that nothing is lost
+//! when the log forgets its oldest events. At the end of the game, the record can be written as a
+//! Markdown document listing every turn and the final scores of each round.
+
+use std::fs::File;
+use std::io::Write;
+use crate::sequence_cards::*;
+use crate::events::{ Event, EventLog };
+use crate::hand::Hand;
+
+/// flag giving the file the transcript is written to at the end of the game
+pub const TRANSCRIPT_FLAG: &str = "--transcript";
+
+/// A turn, with the events which happened during it
+#[derive(Debug, PartialEq, Clone)]
+pub struct Turn {
+    /// index of the player whose turn it was
+    pub player: usize,
+    pub events: Vec<Event>
+}
+
+/// A round, with the hands left at its end
+#[derive(Debug, PartialEq, Clone)]
+pub struct Round {
+    /// events before the first turn, such as mulligans
+    pub deal: Vec<Event>,
+    pub turns: Vec<Turn>,
+    /// empty until the round is over
+    pub final_hands: Vec<Sequence>
+}
+
+/// Record of a game
+#[derive(Debug, PartialEq, Clone)]
+pub struct GameRecord {
+    pub player_names: Vec<String>,
+    pub rounds: Vec<Round>,
+    /// total of the event log when it was last copied
+    n_seen: usize
+}
+
+impl GameRecord {
+
+    /// Create an empty record, with its first round
+    pub fn new(player_names: &[String]) -> GameRecord {
+        GameRecord {
+            player_names: player_names.to_vec(),
+            rounds: vec![Round { deal: Vec::new(), turns: Vec::new(), final_hands: Vec::new() }],
+            n_seen: 0
+        }
+    }
+
+    /// Start a new round, unless the current one has not started yet
+    pub fn start_round(&mut self) {
+        match self.rounds.last() {
+            Some(round) if round.turns.is_empty() && round.deal.is_empty() => (),
+            _ => self.rounds.push(Round { deal: Vec::new(), turns: Vec::new(), final_hands: Vec::new() })
+        }
+    }
+
+    /// Start a turn of a player
+    pub fn start_turn(&mut self, player: usize) {
+        if let Some(round) = self.rounds.last_mut() {
+            round.turns.push(Turn { player, events: Vec::new() });
+        }
+    }
+
+    /// Copy the events pushed to the log since the last call into the current turn
+    ///
+    /// Events happening before the first turn of a round go to its deal.
+    pub fn sync(&mut self, log: &EventLog) {
+        let new_events: Vec<Event> = log.since(self.n_seen).into_iter().cloned().collect();
+        self.n_seen = log.total();
+        if let Some(round) = self.rounds.last_mut() {
+            match round.turns.last_mut() {
+                Some(turn) => turn.events.extend(new_events),
+                None => round.deal.extend(new_events)
+            }
+        }
+    }
+
+    /// Forget the turns of the current round after the first `n_turns`, when the game is rewound;
+    /// return the number of turns forgotten
+    pub fn rewind(&mut self, n_turns: usize) -> usize {
+        match self.rounds.last_mut() {
+            Some(round) if round.turns.len() > n_turns => round.turns.drain(n_turns..).count(),
+            _ => 0
+        }
+    }
+
+    /// Record the hands left at the end of the current round
+    pub fn end_round<H: AsRef<Hand>>(&mut self, hands: &[H]) {
+        if let Some(round) = self.rounds.last_mut() {
+            round.final_hands = hands.iter().map(|h| h.as_ref().cards().clone()).collect();
+        }
+    }
+}
+
+/// Write a record as a Markdown transcript
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::events::*;
+/// use machiavelli::replay::*;
+/// use machiavelli::hand::Hand;
+///
+/// let names = vec!["Alice".to_string(), "Bob".to_string()];
+/// let mut record = GameRecord::new(&names);
+/// let mut log = EventLog::new();
+///
+/// record.start_turn(0);
+/// log.push(Event::DrewCard("Alice".to_string()));
+/// record.sync(&log);
+/// record.end_round(&Hand::deal(&names, vec![Sequence::from_cards(&[Joker]), Sequence::new()]));
+///
+/// let transcript = to_transcript(&record, &PointRules::new(), "it");
+///
+/// assert!(transcript.contains("### Turn 1: Alice\n\n- Alice drew a card\n"));
+/// assert!(transcript.contains("| Alice | 1 | 25 | Jolly |\n"));
+/// ```
+///
+/// The cards left in the hands are named in the language of `locale`.
+pub fn to_transcript(record: &GameRecord, rules: &PointRules, locale: &str) -> String {
+    let name = |i: usize| record.player_names.get(i).cloned().unwrap_or_else(|| format!("Player {}", i + 1));
+    let mut res = format!("# Machiavelli game\n\nPlayers: {}\n", record.player_names.join(", "));
+    for (i_round, round) in record.rounds.iter().enumerate() {
+        res += &format!("\n## Round {}\n", i_round + 1);
+        if !round.deal.is_empty() {
+            res += "\n### Deal\n\n";
+            for event in &round.deal {
+                res += &format!("- {}\n", plain(&event.to_string()).trim_end());
+            }
+        }
+        for (i_turn, turn) in round.turns.iter().enumerate() {
+            res += &format!("\n### Turn {}: {}\n\n", i_turn + 1, name(turn.player));
+            if turn.events.is_empty() {
+                res += "- (nothing happened)\n";
+            }
+            for event in &turn.events {
+                res += &format!("- {}\n", plain(&event.to_string()).trim_end());
+            }
+        }
+        if !round.final_hands.is_empty() {
+            res += "\n### Final scores\n\n| Player | Cards | Points | Hand |\n|---|---|---|---|\n";
+            for (i, hand) in round.final_hands.iter().enumerate() {
+                let cards: Vec<String> = hand.to_vec().iter().map(|c| c.long_name(locale)).collect();
+                res += &format!("| {} | {} | {} | {} |\n", name(i), hand.number_cards(), hand.points(rules), 
+                                cards.join(", "));
+            }
+        }
+    }
+    res
+}
+
+/// Write the transcript of a record to a file
+pub fn save_transcript(record: &GameRecord, rules: &PointRules, locale: &str, fname: &str) 
+    -> std::io::Result<()> 
+{
+    File::create(fname)?.write_all(to_transcript(record, rules, locale).as_bytes())
+}
+
+/// Name of the transcript file given with `TRANSCRIPT_FLAG`, and the arguments without the flag
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::replay::transcript_file;
+///
+/// let args: Vec<String> = vec!["machiavelli", "--transcript", "game.md", "puzzle"]
+///     .into_iter().map(String::from).collect();
+///
+/// assert_eq!((Some("game.md".to_string()), vec!["machiavelli".to_string(), "puzzle".to_string()]),
+///            transcript_file(args));
+/// ```
+pub fn transcript_file(args: Vec<String>) -> (Option<String>, Vec<String>) {
+    crate::take_flag_value(args, TRANSCRIPT_FLAG)
+}
+
+/// remove the terminal escape sequences from a string
+fn plain(s: &str) -> String {
+    let mut res = String::new();
+    let mut in_escape = false;
+    for c in s.chars() {
+        if c == '\x1b' {
+            in_escape = true;
+        } else if in_escape {
+            in_escape = !c.is_ascii_alphabetic();
+        } else {
+            res.push(c);
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn events_go_to_the_current_turn() {
+        let names = vec!["Alice".to_string(), "Bob".to_string()];
+        let mut record = GameRecord::new(&names);
+        let mut log = EventLog::new();
+        log.push(Event::NewDeck);
+        record.sync(&log);
+        record.start_turn(1);
+        log.push(Event::PlayedSequence("Bob".to_string(), Sequence::from_cards(&[Joker])));
+        log.push(Event::EndedTurn("Bob".to_string()));
+        record.sync(&log);
+        record.start_round();
+        record.start_round();
+        assert_eq!(2, record.rounds.len());
+        assert_eq!(2, record.rounds[0].turns[0].events.len());
+        assert_eq!(vec![Event::NewDeck], record.rounds[0].deal);
+        let transcript = to_transcript(&record, &PointRules::new(), "en");
+        assert!(transcript.contains("### Deal\n\n- A new deck was shuffled\n"));
+        assert!(transcript.contains("- Bob played #\n"));
+        assert!(!transcript.contains('\x1b'));
+        assert!(transcript.contains("## Round 2\n"));
+    }
+
+    #[test]
+    fn rewound_turns_are_forgotten() {
+        let names = vec!["Alice".to_string(), "Bob".to_string()];
+        let mut record = GameRecord::new(&names);
+        let mut log = EventLog::new();
+        for i in 0..4 {
+            record.start_turn(i % 2);
+            log.push(Event::DrewCard(names[i % 2].clone()));
+            record.sync(&log);
+        }
+        assert_eq!(2, record.rewind(2));
+        assert_eq!(0, record.rewind(3));
+        log.push(Event::Rewound("Alice".to_string(), 3));
+        record.sync(&log);
+        record.start_turn(0);
+        let transcript = to_transcript(&record, &PointRules::new(), "en");
+        assert!(transcript.contains("### Turn 2: Bob\n\n- Bob drew a card\n- Alice rewound the game to the start of turn 3\n"));
+        assert!(transcript.contains("### Turn 3: Alice\n\n- (nothing happened)\n"));
+        assert!(!transcript.contains("Turn 4"));
+    }
+
+    #[test]
+    fn missing_file_name() {
+        let args = vec!["server".to_string(), TRANSCRIPT_FLAG.to_string()];
+        assert_eq!((None, vec!["server".to_string()]), transcript_file(args));
+    }
+}
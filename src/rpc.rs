@@ -0,0 +1,541 @@
+//! JSON-RPC interface to games, for clients not written in Rust
+//!
+//! With `server --rpc <address>` (and the `json_rpc` feature), the server hosts a lobby of
+//! headless games (see the `script` module) instead of a terminal game. Clients connect over TCP
+//! and send JSON-RPC 2.0 requests, one per line; each answer is also on its own line. The methods
+//! are:
+//!
+//! * `lobby.create`, with the keys of the config file as optional parameters (`preset`,
+//!   `n_players`, `n_decks`, `empty_deck`, ...) and an optional `seed`: create a game, and return its id as
+//!   `{"game": id}`; a config with odd parameters (see `Config::warnings`) is refused unless
+//!   `confirm` is `true`, and so is a game with more players than the server allows
+//! * `lobby.list`: the games, with the players who joined them, whether they are over, and the
+//!   description of their config
+//! * `lobby.join`, with `game` and `name`: take the next seat of a game, and return it with the
+//!   token needed to play as `{"seat": seat, "token": token}`; the cards are dealt once all the
+//!   seats are taken
+//! * `game.state`, with `game` and `token`: the game as the player sees it, as `{"seat": seat,
+//!   "state": snapshot, "taken": cards, "over": bool, "winner": seat}`, where the snapshot is
+//!   that of the `snapshot` module and `taken` lists the cards taken from the table and not
+//!   played yet during the player's turn
+//! * `game.play`, with `game`, `token`, and `action`: play an action of the player's turn,
+//!   written as in a script (`p 1 2 3`, `t 2`, `a 1 4`, `e`, or `g`)
+//!
+//! Errors use the codes of the JSON-RPC specification, and -32000 for actions refused by the
+//! game, with the reason as message.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::sync::{ Arc, Mutex };
+use crate::admin::json_string;
+use crate::parse_config;
+use crate::player::new_token;
+use crate::player_name::PlayerName;
+use crate::script::{ Outcome, ScriptedGame };
+use crate::snapshot::{ card_code, snapshot, Visibility };
+use crate::toml::quote;
+
+/// flag giving the address the JSON-RPC interface listens on (for instance `127.0.0.1:9000`)
+pub const RPC_FLAG: &str = "--rpc";
+
+/// keys of the config which can be given to `lobby.create`
+const CONFIG_KEYS: [&str; 9] = ["preset", "n_decks", "n_jokers", "n_cards_to_start", "custom_rule_jokers",
+                                "n_players", "empty_deck", "joker_penalty", "mulligan"];
+
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const GAME_ERROR: i32 = -32000;
+
+/// JSON value, as read from a request
+#[derive(Debug, PartialEq, Clone)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>)
+}
+
+impl Json {
+
+    /// Read a JSON value, which must fill the whole string but for white space
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::rpc::Json;
+    ///
+    /// let value = Json::parse(r#"{"id": 3, "params": {"name": "Al\"ice", "seats": [1, true, null]}}"#).unwrap();
+    ///
+    /// assert_eq!(Some(3), value.get("id").and_then(Json::as_u64));
+    /// assert_eq!(Some("Al\"ice"), value.get("params").and_then(|p| p.get("name")).and_then(Json::as_str));
+    /// assert_eq!(r#"{"id":3,"params":{"name":"Al\"ice","seats":[1,true,null]}}"#, value.to_string());
+    /// assert_eq!(None, Json::parse("{\"id\": 3"));
+    /// ```
+    pub fn parse(s: &str) -> Option<Json> {
+        let mut chars = s.chars().peekable();
+        let value = parse_value(&mut chars)?;
+        skip_spaces(&mut chars);
+        match chars.next() {
+            None => Some(value),
+            Some(_) => None
+        }
+    }
+
+    /// Value of a key of an object
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Str(s) => Some(s),
+            _ => None
+        }
+    }
+
+    /// The value if it is a non-negative integer
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Json::Number(x) if *x >= 0. && x.fract() == 0. && *x <= u64::MAX as f64 => Some(*x as u64),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(x) => write!(f, "{}", x),
+            Json::Str(s) => write!(f, "{}", json_string(s)),
+            Json::Array(values) => {
+                let values: Vec<String> = values.iter().map(Json::to_string).collect();
+                write!(f, "[{}]", values.join(","))
+            },
+            Json::Object(entries) => {
+                let entries: Vec<String> = entries.iter().map(|(k, v)| format!("{}:{}", json_string(k), v)).collect();
+                write!(f, "{{{}}}", entries.join(","))
+            }
+        }
+    }
+}
+
+fn skip_spaces(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Option<Json> {
+    skip_spaces(chars);
+    match *chars.peek()? {
+        '{' => {
+            chars.next();
+            let mut entries = Vec::new();
+            skip_spaces(chars);
+            if chars.peek() == Some(&'}') {
+                chars.next();
+                return Some(Json::Object(entries));
+            }
+            loop {
+                skip_spaces(chars);
+                let key = match parse_value(chars)? {
+                    Json::Str(key) => key,
+                    _ => return None
+                };
+                skip_spaces(chars);
+                if chars.next()? != ':' {
+                    return None;
+                }
+                entries.push((key, parse_value(chars)?));
+                skip_spaces(chars);
+                match chars.next()? {
+                    ',' => (),
+                    '}' => return Some(Json::Object(entries)),
+                    _ => return None
+                }
+            }
+        },
+        '[' => {
+            chars.next();
+            let mut values = Vec::new();
+            skip_spaces(chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Some(Json::Array(values));
+            }
+            loop {
+                values.push(parse_value(chars)?);
+                skip_spaces(chars);
+                match chars.next()? {
+                    ',' => (),
+                    ']' => return Some(Json::Array(values)),
+                    _ => return None
+                }
+            }
+        },
+        '"' => {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next()? {
+                    '"' => return Some(Json::Str(s)),
+                    '\\' => match chars.next()? {
+                        'n' => s.push('\n'),
+                        't' => s.push('\t'),
+                        'r' => s.push('\r'),
+                        'b' => s.push('\u{8}'),
+                        'f' => s.push('\u{c}'),
+                        'u' => {
+                            let code: String = (0..4).filter_map(|_| chars.next()).collect();
+                            s.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                        },
+                        c => s.push(c)
+                    },
+                    c => s.push(c)
+                }
+            }
+        },
+        c if c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_ascii_digit() || "+-.eE".contains(c)) {
+                    break;
+                }
+                number.push(c);
+                chars.next();
+            }
+            number.parse().ok().map(Json::Number)
+        },
+        _ => {
+            let word: String = std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_alphabetic())).collect();
+            match word.as_str() {
+                "null" => Some(Json::Null),
+                "true" => Some(Json::Bool(true)),
+                "false" => Some(Json::Bool(false)),
+                _ => None
+            }
+        }
+    }
+}
+
+/// Game of the lobby, waiting for players or being played
+#[derive(Debug, Clone)]
+struct LobbyGame {
+    config: crate::Config,
+    seed: Option<u64>,
+    /// names and tokens of the players who joined, in the order of their seats
+    seats: Vec<(String, String)>,
+    /// the game, once all the seats are taken
+    game: Option<ScriptedGame>
+}
+
+/// Games hosted by the JSON-RPC interface
+#[derive(Debug, Clone)]
+pub struct Lobby {
+    games: Vec<LobbyGame>,
+    /// most players a new game can have, which can change while the server runs
+    pub max_players: u8
+}
+
+/// Lobby shared between the connections
+pub type SharedLobby = Arc<Mutex<Lobby>>;
+
+impl Default for Lobby {
+    fn default() -> Self {
+        Lobby::new()
+    }
+}
+
+/// error code and message
+type RpcError = (i32, String);
+
+fn invalid_params(message: &str) -> RpcError {
+    (INVALID_PARAMS, message.to_string())
+}
+
+impl Lobby {
+
+    /// Create a lobby without any game
+    pub fn new() -> Lobby {
+        Lobby { games: Vec::new(), max_players: crate::MAX_N_PLAYERS }
+    }
+
+    /// Create a lobby which can be shared between connections
+    pub fn shared() -> SharedLobby {
+        Arc::new(Mutex::new(Lobby::new()))
+    }
+
+    /// Answer a request, unless it is a notification (a request without id)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::rpc::Lobby;
+    ///
+    /// let mut lobby = Lobby::new();
+    ///
+    /// assert_eq!(Some(r#"{"jsonrpc":"2.0","id":1,"result":{"game":0}}"#.to_string()),
+    ///            lobby.handle(r#"{"jsonrpc": "2.0", "id": 1, "method": "lobby.create", "params": {"n_players": 3}}"#));
+    /// assert_eq!(Some(r#"{"jsonrpc":"2.0","id":"a","error":{"code":-32601,"message":"unknown method `hello`"}}"#.to_string()),
+    ///            lobby.handle(r#"{"jsonrpc": "2.0", "id": "a", "method": "hello"}"#));
+    /// assert_eq!(None, lobby.handle(r#"{"jsonrpc": "2.0", "method": "lobby.list"}"#));
+    /// ```
+    pub fn handle(&mut self, request: &str) -> Option<String> {
+        let request = match Json::parse(request) {
+            Some(request) => request,
+            None => return Some(response(&Json::Null, Err((PARSE_ERROR, "invalid JSON".to_string()))))
+        };
+        let id = request.get("id").cloned();
+        let method = match (request.get("jsonrpc").and_then(Json::as_str), request.get("method").and_then(Json::as_str)) {
+            (Some("2.0"), Some(method)) => method,
+            _ => return Some(response(&id.unwrap_or(Json::Null),
+                                      Err((INVALID_REQUEST, "not a JSON-RPC 2.0 request".to_string()))))
+        };
+        let no_params = Json::Object(Vec::new());
+        let params = request.get("params").unwrap_or(&no_params);
+        let result = self.call(method, params);
+        id.map(|id| response(&id, result))
+    }
+
+    fn call(&mut self, method: &str, params: &Json) -> Result<String, RpcError> {
+        match method {
+            "lobby.create" => self.create(params),
+            "lobby.list" => Ok(self.list()),
+            "lobby.join" => self.join(params),
+            "game.state" => self.state(params),
+            "game.play" => self.play(params),
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method `{}`", method)))
+        }
+    }
+
+    fn create(&mut self, params: &Json) -> Result<String, RpcError> {
+
+        // the config is checked as if it had been read from a file
+        let mut config_text = String::new();
+        for key in CONFIG_KEYS.iter() {
+            let value = match params.get(key) {
+                None => continue,
+                Some(Json::Str(s)) => quote(s),
+                Some(Json::Bool(b)) => b.to_string(),
+                Some(value) => value.as_u64().map(|n| n.to_string())
+                    .ok_or_else(|| invalid_params(&format!("`{}` has an invalid value", key)))?
+            };
+            config_text += &format!("{} = {}\n", key, value);
+        }
+        let (config, _) = parse_config(&config_text).map_err(|e| invalid_params(&e.message))?;
+        if config.n_players > self.max_players {
+            return Err((GAME_ERROR, format!("games on this server have at most {} players", self.max_players)));
+        }
+        let warnings = config.warnings();
+        if !warnings.is_empty() && params.get("confirm") != Some(&Json::Bool(true)) {
+            return Err((GAME_ERROR, format!("{}; set `confirm` to true to create the game anyway", warnings.join("; "))));
+        }
+        let seed = match params.get("seed") {
+            Some(seed) => Some(seed.as_u64().ok_or_else(|| invalid_params("`seed` should be a positive integer"))?),
+            None => None
+        };
+        self.games.push(LobbyGame { config, seed, seats: Vec::new(), game: None });
+        Ok(format!("{{\"game\":{}}}", self.games.len() - 1))
+    }
+
+    fn list(&self) -> String {
+        let games: Vec<String> = self.games.iter().enumerate().map(|(id, g)| {
+            let names: Vec<String> = g.seats.iter().map(|(name, _)| json_string(name)).collect();
+            format!("{{\"game\":{},\"n_players\":{},\"players\":[{}],\"started\":{},\"over\":{},\"description\":{}}}",
+                    id, g.config.n_players, names.join(","), g.game.is_some(),
+                    g.game.as_ref().is_some_and(|g| g.outcome.is_some()), json_string(&g.config.describe()))
+        }).collect();
+        format!("[{}]", games.join(","))
+    }
+
+    fn game_mut(&mut self, params: &Json) -> Result<&mut LobbyGame, RpcError> {
+        let id = params.get("game").and_then(Json::as_u64).ok_or_else(|| invalid_params("missing `game`"))?;
+        self.games.get_mut(id as usize).ok_or_else(|| invalid_params(&format!("there is no game {}", id)))
+    }
+
+    fn join(&mut self, params: &Json) -> Result<String, RpcError> {
+        let name = params.get("name").and_then(Json::as_str).ok_or_else(|| invalid_params("missing `name`"))?;
+        let name = PlayerName::new(name).map_err(|e| invalid_params(&e.message))?;
+        let lobby_game = self.game_mut(params)?;
+        if lobby_game.game.is_some() {
+            return Err((GAME_ERROR, "the game has already started".to_string()));
+        }
+        if lobby_game.seats.iter().any(|(n, _)| n == name.as_str()) {
+            return Err((GAME_ERROR, format!("{} is already taken", name.as_str())));
+        }
+        let token = new_token(&mut rand::thread_rng());
+        lobby_game.seats.push((name.as_str().to_string(), token.clone()));
+        let seat = lobby_game.seats.len() - 1;
+        if lobby_game.seats.len() == lobby_game.config.n_players as usize {
+            let names = lobby_game.seats.iter().map(|(name, _)| name.clone()).collect();
+            lobby_game.game = Some(ScriptedGame::new(lobby_game.config.clone(), names, lobby_game.seed, None));
+        }
+        Ok(format!("{{\"seat\":{},\"token\":{}}}", seat, json_string(&token)))
+    }
+
+    /// started game and seat of the player with the token of the request
+    fn seat_mut(&mut self, params: &Json) -> Result<(&mut ScriptedGame, usize), RpcError> {
+        let token = params.get("token").and_then(Json::as_str).ok_or_else(|| invalid_params("missing `token`"))?;
+        let lobby_game = self.game_mut(params)?;
+        let seat = lobby_game.seats.iter().position(|(_, t)| t == token)
+            .ok_or_else(|| invalid_params("unknown token"))?;
+        let n_missing = lobby_game.config.n_players as usize - lobby_game.seats.len();
+        match &mut lobby_game.game {
+            Some(game) => Ok((game, seat)),
+            None => Err((GAME_ERROR, format!("the game has not started; waiting for {} more players", n_missing)))
+        }
+    }
+
+    fn state(&mut self, params: &Json) -> Result<String, RpcError> {
+        let (game, seat) = self.seat_mut(params)?;
+        let state = snapshot(&game.table, &game.hands, &game.deck, game.current_player(), Visibility::Player(seat));
+        let taken: Vec<String> = if game.current_player() == seat {
+            game.cards_from_table().to_vec().iter().map(|c| format!("\"{}\"", card_code(c))).collect()
+        } else {
+            Vec::new()
+        };
+        let winner = match game.outcome {
+            Some(Outcome::Won(i)) | Some(Outcome::Stalemate(Some(i))) => i.to_string(),
+            _ => "null".to_string()
+        };
+        Ok(format!("{{\"seat\":{},\"state\":{},\"taken\":[{}],\"over\":{},\"winner\":{}}}",
+                   seat, state.to_json(), taken.join(","), game.outcome.is_some(), winner))
+    }
+
+    fn play(&mut self, params: &Json) -> Result<String, RpcError> {
+        let action = params.get("action").and_then(Json::as_str).ok_or_else(|| invalid_params("missing `action`"))?
+            .to_string();
+        let (game, seat) = self.seat_mut(params)?;
+        if game.outcome.is_none() && game.current_player() != seat {
+            return Err((GAME_ERROR, "it is not your turn".to_string()));
+        }
+        game.play(&action).map_err(|m| (GAME_ERROR, m))?;
+        Ok("{\"ok\":true}".to_string())
+    }
+}
+
+/// line of the answer to a request with the id `id`
+fn response(id: &Json, result: Result<String, RpcError>) -> String {
+    match result {
+        Ok(result) => format!("{{\"jsonrpc\":\"2.0\",\"id\":{},\"result\":{}}}", id, result),
+        Err((code, message)) => format!("{{\"jsonrpc\":\"2.0\",\"id\":{},\"error\":{{\"code\":{},\"message\":{}}}}}",
+                                        id, code, json_string(&message))
+    }
+}
+
+/// Address given with `RPC_FLAG`, and the arguments without the flag
+pub fn rpc_address(args: Vec<String>) -> (Option<String>, Vec<String>) {
+    crate::take_flag_value(args, RPC_FLAG)
+}
+
+/// Answer the requests sent to an address, with a thread for each connection, until the process
+/// stops
+#[cfg(feature = "json_rpc")]
+pub fn serve(address: &str, lobby: SharedLobby) -> std::io::Result<()> {
+    let listener = std::net::TcpListener::bind(address)?;
+    for stream in listener.incoming().flatten() {
+        let lobby = lobby.clone();
+        std::thread::spawn(move || {
+            // a connection which fails should not stop the others
+            handle_connection(stream, &lobby).unwrap_or(());
+        });
+    }
+    Ok(())
+}
+
+#[cfg(feature = "json_rpc")]
+fn handle_connection(stream: std::net::TcpStream, lobby: &SharedLobby) -> std::io::Result<()> {
+    use std::io::{ BufRead, BufReader, Write };
+
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let answer = match lobby.lock() {
+            Ok(mut lobby) => lobby.handle(&line),
+            Err(_) => Some(response(&Json::Null, Err((GAME_ERROR, "internal error".to_string()))))
+        };
+        if let Some(answer) = answer {
+            writeln!(writer, "{}", answer)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn request(lobby: &mut Lobby, method: &str, params: &str) -> Json {
+        let answer = lobby.handle(&format!("{{\"jsonrpc\":\"2.0\",\"id\":0,\"method\":\"{}\",\"params\":{}}}",
+                                           method, params)).unwrap();
+        Json::parse(&answer).unwrap()
+    }
+
+    fn token(answer: &Json) -> String {
+        answer.get("result").and_then(|r| r.get("token")).and_then(Json::as_str).unwrap().to_string()
+    }
+
+    #[test]
+    fn players_join_and_play_in_turn() {
+        let mut lobby = Lobby::new();
+        request(&mut lobby, "lobby.create", "{\"seed\":42,\"custom_rule_jokers\":false}");
+        let alice = token(&request(&mut lobby, "lobby.join", "{\"game\":0,\"name\":\"Alice\"}"));
+        let waiting = request(&mut lobby, "game.state", &format!("{{\"game\":0,\"token\":\"{}\"}}", alice));
+        assert_eq!(Some(GAME_ERROR as f64), waiting.get("error").and_then(|e| e.get("code")).and_then(|c| match c {
+            Json::Number(x) => Some(*x),
+            _ => None
+        }));
+        let bob = token(&request(&mut lobby, "lobby.join", "{\"game\":0,\"name\":\"Bob\"}"));
+
+        // Bob can't play first, and only sees his own hand
+        let refused = request(&mut lobby, "game.play", &format!("{{\"game\":0,\"token\":\"{}\",\"action\":\"e\"}}", bob));
+        assert!(refused.to_string().contains("it is not your turn"));
+        let state = request(&mut lobby, "game.state", &format!("{{\"game\":0,\"token\":\"{}\"}}", bob));
+        let players = match state.get("result").and_then(|r| r.get("state")).and_then(|s| s.get("players")) {
+            Some(Json::Array(players)) => players.clone(),
+            _ => panic!("no players in {}", state)
+        };
+        assert_eq!(None, players[0].get("hand"));
+        assert!(players[1].get("hand").is_some());
+
+        let played = request(&mut lobby, "game.play", &format!("{{\"game\":0,\"token\":\"{}\",\"action\":\"e\"}}", alice));
+        assert_eq!(Some(&Json::Object(vec![("ok".to_string(), Json::Bool(true))])), played.get("result"));
+        assert!(lobby.list().contains("\"players\":[\"Alice\",\"Bob\"],\"started\":true,\"over\":false"));
+    }
+
+    #[test]
+    fn invalid_requests() {
+        let mut lobby = Lobby::new();
+        assert!(lobby.handle("{").unwrap().contains("-32700"));
+        assert!(lobby.handle("{\"id\":1,\"method\":\"lobby.list\"}").unwrap().contains("-32600"));
+        assert!(request(&mut lobby, "lobby.create", "{\"n_decks\":0}").to_string().contains("-32602"));
+        assert!(request(&mut lobby, "lobby.join", "{\"game\":3,\"name\":\"Alice\"}").to_string()
+                .contains("there is no game 3"));
+        lobby.max_players = 3;
+        assert!(request(&mut lobby, "lobby.create", "{\"n_players\":4}").to_string()
+                .contains("games on this server have at most 3 players"));
+    }
+
+    #[test]
+    fn odd_configs_need_a_confirmation() {
+        let mut lobby = Lobby::new();
+        let refused = request(&mut lobby, "lobby.create", "{\"n_decks\":1,\"n_jokers\":200}");
+        assert!(refused.to_string().contains("200 of the 252 cards are jokers"));
+        request(&mut lobby, "lobby.create", "{\"n_decks\":1,\"n_jokers\":200,\"confirm\":true}");
+        assert!(lobby.list().contains("\"description\":\"1 deck and 200 jokers: 252 cards, 79% of which are jokers."));
+    }
+}
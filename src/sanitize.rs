@@ -0,0 +1,93 @@
+//! Cleaning of the strings received from the clients
+//!
+//! Whatever a client sends may end up on the screens of the other players. Terminal escape
+//! sequences and non-printable characters are removed from every string read from a client, so
+//! that nobody can clear, recolour, or retitle the terminals of the others.
+
+/// Remove escape sequences and non-printable characters from a string
+///
+/// CSI sequences (such as colours or cursor moves), OSC sequences (such as window titles), and
+/// two-character escape sequences are removed, as well as control characters and the characters
+/// changing the direction of the text.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::sanitize::sanitize;
+///
+/// assert_eq!("p 1 2 3", sanitize("p 1\x1b[2J 2\x07 3"));
+/// assert_eq!("Hello", sanitize("\x1b]0;pwned\x07Hel\u{202e}lo\n"));
+/// ```
+pub fn sanitize(s: &str) -> String {
+    strip_escape_sequences(s).chars().filter(|&c| !is_non_printable(c)).collect()
+}
+
+/// Read bytes as a string, and clean it
+pub fn sanitize_bytes(bytes: Vec<u8>) -> Result<String, std::string::FromUtf8Error> {
+    String::from_utf8(bytes).map(|s| sanitize(&s))
+}
+
+/// remove the terminal escape sequences (CSI and OSC sequences, and two-character ones)
+fn strip_escape_sequences(s: &str) -> String {
+    let mut res = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            res.push(c);
+            continue;
+        }
+        match chars.next() {
+
+            // CSI: parameters, then a final character between '@' and '~'
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            },
+
+            // OSC: ends with BEL or ESC '\'
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            },
+
+            _ => ()
+        }
+    }
+    res
+}
+
+/// control characters, and characters changing the direction of the text
+fn is_non_printable(c: char) -> bool {
+    c.is_control() || ('\u{202a}'..='\u{202e}').contains(&c) || ('\u{2066}'..='\u{2069}').contains(&c)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn escape_sequences() {
+        assert_eq!("ab", strip_escape_sequences("a\x1b]0;title\x07b"));
+        assert_eq!("ab", strip_escape_sequences("a\x1b]0;title\x1b\\b"));
+        assert_eq!("ab", strip_escape_sequences("a\x1bcb"));
+        assert_eq!("a", strip_escape_sequences("a\x1b[1;3"));
+    }
+
+    #[test]
+    fn non_printable_characters() {
+        assert_eq!("Bob", sanitize("B\u{202e}o\u{7}b\u{9b}\r"));
+        assert_eq!("Niccolò 🃏", sanitize("Niccolò 🃏"));
+        assert!(sanitize_bytes(vec![0x66, 0xff]).is_err());
+    }
+}
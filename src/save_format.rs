@@ -0,0 +1,165 @@
+//! Formats of the saved games
+//!
+//! Saves start with `SAVE_MAGIC` and the number of their format, `CURRENT_VERSION`, which writes
+//! the table with its number of sequences and the length of each one (see `Table::from_bytes`),
+//! and ends with the cards set aside with the deck. Format 2 did not have these cards, and is
+//! loaded without them.
+//! Saves written by older versions start with the rules, and end with a table whose sequences
+//! are separated by 255: they are recognised as they can not start with `SAVE_MAGIC` (a game has
+//! at least one deck), still loaded, and written in the current format the next time the game is
+//! saved. `load` reads both, and reports the format it found and what had to be changed to load
+//! the save. A save written by an older version is kept in `tests/fixtures/saves`, to check that
+//! it still loads.
+
+use std::fmt;
+use crate::{ load_save, LoadingError, SavedGame, SavedTurn };
+
+/// bytes at the start of the saves which have a format number
+pub const SAVE_MAGIC: [u8; 4] = [0, b'M', b'C', b'V'];
+
+/// format of the saves written by this version
+pub const CURRENT_VERSION: u8 = 3;
+
+/// Format of a save
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SaveFormat {
+    /// written before the saves had a format number, with 255 between the sequences of the table
+    Legacy,
+    /// starting with `SAVE_MAGIC` and this format number
+    Versioned(u8)
+}
+
+impl fmt::Display for SaveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveFormat::Legacy => write!(f, "the format of older versions"),
+            SaveFormat::Versioned(version) => write!(f, "format {}", version)
+        }
+    }
+}
+
+/// What was found while loading a save
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LoadReport {
+    pub format: SaveFormat,
+    /// players whose names could not be read, and had the characters which could not be replaced
+    pub repaired_names: Vec<usize>,
+    /// bytes of the table which were dropped as they were not cards or came after the last
+    /// sequence (in legacy saves only)
+    pub dropped_table_bytes: usize
+}
+
+impl LoadReport {
+
+    /// Report of a save in the current format, loaded as it was
+    pub fn new() -> LoadReport {
+        LoadReport { format: SaveFormat::Versioned(CURRENT_VERSION), repaired_names: Vec::new(), dropped_table_bytes: 0 }
+    }
+
+    /// Whether something was changed or dropped to load the save
+    pub fn is_lossy(&self) -> bool {
+        !self.repaired_names.is_empty() || self.dropped_table_bytes > 0
+    }
+
+    /// Lines telling the user about the format of the save and what was changed to load it,
+    /// none for a save in the current format loaded as it was
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::save_format::*;
+    ///
+    /// assert!(LoadReport::new().notes().is_empty());
+    ///
+    /// let report = LoadReport { format: SaveFormat::Legacy, repaired_names: vec![1], dropped_table_bytes: 0 };
+    /// assert_eq!(vec!["The save is in the format of older versions; it will be saved in format 3.",
+    ///                 "The name of player 2 could not be read, and was repaired."], report.notes());
+    /// ```
+    pub fn notes(&self) -> Vec<String> {
+        let mut res = Vec::new();
+        if self.format != SaveFormat::Versioned(CURRENT_VERSION) {
+            res.push(format!("The save is in {}; it will be saved in {}.", self.format,
+                             SaveFormat::Versioned(CURRENT_VERSION)));
+        }
+        for i in &self.repaired_names {
+            res.push(format!("The name of player {} could not be read, and was repaired.", i + 1));
+        }
+        if self.dropped_table_bytes > 0 {
+            res.push(format!("{} bytes of the table could not be read, and were dropped.", self.dropped_table_bytes));
+        }
+        res
+    }
+}
+
+impl Default for LoadReport {
+    fn default() -> Self {
+        LoadReport::new()
+    }
+}
+
+/// Load a save in any format, with the turn in progress if there is one
+///
+/// If the name of a player can not be read, the save is loaded with the characters which can
+/// not be replaced if `repair` (given the index of the player) says so, and an error is returned
+/// otherwise. The report tells the format of the save and what was changed.
+pub fn load<F: FnOnce(usize) -> bool>(bytes: &[u8], repair: F)
+    -> Result<(SavedGame, Option<SavedTurn>, LoadReport), LoadingError>
+{
+    match load_save(bytes, false) {
+        Err(LoadingError::InvalidName(i)) if repair(i) => load_save(bytes, true),
+        loaded => loaded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{ game_to_bytes, hand, Config, EmptyDeckRule, MulliganRule, DEFAULT_JOKER_PENALTY };
+    use crate::deck::Deck;
+    use crate::hand::Hand;
+    use crate::sequence_cards::*;
+    use crate::table::Table;
+
+    #[test]
+    fn legacy_saves_are_still_loaded() {
+        let config = Config { n_decks: 1, n_jokers: 0, n_cards_to_start: 1, custom_rule_jokers: false, n_players: 2,
+                              empty_deck: EmptyDeckRule::Draw, joker_penalty: DEFAULT_JOKER_PENALTY,
+                              mulligan: MulliganRule::Off, draw_for_first: false };
+        let hands = Hand::deal(&["a".to_string(), "b".to_string()], vec![Sequence::from_cards(&[Joker]); 2]);
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5), RegularCard(Club, 6)]));
+        let mut deck = Deck::new();
+        deck.discard(&[RegularCard(Heart, 2)]);
+        let bytes = game_to_bytes(0, 0, &table, &hands, &deck, &config);
+        let (game, _, report) = load(&bytes, |_| false).ok().unwrap();
+        assert_eq!(deck, game.5);
+        assert_eq!(LoadReport::new(), report);
+
+        // the same game as written by older versions, with a card which does not exist
+        let mut legacy = bytes[SAVE_MAGIC.len() + 1..].to_vec();
+        let n_table = table.to_bytes().len();
+        legacy.truncate(legacy.len() - n_table - 3);
+        legacy.extend_from_slice(&[17, 18, 200, 19, 255]);
+        assert_eq!(b'a', legacy[15]);
+        legacy[15] = 0xff;
+        assert_eq!(Some(LoadingError::InvalidName(0)), load(&legacy, |_| false).err());
+        let (game, _, report) = load(&legacy, |_| true).ok().unwrap();
+        assert_eq!(table, game.3);
+        assert_eq!(vec!["\u{fffd}".to_string(), "b".to_string()], hand::owners(&game.4));
+        assert_eq!(LoadReport { format: SaveFormat::Legacy, repaired_names: vec![0], dropped_table_bytes: 1 }, report);
+
+        // format 2, without the cards set aside
+        let mut version_2 = bytes[..bytes.len() - 3].to_vec();
+        version_2[SAVE_MAGIC.len()] = 2;
+        let (game, _, report) = load(&version_2, |_| false).ok().unwrap();
+        assert_eq!(table, game.3);
+        assert_eq!(Deck::new(), game.5);
+        assert_eq!(SaveFormat::Versioned(2), report.format);
+
+        // a format from the future
+        let mut future = bytes;
+        future[SAVE_MAGIC.len()] = CURRENT_VERSION + 1;
+        assert_eq!(Some(LoadingError::Corrupted), load(&future, |_| true).err());
+    }
+}
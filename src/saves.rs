@@ -0,0 +1,528 @@
+//! Save files found in a directory, with what is needed to choose one
+//!
+//! Saves start with a block of metadata (see `SaveMetadata`), which is not encrypted, so that
+//! the saves of a directory can be listed without deriving the key of each one. Older saves,
+//! without it, are loaded to be described.
+//!
+//! Saves are also managed as slots here: a new save can get a free name next to an existing
+//! one, and saves are deleted or renamed with their backup and audit log.
+
+use std::convert::TryInto;
+use std::io;
+use std::path::Path;
+use std::time::{ Duration, SystemTime, UNIX_EPOCH };
+use crate::{ encode, load_game, write_atomically, Config };
+use crate::audit::AUDIT_EXTENSION;
+use crate::clock::{ Clock, PlayerTime };
+use crate::hand::Hand;
+use crate::puzzle::civil_from_days;
+
+/// What is written before the encrypted part of a save
+#[derive(Debug, PartialEq, Clone)]
+pub struct SaveMetadata {
+    /// when the game was saved, in seconds since 1970-01-01 (UTC)
+    pub saved_at: u64,
+    /// number of the round, from 1
+    pub round: u32,
+    /// number of turns played in the round
+    pub turn: u32,
+    /// index of the player whose turn it is
+    pub current_player: u8,
+    /// name of each player, with the number of cards in their hand
+    pub players: Vec<(String, u16)>,
+    pub config: Config,
+    /// time taken by each player
+    pub times: Vec<PlayerTime>,
+    /// time already spent on the current turn
+    pub turn_elapsed: Duration
+}
+
+impl SaveMetadata {
+
+    /// Metadata of a game saved now
+    pub fn new<H: AsRef<Hand>>(round: u32, turn: u32, current_player: u8, hands: &[H], config: &Config)
+        -> SaveMetadata
+    {
+        SaveMetadata {
+            saved_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            round,
+            turn,
+            current_player,
+            players: hands.iter()
+                .map(|h| (h.as_ref().owner().to_string(), h.as_ref().number_cards().min(u16::MAX as usize) as u16))
+                .collect(),
+            config: config.clone(),
+            times: vec![PlayerTime::default(); hands.len()],
+            turn_elapsed: Duration::from_secs(0)
+        }
+    }
+
+    /// The same metadata, with the times measured by `clock`
+    pub fn with_clock(self, clock: &Clock) -> SaveMetadata {
+        SaveMetadata { times: clock.times().to_vec(), turn_elapsed: clock.elapsed(), ..self }
+    }
+
+    /// Clock of the game, as it was when it was saved
+    pub fn clock(&self) -> Clock {
+        let mut times = self.times.clone();
+        times.resize(self.players.len(), PlayerTime::default());
+        Clock::resume(times, self.turn_elapsed)
+    }
+
+    /// Convert the metadata to bytes
+    ///
+    /// The time is written on eight bytes, the round and the turn on four bytes each, and the
+    /// current player and the number of players on one byte each (all big-endian). Each player
+    /// follows, as the length of their name on two bytes, the name, and the number of cards on
+    /// two bytes; then the config, as written by `Config::to_bytes`. The times come last, in
+    /// milliseconds: for each player, the total on eight bytes, the number of turns on four, and
+    /// the longest turn on eight; then the time spent on the current turn on eight bytes. Metadata
+    /// without them, from older saves, has no time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use machiavelli::{ Config, EmptyDeckRule, MulliganRule, DEFAULT_JOKER_PENALTY };
+    /// use machiavelli::clock::PlayerTime;
+    /// use machiavelli::saves::SaveMetadata;
+    ///
+    /// let metadata = SaveMetadata {
+    ///     saved_at: 1_622_556_000,
+    ///     round: 2,
+    ///     turn: 14,
+    ///     current_player: 1,
+    ///     players: vec![("Alice".to_string(), 7), ("Bob".to_string(), 12)],
+    ///     config: Config { n_decks: 2, n_jokers: 4, n_cards_to_start: 13, custom_rule_jokers: false,
+    ///                      n_players: 2, empty_deck: EmptyDeckRule::Draw, joker_penalty: DEFAULT_JOKER_PENALTY,
+    ///                      mulligan: MulliganRule::Off, draw_for_first: false },
+    ///     times: vec![PlayerTime { total: Duration::from_secs(95), n_turns: 7, longest: Duration::from_secs(30) },
+    ///                 PlayerTime { total: Duration::from_secs(60), n_turns: 7, longest: Duration::from_secs(12) }],
+    ///     turn_elapsed: Duration::from_millis(2500)
+    /// };
+    ///
+    /// assert_eq!(Some(metadata.clone()), SaveMetadata::from_bytes(&metadata.to_bytes()));
+    /// assert_eq!(None, SaveMetadata::from_bytes(&metadata.to_bytes()[..30]));
+    ///
+    /// // without the times
+    /// let bytes = metadata.to_bytes();
+    /// let old = SaveMetadata::from_bytes(&bytes[..bytes.len() - 48]).unwrap();
+    /// assert_eq!((Vec::new(), Duration::from_secs(0)), (old.times, old.turn_elapsed));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.saved_at.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.round.to_be_bytes());
+        bytes.extend_from_slice(&self.turn.to_be_bytes());
+        bytes.push(self.current_player);
+        bytes.push(self.players.len() as u8);
+        for (name, n_cards) in &self.players {
+            bytes.extend_from_slice(&(name.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&n_cards.to_be_bytes());
+        }
+        bytes.extend(self.config.to_bytes());
+        for time in &self.times {
+            bytes.extend_from_slice(&(time.total.as_millis() as u64).to_be_bytes());
+            bytes.extend_from_slice(&time.n_turns.to_be_bytes());
+            bytes.extend_from_slice(&(time.longest.as_millis() as u64).to_be_bytes());
+        }
+        bytes.extend_from_slice(&(self.turn_elapsed.as_millis() as u64).to_be_bytes());
+        bytes
+    }
+
+    /// Read metadata written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Option<SaveMetadata> {
+        let mut rest = bytes;
+        let saved_at = u64::from_be_bytes(take(&mut rest, 8)?.try_into().ok()?);
+        let round = u32::from_be_bytes(take(&mut rest, 4)?.try_into().ok()?);
+        let turn = u32::from_be_bytes(take(&mut rest, 4)?.try_into().ok()?);
+        let current_player = take(&mut rest, 1)?[0];
+        let n_players = take(&mut rest, 1)?[0];
+        let mut players = Vec::new();
+        for _ in 0..n_players {
+            let length = u16::from_be_bytes(take(&mut rest, 2)?.try_into().ok()?) as usize;
+            let name = String::from_utf8(take(&mut rest, length)?.to_vec()).ok()?;
+            players.push((name, u16::from_be_bytes(take(&mut rest, 2)?.try_into().ok()?)));
+        }
+        let n_bytes_config = Config::n_bytes(rest);
+        let config = Config::from_bytes(take(&mut rest, n_bytes_config)?).ok()?;
+        let mut times = Vec::new();
+        let mut turn_elapsed = Duration::from_secs(0);
+        if !rest.is_empty() {
+            for _ in 0..n_players {
+                let total = take_millis(&mut rest)?;
+                let n_turns = u32::from_be_bytes(take(&mut rest, 4)?.try_into().ok()?);
+                times.push(PlayerTime { total, n_turns, longest: take_millis(&mut rest)? });
+            }
+            turn_elapsed = take_millis(&mut rest)?;
+        }
+        Some(SaveMetadata { saved_at, round, turn, current_player, players, config, times, turn_elapsed })
+    }
+}
+
+/// First `n` bytes of `bytes`, which then starts after them
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+    let part = bytes.get(..n)?;
+    *bytes = &bytes[n..];
+    Some(part)
+}
+
+/// Duration written as a number of milliseconds on eight bytes
+fn take_millis(bytes: &mut &[u8]) -> Option<Duration> {
+    Some(Duration::from_millis(u64::from_be_bytes(take(bytes, 8)?.try_into().ok()?)))
+}
+
+/// What a save file contains, as shown to the players choosing a game to load
+#[derive(Debug, PartialEq, Clone)]
+pub struct SaveInfo {
+    /// path of the file, whose name is also its password
+    pub path: String,
+    /// when the game was saved (for older saves, when the file was last written)
+    pub modified: SystemTime,
+    pub config: Config,
+    /// name of each player, with the number of cards in their hand
+    pub players: Vec<(String, usize)>,
+    /// index of the player whose turn it is
+    pub current_player: usize,
+    /// number of the round and of turns played in it, unknown for older saves
+    pub round_and_turn: Option<(u32, u32)>
+}
+
+impl SaveInfo {
+
+    /// Read a save file, from its metadata if it has some
+    ///
+    /// `None` is returned if the file can not be read, or if it has no valid metadata and can not
+    /// be decrypted or loaded. The metadata is not checked against the rest of the file, which is
+    /// only done when the game is loaded.
+    pub fn read(path: &str) -> Option<SaveInfo> {
+        let data = std::fs::read(path).ok()?;
+        if let Some(metadata) = encode::save_metadata(&data).and_then(SaveMetadata::from_bytes) {
+            return Some(SaveInfo::from_metadata(path, metadata));
+        }
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+        // saves of older versions are listed, so that they can be picked and loaded, which asks
+        // the user before reading them
+        let bytes = encode::decrypt_save_or_legacy(&data, path, || true).ok()?;
+        let (config, _, current_player, _, hands, _) = load_game(&bytes).ok()?;
+        Some(SaveInfo {
+            path: path.to_string(),
+            modified,
+            config,
+            players: hands.iter().map(|h| (h.owner().to_string(), h.number_cards())).collect(),
+            current_player: current_player as usize,
+            round_and_turn: None
+        })
+    }
+
+    /// Information of the save `path`, with this metadata
+    pub fn from_metadata(path: &str, metadata: SaveMetadata) -> SaveInfo {
+        SaveInfo {
+            path: path.to_string(),
+            modified: UNIX_EPOCH + Duration::from_secs(metadata.saved_at),
+            config: metadata.config,
+            players: metadata.players.into_iter().map(|(name, n)| (name, n as usize)).collect(),
+            current_player: metadata.current_player as usize,
+            round_and_turn: Some((metadata.round, metadata.turn))
+        }
+    }
+
+    /// Whether a player has emptied their hand
+    pub fn is_finished(&self) -> bool {
+        self.players.iter().any(|(_, n)| *n == 0)
+    }
+
+    /// One-line description of the save
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::{ Duration, UNIX_EPOCH };
+    /// use machiavelli::{ Config, EmptyDeckRule, MulliganRule, DEFAULT_JOKER_PENALTY };
+    /// use machiavelli::saves::SaveInfo;
+    ///
+    /// let info = SaveInfo {
+    ///     path: "saves/game.sav".to_string(),
+    ///     modified: UNIX_EPOCH + Duration::from_secs(1_622_556_000),
+    ///     config: Config { n_decks: 2, n_jokers: 4, n_cards_to_start: 13, custom_rule_jokers: false,
+    ///                      n_players: 2, empty_deck: EmptyDeckRule::Draw, joker_penalty: DEFAULT_JOKER_PENALTY,
+    ///                      mulligan: MulliganRule::Off, draw_for_first: false },
+    ///     players: vec![("Alice".to_string(), 7), ("Bob".to_string(), 12)],
+    ///     current_player: 1,
+    ///     round_and_turn: Some((2, 14))
+    /// };
+    ///
+    /// assert_eq!("game.sav (2021-06-01 14:00 UTC, round 2, turn 15): Alice (7 cards), Bob (12 cards, to play); \
+    ///             2 decks, 4 jokers, 13 cards each", info.describe());
+    /// ```
+    pub fn describe(&self) -> String {
+        let name = Path::new(&self.path).file_name().map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.path.clone());
+        let players: Vec<String> = self.players.iter().enumerate()
+            .map(|(i, (player, n))| {
+                let to_play = if i == self.current_player { ", to play" } else { "" };
+                format!("{} ({} card{}{})", player, n, if *n == 1 { "" } else { "s" }, to_play)
+            })
+            .collect();
+        let progress = match self.round_and_turn {
+            Some((round, turn)) => format!(", round {}, turn {}", round, turn + 1),
+            None => String::new()
+        };
+        format!("{} ({}{}): {}; {} deck{}, {} joker{}, {} cards each", name, format_time(self.modified), progress,
+                players.join(", "), self.config.n_decks, if self.config.n_decks == 1 { "" } else { "s" },
+                self.config.n_jokers, if self.config.n_jokers == 1 { "" } else { "s" },
+                self.config.n_cards_to_start)
+    }
+}
+
+/// Date and time, in UTC, as `YYYY-MM-DD HH:MM UTC`
+pub fn format_time(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    format!("{}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, seconds % 86400 / 3600, seconds % 3600 / 60)
+}
+
+/// Save files of a directory, from the most recently saved
+///
+/// A file is a save if its name ends with `extension` (but not with `_bak` and the extension, as
+/// for backups, nor starts with a dot, as temporary files), and it can be loaded. Each file is
+/// decoded with its name, as it was when the game was saved.
+pub fn list_saves(dir: &Path, extension: &str) -> Vec<SaveInfo> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new()
+    };
+    let backup_suffix = format!("_bak{}", extension);
+    let mut saves: Vec<SaveInfo> = entries.flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.ends_with(extension) || name.ends_with(&backup_suffix) || name.starts_with('.') {
+                return None;
+            }
+            SaveInfo::read(&dir.join(&name).to_string_lossy())
+        })
+        .collect();
+    saves.sort_by_key(|s| std::cmp::Reverse(s.modified));
+    saves
+}
+
+/// Print the saves, numbered from 1
+pub fn print_saves(saves: &[SaveInfo]) {
+    for (i, save) in saves.iter().enumerate() {
+        println!("{:>3}. {}", i + 1, save.describe());
+    }
+}
+
+/// Save chosen by its number in the list printed by `print_saves`, if the input is one
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::saves::pick_save;
+///
+/// let saves = vec!["a.sav".to_string(), "b.sav".to_string()];
+///
+/// assert_eq!(Some(&"b.sav".to_string()), pick_save(&saves, " 2\n"));
+/// assert_eq!(None, pick_save(&saves, "3"));
+/// assert_eq!(None, pick_save(&saves, "b.sav"));
+/// ```
+pub fn pick_save<'a, T>(saves: &'a [T], input: &str) -> Option<&'a T> {
+    let n = input.trim().parse::<usize>().ok()?;
+    saves.get(n.checked_sub(1)?)
+}
+
+/// Part of `path` before `extension`, or the whole path if it does not end with it
+fn stem<'a>(path: &'a str, extension: &str) -> &'a str {
+    if extension.is_empty() {
+        return path;
+    }
+    path.strip_suffix(extension).unwrap_or(path)
+}
+
+/// Path of the backup of the save `path`
+fn backup_path(path: &str, extension: &str) -> String {
+    format!("{}_bak{}", stem(path, extension), extension)
+}
+
+/// Path of the audit log of the save `path`
+fn audit_path(path: &str, extension: &str) -> String {
+    stem(path, extension).to_string() + AUDIT_EXTENSION
+}
+
+/// `path` if no file has it, otherwise the first free path with the date and a number before
+/// the extension, as `game_2021-06-01_1.sav`
+pub fn versioned_name(path: &str, extension: &str) -> String {
+    if !Path::new(path).exists() {
+        return path.to_string();
+    }
+    let date = &format_time(SystemTime::now())[..10];
+    (1..)
+        .map(|n| format!("{}_{}_{}{}", stem(path, extension), date, n, extension))
+        .find(|p| !Path::new(p).exists())
+        .unwrap()
+}
+
+/// Delete the save `path`, with its backup and audit log if it has them
+pub fn delete_save(path: &str, extension: &str) -> io::Result<()> {
+    std::fs::remove_file(path)?;
+    for other in &[backup_path(path, extension), audit_path(path, extension)] {
+        if Path::new(other).exists() {
+            std::fs::remove_file(other)?;
+        }
+    }
+    Ok(())
+}
+
+/// Rename the save `from` to `to`, which must not exist
+///
+/// As the name of a save is its password, the game is encrypted again for the new name. The
+/// audit log follows the save, and the backup, a copy of the save for the old path, is deleted.
+pub fn rename_save(from: &str, to: &str, extension: &str) -> io::Result<()> {
+    if Path::new(to).exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", to)));
+    }
+    let data = std::fs::read(from)?;
+    let bytes = encode::decrypt_save(&data, from)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let metadata = encode::save_metadata(&data).unwrap_or(&[]);
+    write_atomically(to, &encode::encrypt_save(&bytes, metadata, to, &mut rand::thread_rng()))?;
+    std::fs::remove_file(from)?;
+    let backup = backup_path(from, extension);
+    if Path::new(&backup).exists() {
+        std::fs::remove_file(&backup)?;
+    }
+    let audit = audit_path(from, extension);
+    if Path::new(&audit).exists() {
+        std::fs::rename(&audit, audit_path(to, extension))?;
+    }
+    Ok(())
+}
+
+/// help on the commands of `manage_saves`
+pub const MANAGE_SAVES_HELP: &str = "‘d’ and a number to delete that save, ‘r’, a number, and a name to rename it";
+
+/// Delete or rename a save of the list printed by `print_saves`, if the input is a command
+///
+/// The commands are `d <number>` and `r <number> <new name>`; new names without a directory
+/// are in `dir`. The returned message tells how it went, and `None` is returned if the input
+/// is not a command.
+pub fn manage_saves(input: &str, saves: &[SaveInfo], dir: Option<&Path>, extension: &str) -> Option<String> {
+    let mut words = input.split_whitespace();
+    let command = words.next()?;
+    if command != "d" && command != "r" {
+        return None;
+    }
+    let save = match words.next().and_then(|n| pick_save(saves, n)) {
+        Some(save) => save,
+        None => return Some("No save with this number".to_string())
+    };
+    let result = if command == "d" {
+        delete_save(&save.path, extension).map(|_| format!("{} deleted", save.path))
+    } else {
+        let name = words.collect::<Vec<_>>().join(" ");
+        if name.is_empty() {
+            return Some("Missing new name".to_string());
+        }
+        let new_path = crate::paths::in_dir(&name, dir);
+        rename_save(&save.path, &new_path, extension).map(|_| format!("{} renamed to {}", save.path, new_path))
+    };
+    Some(result.unwrap_or_else(|e| format!("Could not change {}: {}", save.path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{ game_to_bytes, EmptyDeckRule, MulliganRule, DEFAULT_JOKER_PENALTY };
+    use crate::sequence_cards::*;
+    use crate::table::Table;
+    use crate::hand::Hand;
+    use crate::deck::Deck;
+
+    #[test]
+    fn saves_of_a_directory() {
+        let dir = std::env::temp_dir().join(format!("machiavelli_save_list_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = Config { n_decks: 1, n_jokers: 2, n_cards_to_start: 3, custom_rule_jokers: false, n_players: 2,
+                              empty_deck: EmptyDeckRule::Draw, joker_penalty: DEFAULT_JOKER_PENALTY,
+                              mulligan: MulliganRule::Off, draw_for_first: false };
+        let names = vec!["Alice".to_string(), "Bob".to_string()];
+        let hands = Hand::deal(&names, vec![Sequence::from_cards(&[Joker]), Sequence::from_cards(&[Joker, Joker])]);
+        let bytes = game_to_bytes(0, 1, &Table::new(), &hands, &Deck::new(), &config);
+        for name in &["game.sav", "game_bak.sav", ".game.sav.1.tmp"] {
+            let path = dir.join(name).to_string_lossy().to_string();
+            let metadata = SaveMetadata::new(3, 5, 1, &hands, &config);
+            std::fs::write(&path, encode::encrypt_save(&bytes, &metadata.to_bytes(), &path, &mut rand::thread_rng()))
+                .unwrap();
+        }
+        std::fs::write(dir.join("notes.sav"), b"not a save").unwrap();
+
+        // a save without metadata
+        let old = dir.join("old.sav").to_string_lossy().to_string();
+        std::fs::write(&old, encode::xor(&bytes, b"old.sav")).unwrap();
+
+        let saves = list_saves(&dir, ".sav");
+        assert_eq!(2, saves.len());
+        let new = saves.iter().find(|s| s.path.ends_with("game.sav")).unwrap();
+        assert_eq!(vec![("Alice".to_string(), 1), ("Bob".to_string(), 2)], new.players);
+        assert!(new.describe().ends_with(", round 3, turn 6): Alice (1 card), Bob (2 cards, to play); 1 deck, 2 jokers, 3 cards each"));
+        assert!(!new.is_finished());
+        let old = saves.iter().find(|s| s.path == old).unwrap();
+        assert_eq!(new.players, old.players);
+        assert_eq!(None, old.round_and_turn);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn save_slots() {
+        let dir = std::env::temp_dir().join(format!("machiavelli_save_slots_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        let config = Config { n_decks: 1, n_jokers: 2, n_cards_to_start: 3, custom_rule_jokers: false, n_players: 2,
+                              empty_deck: EmptyDeckRule::Draw, joker_penalty: DEFAULT_JOKER_PENALTY,
+                              mulligan: MulliganRule::Off, draw_for_first: false };
+        let hands = Hand::deal(&["Alice".to_string(), "Bob".to_string()],
+                               vec![Sequence::from_cards(&[Joker]), Sequence::from_cards(&[Joker, Joker])]);
+        let bytes = game_to_bytes(0, 1, &Table::new(), &hands, &Deck::new(), &config);
+        let metadata = SaveMetadata::new(2, 3, 1, &hands, &config).to_bytes();
+        let game = path("game.sav");
+        std::fs::write(&game, encode::encrypt_save(&bytes, &metadata, &game, &mut rand::thread_rng())).unwrap();
+        std::fs::write(path("game_bak.sav"), b"backup").unwrap();
+        std::fs::write(path("game.audit"), b"audit").unwrap();
+
+        // a free name is found next to the existing save
+        assert_eq!(path("other.sav"), versioned_name(&path("other.sav"), ".sav"));
+        let versioned = versioned_name(&game, ".sav");
+        assert!(versioned.starts_with(&path("game_")) && versioned.ends_with("_1.sav"));
+        std::fs::write(&versioned, b"").unwrap();
+        assert!(versioned_name(&game, ".sav").ends_with("_2.sav"));
+        std::fs::remove_file(&versioned).unwrap();
+
+        // renaming encrypts the game for its new name, and keeps the audit log
+        let renamed = path("renamed.sav");
+        rename_save(&game, &renamed, ".sav").unwrap();
+        assert!(!Path::new(&game).exists() && !Path::new(&path("game_bak.sav")).exists());
+        assert_eq!(b"audit".to_vec(), std::fs::read(path("renamed.audit")).unwrap());
+        let data = std::fs::read(&renamed).unwrap();
+        assert_eq!(bytes, encode::decrypt_save(&data, &renamed).unwrap());
+        assert_eq!(Some(&metadata[..]), encode::save_metadata(&data));
+
+        // or with a command, by its number in the list
+        let saves = list_saves(&dir, ".sav");
+        assert_eq!(None, manage_saves("1", &saves, Some(&dir), ".sav"));
+        assert_eq!(Some("No save with this number".to_string()), manage_saves("d 2", &saves, Some(&dir), ".sav"));
+        assert_eq!(Some("Missing new name".to_string()), manage_saves("r 1", &saves, Some(&dir), ".sav"));
+        manage_saves("r 1 again.sav", &saves, Some(&dir), ".sav").unwrap();
+        let saves = list_saves(&dir, ".sav");
+        assert_eq!(path("again.sav"), saves[0].path);
+        manage_saves("r 1 renamed.sav", &saves, Some(&dir), ".sav").unwrap();
+
+        // a save is not renamed over another one
+        std::fs::write(path("taken.sav"), b"").unwrap();
+        assert_eq!(io::ErrorKind::AlreadyExists, rename_save(&renamed, &path("taken.sav"), ".sav").unwrap_err().kind());
+
+        delete_save(&renamed, ".sav").unwrap();
+        assert!(!Path::new(&renamed).exists() && !Path::new(&path("renamed.audit")).exists());
+        assert!(delete_save(&renamed, ".sav").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
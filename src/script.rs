@@ -0,0 +1,633 @@
+//! Games played from a script, without a terminal
+//!
+//! A script is a text file (or what is piped to `machiavelli script -`) with one action per line,
+//! played in turn by the current player. Before the first action, it can set up the game:
+//!
+//! * `key = value` lines set the rules, with the keys of the config file (see `parse_config`)
+//! * `seed N` shuffles the deck with the seed `N` (by default, the seed is random)
+//! * `deck C C ...` replaces the shuffled deck by the given cards, in the order they are drawn
+//!   (cards are written as in the snapshots, like `QS`, `10H`, or `*` for a joker)
+//! * `players Alice Bob ...` names the players (by default, `Player 1`, `Player 2`, ...)
+//! * `bot Alice command ...` lets an external program play for Alice (see `bot::ExternalBot`);
+//!   the script then has no action for her
+//!
+//! The actions are those of the client: `e` ends the turn (picking a card if nothing was
+//! played), `p x y ...` plays the cards x, y, ..., `t x y ...` takes the sequences x, y, ... from
+//! the table, `a x y z ...` adds the cards y, z, ... to sequence x, `d x y` splits sequence x
+//! after its first y cards (taking the halves which are not valid), `g` gives up, and `r` and `s`
+//! sort the hand. As in the client, the cards taken from the table are numbered after those of
+//! the hand, and the indices refer to the cards and sequences before the action. An action may
+//! start with the name of the player followed by `:`, which is then checked. A line can also hold
+//! several actions separated by `;`, played as a whole (see the `commands` module). Empty lines
+//! and what follows a `#` are ignored.
+//!
+//! The game stops at the first invalid action, and when it is over any line left is an error,
+//! so that scripts can be used as regression tests. `ScriptedGame::report` gives the outcome
+//! and the final state, with all the hands.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{ BufRead, Write };
+use rand::{ Rng, SeedableRng };
+use rand::rngs::StdRng;
+use crate::{ Config, EmptyDeckRule, parse_config, round_summary, round_winner };
+use crate::bot::ExternalBot;
+use crate::commands::{ self, Executed, TurnCards };
+use crate::deck::Deck;
+use crate::hand::{ self, Hand };
+use crate::invariants::CardLedger;
+use crate::plugins;
+use crate::sequence_cards::{ Card, Sequence };
+use crate::snapshot::{ card_code, parse_card_code, snapshot, GameSnapshot, Visibility };
+use crate::stalemate::StalemateDetector;
+use crate::table::Table;
+use crate::turn::Ongoing;
+use crate::turn_order::TurnOrder;
+
+/// How a scripted game ended
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Outcome {
+    /// a player laid down all their cards
+    Won(usize),
+    /// the deck was empty with the `draw` rule
+    Draw,
+    /// nobody could play any more with the `score` rule, and the player with the fewest cards
+    /// left, if there is only one
+    Stalemate(Option<usize>)
+}
+
+/// Invalid script, with the line where the problem is if it comes from one
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScriptError {
+    pub line: Option<usize>,
+    pub message: String
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message)
+        }
+    }
+}
+
+/// Game played from a script
+#[derive(Debug, Clone)]
+pub struct ScriptedGame {
+    pub config: Config,
+    pub table: Table,
+    pub hands: Vec<Hand>,
+    pub deck: Deck,
+    /// how the game ended, if it did
+    pub outcome: Option<Outcome>,
+    /// number of turns played
+    pub n_turns: usize,
+    order: TurnOrder,
+    turn: Ongoing,
+    cards_from_table: Sequence,
+    /// number of cards in the deck and in the hand of the player at the start of the turn
+    n_cards_start: (usize, usize),
+    stalemate: StalemateDetector,
+    ledger: CardLedger,
+    rng: StdRng
+}
+
+/// Play a script, and return the game where it stopped
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::script::{ run, Outcome };
+///
+/// let script = "n_cards_to_start = 3\n\
+///               custom_rule_jokers = false\n\
+///               deck AH 2H 3H 7S 8C KD 5D\n\
+///               players Alice Bob\n\
+///               \n\
+///               Alice: p 1 2 3\n";
+/// let game = run(script).unwrap();
+///
+/// assert_eq!(Some(Outcome::Won(0)), game.outcome);
+/// assert!(game.report().starts_with("Alice wins!"));
+///
+/// let error = run("players Alice Bob\nBob: e\n").unwrap_err();
+/// assert_eq!("line 2: it is the turn of Alice, not Bob", error.to_string());
+/// ```
+pub fn run(script: &str) -> Result<ScriptedGame, ScriptError> {
+    run_with(script, |_, _, _| true)
+}
+
+/// Play a script as `run` does, calling `after_action` with the game, the line, and the action
+/// after each action played, including the turns of the external bots
+///
+/// The script stops early, without an error, when `after_action` returns `false`.
+pub fn run_with<F>(script: &str, mut after_action: F) -> Result<ScriptedGame, ScriptError>
+    where F: FnMut(&ScriptedGame, usize, &str) -> bool
+{
+    let lines: Vec<(usize, &str)> = script.lines()
+        .map(|l| l.split('#').next().unwrap_or("").trim())
+        .enumerate()
+        .map(|(i, l)| (i + 1, l))
+        .filter(|(_, l)| !l.is_empty())
+        .collect();
+    let n_setup = lines.iter().position(|(_, l)| !is_setup(l)).unwrap_or(lines.len());
+    let mut game = ScriptedGame::set_up(script, &lines[..n_setup])?;
+
+    // start the external bots
+    let mut bots = HashMap::new();
+    for &(i, line) in lines[..n_setup].iter().filter(|(_, l)| l.starts_with("bot ")) {
+        let error = |message: String| ScriptError { line: Some(i), message };
+        let (name, command) = line["bot ".len()..].trim().split_once(' ')
+            .ok_or_else(|| error("usage: bot <player> <command>".to_string()))?;
+        let player = game.hands.iter().position(|h| h.owner() == name)
+            .ok_or_else(|| error(format!("no player is called {}", name)))?;
+        let bot = ExternalBot::spawn(command.trim())
+            .map_err(|e| error(format!("could not start the bot of {}: {}", name, e)))?;
+        bots.insert(player, (i, bot));
+    }
+
+    if !play_bots(&mut game, &mut bots, &mut after_action)? {
+        return Ok(game);
+    }
+    for &(i, line) in &lines[n_setup..] {
+        game.play(line).map_err(|message| ScriptError { line: Some(i), message })?;
+        if !after_action(&game, i, line) || !play_bots(&mut game, &mut bots, &mut after_action)? {
+            break;
+        }
+    }
+    Ok(game)
+}
+
+/// Let the external bots play as long as it is the turn of one of them, and return `false` if
+/// `after_action` stopped the script
+fn play_bots<R, W, F>(game: &mut ScriptedGame, bots: &mut HashMap<usize, (usize, ExternalBot<R, W>)>,
+                      after_action: &mut F) -> Result<bool, ScriptError>
+    where R: BufRead, W: Write, F: FnMut(&ScriptedGame, usize, &str) -> bool
+{
+    while game.outcome.is_none() {
+        let player = game.current_player();
+        let (line, bot) = match bots.get_mut(&player) {
+            Some(bot) => bot,
+            None => break
+        };
+        bot.play_turn(game).map_err(|e| ScriptError { line: Some(*line), message: format!("bot error: {}", e) })?;
+        if !after_action(game, *line, &format!("turn of the bot of {}", game.hands[player].owner())) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// whether a line sets up the game instead of playing an action
+fn is_setup(line: &str) -> bool {
+    line.contains('=') || ["seed", "deck", "players", "bot"].contains(&line.split(' ').next().unwrap_or(""))
+}
+
+impl ScriptedGame {
+
+    /// Deal the cards with the setup lines of a script
+    fn set_up(script: &str, setup: &[(usize, &str)]) -> Result<ScriptedGame, ScriptError> {
+
+        // the lines of the config keep their numbers, so that its errors point to them
+        let config_lines: Vec<usize> = setup.iter().filter(|(_, l)| l.contains('=')).map(|&(i, _)| i).collect();
+        let config_text: Vec<&str> = script.lines().enumerate()
+            .map(|(i, l)| if config_lines.contains(&(i + 1)) { l } else { "" })
+            .collect();
+        let (config, _) = parse_config(&config_text.join("\n"))
+            .map_err(|e| ScriptError { line: None, message: e.message })?;
+
+        let mut seed = None;
+        let mut cards = None;
+        let mut names: Vec<String> = (1..=config.n_players).map(|i| format!("Player {}", i)).collect();
+        for &(i, line) in setup.iter().filter(|(_, l)| !l.contains('=')) {
+            let error = |message: String| ScriptError { line: Some(i), message };
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("seed") => seed = Some(words.next().and_then(|w| w.parse::<u64>().ok())
+                    .ok_or_else(|| error("the seed should be a positive integer".to_string()))?),
+                Some("deck") => cards = Some(words.map(|w| parse_card_code(w)
+                    .ok_or_else(|| error(format!("unknown card `{}`", w)))).collect::<Result<Vec<_>, _>>()?),
+                Some("players") => {
+                    names = words.map(str::to_string).collect();
+                    if names.len() != config.n_players as usize {
+                        return Err(error(format!("{} names for {} players", names.len(), config.n_players)));
+                    }
+                },
+                _ => ()
+            };
+        }
+
+        Ok(ScriptedGame::new(config, names, seed, cards))
+    }
+
+    /// Game between players with the given names, with the deck shuffled with `seed` (a random
+    /// one by default), or made of the given cards, in the order they are drawn
+    pub fn new(config: Config, names: Vec<String>, seed: Option<u64>, cards: Option<Vec<Card>>) -> ScriptedGame {
+        let mut rng = StdRng::seed_from_u64(seed.unwrap_or_else(|| rand::thread_rng().gen()));
+        let mut deck = match cards {
+            Some(mut cards) => {
+                cards.reverse();
+                Deck::from_cards(&cards)
+            },
+            None => Deck::shuffled(config.n_decks, config.n_jokers, &mut rng)
+        };
+        let dealt: Vec<Sequence> = names.iter()
+            .map(|_| Sequence::from_cards(&deck.draw_n(config.n_cards_to_start as usize)))
+            .collect();
+        let hands = Hand::deal(&names, dealt);
+        let table = Table::new();
+        let ledger = CardLedger::new(&table, &hands, &deck, &Sequence::new());
+        let mut game = ScriptedGame {
+            order: TurnOrder::new(names.len(), 0),
+            turn: Ongoing::start(&table, &Sequence::new()),
+            cards_from_table: Sequence::new(),
+            n_cards_start: (0, 0),
+            stalemate: StalemateDetector::new(names.len()),
+            outcome: None,
+            n_turns: 0,
+            config,
+            table,
+            hands,
+            deck,
+            ledger,
+            rng
+        };
+        game.start_turn();
+        game
+    }
+
+    /// Player whose turn it is
+    pub fn current_player(&self) -> usize {
+        self.order.current()
+    }
+
+    /// Cards taken from the table during the current turn and not played yet
+    pub fn cards_from_table(&self) -> &Sequence {
+        &self.cards_from_table
+    }
+
+    /// Play an action of the current player, written as in a script
+    pub fn play(&mut self, action: &str) -> Result<(), String> {
+        if self.outcome.is_some() {
+            return Err("the game is over".to_string());
+        }
+        let player = self.order.current();
+        let action = match action.split_once(':') {
+            Some((name, _)) if name.trim() != self.hands[player].owner() => {
+                return Err(format!("it is the turn of {}, not {}", self.hands[player].owner(), name.trim()));
+            },
+            Some((_, action)) => action.trim(),
+            None => action
+        };
+
+        // a list of actions is played as a whole
+        if commands::is_list(action) {
+            let steps = commands::parse(action)?;
+            let name = self.hands[player].owner().to_string();
+            let turn = std::mem::replace(&mut self.turn, Ongoing::start(&Table::new(), &Sequence::new()));
+            let cards = TurnCards { table: &mut self.table, hand: &mut self.hands[player],
+                                    cards_from_table: &mut self.cards_from_table, deck: &mut self.deck };
+            let ended = match commands::execute(&steps, turn, &name, cards, self.config.custom_rule_jokers) {
+                Ok((Executed::Ongoing(turn), _)) => {
+                    self.turn = turn;
+                    false
+                },
+                Ok((Executed::Ended(_), _)) => true,
+                Err((turn, message)) => {
+                    self.turn = turn;
+                    return Err(message);
+                }
+            };
+            self.after_action(player, ended);
+            return Ok(());
+        }
+
+        let mut words = action.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let args = words.map(|w| w.parse::<usize>().map_err(|_| format!("`{}` is not a number", w)))
+            .collect::<Result<Vec<usize>, String>>()?;
+        let custom_rule_jokers = self.config.custom_rule_jokers;
+        let hand = &mut self.hands[player];
+
+        // an ended turn is replaced when the next one starts
+        let ended_turn = || Ongoing::start(&Table::new(), &Sequence::new());
+        let turn = std::mem::replace(&mut self.turn, ended_turn());
+        let result = match (command, turn) {
+            ("e", Ongoing::Draw(draw)) => match draw.draw(hand, &mut self.deck, custom_rule_jokers) {
+                Ok(_) => Ok((ended_turn(), true)),
+                Err((draw, m)) => Err((Ongoing::Draw(draw), m))
+            },
+            ("e", Ongoing::Meld(meld)) => match meld.pass(hand, &self.cards_from_table, custom_rule_jokers) {
+                Ok(_) => Ok((ended_turn(), true)),
+                Err((meld, m)) => Err((Ongoing::Meld(meld), m))
+            },
+            ("p", turn) => {
+                let (hand_before, cards_before) = (hand.clone(), self.cards_from_table.clone());
+                match take_cards(hand, &mut self.cards_from_table, &args) {
+                    Ok(mut seq) => {
+                        let played = seq.to_vec();
+                        let validity = if seq.is_valid() {
+                            plugins::registered().validate_meld(&seq)
+                        } else {
+                            Err(format!("{:?} is not a valid sequence", played))
+                        };
+                        match validity {
+                            Ok(()) => {
+                                self.table.add(seq);
+                                Ok((turn.meld(), false))
+                            },
+                            Err(m) => {
+                                *hand = hand_before;
+                                self.cards_from_table = cards_before;
+                                Err((turn, m))
+                            }
+                        }
+                    },
+                    Err(m) => Err((turn, m))
+                }
+            },
+            ("t", turn) => {
+                let mut sequences = args.clone();
+                sequences.sort_unstable();
+                sequences.dedup();
+                if args.is_empty() || sequences.iter().any(|&n| n == 0 || n > self.table.number_sequences()) {
+                    Err((turn, "this sequence is not on the table".to_string()))
+                } else {
+                    for &n in sequences.iter().rev() {
+                        if let Some(seq) = self.table.take(n) {
+                            self.cards_from_table.merge(seq.reverse());
+                        }
+                    }
+                    Ok((turn.meld(), false))
+                }
+            },
+            ("a", turn) if !args.is_empty() => match self.table.get(args[0]).cloned() {
+                Some(mut target) => {
+                    let (hand_before, cards_before) = (hand.clone(), self.cards_from_table.clone());
+                    match take_cards(hand, &mut self.cards_from_table, &args[1..]) {
+                        Ok(added) => {
+                            target.merge(added);
+                            let validity = if target.is_valid() {
+                                plugins::registered().validate_meld(&target)
+                            } else {
+                                Err(format!("{:?} is not a valid sequence", target.to_vec()))
+                            };
+                            match validity {
+                                Ok(()) => {
+                                    self.table.take(args[0]);
+                                    self.table.add(target);
+                                    Ok((turn.meld(), false))
+                                },
+                                Err(m) => {
+                                    *hand = hand_before;
+                                    self.cards_from_table = cards_before;
+                                    Err((turn, m))
+                                }
+                            }
+                        },
+                        Err(m) => Err((turn, m))
+                    }
+                },
+                None => Err((turn, format!("sequence {} is not on the table", args[0])))
+            },
+            ("d", turn) => match args[..] {
+                [n, index] => match self.table.split(n, index) {
+                    Some(taken) => {
+                        self.cards_from_table.merge(taken);
+                        Ok((turn.meld(), false))
+                    },
+                    None => Err((turn, format!("sequence {} can not be split after its first {} cards", n, index)))
+                },
+                _ => Err((turn, "a split needs a sequence and a number of cards".to_string()))
+            },
+            ("g", Ongoing::Meld(meld)) => {
+                meld.give_up(&mut self.table, hand, &mut self.deck, &mut self.cards_from_table);
+                Ok((ended_turn(), true))
+            },
+            ("g", turn) => Err((turn, "there is nothing to give up".to_string())),
+            ("r", turn) => {
+                hand.sort_by_rank();
+                self.cards_from_table.sort_by_rank();
+                Ok((turn, false))
+            },
+            ("s", turn) => {
+                hand.sort_by_suit();
+                self.cards_from_table.sort_by_suit();
+                Ok((turn, false))
+            },
+            (_, turn) => Err((turn, format!("unknown action `{}`", action)))
+        };
+        let (turn, ended) = match result {
+            Ok(played) => played,
+            Err((turn, message)) => {
+                self.turn = turn;
+                return Err(message);
+            }
+        };
+        self.turn = turn;
+        self.after_action(player, ended);
+        Ok(())
+    }
+
+    /// Check the cards after an action of a player, and end the turn if the action did or if they
+    /// laid down all their cards
+    fn after_action(&mut self, player: usize, ended: bool) {
+        self.ledger.assert_holds(&self.table, &self.hands, &self.deck, &self.cards_from_table, "a scripted action");
+
+        // as in the client, the turn ends once all the cards are laid down
+        let emptied = self.hands[player].number_cards() == 0 && self.cards_from_table.number_cards() == 0;
+        if ended || emptied {
+            self.end_turn();
+        }
+    }
+
+    /// Apply the rule for an empty deck, and start the turn of the current player
+    fn start_turn(&mut self) {
+        if self.deck.remaining() == 0 {
+            match self.config.empty_deck {
+                EmptyDeckRule::NewDeck => {
+                    self.deck.refill(&mut self.rng);
+                },
+                EmptyDeckRule::Draw => {
+                    self.outcome = Some(Outcome::Draw);
+                    return;
+                },
+                EmptyDeckRule::Score => ()
+            }
+        }
+        let hand = &mut self.hands[self.order.current()];
+        hand.start_turn();
+        self.n_cards_start = (self.deck.remaining(), hand.number_cards());
+        self.turn = Ongoing::start(&self.table, hand);
+    }
+
+    /// End the turn of the current player, and start that of the next one unless the game is over
+    fn end_turn(&mut self) {
+        let player = self.order.current();
+        let n_drawn = self.n_cards_start.0.saturating_sub(self.deck.remaining());
+        self.hands[player].end_turn(n_drawn);
+        self.table.normalize();
+        self.n_turns += 1;
+        if self.hands[player].number_cards() == 0 {
+            self.outcome = Some(Outcome::Won(player));
+            return;
+        }
+        if self.config.empty_deck == EmptyDeckRule::Score {
+            let played = self.hands[player].number_cards() < self.n_cards_start.1;
+            if self.stalemate.record_turn(self.deck.remaining() == 0, played) {
+                self.outcome = Some(Outcome::Stalemate(round_winner(&self.hands, &self.config.point_rules())));
+                return;
+            }
+        }
+        self.order.advance();
+        self.start_turn();
+    }
+
+    /// Outcome of the game, the cards left in each hand, and the final state as JSON
+    pub fn report(&self) -> String {
+        let names = hand::owners(&self.hands);
+        let outcome = match self.outcome {
+            Some(Outcome::Won(i)) => format!("{} wins!", names[i]),
+            Some(Outcome::Draw) => "No more cards in the deck—it's a draw!".to_string(),
+            Some(Outcome::Stalemate(Some(i))) =>
+                format!("Nobody can play any more—{} wins with the fewest cards left!", names[i]),
+            Some(Outcome::Stalemate(None)) => "Nobody can play any more—it's a draw!".to_string(),
+            None => format!("The script stops during the turn of {}.", names[self.order.current()])
+        };
+        format!("{}\n{}\n{}\n", outcome, round_summary(&self.hands, &self.config.point_rules()),
+                self.snapshot().to_json())
+    }
+
+    /// Snapshot of the game, with all the hands
+    pub fn snapshot(&self) -> GameSnapshot {
+        snapshot(&self.table, &self.hands, &self.deck, self.order.current(), Visibility::All)
+    }
+
+    /// Everything the game holds, for debugging: the turn and its phase, all the hands, the deck
+    /// in the order it is drawn, the table, and whether the cards are all there and the sequences
+    /// of the table valid
+    ///
+    /// The cards are written as in the snapshots, without colours.
+    pub fn debug_state(&self) -> String {
+        let codes = |cards: &[Card]| match cards.len() {
+            0 => "-".to_string(),
+            _ => cards.iter().map(card_code).collect::<Vec<_>>().join(" ")
+        };
+        let player = self.order.current();
+        let phase = match self.turn {
+            Ongoing::Draw(_) => "nothing played yet",
+            Ongoing::Meld(_) => "melding"
+        };
+        let mut res = match self.outcome {
+            Some(outcome) => format!("Game over after {} turns: {:?}\n", self.n_turns, outcome),
+            None => format!("Turn {} of {} ({})\n", self.n_turns + 1, self.hands[player].owner(), phase)
+        };
+        res += &format!("Cards from the table: {}\nHands:\n", codes(&self.cards_from_table.to_vec()));
+        for (i, hand) in self.hands.iter().enumerate() {
+            res += &format!("{} {}{} ({} cards): {}\n", if i == player { ">" } else { " " }, hand.owner(),
+                            if hand.has_opened() { ", opened" } else { "" }, hand.number_cards(),
+                            codes(&hand.cards().to_vec()));
+        }
+        let deck: Vec<Card> = self.deck.cards().iter().rev().cloned().collect();
+        res += &format!("Deck ({} cards, next first): {}\nTable:\n", deck.len(), codes(&deck));
+        let mut invalid = Vec::new();
+        for n in 1..=self.table.number_sequences() {
+            if let Some(seq) = self.table.get(n) {
+                res += &format!("{}: {}\n", n, codes(&seq.to_vec()));
+                if !seq.clone().is_valid() {
+                    invalid.push(n.to_string());
+                }
+            }
+        }
+        res += &match self.ledger.check(&self.table, &self.hands, &self.deck, &self.cards_from_table) {
+            Ok(()) => "Cards: all accounted for\n".to_string(),
+            Err(e) => format!("Cards: {}\n", e)
+        };
+        if !invalid.is_empty() {
+            res += &format!("Invalid sequences on the table: {}\n", invalid.join(", "));
+        }
+        res
+    }
+}
+
+/// Take the cards with the given indices from the hand and, after it, the cards taken from the
+/// table, in the order of the indices
+fn take_cards(hand: &mut Sequence, cards_from_table: &mut Sequence, indices: &[usize]) -> Result<Sequence, String> {
+    let n_hand = hand.number_cards();
+    let all: Vec<_> = hand.to_vec().into_iter().chain(cards_from_table.to_vec()).collect();
+    let mut sorted = indices.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    if indices.is_empty() || sorted.len() < indices.len() || sorted.iter().any(|&i| i == 0 || i > all.len()) {
+        return Err(format!("the cards should be distinct numbers between 1 and {}", all.len()));
+    }
+    let seq = Sequence::from_cards(&indices.iter().map(|&i| all[i - 1].clone()).collect::<Vec<_>>());
+    for &i in sorted.iter().rev() {
+        if i <= n_hand {
+            hand.take_card(i);
+        } else {
+            cards_from_table.take_card(i - n_hand);
+        }
+    }
+    Ok(seq)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const SETUP: &str = "n_cards_to_start = 4\ncustom_rule_jokers = false\nempty_deck = \"draw\"\n\
+                         deck AH 2H 3H 9C  4H 5H 6H 9D  KS QS\nplayers Alice Bob\n";
+
+    #[test]
+    fn cards_taken_from_the_table_must_be_played() {
+        let script = format!("{}Alice: p 1 2 3\nAlice: e\nBob: t 1\n", SETUP);
+        let mut game = run(&script).unwrap();
+
+        // Bob holds 4H 5H 6H 9D, then AH 2H 3H from the table
+        assert_eq!(Err("You can't end your turn until you've played all the cards you've taken from the \
+                        table!".to_string()), game.play("e"));
+        let message = game.play("Bob: play 4♥ 5♥ 6♥; e").unwrap_err();
+        assert!(message.starts_with("Nothing was played: action 2 (`e`): You can't end your turn"));
+        assert_eq!(4, game.hands[1].number_cards());
+        game.play("p 5 6 7 1 2 3").unwrap();
+        game.play("e").unwrap();
+        assert_eq!(0, game.current_player());
+        assert_eq!(1, game.hands[1].number_cards());
+        game.play("e").unwrap();
+        game.play("Bob: e").unwrap();
+
+        // the deck is empty at the start of the turn of Alice
+        assert_eq!(Some(Outcome::Draw), game.outcome);
+        assert_eq!(Err("the game is over".to_string()), game.play("e"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn external_bots_play_their_turns() {
+        let bot = std::env::temp_dir().join(format!("machiavelli_bot_{}.sh", std::process::id()));
+        std::fs::write(&bot, "while read command rest; do\n\
+                                case $command in\n\
+                                  mvi) echo mviok;;\n\
+                                  go) echo bestmove e;;\n\
+                                  quit) exit 0;;\n\
+                                esac\n\
+                              done\n").unwrap();
+
+        // the bot picks a card each turn, until the deck is empty
+        let game = run(&format!("{}bot Bob sh {}\nAlice: e\n", SETUP, bot.display())).unwrap();
+        std::fs::remove_file(&bot).unwrap_or(());
+        assert_eq!(Some(Outcome::Draw), game.outcome);
+        assert_eq!(5, game.hands[1].number_cards());
+    }
+
+    #[test]
+    fn errors_point_to_their_line() {
+        let error = run(&format!("{}\n# Alice plays\nAlice: p 1 2 4\n", SETUP)).unwrap_err();
+        assert_eq!(Some(8), error.line);
+        assert_eq!(1, run("deck AH ZZ\n").unwrap_err().line.unwrap());
+        assert_eq!(None, run("n_players = 2\nn_decks = 0\n").unwrap_err().line);
+    }
+}
@@ -0,0 +1,195 @@
+//! Search for cards in the hand and on the table
+//!
+//! `/find 7♦` lists where the seven of diamonds is: its indices in the player's hand, and the
+//! sequences of the table holding it, with its position in each of them. In games with many decks,
+//! this helps to find the sequences a card can be added to. A rank alone (`/find 7`) finds the
+//! cards of this rank, a suit symbol alone (`/find ♦`) the cards of this suit, and `/find *` the
+//! jokers. Cards are written as in the `check` subcommand.
+
+use crate::check::{ name, parse_card, rank_name, suit_symbol };
+use crate::i18n::parse_rank;
+use crate::sequence_cards::{ Card, Sequence, Suit };
+use crate::table::Table;
+
+/// Cards looked for
+#[derive(Debug, PartialEq, Clone)]
+pub enum Query {
+    Card(Card),
+    Rank(u8),
+    Suit(Suit)
+}
+
+impl Query {
+
+    /// Read a card, a rank, or a suit symbol
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::search::Query;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// assert_eq!(Some(Query::Card(RegularCard(Diamond, 7))), Query::parse("7♦"));
+    /// assert_eq!(Some(Query::Rank(12)), Query::parse("Q"));
+    /// assert_eq!(Some(Query::Suit(Spade)), Query::parse("♠"));
+    /// assert_eq!(None, Query::parse("1♦"));
+    /// ```
+    pub fn parse(s: &str) -> Option<Query> {
+        let s = s.trim();
+        let suit = match s {
+            "♥" => Some(Suit::Heart),
+            "♦" => Some(Suit::Diamond),
+            "♣" => Some(Suit::Club),
+            "♠" => Some(Suit::Spade),
+            _ => None
+        };
+        if let Some(suit) = suit {
+            return Some(Query::Suit(suit));
+        }
+        if let Some(rank) = parse_rank(s) {
+            return Some(Query::Rank(rank));
+        }
+        parse_card(s).map(Query::Card)
+    }
+
+    /// Whether a card is one of those looked for
+    pub fn matches(&self, card: &Card) -> bool {
+        match (self, card) {
+            (Query::Card(c), card) => c == card,
+            (Query::Rank(r), Card::RegularCard(_, rank)) => r == rank,
+            (Query::Suit(s), Card::RegularCard(suit, _)) => s == suit,
+            _ => false
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Query::Card(card) => name(card),
+            Query::Rank(rank) => format!("Cards of rank {}", rank_name(*rank)),
+            Query::Suit(suit) => format!("Cards of suit {}", suit_symbol(*suit))
+        }
+    }
+}
+
+/// Positions of the cards found, from 1 as in the commands
+#[derive(Debug, PartialEq, Clone)]
+pub struct Found {
+    /// indices in the hand
+    pub hand: Vec<usize>,
+    /// sequences of the table, with the positions of the cards in each of them
+    pub table: Vec<(usize, Vec<usize>)>
+}
+
+// positions of the cards of a sequence matching the query
+fn positions(query: &Query, seq: &Sequence) -> Vec<usize> {
+    seq.to_vec().iter().enumerate()
+        .filter(|(_, card)| query.matches(card))
+        .map(|(i, _)| i + 1)
+        .collect()
+}
+
+/// Look for cards in a hand and on the table
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::search::*;
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::table::Table;
+///
+/// let hand = Sequence::from_cards(&[RegularCard(Heart, 7), RegularCard(Diamond, 7)]);
+/// let mut table = Table::new();
+/// table.add(Sequence::from_cards(&[RegularCard(Club, 2), RegularCard(Club, 3), RegularCard(Club, 4)]));
+/// table.add(Sequence::from_cards(&[RegularCard(Diamond, 6), RegularCard(Diamond, 7), RegularCard(Diamond, 8)]));
+///
+/// let found = find(&Query::parse("7♦").unwrap(), &hand, &table);
+/// assert_eq!(vec![2], found.hand);
+/// assert_eq!(vec![(1, vec![2])], found.table);
+/// ```
+pub fn find(query: &Query, hand: &Sequence, table: &Table) -> Found {
+    Found {
+        hand: positions(query, hand),
+        table: (1..=table.number_sequences())
+            .filter_map(|i| table.get(i).map(|seq| (i, positions(query, seq))))
+            .filter(|(_, pos)| !pos.is_empty())
+            .collect()
+    }
+}
+
+fn list(positions: &[usize]) -> String {
+    positions.iter().map(|p| p.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+impl Found {
+
+    /// Where the cards were found, in a few lines
+    pub fn render(&self, query: &Query) -> String {
+        let hand = if self.hand.is_empty() {
+            "none".to_string()
+        } else {
+            format!("card{} {}", if self.hand.len() > 1 { "s" } else { "" }, list(&self.hand))
+        };
+        let table = if self.table.is_empty() {
+            "none".to_string()
+        } else {
+            self.table.iter()
+                .map(|(i, pos)| format!("sequence {} (card{} {})", i, if pos.len() > 1 { "s" } else { "" }, list(pos)))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        format!("{}\n  In your hand: {}\n  On the table: {}\n", query.describe(), hand, table)
+    }
+}
+
+/// Read the argument of a `/find` (or `/f`) command, if the input is one
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::search::parse_find_command;
+///
+/// assert_eq!(Some("7♦"), parse_find_command("/find 7♦"));
+/// assert_eq!(Some("K"), parse_find_command("/f K"));
+/// assert_eq!(None, parse_find_command("/finder K"));
+/// ```
+pub fn parse_find_command(input: &str) -> Option<&str> {
+    let (command, arg) = input.trim().split_once(' ')?;
+    match command {
+        "/find" | "/f" => Some(arg.trim()),
+        _ => None
+    }
+}
+
+/// Answer to a `/find` command, or how to use it if the cards can not be read
+pub fn answer(arg: &str, hand: &Sequence, table: &Table) -> String {
+    match Query::parse(arg) {
+        Some(query) => find(&query, hand, table).render(&query),
+        None => format!("Unknown card ‘{}’! Usage: /find 7♦, /find 7, or /find ♦\n", arg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sequence_cards::Card::*;
+    use crate::sequence_cards::Suit::*;
+
+    #[test]
+    fn find_a_rank_across_the_table() {
+        let hand = Sequence::from_cards(&[RegularCard(Heart, 7), Joker, RegularCard(Diamond, 7)]);
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[RegularCard(Club, 7), RegularCard(Spade, 7), Joker]));
+        table.add(Sequence::from_cards(&[RegularCard(Club, 2), RegularCard(Club, 3), RegularCard(Club, 4)]));
+        table.add(Sequence::from_cards(&[RegularCard(Heart, 5), RegularCard(Heart, 6), RegularCard(Heart, 7)]));
+
+        let found = find(&Query::Rank(7), &hand, &table);
+        assert_eq!(vec![1, 3], found.hand);
+        assert_eq!(vec![(1, vec![3]), (3, vec![1, 2])], found.table);
+        assert!(found.render(&Query::Rank(7)).contains("On the table: sequence 1 (card 3), sequence 3 (cards 1, 2)"));
+
+        let found = find(&Query::parse("*").unwrap(), &hand, &table);
+        assert_eq!((vec![2], vec![(3, vec![3])]), (found.hand, found.table));
+        assert!(answer("♦", &Sequence::new(), &table).contains("In your hand: none\n  On the table: none"));
+    }
+}
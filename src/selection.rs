@@ -0,0 +1,266 @@
+//! Cards picked by their numbers
+//!
+//! To play a sequence, the player types the numbers shown next to the cards of their hand (and of
+//! the cards taken from the table, numbered after it), separated by spaces. The input is read
+//! strictly: a word which is not a number, a number no card has, or a card picked twice rejects the
+//! whole input with a message saying why, so that a typo can not lay down other cards than the
+//! player meant to. Before anything is laid down, the player sees the resulting sequence, whether
+//! it is valid, and where it goes on the table (see `PlayPreview`), and confirms or cancels.
+
+use std::fmt;
+use crate::{ plugins, reset_style_string };
+use crate::sequence_cards::*;
+use crate::table::Table;
+use crate::turn::Position;
+
+/// Reason a selection was rejected
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SelectionError {
+    /// a word which is not a number
+    InvalidToken(String),
+    /// a number no card has
+    OutOfRange(usize),
+    /// a card picked more than once
+    Repeated(usize),
+    /// a sequence which is not on the table
+    NoSequence(usize),
+    Empty
+}
+
+impl fmt::Display for SelectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelectionError::InvalidToken(token) => write!(f, "token '{}' invalid", token),
+            SelectionError::OutOfRange(i) => write!(f, "index {} out of range", i),
+            SelectionError::Repeated(i) => write!(f, "index {} given twice", i),
+            SelectionError::NoSequence(n) => write!(f, "sequence {} is not on the table", n),
+            SelectionError::Empty => write!(f, "no card selected")
+        }
+    }
+}
+
+/// Read the numbers of the cards picked among `n_cards`, in the order they were given
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::selection::*;
+///
+/// assert_eq!(Ok(vec![3, 1, 2]), parse_indices(" 3 1  2\n", 5));
+/// assert_eq!(Err(SelectionError::InvalidToken("q".to_string())), parse_indices("1 q 2", 5));
+/// assert_eq!("index 17 out of range", parse_indices("1 17", 5).unwrap_err().to_string());
+/// ```
+pub fn parse_indices(input: &str, n_cards: usize) -> Result<Vec<usize>, SelectionError> {
+    let mut res = Vec::new();
+    for token in input.split_whitespace() {
+        let i = token.parse::<usize>().map_err(|_| SelectionError::InvalidToken(token.to_string()))?;
+        if i == 0 || i > n_cards {
+            return Err(SelectionError::OutOfRange(i));
+        }
+        if res.contains(&i) {
+            return Err(SelectionError::Repeated(i));
+        }
+        res.push(i);
+    }
+    if res.is_empty() {
+        return Err(SelectionError::Empty);
+    }
+    Ok(res)
+}
+
+/// Read the sequence of the table to add cards to, among `n_sequences`, where the cards go (`<`
+/// after the sequence puts them before its cards; they go after them otherwise), and the numbers
+/// of the cards among `n_cards`
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::selection::*;
+/// use machiavelli::turn::Position;
+///
+/// assert_eq!(Ok((3, Position::End, vec![5])), parse_extension("3 5", 4, 6));
+/// assert_eq!(Ok((1, Position::Start, vec![2, 6])), parse_extension("1 < 2 6", 4, 6));
+/// assert_eq!("sequence 7 is not on the table", parse_extension("7 1", 4, 6).unwrap_err().to_string());
+/// ```
+pub fn parse_extension(input: &str, n_sequences: usize, n_cards: usize)
+    -> Result<(usize, Position, Vec<usize>), SelectionError>
+{
+    let mut tokens = input.split_whitespace().peekable();
+    let first = tokens.next().ok_or(SelectionError::Empty)?;
+    let n = first.parse::<usize>().map_err(|_| SelectionError::InvalidToken(first.to_string()))?;
+    if n == 0 || n > n_sequences {
+        return Err(SelectionError::NoSequence(n));
+    }
+    let position = match tokens.peek() {
+        Some(&"<") => Some(Position::Start),
+        Some(&">") => Some(Position::End),
+        _ => None
+    };
+    if position.is_some() {
+        tokens.next();
+    }
+    let cards = tokens.collect::<Vec<&str>>().join(" ");
+    Ok((n, position.unwrap_or(Position::End), parse_indices(&cards, n_cards)?))
+}
+
+/// Read the sequence of the table to split and the number of its cards which stay in the first
+/// half
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::selection::*;
+/// use machiavelli::table::Table;
+/// use machiavelli::sequence_cards::*;
+///
+/// let mut table = Table::new();
+/// table.add(Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5), RegularCard(Club, 6)]));
+///
+/// assert_eq!(Ok((1, 2)), parse_split(" 1 2\n", &table));
+/// assert_eq!("index 3 out of range", parse_split("1 3", &table).unwrap_err().to_string());
+/// assert_eq!("token '4' invalid", parse_split("1 2 4", &table).unwrap_err().to_string());
+/// ```
+pub fn parse_split(input: &str, table: &Table) -> Result<(usize, usize), SelectionError> {
+    let mut numbers = Vec::<usize>::new();
+    for token in input.split_whitespace() {
+        if numbers.len() == 2 {
+            return Err(SelectionError::InvalidToken(token.to_string()));
+        }
+        numbers.push(token.parse::<usize>().map_err(|_| SelectionError::InvalidToken(token.to_string()))?);
+    }
+    match numbers[..] {
+        [n, index] => {
+            let n_cards = table.get(n).ok_or(SelectionError::NoSequence(n))?.number_cards();
+            if index == 0 || index >= n_cards {
+                return Err(SelectionError::OutOfRange(index));
+            }
+            Ok((n, index))
+        },
+        _ => Err(SelectionError::Empty)
+    }
+}
+
+/// Take the cards with the given numbers out of `sources`, numbered one after the other, and
+/// return them in the order of the numbers
+///
+/// The numbers should come from `parse_indices`; those no card has are ignored.
+pub fn take_cards(sources: &mut [&mut Sequence], indices: &[usize]) -> Sequence {
+    let all: Vec<Card> = sources.iter().flat_map(|s| s.to_vec()).collect();
+    let picked: Vec<Card> = indices.iter().filter_map(|&i| all.get(i.wrapping_sub(1)).cloned()).collect();
+    let mut sorted = indices.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    for &i in sorted.iter().rev() {
+        let mut i = i;
+        for source in sources.iter_mut() {
+            let n = source.number_cards();
+            if i <= n {
+                source.take_card(i);
+                break;
+            }
+            i -= n;
+        }
+    }
+    Sequence::from_cards(&picked)
+}
+
+/// What a play would lay on the table, shown to the player before they confirm it
+#[derive(Debug, PartialEq, Clone)]
+pub struct PlayPreview {
+    /// sequence laid down, in order if it is valid
+    pub sequence: Sequence,
+    /// number of the sequence of the table it extends, if any
+    pub extended: Option<usize>,
+    /// why the sequence can not be laid down, if it can not
+    pub validity: Result<(), String>
+}
+
+impl PlayPreview {
+
+    /// Check a sequence to lay down, or a sequence of the table with the cards added to it
+    pub fn new(mut sequence: Sequence, extended: Option<usize>) -> PlayPreview {
+        let validity = if sequence.is_valid() {
+            plugins::registered().validate_meld(&sequence)
+        } else {
+            Err(format!("{}{} is not a valid sequence!", &sequence, reset_style_string()))
+        };
+        PlayPreview { sequence, extended, validity }
+    }
+
+    /// Check a sequence of the table, at position `n`, with cards added to it
+    ///
+    /// Unlike a new sequence, the cards keep the place the player gave them, until the table is
+    /// sorted at the end of the turn.
+    pub fn extension(target: &Sequence, n: usize, cards: &Sequence, position: Position) -> PlayPreview {
+        let sequence = position.extend(target, cards);
+        let checked = PlayPreview::new(sequence.clone(), Some(n));
+        PlayPreview { sequence, ..checked }
+    }
+
+    /// Whether the sequence can be laid down
+    pub fn is_valid(&self) -> bool {
+        self.validity.is_ok()
+    }
+
+    /// Lines showing the sequence, whether it is valid, and where it goes on the table
+    ///
+    /// New and extended sequences go first on the table until it is sorted at the end of the turn.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::sequence_cards::*;
+    /// use machiavelli::selection::PlayPreview;
+    ///
+    /// let seq = Sequence::from_cards(&[RegularCard(Club, 5), RegularCard(Club, 4), RegularCard(Club, 6)]);
+    /// let preview = PlayPreview::new(seq, Some(3));
+    ///
+    /// assert!(preview.is_valid());
+    /// assert!(preview.render().ends_with("\nValid: sequence 3 becomes sequence 1 of the table"));
+    /// assert!(!PlayPreview::new(Sequence::from_cards(&[RegularCard(Club, 5)]), None).is_valid());
+    /// ```
+    pub fn render(&self) -> String {
+        let status = match (&self.validity, self.extended) {
+            (Err(message), _) => format!("Not valid: {}", message),
+            (Ok(()), None) => "Valid: it becomes sequence 1 of the table".to_string(),
+            (Ok(()), Some(n)) => format!("Valid: sequence {} becomes sequence 1 of the table", n)
+        };
+        format!("Sequence: {}{}\n{}", self.sequence, reset_style_string(), status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn cards_are_taken_from_both_sources() {
+        let mut hand = Sequence::from_cards(&[RegularCard(Heart, 4), RegularCard(Heart, 9), RegularCard(Heart, 6)]);
+        let mut from_table = Sequence::from_cards(&[RegularCard(Spade, 1), RegularCard(Heart, 5)]);
+        let indices = parse_indices("1 5 3", 5).unwrap();
+        let seq = take_cards(&mut [&mut hand, &mut from_table], &indices);
+        assert_eq!(vec![RegularCard(Heart, 4), RegularCard(Heart, 5), RegularCard(Heart, 6)], seq.to_vec());
+        assert_eq!(vec![RegularCard(Heart, 9)], hand.to_vec());
+        assert_eq!(vec![RegularCard(Spade, 1)], from_table.to_vec());
+
+        assert_eq!(Err(SelectionError::Repeated(2)), parse_indices("2 1 2", 5));
+        assert_eq!(Err(SelectionError::OutOfRange(0)), parse_indices("0", 5));
+        assert_eq!(Err(SelectionError::Empty), parse_indices("  \n", 5));
+    }
+
+    #[test]
+    fn added_cards_keep_their_place() {
+        let target = Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5), RegularCard(Club, 6)]);
+        let (n, position, indices) = parse_extension("2 < 1", 2, 1).unwrap();
+        let mut hand = Sequence::from_cards(&[Joker]);
+        let added = take_cards(&mut [&mut hand], &indices);
+        let preview = PlayPreview::extension(&target, n, &added, position);
+        assert!(preview.is_valid());
+        assert_eq!(Joker, preview.sequence.to_vec()[0]);
+
+        let added = Sequence::from_cards(&[RegularCard(Heart, 7)]);
+        assert!(!PlayPreview::extension(&target, n, &added, Position::End).is_valid());
+        assert_eq!(Err(SelectionError::Empty), parse_extension("2 <", 2, 1));
+    }
+}
@@ -2,8 +2,9 @@
 
 use std::fmt;
 use std::collections::HashMap;
+use std::io::{ Read, Write };
+use rand::Rng;
 use rand::seq::SliceRandom;
-use rand::rngs::ThreadRng;
 use crate::sort::sort;
 pub use Card::*;
 pub use Suit::*;
@@ -45,7 +46,7 @@ fn int_to_suit(s: u8) -> Option<Suit> {
 
 impl Card {
 
-    fn from_byte(x: u8) -> Option<Card> {
+    pub(crate) fn from_byte(x: u8) -> Option<Card> {
         if x == 0 {
             return Some(Joker);
         }
@@ -59,38 +60,61 @@ impl Card {
         }
     }
 
-    fn to_byte(&self) -> u8 {
+    /// Number of columns the card takes on the screen
+    pub fn width(&self) -> usize {
+        match self {
+            Joker => 1,
+            RegularCard(_, value) => crate::i18n::rank_symbol(*value, crate::settings::rank_locale()).chars().count() + 1
+        }
+    }
+
+    pub(crate) fn to_byte(&self) -> u8 {
         match self {
             Joker => 0,
             RegularCard(suit, value) => (suit_to_int(*suit)-1) * MAX_VAL + value
         }
     }
 
+    /// Full name of the card in the language of `locale` (English if it is not available)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// assert_eq!("Queen of Hearts", RegularCard(Heart, 12).long_name("en"));
+    /// assert_eq!("Dame de cœur", RegularCard(Heart, 12).long_name("fr"));
+    /// assert_eq!("Joker", Joker.long_name("fr"));
+    /// ```
+    pub fn long_name(&self, locale: &str) -> String {
+        match self {
+            Joker => crate::i18n::joker_name(locale).to_string(),
+            RegularCard(suit, value) => crate::i18n::card_name(*value, *suit, locale)
+        }
+    }
+
 }
 
 impl fmt::Display for Card {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             RegularCard(suit, val) => {
-                let str_val = match val {
-                    1 => "A".to_string(),
-                    11 => "J".to_string(),
-                    12 => "Q".to_string(),
-                    13 => "K".to_string(),
-                    10 => "10".to_string(),
-                    _ => format!("{}", val)
-                };
-                let char_suit = match suit {
-                    Heart => '♥',
-                    Diamond => '♦',
-                    Club => '♣',
-                    Spade => '♠',
+                let str_val = crate::i18n::rank_symbol(*val, crate::settings::rank_locale());
+                let char_suit = match (suit, crate::settings::ascii_mode()) {
+                    (Heart, false) => '♥',
+                    (Diamond, false) => '♦',
+                    (Club, false) => '♣',
+                    (Spade, false) => '♠',
+                    (Heart, true) => 'H',
+                    (Diamond, true) => 'D',
+                    (Club, true) => 'C',
+                    (Spade, true) => 'S',
                 };
                 let color = match suit {
                     Heart => "31",
                     Diamond => "31",
-                    Club => "30",
-                    Spade => "30",
+                    Club => crate::settings::current_theme().black_cards(),
+                    Spade => crate::settings::current_theme().black_cards(),
                 };
                 write!(f, "\x1b[1;{}m{}{}", color, str_val, char_suit)
             },
@@ -99,6 +123,50 @@ impl fmt::Display for Card {
     }
 }
 
+/// default number of points of a joker left in a hand
+pub const DEFAULT_JOKER_PENALTY: u16 = 25;
+
+/// Number of points of each card left in a hand
+#[derive(Debug, PartialEq, Clone)]
+pub struct PointRules {
+    /// points of each rank, from the ace to the king
+    pub rank_values: [u16; 13],
+    pub joker_penalty: u16
+}
+
+impl PointRules {
+
+    /// Usual values: one point for an ace, the rank for cards from 2 to 10, ten points for the
+    /// jacks, queens, and kings, and `DEFAULT_JOKER_PENALTY` for a joker
+    pub fn new() -> PointRules {
+        PointRules {
+            rank_values: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 10, 10, 10],
+            joker_penalty: DEFAULT_JOKER_PENALTY
+        }
+    }
+
+    /// Same values as `new`, with a different penalty for the jokers
+    pub fn with_joker_penalty(joker_penalty: u16) -> PointRules {
+        PointRules { joker_penalty, ..PointRules::new() }
+    }
+
+    /// Points of a single card
+    pub fn card_points(&self, card: &Card) -> u16 {
+        match card {
+            Joker => self.joker_penalty,
+            RegularCard(_, rank) => self.rank_values.get((*rank as usize).wrapping_sub(1))
+                .copied()
+                .unwrap_or(0)
+        }
+    }
+}
+
+impl Default for PointRules {
+    fn default() -> Self {
+        PointRules::new()
+    }
+}
+
 /// Sequence of cards
 #[derive(Debug, PartialEq, Clone)]
 pub struct Sequence(Vec<Card>);
@@ -191,11 +259,46 @@ impl Sequence {
     /// assert_eq!(vec![0, 1, 33, 22, 51], bytes);
     /// ```
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut res = Vec::<u8>::new();
+        self.0.iter().map(|card| card.to_byte()).collect()
+    }
+
+    /// Write the bytes of a sequence, as given by `to_bytes`, without allocating them
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
         for card in &self.0 {
-            res.push(card.to_byte());
+            writer.write_all(&[card.to_byte()])?;
         }
-        res
+        Ok(())
+    }
+
+    /// Read a sequence of `n_cards` bytes written by `write_to`
+    ///
+    /// As with `from_bytes`, bytes which are not cards are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::sequence_cards::{ Sequence, Card::* , Suit::*};
+    ///
+    /// let sequence = Sequence::from_cards(&[Joker, RegularCard(Spade, 12)]);
+    /// let mut bytes = Vec::<u8>::new();
+    /// sequence.write_to(&mut bytes).unwrap();
+    /// bytes.push(7);
+    ///
+    /// let mut reader = &bytes[..];
+    /// assert_eq!(sequence, Sequence::read_from(&mut reader, 2).unwrap());
+    /// assert_eq!(&[7], reader);
+    /// assert!(Sequence::read_from(&mut reader, 2).is_err());
+    /// ```
+    pub fn read_from<R: Read>(reader: &mut R, n_cards: usize) -> std::io::Result<Sequence> {
+        let mut cards = Vec::<Card>::with_capacity(n_cards);
+        let mut byte: [u8; 1] = [0];
+        for _ in 0..n_cards {
+            reader.read_exact(&mut byte)?;
+            if let Some(card) = Card::from_byte(byte[0]) {
+                cards.push(card);
+            }
+        }
+        Ok(Sequence(cards))
     }
 
     /// Return the number of cards in the sequence
@@ -248,48 +351,7 @@ impl Sequence {
     ///             "1 2  3  4   5 6  7  8  9  10  11 12".to_string()));
     /// ```
     pub fn show_indices(&self) -> (String,String) {
-
-        let mut first_line = String::new();
-        let mut second_line = String::new();
-        let mut n_chars_1: usize;
-        let mut n_chars_2: usize = 2;
-        let mut power_of_ten: usize = 10;
-        for i in 1..=self.0.len() {
-            
-            // if i is a power of 10, increase the number of characters for the second line by 1
-            if i==power_of_ten {
-                n_chars_2 += 1;
-                power_of_ten *= 10;
-            }
-            
-            // print the current card with a space
-            let current_card = &self.0[i-1];
-            first_line.push_str(&format!("{} ", current_card));
-            
-            // see how many characters the current caerd take
-            match current_card {
-                Joker => n_chars_1 = 2,
-                RegularCard(_,10) => n_chars_1 = 4,
-                _ => n_chars_1 = 3
-            };
-
-            // print the index
-            second_line.push_str(&format!("{} ", i));
-
-            // pad the first line with spaces if necessary
-            for _ in n_chars_1..n_chars_2 {
-                first_line.push_str(&" ");
-            }
-            
-            // pad the second line with spaces if necessary
-            for _ in n_chars_2..n_chars_1 {
-                second_line.push_str(&" ");
-            }
-        }
-        
-        first_line = first_line.trim().to_string();
-        second_line = second_line.trim().to_string();
-        return (first_line.to_string(), second_line.to_string());
+        self.show_indices_shifted(0)
     }
     
     /// Return a string with the indices shifted by `n`
@@ -320,53 +382,42 @@ impl Sequence {
     ///             "2 3  4  5   6 7  8  9  10 11  12 13".to_string()));
     /// ```
     pub fn show_indices_shifted(&self, n: usize) -> (String,String) {
-
         let mut first_line = String::new();
         let mut second_line = String::new();
-        let mut n_chars_1: usize;
-        let mut n_chars_2: usize = 2;
-        let mut power_of_ten: usize = 10;
-        while power_of_ten <= n {
-            power_of_ten *= 10;
-            n_chars_2 += 1;
-        }
-        
-        for i in (n+1)..=(n+self.0.len()) {
-            
-            // if i is a power of 10, increase the number of characters for the second line by 1
-            if i==power_of_ten {
-                n_chars_2 += 1;
-                power_of_ten *= 10;
-            }
-            
-            // print the current card with a space
-            let current_card = &self.0[i-n-1];
-            first_line.push_str(&format!("{} ", current_card));
-            
-            // see how many characters the current card takes
-            match current_card {
-                Joker => n_chars_1 = 2,
-                RegularCard(_,10) => n_chars_1 = 4,
-                _ => n_chars_1 = 3
-            };
-
-            // print the index
-            second_line.push_str(&format!("{} ", i));
-
-            // pad the first line with spaces if necessary
-            for _ in n_chars_1..n_chars_2 {
-                first_line.push_str(&" ");
-            }
-            
-            // pad the second line with spaces if necessary
-            for _ in n_chars_2..n_chars_1 {
-                second_line.push_str(&" ");
-            }
+        for (i, card) in self.0.iter().enumerate() {
+
+            // each column is as wide as the widest of the card and its index
+            let index = (n + i + 1).to_string();
+            let width = card.width().max(index.len());
+            first_line += &format!("{}{} ", card, " ".repeat(width - card.width()));
+            second_line += &format!("{:<width$} ", index, width = width);
         }
-        
-        first_line = first_line.trim().to_string();
-        second_line = second_line.trim().to_string();
-        return (first_line.to_string(), second_line.to_string());
+        (first_line.trim_end().to_string(), second_line.trim_end().to_string())
+    }
+
+    /// Show the cards one per line, each after its index, starting the indices from n+1
+    ///
+    /// This is used instead of `show_indices` when a hand is too large to fit on a line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::sequence_cards::{ Sequence, Card::* , Suit::*};
+    ///
+    /// let sequence = Sequence::from_cards(&[Joker, RegularCard(Club, 10)]);
+    ///
+    /// let shown = sequence.show_indices_vertical(8);
+    /// let lines: Vec<&str> = shown.lines().collect();
+    ///
+    /// assert!(lines[0].starts_with(" 9: \u{1b}[1;34m#"));
+    /// assert!(lines[1].starts_with("10: \u{1b}[1;30m10♣"));
+    /// ```
+    pub fn show_indices_vertical(&self, n: usize) -> String {
+        let width = (n + self.0.len()).to_string().len();
+        let lines: Vec<String> = self.0.iter().enumerate()
+            .map(|(i, card)| format!("{:>width$}: {}{}", n + i + 1, card, crate::reset_style_string(), width = width))
+            .collect();
+        lines.join("\n")
     }
     
     /// Sort cards by suit
@@ -506,6 +557,8 @@ impl Sequence {
     /// * `n_jokers`: the number of jokers
     /// * `rng`: mutable reference to the random-number generator used foor shuffling
     ///
+    /// Any random-number generator can be used; a seeded one gives a reproducible deal.
+    ///
     /// # Example
     ///
     /// ```
@@ -517,7 +570,7 @@ impl Sequence {
     ///
     /// assert_eq!(162, sequence.number_cards());
     /// ```
-    pub fn multi_deck(n_decks: u8, n_jokers: u8, rng: &mut ThreadRng) -> Sequence {
+    pub fn multi_deck<R: Rng + ?Sized>(n_decks: u8, n_jokers: u8, rng: &mut R) -> Sequence {
         
         let mut deck = Sequence::new();
 
@@ -618,6 +671,35 @@ impl Sequence {
         None
     }
     
+    /// Remove one copy of a card from a sequence
+    ///
+    /// Return `false` if the card is not in the sequence.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::sequence_cards::{ Sequence, Card::* , Suit::*};
+    ///
+    /// let mut sequence = Sequence::from_cards(&[
+    ///     Joker, 
+    ///     RegularCard(Heart, 1),
+    ///     Joker, 
+    /// ]);
+    ///
+    /// assert!(sequence.remove_card(&Joker));
+    /// assert!(!sequence.remove_card(&RegularCard(Club, 1)));
+    /// assert_eq!(Sequence::from_cards(&[RegularCard(Heart, 1), Joker]), sequence);
+    /// ```
+    pub fn remove_card(&mut self, card: &Card) -> bool {
+        match self.0.iter().position(|c| c == card) {
+            Some(i) => {
+                self.0.remove(i);
+                true
+            },
+            None => false
+        }
+    }
+    
     /// Check if a sequence has a joker
     ///
     /// # Example
@@ -651,6 +733,28 @@ impl Sequence {
         false
     }
 
+    /// Total number of points of the cards, for instance those left in a hand at the end of a
+    /// round
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let hand = Sequence::from_cards(&[
+    ///     RegularCard(Heart, 1),
+    ///     RegularCard(Club, 7),
+    ///     RegularCard(Spade, 12),
+    ///     Joker,
+    /// ]);
+    ///
+    /// assert_eq!(43, hand.points(&PointRules::new()));
+    /// assert_eq!(18, hand.points(&PointRules::with_joker_penalty(0)));
+    /// ```
+    pub fn points(&self, rules: &PointRules) -> u16 {
+        self.0.iter().fold(0u16, |sum, card| sum.saturating_add(rules.card_points(card)))
+    }
+
     /// Check if a sequence if valid for the Machiavelli game
     ///
     /// # Example
@@ -730,7 +834,7 @@ impl Sequence {
     }
 
     /// randomly shuffle the sequence
-    fn shuffle(&mut self, rng: &mut ThreadRng) {
+    pub(crate) fn shuffle<R: Rng + ?Sized>(&mut self, rng: &mut R) {
         self.0.shuffle(rng);
     }
         
@@ -898,6 +1002,20 @@ mod tests {
     use Card::{ RegularCard, Joker };
     use rand::thread_rng;
 
+    #[test]
+    fn points_of_each_rank() {
+        let rules = PointRules::new();
+        let values: Vec<u16> = (1..=13).map(|r| rules.card_points(&RegularCard(Diamond, r))).collect();
+        assert_eq!(rules.rank_values.to_vec(), values);
+        assert_eq!(0, Sequence::new().points(&rules));
+    }
+
+    #[test]
+    fn points_do_not_overflow() {
+        let rules = PointRules::with_joker_penalty(u16::MAX);
+        assert_eq!(u16::MAX, Sequence::from_cards(&[Joker, Joker]).points(&rules));
+    }
+
     #[test]
     fn take_jokers_1() {
         let mut seq = Sequence::from_cards(&[
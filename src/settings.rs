@@ -0,0 +1,563 @@
+//! Per-user settings
+//!
+//! The settings are read from a small TOML file, `settings.toml`, in the configuration directory
+//! of the platform (for instance `~/.config/machiavelli/` on Linux). They are shared by the
+//! single-terminal game and the client; the layout of the hand is only used by the client, which
+//! saves it again when the player changes it during a game. How the client tells the player that
+//! their turn starts is in a `[notifications]` section, the key bindings in a `[keys]` section,
+//! and the macros, named lists of actions (see the `commands` module), in a `[macros]` section.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{ AtomicBool, AtomicU8, Ordering };
+use std::time::Duration;
+use crate::colours;
+use crate::commands;
+use crate::i18n;
+use crate::layout::{ self, HandLayout };
+use crate::solver::Budget;
+use crate::toml::{ self, quote };
+pub use crate::paths::config_dir;
+
+/// name of the settings file, in the configuration directory
+pub const SETTINGS_FILE_NAME: &str = "settings.toml";
+
+/// colours used by the light theme, which are those sent by the server
+const LIGHT_COLOURS: &str = "\x1b[30;47m";
+
+/// colour of the black cards in the light theme
+const LIGHT_BLACK_CARDS: &str = "\x1b[1;30m";
+
+/// theme in use, as given by `Theme::to_byte`
+static THEME: AtomicU8 = AtomicU8::new(0);
+
+/// whether the suits are written with letters
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+/// locale of the letters of the ace and the court cards, as an index in `i18n::LOCALES`
+static RANK_LETTERS: AtomicU8 = AtomicU8::new(0);
+
+/// Colours of the terminal
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Theme {
+    /// black on white
+    Light,
+    /// white on black
+    Dark,
+    /// the default colours of the terminal
+    Plain
+}
+
+impl Theme {
+
+    /// Parse the name of a theme (`light`, `dark`, or `plain`)
+    pub fn parse(s: &str) -> Option<Theme> {
+        match s {
+            "light" => Some(Theme::Light),
+            "dark" => Some(Theme::Dark),
+            "plain" => Some(Theme::Plain),
+            _ => None
+        }
+    }
+
+    /// Name of the theme, as accepted by `parse`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+            Theme::Plain => "plain"
+        }
+    }
+
+    /// Escape sequence setting the foreground and background colours
+    pub fn colours(&self) -> &'static str {
+        match self {
+            Theme::Light => LIGHT_COLOURS,
+            Theme::Dark => "\x1b[37;40m",
+            Theme::Plain => ""
+        }
+    }
+
+    /// Code of the colour of clubs and spades
+    pub fn black_cards(&self) -> &'static str {
+        match self {
+            Theme::Light => "30",
+            Theme::Dark => "37",
+            Theme::Plain => "39"
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Theme::Light => 0,
+            Theme::Dark => 1,
+            Theme::Plain => 2
+        }
+    }
+
+    fn from_byte(x: u8) -> Theme {
+        match x {
+            1 => Theme::Dark,
+            2 => Theme::Plain,
+            _ => Theme::Light
+        }
+    }
+}
+
+/// Theme in use in this process
+pub fn current_theme() -> Theme {
+    Theme::from_byte(THEME.load(Ordering::Relaxed))
+}
+
+/// Whether the suits are written with letters in this process
+pub fn ascii_mode() -> bool {
+    ASCII.load(Ordering::Relaxed)
+}
+
+/// Locale whose letters are used for the ace and the court cards in this process
+pub fn rank_locale() -> &'static str {
+    i18n::LOCALES.get(RANK_LETTERS.load(Ordering::Relaxed) as usize).copied().unwrap_or(i18n::DEFAULT_LOCALE)
+}
+
+/// Adapt a text rendered with the default style (for instance by the server) to the theme and
+/// ASCII mode in use, and to the letters of the ranks
+///
+/// The colours of the names are adapted to the theme, or removed if names are not coloured. In
+/// ASCII mode, the underlined numbers of the sequences changed during the previous turn are
+/// marked with a `*` instead.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::settings::restyle;
+///
+/// // nothing changes with the default settings
+/// assert_eq!("\x1b[1;30m2♣", restyle("\x1b[1;30m2♣"));
+/// ```
+pub fn restyle(s: &str) -> String {
+    let theme = current_theme();
+    let mut res = s.to_string();
+    if colours::no_colour() {
+        res = colours::strip(&res);
+    } else if theme != Theme::Light {
+        res = res.replace(&colours::name_end(Theme::Light), &colours::name_end(theme));
+    }
+    if theme != Theme::Light {
+        res = res.replace(LIGHT_COLOURS, theme.colours())
+                 .replace(LIGHT_BLACK_CARDS, &format!("\x1b[1;{}m", theme.black_cards()));
+    }
+    let locale = rank_locale();
+    if locale != i18n::DEFAULT_LOCALE {
+        for rank in [1, 11, 12, 13] {
+            let (from, to) = (i18n::rank_symbol(rank, i18n::DEFAULT_LOCALE), i18n::rank_symbol(rank, locale));
+            for suit in ['♥', '♦', '♣', '♠'] {
+                res = res.replace(&format!("{}{}", from, suit), &format!("{}{}", to, suit));
+            }
+        }
+    }
+    if ascii_mode() {
+        res = res.replace(layout::CHANGED_START, "*").replace(layout::CHANGED_END, "");
+        res = res.replace('♥', "H").replace('♦', "D").replace('♣', "C").replace('♠', "S");
+    }
+    res
+}
+
+/// Path of the settings file, if the configuration directory can be found
+pub fn settings_path() -> Option<PathBuf> {
+    config_dir().map(|d| d.join(SETTINGS_FILE_NAME))
+}
+
+/// How the client tells the player that their turn starts
+#[derive(Debug, PartialEq, Clone)]
+pub struct NotificationSettings {
+    /// ring the terminal bell
+    pub bell: bool,
+    /// send a desktop notification (with `notify-send` on Linux)
+    pub desktop: bool
+}
+
+impl NotificationSettings {
+
+    /// Default settings: ring the terminal bell, no desktop notification
+    pub fn new() -> NotificationSettings {
+        NotificationSettings {
+            bell: true,
+            desktop: false
+        }
+    }
+
+    /// Parse the settings from the content of the file used by earlier versions of the client,
+    /// `Config/client.dat`
+    ///
+    /// As for the game config, each setting is given by the first word of a line:
+    ///
+    /// * whether to ring the terminal bell (`1` for yes and `0` for no)
+    /// * whether to send a desktop notification (`1` for yes and `0` for no)
+    ///
+    /// Missing lines keep their default value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::settings::NotificationSettings;
+    ///
+    /// let settings = NotificationSettings::parse("0 terminal bell\n1 desktop notification\n");
+    ///
+    /// assert_eq!(NotificationSettings { bell: false, desktop: true }, settings);
+    /// ```
+    pub fn parse(content: &str) -> NotificationSettings {
+        let mut settings = NotificationSettings::new();
+        let mut lines = content.lines().map(|l| l.split(' ').next().unwrap_or(""));
+        if let Some(w) = lines.next() {
+            settings.bell = w != "0";
+        }
+        if let Some(w) = lines.next() {
+            settings.desktop = w == "1";
+        }
+        settings
+    }
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        NotificationSettings::new()
+    }
+}
+
+/// Preferences of a player
+#[derive(Debug, PartialEq, Clone)]
+pub struct Settings {
+    pub theme: Theme,
+    /// language of the game (only `en` for now, but the names of the cards are also available in
+    /// `fr` and `it`)
+    pub locale: String,
+    /// write the suits with letters (`H`, `D`, `C`, `S`) instead of symbols
+    pub ascii: bool,
+    /// write the ace and the court cards with the letters of the locale (`V`, `D`, `R` in French)
+    pub local_ranks: bool,
+    /// address and port of the server to use when `Config/port_client.dat` is missing
+    pub server: Option<String>,
+    /// name to use when none is given
+    pub player_name: Option<String>,
+    /// passphrase of the server, to encrypt the connection (it must be the same on both sides)
+    pub passphrase: Option<String>,
+    /// ask before laying down cards, taking a long sequence from the table or saving and quitting
+    pub confirm: bool,
+    /// time the search for a hint can take, in seconds
+    pub hint_seconds: u64,
+    /// layout of the hand in the client
+    pub layout: HandLayout,
+    /// how the client tells the player that their turn starts
+    pub notifications: NotificationSettings,
+    /// pairs `(key, command)`: typing `key` has the same effect as typing the command letter
+    pub key_bindings: Vec<(String, String)>,
+    /// pairs `(name, actions)`: typing `name` plays the actions, separated by `;`
+    pub macros: Vec<(String, String)>
+}
+
+impl Settings {
+
+    /// Default settings
+    pub fn new() -> Settings {
+        Settings {
+            theme: Theme::Light,
+            locale: "en".to_string(),
+            ascii: false,
+            local_ranks: false,
+            server: None,
+            player_name: None,
+            passphrase: None,
+            confirm: true,
+            hint_seconds: 2,
+            layout: HandLayout::Line,
+            notifications: NotificationSettings::new(),
+            key_bindings: Vec::new(),
+            macros: Vec::new()
+        }
+    }
+
+    /// Load the settings from the settings file
+    ///
+    /// If the file does not exist, it is created with the default settings so that it can be
+    /// edited.
+    pub fn load() -> Result<Settings, SettingsError> {
+        let path = match settings_path() {
+            Some(p) => p,
+            None => return Ok(Settings::new())
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(content) => Settings::parse(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let settings = Settings::new();
+                settings.save().unwrap_or(());
+                Ok(settings)
+            },
+            Err(e) => Err(SettingsError::from(e))
+        }
+    }
+
+    /// Write the settings to the settings file
+    pub fn save(&self) -> Result<(), SettingsError> {
+        let path = match settings_path() {
+            Some(p) => p,
+            None => return Err(SettingsError { message: "no configuration directory".to_string() })
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_toml())?;
+        Ok(())
+    }
+
+    /// Save a new layout of the hand to the settings file, keeping the other settings as they are
+    /// in the file
+    pub fn save_layout(layout: HandLayout) -> Result<(), SettingsError> {
+        let mut settings = Settings::load()?;
+        settings.layout = layout;
+        settings.save()
+    }
+
+    /// Parse the content of a settings file
+    ///
+    /// Missing keys keep their default value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::settings::*;
+    ///
+    /// let settings = Settings::parse("theme = \"dark\"\n\n[keys]\nend = \"e\"\n").unwrap();
+    ///
+    /// assert_eq!(Theme::Dark, settings.theme);
+    /// assert_eq!("e", settings.translate_input("end"));
+    ///
+    /// let error = Settings::parse("ascii = \"yes\"").unwrap_err();
+    /// assert_eq!("SettingsError: line 1: `ascii` should be true or false", error.to_string());
+    /// ```
+    pub fn parse(content: &str) -> Result<Settings, SettingsError> {
+        let mut settings = Settings::new();
+        for entry in toml::parse(content)? {
+            match (entry.section.as_deref(), entry.key.as_str()) {
+                (Some("notifications"), "bell") => settings.notifications.bell = entry.boolean()?,
+                (Some("notifications"), "desktop") => settings.notifications.desktop = entry.boolean()?,
+                (Some("notifications"), key) => return Err(SettingsError::from(
+                        entry.error(&format!("unknown notification setting `{}`", key)))),
+                (Some("keys"), key) => settings.key_bindings.push((key.to_string(), entry.string()?)),
+                (Some("macros"), name) => {
+                    let actions = entry.string()?;
+                    commands::parse(&actions).map_err(|m| entry.error(&format!("macro `{}`: {}", name, m)))?;
+                    settings.macros.push((name.to_string(), actions));
+                },
+                (Some(section), _) => return Err(SettingsError::from(
+                        entry.error(&format!("unknown section `{}`", section)))),
+                (None, "theme") => {
+                    settings.theme = Theme::parse(&entry.string()?).ok_or_else(|| 
+                        entry.error("the theme should be \"light\", \"dark\", or \"plain\""))?;
+                },
+                (None, "locale") => settings.locale = entry.string()?,
+                (None, "ascii") => settings.ascii = entry.boolean()?,
+                (None, "local_ranks") => settings.local_ranks = entry.boolean()?,
+                (None, "server") => settings.server = Some(entry.string()?).filter(|s| !s.is_empty()),
+                (None, "player_name") => settings.player_name = Some(entry.string()?).filter(|s| !s.is_empty()),
+                (None, "passphrase") => settings.passphrase = Some(entry.string()?).filter(|s| !s.is_empty()),
+                (None, "confirm") => settings.confirm = entry.boolean()?,
+                (None, "hint_seconds") => settings.hint_seconds = entry.integer(1, 60)? as u64,
+                (None, "layout") => {
+                    settings.layout = HandLayout::parse(&entry.string()?).ok_or_else(||
+                        entry.error("the layout should be \"line\", \"wrap\", \"suit\", or \"grid\""))?;
+                },
+                (None, key) => return Err(SettingsError::from(
+                        entry.error(&format!("unknown setting `{}`", key))))
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Write the settings in the format read by `parse`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::settings::*;
+    ///
+    /// let mut settings = Settings::new();
+    /// settings.player_name = Some("Niccolò \"il Segretario\"".to_string());
+    /// settings.key_bindings.push(("x".to_string(), "e".to_string()));
+    ///
+    /// assert_eq!(settings, Settings::parse(&settings.to_toml()).unwrap());
+    /// ```
+    pub fn to_toml(&self) -> String {
+        let mut res = String::new();
+        res += "# Machiavelli settings\n\n";
+        res += "# colours: \"light\", \"dark\", or \"plain\" (default colours of the terminal)\n";
+        res += &format!("theme = {}\n", quote(self.theme.name()));
+        res += "# language of the names of the cards: \"en\", \"fr\", or \"it\"\n";
+        res += &format!("locale = {}\n", quote(&self.locale));
+        res += "# write the suits with letters instead of symbols\n";
+        res += &format!("ascii = {}\n", self.ascii);
+        res += "# write the ace and the court cards with the letters of the locale, e.g. V, D, R in French\n";
+        res += &format!("local_ranks = {}\n", self.local_ranks);
+        res += "# server used when Config/port_client.dat is missing, e.g. \"192.168.1.10:3333\"\n";
+        res += &format!("server = {}\n", quote(self.server.as_deref().unwrap_or("")));
+        res += &format!("player_name = {}\n", quote(self.player_name.as_deref().unwrap_or("")));
+        res += "# passphrase of the server, if it encrypts the connections\n";
+        res += &format!("passphrase = {}\n", quote(self.passphrase.as_deref().unwrap_or("")));
+        res += "# ask before laying down cards, taking a long sequence from the table or saving and quitting\n";
+        res += &format!("confirm = {}\n", self.confirm);
+        res += "# longest time the search for a hint can take, in seconds (from 1 to 60)\n";
+        res += &format!("hint_seconds = {}\n", self.hint_seconds);
+        res += "# layout of your hand in the client: \"line\", \"wrap\", \"suit\", or \"grid\"\n";
+        res += &format!("layout = {}\n", quote(self.layout.name()));
+        res += "\n# how the client tells you that your turn starts\n";
+        res += "[notifications]\n";
+        res += "# ring the terminal bell\n";
+        res += &format!("bell = {}\n", self.notifications.bell);
+        res += "# send a desktop notification (with notify-send on Linux)\n";
+        res += &format!("desktop = {}\n", self.notifications.desktop);
+        res += "\n# key bindings: `key = \"command letter\"`, e.g. `x = \"e\"` to end your turn with x\n";
+        res += "[keys]\n";
+        for (key, command) in &self.key_bindings {
+            res += &format!("{} = {}\n", key, quote(command));
+        }
+        res += "\n# macros: `name = \"actions\"`, e.g. `tidy = \"sort suit\"` to sort your hand by suit with tidy\n";
+        res += "[macros]\n";
+        for (name, actions) in &self.macros {
+            res += &format!("{} = {}\n", name, quote(actions));
+        }
+        res
+    }
+
+    /// Use the theme, ASCII mode, and letters of the ranks for everything printed by this process
+    pub fn apply(&self) {
+        THEME.store(self.theme.to_byte(), Ordering::Relaxed);
+        ASCII.store(self.ascii, Ordering::Relaxed);
+        RANK_LETTERS.store(if self.local_ranks { i18n::locale_index(&self.locale) } else { 0 }, Ordering::Relaxed);
+    }
+
+    /// Budget of the search for a hint
+    pub fn hint_budget(&self) -> Budget {
+        Budget::new(Some(Duration::from_secs(self.hint_seconds)), None)
+    }
+
+    /// Replace the names of the macros in an input by their actions, or if there are none, the
+    /// first word of the input by the command it is bound to, if any
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::settings::Settings;
+    ///
+    /// let mut settings = Settings::new();
+    /// settings.key_bindings.push(("play".to_string(), "p".to_string()));
+    /// settings.macros.push(("tidy".to_string(), "sort suit".to_string()));
+    ///
+    /// assert_eq!("p 1 2 3", settings.translate_input("play 1 2 3"));
+    /// assert_eq!("t 1", settings.translate_input("t 1"));
+    /// assert_eq!("sort suit; end", settings.translate_input("tidy; end"));
+    /// ```
+    pub fn translate_input(&self, input: &str) -> String {
+        let expanded = commands::expand_macros(input, &self.macros);
+        if expanded != input {
+            return expanded;
+        }
+        let (first, rest) = match input.find(' ') {
+            Some(j) => (&input[..j], &input[j..]),
+            None => (input, "")
+        };
+        match self.key_bindings.iter().find(|(key, _)| key == first) {
+            Some((_, command)) => format!("{}{}", command, rest),
+            None => input.to_string()
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings::new()
+    }
+}
+
+/// Error reading or writing the settings
+#[derive(Debug)]
+pub struct SettingsError {
+    pub message: String
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SettingsError: {}", self.message)
+    }
+}
+
+impl std::convert::From<toml::TomlError> for SettingsError {
+    fn from(error: toml::TomlError) -> Self {
+        SettingsError { message: error.to_string() }
+    }
+}
+
+impl std::convert::From<std::io::Error> for SettingsError {
+    fn from(error: std::io::Error) -> Self {
+        SettingsError { message: format!("IO Error: {}", error) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_full_file() {
+        let content = "# comment\n\
+                       theme = \"plain\"   # trailing comment\n\
+                       locale = \"it\"\n\
+                       ascii = true\n\
+                       local_ranks = true\n\
+                       server = \"127.0.0.1:3333\"\n\
+                       player_name = \"Lorenzo # de' Medici\"\n\
+                       confirm = false\n\
+                       layout = \"suit\"\n\
+                       \n\
+                       [notifications]\n\
+                       bell = false\n\
+                       desktop = true\n\
+                       [keys]\n\
+                       x = \"e\"\n\
+                       [macros]\n\
+                       tidy = \"sort suit; sort rank\"\n";
+        let settings = Settings::parse(content).unwrap();
+        assert_eq!(Theme::Plain, settings.theme);
+        assert_eq!("it", settings.locale);
+        assert!(settings.ascii);
+        assert!(settings.local_ranks);
+        assert_eq!(Some("127.0.0.1:3333".to_string()), settings.server);
+        assert_eq!(Some("Lorenzo # de' Medici".to_string()), settings.player_name);
+        assert!(!settings.confirm);
+        assert_eq!(HandLayout::BySuit, settings.layout);
+        assert_eq!(NotificationSettings { bell: false, desktop: true }, settings.notifications);
+        assert_eq!(vec![("x".to_string(), "e".to_string())], settings.key_bindings);
+        assert_eq!(vec![("tidy".to_string(), "sort suit; sort rank".to_string())], settings.macros);
+    }
+
+    #[test]
+    fn errors_have_line_numbers() {
+        let error = Settings::parse("ascii = true\n\ntheme = \"neon\"\n").unwrap_err();
+        assert!(error.message.starts_with("line 3:"));
+        let error = Settings::parse("player_name = \"Cesare\n").unwrap_err();
+        assert_eq!("line 1: missing closing quote", error.message);
+        let error = Settings::parse("colour = \"red\"\n").unwrap_err();
+        assert_eq!("line 1: unknown setting `colour`", error.message);
+        let error = Settings::parse("[notifications]\nsound = true\n").unwrap_err();
+        assert_eq!("line 2: unknown notification setting `sound`", error.message);
+        let error = Settings::parse("layout = \"spiral\"\n").unwrap_err();
+        assert_eq!("line 1: the layout should be \"line\", \"wrap\", \"suit\", or \"grid\"", error.message);
+        let error = Settings::parse("[macros]\nwin = \"play all\"\n").unwrap_err();
+        assert_eq!("line 2: macro `win`: `play all`: unknown card `all`", error.message);
+    }
+
+    #[test]
+    fn empty_values_are_unset() {
+        let settings = Settings::parse("server = \"\"\nplayer_name = \"\"\n").unwrap();
+        assert_eq!(Settings::new(), settings);
+    }
+}
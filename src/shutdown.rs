@@ -0,0 +1,117 @@
+//! Graceful shutdown of the server
+//!
+//! When the server receives SIGINT or SIGTERM during a game, the reading half of each client
+//! connection is closed, so that the game loop stops waiting for the current player. The loop then
+//! puts the turn back as it was when it started (which is what the last autosave contains), tells
+//! the players, and ends. Before the game starts, there is nothing to save, and the server exits
+//! right away. A second signal also makes it exit right away.
+
+use std::net::{ Shutdown, TcpStream };
+use crate::connection::Connection;
+use std::sync::Mutex;
+use std::sync::atomic::{ AtomicBool, Ordering };
+
+/// Whether a shutdown was requested, and the connections to close when it is
+pub struct ShutdownSignal {
+    requested: AtomicBool,
+    in_game: AtomicBool,
+    streams: Mutex<Vec<TcpStream>>
+}
+
+impl ShutdownSignal {
+
+    /// Create a signal which has not been raised
+    pub const fn new() -> ShutdownSignal {
+        ShutdownSignal {
+            requested: AtomicBool::new(false),
+            in_game: AtomicBool::new(false),
+            streams: Mutex::new(Vec::new())
+        }
+    }
+
+    /// Check if a shutdown was requested
+    pub fn requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    /// Check if the game has started
+    pub fn in_game(&self) -> bool {
+        self.in_game.load(Ordering::SeqCst)
+    }
+
+    /// Record that the game has started, so that a shutdown goes through the game loop
+    pub fn start_game(&self) {
+        self.in_game.store(true, Ordering::SeqCst);
+    }
+
+    /// Set the connections to the clients, replacing the previous ones
+    ///
+    /// This should be called again when a player reconnects. Only TCP connections are closed.
+    pub fn watch<C: Connection>(&self, streams: &[C]) {
+        if let Ok(mut watched) = self.streams.lock() {
+            *watched = streams.iter().filter_map(|s| s.tcp_stream()?.try_clone().ok()).collect();
+        }
+    }
+
+    /// Request a shutdown, and stop reading from the clients
+    ///
+    /// Return `true` if a shutdown had already been requested.
+    pub fn request(&self) -> bool {
+        let already_requested = self.requested.swap(true, Ordering::SeqCst);
+        if let Ok(watched) = self.streams.lock() {
+            for stream in watched.iter() {
+                stream.shutdown(Shutdown::Read).unwrap_or(());
+            }
+        }
+        already_requested
+    }
+}
+
+impl Default for ShutdownSignal {
+    fn default() -> Self {
+        ShutdownSignal::new()
+    }
+}
+
+/// signal raised by the handler of SIGINT and SIGTERM
+pub static SIGNAL: ShutdownSignal = ShutdownSignal::new();
+
+/// Check if the server should shut down
+pub fn requested() -> bool {
+    SIGNAL.requested()
+}
+
+/// Handle SIGINT and SIGTERM by raising `SIGNAL`
+pub fn set_handler() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        if SIGNAL.request() || !SIGNAL.in_game() {
+            println!("\nShutting down");
+            std::process::exit(0);
+        }
+        println!("\nShutting down after saving the game (send the signal again to quit now)");
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    #[test]
+    fn request_stops_reading() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server_side, _) = listener.accept().unwrap();
+        let signal = ShutdownSignal::new();
+        signal.watch(&[server_side.try_clone().unwrap()]);
+        assert!(!signal.request());
+        assert!(signal.requested());
+        assert!(signal.request());
+
+        // the read returns at once instead of waiting for the client
+        let mut buffer = [0u8; 1];
+        assert_eq!(0, server_side.read(&mut buffer).unwrap_or(0));
+    }
+}
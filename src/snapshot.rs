@@ -0,0 +1,223 @@
+//! Read-only picture of a game, for tools which analyse it
+//!
+//! A `GameSnapshot` owns a copy of what can be seen of a game: the table, the number of cards
+//! left in the deck, and for each player their name, the size of their hand, and whether they
+//! have opened. The hands themselves are only included for the players the snapshot is allowed
+//! to show. Snapshots can be compared in tests, and converted to JSON for external analyzers and
+//! the admin endpoint.
+//!
+//! In JSON, a card is written as its rank followed by the initial of its suit (`"10H"`, `"QS"`),
+//! and a joker as `"*"`.
+
+use crate::admin::json_string;
+use crate::deck::Deck;
+use crate::hand::Hand;
+use crate::sequence_cards::{ Card, Sequence, Suit };
+use crate::table::Table;
+
+/// Hands a snapshot shows
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Visibility {
+    /// no hand, only what all the players see
+    Public,
+    /// the hand of one player, as they see the game
+    Player(usize),
+    /// all the hands
+    All
+}
+
+/// What a snapshot shows of a player
+#[derive(Debug, PartialEq, Clone)]
+pub struct PlayerSnapshot {
+    pub name: String,
+    pub n_cards: usize,
+    /// whether they have laid cards from their hand on the table this round
+    pub has_opened: bool,
+    /// their cards, if the snapshot can show them
+    pub hand: Option<Sequence>
+}
+
+/// Copy of the state of a game
+#[derive(Debug, PartialEq, Clone)]
+pub struct GameSnapshot {
+    /// seat of the player whose turn it is
+    pub current_player: usize,
+    /// players in the order of their seats
+    pub players: Vec<PlayerSnapshot>,
+    /// number of cards left in the deck
+    pub deck_remaining: usize,
+    /// sequences on the table, in the order they are shown
+    pub table: Vec<Sequence>
+}
+
+/// Snapshot of a game at the turn of `current_player`, showing the hands allowed by `visibility`
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::deck::Deck;
+/// use machiavelli::hand::Hand;
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::snapshot::{ snapshot, Visibility };
+/// use machiavelli::table::Table;
+///
+/// let mut table = Table::new();
+/// table.add(Sequence::from_cards(&[RegularCard(Heart, 1), RegularCard(Heart, 2), RegularCard(Heart, 3)]));
+/// let hands = vec![
+///     Hand::new("Alice", Sequence::from_cards(&[Joker])),
+///     Hand::new("Bob", Sequence::from_cards(&[RegularCard(Spade, 12), RegularCard(Club, 10)]))
+/// ];
+/// let deck = Deck::from_cards(&[RegularCard(Diamond, 7)]);
+///
+/// let seen_by_bob = snapshot(&table, &hands, &deck, 1, Visibility::Player(1));
+/// assert_eq!(None, seen_by_bob.players[0].hand);
+/// assert_eq!(Some(hands[1].cards().clone()), seen_by_bob.players[1].hand);
+///
+/// assert_eq!("{\"current_player\":1,\"deck_remaining\":1,\"table\":[[\"AH\",\"2H\",\"3H\"]],\"players\":[\
+///             {\"name\":\"Alice\",\"n_cards\":1,\"has_opened\":false},\
+///             {\"name\":\"Bob\",\"n_cards\":2,\"has_opened\":false,\"hand\":[\"QS\",\"10C\"]}]}",
+///            seen_by_bob.to_json());
+/// ```
+pub fn snapshot<H: AsRef<Hand>>(table: &Table, hands: &[H], deck: &Deck, current_player: usize,
+                                visibility: Visibility) -> GameSnapshot {
+    let players = hands.iter().enumerate().map(|(i, hand)| {
+        let hand = hand.as_ref();
+        let shown = match visibility {
+            Visibility::Public => false,
+            Visibility::Player(j) => i == j,
+            Visibility::All => true
+        };
+        PlayerSnapshot {
+            name: hand.owner().to_string(),
+            n_cards: hand.number_cards(),
+            has_opened: hand.has_opened(),
+            hand: if shown { Some(hand.cards().clone()) } else { None }
+        }
+    }).collect();
+    GameSnapshot {
+        current_player,
+        players,
+        deck_remaining: deck.remaining(),
+        table: (1..=table.number_sequences()).filter_map(|n| table.get(n).cloned()).collect()
+    }
+}
+
+impl GameSnapshot {
+
+    /// The same snapshot without any hand
+    pub fn public(&self) -> GameSnapshot {
+        let mut res = self.clone();
+        for player in res.players.iter_mut() {
+            player.hand = None;
+        }
+        res
+    }
+
+    /// Snapshot as a JSON object; the hands which are not shown are left out
+    pub fn to_json(&self) -> String {
+        let table: Vec<String> = self.table.iter().map(sequence_json).collect();
+        let players: Vec<String> = self.players.iter().map(|p| {
+            let hand = match &p.hand {
+                Some(hand) => format!(",\"hand\":{}", sequence_json(hand)),
+                None => String::new()
+            };
+            format!("{{\"name\":{},\"n_cards\":{},\"has_opened\":{}{}}}",
+                    json_string(&p.name), p.n_cards, p.has_opened, hand)
+        }).collect();
+        format!("{{\"current_player\":{},\"deck_remaining\":{},\"table\":[{}],\"players\":[{}]}}",
+                self.current_player, self.deck_remaining, table.join(","), players.join(","))
+    }
+}
+
+/// Short name of a card, as written in the JSON snapshots
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::snapshot::card_code;
+///
+/// assert_eq!("AD", card_code(&RegularCard(Diamond, 1)));
+/// assert_eq!("10C", card_code(&RegularCard(Club, 10)));
+/// assert_eq!("*", card_code(&Joker));
+/// ```
+pub fn card_code(card: &Card) -> String {
+    match card {
+        Card::Joker => "*".to_string(),
+        Card::RegularCard(suit, rank) => {
+            let rank = match rank {
+                1 => "A".to_string(),
+                11 => "J".to_string(),
+                12 => "Q".to_string(),
+                13 => "K".to_string(),
+                _ => rank.to_string()
+            };
+            format!("{}{}", rank, &format!("{:?}", suit)[..1])
+        }
+    }
+}
+
+/// Card with a short name written by `card_code`, in upper or lower case
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::snapshot::parse_card_code;
+///
+/// assert_eq!(Some(RegularCard(Heart, 12)), parse_card_code("QH"));
+/// assert_eq!(Some(RegularCard(Club, 10)), parse_card_code("10c"));
+/// assert_eq!(Some(Joker), parse_card_code("*"));
+/// assert_eq!(None, parse_card_code("14S"));
+/// ```
+pub fn parse_card_code(code: &str) -> Option<Card> {
+    if code == "*" {
+        return Some(Card::Joker);
+    }
+    let code = code.to_uppercase();
+    let suit = match code.chars().last()? {
+        'H' => Suit::Heart,
+        'D' => Suit::Diamond,
+        'C' => Suit::Club,
+        'S' => Suit::Spade,
+        _ => return None
+    };
+    let rank = match &code[..code.len() - 1] {
+        "A" => 1,
+        "J" => 11,
+        "Q" => 12,
+        "K" => 13,
+        n => n.parse::<u8>().ok().filter(|n| (2..=10).contains(n))?
+    };
+    Some(Card::RegularCard(suit, rank))
+}
+
+fn sequence_json(sequence: &Sequence) -> String {
+    let cards: Vec<String> = sequence.to_vec().iter().map(|c| format!("\"{}\"", card_code(c))).collect();
+    format!("[{}]", cards.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sequence_cards::Card::*;
+    use crate::sequence_cards::Suit::*;
+
+    #[test]
+    fn public_snapshots_show_no_hand() {
+        let hands = vec![
+            Hand::new("Alice", Sequence::from_cards(&[Joker])),
+            Hand::new("Bob \"B\"", Sequence::from_cards(&[RegularCard(Heart, 13)]))
+        ];
+        let all = snapshot(&Table::new(), &hands, &Deck::new(), 0, Visibility::All);
+        assert!(all.players.iter().all(|p| p.hand.is_some()));
+
+        let public = all.public();
+        assert_eq!(snapshot(&Table::new(), &hands, &Deck::new(), 0, Visibility::Public), public);
+        assert_eq!("{\"current_player\":0,\"deck_remaining\":0,\"table\":[],\"players\":[\
+                    {\"name\":\"Alice\",\"n_cards\":1,\"has_opened\":false},\
+                    {\"name\":\"Bob \\\"B\\\"\",\"n_cards\":1,\"has_opened\":false}]}",
+                   public.to_json());
+    }
+}
@@ -0,0 +1,1004 @@
+//! Search for ways to lay down a set of cards as valid sequences
+//!
+//! The cards are represented as a count of each card type, indexed by the byte representation of
+//! the cards (0 for jokers, 1 to 52 for the regular cards).
+//!
+//! `solve` only tells whether the whole hand can be laid down, and may take long to say no with
+//! large hands and tables. `best_play` looks for the play laying down the most cards instead,
+//! with several threads and within a `Budget`: when the budget runs out, it returns the best play
+//! found so far.
+//!
+//! The same cards are often left after laying down sequences in different orders, so both searches
+//! remember the positions they have already been through: `search` those from which the cards
+//! can't be arranged, and `best_play` the fewest cards kept when reaching each position. The cache
+//! statistics of `best_play` are in its `CacheStats`.
+//!
+//! `can_go_out` answers the question players ask most, whether the whole hand can be laid down,
+//! with the house rules of the plugins: its search goes through every way of laying down the
+//! cards, so its answer is exact.
+
+use std::collections::{ HashMap, HashSet };
+use std::hash::{ Hash, Hasher };
+use std::collections::hash_map::DefaultHasher;
+use std::sync::{ Mutex, atomic::{ AtomicBool, AtomicUsize, Ordering } };
+use std::time::{ Duration, Instant };
+use crate::plugins::Plugins;
+use crate::sequence_cards::*;
+use crate::table::Table;
+
+/// number of different card types, including the joker
+const N_CARD_TYPES: usize = 53;
+
+/// number of values in a suit
+const N_VALUES: u8 = 13;
+
+/// number of positions searched between two looks at the clock
+const NODES_PER_CLOCK_CHECK: usize = 256;
+
+/// number of branches given to each thread of `best_play`, so that they stay busy until the end
+const BRANCHES_PER_THREAD: usize = 4;
+
+/// number of parts of the cache of `best_play`, each with its own lock
+const N_CACHE_SHARDS: usize = 16;
+
+/// number of positions the cache of `best_play` can hold
+const CACHE_CAPACITY: usize = 1 << 18;
+
+type CardCount = [u16; N_CARD_TYPES];
+
+/// Find a way to arrange all the cards of a sequence into valid sequences
+///
+/// Return `None` if there is no such arrangement.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::solver::arrange;
+///
+/// let cards = Sequence::from_cards(&[
+///     RegularCard(Heart, 5),
+///     RegularCard(Club, 7),
+///     RegularCard(Heart, 6),
+///     RegularCard(Spade, 7),
+///     RegularCard(Heart, 4),
+///     RegularCard(Diamond, 7),
+/// ]);
+///
+/// let sequences = arrange(&cards).unwrap();
+/// assert_eq!(2, sequences.len());
+///
+/// let cards = Sequence::from_cards(&[
+///     RegularCard(Heart, 5),
+///     RegularCard(Club, 7),
+///     RegularCard(Heart, 6),
+/// ]);
+///
+/// assert_eq!(None, arrange(&cards));
+/// ```
+pub fn arrange(cards: &Sequence) -> Option<Vec<Sequence>> {
+    let mut count = count_of_sequence(cards);
+    let mut sequences = Vec::<Sequence>::new();
+    if search(&mut count, &mut sequences, &mut HashSet::new(), None) {
+        Some(sequences)
+    } else {
+        None
+    }
+}
+
+/// Find a way to lay down all the cards of a hand, possibly rearranging the table
+///
+/// If it exists, the result is the list of sequences forming the new table.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::table::Table;
+/// use machiavelli::solver::solve;
+///
+/// let mut table = Table::new();
+/// table.add(Sequence::from_cards(&[
+///     RegularCard(Club, 4),
+///     RegularCard(Club, 5),
+///     RegularCard(Club, 6),
+///     RegularCard(Club, 7),
+/// ]));
+///
+/// // the 7♣ must be taken from the table to complete the set of 7s
+/// let hand = Sequence::from_cards(&[
+///     RegularCard(Heart, 7),
+///     RegularCard(Spade, 7),
+/// ]);
+///
+/// assert_eq!(2, solve(&hand, &table).unwrap().len());
+/// ```
+pub fn solve(hand: &Sequence, table: &Table) -> Option<Vec<Sequence>> {
+    let mut count = count_of_sequence(hand);
+    for (card, n) in table.count_cards() {
+        count[card.to_byte() as usize] += n;
+    }
+    let mut sequences = Vec::<Sequence>::new();
+    if search(&mut count, &mut sequences, &mut HashSet::new(), None) {
+        Some(sequences)
+    } else {
+        None
+    }
+}
+
+/// How to lay down a whole hand
+#[derive(Debug, PartialEq, Clone)]
+pub struct Plan {
+    /// numbers of the sequences of the table to take, in increasing order; the others stay as
+    /// they are
+    pub take: Vec<usize>,
+    /// sequences to lay down, with the cards of the hand and those taken from the table
+    pub play: Vec<Sequence>
+}
+
+/// Find a way to lay down all the cards of a hand, possibly rearranging the table, with sequences
+/// accepted by the rules of the game and those of `rules`
+///
+/// Every way of laying down the cards is considered, so that the result is `None` only if there
+/// is no way to go out.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::plugins::Plugins;
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::table::Table;
+/// use machiavelli::solver::{ can_go_out, Plan };
+///
+/// let mut table = Table::new();
+/// table.add(Sequence::from_cards(&[RegularCard(Spade, 9), RegularCard(Spade, 10), RegularCard(Spade, 11)]));
+/// table.add(Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5), RegularCard(Club, 6),
+///                                  RegularCard(Club, 7)]));
+///
+/// // the 7♣ is taken from the last sequence laid down, number 1, to make a set of 7s
+/// let hand = Sequence::from_cards(&[RegularCard(Heart, 7), RegularCard(Diamond, 7)]);
+/// let plan = can_go_out(&hand, &table, &Plugins::new()).unwrap();
+/// assert_eq!(vec![1], plan.take);
+/// assert_eq!(2, plan.play.len());
+///
+/// let hand = Sequence::from_cards(&[RegularCard(Heart, 7), RegularCard(Diamond, 8)]);
+/// assert_eq!(None, can_go_out(&hand, &table, &Plugins::new()));
+/// ```
+pub fn can_go_out(hand: &Sequence, table: &Table, rules: &Plugins) -> Option<Plan> {
+    let mut count = count_of_sequence(hand);
+    for (card, n) in table.count_cards() {
+        count[card.to_byte() as usize] += n;
+    }
+    let mut sequences = Vec::<Sequence>::new();
+    if !search(&mut count, &mut sequences, &mut HashSet::new(), Some(rules)) {
+        return None;
+    }
+
+    // sequences of the table found as they are in the arrangement stay on the table
+    let mut play: Vec<(CardCount, Sequence)> = sequences.into_iter().map(|seq| (count_of_sequence(&seq), seq)).collect();
+    let mut take = Vec::<usize>::new();
+    for n in 1..=table.number_sequences() {
+        let seq_count = count_of_sequence(table.get(n)?);
+        match play.iter().position(|(c, _)| *c == seq_count) {
+            Some(j) => {
+                play.remove(j);
+            },
+            None => take.push(n)
+        }
+    }
+    Some(Plan { take, play: play.into_iter().map(|(_, seq)| seq).collect() })
+}
+
+/// Whether a card, or a pair of cards, of a hand can be laid down with one of the sequences of the
+/// table, rearranging it if needed
+///
+/// Moves which need larger groups of cards or several sequences of the table are not looked for,
+/// so that the answer comes quickly even for large tables.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::table::Table;
+/// use machiavelli::solver::can_lay_down_some;
+///
+/// let mut table = Table::new();
+/// table.add(Sequence::from_cards(&[
+///     RegularCard(Club, 4),
+///     RegularCard(Club, 5),
+///     RegularCard(Club, 6),
+///     RegularCard(Club, 7),
+/// ]));
+///
+/// // the two 7s complete a set with the 7♣ from the table
+/// let hand = Sequence::from_cards(&[RegularCard(Heart, 7), RegularCard(Spade, 10), RegularCard(Spade, 7)]);
+/// assert!(can_lay_down_some(&hand, &table));
+///
+/// let hand = Sequence::from_cards(&[RegularCard(Heart, 7), RegularCard(Spade, 10)]);
+/// assert!(!can_lay_down_some(&hand, &table));
+/// ```
+pub fn can_lay_down_some(hand: &Sequence, table: &Table) -> bool {
+    let cards = hand.to_vec();
+    let mut groups: Vec<Vec<Card>> = cards.iter().map(|card| vec![card.clone()]).collect();
+    for i in 0..cards.len() {
+        for j in i+1..cards.len() {
+            groups.push(vec![cards[i].clone(), cards[j].clone()]);
+        }
+    }
+    (1..=table.number_sequences())
+        .filter_map(|n| table.get(n))
+        .any(|seq| groups.iter().any(|group| {
+            let mut cards = seq.clone();
+            cards.merge(Sequence::from_cards(group));
+            arrange(&cards).is_some()
+        }))
+}
+
+/// Find a valid sequence of at least three cards among a set of cards
+///
+/// Sequences using fewer jokers are preferred. Return `None` if there is no such sequence.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::solver::find_sequence;
+///
+/// let cards = Sequence::from_cards(&[
+///     RegularCard(Heart, 5),
+///     Joker,
+///     RegularCard(Club, 7),
+///     RegularCard(Heart, 6),
+///     RegularCard(Heart, 4),
+/// ]);
+///
+/// assert_eq!(Some(Sequence::from_cards(&[
+///     RegularCard(Heart, 4),
+///     RegularCard(Heart, 5),
+///     RegularCard(Heart, 6),
+/// ])), find_sequence(&cards));
+/// ```
+pub fn find_sequence(cards: &Sequence) -> Option<Sequence> {
+    let count = count_of_sequence(cards);
+    (1..N_CARD_TYPES)
+        .filter(|&i| count[i] > 0)
+        .flat_map(|i| candidate_sequences(&count, i))
+        .min_by_key(|candidate| candidate.iter().filter(|&&i| i == 0).count())
+        .map(|candidate| Sequence::from_cards(
+            &candidate.iter().map(|&i| Card::from_byte(i as u8).unwrap()).collect::<Vec<Card>>()
+        ))
+}
+
+/// Whether a card forms a valid sequence of at least three cards with some of the cards
+///
+/// For a joker, the sequence must need it.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::solver::completes_sequence;
+///
+/// let cards = Sequence::from_cards(&[RegularCard(Heart, 5), RegularCard(Heart, 6), RegularCard(Club, 9)]);
+///
+/// assert!(completes_sequence(&cards, &RegularCard(Heart, 7)));
+/// assert!(completes_sequence(&cards, &Joker));
+/// assert!(!completes_sequence(&cards, &RegularCard(Club, 7)));
+/// ```
+pub fn completes_sequence(cards: &Sequence, card: &Card) -> bool {
+    let mut count = count_of_sequence(cards);
+    let byte = card.to_byte() as usize;
+    count[byte] += 1;
+    if byte == 0 {
+        (1..N_CARD_TYPES)
+            .filter(|&i| count[i] > 0)
+            .any(|i| candidate_sequences(&count, i).iter().any(|candidate| candidate.contains(&0)))
+    } else {
+        !candidate_sequences(&count, byte).is_empty()
+    }
+}
+
+/// Limits of the search of `best_play`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Budget {
+    /// time after which the best play found so far is returned
+    pub max_time: Option<Duration>,
+    /// number of positions after which the best play found so far is returned
+    pub max_nodes: Option<usize>,
+    /// number of threads searching
+    pub n_threads: usize
+}
+
+impl Budget {
+
+    /// Budget with a time and a number of positions, using all the cores of the machine
+    pub fn new(max_time: Option<Duration>, max_nodes: Option<usize>) -> Budget {
+        let n_threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        Budget { max_time, max_nodes, n_threads }
+    }
+
+    /// No limit: the search goes to the end
+    pub fn unlimited() -> Budget {
+        Budget::new(None, None)
+    }
+}
+
+impl Default for Budget {
+    /// Two seconds, which is about as long as a hint can take
+    fn default() -> Self {
+        Budget::new(Some(Duration::from_secs(2)), None)
+    }
+}
+
+/// Result of `best_play`
+#[derive(Debug, PartialEq, Clone)]
+pub struct BestPlay {
+    /// sequences forming the new table
+    pub sequences: Vec<Sequence>,
+    /// number of cards of the hand laid down
+    pub n_laid: usize,
+    /// number of cards left in the hand
+    pub n_kept: usize,
+    /// whether no better play exists: the search went to the end, or the whole hand is laid down
+    pub optimal: bool,
+    /// number of positions searched
+    pub n_nodes: usize,
+    /// use of the cache of positions
+    pub cache: CacheStats
+}
+
+/// Use of the cache of positions of `best_play`
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct CacheStats {
+    /// positions reached again with as many cards kept as before, which were not searched again
+    pub hits: usize,
+    /// positions reached for the first time, or with fewer cards kept than before
+    pub misses: usize,
+    /// positions in the cache at the end of the search
+    pub entries: usize
+}
+
+impl CacheStats {
+
+    /// Share of the positions found in the cache
+    pub fn hit_rate(&self) -> f64 {
+        if self.hits + self.misses == 0 {
+            0.
+        } else {
+            self.hits as f64 / (self.hits + self.misses) as f64
+        }
+    }
+}
+
+impl BestPlay {
+
+    /// Whether the whole hand is laid down
+    pub fn goes_out(&self) -> bool {
+        self.n_kept == 0
+    }
+
+    /// Play as shown to the player
+    pub fn render(&self) -> String {
+        let mut res = if self.goes_out() {
+            "You can lay down all your cards:\n".to_string()
+        } else if self.n_laid == 0 {
+            return format!("No way to lay down some of your cards was found{}",
+                           if self.optimal { "" } else { " (the search was stopped before the end)" });
+        } else {
+            format!("You can lay down {} of your {} cards{}:\n", self.n_laid, self.n_laid + self.n_kept,
+                    if self.optimal { "" } else { " (there may be better plays: the search was stopped before the end)" })
+        };
+        for seq in &self.sequences {
+            res += &format!("{}\n", seq);
+        }
+        res
+    }
+}
+
+/// Find the play laying down as many cards of a hand as possible, possibly rearranging the table,
+/// within a budget
+///
+/// The search is split between the threads of the budget. When the budget runs out, the best play
+/// found so far is returned, and `optimal` is false unless it lays down the whole hand.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::sequence_cards::*;
+/// use machiavelli::table::Table;
+/// use machiavelli::solver::{ best_play, Budget };
+///
+/// let mut table = Table::new();
+/// table.add(Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5), RegularCard(Club, 6)]));
+///
+/// // the 7♣ joins the run, and the K♠ stays in the hand
+/// let hand = Sequence::from_cards(&[RegularCard(Club, 7), RegularCard(Spade, 13)]);
+///
+/// let play = best_play(&hand, &table, &Budget::unlimited());
+/// assert_eq!((1, 1), (play.n_laid, play.n_kept));
+/// assert!(play.optimal);
+/// assert_eq!(vec![Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5),
+///                                        RegularCard(Club, 6), RegularCard(Club, 7)])],
+///            play.sequences);
+/// ```
+pub fn best_play(hand: &Sequence, table: &Table, budget: &Budget) -> BestPlay {
+    let mut start = Position { count: count_of_sequence(hand), required: [0; N_CARD_TYPES], n_kept: 0 };
+    for (card, n) in table.count_cards() {
+        start.count[card.to_byte() as usize] += n;
+        start.required[card.to_byte() as usize] += n;
+    }
+    let n_hand = hand.number_cards();
+    let table_sequences: Vec<Sequence> = (1..=table.number_sequences()).filter_map(|n| table.get(n).cloned()).collect();
+    let shared = Shared {
+        start: Instant::now(),
+        budget: *budget,
+        n_nodes: AtomicUsize::new(0),
+        out_of_budget: AtomicBool::new(false),
+        n_kept: AtomicUsize::new(n_hand),
+        best: Mutex::new(None),
+        cache: (0..N_CACHE_SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+        n_cached: AtomicUsize::new(0),
+        cache_hits: AtomicUsize::new(0),
+        cache_misses: AtomicUsize::new(0)
+    };
+
+    // split the top of the search tree into branches, which the threads take one after the other
+    let n_threads = budget.n_threads.max(1);
+    let mut branches = vec![(start, Vec::<Vec<usize>>::new())];
+    while branches.len() < BRANCHES_PER_THREAD * n_threads {
+        let mut expanded = false;
+        let mut next = Vec::new();
+        for (position, sequences) in branches {
+            match position.moves() {
+                Some(moves) if !moves.is_empty() => {
+                    expanded = true;
+                    for m in moves {
+                        let mut sequences = sequences.clone();
+                        next.push((position.play(&m, &mut sequences), sequences));
+                    }
+                },
+                Some(_) => (),
+                None => next.push((position, sequences))
+            }
+        }
+        branches = next;
+        if !expanded {
+            break;
+        }
+    }
+    let next_branch = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        for _ in 0..n_threads {
+            scope.spawn(|| {
+                while let Some((position, sequences)) = branches.get(next_branch.fetch_add(1, Ordering::Relaxed)) {
+                    shared.search(position, &mut sequences.clone());
+                }
+            });
+        }
+    });
+
+    let n_kept = shared.n_kept.load(Ordering::Relaxed);
+    let sequences = match shared.best.into_inner().unwrap_or_else(|e| e.into_inner()) {
+        Some(sequences) => sequences.iter().map(|candidate| to_sequence(candidate)).collect(),
+        None => table_sequences
+    };
+    BestPlay {
+        sequences,
+        n_laid: n_hand - n_kept,
+        n_kept,
+        optimal: n_kept == 0 || !shared.out_of_budget.load(Ordering::Relaxed),
+        n_nodes: shared.n_nodes.load(Ordering::Relaxed),
+        cache: CacheStats {
+            hits: shared.cache_hits.load(Ordering::Relaxed),
+            misses: shared.cache_misses.load(Ordering::Relaxed),
+            entries: shared.n_cached.load(Ordering::Relaxed)
+        }
+    }
+}
+
+/// cards left to lay down in the search of `best_play`
+#[derive(Clone, Copy)]
+struct Position {
+    /// cards from the hand and the table
+    count: CardCount,
+    /// cards from the table, which must all be laid down
+    required: CardCount,
+    /// number of cards of the hand kept so far
+    n_kept: usize
+}
+
+/// move in the search of `best_play`
+enum Move {
+    /// lay down a sequence
+    Lay(Vec<usize>),
+    /// keep a card in the hand
+    Keep(usize)
+}
+
+impl Position {
+
+    /// moves from this position, or `None` if only jokers are left
+    ///
+    /// The first regular card left is either laid down in a sequence or, if it comes from the
+    /// hand, kept. Sequences are tried first, so that good plays are found early.
+    fn moves(&self) -> Option<Vec<Move>> {
+        let first = (1..N_CARD_TYPES).find(|&i| self.count[i] > 0)?;
+        let mut moves: Vec<Move> = candidate_sequences(&self.count, first).into_iter().map(Move::Lay).collect();
+        if self.count[first] > self.required[first] {
+            moves.push(Move::Keep(first));
+        }
+        Some(moves)
+    }
+
+    /// position after a move, whose sequence is added to `sequences`
+    fn play(&self, m: &Move, sequences: &mut Vec<Vec<usize>>) -> Position {
+        let mut res = *self;
+        match m {
+            Move::Lay(candidate) => {
+                for &i in candidate {
+                    res.count[i] -= 1;
+                    res.required[i] = res.required[i].saturating_sub(1);
+                }
+                sequences.push(candidate.clone());
+            },
+            Move::Keep(i) => {
+                res.count[*i] -= 1;
+                res.n_kept += 1;
+            }
+        }
+        res
+    }
+}
+
+/// state of `best_play` shared by the threads
+struct Shared {
+    start: Instant,
+    budget: Budget,
+    n_nodes: AtomicUsize,
+    out_of_budget: AtomicBool,
+    /// number of cards kept by the best play found
+    n_kept: AtomicUsize,
+    /// sequences of the best play found
+    best: Mutex<Option<Vec<Vec<usize>>>>,
+    /// fewest cards kept when reaching each position, split by the hash of the position
+    cache: Vec<Mutex<HashMap<(CardCount, CardCount), usize>>>,
+    n_cached: AtomicUsize,
+    cache_hits: AtomicUsize,
+    cache_misses: AtomicUsize
+}
+
+impl Shared {
+
+    /// count a position, and tell whether the search should go on
+    fn go_on(&self) -> bool {
+        if self.out_of_budget.load(Ordering::Relaxed) || self.n_kept.load(Ordering::Relaxed) == 0 {
+            return false;
+        }
+        let n = self.n_nodes.fetch_add(1, Ordering::Relaxed) + 1;
+        let out_of_budget = self.budget.max_nodes.is_some_and(|max| n > max)
+            || (n.is_multiple_of(NODES_PER_CLOCK_CHECK)
+                && self.budget.max_time.is_some_and(|max| self.start.elapsed() >= max));
+        if out_of_budget {
+            self.out_of_budget.store(true, Ordering::Relaxed);
+        }
+        !out_of_budget
+    }
+
+    /// whether the position was already reached with as few cards kept, in which case searching
+    /// it again can't find a better play; otherwise, it is recorded
+    fn is_cached(&self, position: &Position) -> bool {
+        let key = (position.count, position.required);
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let mut shard = self.cache[hasher.finish() as usize % N_CACHE_SHARDS].lock().unwrap_or_else(|e| e.into_inner());
+        match shard.get_mut(&key) {
+            Some(n_kept) if *n_kept <= position.n_kept => {
+                self.cache_hits.fetch_add(1, Ordering::Relaxed);
+                return true;
+            },
+            Some(n_kept) => *n_kept = position.n_kept,
+            None => if self.n_cached.load(Ordering::Relaxed) < CACHE_CAPACITY {
+                shard.insert(key, position.n_kept);
+                self.n_cached.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+        false
+    }
+
+    /// depth-first search for plays keeping fewer cards than the best one found
+    fn search(&self, position: &Position, sequences: &mut Vec<Vec<usize>>) {
+        if position.n_kept >= self.n_kept.load(Ordering::Relaxed) || !self.go_on() {
+            return;
+        }
+        let moves = position.moves();
+        if moves.is_some() && self.is_cached(position) {
+            return;
+        }
+        match moves {
+            Some(moves) => for m in moves {
+                let n_sequences = sequences.len();
+                let next = position.play(&m, sequences);
+                self.search(&next, sequences);
+                sequences.truncate(n_sequences);
+            },
+            None => {
+                let mut best = self.best.lock().unwrap_or_else(|e| e.into_inner());
+                if position.n_kept < self.n_kept.load(Ordering::Relaxed) {
+                    let mut sequences = sequences.clone();
+                    if position.count[0] > 0 {
+                        sequences.push(vec![0; position.count[0] as usize]);
+                    }
+                    *best = Some(sequences);
+                    self.n_kept.store(position.n_kept, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+fn count_of_sequence(seq: &Sequence) -> CardCount {
+    let mut count: CardCount = [0; N_CARD_TYPES];
+    for card in seq.to_vec() {
+        count[card.to_byte() as usize] += 1;
+    }
+    count
+}
+
+/// byte of the regular card with a given suit and position in a run (14 standing for a high ace)
+fn byte_at(suit_byte: u8, position: u8) -> usize {
+    let value = if position > N_VALUES { position - N_VALUES } else { position };
+    (suit_byte + value) as usize
+}
+
+/// depth-first search for a partition of the cards into valid sequences
+///
+/// The first regular card left must belong to one of the sequences, so only the sequences
+/// containing it are tried at each step. Jokers left at the end are laid down on their own.
+/// Positions from which no partition exists are added to `dead_ends`, and not searched again.
+///
+/// With `rules`, only the sequences they accept are laid down. Since they may refuse sequences
+/// which the game would lay down otherwise, sets with more jokers than needed are also tried, and
+/// the jokers left at the end may be split into several sequences.
+fn search(count: &mut CardCount, sequences: &mut Vec<Sequence>, dead_ends: &mut HashSet<CardCount>,
+          rules: Option<&Plugins>) -> bool {
+
+    let first = match (1..N_CARD_TYPES).find(|&i| count[i] > 0) {
+        Some(i) => i,
+        None => {
+            return match rules {
+                Some(rules) => match split_jokers(count[0] as usize, rules) {
+                    Some(jokers) => {
+                        sequences.extend(jokers);
+                        true
+                    },
+                    None => false
+                },
+                None => {
+                    if count[0] > 0 {
+                        sequences.push(Sequence::from_cards(&vec![Joker; count[0] as usize]));
+                    }
+                    true
+                }
+            };
+        }
+    };
+    if dead_ends.contains(count) {
+        return false;
+    }
+
+    let mut candidates = candidate_sequences(count, first);
+    if let Some(rules) = rules {
+        let n_jokers = count[0] as usize;
+        let extra: Vec<Vec<usize>> = candidates.iter()
+            .filter(|candidate| candidate.iter().all(|&i| i == 0 || (i - 1) % N_VALUES as usize == (first - 1) % N_VALUES as usize))
+            .flat_map(|candidate| {
+                let n_used = candidate.iter().filter(|&&i| i == 0).count();
+                (1..=n_jokers - n_used).map(move |k| {
+                    let mut c = candidate.clone();
+                    c.extend(vec![0; k]);
+                    c
+                })
+            })
+            .filter(|c| to_sequence(c).is_valid())
+            .collect();
+        candidates.extend(extra);
+        candidates.retain(|c| rules.validate_meld(&to_sequence(c)).is_ok());
+    }
+
+    for candidate in candidates {
+        for &i in &candidate {
+            count[i] -= 1;
+        }
+        sequences.push(Sequence::from_cards(
+            &candidate.iter().map(|&i| Card::from_byte(i as u8).unwrap()).collect::<Vec<Card>>()
+        ));
+        if search(count, sequences, dead_ends, rules) {
+            return true;
+        }
+        sequences.pop();
+        for &i in &candidate {
+            count[i] += 1;
+        }
+    }
+
+    dead_ends.insert(*count);
+    false
+}
+
+/// sequence of the cards with the given bytes
+fn to_sequence(candidate: &[usize]) -> Sequence {
+    Sequence::from_cards(&candidate.iter().map(|&i| Card::from_byte(i as u8).unwrap()).collect::<Vec<Card>>())
+}
+
+/// split jokers into sequences accepted by the rules, as long as possible
+fn split_jokers(n_jokers: usize, rules: &Plugins) -> Option<Vec<Sequence>> {
+    if n_jokers == 0 {
+        return Some(Vec::new());
+    }
+    (1..=n_jokers).rev().find_map(|n| {
+        let jokers = Sequence::from_cards(&vec![Joker; n]);
+        rules.validate_meld(&jokers).ok()?;
+        let mut res = split_jokers(n_jokers - n, rules)?;
+        res.insert(0, jokers);
+        Some(res)
+    })
+}
+
+/// list the sequences (as vectors of card bytes) containing the card `first` that can be built
+/// from the available cards, using jokers only where needed
+fn candidate_sequences(count: &CardCount, first: usize) -> Vec<Vec<usize>> {
+
+    let mut res = Vec::<Vec<usize>>::new();
+    let jokers = count[0] as usize;
+    let suit_byte = (((first - 1) as u8) / N_VALUES) * N_VALUES;
+    let value = first as u8 - suit_byte;
+
+    // cards with the same value and different suits
+    let others: Vec<usize> = (0..4u8)
+        .map(|s| (s * N_VALUES + value) as usize)
+        .filter(|&i| i != first && count[i] > 0)
+        .collect();
+    for mask in 0..(1usize << others.len()) {
+        let mut candidate = vec![first];
+        for (j, &i) in others.iter().enumerate() {
+            if mask & (1 << j) != 0 {
+                candidate.push(i);
+            }
+        }
+        let n_missing = 3usize.saturating_sub(candidate.len());
+        if n_missing <= jokers {
+            candidate.extend(vec![0; n_missing]);
+            res.push(candidate);
+        }
+    }
+
+    // runs of the same suit, with the ace either low or high
+    let positions: Vec<u8> = if value == 1 { vec![1, N_VALUES + 1] } else { vec![value] };
+    for &p in &positions {
+        for start in p.saturating_sub(N_VALUES - 1).max(1)..=p {
+            let mut partial: Vec<Vec<usize>> = vec![Vec::new()];
+            let mut end = start;
+            while end <= N_VALUES + 1 && end - start < N_VALUES {
+                let i = byte_at(suit_byte, end);
+                let mut extended = Vec::<Vec<usize>>::new();
+                for candidate in &partial {
+                    if end == p {
+                        let mut c = candidate.clone();
+                        c.push(first);
+                        extended.push(c);
+                        continue;
+                    }
+                    let n_used = candidate.iter().filter(|&&j| j == i).count() as u16;
+                    if count[i] > n_used {
+                        let mut c = candidate.clone();
+                        c.push(i);
+                        extended.push(c);
+                    }
+                    if candidate.iter().filter(|&&j| j == 0).count() < jokers {
+                        let mut c = candidate.clone();
+                        c.push(0);
+                        extended.push(c);
+                    }
+                }
+                partial = extended;
+                if partial.is_empty() {
+                    break;
+                }
+                if end >= p && end - start >= 2 {
+                    res.extend(partial.iter().cloned());
+                }
+                end += 1;
+            }
+        }
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn all_valid(sequences: &[Sequence]) -> bool {
+        sequences.iter().all(|seq| seq.clone().is_valid())
+    }
+
+    #[test]
+    fn arrange_empty() {
+        assert_eq!(Some(Vec::<Sequence>::new()), arrange(&Sequence::new()));
+    }
+
+    #[test]
+    fn arrange_runs_with_aces() {
+        let cards = Sequence::from_cards(&[
+            RegularCard(Spade, 1),
+            RegularCard(Spade, 13),
+            RegularCard(Spade, 12),
+            RegularCard(Heart, 1),
+            RegularCard(Heart, 2),
+            RegularCard(Heart, 3),
+        ]);
+        let sequences = arrange(&cards).unwrap();
+        assert_eq!(2, sequences.len());
+        assert!(all_valid(&sequences));
+    }
+
+    #[test]
+    fn arrange_no_wrap_around() {
+        let cards = Sequence::from_cards(&[
+            RegularCard(Club, 13),
+            RegularCard(Club, 1),
+            RegularCard(Club, 2),
+        ]);
+        assert_eq!(None, arrange(&cards));
+    }
+
+    #[test]
+    fn arrange_with_jokers() {
+        let cards = Sequence::from_cards(&[
+            RegularCard(Diamond, 3),
+            Joker,
+            RegularCard(Diamond, 6),
+            RegularCard(Diamond, 5),
+            RegularCard(Club, 9),
+            Joker,
+            Joker,
+        ]);
+        let sequences = arrange(&cards).unwrap();
+        assert!(all_valid(&sequences));
+        let n_cards: usize = sequences.iter().map(|seq| seq.number_cards()).sum();
+        assert_eq!(7, n_cards);
+    }
+
+    #[test]
+    fn arrange_needs_joker_in_run_not_set() {
+        // the joker must be used in the run, leaving a pure set of 8s
+        let cards = Sequence::from_cards(&[
+            RegularCard(Heart, 8),
+            RegularCard(Club, 8),
+            RegularCard(Spade, 8),
+            RegularCard(Heart, 9),
+            Joker,
+            RegularCard(Heart, 11),
+        ]);
+        let sequences = arrange(&cards).unwrap();
+        assert!(all_valid(&sequences));
+    }
+
+    #[test]
+    fn solve_uses_the_table() {
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[
+            RegularCard(Heart, 2),
+            RegularCard(Heart, 3),
+            RegularCard(Heart, 4),
+        ]));
+        let hand = Sequence::from_cards(&[RegularCard(Heart, 5)]);
+        assert_eq!(None, arrange(&hand));
+        let sequences = solve(&hand, &table).unwrap();
+        assert_eq!(1, sequences.len());
+        assert!(all_valid(&sequences));
+    }
+
+    #[test]
+    fn best_play_within_a_budget() {
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[RegularCard(Heart, 7), RegularCard(Club, 7), RegularCard(Spade, 7)]));
+        table.add(Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5), RegularCard(Club, 6)]));
+        let hand = Sequence::from_cards(&[RegularCard(Club, 3), RegularCard(Diamond, 7), RegularCard(Club, 8),
+                                          RegularCard(Diamond, 1), RegularCard(Spade, 12)]);
+
+        // 3♣ 7♦ 8♣ can be laid down, whatever the number of threads
+        for n_threads in 1..=4 {
+            let budget = Budget { n_threads, ..Budget::unlimited() };
+            let play = best_play(&hand, &table, &budget);
+            assert_eq!((3, 2, true), (play.n_laid, play.n_kept, play.optimal));
+            assert!(all_valid(&play.sequences));
+            let n_cards: usize = play.sequences.iter().map(|seq| seq.number_cards()).sum();
+            assert_eq!(6 + 3, n_cards);
+        }
+
+        // with almost no budget, the play is only the best found so far
+        let play = best_play(&hand, &table, &Budget { max_nodes: Some(1), ..Budget::unlimited() });
+        assert!(!play.optimal);
+        assert!(play.n_kept >= 2);
+        assert!(all_valid(&play.sequences));
+
+        let hand = Sequence::from_cards(&[RegularCard(Club, 3), RegularCard(Club, 8), Joker]);
+        let play = best_play(&hand, &table, &Budget::default());
+        assert!(play.goes_out() && play.optimal);
+        assert!(play.render().starts_with("You can lay down all your cards"));
+    }
+
+    /// sets only, with no more than one joker
+    struct SetsWithOneJoker;
+
+    impl crate::plugins::RulePlugin for SetsWithOneJoker {
+        fn name(&self) -> &str {
+            "sets with one joker"
+        }
+
+        fn validate_meld(&self, sequence: &Sequence) -> Result<(), String> {
+            let cards = sequence.to_vec();
+            let n_jokers = cards.iter().filter(|card| **card == Joker).count();
+            let values: Vec<u8> = cards.iter().filter_map(|card| match card {
+                RegularCard(_, v) => Some(*v),
+                Joker => None
+            }).collect();
+            if n_jokers > 1 || values.is_empty() || values.iter().any(|v| *v != values[0]) {
+                Err("only sets with one joker".to_string())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn going_out_with_house_rules() {
+        let mut rules = Plugins::new();
+        rules.register(SetsWithOneJoker);
+
+        // the jokers must go in two sets, although one set and a group of jokers would do otherwise
+        let hand = Sequence::from_cards(&[RegularCard(Heart, 8), RegularCard(Club, 8), Joker,
+                                          RegularCard(Spade, 2), RegularCard(Diamond, 2), Joker]);
+        let plan = can_go_out(&hand, &Table::new(), &rules).unwrap();
+        assert_eq!(Vec::<usize>::new(), plan.take);
+        assert_eq!(2, plan.play.len());
+        assert!(plan.play.iter().all(|seq| seq.clone().is_valid() && rules.validate_meld(seq).is_ok()));
+
+        // a run is refused, but the 9♥ can join a set of the table
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[RegularCard(Spade, 9), RegularCard(Club, 9), RegularCard(Diamond, 9)]));
+        let hand = Sequence::from_cards(&[RegularCard(Heart, 7), RegularCard(Heart, 8), RegularCard(Heart, 9)]);
+        assert!(can_go_out(&hand, &table, &Plugins::new()).is_some());
+        assert_eq!(None, can_go_out(&hand, &table, &rules));
+        let hand = Sequence::from_cards(&[RegularCard(Heart, 9)]);
+        assert_eq!(Some(Plan { take: vec![1], play: vec![Sequence::from_cards(&[
+            RegularCard(Heart, 9), RegularCard(Club, 9), RegularCard(Diamond, 9), RegularCard(Spade, 9)])] }),
+            can_go_out(&hand, &table, &rules));
+
+        // three jokers can't be laid down on their own
+        let hand = Sequence::from_cards(&[Joker, Joker, Joker]);
+        assert_eq!(None, can_go_out(&hand, &Table::new(), &rules));
+        assert!(can_go_out(&hand, &Table::new(), &Plugins::new()).is_some());
+    }
+
+    #[test]
+    fn positions_are_not_searched_twice() {
+
+        // keeping the 2♥ then the 2♠ or the 2♠ then the 2♥ leads to the same position
+        let hand = Sequence::from_cards(&[RegularCard(Heart, 2), RegularCard(Spade, 2), RegularCard(Heart, 5),
+                                          RegularCard(Heart, 6), RegularCard(Heart, 9), RegularCard(Spade, 11),
+                                          RegularCard(Club, 13)]);
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[RegularCard(Heart, 3), RegularCard(Heart, 4), RegularCard(Heart, 5)]));
+        table.add(Sequence::from_cards(&[RegularCard(Spade, 3), RegularCard(Spade, 4), RegularCard(Spade, 5)]));
+        let play = best_play(&hand, &table, &Budget { n_threads: 1, ..Budget::unlimited() });
+        assert_eq!((3, 4), (play.n_laid, play.n_kept));
+        assert!(play.cache.hits > 0);
+        assert!(play.cache.entries <= play.cache.misses);
+        assert!(play.cache.hit_rate() > 0. && play.cache.hit_rate() < 1.);
+    }
+}
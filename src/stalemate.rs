@@ -0,0 +1,77 @@
+//! Detection of rounds which can not end
+//!
+//! Once the deck is empty, a player who has nothing to play can only pass. When every player has
+//! passed in a row, nothing can change any more: the round is a stalemate, and the hands are
+//! scored.
+
+/// Counts the consecutive passes made while the deck is empty
+#[derive(Debug, PartialEq, Clone)]
+pub struct StalemateDetector {
+    n_players: usize,
+    n_passes: usize
+}
+
+impl StalemateDetector {
+
+    /// Create a detector for a game with `n_players` players
+    pub fn new(n_players: usize) -> StalemateDetector {
+        StalemateDetector {
+            n_players,
+            n_passes: 0
+        }
+    }
+
+    /// Record a turn, and return `true` if the round is a stalemate
+    ///
+    /// A turn is a pass if the deck is empty at its end and the player has not laid down any
+    /// card from their hand.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::stalemate::StalemateDetector;
+    ///
+    /// let mut detector = StalemateDetector::new(2);
+    ///
+    /// assert!(!detector.record_turn(true, false));
+    /// assert!(!detector.record_turn(true, true));
+    /// assert!(!detector.record_turn(true, false));
+    /// assert!(detector.record_turn(true, false));
+    /// ```
+    pub fn record_turn(&mut self, deck_empty: bool, played: bool) -> bool {
+        if deck_empty && !played {
+            self.n_passes += 1;
+        } else {
+            self.n_passes = 0;
+        }
+        self.n_passes >= self.n_players.max(1)
+    }
+
+    /// Forget the passes, for instance when a new round starts
+    pub fn reset(&mut self) {
+        self.n_passes = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn passes_with_cards_left_do_not_count() {
+        let mut detector = StalemateDetector::new(2);
+        assert!(!detector.record_turn(false, false));
+        assert!(!detector.record_turn(false, false));
+        assert!(!detector.record_turn(true, false));
+        assert!(detector.record_turn(true, false));
+    }
+
+    #[test]
+    fn reset() {
+        let mut detector = StalemateDetector::new(1);
+        assert!(detector.record_turn(true, false));
+        detector.reset();
+        assert!(!detector.record_turn(true, true));
+    }
+}
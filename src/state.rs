@@ -0,0 +1,502 @@
+//! Public state of the game, as the server shares it with the clients
+//!
+//! The public state is what every player can see: the table, the number of cards in each hand
+//! and in the deck, and whose turn it is. At the start of each turn, the server sends the
+//! clients what changed since the previous turn, with the SHA-256 checksum of the canonical
+//! bytes of the new state. Each sequence on the table keeps an id while it stays there, so that
+//! only the sequences laid, changed, or taken away are sent. Every `FULL_STATE_PERIOD` turns,
+//! and whenever a client finds another checksum, the whole state is sent instead; the server
+//! logs the desyncs.
+//! The server then sends each player what they see besides the public state: their hand, the
+//! sequences changed by the previous turn, and the pages they turned to. The client draws the
+//! situation from its copy of the state, so the table is not sent in full each turn.
+
+use std::io::Write;
+use crate::deck::Deck;
+use crate::hand::Hand;
+use crate::layout::Pages;
+use crate::table::Table;
+use crate::sequence_cards::{ Card, Sequence };
+use crate::sha256::{ sha256, to_hex };
+
+/// number of turns between two updates of the whole state
+pub const FULL_STATE_PERIOD: usize = 10;
+
+/// What all the players know about the game
+#[derive(Debug, PartialEq, Clone)]
+pub struct PublicState {
+    /// seat of the player whose turn it is
+    pub current_player: usize,
+    /// number of cards in the hand of each player, in the order of the seats
+    pub hand_sizes: Vec<usize>,
+    /// number of cards left in the deck
+    pub deck_remaining: usize,
+    /// sequences on the table, normalized
+    pub table: Table
+}
+
+impl PublicState {
+
+    /// Public state of a game, at the turn of `current_player`
+    pub fn new<H: AsRef<Hand>>(table: &Table, hands: &[H], deck: &Deck, current_player: usize) -> PublicState {
+        let mut table = table.clone();
+        table.normalize();
+        PublicState {
+            current_player,
+            hand_sizes: hands.iter().map(|h| h.as_ref().number_cards()).collect(),
+            deck_remaining: deck.remaining(),
+            table
+        }
+    }
+
+    /// Canonical bytes of the state
+    ///
+    /// The seat of the current player and the number of players take a byte each, then come
+    /// the sizes of the hands and of the deck on two bytes each (big-endian), and the table.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::state::PublicState;
+    /// use machiavelli::table::Table;
+    ///
+    /// let state = PublicState {
+    ///     current_player: 1,
+    ///     hand_sizes: vec![3, 260],
+    ///     deck_remaining: 40,
+    ///     table: Table::new()
+    /// };
+    ///
+    /// assert_eq!(vec![1, 2, 0, 3, 1, 4, 0, 40, 0, 0], state.to_bytes());
+    /// assert_eq!(Some(state.clone()), PublicState::from_bytes(&state.to_bytes()));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::new();
+        self.write_to(&mut res).unwrap_or(());
+        res
+    }
+
+    /// Write the bytes given by `to_bytes`
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&[self.current_player as u8, self.hand_sizes.len() as u8])?;
+        for &size in self.hand_sizes.iter().chain(std::iter::once(&self.deck_remaining)) {
+            write_size(writer, size)?;
+        }
+        self.table.write_to(writer)
+    }
+
+    /// Read a state converted with `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Option<PublicState> {
+        let n_players = *bytes.get(1)? as usize;
+        let sizes: Vec<usize> = bytes.get(2..4 + 2 * n_players)?
+            .chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as usize)
+            .collect();
+        Some(PublicState {
+            current_player: bytes[0] as usize,
+            hand_sizes: sizes[..n_players].to_vec(),
+            deck_remaining: sizes[n_players],
+            table: Table::from_bytes(&bytes[4 + 2 * n_players..]).ok()?
+        })
+    }
+
+    /// SHA-256 hash of the canonical bytes, in hexadecimal
+    ///
+    /// The order in which the sequences were laid on the table does not change the checksum.
+    pub fn checksum(&self) -> String {
+        let mut state = self.clone();
+        state.table.normalize();
+        to_hex(&sha256(&state.to_bytes()))
+    }
+}
+
+/// Changes to the public state, or the whole state if `full` is true
+#[derive(Debug, PartialEq, Clone)]
+pub struct StateUpdate {
+    /// whether the update replaces the whole state
+    pub full: bool,
+    pub current_player: usize,
+    pub hand_sizes: Vec<usize>,
+    pub deck_remaining: usize,
+    /// ids of the sequences taken from the table
+    pub removed: Vec<u32>,
+    /// sequences laid on the table or changed, with their ids
+    pub changed: Vec<(u32, Sequence)>
+}
+
+impl StateUpdate {
+
+    /// Convert an update to bytes
+    ///
+    /// The update starts with 1 if it is full and 0 otherwise, then come the seat of the
+    /// current player, the number of players, the sizes of the hands and of the deck (two bytes
+    /// each), the number of sequences removed (two bytes) and their ids (four bytes each), and
+    /// the sequences changed: each one is its id followed by its cards and 255.
+    /// Numbers are big-endian.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::state::StateUpdate;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let update = StateUpdate {
+    ///     full: false,
+    ///     current_player: 1,
+    ///     hand_sizes: vec![3, 5],
+    ///     deck_remaining: 40,
+    ///     removed: vec![2],
+    ///     changed: vec![(7, Sequence::from_cards(&[RegularCard(Heart, 1), Joker, RegularCard(Heart, 3)]))]
+    /// };
+    ///
+    /// assert_eq!(vec![0, 1, 2, 0, 3, 0, 5, 0, 40, 0, 1, 0, 0, 0, 2, 0, 0, 0, 7, 1, 0, 3, 255],
+    ///            update.to_bytes());
+    /// assert_eq!(Some(update.clone()), StateUpdate::from_bytes(&update.to_bytes()));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::new();
+        self.write_to(&mut res).unwrap_or(());
+        res
+    }
+
+    /// Write the bytes given by `to_bytes`
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&[self.full as u8, self.current_player as u8, self.hand_sizes.len() as u8])?;
+        for &size in self.hand_sizes.iter().chain(std::iter::once(&self.deck_remaining)) {
+            write_size(writer, size)?;
+        }
+        writer.write_all(&(self.removed.len() as u16).to_be_bytes())?;
+        for id in &self.removed {
+            writer.write_all(&id.to_be_bytes())?;
+        }
+        for (id, seq) in &self.changed {
+            writer.write_all(&id.to_be_bytes())?;
+            seq.write_to(writer)?;
+            writer.write_all(&[255])?;
+        }
+        Ok(())
+    }
+
+    /// Read an update converted with `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Option<StateUpdate> {
+        let n_players = *bytes.get(2)? as usize;
+        let sizes: Vec<usize> = bytes.get(3..5 + 2 * n_players)?
+            .chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as usize)
+            .collect();
+        let mut rest = &bytes[5 + 2 * n_players..];
+        let n_removed = u16::from_be_bytes([*rest.first()?, *rest.get(1)?]) as usize;
+        let removed: Vec<u32> = rest.get(2..2 + 4 * n_removed)?.chunks(4).map(read_id).collect();
+        rest = &rest[2 + 4 * n_removed..];
+        let mut changed = Vec::new();
+        while !rest.is_empty() {
+            let id = read_id(rest.get(..4)?);
+            let end = 4 + rest[4..].iter().position(|&b| b == 255)?;
+            changed.push((id, Sequence::from_bytes(&rest[4..end])));
+            rest = &rest[end + 1..];
+        }
+        Some(StateUpdate {
+            full: bytes[0] == 1,
+            current_player: bytes[1] as usize,
+            hand_sizes: sizes[..n_players].to_vec(),
+            deck_remaining: sizes[n_players],
+            removed,
+            changed
+        })
+    }
+}
+
+/// What a player sees at the start of a turn, besides the public state and the texts around it
+///
+/// The server sends it after the update of the public state, and the client draws the table
+/// from its own copy of the state, with its own layout.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TurnView {
+    /// cards in the hand of the player
+    pub hand: Sequence,
+    /// sequences of the table laid or changed during the previous turn
+    pub changed: Vec<Sequence>,
+    /// pages of the table and of the hand the player turned to
+    pub pages: Pages
+}
+
+impl TurnView {
+
+    /// Convert the view to bytes
+    ///
+    /// The pages of the table and of the hand come first, on two bytes each (big-endian), then
+    /// the hand and each sequence changed, each one followed by 255.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::state::TurnView;
+    /// use machiavelli::layout::Pages;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let view = TurnView {
+    ///     hand: Sequence::from_cards(&[Joker]),
+    ///     changed: vec![Sequence::from_cards(&[RegularCard(Heart, 1), RegularCard(Heart, 2), RegularCard(Heart, 3)])],
+    ///     pages: Pages { table: 1, hand: 0 }
+    /// };
+    ///
+    /// assert_eq!(vec![0, 1, 0, 0, 0, 255, 1, 2, 3, 255], view.to_bytes());
+    /// assert_eq!(Some(view.clone()), TurnView::from_bytes(&view.to_bytes()));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::new();
+        self.write_to(&mut res).unwrap_or(());
+        res
+    }
+
+    /// Write the bytes given by `to_bytes`
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        write_size(writer, self.pages.table)?;
+        write_size(writer, self.pages.hand)?;
+        for seq in std::iter::once(&self.hand).chain(self.changed.iter()) {
+            seq.write_to(writer)?;
+            writer.write_all(&[255])?;
+        }
+        Ok(())
+    }
+
+    /// Read a view converted with `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Option<TurnView> {
+        let pages = bytes.get(..4)?;
+        let mut sequences = bytes[4..].split(|&b| b == 255).map(Sequence::from_bytes);
+        let hand = sequences.next()?;
+        let mut changed: Vec<Sequence> = sequences.collect();
+        // the last 255 leaves an empty slice behind it
+        changed.pop()?;
+        Some(TurnView {
+            hand,
+            changed,
+            pages: Pages {
+                table: u16::from_be_bytes([pages[0], pages[1]]) as usize,
+                hand: u16::from_be_bytes([pages[2], pages[3]]) as usize
+            }
+        })
+    }
+}
+
+/// write a size on two bytes (big-endian), capped at the largest one they can hold
+fn write_size<W: Write>(writer: &mut W, size: usize) -> std::io::Result<()> {
+    writer.write_all(&(size.min(u16::MAX as usize) as u16).to_be_bytes())
+}
+
+/// read an id from four bytes
+fn read_id(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// The public state, with an id for each sequence on the table
+///
+/// The server keeps one to know what changed from a turn to the next, and each client keeps
+/// one to apply the updates.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::state::{ PublicState, StateTracker };
+/// use machiavelli::table::Table;
+/// use machiavelli::sequence_cards::*;
+///
+/// let mut server = StateTracker::new();
+/// let mut client = StateTracker::new();
+/// let mut table = Table::new();
+/// table.add(Sequence::from_cards(&[RegularCard(Club, 2), RegularCard(Club, 3), RegularCard(Club, 4)]));
+/// table.add(Sequence::from_cards(&[RegularCard(Heart, 9), RegularCard(Spade, 9), RegularCard(Club, 9)]));
+/// let state = PublicState { current_player: 0, hand_sizes: vec![5, 6], deck_remaining: 30, table };
+/// client.apply(&server.diff(&state));
+///
+/// // a card added to a sequence only sends that sequence
+/// let mut table = state.table.clone();
+/// let mut run = table.take(1).unwrap();
+/// run.add_card(RegularCard(Club, 5));
+/// table.add(run);
+/// let state = PublicState { current_player: 1, hand_sizes: vec![4, 6], table, ..state };
+/// let update = server.diff(&state);
+/// assert!(update.removed.is_empty());
+/// assert_eq!(1, update.changed.len());
+///
+/// client.apply(&update);
+/// assert_eq!(state.checksum(), client.public_state().checksum());
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct StateTracker {
+    current_player: usize,
+    hand_sizes: Vec<usize>,
+    deck_remaining: usize,
+    sequences: Vec<(u32, Sequence)>,
+    next_id: u32
+}
+
+impl StateTracker {
+
+    /// State before anything is known
+    pub fn new() -> StateTracker {
+        StateTracker {
+            current_player: 0,
+            hand_sizes: Vec::new(),
+            deck_remaining: 0,
+            sequences: Vec::new(),
+            next_id: 0
+        }
+    }
+
+    /// Public state, with the table normalized
+    pub fn public_state(&self) -> PublicState {
+        let mut table = Table::new();
+        for (_, seq) in &self.sequences {
+            table.add(seq.clone());
+        }
+        table.normalize();
+        PublicState {
+            current_player: self.current_player,
+            hand_sizes: self.hand_sizes.clone(),
+            deck_remaining: self.deck_remaining,
+            table
+        }
+    }
+
+    /// Update giving the whole state
+    pub fn snapshot(&self) -> StateUpdate {
+        StateUpdate {
+            full: true,
+            current_player: self.current_player,
+            hand_sizes: self.hand_sizes.clone(),
+            deck_remaining: self.deck_remaining,
+            removed: Vec::new(),
+            changed: self.sequences.clone()
+        }
+    }
+
+    /// Move to a new state, and return the update from the previous one
+    ///
+    /// Sequences still on the table keep their ids. A new sequence sharing cards with a
+    /// sequence which is gone takes its id, as it is most likely the same sequence changed;
+    /// the other new sequences get new ids.
+    pub fn diff(&mut self, state: &PublicState) -> StateUpdate {
+        let mut table = state.table.clone();
+        table.normalize();
+        let mut gone = std::mem::take(&mut self.sequences);
+        let mut new_sequences = Vec::new();
+        for seq in (1..=table.number_sequences()).filter_map(|i| table.get(i)) {
+            match gone.iter().position(|(_, s)| s == seq) {
+                Some(k) => self.sequences.push(gone.remove(k)),
+                None => new_sequences.push(seq.clone())
+            }
+        }
+        let mut changed = Vec::new();
+        for seq in new_sequences {
+            let cards = seq.to_vec();
+            let id = match gone.iter().position(|(_, s)| shares_a_card(s, &cards)) {
+                Some(k) => gone.remove(k).0,
+                None => {
+                    self.next_id += 1;
+                    self.next_id
+                }
+            };
+            changed.push((id, seq.clone()));
+            self.sequences.push((id, seq));
+        }
+        self.current_player = state.current_player;
+        self.hand_sizes = state.hand_sizes.clone();
+        self.deck_remaining = state.deck_remaining;
+        StateUpdate {
+            full: false,
+            current_player: self.current_player,
+            hand_sizes: self.hand_sizes.clone(),
+            deck_remaining: self.deck_remaining,
+            removed: gone.into_iter().map(|(id, _)| id).collect(),
+            changed
+        }
+    }
+
+    /// Apply an update sent by the server
+    pub fn apply(&mut self, update: &StateUpdate) {
+        if update.full {
+            self.sequences.clear();
+        }
+        self.sequences.retain(|(id, _)| !update.removed.contains(id));
+        for (id, seq) in &update.changed {
+            match self.sequences.iter_mut().find(|(i, _)| i == id) {
+                Some(entry) => entry.1 = seq.clone(),
+                None => self.sequences.push((*id, seq.clone()))
+            }
+            self.next_id = self.next_id.max(*id);
+        }
+        self.current_player = update.current_player;
+        self.hand_sizes = update.hand_sizes.clone();
+        self.deck_remaining = update.deck_remaining;
+    }
+}
+
+impl Default for StateTracker {
+    fn default() -> Self {
+        StateTracker::new()
+    }
+}
+
+/// whether a sequence has a card in common with a list of cards
+fn shares_a_card(seq: &Sequence, cards: &[Card]) -> bool {
+    seq.to_vec().iter().any(|card| cards.contains(card))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sequence_cards::*;
+
+    #[test]
+    fn checksum_depends_on_the_cards_not_on_their_order() {
+        let hands = Hand::deal(&["Alice".to_string(), "Bob".to_string()],
+                               vec![Sequence::from_cards(&[Joker]), Sequence::new()]);
+        let deck = Deck::from_cards(&[RegularCard(Spade, 2)]);
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[RegularCard(Club, 3), RegularCard(Club, 4), RegularCard(Club, 5)]));
+        table.add(Sequence::from_cards(&[RegularCard(Heart, 9), RegularCard(Spade, 9), RegularCard(Club, 9)]));
+        let state = PublicState::new(&table, &hands, &deck, 1);
+
+        let mut reordered = Table::new();
+        reordered.add(table.get(2).unwrap().clone());
+        reordered.add(table.get(1).unwrap().clone());
+        let same_state = PublicState { table: reordered, ..state.clone() };
+        assert_eq!(state.checksum(), same_state.checksum());
+
+        let other_state = PublicState { deck_remaining: 0, ..state.clone() };
+        assert_ne!(state.checksum(), other_state.checksum());
+        assert_eq!(Some(state.clone()), PublicState::from_bytes(&state.to_bytes()));
+        assert_eq!(None, PublicState::from_bytes(&state.to_bytes()[..5]));
+    }
+
+    #[test]
+    fn taken_sequences_are_removed_and_full_updates_replace_the_state() {
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[RegularCard(Club, 2), RegularCard(Club, 3), RegularCard(Club, 4)]));
+        table.add(Sequence::from_cards(&[Joker, Joker, Joker]));
+        let state = PublicState { current_player: 0, hand_sizes: vec![1, 2], deck_remaining: 3, table };
+        let mut server = StateTracker::new();
+        let first = server.diff(&state);
+        assert_eq!(2, first.changed.len());
+
+        // the jokers are taken to form another sequence, which gets a new id
+        let mut table = state.table.clone();
+        table.take(2);
+        table.add(Sequence::from_cards(&[RegularCard(Heart, 5), RegularCard(Heart, 6), RegularCard(Heart, 7)]));
+        let state = PublicState { table, ..state };
+        let update = server.diff(&state);
+        assert_eq!(1, update.removed.len());
+        assert_eq!(1, update.changed.len());
+        assert!(first.changed.iter().all(|(id, _)| *id != update.changed[0].0));
+
+        // a client which missed the first update gets the right state from a snapshot
+        let mut client = StateTracker::new();
+        client.apply(&update);
+        assert_ne!(state.checksum(), client.public_state().checksum());
+        client.apply(&server.snapshot());
+        assert_eq!(server, client);
+        assert_eq!(state.checksum(), client.public_state().checksum());
+    }
+}
@@ -4,6 +4,7 @@
 
 use std::fmt;
 use std::collections::HashMap;
+use std::io::{ Read, Write };
 use core::mem::swap;
 use crate::sequence_cards::*;
 use super::reset_style_string;
@@ -33,9 +34,12 @@ impl Table {
         }
     }
     
-    /// Get a table from a sequence of bytes
+    /// Get a table from a sequence of bytes, as given by `to_bytes`
     ///
-    /// Sequences of cards are separated by 255.
+    /// The bytes start with the number of sequences, then give the number of cards of each
+    /// sequence before its cards, all the numbers on two bytes (the most significant first). An
+    /// error is returned if they end before the last sequence, if a card is unknown, or if bytes
+    /// are left after it.
     ///
     /// # Example 
     /// ```
@@ -54,36 +58,77 @@ impl Table {
     ///     RegularCard(Heart, 13), 
     /// ]));
     ///
-    /// let seq_bytes: Vec<u8> = vec![30,31,32,255,11,12,13,255];
-    /// let table2 = Table::from_bytes(&seq_bytes);
+    /// let seq_bytes: Vec<u8> = vec![0,2, 0,3,30,31,32, 0,3,11,12,13];
+    /// let table2 = Table::from_bytes(&seq_bytes).unwrap();
     ///
     /// assert_eq!(table1, table2);
+    ///
+    /// // a truncated table, and bytes after the last sequence
+    /// assert!(Table::from_bytes(&seq_bytes[..10]).is_err());
+    /// assert!(Table::from_bytes(&[0,0,7]).is_err());
     /// ```
-    pub fn from_bytes(bytes: &[u8]) -> Table {
-        let mut cur_seq = Vec::<u8>::new();
-        let mut sequences = Nil;
-        let mut number_sequences: usize = 0;
-        for i in 0..bytes.len() {
-            match bytes[i] {
-                255 => {
-                    number_sequences += 1;
-                    sequences = Cons(Sequence::from_bytes(&cur_seq), Box::new(sequences));
-                    cur_seq = Vec::<u8>::new();
-                },
-                n => {
-                    cur_seq.push(n);
+    pub fn from_bytes(bytes: &[u8]) -> std::io::Result<Table> {
+        let mut reader = bytes;
+        let table = Table::read_from(&mut reader)?;
+        if !reader.is_empty() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData,
+                                           format!("{} bytes after the end of the table", reader.len())));
+        }
+        Ok(table)
+    }
+
+    /// Read a table written by `write_to`, leaving the reader after its last sequence
+    pub fn read_from<R: Read>(reader: &mut R) -> std::io::Result<Table> {
+        let mut table = Table::new();
+        let n_sequences = read_u16(reader)?;
+        for _ in 0..n_sequences {
+            let mut bytes = vec![0u8; read_u16(reader)? as usize];
+            reader.read_exact(&mut bytes)?;
+            let cards = bytes.iter()
+                .map(|&byte| Card::from_byte(byte).ok_or_else(|| std::io::Error::new(
+                    std::io::ErrorKind::InvalidData, format!("unknown card {} on the table", byte))))
+                .collect::<std::io::Result<Vec<Card>>>()?;
+            table.add(Sequence::from_cards(&cards));
+        }
+        Ok(table)
+    }
+
+    /// Get a table from the bytes written by older versions, where each sequence is followed by
+    /// 255, with the number of bytes dropped as they were not cards or came after the last 255
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::table::*;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let (table, n_dropped) = Table::from_legacy_bytes(&[17,18,19,255,11,200,12,13,255,5]);
+    ///
+    /// assert_eq!(2, table.number_sequences());
+    /// assert_eq!(Some(&Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5), 
+    ///                                         RegularCard(Club, 6)])), table.get(2));
+    /// assert_eq!(2, n_dropped);
+    /// ```
+    pub fn from_legacy_bytes(bytes: &[u8]) -> (Table, usize) {
+        let mut table = Table::new();
+        let mut cur_seq = Vec::<Card>::new();
+        let mut n_dropped = 0;
+        for &byte in bytes {
+            match byte {
+                255 => table.add(Sequence::from_cards(&std::mem::take(&mut cur_seq))),
+                n => match Card::from_byte(n) {
+                    Some(card) => cur_seq.push(card),
+                    None => n_dropped += 1
                 }
             }
         }
-        Table {
-            number_sequences,
-            sequences
-        }
+        (table, n_dropped + cur_seq.len())
     }
 
     /// Convert a table to a sequence of bytes
     ///
-    /// Sequences of cards are separated by 255.
+    /// The number of sequences comes first, then the number of cards of each sequence before its
+    /// cards (see `from_bytes`).
     ///
     /// # Example 
     /// ```
@@ -105,26 +150,37 @@ impl Table {
     /// let seq_bytes = table.to_bytes();
     ///
     /// assert_eq!(
-    ///     vec![17,18,19,255,11,12,13,255], 
+    ///     vec![0,2, 0,3,17,18,19, 0,3,11,12,13], 
     ///     seq_bytes);
     /// ```
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut res = Vec::<u8>::new();
-        let mut cur_seq = &self.sequences;
-        for _i in 0..self.number_sequences {
-            match cur_seq {
-                Nil => (),
-                Cons(seq, box_list) => {
-                    let mut buffer = res;
-                    res = (*seq).to_bytes();
-                    res.push(255);
-                    res.append(&mut buffer);
-                    cur_seq = &**box_list;
-                }
-            }
-        }
+        self.write_to(&mut res).unwrap_or(());
         res
     }
+
+    /// Write the bytes of a table, as given by `to_bytes`, without allocating them
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::table::*;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let mut table = Table::new();
+    /// table.add(Sequence::from_cards(&[Joker, Joker, Joker]));
+    /// table.add(Sequence::from_cards(&[RegularCard(Heart, 1), RegularCard(Heart, 2), Joker]));
+    ///
+    /// let mut bytes = Vec::<u8>::new();
+    /// table.write_to(&mut bytes).unwrap();
+    ///
+    /// assert_eq!(table.to_bytes(), bytes);
+    /// assert_eq!(table, Table::read_from(&mut &bytes[..]).unwrap());
+    /// ```
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&(self.number_sequences as u16).to_be_bytes())?;
+        self.sequences.write_to(writer)
+    }
     
     /// Add a new sequence to a table
     ///
@@ -264,6 +320,200 @@ impl Table {
         return Some(res)
     }
 
+    /// Get a reference to a sequence on the table, without taking it
+    ///
+    /// Sequences are numbered from 1, in the order in which they are displayed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::table::*;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let mut table = Table::new();
+    /// table.add(Sequence::from_cards(&[
+    ///     RegularCard(Club, 4),
+    ///     RegularCard(Club, 5),
+    ///     RegularCard(Club, 6),
+    /// ]));
+    /// table.add(Sequence::from_cards(&[
+    ///     RegularCard(Heart, 11),
+    ///     RegularCard(Heart, 12),
+    ///     RegularCard(Heart, 13),
+    /// ]));
+    ///
+    /// assert_eq!(Some(&Sequence::from_cards(&[
+    ///     RegularCard(Club, 4),
+    ///     RegularCard(Club, 5),
+    ///     RegularCard(Club, 6),
+    /// ])), table.get(2));
+    /// assert_eq!(None, table.get(3));
+    /// ```
+    pub fn get(&self, n: usize) -> Option<&Sequence> {
+        let mut i_seq = 1;
+        let mut sl = &self.sequences;
+        while let Cons(seq, new_sl) = sl {
+            if i_seq == n {
+                return Some(seq);
+            }
+            i_seq += 1;
+            sl = new_sl;
+        }
+        None
+    }
+
+    /// Split the sequence at position `n` (from 1) after its first `index` cards
+    ///
+    /// The halves which are valid sequences go back on the table, first, and the cards of the
+    /// others are returned, to be laid down again during the turn. Nothing changes, and `None` is
+    /// returned, if the sequence is not on the table or one of the halves would be empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::table::*;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let mut table = Table::new();
+    /// table.add(Sequence::from_cards(&[
+    ///     RegularCard(Club, 4),
+    ///     RegularCard(Club, 5),
+    ///     RegularCard(Club, 6),
+    ///     RegularCard(Club, 7),
+    ///     RegularCard(Club, 8),
+    /// ]));
+    ///
+    /// let taken = table.split(1, 3).unwrap();
+    /// assert_eq!(vec![RegularCard(Club, 7), RegularCard(Club, 8)], taken.to_vec());
+    /// assert_eq!(Some(&Sequence::from_cards(&[
+    ///     RegularCard(Club, 4),
+    ///     RegularCard(Club, 5),
+    ///     RegularCard(Club, 6),
+    /// ])), table.get(1));
+    /// assert_eq!(None, table.split(1, 3));
+    /// ```
+    pub fn split(&mut self, n: usize, index: usize) -> Option<Sequence> {
+        let cards = self.get(n)?.to_vec();
+        if index == 0 || index >= cards.len() {
+            return None;
+        }
+        self.take(n);
+        let mut taken = Vec::<Card>::new();
+        for half in [&cards[index..], &cards[..index]] {
+            let seq = Sequence::from_cards(half);
+            if seq.clone().is_valid() {
+                self.add(seq);
+            } else {
+                taken.splice(0..0, half.iter().cloned());
+            }
+        }
+        Some(Sequence::from_cards(&taken))
+    }
+
+    /// Put the table in a canonical form
+    ///
+    /// The cards of each sequence are sorted (runs in ascending order with the jokers in the
+    /// gaps, groups by suit), and the sequences are ordered by the suit and rank of their first
+    /// card, sequences of jokers coming last. Two tables with the same sequences are then shown
+    /// and written the same way, whatever the order in which the sequences were laid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::table::*;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let mut table_1 = Table::new();
+    /// table_1.add(Sequence::from_cards(&[
+    ///     RegularCard(Heart, 13),
+    ///     Joker,
+    ///     RegularCard(Heart, 11),
+    /// ]));
+    /// table_1.add(Sequence::from_cards(&[
+    ///     RegularCard(Spade, 7),
+    ///     RegularCard(Club, 7),
+    ///     RegularCard(Heart, 7),
+    /// ]));
+    ///
+    /// let mut table_2 = Table::new();
+    /// table_2.add(Sequence::from_cards(&[
+    ///     RegularCard(Heart, 7),
+    ///     RegularCard(Spade, 7),
+    ///     RegularCard(Club, 7),
+    /// ]));
+    /// table_2.add(Sequence::from_cards(&[
+    ///     RegularCard(Heart, 11),
+    ///     RegularCard(Heart, 13),
+    ///     Joker,
+    /// ]));
+    ///
+    /// table_1.normalize();
+    /// table_2.normalize();
+    /// assert_eq!(table_1, table_2);
+    /// assert_eq!(Some(&Sequence::from_cards(&[
+    ///     RegularCard(Heart, 11),
+    ///     Joker,
+    ///     RegularCard(Heart, 13),
+    /// ])), table_1.get(2));
+    /// ```
+    pub fn normalize(&mut self) {
+        let mut sequences = Vec::<Sequence>::with_capacity(self.number_sequences);
+        while let Some(mut seq) = self.take(1) {
+            normalize_sequence(&mut seq);
+            sequences.push(seq);
+        }
+        sequences.sort_by_cached_key(|seq| {
+            let first = seq.to_vec().into_iter().find(|card| *card != Joker);
+            (first.map(|card| card.to_byte()).unwrap_or(u8::MAX), seq.to_bytes())
+        });
+        for seq in sequences.into_iter().rev() {
+            self.add(seq);
+        }
+    }
+
+    /// Number of sequences on the table
+    pub fn number_sequences(&self) -> usize {
+        self.number_sequences
+    }
+
+    /// Sequences of the table which were not on an earlier table, as they were laid or changed
+    /// since
+    ///
+    /// Each sequence of the earlier table matches at most one identical sequence of this one, so
+    /// that a copy of a sequence which was already there is found.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::table::*;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let mut before = Table::new();
+    /// before.add(Sequence::from_cards(&[
+    ///     RegularCard(Club, 4),
+    ///     RegularCard(Club, 5),
+    ///     RegularCard(Club, 6),
+    /// ]));
+    /// let mut table = before.clone();
+    /// let mut run = table.take(1).unwrap();
+    /// run.add_card(RegularCard(Club, 7));
+    /// table.add(run.clone());
+    /// table.add(before.get(1).unwrap().clone());
+    ///
+    /// assert_eq!(vec![run], table.changed_since(&before));
+    /// ```
+    pub fn changed_since(&self, before: &Table) -> Vec<Sequence> {
+        let mut earlier: Vec<&Sequence> = (1..=before.number_sequences()).filter_map(|i| before.get(i)).collect();
+        let mut res = Vec::new();
+        for seq in (1..=self.number_sequences()).filter_map(|i| self.get(i)) {
+            match earlier.iter().position(|s| *s == seq) {
+                Some(k) => { earlier.remove(k); },
+                None => res.push(seq.clone())
+            }
+        }
+        res
+    }
+
     /// HashMap of the type and number of each card on the table
     ///
     /// # Example
@@ -370,6 +620,23 @@ impl Table {
     }
 }
 
+/// sort the cards of a sequence: runs as arranged by `is_valid`, groups by suit
+fn normalize_sequence(seq: &mut Sequence) {
+    if !seq.is_valid() {
+        seq.sort_by_suit();
+        return;
+    }
+    let cards = seq.to_vec();
+    let mut ranks = cards.iter().filter_map(|card| match card {
+        RegularCard(_, rank) => Some(*rank),
+        Joker => None
+    });
+    let first_rank = ranks.next();
+    if ranks.all(|rank| Some(rank) == first_rank) {
+        seq.sort_by_suit();
+    }
+}
+
 impl fmt::Display for Table {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut i_seq = 1;
@@ -389,6 +656,26 @@ enum SequenceList {
     Nil
 }
 
+impl SequenceList {
+
+    /// write the sequences from the first one added, each followed by 255
+    fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        if let Cons(seq, rest) = self {
+            rest.write_to(writer)?;
+            writer.write_all(&(seq.number_cards() as u16).to_be_bytes())?;
+            seq.write_to(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// read a number written as two bytes, the most significant first
+fn read_u16<R: Read>(reader: &mut R) -> std::io::Result<u16> {
+    let mut bytes: [u8; 2] = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
 impl Clone for SequenceList {
     fn clone(&self) -> Self {
         match self {
@@ -425,4 +712,49 @@ mod tests {
         assert_eq!("1: \u{1b}[1;30m2♣ \u{1b}[1;34m# \u{1b}[1;31m3♦ \u{1b}[1;31m2♥ \u{1b}[0m\u{1b}[30;47m\u{1b}[?25l\u{1b}[K\n2: \u{1b}[1;30m4♣ \u{1b}[1;31m5♦ \u{1b}[1;31m6♥ \u{1b}[0m\u{1b}[30;47m\u{1b}[?25l\u{1b}[K\n".to_string(), format!("{}", &table));
     }
 
+    #[test]
+    fn normalize_keeps_the_cards_and_puts_jokers_last() {
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[Joker, Joker, Joker]));
+        table.add(Sequence::from_cards(&[
+            RegularCard(Spade, 1),
+            RegularCard(Spade, 13),
+            RegularCard(Spade, 12),
+        ]));
+        table.add(Sequence::from_cards(&[
+            RegularCard(Diamond, 2),
+            Joker,
+            RegularCard(Heart, 2),
+        ]));
+        let count_before = table.count_cards();
+        table.normalize();
+
+        assert_eq!(count_before, table.count_cards());
+        assert_eq!(vec![RegularCard(Heart, 2), RegularCard(Diamond, 2), Joker], table.get(1).unwrap().to_vec());
+        assert_eq!(vec![RegularCard(Spade, 12), RegularCard(Spade, 13), RegularCard(Spade, 1)],
+                   table.get(2).unwrap().to_vec());
+        assert_eq!(vec![Joker, Joker, Joker], table.get(3).unwrap().to_vec());
+
+        let normalized = table.clone();
+        table.normalize();
+        assert_eq!(normalized, table);
+    }
+
+    #[test]
+    fn truncated_or_damaged_tables_are_rejected() {
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[RegularCard(Spade, 1), RegularCard(Spade, 2), Joker]));
+        table.add(Sequence::new());
+        table.add(Sequence::from_cards(&[RegularCard(Heart, 7), RegularCard(Club, 7), RegularCard(Diamond, 7)]));
+        let bytes = table.to_bytes();
+        assert_eq!(table, Table::from_bytes(&bytes).unwrap());
+        for n in 0..bytes.len() {
+            assert!(Table::from_bytes(&bytes[..n]).is_err());
+        }
+
+        // a card which does not exist
+        let mut damaged = bytes;
+        *damaged.last_mut().unwrap() = 200;
+        assert!(Table::from_bytes(&damaged).is_err());
+    }
 }
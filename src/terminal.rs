@@ -0,0 +1,158 @@
+//! Restoring the terminal
+//!
+//! The game hides the cursor and changes the colours of the terminal. `TerminalGuard` puts them
+//! back when it is dropped, including when the program panics (the panic message is then printed
+//! with the usual style), and `set_handler` does the same on Ctrl-C. As `std::process::exit` does
+//! not run destructors, the programs quit with `exit` from this module once the guard is created.
+//!
+//! The screen is drawn with ANSI escape sequences. The consoles of Windows 10 and later understand
+//! them once asked to, which `enable_virtual_terminal` does; older consoles do not.
+
+use std::io::{ stdout, Write };
+use std::sync::Once;
+
+/// Escape sequence resetting the attributes and showing the cursor
+pub const RESTORE: &str = "\x1b[0m\x1b[?25h";
+
+/// Escape sequence clearing the screen
+pub const CLEAR: &str = "\x1b[2J\x1b[1;1H";
+
+static PANIC_HOOK: Once = Once::new();
+
+/// Reset the attributes of the terminal and show the cursor
+pub fn restore() {
+    print!("{}", RESTORE);
+    stdout().flush().unwrap_or(());
+}
+
+/// Restore the terminal, then quit with a status code
+pub fn exit(code: i32) -> ! {
+    restore();
+    std::process::exit(code)
+}
+
+/// Restore the terminal before the message of a panic is printed
+///
+/// The hook is only installed once, whatever the number of calls.
+pub fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            println!("{}", RESTORE);
+            previous(info);
+        }));
+    });
+}
+
+/// Restore the terminal on Ctrl-C (and SIGTERM), clearing the screen first if `clear_screen` is
+/// `true`, then quit
+pub fn set_handler(clear_screen: bool) -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(move || {
+        print!("{}", RESTORE);
+        if clear_screen {
+            print!("{}\x1b[K", CLEAR);
+        } else {
+            println!();
+        }
+        exit(130);
+    })
+}
+
+#[cfg(windows)]
+mod console {
+
+    use std::ffi::c_void;
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+    const CP_UTF8: u32 = 65001;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(n_std_handle: u32) -> *mut c_void;
+        fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+        fn SetConsoleOutputCP(code_page: u32) -> i32;
+    }
+
+    pub fn enable_virtual_terminal() -> bool {
+        // SAFETY: the handle is checked before use, and `mode` outlives the call writing to it
+        unsafe {
+            // print the suits and the accents as they are sent
+            SetConsoleOutputCP(CP_UTF8);
+            let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+            if handle.is_null() || handle as isize == -1 {
+                return false;
+            }
+            let mut mode = 0;
+            if GetConsoleMode(handle, &mut mode) == 0 {
+                return false;
+            }
+            mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+                || SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod console {
+
+    pub fn enable_virtual_terminal() -> bool {
+        true
+    }
+}
+
+/// Ask the console to interpret the escape sequences
+///
+/// Return `false` if it can not, in which case the screen will not be shown properly. This only
+/// does something on Windows; the other terminals always interpret them.
+pub fn enable_virtual_terminal() -> bool {
+    console::enable_virtual_terminal()
+}
+
+/// Message for consoles which can not interpret the escape sequences
+pub const NO_VIRTUAL_TERMINAL: &str = "This console does not support the escape sequences used to draw the game; \
+                                       it may not be shown properly (try Windows Terminal or Windows 10 or later).";
+
+/// Terminal whose style is restored when this goes out of scope or the program panics
+pub struct TerminalGuard {
+    _private: ()
+}
+
+impl TerminalGuard {
+
+    /// Install the panic hook and return the guard
+    pub fn new() -> TerminalGuard {
+        install_panic_hook();
+        TerminalGuard { _private: () }
+    }
+}
+
+impl Default for TerminalGuard {
+    fn default() -> Self {
+        TerminalGuard::new()
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn guard_survives_a_panic() {
+        let result = std::panic::catch_unwind(|| {
+            let _guard = TerminalGuard::new();
+            let _other = TerminalGuard::new();
+            panic!("test panic");
+        });
+        assert!(result.is_err());
+        assert!(PANIC_HOOK.is_completed());
+    }
+}
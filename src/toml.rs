@@ -0,0 +1,197 @@
+//! Parser for a small subset of TOML
+//!
+//! Only `key = value` pairs, `[section]` headers, and `#` comments are understood, where a value
+//! is a string between double quotes, a boolean, or an integer. This is all the settings and
+//! config files need.
+
+use std::fmt;
+
+/// Value of a key
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Str(String),
+    Bool(bool),
+    Int(i64)
+}
+
+impl Value {
+
+    fn parse(s: &str) -> Result<Value, String> {
+        if s.starts_with('"') {
+            return unquote(s).map(Value::Str);
+        }
+        match s {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => s.parse().map(Value::Int).map_err(|_| format!("invalid value `{}`", s))
+        }
+    }
+}
+
+/// A `key = value` pair, with the line it comes from
+#[derive(Debug, PartialEq, Clone)]
+pub struct Entry {
+    /// line number, starting from 1
+    pub line: usize,
+    /// section the pair is in, if any
+    pub section: Option<String>,
+    pub key: String,
+    pub value: Value
+}
+
+impl Entry {
+
+    /// Error about this entry
+    pub fn error(&self, message: &str) -> TomlError {
+        TomlError { line: self.line, message: message.to_string() }
+    }
+
+    /// The value, if it is a string
+    pub fn string(&self) -> Result<String, TomlError> {
+        match &self.value {
+            Value::Str(s) => Ok(s.clone()),
+            _ => Err(self.error(&format!("`{}` should be a string between double quotes", self.key)))
+        }
+    }
+
+    /// The value, if it is a boolean
+    pub fn boolean(&self) -> Result<bool, TomlError> {
+        match self.value {
+            Value::Bool(b) => Ok(b),
+            _ => Err(self.error(&format!("`{}` should be true or false", self.key)))
+        }
+    }
+
+    /// The value, if it is an integer between `min` and `max`
+    pub fn integer(&self, min: i64, max: i64) -> Result<i64, TomlError> {
+        match self.value {
+            Value::Int(n) if n >= min && n <= max => Ok(n),
+            _ => Err(self.error(&format!("`{}` should be an integer between {} and {}",
+                                         self.key, min, max)))
+        }
+    }
+}
+
+/// Parse the content of a file into its entries
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::toml::*;
+///
+/// let entries = parse("n_decks = 2 # comment\n\n[keys]\nx = \"e\"\n").unwrap();
+///
+/// assert_eq!(2, entries.len());
+/// assert_eq!(Value::Int(2), entries[0].value);
+/// assert_eq!(Some("keys".to_string()), entries[1].section);
+/// assert_eq!(4, entries[1].line);
+///
+/// assert_eq!("line 2: expected `key = value`", parse("a = 1\nb\n").unwrap_err().to_string());
+/// ```
+pub fn parse(content: &str) -> Result<Vec<Entry>, TomlError> {
+    let mut res = Vec::<Entry>::new();
+    let mut section = None;
+    for (i, line) in content.lines().enumerate() {
+        let line_error = |message: &str| TomlError { line: i + 1, message: message.to_string() };
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            section = Some(line[1..line.len()-1].trim().to_string());
+            continue;
+        }
+        match line.find('=') {
+            Some(j) => res.push(Entry {
+                line: i + 1,
+                section: section.clone(),
+                key: line[..j].trim().to_string(),
+                value: Value::parse(line[j+1..].trim()).map_err(|e| line_error(&e))?
+            }),
+            None => return Err(line_error("expected `key = value`"))
+        }
+    }
+    Ok(res)
+}
+
+/// Write a string between double quotes, escaping them
+pub fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// remove a `#` comment, unless it is in a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => ()
+        }
+    }
+    line
+}
+
+/// read a string written between double quotes
+fn unquote(s: &str) -> Result<String, String> {
+    let mut res = String::new();
+    let mut chars = s.chars().skip(1);
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                return if chars.next().is_none() {
+                    Ok(res)
+                } else {
+                    Err("unexpected characters after the string".to_string())
+                };
+            },
+            '\\' => match chars.next() {
+                Some('"') => res.push('"'),
+                Some('\\') => res.push('\\'),
+                Some('n') => res.push('\n'),
+                Some('t') => res.push('\t'),
+                _ => return Err("invalid escape sequence".to_string())
+            },
+            _ => res.push(c)
+        }
+    }
+    Err("missing closing quote".to_string())
+}
+
+/// Error in a file, with the line where it occurs
+#[derive(Debug, PartialEq)]
+pub struct TomlError {
+    pub line: usize,
+    pub message: String
+}
+
+impl fmt::Display for TomlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn strings_and_comments() {
+        let entries = parse("name = \"a # b \\\"c\\\"\"  # comment\n").unwrap();
+        assert_eq!(Value::Str("a # b \"c\"".to_string()), entries[0].value);
+        assert_eq!("\"a # b \\\"c\\\"\"", quote("a # b \"c\""));
+    }
+
+    #[test]
+    fn errors_have_line_numbers() {
+        assert_eq!(3, parse("a = 1\n\nb = \"c\n").unwrap_err().line);
+        assert_eq!(1, parse("a = \"b\" c\n").unwrap_err().line);
+        let entries = parse("\n\nn = 300\n").unwrap();
+        assert_eq!("line 3: `n` should be an integer between 0 and 255",
+                   entries[0].integer(0, 255).unwrap_err().to_string());
+    }
+}
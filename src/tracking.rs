@@ -0,0 +1,222 @@
+//! Memory of the cards seen during a round
+//!
+//! Every card laid on the table has been seen by all the players, even if it was taken back into a
+//! hand later. A `CardTracker` follows the event log and counts these cards, so that a player can
+//! see how many copies of each card they have not seen yet: those are either in the deck or in
+//! the hands of the others. Since it is only derived from the public events, it needs no help from
+//! the game.
+//!
+//! Cards taken from the table are still known while they are in a hand, and cards played in a
+//! turn which is given up go back to the hand of their player, who has shown them.
+
+use std::collections::HashMap;
+use crate::check::{ name, rank_name, suit_symbol };
+use crate::events::{ Event, EventLog };
+use crate::sequence_cards::{ Card, Sequence, Suit };
+use crate::table::Table;
+
+/// Multiset of cards
+type Counts = HashMap<Card, usize>;
+
+fn add(counts: &mut Counts, cards: &[Card]) {
+    for card in cards {
+        *counts.entry(card.clone()).or_insert(0) += 1;
+    }
+}
+
+/// remove a card if it is there, and tell whether it was
+fn remove(counts: &mut Counts, card: &Card) -> bool {
+    match counts.get_mut(card) {
+        Some(n) if *n > 0 => {
+            *n -= 1;
+            true
+        },
+        _ => false
+    }
+}
+
+/// Cards seen by all the players during a round
+#[derive(Debug, PartialEq, Clone)]
+pub struct CardTracker {
+    n_decks: u8,
+    n_jokers: u8,
+    /// number of decks added when the deck ran out
+    n_new_decks: usize,
+    /// copies of each card seen on the table
+    seen: Counts,
+    /// cards seen on the table which are now in the hand of each player
+    held: HashMap<String, Counts>,
+    /// player of the current turn, with what they held when it started, and the cards they
+    /// played and took since
+    turn: Option<(String, Counts, Vec<Card>, Vec<Card>)>,
+    /// total of the event log when it was last read
+    n_seen: usize
+}
+
+impl CardTracker {
+
+    /// Tracker for a game with `n_decks` decks and `n_jokers` jokers, starting with `table`
+    pub fn new(n_decks: u8, n_jokers: u8, table: &Table) -> CardTracker {
+        let mut seen = Counts::new();
+        for (card, n) in table.count_cards() {
+            seen.insert(card, n as usize);
+        }
+        CardTracker {
+            n_decks,
+            n_jokers,
+            n_new_decks: 0,
+            seen,
+            held: HashMap::new(),
+            turn: None,
+            n_seen: 0
+        }
+    }
+
+    /// Read the events pushed to the log since the last call
+    pub fn sync(&mut self, log: &EventLog) {
+        let new_events: Vec<Event> = log.since(self.n_seen).into_iter().cloned().collect();
+        self.n_seen = log.total();
+        for event in &new_events {
+            self.observe(event);
+        }
+    }
+
+    /// Update the tracker with an event
+    pub fn observe(&mut self, event: &Event) {
+        match event {
+            Event::PlayedSequence(player, seq) | Event::AddedToSequence(player, _, seq) => {
+                let cards = seq.to_vec();
+                self.start_turn(player).2.extend(cards.clone());
+                let held = self.held.entry(player.clone()).or_default();
+                for card in &cards {
+                    if !remove(held, card) {
+                        add(&mut self.seen, std::slice::from_ref(card));
+                    }
+                }
+            },
+            Event::TookSequence(player, _, seq) | Event::SplitSequence(player, _, _, seq) => {
+                let cards = seq.to_vec();
+                self.start_turn(player).3.extend(cards.clone());
+                add(self.held.entry(player.clone()).or_default(), &cards);
+            },
+            Event::GaveUp(player) => {
+
+                // the cards taken go back to the table, and those played from the hand to the hand
+                let (_, mut held, played, taken) = self.start_turn(player).clone();
+                let mut from_hand = Counts::new();
+                add(&mut from_hand, &played);
+                for card in &taken {
+                    remove(&mut from_hand, card);
+                }
+                for (card, n) in from_hand {
+                    *held.entry(card).or_insert(0) += n;
+                }
+                self.held.insert(player.clone(), held);
+                self.turn = None;
+            },
+            Event::DrewCard(_) | Event::EndedTurn(_) => self.turn = None,
+            Event::NewDeck => self.n_new_decks += 1,
+            _ => ()
+        }
+    }
+
+    /// current turn, started by `player` if it is someone else's
+    fn start_turn(&mut self, player: &str) -> &mut (String, Counts, Vec<Card>, Vec<Card>) {
+        if self.turn.as_ref().is_none_or(|(p, _, _, _)| p != player) {
+            let held = self.held.get(player).cloned().unwrap_or_default();
+            self.turn = Some((player.to_string(), held, Vec::new(), Vec::new()));
+        }
+        self.turn.as_mut().unwrap()
+    }
+
+    /// Cards seen on the table which are now in the hands of the players other than `player`
+    pub fn held_by_others(&self, player: &str) -> Vec<Card> {
+        self.held.iter()
+            .filter(|(p, _)| p.as_str() != player)
+            .flat_map(|(_, counts)| counts.iter().flat_map(|(card, &n)| std::iter::repeat_n(card.clone(), n)))
+            .collect()
+    }
+
+    /// Number of copies of each card that `player`, holding `hand`, has not seen
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::events::{ Event, EventLog };
+    /// use machiavelli::sequence_cards::*;
+    /// use machiavelli::table::Table;
+    /// use machiavelli::tracking::CardTracker;
+    ///
+    /// let mut tracker = CardTracker::new(2, 4, &Table::new());
+    /// let mut log = EventLog::new();
+    /// let run = Sequence::from_cards(&[RegularCard(Heart, 1), RegularCard(Heart, 2), RegularCard(Heart, 3)]);
+    /// log.push(Event::PlayedSequence("Alice".to_string(), run.clone()));
+    /// log.push(Event::EndedTurn("Alice".to_string()));
+    ///
+    /// // Bob takes the run back into his hand, and gives up
+    /// log.push(Event::TookSequence("Bob".to_string(), 1, run.clone()));
+    /// log.push(Event::GaveUp("Bob".to_string()));
+    /// tracker.sync(&log);
+    ///
+    /// let unseen = tracker.unseen("Bob", &Sequence::from_cards(&[RegularCard(Heart, 1), Joker]));
+    /// assert_eq!(0, unseen[&RegularCard(Heart, 1)]);
+    /// assert_eq!(1, unseen[&RegularCard(Heart, 2)]);
+    /// assert_eq!(2, unseen[&RegularCard(Spade, 2)]);
+    /// assert_eq!(3, unseen[&Joker]);
+    /// ```
+    pub fn unseen(&self, player: &str, hand: &Sequence) -> HashMap<Card, usize> {
+        let n_times = 1 + self.n_new_decks;
+        let mut known = self.seen.clone();
+        let held = self.held.get(player).cloned().unwrap_or_default();
+        let mut in_hand = Counts::new();
+        add(&mut in_hand, &hand.to_vec());
+        for (card, n) in in_hand {
+            let already_seen = held.get(&card).cloned().unwrap_or(0);
+            *known.entry(card).or_insert(0) += n.saturating_sub(already_seen);
+        }
+        (0..53).filter_map(Card::from_byte).map(|card| {
+            let n_copies = n_times * if card == Card::Joker { self.n_jokers } else { self.n_decks } as usize;
+            let n = n_copies.saturating_sub(known.get(&card).cloned().unwrap_or(0));
+            (card, n)
+        }).collect()
+    }
+
+    /// Table of the cards `player` has not seen, with a row for each suit
+    pub fn render(&self, player: &str, hand: &Sequence) -> String {
+        let unseen = self.unseen(player, hand);
+        let mut res = format!("Unseen cards:\n  {}\n", (1..=13).map(|r| format!("{:>3}", rank_name(r)))
+                              .collect::<String>());
+        for suit in [Suit::Heart, Suit::Diamond, Suit::Club, Suit::Spade].iter() {
+            res += suit_symbol(*suit);
+            res += " ";
+            res += &(1..=13).map(|r| format!("{:>3}", unseen[&Card::RegularCard(*suit, r)])).collect::<String>();
+            res += "\n";
+        }
+        res + &format!("{}  {}\n", name(&Card::Joker), unseen[&Card::Joker])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sequence_cards::Card::*;
+    use crate::sequence_cards::Suit::*;
+
+    #[test]
+    fn cards_played_in_a_turn_given_up_stay_known() {
+        let mut tracker = CardTracker::new(1, 2, &Table::new());
+        let set = Sequence::from_cards(&[RegularCard(Club, 7), RegularCard(Heart, 7), Joker]);
+        tracker.observe(&Event::PlayedSequence("Alice".to_string(), set.clone()));
+        tracker.observe(&Event::GaveUp("Alice".to_string()));
+        tracker.observe(&Event::NewDeck);
+
+        // Alice knows her own cards, and Bob knows she holds them
+        let unseen = tracker.unseen("Alice", &set);
+        assert_eq!((1, 3), (unseen[&RegularCard(Club, 7)], unseen[&Joker]));
+        assert_eq!(3, tracker.held_by_others("Bob").len());
+        let unseen = tracker.unseen("Bob", &Sequence::new());
+        assert_eq!((1, 3, 2), (unseen[&RegularCard(Club, 7)], unseen[&Joker], unseen[&RegularCard(Club, 8)]));
+        assert!(tracker.render("Bob", &Sequence::new()).contains("♣   2  2  2  2  2  2  1  2"));
+    }
+}
@@ -0,0 +1,625 @@
+//! Phases of a turn
+//!
+//! A turn starts in the draw phase, where the player may draw a card. Playing a
+//! sequence or taking one from the table moves the turn to the meld phase, which is left by
+//! passing or by giving up. Both phases lead to the end phase. Each phase only has the actions
+//! allowed in it, so that, for instance, a card can not be drawn once something has been played.
+//! The actions available at any point can also be listed, to show them to the player.
+//!
+//! The game can be saved in either phase: a turn which is not over is written with the game, so
+//! that the save resumes it as it was.
+
+use std::io::{ self, Read, Write };
+use crate::sequence_cards::{ Card, Sequence };
+use crate::table::Table;
+use crate::deck::Deck;
+use crate::solver::{ arrange, can_lay_down_some, find_sequence };
+use crate::plugins;
+
+/// Nothing has been played yet: the player may draw a card or start melding
+#[derive(Debug, PartialEq, Clone)]
+pub struct DrawPhase;
+
+/// The player has laid cards on the table or taken some from it
+#[derive(Debug, PartialEq, Clone)]
+pub struct MeldPhase;
+
+/// The turn is over
+#[derive(Debug, PartialEq, Clone)]
+pub struct EndPhase(TurnEnd);
+
+/// How a turn ended
+#[derive(Debug, PartialEq, Clone)]
+pub enum TurnEnd {
+    /// the player drew a card, or there was none left
+    Drew(Option<Card>),
+    Passed,
+    /// the player put the turn back as it was and drew the penalty
+    GaveUp,
+    Saved
+}
+
+/// Action a player can take during their turn, apart from sorting their hand
+#[derive(Debug, PartialEq, Clone)]
+pub enum Action {
+    Draw,
+    Save,
+    /// lay down a sequence from the hand and the cards taken from the table
+    Play(Sequence),
+    /// take the sequence at this position on the table (from 1)
+    Take(usize),
+    /// add cards from the hand and the cards taken from the table to the sequence at this position
+    /// on the table (from 1), before or after its cards
+    Extend(usize, Sequence, Position),
+    /// split the sequence at this position on the table (from 1) after its first cards; the halves
+    /// which are not valid sequences are taken, and must be laid down again during the turn
+    Split(usize, usize),
+    Pass,
+    GiveUp
+}
+
+/// Where cards added to a sequence of the table go
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Position {
+    Start,
+    End
+}
+
+impl Position {
+
+    /// Sequence with cards added at this position
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::sequence_cards::*;
+    /// use machiavelli::turn::Position;
+    ///
+    /// let seq = Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5), RegularCard(Club, 6)]);
+    /// let extended = Position::Start.extend(&seq, &Sequence::from_cards(&[Joker]));
+    /// assert_eq!(vec![Joker, RegularCard(Club, 4), RegularCard(Club, 5), RegularCard(Club, 6)], extended.to_vec());
+    /// ```
+    pub fn extend(self, seq: &Sequence, cards: &Sequence) -> Sequence {
+        let (first, second) = match self {
+            Position::Start => (cards, seq),
+            Position::End => (seq, cards)
+        };
+        let mut res = first.to_vec();
+        res.extend(second.to_vec());
+        Sequence::from_cards(&res)
+    }
+}
+
+/// A turn, in the phase `P`, with the situation at its start
+#[derive(Debug, PartialEq, Clone)]
+pub struct Turn<P> {
+    hand_start: Sequence,
+    table_start: Table,
+    phase: P
+}
+
+impl<P> Turn<P> {
+
+    /// Hand of the player at the start of the turn
+    pub fn hand_start(&self) -> &Sequence {
+        &self.hand_start
+    }
+
+    /// Table at the start of the turn
+    pub fn table_start(&self) -> &Table {
+        &self.table_start
+    }
+
+    /// Whether cards from the hand have been laid on the table
+    pub fn has_played(&self, hand: &Sequence) -> bool {
+        !hand.contains(&self.hand_start)
+    }
+
+    /// Put the hand and the table back as they were at the start of the turn
+    pub fn restore(self, table: &mut Table, hand: &mut Sequence) {
+        *table = self.table_start;
+        *hand = self.hand_start;
+    }
+
+    /// Cards taken from the table and not played yet, whether they were kept apart or put in
+    /// the hand
+    fn pending_cards(&self, hand: &Sequence, cards_from_table: &Sequence) -> Sequence {
+        let mut pending = hand.clone();
+        for card in self.hand_start.to_vec() {
+            pending.remove_card(&card);
+        }
+        pending.merge(cards_from_table.clone());
+        pending
+    }
+
+    fn into_phase<Q>(self, phase: Q) -> Turn<Q> {
+        Turn { hand_start: self.hand_start, table_start: self.table_start, phase }
+    }
+}
+
+impl Turn<DrawPhase> {
+
+    /// Start a turn with the current table and hand
+    pub fn start(table: &Table, hand: &Sequence) -> Turn<DrawPhase> {
+        Turn { hand_start: hand.clone(), table_start: table.clone(), phase: DrawPhase }
+    }
+
+    /// Draw a card and end the turn
+    ///
+    /// With the custom rule on jokers, a player holding one can not draw, and neither can a player
+    /// whom a plugin forbids it; the turn is then given back with the reason.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::deck::Deck;
+    /// use machiavelli::table::Table;
+    /// use machiavelli::sequence_cards::*;
+    /// use machiavelli::turn::{ Turn, TurnEnd };
+    ///
+    /// let mut hand = Sequence::from_cards(&[RegularCard(Heart, 5)]);
+    /// let mut deck = Deck::from_cards(&[Joker]);
+    /// let turn = Turn::start(&Table::new(), &hand);
+    ///
+    /// let end = turn.draw(&mut hand, &mut deck, true).unwrap();
+    /// assert_eq!(&TurnEnd::Drew(Some(Joker)), end.outcome());
+    /// assert_eq!(2, hand.number_cards());
+    /// ```
+    pub fn draw(self, hand: &mut Sequence, deck: &mut Deck, custom_rule_jokers: bool)
+        -> Result<Turn<EndPhase>, (Turn<DrawPhase>, String)>
+    {
+        if custom_rule_jokers && hand.contains_joker() {
+            return Err((self, "Jokers must be played!".to_string()));
+        }
+        let plugins = plugins::registered();
+        if let Some(reason) = plugins.refusal(&self.hand_start, hand, &Action::Draw) {
+            return Err((self, reason));
+        }
+        let card = crate::pick_a_card(hand, deck).ok();
+        let outcome = TurnEnd::Drew(card);
+        plugins.on_turn_end(hand, &outcome);
+        Ok(self.into_phase(EndPhase(outcome)))
+    }
+
+    /// Stop the turn to save the game
+    pub fn save(self) -> Turn<EndPhase> {
+        self.into_phase(EndPhase(TurnEnd::Saved))
+    }
+
+    /// Go on after a sequence has been played or taken from the table
+    pub fn meld(self) -> Turn<MeldPhase> {
+        self.into_phase(MeldPhase)
+    }
+}
+
+impl Turn<MeldPhase> {
+
+    /// End the turn without drawing
+    ///
+    /// The cards taken from the table, whether they are kept apart (`cards_from_table`) or in the
+    /// hand, must all have been played, and so must the jokers with the custom rule. The player
+    /// must also have played something from their hand, and no plugin must forbid passing.
+    /// Otherwise, the turn is given back with the reason.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::table::Table;
+    /// use machiavelli::sequence_cards::*;
+    /// use machiavelli::turn::{ Turn, TurnEnd };
+    ///
+    /// let mut table = Table::new();
+    /// let mut hand = Sequence::from_cards(&[RegularCard(Heart, 1), RegularCard(Heart, 2),
+    ///                                       RegularCard(Heart, 3), RegularCard(Spade, 7)]);
+    /// let turn = Turn::start(&table, &hand).meld();
+    ///
+    /// // nothing has been played yet
+    /// let (turn, _) = turn.pass(&hand, &Sequence::new(), false).unwrap_err();
+    ///
+    /// let mut played = Sequence::new();
+    /// for _ in 0..3 {
+    ///     played.add_card(hand.take_card(1).unwrap());
+    /// }
+    /// table.add(played);
+    /// let end = turn.pass(&hand, &Sequence::new(), false).unwrap();
+    /// assert_eq!(&TurnEnd::Passed, end.outcome());
+    /// ```
+    pub fn pass(self, hand: &Sequence, cards_from_table: &Sequence, custom_rule_jokers: bool)
+        -> Result<Turn<EndPhase>, (Turn<MeldPhase>, String)>
+    {
+        match self.pass_refusal(hand, cards_from_table, custom_rule_jokers) {
+            Some(reason) => Err((self, reason)),
+            None => {
+                plugins::registered().on_turn_end(hand, &TurnEnd::Passed);
+                Ok(self.into_phase(EndPhase(TurnEnd::Passed)))
+            }
+        }
+    }
+
+    /// Check that the player could end the turn with `pass`, without ending it, and give the
+    /// reason if they could not
+    pub fn can_pass(&self, hand: &Sequence, cards_from_table: &Sequence, custom_rule_jokers: bool)
+        -> Result<(), String>
+    {
+        self.pass_refusal(hand, cards_from_table, custom_rule_jokers).map_or(Ok(()), Err)
+    }
+
+    /// Why the player can't pass, if they can't
+    fn pass_refusal(&self, hand: &Sequence, cards_from_table: &Sequence, custom_rule_jokers: bool)
+        -> Option<String>
+    {
+        if cards_from_table.number_cards() != 0 || !self.hand_start.contains(hand) {
+            Some("You can't end your turn until you've played all the cards you've taken from the table!"
+                 .to_string())
+        } else if custom_rule_jokers && hand.contains_joker() {
+            Some("Jokers must be played!".to_string())
+        } else if !self.has_played(hand) {
+            Some("You need to play something to pass".to_string())
+        } else {
+            plugins::registered().refusal(&self.hand_start, hand, &Action::Pass)
+        }
+    }
+
+    /// Put the hand and the table back as they were, draw the penalty, and end the turn
+    pub fn give_up(self, table: &mut Table, hand: &mut Sequence, deck: &mut Deck,
+                   cards_from_table: &mut Sequence) -> Turn<EndPhase> {
+        crate::give_up(table, hand, deck, &self.hand_start, &self.table_start, cards_from_table);
+        plugins::registered().on_turn_end(hand, &TurnEnd::GaveUp);
+        self.into_phase(EndPhase(TurnEnd::GaveUp))
+    }
+}
+
+impl Turn<EndPhase> {
+
+    /// How the turn ended
+    pub fn outcome(&self) -> &TurnEnd {
+        &self.phase.0
+    }
+}
+
+/// A turn which is not over, in either phase
+#[derive(Debug, PartialEq, Clone)]
+pub enum Ongoing {
+    Draw(Turn<DrawPhase>),
+    Meld(Turn<MeldPhase>)
+}
+
+impl Ongoing {
+
+    /// Start a turn with the current table and hand
+    pub fn start(table: &Table, hand: &Sequence) -> Ongoing {
+        Ongoing::Draw(Turn::start(table, hand))
+    }
+
+    /// Move to the meld phase, if the turn is not there yet
+    pub fn meld(self) -> Ongoing {
+        match self {
+            Ongoing::Draw(turn) => Ongoing::Meld(turn.meld()),
+            meld => meld
+        }
+    }
+
+    /// Hand of the player at the start of the turn
+    pub fn hand_start(&self) -> &Sequence {
+        match self {
+            Ongoing::Draw(turn) => turn.hand_start(),
+            Ongoing::Meld(turn) => turn.hand_start()
+        }
+    }
+
+    /// Table at the start of the turn
+    pub fn table_start(&self) -> &Table {
+        match self {
+            Ongoing::Draw(turn) => turn.table_start(),
+            Ongoing::Meld(turn) => turn.table_start()
+        }
+    }
+
+    /// Whether cards from the hand have been laid on the table
+    pub fn has_played(&self, hand: &Sequence) -> bool {
+        !hand.contains(self.hand_start())
+    }
+
+    /// Put the hand and the table back as they were at the start of the turn
+    pub fn restore(self, table: &mut Table, hand: &mut Sequence) {
+        match self {
+            Ongoing::Draw(turn) => turn.restore(table, hand),
+            Ongoing::Meld(turn) => turn.restore(table, hand)
+        }
+    }
+
+    /// Actions the player can take now
+    ///
+    /// The sequences to play are found among the cards of the hand and those taken from the
+    /// table, one after the other, so that they can all be played. Once cards have been taken from
+    /// the table, only the sequences with which they can all be laid down are listed; the sequences
+    /// of the table can be split where both halves are valid, or where the cards of the halves
+    /// which are not can all be laid down with them. The cards which can be added to a sequence of
+    /// the table are listed one by one, at its end. The plugins
+    /// can refuse some of the sequences and remove actions.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::table::Table;
+    /// use machiavelli::sequence_cards::*;
+    /// use machiavelli::turn::{ Action, Ongoing, Position };
+    ///
+    /// let mut table = Table::new();
+    /// table.add(Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5),
+    ///                                  RegularCard(Club, 6), RegularCard(Club, 7)]));
+    /// table.add(Sequence::from_cards(&[RegularCard(Heart, 1), RegularCard(Heart, 2),
+    ///                                  RegularCard(Heart, 3)]));
+    /// let hand = Sequence::from_cards(&[RegularCard(Heart, 7), RegularCard(Spade, 7), Joker]);
+    /// let turn = Ongoing::start(&table, &hand);
+    ///
+    /// assert_eq!(vec![Action::Draw, Action::Save, Action::Play(Sequence::from_cards(&[
+    ///                     RegularCard(Heart, 7), RegularCard(Spade, 7), Joker])),
+    ///                 Action::Take(1), Action::Take(2),
+    ///                 Action::Extend(1, Sequence::from_cards(&[Joker]), Position::End),
+    ///                 Action::Extend(2, Sequence::from_cards(&[Joker]), Position::End)],
+    ///            turn.legal_actions(&table, &hand, &Sequence::new(), false));
+    ///
+    /// // with the custom rule, the joker must be played before drawing
+    /// assert!(!turn.legal_actions(&table, &hand, &Sequence::new(), true).contains(&Action::Draw));
+    /// ```
+    pub fn legal_actions(&self, table: &Table, hand: &Sequence, cards_from_table: &Sequence, 
+                         custom_rule_jokers: bool) -> Vec<Action> {
+        let mut actions = Vec::<Action>::new();
+        let pending = match self {
+            Ongoing::Draw(turn) => {
+                if !(custom_rule_jokers && hand.contains_joker()) {
+                    actions.push(Action::Draw);
+                }
+                actions.push(Action::Save);
+                turn.pending_cards(hand, cards_from_table)
+            },
+            Ongoing::Meld(turn) => {
+                actions.push(Action::Save);
+                turn.pending_cards(hand, cards_from_table)
+            }
+        };
+
+        // sequences to play
+        let plugins = plugins::registered();
+        let mut cards = hand.clone();
+        cards.merge(cards_from_table.clone());
+        while let Some(seq) = find_sequence(&cards) {
+            for card in seq.to_vec() {
+                cards.remove_card(&card);
+            }
+            if plugins.validate_meld(&seq).is_ok() {
+                actions.push(Action::Play(seq));
+            }
+        }
+
+        // sequences from the table with which the cards already taken can be laid down
+        for n in 1..=table.number_sequences() {
+            if let Some(seq) = table.get(n) {
+                let mut taken = pending.clone();
+                taken.merge(seq.clone());
+                if pending.number_cards() == 0 || arrange(&taken).is_some() {
+                    actions.push(Action::Take(n));
+                }
+            }
+        }
+
+        // splits of the sequences of the table whose halves are valid, or, once cards have been
+        // taken, with which they can all be laid down (before that, the halves could only be put
+        // back together, as after taking the sequence)
+        for n in 1..=table.number_sequences() {
+            if let Some(seq) = table.get(n) {
+                let cards = seq.to_vec();
+                for index in 1..cards.len() {
+                    let mut taken = pending.clone();
+                    for half in [&cards[..index], &cards[index..]] {
+                        if !Sequence::from_cards(half).is_valid() {
+                            taken.merge(Sequence::from_cards(half));
+                        }
+                    }
+                    let valid = taken.number_cards() == pending.number_cards();
+                    if valid || (pending.number_cards() > 0 && arrange(&taken).is_some()) {
+                        actions.push(Action::Split(n, index));
+                    }
+                }
+            }
+        }
+
+        // single cards which can be added to a sequence of the table
+        let mut cards = hand.to_vec();
+        cards.extend(cards_from_table.to_vec());
+        let mut tried = Vec::<Card>::new();
+        for n in 1..=table.number_sequences() {
+            tried.clear();
+            for card in &cards {
+                if tried.contains(card) {
+                    continue;
+                }
+                tried.push(card.clone());
+                let added = Sequence::from_cards(std::slice::from_ref(card));
+                if let Some(seq) = table.get(n) {
+                    let mut extended = Position::End.extend(seq, &added);
+                    if extended.is_valid() && plugins.validate_meld(&extended).is_ok() {
+                        actions.push(Action::Extend(n, added, Position::End));
+                    }
+                }
+            }
+        }
+
+        if let Ongoing::Meld(turn) = self {
+            if turn.pass_refusal(hand, cards_from_table, custom_rule_jokers).is_none() {
+                actions.push(Action::Pass);
+            }
+            actions.push(Action::GiveUp);
+        }
+        plugins.allowed_actions(self.hand_start(), hand, actions)
+    }
+
+    /// Whether the only thing the player can do is to draw a card
+    ///
+    /// This is the case at the start of the turn if no sequence can be formed from the hand and no
+    /// card of the hand can be laid down by rearranging the table.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::table::Table;
+    /// use machiavelli::sequence_cards::*;
+    /// use machiavelli::turn::Ongoing;
+    ///
+    /// let mut table = Table::new();
+    /// table.add(Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5),
+    ///                                  RegularCard(Club, 6)]));
+    /// let hand = Sequence::from_cards(&[RegularCard(Heart, 9), RegularCard(Spade, 12)]);
+    /// assert!(Ongoing::start(&table, &hand).is_forced(&table, &hand, false));
+    ///
+    /// let hand = Sequence::from_cards(&[RegularCard(Heart, 9), RegularCard(Club, 7)]);
+    /// assert!(!Ongoing::start(&table, &hand).is_forced(&table, &hand, false));
+    /// ```
+    pub fn is_forced(&self, table: &Table, hand: &Sequence, custom_rule_jokers: bool) -> bool {
+        match self {
+            Ongoing::Draw(_) => !(custom_rule_jokers && hand.contains_joker())
+                && find_sequence(hand).is_none()
+                && !can_lay_down_some(hand, table),
+            Ongoing::Meld(_) => false
+        }
+    }
+
+    /// Write the turn: its phase on one byte (0 to draw, 1 to meld), the hand at its start (the
+    /// number of cards on two bytes, then the cards), and the table at its start (the number of
+    /// bytes on two bytes, then the table)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::table::Table;
+    /// use machiavelli::sequence_cards::*;
+    /// use machiavelli::turn::Ongoing;
+    ///
+    /// let mut table = Table::new();
+    /// table.add(Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Club, 5),
+    ///                                  RegularCard(Club, 6)]));
+    /// let turn = Ongoing::start(&table, &Sequence::from_cards(&[Joker])).meld();
+    ///
+    /// let mut bytes = Vec::new();
+    /// turn.write_to(&mut bytes).unwrap();
+    /// assert_eq!(turn, Ongoing::read_from(&mut &bytes[..]).unwrap());
+    /// ```
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let (phase, hand_start, table_start) = match self {
+            Ongoing::Draw(turn) => (0, &turn.hand_start, &turn.table_start),
+            Ongoing::Meld(turn) => (1, &turn.hand_start, &turn.table_start)
+        };
+        writer.write_all(&[phase])?;
+        writer.write_all(&(hand_start.number_cards() as u16).to_be_bytes())?;
+        hand_start.write_to(writer)?;
+        let mut table_bytes = Vec::new();
+        table_start.write_to(&mut table_bytes)?;
+        writer.write_all(&(table_bytes.len() as u16).to_be_bytes())?;
+        writer.write_all(&table_bytes)
+    }
+
+    /// Read a turn written by `write_to`
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Ongoing> {
+        Ongoing::read_with_table(reader, Table::from_bytes)
+    }
+
+    /// Read a turn saved by older versions, whose table is read with `Table::from_legacy_bytes`,
+    /// with the number of bytes of the table which were dropped
+    pub fn read_legacy<R: Read>(reader: &mut R) -> io::Result<(Ongoing, usize)> {
+        let mut n_dropped = 0;
+        let turn = Ongoing::read_with_table(reader, |bytes| {
+            let (table, n) = Table::from_legacy_bytes(bytes);
+            n_dropped = n;
+            Ok(table)
+        })?;
+        Ok((turn, n_dropped))
+    }
+
+    // read a turn, with its table read from its bytes by `read_table`
+    fn read_with_table<R: Read, F: FnOnce(&[u8]) -> io::Result<Table>>(reader: &mut R, read_table: F)
+        -> io::Result<Ongoing>
+    {
+        let mut bytes = [0u8; 2];
+        let mut phase = [0u8; 1];
+        reader.read_exact(&mut phase)?;
+        reader.read_exact(&mut bytes)?;
+        let hand_start = Sequence::read_from(reader, u16::from_be_bytes(bytes) as usize)?;
+        reader.read_exact(&mut bytes)?;
+        let mut table_bytes = vec![0u8; u16::from_be_bytes(bytes) as usize];
+        reader.read_exact(&mut table_bytes)?;
+        let table_start = read_table(&table_bytes)?;
+        let turn = Turn { hand_start, table_start, phase: DrawPhase };
+        match phase[0] {
+            0 => Ok(Ongoing::Draw(turn)),
+            1 => Ok(Ongoing::Meld(turn.meld())),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unknown phase of the turn"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sequence_cards::Card::*;
+    use crate::sequence_cards::Suit::*;
+
+    #[test]
+    fn jokers_must_be_played_before_drawing() {
+        let mut hand = Sequence::from_cards(&[Joker]);
+        let mut deck = Deck::from_cards(&[RegularCard(Club, 4)]);
+        let turn = Turn::start(&Table::new(), &hand);
+        let (turn, message) = turn.draw(&mut hand, &mut deck, true).unwrap_err();
+        assert_eq!("Jokers must be played!", message);
+        let end = turn.draw(&mut hand, &mut deck, false).unwrap();
+        assert_eq!(&TurnEnd::Drew(Some(RegularCard(Club, 4))), end.outcome());
+        let end = Turn::start(&Table::new(), &hand).draw(&mut hand, &mut deck, false).unwrap();
+        assert_eq!(&TurnEnd::Drew(None), end.outcome());
+    }
+
+    #[test]
+    fn cards_from_the_table_must_be_played_to_pass() {
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[RegularCard(Club, 1), RegularCard(Club, 2), RegularCard(Club, 3)]));
+        let mut hand = Sequence::from_cards(&[RegularCard(Club, 4), RegularCard(Heart, 9)]);
+        let mut deck = Deck::from_cards(&[RegularCard(Spade, 8), RegularCard(Spade, 9), RegularCard(Spade, 10)]);
+        let turn = Ongoing::start(&table, &hand);
+        hand.merge(table.take(1).unwrap());
+        let turn = match turn.meld() {
+            Ongoing::Meld(turn) => turn,
+            Ongoing::Draw(_) => panic!("the turn should be in the meld phase")
+        };
+        let (turn, _) = turn.pass(&hand, &Sequence::new(), false).unwrap_err();
+        let end = turn.give_up(&mut table, &mut hand, &mut deck, &mut Sequence::new());
+        assert_eq!(&TurnEnd::GaveUp, end.outcome());
+        assert_eq!(1, table.number_sequences());
+        assert_eq!(2 + crate::PENALTY_RESET, hand.number_cards());
+    }
+
+    #[test]
+    fn takes_must_leave_a_way_to_lay_down_the_cards() {
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[RegularCard(Heart, 1), RegularCard(Heart, 2), RegularCard(Heart, 3)]));
+        let hand = Sequence::from_cards(&[RegularCard(Diamond, 12)]);
+        let cards_from_table = Sequence::from_cards(&[RegularCard(Spade, 9)]);
+        let turn = Ongoing::start(&table, &hand).meld();
+        assert_eq!(vec![Action::Save, Action::GiveUp], turn.legal_actions(&table, &hand, &cards_from_table, false));
+    }
+
+    #[test]
+    fn splits_must_leave_valid_sequences() {
+        let mut table = Table::new();
+        table.add(Sequence::from_cards(&[RegularCard(Spade, 3), RegularCard(Spade, 4), RegularCard(Spade, 5),
+                                         RegularCard(Spade, 6), RegularCard(Spade, 7), RegularCard(Spade, 8)]));
+        let hand = Sequence::from_cards(&[RegularCard(Diamond, 12)]);
+        let turn = Ongoing::start(&table, &hand).meld();
+        let splits = |cards_from_table: &Sequence| turn.legal_actions(&table, &hand, cards_from_table, false)
+            .into_iter().filter(|a| matches!(a, Action::Split(..))).collect::<Vec<Action>>();
+        assert_eq!(vec![Action::Split(1, 3)], splits(&Sequence::new()));
+
+        // the 2♠ taken from the table can be laid down with the 3♠ and the 4♠
+        let cards_from_table = Sequence::from_cards(&[RegularCard(Spade, 2)]);
+        assert_eq!(vec![Action::Split(1, 2), Action::Split(1, 3)], splits(&cards_from_table));
+    }
+}
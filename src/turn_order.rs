@@ -0,0 +1,241 @@
+//! Order in which the players take their turns
+//!
+//! The players sit in the order in which they joined, and play one after the other around the
+//! table. The player starting a round moves one seat further at each new round, so that nobody
+//! keeps the advantage of playing first.
+//!
+//! With the `draw_for_first` rule, who starts the game is decided by drawing cards: each player
+//! draws one from a separate deck, aces high, and the players tied for the highest card draw again.
+
+use rand::Rng;
+use crate::deck::Deck;
+use crate::colours::paint;
+use crate::reset_style_string;
+use crate::sequence_cards::Card;
+
+/// Seating of the players, with the player who started the round and the one whose turn it is
+#[derive(Debug, PartialEq, Clone)]
+pub struct TurnOrder {
+    n_players: usize,
+    starting_player: usize,
+    current: usize
+}
+
+impl TurnOrder {
+
+    /// Order for a new round started by `starting_player`
+    pub fn new(n_players: usize, starting_player: usize) -> TurnOrder {
+        TurnOrder::resume(n_players, starting_player, starting_player)
+    }
+
+    /// Order for a round started by `starting_player`, during the turn of `current`
+    pub fn resume(n_players: usize, starting_player: usize, current: usize) -> TurnOrder {
+        let n_players = n_players.max(1);
+        TurnOrder {
+            n_players,
+            starting_player: starting_player % n_players,
+            current: current % n_players
+        }
+    }
+
+    /// Player whose turn it is
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Player who started the round
+    pub fn starting_player(&self) -> usize {
+        self.starting_player
+    }
+
+    /// Player who plays after the current one
+    pub fn next(&self) -> usize {
+        (self.current + 1) % self.n_players
+    }
+
+    /// Pass the turn to the next player
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::turn_order::TurnOrder;
+    ///
+    /// let mut order = TurnOrder::new(3, 2);
+    /// order.advance();
+    ///
+    /// assert_eq!(0, order.current());
+    /// assert_eq!(1, order.next());
+    /// assert_eq!(2, order.starting_player());
+    /// ```
+    pub fn advance(&mut self) {
+        self.current = self.next();
+    }
+
+    /// Start a new round with the next player around the table
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::turn_order::TurnOrder;
+    ///
+    /// let mut order = TurnOrder::new(2, 1);
+    /// order.advance();
+    /// order.new_round();
+    ///
+    /// assert_eq!(0, order.starting_player());
+    /// assert_eq!(0, order.current());
+    /// ```
+    pub fn new_round(&mut self) {
+        self.starting_player = (self.starting_player + 1) % self.n_players;
+        self.current = self.starting_player;
+    }
+
+    /// Line showing the seating, with the current player in brackets, and who plays next
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::turn_order::TurnOrder;
+    ///
+    /// let names = vec!["Alice".to_string(), "Bob".to_string(), "Carol".to_string()];
+    ///
+    /// assert_eq!("Turn order: Alice → [Bob] → Carol (next: Carol)", TurnOrder::resume(3, 0, 1).render(&names));
+    /// ```
+    pub fn render(&self, player_names: &[String]) -> String {
+        let seats: Vec<String> = player_names.iter().enumerate()
+            .map(|(i, name)| if i == self.current { format!("[{}]", paint(name)) } else { paint(name) })
+            .collect();
+        match player_names.get(self.next()) {
+            Some(next) if self.n_players > 1 => format!("Turn order: {} (next: {})", seats.join(" → "), paint(next)),
+            _ => format!("Turn order: {}", seats.join(" → "))
+        }
+    }
+}
+
+/// Cards drawn to decide who plays first
+#[derive(Debug, PartialEq, Clone)]
+pub struct FirstPlayerDraw {
+    /// cards drawn at each pass, with the player who drew them
+    pub passes: Vec<Vec<(usize, Card)>>,
+    /// player who drew the highest card
+    pub winner: usize
+}
+
+impl FirstPlayerDraw {
+
+    /// Lines telling the players who drew what, and who plays first
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use machiavelli::turn_order::FirstPlayerDraw;
+    /// use machiavelli::sequence_cards::*;
+    ///
+    /// let names = vec!["Alice".to_string(), "Bob".to_string()];
+    /// let draw = FirstPlayerDraw { passes: vec![vec![(0, RegularCard(Heart, 1)), (1, RegularCard(Spade, 13))]],
+    ///                              winner: 0 };
+    ///
+    /// assert!(draw.render(&names).ends_with("Alice drew the highest card and plays first"));
+    /// ```
+    pub fn render(&self, player_names: &[String]) -> String {
+        let name = |i: usize| player_names.get(i).map(|n| paint(n)).unwrap_or_else(|| "?".to_string());
+        let mut lines: Vec<String> = self.passes.iter().enumerate()
+            .map(|(i, pass)| {
+                let cards: Vec<String> = pass.iter()
+                    .map(|(player, card)| format!("{} draws {}{}", name(*player), card, reset_style_string()))
+                    .collect();
+                format!("{}{}", if i == 0 { "" } else { "Tie! " }, cards.join(", "))
+            })
+            .collect();
+        lines.push(format!("{} drew the highest card and plays first", name(self.winner)));
+        lines.join("\n")
+    }
+}
+
+// rank of a card when drawing for the first turn, aces high
+fn high_card_rank(card: &Card) -> u8 {
+    match card {
+        Card::RegularCard(_, 1) => 14,
+        Card::RegularCard(_, value) => *value,
+        Card::Joker => 0
+    }
+}
+
+// let the players draw until a single one has the highest card
+fn resolve<F: FnMut() -> Card>(n_players: usize, mut draw: F) -> FirstPlayerDraw {
+    let mut in_the_running: Vec<usize> = (0..n_players.max(1)).collect();
+    let mut passes = Vec::new();
+    loop {
+        let pass: Vec<(usize, Card)> = in_the_running.iter().map(|&i| (i, draw())).collect();
+        let highest = pass.iter().map(|(_, card)| high_card_rank(card)).max().unwrap_or(0);
+        in_the_running = pass.iter().filter(|(_, card)| high_card_rank(card) == highest).map(|(i, _)| *i).collect();
+        passes.push(pass);
+        if in_the_running.len() == 1 {
+            return FirstPlayerDraw { passes, winner: in_the_running[0] };
+        }
+    }
+}
+
+/// Let each player draw a card from a separate deck to decide who plays first
+///
+/// The players who tie for the highest card draw again, until one of them has the highest card.
+///
+/// # Example
+///
+/// ```
+/// use machiavelli::turn_order::draw_for_first;
+///
+/// let draw = draw_for_first(3, &mut rand::thread_rng());
+///
+/// assert!(draw.winner < 3);
+/// assert_eq!(3, draw.passes[0].len());
+/// assert!(draw.passes.last().unwrap().iter().any(|(i, _)| *i == draw.winner));
+/// ```
+pub fn draw_for_first<R: Rng + ?Sized>(n_players: usize, rng: &mut R) -> FirstPlayerDraw {
+    let mut deck = Deck::new();
+    resolve(n_players, || loop {
+        match deck.draw() {
+            Some(card) => return card,
+            None => deck = Deck::shuffled(1, 0, rng)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn rounds_rotate() {
+        let mut order = TurnOrder::new(3, 0);
+        let mut starting_players = Vec::new();
+        for _ in 0..4 {
+            starting_players.push(order.current());
+            order.advance();
+            order.advance();
+            order.new_round();
+        }
+        assert_eq!(vec![0, 1, 2, 0], starting_players);
+    }
+
+    #[test]
+    fn out_of_range_players() {
+        let order = TurnOrder::resume(2, 5, 3);
+        assert_eq!((1, 1), (order.starting_player(), order.current()));
+        assert_eq!("Turn order: [Solo]", TurnOrder::new(1, 0).render(&["Solo".to_string()]));
+    }
+
+    #[test]
+    fn ties_draw_again() {
+        use crate::sequence_cards::*;
+        let mut cards = vec![RegularCard(Heart, 1), RegularCard(Club, 1), RegularCard(Spade, 13),
+                             RegularCard(Heart, 9), RegularCard(Club, 4)].into_iter();
+        let draw = resolve(3, || cards.next().unwrap());
+        assert_eq!(0, draw.winner);
+        assert_eq!(vec![(0, RegularCard(Heart, 9)), (1, RegularCard(Club, 4))], draw.passes[1]);
+        let names = vec!["Alice".to_string(), "Bob".to_string(), "Carol".to_string()];
+        assert_eq!(3, draw.render(&names).lines().count());
+        assert_eq!(0, resolve(1, || Joker).winner);
+    }
+}
@@ -0,0 +1,138 @@
+//! Screen of a network player waiting for their turn
+//!
+//! Between their turns, a player sees the situation sent by the server after each action of the
+//! active player: the status bar with the cards left in the deck and in each hand, the table,
+//! and the latest events. Below it, a line tells whose turn it is and how long it has lasted,
+//! and what the active player is doing when it does not show on the table, like sorting their
+//! hand or looking at a play before laying it down.
+//! A thread of the client redraws this line every second, so that the screen does not look
+//! frozen while the active player thinks; it is stopped, and the line erased, before anything
+//! else is printed.
+
+use std::io::{ stdout, Write };
+use std::sync::mpsc::{ channel, RecvTimeoutError, Sender };
+use std::thread::{ spawn, JoinHandle };
+use std::time::{ Duration, Instant };
+use crate::clock::format_duration;
+
+/// time between two updates of the line
+const N_MILLISECONDS_TICK: u64 = 1000;
+
+/// Line telling whose turn it is, or what they are doing, and for how long they have been playing
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use machiavelli::waiting::waiting_line;
+///
+/// assert_eq!("Waiting for Alice (playing for 1m 05s)", waiting_line("Alice", None, Duration::from_secs(65)));
+/// assert_eq!("Alice is arranging cards... (playing for 5s)",
+///            waiting_line("Alice", Some("Alice is arranging cards..."), Duration::from_secs(5)));
+/// ```
+pub fn waiting_line(name: &str, activity: Option<&str>, elapsed: Duration) -> String {
+    match activity {
+        Some(activity) => format!("{} (playing for {})", activity, format_duration(elapsed)),
+        None => format!("Waiting for {} (playing for {})", name, format_duration(elapsed))
+    }
+}
+
+/// Turn a player is waiting for, with the thread showing it
+#[derive(Debug, Default)]
+pub struct WaitingScreen {
+    /// player whose turn it is, and when it started
+    turn: Option<(String, Instant)>,
+    /// what the active player is doing, since the screen was last updated
+    activity: Option<String>,
+    /// thread redrawing the line, which stops when the channel is closed
+    ticker: Option<(Sender<()>, JoinHandle<()>)>
+}
+
+impl WaitingScreen {
+
+    /// Screen of a player who is not waiting yet
+    pub fn new() -> WaitingScreen {
+        WaitingScreen::default()
+    }
+
+    /// Wait for the turn of `name`, who started it `elapsed` ago
+    pub fn wait_for(&mut self, name: &str, elapsed: Duration) {
+        let now = Instant::now();
+        self.turn = Some((name.to_string(), now.checked_sub(elapsed).unwrap_or(now)));
+        self.activity = None;
+    }
+
+    /// Show what the active player is doing, until the screen is updated
+    pub fn show_activity(&mut self, activity: &str) {
+        if self.turn.is_some() {
+            self.activity = Some(activity.to_string());
+        }
+    }
+
+    /// Stop waiting, when the screen is cleared or the player has something to do
+    pub fn stop(&mut self) {
+        self.turn = None;
+        self.activity = None;
+    }
+
+    /// Line to show, if a turn is being waited for
+    pub fn line(&self) -> Option<String> {
+        self.turn.as_ref().map(|(name, start)| waiting_line(name, self.activity.as_deref(), start.elapsed()))
+    }
+
+    /// Stop redrawing the line, and erase it
+    pub fn pause(&mut self) {
+        if let Some((sender, handle)) = self.ticker.take() {
+            drop(sender);
+            handle.join().unwrap_or(());
+            print!("\r\x1b[K");
+            stdout().flush().unwrap_or(());
+        }
+    }
+
+    /// Draw the line below what was printed, and redraw it every second until `pause` is called
+    pub fn resume(&mut self) {
+        self.pause();
+        let (name, start) = match self.turn.clone() {
+            Some(turn) => turn,
+            None => return
+        };
+        let activity = self.activity.clone();
+        let (sender, receiver) = channel::<()>();
+        let handle = spawn(move || loop {
+            print!("\r\x1b[K{}", waiting_line(&name, activity.as_deref(), start.elapsed()));
+            stdout().flush().unwrap_or(());
+            if receiver.recv_timeout(Duration::from_millis(N_MILLISECONDS_TICK)) != Err(RecvTimeoutError::Timeout) {
+                break;
+            }
+        });
+        self.ticker = Some((sender, handle));
+    }
+}
+
+impl Drop for WaitingScreen {
+    fn drop(&mut self) {
+        self.pause();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn turn_time_counts_from_the_start_of_the_turn() {
+        let mut screen = WaitingScreen::new();
+        assert_eq!(None, screen.line());
+        screen.wait_for("Alice", Duration::from_secs(30));
+        assert_eq!(Some("Waiting for Alice (playing for 30s)".to_string()), screen.line());
+        screen.show_activity("Alice is arranging cards...");
+        assert_eq!(Some("Alice is arranging cards... (playing for 30s)".to_string()), screen.line());
+        screen.resume();
+        screen.pause();
+        screen.stop();
+        screen.show_activity("Alice is arranging cards...");
+        assert_eq!(None, screen.line());
+    }
+}
@@ -0,0 +1,200 @@
+//! Whole games played by bots from seeded decks, checking the state of the game after each turn
+
+use machiavelli::*;
+use machiavelli::table::Table;
+use machiavelli::events::EventLog;
+use machiavelli::stalemate::StalemateDetector;
+use machiavelli::audit::shuffled_deck;
+
+/// turns after which a game is stopped, if nobody has won
+const MAX_N_TURNS: usize = 1000;
+
+/// turns after which a game with new decks is stopped (the hands grow, and the bots slow down)
+const MAX_N_TURNS_NEW_DECKS: usize = 250;
+
+/// State of a game between two turns
+#[derive(Debug, PartialEq, Clone)]
+struct Game {
+    table: Table,
+    hands: Vec<Hand>,
+    deck: Deck,
+    player: usize
+}
+
+fn config(n_players: u8, custom_rule_jokers: bool, empty_deck: EmptyDeckRule) -> Config {
+    Config {
+        n_decks: 2,
+        n_jokers: 4,
+        n_cards_to_start: 13,
+        custom_rule_jokers,
+        n_players,
+        empty_deck,
+        joker_penalty: DEFAULT_JOKER_PENALTY,
+        mulligan: MulliganRule::Off,
+        draw_for_first: false
+    }
+}
+
+fn player_names(config: &Config) -> Vec<String> {
+    (1..=config.n_players).map(|i| format!("Bot {}", i)).collect()
+}
+
+/// Shuffle a deck from a seed and deal the hands, as the server does
+fn deal(config: &Config, seed: u64) -> Game {
+    let mut deck = shuffled_deck(config.n_decks, config.n_jokers, seed);
+    let mut hands = vec![Sequence::new(); config.n_players as usize];
+    for hand in hands.iter_mut() {
+        for _ in 0..config.n_cards_to_start {
+            hand.add_card(deck.draw().unwrap());
+        }
+    }
+    Game { table: Table::new(), hands: Hand::deal(&player_names(config), hands), deck, player: 0 }
+}
+
+fn n_cards(game: &Game) -> usize {
+    let n_on_table: u16 = game.table.count_cards().values().sum();
+    n_on_table as usize + game.deck.remaining()
+        + game.hands.iter().map(|h| h.number_cards()).sum::<usize>()
+}
+
+/// Check the state of the game after a turn, with `n_total` cards in the game
+fn check_turn(config: &Config, n_total: usize, before: &Game, after: &Game) {
+
+    // no card appears or disappears
+    assert_eq!(n_total, n_cards(after));
+
+    // every sequence on the table is valid
+    for i in 1..=after.table.number_sequences() {
+        let mut seq = after.table.get(i).unwrap().clone();
+        assert!(seq.is_valid(), "invalid sequence on the table: {}", seq);
+    }
+
+    // at most one card is drawn per turn
+    assert!(after.deck.remaining() <= before.deck.remaining());
+    assert!(before.deck.remaining() - after.deck.remaining() <= 1);
+
+    // jokers can't be kept with the custom rule, unless one was just drawn
+    if config.custom_rule_jokers && after.deck.remaining() == before.deck.remaining() {
+        assert!(!after.hands[before.player].contains_joker());
+    }
+}
+
+/// Save the game and load it back, checking that nothing changed
+fn save_and_load(config: &Config, game: &Game) -> Game {
+    let bytes = game_to_bytes(0, game.player as u8, &game.table, &game.hands, &game.deck, config);
+    let bytes = encode::xor(&bytes, b"machiavelli_save.sav");
+    let bytes = encode::xor(&bytes, b"machiavelli_save.sav");
+    let (loaded_config, _, player, table, hands, deck) = match load_game(&bytes) {
+        Ok(loaded) => loaded,
+        Err(_) => panic!("the save could not be loaded")
+    };
+    assert_eq!(*config, loaded_config);
+    assert_eq!(player_names(config), hand::owners(&hands));
+    let loaded = Game { table, hands, deck, player: player as usize };
+    assert_eq!(game.hands, loaded.hands);
+    assert_eq!(game.deck, loaded.deck);
+    assert_eq!(game.table.count_cards(), loaded.table.count_cards());
+    assert_eq!(game.table.to_bytes(), loaded.table.to_bytes());
+    loaded
+}
+
+/// Play a game between bots; return the last state, the number of turns, and whether it ended
+///
+/// With `reload`, the game is saved and loaded back after each turn, and goes on from the loaded
+/// state.
+fn play(config: &Config, seed: u64, reload: bool) -> (Game, usize, bool) {
+    let max_n_turns = match config.empty_deck {
+        EmptyDeckRule::NewDeck => MAX_N_TURNS_NEW_DECKS,
+        _ => MAX_N_TURNS
+    };
+    let names = player_names(config);
+    let mut game = deal(config, seed);
+    let mut n_total = n_cards(&game);
+    let mut log = EventLog::new();
+    let mut stalemate = StalemateDetector::new(config.n_players as usize);
+    let mut n_new_decks: u64 = 0;
+    for n_turns in 1..=max_n_turns {
+        let player = game.player;
+
+        // apply the rule for an empty deck; a new deck adds its cards to the game
+        if game.deck.remaining() == 0 {
+            match config.empty_deck {
+                EmptyDeckRule::NewDeck => {
+                    n_new_decks += 1;
+                    game.deck = shuffled_deck(config.n_decks, config.n_jokers, seed + n_new_decks);
+                    n_total += game.deck.remaining();
+                },
+                EmptyDeckRule::Draw => return (game, n_turns - 1, true),
+                EmptyDeckRule::Score => ()
+            }
+        }
+
+        let before = game.clone();
+        bot::play_turn(&mut game.table, &mut game.hands[player], &mut game.deck,
+                       config, &names[player], &mut log);
+        game.player = (player + 1) % config.n_players as usize;
+        check_turn(config, n_total, &before, &game);
+
+        if game.hands[player].number_cards() == 0 {
+            return (game, n_turns, true);
+        }
+        let played = game.hands[player].number_cards() < before.hands[player].number_cards();
+        if config.empty_deck == EmptyDeckRule::Score
+            && stalemate.record_turn(game.deck.remaining() == 0, played)
+        {
+            return (game, n_turns, true);
+        }
+        if reload {
+            game = save_and_load(config, &game);
+        }
+    }
+    (game, max_n_turns, false)
+}
+
+#[test]
+fn games_with_the_draw_rule() {
+    for n_players in 2..=4 {
+        let config = config(n_players, false, EmptyDeckRule::Draw);
+        for seed in 0..10 {
+            let (game, n_turns, ended) = play(&config, seed, false);
+            assert!(ended, "seed {}: the game did not end", seed);
+            assert!(n_turns > 0);
+            assert!(game.deck.remaining() == 0 || game.hands.iter().any(|h| h.number_cards() == 0));
+        }
+    }
+}
+
+#[test]
+fn games_with_the_custom_rule_for_jokers() {
+    let config = config(3, true, EmptyDeckRule::Score);
+    for seed in 0..10 {
+        let (_, _, ended) = play(&config, seed, false);
+        assert!(ended, "seed {}: the game did not end", seed);
+    }
+}
+
+#[test]
+fn games_with_new_decks() {
+    let config = config(2, false, EmptyDeckRule::NewDeck);
+    let n_cards_in_decks = 2 * 52 + 4;
+    let mut shuffled = false;
+    for seed in 0..3 {
+        let (game, _, ended) = play(&config, seed, false);
+        assert!(!ended || game.hands.iter().any(|h| h.number_cards() == 0));
+        shuffled |= n_cards(&game) > n_cards_in_decks;
+    }
+    assert!(shuffled);
+}
+
+#[test]
+fn saved_games_go_on_as_if_never_saved() {
+    let config = config(3, false, EmptyDeckRule::Score);
+    for seed in 0..5 {
+        let straight = play(&config, seed, false);
+        let reloaded = play(&config, seed, true);
+        assert_eq!(straight.0.hands, reloaded.0.hands);
+        assert_eq!(straight.0.deck, reloaded.0.deck);
+        assert_eq!(straight.0.table.count_cards(), reloaded.0.table.count_cards());
+        assert_eq!(straight.1, reloaded.1);
+    }
+}
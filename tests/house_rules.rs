@@ -0,0 +1,58 @@
+//! Games with a house rule added by a plugin
+//!
+//! The plugins are registered for the whole program, so these tests have their own binary.
+
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use machiavelli::*;
+use machiavelli::plugins::{ self, RulePlugin };
+use machiavelli::script;
+use machiavelli::turn::{ Action, Ongoing, TurnEnd };
+
+/// turns ended since the plugin was registered
+static N_TURNS_ENDED: AtomicUsize = AtomicUsize::new(0);
+
+/// aces stay in the hands, and a player must lay down four cards before passing
+struct Opening;
+
+impl RulePlugin for Opening {
+    fn name(&self) -> &str {
+        "opening"
+    }
+
+    fn validate_meld(&self, sequence: &Sequence) -> Result<(), String> {
+        if sequence.to_vec().iter().any(|card| matches!(card, RegularCard(_, 1))) {
+            Err("Aces can't be laid down".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn allowed_actions(&self, hand_start: &Sequence, hand: &Sequence, actions: Vec<Action>) -> Vec<Action> {
+        let n_played = hand_start.number_cards().saturating_sub(hand.number_cards());
+        actions.into_iter().filter(|action| *action != Action::Pass || n_played >= 4).collect()
+    }
+
+    fn on_turn_end(&self, _hand: &Sequence, _outcome: &TurnEnd) {
+        N_TURNS_ENDED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn plugins_are_run_by_the_game() {
+    plugins::register(Opening);
+    let mut game = script::run("n_cards_to_start = 4\ncustom_rule_jokers = false\n\
+                                deck AH 2H 3H 9C  4H 5H 6H 9D  KS QS\nplayers Alice Bob\n").unwrap();
+
+    // Alice holds AH 2H 3H 9C, and can only draw
+    let actions = Ongoing::start(&game.table, &game.hands[0]).legal_actions(&game.table, &game.hands[0],
+                                                                          &Sequence::new(), false);
+    assert_eq!(vec![Action::Draw, Action::Save], actions);
+    assert_eq!(Err("Aces can't be laid down".to_string()), game.play("p 1 2 3"));
+    game.play("e").unwrap();
+
+    // Bob lays down three cards, which is not enough to pass
+    game.play("p 1 2 3").unwrap();
+    assert_eq!(Err("This is not allowed by the opening rule".to_string()), game.play("e"));
+    game.play("g").unwrap();
+    assert_eq!(2, N_TURNS_ENDED.load(Ordering::Relaxed));
+}
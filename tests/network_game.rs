@@ -0,0 +1,488 @@
+//! Scripted network games, played through memory connections
+
+use std::io::Read;
+use std::sync::{ mpsc, Arc };
+use std::thread;
+use std::time::{ Duration, Instant };
+use machiavelli::sequence_cards::*;
+use machiavelli::table::Table;
+use machiavelli::deck::Deck;
+use machiavelli::hand::Hand;
+use machiavelli::events::{ Event, EventLog, PreviousTurn };
+use machiavelli::layout::LayoutSettings;
+use machiavelli::connection::{ Connection, MemoryConnection, MemoryListener };
+use machiavelli::player_name::display_names;
+use machiavelli::lib_server::{ start_player_turn, send_message_all_players, send_exit_message_to_client,
+                               send_token_to_client, send_situation_to_client, sync_state_with_client,
+                               try_reconnection, Player, PlayerView, PublicState, Seats, ServerConfig,
+                               StateTracker, TurnOrder, TurnView };
+use machiavelli::lib_client::{ get_str_from_server, send_str_to_server, send_bytes_to_server, send_hello,
+                                private_message_to_string, receive_situation, receive_state };
+use machiavelli::situation_to_string;
+
+/// Play the requests of the server with the replies of a script, and return what was printed
+///
+/// The client stops when the server tells it to, when the connection is closed, or when the
+/// script has no reply left.
+fn run_client(stream: MemoryConnection, script: &[&str]) -> String {
+    run_client_with(stream, script, Some(LayoutSettings::new()), Duration::from_secs(0))
+}
+
+/// Same as `run_client`, sending `layout` when the server asks for it, or never replying if it is
+/// `None`, and waiting for `first_reply_delay` before the first reply
+fn run_client_with(mut stream: MemoryConnection, script: &[&str], layout: Option<LayoutSettings>,
+                   first_reply_delay: Duration) -> String {
+    let mut replies = script.iter();
+    let mut delay = Some(first_reply_delay);
+    let mut printed = String::new();
+    let mut request = [0u8];
+    let mut state = None;
+    while stream.read_exact(&mut request).is_ok() {
+        match request[0] {
+            1 | 2 | 7 => printed += &get_str_from_server(&mut stream).unwrap(),
+            3 | 4 | 11 => {
+                if request[0] != 4 {
+                    printed += &get_str_from_server(&mut stream).unwrap();
+                }
+                if let Some(delay) = delay.take() {
+                    thread::sleep(delay);
+                }
+                match replies.next() {
+                    Some(reply) => if send_str_to_server(&mut stream, reply).is_err() {
+                        break;
+                    },
+                    None => break
+                }
+            },
+            6 => printed += "[turn]",
+            12 => printed += &format!("[token {}]", get_str_from_server(&mut stream).unwrap()),
+            14 => {
+                printed += &format!("[waiting for {}]", get_str_from_server(&mut stream).unwrap());
+                get_str_from_server(&mut stream).unwrap();
+            },
+            15 => printed += &format!("[{}]", get_str_from_server(&mut stream).unwrap()),
+            13 => {
+                let in_sync = receive_state(&mut stream, &mut state).unwrap();
+                send_bytes_to_server(&mut stream, &[if in_sync { 0 } else { 1 }]).unwrap();
+            },
+            10 => {
+                let sender = get_str_from_server(&mut stream).unwrap();
+                let message = get_str_from_server(&mut stream).unwrap();
+                printed += &private_message_to_string(&sender, &message);
+            },
+            8 => if let Some(layout) = &layout {
+                send_bytes_to_server(&mut stream, &layout.to_bytes()).unwrap();
+            },
+            16 => printed += &receive_situation(&mut stream, &state, &LayoutSettings::new()).unwrap(),
+            9 => {
+                printed += &get_str_from_server(&mut stream).unwrap();
+                break;
+            },
+            _ => break
+        }
+    }
+    printed
+}
+
+/// Connect to the listener with a name and a token, as the client does when it starts
+fn say_hello(listener: &MemoryListener, name: &str, token: &str) -> MemoryConnection {
+    let mut stream = listener.connect();
+    send_hello(&mut stream, name, token).unwrap();
+    let mut accepted = [0u8];
+    stream.read_exact(&mut accepted).unwrap();
+    assert_eq!(1, accepted[0]);
+    get_str_from_server(&mut stream).unwrap();
+    stream
+}
+
+/// Play turns until a player has no card left, and return the hands, the table, and the log
+fn run_server(streams: Vec<MemoryConnection>, listener: Arc<MemoryListener>, hands: Vec<Sequence>,
+              deck: Deck) -> (Vec<Hand>, Table, EventLog)
+{
+    run_server_with(streams, listener, hands, deck, ServerConfig::new())
+}
+
+/// Same as `run_server`, with the turn timeout and the bots of `server_config`
+fn run_server_with(streams: Vec<MemoryConnection>, listener: Arc<MemoryListener>, hands: Vec<Sequence>,
+                   mut deck: Deck, server_config: ServerConfig) -> (Vec<Hand>, Table, EventLog)
+{
+    let hands = Hand::deal(&["Alice".to_string(), "Bob".to_string()], hands);
+    let mut players = Player::seat(hands, streams);
+    let mut table = Table::new();
+    let mut log = EventLog::new();
+    let mut seats = Seats::new(2, 0);
+    let mut player = 0;
+    loop {
+        players[player].turn_deadline = server_config.turn_timeout().map(|t| Instant::now() + t);
+        players[player].last_message = start_player_turn(&mut table, &mut players, &mut deck, false, player,
+                                                         listener.as_ref(), &mut log, &server_config,
+                                                         &mut seats, None, &PreviousTurn::default(), &mut |_, _, _, _, _| ()).unwrap();
+        if players[player].hand.number_cards() == 0 {
+            let message = format!("{} wins!\n", players[player].name());
+            log.push(Event::Won(players[player].name().to_string()));
+            send_message_all_players(&mut players, &message);
+            break;
+        }
+        player = 1 - player;
+    }
+    for player in players.iter_mut().filter(|player| player.connected) {
+        send_exit_message_to_client(player, "Bye!").unwrap();
+    }
+    (players.into_iter().map(|player| player.hand).collect(), table, log)
+}
+
+fn hands() -> Vec<Sequence> {
+    vec![Sequence::from_cards(&[RegularCard(Heart, 1), RegularCard(Heart, 2), RegularCard(Heart, 3),
+                                RegularCard(Heart, 4)]),
+         Sequence::from_cards(&[RegularCard(Spade, 5), RegularCard(Spade, 6), RegularCard(Spade, 7)])]
+}
+
+#[test]
+fn two_player_game() {
+    let listener = Arc::new(MemoryListener::new());
+    let (alice_server, alice_client) = MemoryConnection::pair();
+    let (bob_server, bob_client) = MemoryConnection::pair();
+    let alice = thread::spawn(move || run_client(alice_client, &["p 1 2 3", "n", "p 1 2 3", "y", "e", "y", "a 1 1", "y"]));
+    let bob = thread::spawn(move || run_client(bob_client, &["x", "e"]));
+
+    let deck = Deck::from_cards(&[RegularCard(Club, 13)]);
+    let (hands, table, log) = run_server(vec![alice_server, bob_server], listener, hands(), deck);
+    let alice = alice.join().unwrap();
+    let bob = bob.join().unwrap();
+
+    // Alice played her hearts, Bob drew a card, and Alice added her last card
+    assert_eq!(0, hands[0].number_cards());
+    assert_eq!(4, hands[1].number_cards());
+    assert_eq!(1, table.number_sequences());
+    assert_eq!(4, table.get(1).unwrap().number_cards());
+    assert_eq!(5, log.total());
+    assert_eq!(Event::DrewCard("Bob".to_string()), log.last(3)[0].clone());
+
+    // each player saw what they should
+    assert_eq!(2, alice.matches("[turn]").count());
+    assert_eq!(1, bob.matches("[turn]").count());
+    assert!(bob.contains("Invalid input; please try again."));
+    assert!(alice.contains("Valid: it becomes sequence 1 of the table\nPlay it?"));
+    assert!(alice.contains("Nothing was played"));
+    assert!(alice.contains("Valid: sequence 1 becomes sequence 1 of the table\nAdd the cards?"));
+    assert!(alice.contains("This turn:\n") && alice.contains("You will have 1 card left\nEnd your turn?"));
+    assert!(bob.contains("Alice's turn:"));
+    assert!(bob.contains("Deck: 1 │ Alice: 1 card "));
+    assert!(bob.contains("[waiting for Alice]") && alice.contains("[waiting for Bob]"));
+
+    // a play which is not laid down shows once, and ending the turn does not show
+    assert_eq!(2, bob.matches("[Alice is arranging cards...]").count());
+    assert_eq!(1, alice.matches("[Bob is arranging cards...]").count());
+    assert!(alice.contains("Alice wins!") && bob.contains("Alice wins!"));
+    assert!(alice.ends_with("Bye!") && bob.ends_with("Bye!"));
+}
+
+#[test]
+fn waiting_players_do_not_hold_up_the_turn() {
+    let listener = Arc::new(MemoryListener::new());
+    let (alice_server, alice_client) = MemoryConnection::pair();
+    let (bob_server, bob_client) = MemoryConnection::pair();
+    let alice = thread::spawn(move || run_client(alice_client, &["p 1 2 3", "y"]));
+
+    // Bob's client never sends its layout
+    let bob = thread::spawn(move || run_client_with(bob_client, &[], None, Duration::from_secs(0)));
+    let hands = vec![Sequence::from_cards(&[RegularCard(Heart, 1), RegularCard(Heart, 2), RegularCard(Heart, 3)]),
+                     Sequence::from_cards(&[Joker])];
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let result = run_server(vec![alice_server, bob_server], listener, hands, Deck::from_cards(&[]));
+        sender.send(result).unwrap();
+    });
+    let (hands, _, _) = receiver.recv_timeout(Duration::from_secs(10)).expect("the turn was held up");
+    assert_eq!(0, hands[0].number_cards());
+    assert!(alice.join().unwrap().contains("Alice wins!"));
+    assert!(bob.join().unwrap().contains("Alice wins!"));
+}
+
+#[test]
+fn a_bot_takes_the_seat_of_a_waiting_player_who_left() {
+    let listener = Arc::new(MemoryListener::new());
+    let (alice_server, alice_client) = MemoryConnection::pair();
+    let (bob_server, bob_client) = MemoryConnection::pair();
+    drop(bob_client);
+    let alice = thread::spawn(move || run_client(alice_client, &["p 1 2 3", "y"]));
+
+    let hands = vec![Sequence::from_cards(&[RegularCard(Heart, 1), RegularCard(Heart, 2), RegularCard(Heart, 3)]),
+                     Sequence::from_cards(&[Joker])];
+    let (hands, _, log) = run_server(vec![alice_server, bob_server], listener, hands, Deck::from_cards(&[]));
+    assert_eq!(0, hands[0].number_cards());
+    assert_eq!(Event::BotTookSeat("Bob".to_string()), log.last(3)[0].clone());
+    let alice = alice.join().unwrap();
+    assert!(alice.contains("A bot plays for Bob until they reconnect.") && alice.contains("Alice wins!"));
+}
+
+/// Let Alice play her hearts then her spades with a script, while Bob leaves when he is shown her
+/// first play, and check that a bot takes his seat and that Alice wins
+fn play_while_a_player_leaves(alice_script: &'static [&'static str]) {
+    let listener = Arc::new(MemoryListener::new());
+    let (alice_server, alice_client) = MemoryConnection::pair();
+    let (bob_server, mut bob_client) = MemoryConnection::pair();
+    let alice = thread::spawn(move || run_client(alice_client, alice_script));
+
+    // Bob leaves when he is shown Alice's first play
+    let bob = thread::spawn(move || {
+        let mut request = [0u8];
+        while bob_client.read_exact(&mut request).is_ok() && request[0] != 7 {
+            get_str_from_server(&mut bob_client).unwrap();
+            if request[0] == 14 {
+                get_str_from_server(&mut bob_client).unwrap();
+            }
+        }
+    });
+    let hands = vec![Sequence::from_cards(&[RegularCard(Heart, 1), RegularCard(Heart, 2), RegularCard(Heart, 3),
+                                            RegularCard(Spade, 5), RegularCard(Spade, 6), RegularCard(Spade, 7)]),
+                     Sequence::from_cards(&[Joker])];
+    let (hands, table, log) = run_server(vec![alice_server, bob_server], listener, hands, Deck::from_cards(&[]));
+    bob.join().unwrap();
+    assert_eq!(0, hands[0].number_cards());
+    assert_eq!(2, table.number_sequences());
+    assert!(log.last(4).contains(&&Event::BotTookSeat("Bob".to_string())));
+    let alice = alice.join().unwrap();
+    assert!(alice.contains("A bot plays for Bob until they reconnect.") && alice.contains("Alice wins!"));
+}
+
+#[test]
+fn a_bot_takes_the_seat_of_a_player_who_leaves_during_a_turn() {
+    play_while_a_player_leaves(&["p 1 2 3", "y", "p 1 2 3", "y"]);
+}
+
+#[test]
+fn a_bot_takes_the_seat_of_a_player_who_leaves_during_a_list() {
+    play_while_a_player_leaves(&["play 1 2 3; sort suit", "p 1 2 3", "y"]);
+}
+
+#[test]
+fn players_out_of_time_get_a_little_more() {
+    let listener = Arc::new(MemoryListener::new());
+    let (alice_server, alice_client) = MemoryConnection::pair();
+    let (bob_server, bob_client) = MemoryConnection::pair();
+
+    // Alice plays after her 4 seconds, but within the second more she gets
+    let alice = thread::spawn(move || run_client_with(alice_client, &["p 1 2 3", "y"], Some(LayoutSettings::new()),
+                                                      Duration::from_millis(4500)));
+    let bob = thread::spawn(move || run_client(bob_client, &[]));
+    let hands = vec![Sequence::from_cards(&[RegularCard(Heart, 1), RegularCard(Heart, 2), RegularCard(Heart, 3)]),
+                     Sequence::from_cards(&[Joker])];
+    let server_config = ServerConfig { turn_timeout: 4, ..ServerConfig::new() };
+    let (hands, _, log) = run_server_with(vec![alice_server, bob_server], listener, hands, Deck::from_cards(&[]),
+                                          server_config);
+    assert_eq!(0, hands[0].number_cards());
+    assert_eq!(Event::Won("Alice".to_string()), log.last(1)[0].clone());
+    let bob = bob.join().unwrap();
+    assert!(bob.contains("Alice is out of time; waiting a little more for them..."));
+    assert!(!bob.contains("Alice seems to have disconnected"));
+    assert!(alice.join().unwrap().contains("Alice wins!"));
+}
+
+#[test]
+fn players_still_out_of_time_are_disconnected() {
+    let listener = Arc::new(MemoryListener::new());
+    let (alice_server, alice_client) = MemoryConnection::pair();
+    let (bob_server, bob_client) = MemoryConnection::pair();
+
+    // Alice does not play within her second and a quarter, and a bot takes her seat after a second
+    let alice = thread::spawn(move || run_client_with(alice_client, &["e"], Some(LayoutSettings::new()),
+                                                      Duration::from_secs(4)));
+    let bob = thread::spawn(move || run_client(bob_client, &["p 1 2 3", "y"]));
+    let server_config = ServerConfig { turn_timeout: 1, reconnection_timeout: 1, bot_takeover: true,
+                                       ..ServerConfig::new() };
+    let (hands, _, log) = run_server_with(vec![alice_server, bob_server], listener, hands(), Deck::from_cards(&[]),
+                                          server_config);
+    assert_eq!(4, hands[0].number_cards());
+    assert_eq!(0, hands[1].number_cards());
+    assert!(log.last(3).contains(&&Event::BotTookSeat("Alice".to_string())));
+    let bob = bob.join().unwrap();
+    assert!(bob.contains("Alice is out of time; waiting a little more for them..."));
+    assert!(bob.contains("Alice seems to have disconnected"));
+    assert!(bob.contains("Bob wins!"));
+    alice.join().unwrap();
+}
+
+#[test]
+fn lists_of_actions_are_played_as_a_whole() {
+    let listener = Arc::new(MemoryListener::new());
+    let (alice_server, alice_client) = MemoryConnection::pair();
+    let (bob_server, bob_client) = MemoryConnection::pair();
+    let alice = thread::spawn(move || run_client(alice_client, &["play 1 2 3; take 9", "play 1 2 3; end",
+                                                                 "extend 1 with 4♥"]));
+    let bob = thread::spawn(move || run_client(bob_client, &["e"]));
+
+    let deck = Deck::from_cards(&[RegularCard(Club, 13)]);
+    let (hands, table, _) = run_server(vec![alice_server, bob_server], listener, hands(), deck);
+    let alice = alice.join().unwrap();
+    let bob = bob.join().unwrap();
+    assert_eq!(0, hands[0].number_cards());
+    assert_eq!(4, table.get(1).unwrap().number_cards());
+    assert!(alice.contains("Nothing was played: action 2 (`take 9`): sequence 9 is not on the table"));
+    assert!(bob.contains("Alice played"));
+    assert!(alice.contains("Alice wins!"));
+}
+
+#[test]
+fn player_reconnects_during_their_turn() {
+    let listener = Arc::new(MemoryListener::new());
+    let (alice_server, alice_client) = MemoryConnection::pair();
+    let (bob_server, bob_client) = MemoryConnection::pair();
+    let alice = thread::spawn(move || run_client(alice_client, &["e", "p 1 2 3 4", "y"]));
+
+    // Bob leaves when his turn starts, then comes back and plays his spades
+    let bob_first = thread::spawn(move || run_client(bob_client, &[]));
+    let server_listener = Arc::clone(&listener);
+    let deck = Deck::from_cards(&[RegularCard(Club, 13), RegularCard(Club, 12)]);
+    let server = thread::spawn(move || run_server(vec![alice_server, bob_server], server_listener, hands(), deck));
+    bob_first.join().unwrap();
+    let bob_stream = say_hello(&listener, "Bob", "");
+    let bob = thread::spawn(move || run_client(bob_stream, &["p 1 2 3", "y"]));
+
+    let (hands, table, _) = server.join().unwrap();
+    let alice = alice.join().unwrap();
+    let bob = bob.join().unwrap();
+    assert_eq!(0, hands[1].number_cards());
+    assert_eq!(1, table.number_sequences());
+    assert!(alice.contains("Bob seems to have disconnected"));
+    assert!(alice.contains("Bob is back!"));
+    assert!(bob.contains("Bob wins!"));
+}
+
+#[test]
+fn whispers_reach_a_single_player() {
+    let listener = Arc::new(MemoryListener::new());
+    let (alice_server, alice_client) = MemoryConnection::pair();
+    let (bob_server, bob_client) = MemoryConnection::pair();
+    let alice = thread::spawn(move || run_client(alice_client, &["/whisper Bob hi there", "/w Alice hey", 
+                                                                "/whisper Carol hello", "p 1 2 3 4", "y"]));
+    let bob = thread::spawn(move || run_client(bob_client, &[]));
+
+    let deck = Deck::from_cards(&[RegularCard(Club, 13)]);
+    run_server(vec![alice_server, bob_server], listener, hands(), deck);
+    let alice = alice.join().unwrap();
+    let bob = bob.join().unwrap();
+
+    assert!(bob.contains("(private) Alice: hi there"));
+    assert!(alice.contains("(private) To Bob: hi there"));
+    assert!(alice.contains("(private) You can't whisper to yourself!"));
+    assert!(alice.contains("(private) Usage: /whisper name message"));
+    assert!(!bob.contains("hey") && !bob.contains("hello"));
+}
+
+#[test]
+fn taking_a_long_sequence_is_confirmed() {
+    let listener = Arc::new(MemoryListener::new());
+    let (alice_server, alice_client) = MemoryConnection::pair();
+    let (bob_server, bob_client) = MemoryConnection::pair();
+    let alice = thread::spawn(move || run_client(alice_client, &["p 1 2 3 4 5", "y", "e", "y", "a 1 1", "y"]));
+    let bob = thread::spawn(move || run_client(bob_client, &["t 1", "n", "e"]));
+
+    let hands = vec![Sequence::from_cards(&[RegularCard(Heart, 1), RegularCard(Heart, 2), RegularCard(Heart, 3),
+                                            RegularCard(Heart, 4), RegularCard(Heart, 5), RegularCard(Heart, 6)]),
+                     Sequence::from_cards(&[RegularCard(Spade, 5), RegularCard(Spade, 6), RegularCard(Spade, 7)])];
+    let deck = Deck::from_cards(&[RegularCard(Club, 13)]);
+    let (hands, table, _) = run_server(vec![alice_server, bob_server], listener, hands, deck);
+    alice.join().unwrap();
+    let bob = bob.join().unwrap();
+
+    // Bob changed his mind, so the sequence stayed on the table and he drew a card
+    assert!(bob.contains("Take sequence 1 (5 cards) into your hand?"));
+    assert_eq!(4, hands[1].number_cards());
+    assert_eq!(1, table.number_sequences());
+    assert_eq!(6, table.get(1).unwrap().number_cards());
+}
+
+#[test]
+fn players_with_the_same_name_reconnect_with_their_token() {
+    let listener = Arc::new(MemoryListener::new());
+    let names = display_names(&["Bob".to_string(), "Bob".to_string()]);
+    assert_eq!(vec!["Bob".to_string(), "Bob (2)".to_string()], names);
+    let (first_server, first_client) = MemoryConnection::pair();
+    let (second_server, second_client) = MemoryConnection::pair();
+    let mut players = Player::seat(Hand::deal(&names, vec![Sequence::new(); 2]), vec![first_server, second_server]);
+    let clients: Vec<_> = vec![first_client, second_client].into_iter()
+        .map(|client| thread::spawn(move || run_client(client, &[])))
+        .collect();
+    for (player, token) in players.iter_mut().zip(&["a1", "b2"]) {
+        player.token = token.to_string();
+        send_token_to_client(player, token).unwrap();
+        player.close();
+        player.connected = false;
+    }
+    let printed: Vec<String> = clients.into_iter().map(|c| c.join().unwrap()).collect();
+    assert_eq!(vec!["[token a1]".to_string(), "[token b2]".to_string()], printed);
+
+    // the second Bob gets his own seat back thanks to his token
+    let client_listener = Arc::clone(&listener);
+    let client = thread::spawn(move || say_hello(&client_listener, "Bob", "b2"));
+    while !players[1].connected {
+        try_reconnection(listener.as_ref(), &mut players).unwrap();
+    }
+    client.join().unwrap();
+    assert!(!players[0].connected);
+
+    // without a token, the name is enough
+    let client_listener = Arc::clone(&listener);
+    let client = thread::spawn(move || say_hello(&client_listener, "Bob", ""));
+    while !players[0].connected {
+        try_reconnection(listener.as_ref(), &mut players).unwrap();
+    }
+    client.join().unwrap();
+}
+
+#[test]
+fn out_of_sync_clients_get_the_whole_state() {
+    let (server, mut client) = MemoryConnection::pair();
+    let hands = Hand::deal(&["Alice".to_string()], hands());
+    let mut table = Table::new();
+    table.add(Sequence::from_cards(&[RegularCard(Club, 2), RegularCard(Club, 3), RegularCard(Club, 4)]));
+    let state = PublicState::new(&table, &hands, &Deck::from_cards(&[Joker]), 0);
+    let mut tracker = StateTracker::new();
+    let update = tracker.diff(&state);
+
+    // the client has no state yet, so the update alone is not enough
+    let client = thread::spawn(move || {
+        let mut client_state = None;
+        let mut replies = Vec::new();
+        let mut request = [0u8];
+        for _ in 0..2 {
+            client.read_exact(&mut request).unwrap();
+            assert_eq!(13, request[0]);
+            let in_sync = receive_state(&mut client, &mut client_state).unwrap();
+            send_bytes_to_server(&mut client, &[if in_sync { 0 } else { 1 }]).unwrap();
+            replies.push(in_sync);
+        }
+        (replies, client_state.map(|s| s.public_state()))
+    });
+    let mut player = Player::seat(hands, vec![server]).remove(0);
+    assert!(sync_state_with_client(&mut player, &tracker, &update).unwrap());
+    assert_eq!((vec![false, true], Some(state)), client.join().unwrap());
+}
+
+#[test]
+fn clients_draw_the_situation_from_the_public_state() {
+    let (server, client) = MemoryConnection::pair();
+    let hands = Hand::deal(&["Alice".to_string(), "Bob".to_string()], hands());
+    let mut table = Table::new();
+    table.add(Sequence::from_cards(&[RegularCard(Club, 2), RegularCard(Club, 3), RegularCard(Club, 4)]));
+    table.add(Sequence::from_cards(&[RegularCard(Heart, 9), RegularCard(Spade, 9), RegularCard(Club, 9)]));
+    // the server normalizes the table at the end of each turn
+    table.normalize();
+    let deck = Deck::from_cards(&[Joker]);
+    let mut tracker = StateTracker::new();
+    let update = tracker.diff(&PublicState::new(&table, &hands, &deck, 1));
+    let previous_turn = PreviousTurn::new("Bob", &Table::new(), &table, 5, 2, &[]);
+    let view = PlayerView::new(&hands, &TurnOrder::new(2, 1), 0).with_previous_turn(previous_turn);
+    let expected = situation_to_string(&table, &hands[0], &Sequence::new(), &LayoutSettings::new(),
+                                       &Default::default(), deck.remaining(), &view);
+
+    let client = thread::spawn(move || run_client(client, &[]));
+    let mut player = Player::seat(hands, vec![server]).remove(0);
+    assert!(sync_state_with_client(&mut player, &tracker, &update).unwrap());
+    let turn_view = TurnView { hand: player.hand.cards().clone(), changed: view.previous_turn.changed.clone(),
+                               pages: player.pages };
+    send_situation_to_client(&mut player, &view.header(deck.remaining()), &turn_view, "\nevents\n").unwrap();
+    drop(player);
+    assert_eq!(format!("{}\nevents\n", expected), client.join().unwrap());
+}
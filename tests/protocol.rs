@@ -0,0 +1,227 @@
+//! Bytes exchanged by the server and the clients, checked against recorded fixtures
+//!
+//! Each message is encoded by the functions of one side, compared with the bytes stored in
+//! `tests/fixtures/protocol`, and decoded by the functions of the other side. A change of the
+//! wire format makes these tests fail, as clients and servers of different versions could not
+//! talk to each other any more. If the change is intended, run the tests with
+//! `MACHIAVELLI_UPDATE_FIXTURES=1` to record the new bytes.
+
+use std::io::{ Read, Write };
+use machiavelli::sequence_cards::*;
+use machiavelli::connection::Connection;
+use machiavelli::layout::{ HandLayout, LayoutSettings, Pages };
+use machiavelli::state::{ StateTracker, StateUpdate, TurnView };
+use machiavelli::lib_server::{ ask_confirmation, clear_and_send_message_to_client, get_hello_from_client,
+                               get_layout_from_client, get_str_from_client, get_string_from_client,
+                               notify_turn_start, send_exit_message_to_client, send_flash_to_client,
+                               send_message_get_reply, send_message_to_client, send_private_message_to_client,
+                               send_situation_to_client, send_state_to_client, send_token_to_client };
+use machiavelli::lib_client::{ get_bytes_from_server, get_str_from_server, receive_state, send_bytes_to_server,
+                               send_hello, send_str_to_server };
+
+/// Connection writing to memory, and reading what the other end is expected to send
+struct Recorder {
+    written: Vec<u8>,
+    script: std::io::Cursor<Vec<u8>>
+}
+
+impl Recorder {
+
+    fn new(script: Vec<u8>) -> Recorder {
+        Recorder { written: Vec::new(), script: std::io::Cursor::new(script) }
+    }
+
+    /// the next byte, which gives the request of the server
+    fn request(&mut self) -> u8 {
+        let mut request = [0u8];
+        self.read_exact(&mut request).unwrap();
+        request[0]
+    }
+
+    /// whether everything the other end sent was read
+    fn is_done(&self) -> bool {
+        self.script.position() as usize == self.script.get_ref().len()
+    }
+}
+
+impl Read for Recorder {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.script.read(buf)
+    }
+}
+
+impl Write for Recorder {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.written.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Connection for Recorder {}
+
+/// bytes of a message as the other end sends it, with the length first
+fn message(bytes: &[u8]) -> Vec<u8> {
+    let mut res = (bytes.len() as u32).to_be_bytes().to_vec();
+    res.extend_from_slice(bytes);
+    res
+}
+
+/// concatenate the parts sent by the other end
+fn script(parts: &[&[u8]]) -> Vec<u8> {
+    parts.concat()
+}
+
+/// check the bytes against the fixture `name`, or record them if asked to
+fn check_fixture(name: &str, bytes: &[u8]) {
+    let path = format!("{}/tests/fixtures/protocol/{}.hex", env!("CARGO_MANIFEST_DIR"), name);
+    let hex: Vec<String> = bytes.chunks(16)
+        .map(|line| line.iter().map(|b| format!("{:02x}", b)).collect::<Vec<String>>().join(" "))
+        .collect();
+    let hex = hex.join("\n") + "\n";
+    if std::env::var_os("MACHIAVELLI_UPDATE_FIXTURES").is_some() {
+        std::fs::create_dir_all(std::path::Path::new(&path).parent().unwrap()).unwrap();
+        std::fs::write(&path, &hex).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("missing fixture {}", path));
+    assert_eq!(expected, hex, "the bytes of `{}` changed", name);
+}
+
+/// Encode a request of the server, check it against the fixture, and return a client reading it
+fn server_sends<F>(name: &str, replies: Vec<u8>, send: F) -> Recorder
+    where F: FnOnce(&mut Recorder)
+{
+    let mut server = Recorder::new(replies);
+    send(&mut server);
+    assert!(server.is_done(), "`{}` did not read all the replies", name);
+    check_fixture(name, &server.written);
+    Recorder::new(server.written)
+}
+
+/// Encode a message of a client, check it against the fixture, and return a server reading it
+fn client_sends<F>(name: &str, send: F) -> Recorder
+    where F: FnOnce(&mut Recorder)
+{
+    let mut client = Recorder::new(vec![0; 16]);
+    send(&mut client);
+    check_fixture(name, &client.written);
+    Recorder::new(client.written)
+}
+
+#[test]
+fn messages_to_print() {
+    let mut client = server_sends("message", vec![0], |s| send_message_to_client(s, "Hello\n").unwrap());
+    assert_eq!(1, client.request());
+    assert_eq!("Hello\n", get_str_from_server(&mut client).unwrap());
+
+    let mut client = server_sends("clear_and_print", vec![0], |s| clear_and_send_message_to_client(s, "Table").unwrap());
+    assert_eq!(2, client.request());
+    assert_eq!("Table", get_str_from_server(&mut client).unwrap());
+
+    let mut client = server_sends("flash", vec![0], |s| send_flash_to_client(s, "Alice drew").unwrap());
+    assert_eq!(7, client.request());
+    assert_eq!("Alice drew", get_str_from_server(&mut client).unwrap());
+
+    let mut client = server_sends("exit_message", vec![0], |s| send_exit_message_to_client(s, "Bye!").unwrap());
+    assert_eq!(9, client.request());
+    assert_eq!("Bye!", get_str_from_server(&mut client).unwrap());
+
+    let mut client = server_sends("private_message", vec![0, 0],
+                                  |s| send_private_message_to_client(s, "Bob", "hi").unwrap());
+    assert_eq!(10, client.request());
+    assert_eq!("Bob", get_str_from_server(&mut client).unwrap());
+    assert_eq!("hi", get_str_from_server(&mut client).unwrap());
+    assert!(client.is_done());
+}
+
+#[test]
+fn requests_with_a_reply() {
+    let reply = message(b"p 1 2");
+    let mut client = server_sends("print_and_reply", script(&[&[0], &reply]), |s| {
+        assert_eq!(b"p 1 2".to_vec(), send_message_get_reply(s, "Your move?").unwrap());
+    });
+    assert_eq!(3, client.request());
+    assert_eq!("Your move?", get_str_from_server(&mut client).unwrap());
+
+    let mut client = server_sends("input", reply, |s| {
+        assert_eq!("p 1 2", get_string_from_client(s).unwrap());
+    });
+    assert_eq!(4, client.request());
+
+    let layout = LayoutSettings { layout: HandLayout::Grid, width: 100, height: 30 };
+    let mut client = server_sends("layout", message(&layout.to_bytes()), |s| {
+        assert_eq!(layout, get_layout_from_client(s).unwrap());
+    });
+    assert_eq!(8, client.request());
+
+    let mut client = server_sends("confirmation", script(&[&[0], &message(b"y")]), |s| {
+        assert!(ask_confirmation(s, "Sure?").unwrap());
+    });
+    assert_eq!(11, client.request());
+    assert_eq!("Sure?", get_str_from_server(&mut client).unwrap());
+}
+
+#[test]
+fn game_notifications() {
+    let mut client = server_sends("turn", Vec::new(), |s| notify_turn_start(s).unwrap());
+    assert_eq!(6, client.request());
+    assert!(client.is_done());
+
+    let mut client = server_sends("token", vec![0], |s| send_token_to_client(s, "0123456789abcdef").unwrap());
+    assert_eq!(12, client.request());
+    assert_eq!("0123456789abcdef", get_str_from_server(&mut client).unwrap());
+
+    let update = StateUpdate {
+        full: true,
+        current_player: 1,
+        hand_sizes: vec![5, 7],
+        deck_remaining: 80,
+        removed: Vec::new(),
+        changed: vec![(1, Sequence::from_cards(&[RegularCard(Club, 2), RegularCard(Club, 3), Joker]))]
+    };
+    let mut tracker = StateTracker::new();
+    tracker.apply(&update);
+    let checksum = tracker.public_state().checksum();
+    let mut client = server_sends("state", script(&[&[0, 0], &message(&[0])]), |s| {
+        assert!(send_state_to_client(s, &update, &checksum).unwrap());
+    });
+    assert_eq!(13, client.request());
+    let mut state = None;
+    assert!(receive_state(&mut client, &mut state).unwrap());
+    assert_eq!(Some(tracker), state);
+}
+
+#[test]
+fn situation_at_the_start_of_a_turn() {
+    let view = TurnView {
+        hand: Sequence::from_cards(&[RegularCard(Heart, 1), Joker]),
+        changed: vec![Sequence::from_cards(&[RegularCard(Club, 2), RegularCard(Club, 3), Joker])],
+        pages: Pages { table: 1, hand: 0 }
+    };
+    let mut client = server_sends("situation", vec![0, 0, 0], |s| {
+        send_situation_to_client(s, "Deck: 80", &view, "Recent events").unwrap();
+    });
+    assert_eq!(16, client.request());
+    assert_eq!("Deck: 80", get_str_from_server(&mut client).unwrap());
+    assert_eq!(Some(view), TurnView::from_bytes(&get_bytes_from_server(&mut client).unwrap()));
+    assert_eq!("Recent events", get_str_from_server(&mut client).unwrap());
+    assert!(client.is_done());
+}
+
+#[test]
+fn messages_from_clients() {
+    let mut server = client_sends("hello", |c| send_hello(c, "Alice", "0123456789abcdef").unwrap());
+    assert_eq!(("Alice".to_string(), "0123456789abcdef".to_string()), get_hello_from_client(&mut server).unwrap());
+    assert!(server.is_done());
+
+    let mut server = client_sends("reply", |c| send_str_to_server(c, "t 2").unwrap());
+    assert_eq!("t 2", get_str_from_client(&mut server).unwrap());
+
+    let layout = LayoutSettings { layout: HandLayout::BySuit, width: 80, height: 30 };
+    let mut server = client_sends("layout_settings", |c| send_bytes_to_server(c, &layout.to_bytes()).unwrap());
+    assert_eq!(Some(layout), LayoutSettings::from_bytes(&get_bytes_from_server(&mut server).unwrap()));
+}
@@ -0,0 +1,73 @@
+//! Saves which must still load: those written by earlier versions, recorded in
+//! `tests/fixtures/saves`, and those read from another directory than the one they were written to
+
+use machiavelli::*;
+use machiavelli::save_format::{ load, SaveFormat };
+use machiavelli::table::Table;
+use machiavelli::turn::Ongoing;
+
+/// bytes of a save, written in hexadecimal
+fn fixture(name: &str) -> Vec<u8> {
+    let path = format!("{}/tests/fixtures/saves/{}.hex", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap()
+        .split_whitespace()
+        .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+        .collect()
+}
+
+/// A save written before the table had the number of its sequences, with a turn in progress
+#[test]
+fn saves_with_sequences_separated_by_255_still_load() {
+    let ((config, starting_player, player, table, hands, deck), turn, report) =
+        load(&fixture("before_format_numbers"), |_| false).ok().unwrap();
+    assert_eq!(SaveFormat::Legacy, report.format);
+    assert!(!report.is_lossy());
+    assert_eq!(Config { n_decks: 1, n_jokers: 2, n_cards_to_start: 3, custom_rule_jokers: true, n_players: 2,
+                        empty_deck: EmptyDeckRule::Score, joker_penalty: 30, mulligan: MulliganRule::Off,
+                        draw_for_first: false }, config);
+    assert_eq!((0, 0), (starting_player, player));
+    assert_eq!(vec!["Alice".to_string(), "Bob".to_string()], hand::owners(&hands));
+    assert_eq!(Sequence::from_cards(&[RegularCard(Heart, 1), Joker]), *hands[0].cards());
+    assert_eq!(Deck::from_cards(&[RegularCard(Heart, 13), RegularCard(Diamond, 1)]), deck);
+
+    let mut table_start = Table::new();
+    table_start.add(Sequence::from_cards(&[RegularCard(Club, 2), RegularCard(Club, 3), RegularCard(Club, 4)]));
+    let mut expected = table_start.clone();
+    expected.add(Sequence::from_cards(&[RegularCard(Diamond, 9), RegularCard(Spade, 9), Joker]));
+    assert_eq!(expected, table);
+
+    let (turn, cards_from_table) = turn.unwrap();
+    let hand_start = Sequence::from_cards(&[RegularCard(Heart, 1), Joker, RegularCard(Diamond, 9),
+                                            RegularCard(Spade, 9), Joker]);
+    assert_eq!(Ongoing::start(&table_start, &hand_start).meld(), turn);
+    assert_eq!(Sequence::from_cards(&[RegularCard(Club, 5)]), cards_from_table);
+
+    // the game is written again in the current format
+    let bytes = game_to_bytes_with_turn(starting_player, player, &table, &hands, &deck, &config,
+                                        Some(&(turn, cards_from_table)));
+    let (game, _, report) = load(&bytes, |_| false).ok().unwrap();
+    assert_eq!(SaveFormat::Versioned(save_format::CURRENT_VERSION), report.format);
+    assert_eq!(expected, game.3);
+}
+
+/// A save keeps loading once its directory is moved, or written another way
+#[test]
+fn saves_load_through_another_directory() {
+    let root = std::env::temp_dir().join(format!("machiavelli_moved_saves_{}", std::process::id()));
+    let first = root.join("first");
+    std::fs::create_dir_all(&first).unwrap();
+    let config = Config::default();
+    let hands = Hand::deal(&["Alice".to_string(), "Bob".to_string()],
+                           vec![Sequence::from_cards(&[Joker]), Sequence::from_cards(&[RegularCard(Club, 2)])]);
+    let bytes = game_to_bytes(0, 1, &Table::new(), &hands, &Deck::new(), &config);
+    let path = first.join("game.sav").to_str().unwrap().to_string();
+    std::fs::write(&path, encode::encrypt_save(&bytes, &[], &path, &mut rand::thread_rng())).unwrap();
+
+    let moved = root.join("moved");
+    std::fs::rename(&first, &moved).unwrap();
+    let spelling = format!("{}/../moved/./game.sav", moved.to_str().unwrap());
+    let loaded = encode::decrypt_save(&std::fs::read(&spelling).unwrap(), &spelling);
+    assert_eq!(Ok(bytes), loaded);
+    assert_eq!(1, saves::list_saves(&moved, ".sav").len());
+    std::fs::remove_dir_all(&root).unwrap();
+}